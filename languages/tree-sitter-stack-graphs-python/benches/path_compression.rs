@@ -0,0 +1,153 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Compares the size and encoding time of plain and compressed partial paths, for the paths of
+//! the Python test files.  Run with:
+//!
+//! ```text
+//! cargo bench -p tree-sitter-stack-graphs-python --bench path_compression
+//! ```
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+use tree_sitter_stack_graphs::stack_graphs;
+use tree_sitter_stack_graphs::stack_graphs::partial::PartialPaths;
+use tree_sitter_stack_graphs::stack_graphs::serde;
+use tree_sitter_stack_graphs::stack_graphs::serde::CompressedPartialPaths;
+use tree_sitter_stack_graphs::stack_graphs::stitching::ForwardPartialPathStitcher;
+use tree_sitter_stack_graphs::stack_graphs::stitching::StitcherConfig;
+use tree_sitter_stack_graphs::stack_graphs::storage::BINCODE_CONFIG;
+use tree_sitter_stack_graphs::test::Test;
+use tree_sitter_stack_graphs::NoCancellation;
+use tree_sitter_stack_graphs::Variables;
+
+const ITERATIONS: u32 = 100;
+
+#[derive(Default)]
+struct Measurement {
+    size: usize,
+    encode: Duration,
+    decode: Duration,
+}
+
+impl Measurement {
+    fn report(&self, name: &str) {
+        println!(
+            "{:>10}: {:>8} bytes, encode {:>10.3?}, decode {:>10.3?}",
+            name,
+            self.size,
+            self.encode / ITERATIONS,
+            self.decode / ITERATIONS,
+        );
+    }
+}
+
+/// Returns the Python files in the given directory and its subdirectories.
+fn python_files(dir: &Path, result: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            python_files(&path, result);
+        } else if path.extension().and_then(|ext| ext.to_str()) == Some("py") {
+            result.push(path);
+        }
+    }
+}
+
+/// Returns the partial paths of every file of every test, grouped by file.
+fn partial_paths_by_file() -> Vec<Vec<serde::PartialPath>> {
+    let lc = tree_sitter_stack_graphs_python::language_configuration(&NoCancellation);
+    let test_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test");
+    let mut paths = Vec::new();
+    python_files(&test_path, &mut paths);
+    paths.sort();
+    let mut result = Vec::new();
+    for path in paths {
+        let source = std::fs::read_to_string(&path).unwrap();
+        let mut test = Test::from_source(&path, &source, &path).unwrap();
+        let mut globals = Variables::new();
+        for fragment in &test.fragments {
+            globals.clear();
+            fragment.add_globals_to(&mut globals);
+            if lc
+                .sgl
+                .build_stack_graph_into(
+                    &mut test.graph,
+                    fragment.file,
+                    &fragment.source,
+                    &globals,
+                    &NoCancellation,
+                )
+                .is_err()
+            {
+                continue;
+            }
+            let mut partials = PartialPaths::new();
+            let mut paths = Vec::new();
+            ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+                &test.graph,
+                &mut partials,
+                fragment.file,
+                StitcherConfig::default(),
+                &stack_graphs::NoCancellation,
+                |g, ps, p| paths.push(serde::PartialPath::from_partial_path(g, ps, p)),
+            )
+            .unwrap();
+            result.push(paths);
+        }
+    }
+    result
+}
+
+fn main() {
+    let files = partial_paths_by_file();
+    let path_count = files.iter().map(Vec::len).sum::<usize>();
+    println!("{} files, {} partial paths", files.len(), path_count);
+
+    let mut plain = Measurement::default();
+    let mut compressed = Measurement::default();
+    for paths in &files {
+        let start = Instant::now();
+        let mut bytes = Vec::new();
+        for _ in 0..ITERATIONS {
+            bytes = bincode::encode_to_vec(paths, BINCODE_CONFIG).unwrap();
+        }
+        plain.encode += start.elapsed();
+        plain.size += bytes.len();
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            let (decoded, _): (Vec<serde::PartialPath>, _) =
+                bincode::decode_from_slice(&bytes, BINCODE_CONFIG).unwrap();
+            assert_eq!(paths.len(), decoded.len());
+        }
+        plain.decode += start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            let value = CompressedPartialPaths::from_partial_paths(paths);
+            bytes = bincode::encode_to_vec(&value, BINCODE_CONFIG).unwrap();
+        }
+        compressed.encode += start.elapsed();
+        compressed.size += bytes.len();
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            let (value, _): (CompressedPartialPaths, _) =
+                bincode::decode_from_slice(&bytes, BINCODE_CONFIG).unwrap();
+            assert_eq!(paths.len(), value.to_partial_paths().unwrap().len());
+        }
+        compressed.decode += start.elapsed();
+    }
+
+    plain.report("plain");
+    compressed.report("compressed");
+    println!(
+        "compressed size is {:.1}% of plain size",
+        100.0 * compressed.size as f64 / plain.size as f64
+    );
+}
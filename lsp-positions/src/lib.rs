@@ -45,6 +45,12 @@ fn utf16_len(string: &str) -> usize {
 #[repr(C)]
 #[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct Position {
     /// The 0-indexed line number containing the character
@@ -61,6 +67,17 @@ pub struct Position {
 }
 
 impl Position {
+    /// Returns the UTF-8 byte offset of this position within the file.
+    pub fn utf8_offset(&self) -> usize {
+        self.containing_line.start + self.column.utf8_offset
+    }
+
+    /// Returns the 0-indexed line and column of this position, with the column expressed in the
+    /// given encoding.
+    pub fn line_and_column(&self, encoding: PositionEncoding) -> (usize, usize) {
+        (self.line, self.column.in_encoding(encoding))
+    }
+
     /// Returns a tree-sitter [`Point`][Point] for this position.
     ///
     /// [Point]: https://docs.rs/tree-sitter/*/tree_sitter/struct.Point.html
@@ -109,6 +126,12 @@ impl PartialOrd<tree_sitter::Point> for Position {
 #[repr(C)]
 #[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct Span {
     pub start: Position,
@@ -116,6 +139,11 @@ pub struct Span {
 }
 
 impl Span {
+    /// Returns the UTF-8 byte range of this span within the file.
+    pub fn utf8_range(&self) -> Range<usize> {
+        self.start.utf8_offset()..self.end.utf8_offset()
+    }
+
     pub fn contains(&self, position: &Position) -> bool {
         &self.start <= position && &self.end > position
     }
@@ -140,12 +168,40 @@ impl PartialOrd for Span {
     }
 }
 
+#[cfg(feature = "lsp-types")]
+impl From<&Position> for lsp_types::Position {
+    /// Converts to an LSP position, with the column expressed in UTF-16 code units.
+    fn from(position: &Position) -> Self {
+        lsp_types::Position {
+            line: position.line as u32,
+            character: position.column.utf16_offset as u32,
+        }
+    }
+}
+
+#[cfg(feature = "lsp-types")]
+impl From<&Span> for lsp_types::Range {
+    /// Converts to an LSP range, with columns expressed in UTF-16 code units.
+    fn from(span: &Span) -> Self {
+        lsp_types::Range {
+            start: (&span.start).into(),
+            end: (&span.end).into(),
+        }
+    }
+}
+
 /// The offset of a character within a string (typically a line of source code), using several
 /// different units
 ///
 /// All offsets are 0-indexed.
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct Offset {
     /// The number of UTF-8-encoded bytes appearing before this character in the string
@@ -157,6 +213,15 @@ pub struct Offset {
 }
 
 impl Offset {
+    /// Returns this offset expressed in the given encoding.
+    pub fn in_encoding(&self, encoding: PositionEncoding) -> usize {
+        match encoding {
+            PositionEncoding::Utf8 => self.utf8_offset,
+            PositionEncoding::Utf16 => self.utf16_offset,
+            PositionEncoding::Grapheme => self.grapheme_offset,
+        }
+    }
+
     /// Calculates the length of a string, expressed as the position of the non-existent character
     /// after the end of the line.
     pub fn string_length(string: &str) -> Offset {
@@ -211,6 +276,24 @@ impl Offset {
     }
 }
 
+/// The unit in which column offsets are expressed.  Different front-ends expect different units:
+/// byte offsets index into UTF-8 encoded content, [LSP][lsp-utf16] uses UTF-16 code units by
+/// default, and user-facing column numbers are best expressed in graphemes.
+///
+/// [lsp-utf16]: https://microsoft.github.io/language-server-protocol/specification#textDocuments
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum PositionEncoding {
+    /// UTF-8 bytes
+    Utf8,
+    /// UTF-16 code units
+    #[default]
+    Utf16,
+    /// Grapheme clusters
+    Grapheme,
+}
+
 /// A substring and information about where that substring occurs in a larger string.  (Most often,
 /// this is a “line” and information about where that line occurs within a “file”.)
 #[derive(Clone)]
@@ -395,6 +478,39 @@ impl<'a> SpanCalculator<'a> {
         }
     }
 
+    /// Constructs a [`Position`][] instance for a particular line and column in the string.
+    /// You must provide the 0-indexed line number, the byte offset of the line within the string,
+    /// and the UTF-16 code unit offset of the character within the line, as used by LSP.
+    pub fn for_line_and_utf16_column(
+        &mut self,
+        line: usize,
+        line_utf8_offset: usize,
+        column_utf16_offset: usize,
+    ) -> Position {
+        self.replace_current_line(line_utf8_offset);
+        Position {
+            line,
+            column: *self.for_utf16_offset(column_utf16_offset),
+            containing_line: self.containing_line.as_ref().unwrap().utf8_bounds.clone(),
+            trimmed_line: self.trimmed_line.as_ref().unwrap().utf8_bounds.clone(),
+        }
+    }
+
+    /// Constructs a [`Position`][] instance for a UTF-8 byte offset within the string.  The line
+    /// containing the offset is determined automatically.  Offsets past the end of the string are
+    /// clamped to the end, and offsets that point into the middle of a character are rounded down
+    /// to the start of that character.
+    pub fn for_utf8_offset_in_string(&mut self, utf8_offset: usize) -> Position {
+        let mut utf8_offset = utf8_offset.min(self.string.len());
+        while !self.string.is_char_boundary(utf8_offset) {
+            utf8_offset -= 1;
+        }
+        let preceding = &self.string.as_bytes()[..utf8_offset];
+        let line = memchr::memchr_iter(b'\n', preceding).count();
+        let line_utf8_offset = memchr::memrchr(b'\n', preceding).map_or(0, |i| i + 1);
+        self.for_line_and_column(line, line_utf8_offset, utf8_offset - line_utf8_offset)
+    }
+
     /// Updates our internal state to represent the information about the line that starts at a
     /// particular byte offset within the file.
     fn replace_current_line(&mut self, line_utf8_offset: usize) {
@@ -422,6 +538,16 @@ impl<'a> SpanCalculator<'a> {
         &self.columns[index]
     }
 
+    /// Returns the offset of the character at a particular UTF-16 offset in the line.  Offsets
+    /// that point into the middle of a surrogate pair are rounded up to the next character.
+    /// Assumes that you've already called `replace_current_line` for the containing line.
+    fn for_utf16_offset(&self, utf16_offset: usize) -> &Offset {
+        let index = self
+            .columns
+            .partition_point(|pos| pos.utf16_offset < utf16_offset);
+        &self.columns[index.min(self.columns.len() - 1)]
+    }
+
     /// Returns the offset of the character at a particular grapheme offset in the line.
     /// Assumes that you've already called `replace_current_line` for the containing line.
     fn for_grapheme_offset(&self, grapheme_offset: usize) -> &Offset {
@@ -8,6 +8,9 @@
 use unicode_segmentation::UnicodeSegmentation as _;
 
 use lsp_positions::Offset;
+use lsp_positions::PositionEncoding;
+use lsp_positions::Span;
+use lsp_positions::SpanCalculator;
 
 fn check_offsets(line: &str) {
     let offsets = Offset::all_chars(line).collect::<Vec<_>>();
@@ -47,3 +50,54 @@ fn can_calculate_column_offsets() {
     check_offsets("print '❤️', b, '👨‍👨‍👧', c");
     check_offsets("print '✨✨✨', d");
 }
+
+#[test]
+fn can_convert_positions_between_encodings() {
+    let source = "a = 1\nprint('👨‍👨‍👧', b)\n";
+    let mut calculator = SpanCalculator::new(source);
+    let b = source.find("b)").unwrap();
+    let position = calculator.for_utf8_offset_in_string(b);
+
+    assert_eq!(b, position.utf8_offset());
+    assert_eq!((1, 28), position.line_and_column(PositionEncoding::Utf8));
+    assert_eq!((1, 18), position.line_and_column(PositionEncoding::Utf16));
+    assert_eq!(
+        (1, 11),
+        position.line_and_column(PositionEncoding::Grapheme)
+    );
+
+    let from_utf16 = calculator.for_line_and_utf16_column(1, 6, 18);
+    assert_eq!(position, from_utf16);
+
+    let span = Span {
+        start: position,
+        end: calculator.for_utf8_offset_in_string(b + 1),
+    };
+    assert_eq!(b..b + 1, span.utf8_range());
+}
+
+#[test]
+fn utf8_offsets_past_the_end_are_clamped() {
+    let source = "a = 1\nb";
+    let mut calculator = SpanCalculator::new(source);
+    let position = calculator.for_utf8_offset_in_string(source.len() + 10);
+
+    assert_eq!(source.len(), position.utf8_offset());
+    assert_eq!((1, 1), position.line_and_column(PositionEncoding::Utf8));
+}
+
+#[test]
+fn utf8_offsets_inside_characters_are_rounded_down() {
+    let source = "a = 1\nprint('✨', b)\n";
+    let mut calculator = SpanCalculator::new(source);
+    let sparkles = source.find('✨').unwrap();
+    let position = calculator.for_utf8_offset_in_string(sparkles + 1);
+
+    assert_eq!(sparkles, position.utf8_offset());
+    assert_eq!((1, 7), position.line_and_column(PositionEncoding::Utf8));
+    assert_eq!((1, 7), position.line_and_column(PositionEncoding::Utf16));
+    assert_eq!(
+        calculator.for_utf8_offset_in_string(sparkles),
+        calculator.for_utf8_offset_in_string(sparkles + 2)
+    );
+}
@@ -0,0 +1,129 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Measures the query throughput of a database when readers from a connection pool are used by
+//! several threads at the same time.  Run with:
+//!
+//! ```text
+//! cargo bench --features storage --bench concurrent_queries
+//! ```
+
+use stack_graphs::graph::NodeID;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::storage::SQLiteReaderPool;
+use stack_graphs::storage::SQLiteWriter;
+use stack_graphs::NoCancellation;
+use std::path::Path;
+use std::time::Instant;
+
+const FILE_COUNT: usize = 200;
+const QUERIES_PER_THREAD: usize = 500;
+const THREAD_COUNTS: [usize; 4] = [1, 2, 4, 8];
+
+/// The local ID of the reference in every file.
+const REFERENCE_ID: u32 = 1;
+
+/// Stores files that each define one symbol, and refer to the symbol defined by the next file.
+fn create_database(path: &Path) {
+    let mut writer = SQLiteWriter::open(path).unwrap();
+    for i in 0..FILE_COUNT {
+        let mut graph = StackGraph::new();
+        let file = graph.add_file(&format!("file{}.py", i)).unwrap();
+        let root = StackGraph::root_node();
+
+        let referenced = graph.add_symbol(&format!("d{}", (i + 1) % FILE_COUNT));
+        let id = NodeID::new_in_file(file, REFERENCE_ID);
+        let reference = graph.add_push_symbol_node(id, referenced, true).unwrap();
+        graph.add_edge(reference, root, 0);
+
+        let defined = graph.add_symbol(&format!("d{}", i));
+        let id = NodeID::new_in_file(file, REFERENCE_ID + 1);
+        let definition = graph.add_pop_symbol_node(id, defined, true).unwrap();
+        graph.add_edge(root, definition, 0);
+
+        let mut partials = PartialPaths::new();
+        let mut paths = Vec::new();
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, path| paths.push(path.clone()),
+        )
+        .unwrap();
+        writer
+            .store_result_for_file(&graph, file, "", &mut partials, &paths)
+            .unwrap();
+    }
+}
+
+/// Resolves the reference of a file, using a fresh reader from the pool.
+fn query(pool: &SQLiteReaderPool, index: usize) -> usize {
+    let mut reader = pool.reader().unwrap();
+    let file = reader
+        .load_graph_for_file(&format!("file{}.py", index))
+        .unwrap();
+    let (graph, _, _) = reader.get();
+    let reference = graph
+        .node_for_id(NodeID::new_in_file(file, REFERENCE_ID))
+        .unwrap();
+    let mut count = 0;
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut reader,
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, _| count += 1,
+    )
+    .unwrap();
+    count
+}
+
+fn main() {
+    let path = std::env::temp_dir().join(format!(
+        "stack-graphs-concurrent-queries-{}.sqlite",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    create_database(&path);
+    let pool = SQLiteReaderPool::open(&path).unwrap();
+
+    println!(
+        "{:>8} {:>10} {:>12} {:>12}",
+        "threads", "queries", "seconds", "queries/s"
+    );
+    for threads in THREAD_COUNTS {
+        let start = Instant::now();
+        std::thread::scope(|s| {
+            for t in 0..threads {
+                let pool = &pool;
+                s.spawn(move || {
+                    for q in 0..QUERIES_PER_THREAD {
+                        let resolved = query(pool, (t * QUERIES_PER_THREAD + q) % FILE_COUNT);
+                        assert_eq!(1, resolved);
+                    }
+                });
+            }
+        });
+        let elapsed = start.elapsed().as_secs_f64();
+        let queries = threads * QUERIES_PER_THREAD;
+        println!(
+            "{:>8} {:>10} {:>12.3} {:>12.0}",
+            threads,
+            queries,
+            elapsed,
+            queries as f64 / elapsed
+        );
+    }
+
+    drop(pool);
+    let _ = std::fs::remove_file(&path);
+}
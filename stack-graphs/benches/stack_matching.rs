@@ -0,0 +1,111 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Measures how fast fully known symbol stacks are matched against each other, which is what
+//! stitching spends most of its time on when a query considers many candidate paths.  Run with:
+//!
+//! ```text
+//! cargo bench --bench stack_matching
+//! ```
+
+use controlled_option::ControlledOption;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::partial::PartialScopeStackBindings;
+use stack_graphs::partial::PartialScopedSymbol;
+use stack_graphs::partial::PartialSymbolStack;
+use stack_graphs::partial::PartialSymbolStackBindings;
+use std::time::Instant;
+
+const SYMBOL_COUNT: usize = 16;
+const CANDIDATE_COUNT: usize = 1000;
+const ROUNDS: usize = 200;
+const STACK_LENGTHS: [usize; 4] = [1, 2, 4, 8];
+
+/// Creates a stack of the given length, whose symbols are the digits of `index` in base
+/// SYMBOL_COUNT.  Stacks are pushed onto the back, like preconditions that are built while
+/// stitching.
+fn create_stack(
+    graph: &mut StackGraph,
+    partials: &mut PartialPaths,
+    mut index: usize,
+    length: usize,
+) -> PartialSymbolStack {
+    let mut stack = PartialSymbolStack::empty();
+    for _ in 0..length {
+        let symbol = graph.add_symbol(&format!("s{}", index % SYMBOL_COUNT));
+        index /= SYMBOL_COUNT;
+        stack.push_back(
+            partials,
+            PartialScopedSymbol {
+                symbol,
+                scopes: ControlledOption::none(),
+            },
+        );
+    }
+    stack
+}
+
+/// Matches every query against every candidate, and returns the number of successful matches
+/// and the elapsed time.
+fn match_all(
+    partials: &mut PartialPaths,
+    queries: &[PartialSymbolStack],
+    candidates: &[PartialSymbolStack],
+) -> (usize, f64) {
+    let start = Instant::now();
+    let mut matched = 0;
+    for query in queries {
+        for candidate in candidates {
+            let mut symbol_bindings = PartialSymbolStackBindings::new();
+            let mut scope_bindings = PartialScopeStackBindings::new();
+            if query
+                .unify(
+                    partials,
+                    *candidate,
+                    &mut symbol_bindings,
+                    &mut scope_bindings,
+                )
+                .is_ok()
+            {
+                matched += 1;
+            }
+        }
+    }
+    (matched, start.elapsed().as_secs_f64())
+}
+
+fn main() {
+    println!(
+        "{:>10} {:>8} {:>12} {:>12} {:>14}",
+        "workload", "length", "matched", "seconds", "matches/s"
+    );
+    // In the "distinct" workload, most candidates differ from the query, in the "equal" workload
+    // all of them are equal to it.
+    for (workload, distinct_stacks) in [("distinct", CANDIDATE_COUNT), ("equal", 1)] {
+        for length in STACK_LENGTHS {
+            let mut graph = StackGraph::new();
+            let mut partials = PartialPaths::new();
+            let candidates = (0..CANDIDATE_COUNT)
+                .map(|i| create_stack(&mut graph, &mut partials, i % distinct_stacks, length))
+                .collect::<Vec<_>>();
+            let queries = (0..ROUNDS)
+                .map(|i| create_stack(&mut graph, &mut partials, i * 7 % distinct_stacks, length))
+                .collect::<Vec<_>>();
+            let (matched, elapsed) = match_all(&mut partials, &queries, &candidates);
+            let matches = ROUNDS * CANDIDATE_COUNT;
+            println!(
+                "{:>10} {:>8} {:>12} {:>12.3} {:>14.0}",
+                workload,
+                length,
+                matched,
+                elapsed,
+                matches as f64 / elapsed
+            );
+        }
+    }
+}
@@ -0,0 +1,14 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use stack_graphs::fuzzing::check_resolve;
+use stack_graphs::fuzzing::GraphInput;
+
+fuzz_target!(|input: GraphInput| check_resolve(&input));
@@ -554,6 +554,8 @@ struct sg_symbols sg_stack_graph_symbols(const struct sg_stack_graph *graph);
 //
 // Each symbol must be a valid UTF-8 string.  If any symbol isn't valid UTF-8, it won't be added
 // to the stack graph, and the corresponding entry in the output array will be the null handle.
+// Symbols are not normalized, unless the graph was configured to normalize symbols when they are
+// added.
 void sg_stack_graph_add_symbols(struct sg_stack_graph *graph,
                                 size_t count,
                                 const char *symbols,
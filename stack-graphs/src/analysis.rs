@@ -0,0 +1,911 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Static checks that find common mistakes in the rules that construct stack graphs.
+//!
+//! Rule authors usually notice mistakes only when references fail to resolve, which leaves them
+//! searching the whole graph for the cause.  The [`lint`][] pass looks for graph structures that
+//! are almost always the result of a mistake, and returns a [`Diagnostic`][] for each of them,
+//! pointing at the nodes involved:
+//!
+//! ```
+//! # use stack_graphs::analysis::lint;
+//! # use stack_graphs::analysis::Lint;
+//! # use stack_graphs::graph::StackGraph;
+//! # let mut graph = StackGraph::new();
+//! # let file = graph.get_or_create_file("test.py");
+//! # let x = graph.add_symbol("x");
+//! # let id = graph.new_node_id(file);
+//! # let reference = graph.add_push_symbol_node(id, x, true).unwrap();
+//! for diagnostic in lint(&graph) {
+//!     println!("{}", diagnostic.display(&graph));
+//! }
+//! # assert!(lint(&graph).iter().any(|d| d.lint == Lint::DeadEndPushNode));
+//! ```
+//!
+//! The checks only look at the structure of the graph, and do not take the symbol and scope
+//! stacks into account.  A diagnostic is therefore a hint where to look, and does not guarantee
+//! that resolution is broken.  When linting the graph of a single file, symbols that are defined
+//! in other files are reported as [unpopped][Lint::UnpoppedSymbol].
+//!
+//! To find out which references actually fail to resolve, [`unresolved_references`][] resolves
+//! all references in a graph, and returns an [`UnresolvedReference`][] for each reference without
+//! any definitions.  The share of unresolved references on a corpus of source files is a good
+//! measure of how well the rules of a language cover it.  The [`coverage`][] function turns the
+//! unresolved references into a [`CoverageReport`][], with the share of resolved references per
+//! file and per syntax type, which can be written as CSV, or as JSON with the `serde` feature.
+//!
+//! When changing the rules of a language, [`resolutions`][] records which definitions each
+//! reference resolves to, identified by source location, and [`compare_resolutions`][] reports
+//! the references that gained, lost, or changed their definitions between the old and the new
+//! rules.  With the `storage` feature, `compare_databases` does both for two databases that
+//! were indexed from the same source files.
+//!
+//! The [`call_graph`][] function links every definition to the definitions that are referenced
+//! in its body, which is given by the definiens span of the definition.  The resulting
+//! [`CallGraph`][] can be written in Graphviz DOT format, or as JSON with the `serde` feature.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::fmt::Display;
+#[cfg(feature = "storage")]
+use std::path::Path;
+
+use crate::arena::Handle;
+use crate::arena::HandleSet;
+use crate::graph::EdgeDirection;
+use crate::graph::File;
+use crate::graph::Node;
+use crate::graph::StackGraph;
+use crate::graph::Symbol;
+use crate::partial::PartialPath;
+use crate::stitching::Appendable;
+use crate::stitching::ForwardCandidates;
+use crate::stitching::ForwardPartialPathStitcher;
+use crate::stitching::StitcherConfig;
+use crate::stitching::ToAppendable;
+#[cfg(feature = "storage")]
+use crate::storage::SQLiteReader;
+#[cfg(feature = "storage")]
+use crate::storage::StorageError;
+use crate::CancellationError;
+use crate::CancellationFlag;
+
+/// A kind of mistake that is detected by [`lint`][].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Lint {
+    /// A pop node that cannot be reached from the root node, from any push node, or from any
+    /// scope attached by a scoped push node.  No path can ever reach the definition.
+    UnreachablePopNode,
+    /// A push node without outgoing edges.  Paths starting at the reference end immediately,
+    /// and the reference never resolves.
+    DeadEndPushNode,
+    /// An exported scope that is not attached to any scoped symbol.  Usually the scoped push
+    /// node that should refer to it is missing, or refers to another scope.
+    UnusedExportedScope,
+    /// A symbol that is pushed but never popped.  Usually the pop nodes that should match are
+    /// missing, or use a differently spelled symbol.  The diagnostic lists all push nodes of the
+    /// symbol, so that symbols with many unmatched references stand out.
+    UnpoppedSymbol,
+    /// A symbol that is pushed only with attached scopes and popped only without, or the other
+    /// way around.  Scoped and unscoped symbols never match each other.
+    ScopedSymbolMismatch,
+    /// A scope that is not exported, but has [excluded symbols][StackGraph::set_excluded_symbols].
+    /// Partial paths are only split at exported scopes, so a partial path can pass the scope
+    /// before the symbol on top of its symbol stack is known, and the exclusions are not applied.
+    ExcludedSymbolsOnInternalScope,
+    /// A scope with many edges to pop nodes that each push the same symbol again, which
+    /// re-exports symbols one by one.  The diagnostic lists the pop nodes.  A single edge to the
+    /// re-exported scope, with [excluded symbols][StackGraph::set_excluded_symbols] for the
+    /// symbols that are not re-exported, does not need to enumerate the symbols.
+    EagerReexport,
+}
+
+impl Lint {
+    /// Returns a short, stable name for this lint.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::UnreachablePopNode => "unreachable-pop-node",
+            Self::DeadEndPushNode => "dead-end-push-node",
+            Self::UnusedExportedScope => "unused-exported-scope",
+            Self::UnpoppedSymbol => "unpopped-symbol",
+            Self::ScopedSymbolMismatch => "scoped-symbol-mismatch",
+            Self::ExcludedSymbolsOnInternalScope => "excluded-symbols-on-internal-scope",
+            Self::EagerReexport => "eager-reexport",
+        }
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A mistake found by [`lint`][].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub lint: Lint,
+    /// The node the diagnostic is about.
+    pub node: Handle<Node>,
+    /// Other nodes that are involved, such as the remaining push nodes of an unpopped symbol.
+    pub related: Vec<Handle<Node>>,
+}
+
+impl Diagnostic {
+    fn new(lint: Lint, node: Handle<Node>) -> Self {
+        Self {
+            lint,
+            node,
+            related: Vec::new(),
+        }
+    }
+
+    pub fn display<'a>(&'a self, graph: &'a StackGraph) -> impl Display + 'a {
+        DisplayDiagnostic {
+            wrapped: self,
+            graph,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct DisplayDiagnostic<'a> {
+    wrapped: &'a Diagnostic,
+    graph: &'a StackGraph,
+}
+
+impl Display for DisplayDiagnostic<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let diagnostic = self.wrapped;
+        write!(
+            f,
+            "{}: {}",
+            diagnostic.lint,
+            diagnostic.node.display(self.graph)
+        )?;
+        if !diagnostic.related.is_empty() {
+            write!(f, " (and {} more)", diagnostic.related.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the graph for common rule-authoring mistakes.  Diagnostics are ordered by lint, and
+/// then by the order in which their nodes were added to the graph.
+pub fn lint(graph: &StackGraph) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    unreachable_pop_nodes(graph, &mut diagnostics);
+    dead_end_push_nodes(graph, &mut diagnostics);
+    unused_exported_scopes(graph, &mut diagnostics);
+    symbol_mismatches(graph, &mut diagnostics);
+    excluded_symbols_on_internal_scopes(graph, &mut diagnostics);
+    eager_reexports(graph, &mut diagnostics);
+    diagnostics
+}
+
+fn is_push(node: &Node) -> bool {
+    matches!(node, Node::PushSymbol(_) | Node::PushScopedSymbol(_))
+}
+
+fn is_pop(node: &Node) -> bool {
+    matches!(node, Node::PopSymbol(_) | Node::PopScopedSymbol(_))
+}
+
+/// Returns the scopes that are attached to scoped symbols by scoped push nodes.
+fn attached_scopes(graph: &StackGraph) -> HandleSet<Node> {
+    let mut scopes = HandleSet::new();
+    for node in graph.iter_nodes() {
+        if let Node::PushScopedSymbol(push) = &graph[node] {
+            if let Some(scope) = graph.node_for_id(push.scope) {
+                scopes.add(scope);
+            }
+        }
+    }
+    scopes
+}
+
+fn unreachable_pop_nodes(graph: &StackGraph, diagnostics: &mut Vec<Diagnostic>) {
+    let attached_scopes = attached_scopes(graph);
+    let starts = std::iter::once(StackGraph::root_node())
+        .chain(graph.iter_nodes().filter(|n| is_push(&graph[*n])))
+        .chain(attached_scopes.iter());
+    let reachable = graph.reachable_from(starts, EdgeDirection::Forward, |_, _| true);
+    diagnostics.extend(
+        graph
+            .iter_nodes()
+            .filter(|n| is_pop(&graph[*n]) && !reachable.contains(*n))
+            .map(|n| Diagnostic::new(Lint::UnreachablePopNode, n)),
+    );
+}
+
+fn dead_end_push_nodes(graph: &StackGraph, diagnostics: &mut Vec<Diagnostic>) {
+    diagnostics.extend(
+        graph
+            .iter_nodes()
+            .filter(|n| is_push(&graph[*n]) && graph.outgoing_edges(*n).next().is_none())
+            .map(|n| Diagnostic::new(Lint::DeadEndPushNode, n)),
+    );
+}
+
+fn unused_exported_scopes(graph: &StackGraph, diagnostics: &mut Vec<Diagnostic>) {
+    let attached_scopes = attached_scopes(graph);
+    diagnostics.extend(
+        graph
+            .iter_nodes()
+            .filter(|n| graph[*n].is_exported_scope() && !attached_scopes.contains(*n))
+            .map(|n| Diagnostic::new(Lint::UnusedExportedScope, n)),
+    );
+}
+
+/// How a symbol is used by the push and pop nodes of a graph.
+#[derive(Default)]
+struct SymbolUse {
+    pushes: Vec<Handle<Node>>,
+    pushed_scoped: bool,
+    pushed_unscoped: bool,
+    popped_scoped: bool,
+    popped_unscoped: bool,
+}
+
+fn symbol_mismatches(graph: &StackGraph, diagnostics: &mut Vec<Diagnostic>) {
+    let mut uses = HashMap::<Handle<Symbol>, SymbolUse>::new();
+    let mut order = Vec::new();
+    for node in graph.iter_nodes() {
+        let (symbol, is_push, is_scoped) = match &graph[node] {
+            Node::PushSymbol(n) => (n.symbol, true, false),
+            Node::PushScopedSymbol(n) => (n.symbol, true, true),
+            Node::PopSymbol(n) => (n.symbol, false, false),
+            Node::PopScopedSymbol(n) => (n.symbol, false, true),
+            _ => continue,
+        };
+        let symbol_use = uses.entry(symbol).or_insert_with(|| {
+            order.push(symbol);
+            SymbolUse::default()
+        });
+        match (is_push, is_scoped) {
+            (true, true) => symbol_use.pushed_scoped = true,
+            (true, false) => symbol_use.pushed_unscoped = true,
+            (false, true) => symbol_use.popped_scoped = true,
+            (false, false) => symbol_use.popped_unscoped = true,
+        }
+        if is_push {
+            symbol_use.pushes.push(node);
+        }
+    }
+
+    let mut unpopped = Vec::new();
+    let mut mismatched = Vec::new();
+    for symbol in order {
+        let symbol_use = &uses[&symbol];
+        let (first, rest) = match symbol_use.pushes.split_first() {
+            Some(pushes) => pushes,
+            None => continue,
+        };
+        let lint = if !symbol_use.popped_scoped && !symbol_use.popped_unscoped {
+            Lint::UnpoppedSymbol
+        } else if (symbol_use.pushed_scoped && !symbol_use.pushed_unscoped)
+            && (symbol_use.popped_unscoped && !symbol_use.popped_scoped)
+            || (symbol_use.pushed_unscoped && !symbol_use.pushed_scoped)
+                && (symbol_use.popped_scoped && !symbol_use.popped_unscoped)
+        {
+            Lint::ScopedSymbolMismatch
+        } else {
+            continue;
+        };
+        let diagnostic = Diagnostic {
+            lint,
+            node: *first,
+            related: rest.to_vec(),
+        };
+        match lint {
+            Lint::UnpoppedSymbol => unpopped.push(diagnostic),
+            _ => mismatched.push(diagnostic),
+        }
+    }
+    diagnostics.extend(unpopped);
+    diagnostics.extend(mismatched);
+}
+
+fn excluded_symbols_on_internal_scopes(graph: &StackGraph, diagnostics: &mut Vec<Diagnostic>) {
+    diagnostics.extend(
+        graph
+            .iter_nodes()
+            .filter(|n| !graph[*n].is_exported_scope() && graph.excluded_symbols(*n).is_some())
+            .map(|n| Diagnostic::new(Lint::ExcludedSymbolsOnInternalScope, n)),
+    );
+}
+
+/// The number of symbols that a scope must re-export one by one to be reported as an
+/// [eager re-export][Lint::EagerReexport].
+const EAGER_REEXPORT_THRESHOLD: usize = 8;
+
+fn eager_reexports(graph: &StackGraph, diagnostics: &mut Vec<Diagnostic>) {
+    for node in graph.iter_nodes() {
+        let reexports = graph
+            .outgoing_edges(node)
+            .map(|edge| edge.sink)
+            .filter(|pop| {
+                let symbol = match &graph[*pop] {
+                    Node::PopSymbol(pop) => pop.symbol,
+                    _ => return false,
+                };
+                graph.outgoing_edges(*pop).any(|edge| {
+                    matches!(&graph[edge.sink], Node::PushSymbol(push) if push.symbol == symbol)
+                })
+            })
+            .collect::<Vec<_>>();
+        if reexports.len() >= EAGER_REEXPORT_THRESHOLD {
+            diagnostics.push(Diagnostic {
+                lint: Lint::EagerReexport,
+                node,
+                related: reexports,
+            });
+        }
+    }
+}
+
+/// A reference for which resolution found no definitions, found by [`unresolved_references`][].
+#[derive(Clone)]
+pub struct UnresolvedReference {
+    pub reference: Handle<Node>,
+    /// The diagnostics of [`lint`][] that involve the reference, which often explain why it
+    /// does not resolve.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The longest partial path that was found from the reference.  Its end node and symbol
+    /// stack show where resolution got stuck.
+    pub longest_path: PartialPath,
+}
+
+/// Resolves all references in the graph of the candidates, and returns the references that do not
+/// resolve to any definition, in the order in which they were added to the graph.  All paths that
+/// might be needed must be available from the candidates, for example from a [`Database`][]
+/// containing the partial paths of all files, with [`DatabaseCandidates`][].
+///
+/// [`Database`]: crate::stitching::Database
+/// [`DatabaseCandidates`]: crate::stitching::DatabaseCandidates
+pub fn unresolved_references<H, A, Db, C, Err>(
+    candidates: &mut C,
+    config: StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<UnresolvedReference>, Err>
+where
+    H: Clone,
+    A: Appendable,
+    Db: ToAppendable<H, A>,
+    C: ForwardCandidates<H, A, Db, Err>,
+    Err: std::convert::From<CancellationError>,
+{
+    let (graph, _, _) = candidates.get_graph_partials_and_db();
+    let references = graph
+        .iter_nodes()
+        .filter(|n| graph[*n].is_reference())
+        .collect::<Vec<_>>();
+    let diagnostics = lint(graph);
+
+    let mut stitcher = ForwardPartialPathStitcher::<H>::for_complete_partial_paths(
+        candidates,
+        references.iter().copied(),
+        config,
+    );
+    let mut resolved = HandleSet::new();
+    let mut longest_paths = HashMap::new();
+    let mut record_phase = |graph: &StackGraph, paths: &mut dyn Iterator<Item = &PartialPath>| {
+        for path in paths {
+            if path.is_complete(graph) {
+                resolved.add(path.start_node);
+            }
+            let longest = longest_paths
+                .entry(path.start_node)
+                .or_insert_with(|| path.clone());
+            if path.edges.len() > longest.edges.len() {
+                *longest = path.clone();
+            }
+        }
+    };
+    record_phase(
+        candidates.get_graph_partials_and_db().0,
+        &mut stitcher.previous_phase_partial_paths(),
+    );
+    while !stitcher.is_complete() {
+        cancellation_flag.check("finding unresolved references")?;
+        for path in stitcher.partial_paths_to_load() {
+            candidates.load_forward_candidates(path, cancellation_flag)?;
+        }
+        stitcher.process_next_phase(candidates, |_, _, _| true);
+        record_phase(
+            candidates.get_graph_partials_and_db().0,
+            &mut stitcher.previous_phase_partial_paths(),
+        );
+    }
+
+    Ok(references
+        .into_iter()
+        .filter(|reference| !resolved.contains(*reference))
+        .map(|reference| UnresolvedReference {
+            reference,
+            diagnostics: diagnostics
+                .iter()
+                .filter(|d| d.node == reference || d.related.contains(&reference))
+                .cloned()
+                .collect(),
+            longest_path: longest_paths
+                .remove(&reference)
+                .expect("initial path of reference to be recorded"),
+        })
+        .collect())
+}
+
+/// The number of resolved references among a set of references.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CoverageCounts {
+    pub references: usize,
+    pub resolved: usize,
+}
+
+impl CoverageCounts {
+    pub fn unresolved(&self) -> usize {
+        self.references - self.resolved
+    }
+
+    /// Returns the share of resolved references, between 0 and 1.  An empty set of references
+    /// is fully covered.
+    pub fn resolved_ratio(&self) -> f64 {
+        if self.references == 0 {
+            1.0
+        } else {
+            self.resolved as f64 / self.references as f64
+        }
+    }
+
+    fn add(&mut self, resolved: bool) {
+        self.references += 1;
+        if resolved {
+            self.resolved += 1;
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for CoverageCounts {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("CoverageCounts", 3)?;
+        s.serialize_field("references", &self.references)?;
+        s.serialize_field("resolved", &self.resolved)?;
+        s.serialize_field("resolved_ratio", &self.resolved_ratio())?;
+        s.end()
+    }
+}
+
+/// A reference from the body of one definition to another definition, found by [`call_graph`][].
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Call {
+    /// The definition whose body contains the reference.
+    pub caller: SourceLocation,
+    /// The definition that the reference resolves to.
+    pub callee: SourceLocation,
+}
+
+/// The calls between definitions, found by [`call_graph`][], which can be written in Graphviz
+/// DOT format, or as JSON with the `serde` feature.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CallGraph {
+    /// The calls, ordered by caller and callee.  Every pair of definitions occurs once, no matter
+    /// how often the caller refers to the callee.
+    pub calls: Vec<Call>,
+}
+
+impl CallGraph {
+    /// Returns the call graph in Graphviz DOT format, with a node for every definition that
+    /// takes part in a call.
+    pub fn to_dot_string(&self, title: &str) -> String {
+        let escape = |value: &str| value.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = format!("digraph \"{}\" {{\n", escape(title));
+        dot.push_str("  node [shape=box, fontname=monospace];\n");
+        for call in &self.calls {
+            dot.push_str(&format!(
+                "  \"{}\" -> \"{}\";\n",
+                escape(&call.caller.to_string()),
+                escape(&call.callee.to_string())
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Finds the calls between definitions in the given files.  A reference is part of the body of
+/// the innermost definition in the same file whose definiens span contains the span of the
+/// reference.  Every such reference is resolved, and its definition, after shadowing, is linked
+/// to the definitions it resolves to.  References outside of any body, and definitions without
+/// a [`SourceLocation`][], are left out.  All paths that might be needed must be available from
+/// the candidates, as for [`unresolved_references`][].
+pub fn call_graph<I, H, A, Db, C, Err>(
+    candidates: &mut C,
+    files: I,
+    config: StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<CallGraph, Err>
+where
+    I: IntoIterator<Item = Handle<File>>,
+    H: Clone,
+    A: Appendable,
+    Db: ToAppendable<H, A>,
+    C: ForwardCandidates<H, A, Db, Err>,
+    Err: std::convert::From<CancellationError>,
+{
+    let (graph, _, _) = candidates.get_graph_partials_and_db();
+    let mut callers = HashMap::<Handle<Node>, Handle<Node>>::new();
+    for file in files {
+        let mut bodies = Vec::new();
+        let mut references = Vec::new();
+        for node in graph.nodes_for_file(file) {
+            let source_info = match graph.source_info(node) {
+                Some(source_info) => source_info,
+                None => continue,
+            };
+            if graph[node].is_definition() && !source_info.definiens_span.utf8_range().is_empty() {
+                bodies.push((source_info.definiens_span.utf8_range(), node));
+            }
+            if graph[node].is_reference() {
+                references.push((source_info.span.utf8_range(), node));
+            }
+        }
+        for (range, reference) in references {
+            let caller = bodies
+                .iter()
+                .filter(|(body, _)| body.start <= range.start && range.end <= body.end)
+                .min_by_key(|(body, _)| body.len())
+                .map(|(_, definition)| *definition);
+            if let Some(caller) = caller {
+                callers.insert(reference, caller);
+            }
+        }
+    }
+
+    let mut paths = HashMap::<Handle<Node>, Vec<PartialPath>>::new();
+    ForwardPartialPathStitcher::<H>::find_all_complete_partial_paths(
+        candidates,
+        callers.keys().copied(),
+        config,
+        cancellation_flag,
+        |_, _, path| {
+            paths.entry(path.start_node).or_default().push(path.clone());
+        },
+    )?;
+
+    let (graph, partials, _) = candidates.get_graph_partials_and_db();
+    let mut calls = BTreeSet::new();
+    for (reference, caller) in callers {
+        let caller = match SourceLocation::of_node(graph, caller) {
+            Some(caller) => caller,
+            None => continue,
+        };
+        let paths = paths.remove(&reference).unwrap_or_default();
+        for path in &paths {
+            if paths.iter().any(|other| other.shadows(partials, path)) {
+                continue;
+            }
+            if let Some(callee) = SourceLocation::of_node(graph, path.end_node) {
+                calls.insert(Call {
+                    caller: caller.clone(),
+                    callee,
+                });
+            }
+        }
+    }
+    Ok(CallGraph {
+        calls: calls.into_iter().collect(),
+    })
+}
+
+/// The share of resolved references in a set of files, found by [`coverage`][].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CoverageReport {
+    /// Counts over all references.
+    pub total: CoverageCounts,
+    /// Counts per file, keyed by file name.
+    pub files: BTreeMap<String, CoverageCounts>,
+    /// Counts per syntax type of the reference nodes.  References without a syntax type are
+    /// counted under the empty string.
+    pub syntax_types: BTreeMap<String, CoverageCounts>,
+}
+
+impl CoverageReport {
+    /// Returns the report as CSV, with a header line and one line for the total, each file, and
+    /// each syntax type.  The first column is `total`, `file`, or `syntax_type`, and the second
+    /// column the file name or syntax type.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("kind,name,references,resolved,resolved_ratio\n");
+        let mut push = |kind: &str, name: &str, counts: &CoverageCounts| {
+            csv.push_str(&format!(
+                "{},{},{},{},{:.4}\n",
+                kind,
+                csv_field(name),
+                counts.references,
+                counts.resolved,
+                counts.resolved_ratio()
+            ));
+        };
+        push("total", "", &self.total);
+        for (file, counts) in &self.files {
+            push("file", file, counts);
+        }
+        for (syntax_type, counts) in &self.syntax_types {
+            push("syntax_type", syntax_type, counts);
+        }
+        csv
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Computes the share of resolved references in the given files, from the result of
+/// [`unresolved_references`][].  Only references in the given files are counted, so that files
+/// that were loaded during resolution to find definitions do not affect the report.
+pub fn coverage<I>(
+    graph: &StackGraph,
+    files: I,
+    unresolved: &[UnresolvedReference],
+) -> CoverageReport
+where
+    I: IntoIterator<Item = Handle<File>>,
+{
+    let mut unresolved_nodes = HandleSet::new();
+    for reference in unresolved {
+        unresolved_nodes.add(reference.reference);
+    }
+    let mut report = CoverageReport::default();
+    for file in files {
+        let file_counts = report
+            .files
+            .entry(graph[file].name().to_string())
+            .or_default();
+        for node in graph.nodes_for_file(file) {
+            if !graph[node].is_reference() {
+                continue;
+            }
+            let resolved = !unresolved_nodes.contains(node);
+            let syntax_type = graph
+                .source_info(node)
+                .and_then(|s| s.syntax_type.into_option())
+                .map(|s| graph[s].to_string())
+                .unwrap_or_default();
+            report.total.add(resolved);
+            file_counts.add(resolved);
+            report
+                .syntax_types
+                .entry(syntax_type)
+                .or_default()
+                .add(resolved);
+        }
+    }
+    report
+}
+
+/// A location in source code that identifies a reference or definition independently of the graph
+/// it belongs to, so that the resolutions of graphs that were built by different rules can be
+/// compared.  Lines and columns are 1-based, and columns count graphemes, as in the
+/// `PATH:LINE:COLUMN` positions of the command-line tools.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    /// The symbol of the node, which distinguishes references and definitions that start at the
+    /// same position.
+    pub symbol: String,
+}
+
+impl SourceLocation {
+    /// Returns the location of a node, or `None` if the node does not belong to a file or has
+    /// no source info.
+    pub fn of_node(graph: &StackGraph, node: Handle<Node>) -> Option<Self> {
+        let file = graph[node].file()?;
+        let span = &graph.source_info(node)?.span;
+        Some(Self {
+            file: graph[file].name().to_string(),
+            line: span.start.line + 1,
+            column: span.start.column.grapheme_offset + 1,
+            symbol: graph[node]
+                .symbol()
+                .map(|symbol| graph[symbol].to_string())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{} {}",
+            self.file, self.line, self.column, self.symbol
+        )
+    }
+}
+
+/// The definitions that each reference resolves to, found by [`resolutions`][].  References
+/// without definitions map to an empty set.
+pub type Resolutions = BTreeMap<SourceLocation, BTreeSet<SourceLocation>>;
+
+/// Resolves the references in the given files, and returns the definitions that each of them
+/// resolves to, after shadowing.  References and definitions without a [`SourceLocation`][] are
+/// left out.  All paths that might be needed must be available from the candidates, as for
+/// [`unresolved_references`][].
+pub fn resolutions<I, H, A, Db, C, Err>(
+    candidates: &mut C,
+    files: I,
+    config: StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Resolutions, Err>
+where
+    I: IntoIterator<Item = Handle<File>>,
+    H: Clone,
+    A: Appendable,
+    Db: ToAppendable<H, A>,
+    C: ForwardCandidates<H, A, Db, Err>,
+    Err: std::convert::From<CancellationError>,
+{
+    let (graph, _, _) = candidates.get_graph_partials_and_db();
+    let references = files
+        .into_iter()
+        .flat_map(|file| graph.nodes_for_file(file))
+        .filter(|node| graph[*node].is_reference())
+        .collect::<Vec<_>>();
+
+    let mut paths = HashMap::<Handle<Node>, Vec<PartialPath>>::new();
+    ForwardPartialPathStitcher::<H>::find_all_complete_partial_paths(
+        candidates,
+        references.iter().copied(),
+        config,
+        cancellation_flag,
+        |_, _, path| {
+            paths.entry(path.start_node).or_default().push(path.clone());
+        },
+    )?;
+
+    let (graph, partials, _) = candidates.get_graph_partials_and_db();
+    let mut resolutions = Resolutions::new();
+    for reference in references {
+        let location = match SourceLocation::of_node(graph, reference) {
+            Some(location) => location,
+            None => continue,
+        };
+        let definitions = resolutions.entry(location).or_default();
+        let paths = paths.remove(&reference).unwrap_or_default();
+        for path in &paths {
+            if paths.iter().any(|other| other.shadows(partials, path)) {
+                continue;
+            }
+            definitions.extend(SourceLocation::of_node(graph, path.end_node));
+        }
+    }
+    Ok(resolutions)
+}
+
+/// How the resolution of a reference differs between two sets of [`Resolutions`][].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum ResolutionChange {
+    /// The reference did not resolve before, and resolves now.
+    Gained,
+    /// The reference resolved before, and does not resolve now.
+    Lost,
+    /// The reference resolves before and now, but to different definitions.
+    Changed,
+}
+
+/// A reference that resolves differently, found by [`compare_resolutions`][].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ResolutionDifference {
+    pub reference: SourceLocation,
+    pub change: ResolutionChange,
+    /// The definitions the reference resolved to before.
+    pub old: Vec<SourceLocation>,
+    /// The definitions the reference resolves to now.
+    pub new: Vec<SourceLocation>,
+}
+
+/// The differences between two sets of [`Resolutions`][], found by [`compare_resolutions`][],
+/// which can be written as JSON with the `serde` feature.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ResolutionComparison {
+    /// The number of references that were compared.
+    pub references: usize,
+    /// The references that resolve differently, ordered by location.
+    pub differences: Vec<ResolutionDifference>,
+}
+
+impl ResolutionComparison {
+    /// Returns the number of differences of the given kind.
+    pub fn count(&self, change: ResolutionChange) -> usize {
+        self.differences
+            .iter()
+            .filter(|d| d.change == change)
+            .count()
+    }
+}
+
+/// Compares the resolutions of the same code, for example from graphs built by an old and a new
+/// version of the rules of a language.  References that only exist on one side are compared as
+/// if they had no definitions on the other side.
+pub fn compare_resolutions(old: &Resolutions, new: &Resolutions) -> ResolutionComparison {
+    let empty = BTreeSet::new();
+    let references = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+    let mut comparison = ResolutionComparison {
+        references: references.len(),
+        differences: Vec::new(),
+    };
+    for reference in references {
+        let old_definitions = old.get(reference).unwrap_or(&empty);
+        let new_definitions = new.get(reference).unwrap_or(&empty);
+        let change = match (old_definitions.is_empty(), new_definitions.is_empty()) {
+            (true, true) => continue,
+            (true, false) => ResolutionChange::Gained,
+            (false, true) => ResolutionChange::Lost,
+            (false, false) if old_definitions == new_definitions => continue,
+            (false, false) => ResolutionChange::Changed,
+        };
+        comparison.differences.push(ResolutionDifference {
+            reference: reference.clone(),
+            change,
+            old: old_definitions.iter().cloned().collect(),
+            new: new_definitions.iter().cloned().collect(),
+        });
+    }
+    comparison
+}
+
+/// Compares the resolutions of the references in the given source files or directories between
+/// two databases, for example the databases produced by indexing a corpus with an old and a new
+/// version of the rules of a language.
+#[cfg(feature = "storage")]
+pub fn compare_databases(
+    old: &mut SQLiteReader,
+    new: &mut SQLiteReader,
+    source_paths: &[&Path],
+    config: StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<ResolutionComparison, StorageError> {
+    let old = database_resolutions(old, source_paths, config, cancellation_flag)?;
+    let new = database_resolutions(new, source_paths, config, cancellation_flag)?;
+    Ok(compare_resolutions(&old, &new))
+}
+
+#[cfg(feature = "storage")]
+fn database_resolutions(
+    db: &mut SQLiteReader,
+    source_paths: &[&Path],
+    config: StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Resolutions, StorageError> {
+    for source_path in source_paths {
+        db.load_graphs_for_file_or_directory(source_path, cancellation_flag)?;
+    }
+    let (graph, _, _) = db.get();
+    let files = graph.iter_files().collect::<Vec<_>>();
+    resolutions(db, files, config, cancellation_flag)
+}
@@ -0,0 +1,383 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! A flat, zero-copy format for file graphs and partial paths.
+//!
+//! An archive is a single buffer, written by an [`ArchiveBuilder`][], that contains the graphs of
+//! a set of files and their partial paths, together with indices to look up the paths that start
+//! at a node or at the root node.  Reading an archive with an [`ArchiveReader`][] validates the
+//! buffer once, after which the indices and paths are read in place, without deserializing them.
+//! This makes archives suited for latency-sensitive services that memory-map or cache them.
+//!
+//! The reader implements [`ForwardCandidates`][crate::stitching::ForwardCandidates] directly over the archive: looking up the
+//! candidates of a path searches the indices in the buffer, and only the candidate paths, and the
+//! graphs of the files they refer to, are loaded into the reader's arenas.
+//!
+//! ```
+//! # use stack_graphs::archive::ArchiveBuilder;
+//! # use stack_graphs::archive::ArchiveReader;
+//! # use stack_graphs::graph::StackGraph;
+//! # let mut graph = StackGraph::new();
+//! # let file = graph.get_or_create_file("test.py");
+//! let mut builder = ArchiveBuilder::new();
+//! builder.add_file_graph(&graph, file);
+//! let bytes = builder.to_bytes()?;
+//!
+//! let mut reader = ArchiveReader::new(&bytes)?;
+//! let file = reader.load_graph_for_file("test.py")?;
+//! # Ok::<(), stack_graphs::archive::ArchiveError>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+
+use rkyv::Deserialize;
+use thiserror::Error;
+
+use crate::arena::Handle;
+use crate::graph::File;
+use crate::graph::StackGraph;
+use crate::lazy::compatible_root_paths;
+use crate::lazy::root_path_query;
+use crate::lazy::PathLoader;
+use crate::partial::PartialPath;
+use crate::partial::PartialPaths;
+use crate::serde;
+use crate::serde::ArchivedPartialPath;
+use crate::serde::FileFilter;
+use crate::stitching::Database;
+use crate::CancellationError;
+use crate::CancellationFlag;
+
+pub use rkyv::AlignedVec;
+
+/// The version of the archive format.  Archives of other versions are rejected when read.
+const VERSION: u32 = 2;
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("cancelled at {0}")]
+    Cancelled(&'static str),
+    #[error("unsupported archive version {0}")]
+    IncorrectVersion(u32),
+    #[error("invalid archive: {0}")]
+    Invalid(String),
+    #[error("file `{0}` is not in the archive")]
+    MissingFile(String),
+    #[error("failed to write archive: {0}")]
+    SerializeFail(String),
+    #[error(transparent)]
+    Serde(#[from] serde::Error),
+}
+
+impl From<CancellationError> for ArchiveError {
+    fn from(value: CancellationError) -> Self {
+        Self::Cancelled(value.0)
+    }
+}
+
+#[derive(rkyv::Archive, rkyv::Serialize)]
+#[archive(check_bytes)]
+struct Contents {
+    version: u32,
+    /// Sorted by name.
+    files: Vec<FileEntry>,
+    paths: Vec<PathEntry>,
+    /// Sorted by file and local ID.
+    node_paths: Vec<NodePaths>,
+    /// Sorted by symbols, and paths without a variable first.
+    root_paths: Vec<RootPaths>,
+}
+
+#[derive(rkyv::Archive, rkyv::Serialize)]
+#[archive(check_bytes)]
+struct FileEntry {
+    name: String,
+    graph: serde::StackGraph,
+}
+
+#[derive(rkyv::Archive, rkyv::Serialize)]
+#[archive(check_bytes)]
+struct PathEntry {
+    /// The files of all nodes the path refers to, which must be loaded before the path.
+    files: Vec<String>,
+    path: serde::PartialPath,
+}
+
+/// The paths that start at a node in a file.
+#[derive(rkyv::Archive, rkyv::Serialize)]
+#[archive(check_bytes)]
+struct NodePaths {
+    file: String,
+    local_id: u32,
+    paths: Vec<u32>,
+}
+
+/// The paths that start at the root node, with the given symbol stack precondition.
+#[derive(rkyv::Archive, rkyv::Serialize)]
+#[archive(check_bytes)]
+struct RootPaths {
+    symbols: Vec<String>,
+    has_variable: bool,
+    paths: Vec<u32>,
+}
+
+//-------------------------------------------------------------------------------------------------
+// Writing archives
+
+/// Collects file graphs and partial paths, and writes them as an archive.
+#[derive(Default)]
+pub struct ArchiveBuilder {
+    files: BTreeMap<String, serde::StackGraph>,
+    paths: Vec<PathEntry>,
+    node_paths: BTreeMap<(String, u32), Vec<u32>>,
+    root_paths: BTreeMap<(Vec<String>, bool), Vec<u32>>,
+}
+
+impl ArchiveBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the graph of the given file to the archive.
+    pub fn add_file_graph(&mut self, graph: &StackGraph, file: Handle<File>) {
+        self.files.insert(
+            graph[file].name().to_string(),
+            serde::StackGraph::from_graph_filter(graph, &FileFilter(file)),
+        );
+    }
+
+    /// Adds a partial path to the archive.  Paths are indexed by their start node, so only paths
+    /// that start at the root node or at a node in a file can be found as candidates.  The graphs
+    /// of the files the path refers to must be added to the archive as well.
+    pub fn add_partial_path(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        path: &PartialPath,
+    ) {
+        let index = self.paths.len() as u32;
+        let start_node = &graph[path.start_node];
+        let path = serde::PartialPath::from_partial_path(graph, partials, path);
+        if start_node.is_root() {
+            let precondition = &path.symbol_stack_precondition;
+            let symbols = precondition
+                .symbols
+                .iter()
+                .map(|s| s.symbol.clone())
+                .collect();
+            self.root_paths
+                .entry((symbols, precondition.variable.is_some()))
+                .or_default()
+                .push(index);
+        } else if let Some(file) = start_node.id().file() {
+            let key = (graph[file].name().to_string(), start_node.id().local_id());
+            self.node_paths.entry(key).or_default().push(index);
+        }
+        let files = path.files().into_iter().map(str::to_string).collect();
+        self.paths.push(PathEntry { files, path });
+    }
+
+    /// Writes the archive.  The returned buffer is aligned, so that it can be read in place.
+    pub fn to_bytes(&self) -> Result<AlignedVec, ArchiveError> {
+        rkyv::to_bytes::<_, 4096>(&self.contents())
+            .map_err(|e| ArchiveError::SerializeFail(e.to_string()))
+    }
+
+    /// Returns the contents of the archive.  The indices are kept in sorted maps, which are
+    /// written as sorted vectors so that readers can binary search them.
+    fn contents(&self) -> Contents {
+        Contents {
+            version: VERSION,
+            files: self
+                .files
+                .iter()
+                .map(|(name, graph)| FileEntry {
+                    name: name.clone(),
+                    graph: graph.clone(),
+                })
+                .collect(),
+            paths: self
+                .paths
+                .iter()
+                .map(|entry| PathEntry {
+                    files: entry.files.clone(),
+                    path: entry.path.clone(),
+                })
+                .collect(),
+            node_paths: self
+                .node_paths
+                .iter()
+                .map(|((file, local_id), paths)| NodePaths {
+                    file: file.clone(),
+                    local_id: *local_id,
+                    paths: paths.clone(),
+                })
+                .collect(),
+            root_paths: self
+                .root_paths
+                .iter()
+                .map(|((symbols, has_variable), paths)| RootPaths {
+                    symbols: symbols.clone(),
+                    has_variable: *has_variable,
+                    paths: paths.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Reading archives
+
+/// Reads file graphs and partial paths from an archive, without deserializing the archive.
+pub struct ArchiveReader<'a> {
+    contents: &'a ArchivedContents,
+    graph: StackGraph,
+    partials: PartialPaths,
+    db: Database,
+    loaded_paths: HashSet<u32>,
+}
+
+impl<'a> ArchiveReader<'a> {
+    /// Opens an archive, after validating its contents.  The buffer must be aligned as returned
+    /// by [`ArchiveBuilder::to_bytes`][].
+    pub fn new(bytes: &'a [u8]) -> Result<Self, ArchiveError> {
+        let contents = rkyv::check_archived_root::<Contents>(bytes)
+            .map_err(|e| ArchiveError::Invalid(e.to_string()))?;
+        if contents.version != VERSION {
+            return Err(ArchiveError::IncorrectVersion(contents.version));
+        }
+        Ok(Self {
+            contents,
+            graph: StackGraph::new(),
+            partials: PartialPaths::new(),
+            db: Database::new(),
+            loaded_paths: HashSet::new(),
+        })
+    }
+
+    /// Returns the names of the files in the archive.
+    pub fn files(&self) -> impl Iterator<Item = &'a str> + 'a {
+        self.contents.files.iter().map(|f| f.name.as_str())
+    }
+
+    /// Returns the number of partial paths in the archive.
+    pub fn path_count(&self) -> usize {
+        self.contents.paths.len()
+    }
+
+    /// Returns the archived partial paths that start at the given node, without loading them.
+    pub fn archived_paths_for_node(
+        &self,
+        file: &str,
+        local_id: u32,
+    ) -> impl Iterator<Item = &'a ArchivedPartialPath> + 'a {
+        let contents = self.contents;
+        self.node_path_indices(file, local_id)
+            .iter()
+            .map(move |index| &contents.paths[*index as usize].path)
+    }
+
+    /// Load the graph of the given file from the archive, unless it is already loaded.
+    pub fn load_graph_for_file(&mut self, file: &str) -> Result<Handle<File>, ArchiveError> {
+        if let Some(handle) = self.graph.get_file(file) {
+            return Ok(handle);
+        }
+        let entry = self
+            .contents
+            .files
+            .binary_search_by(|f| f.name.as_str().cmp(file))
+            .map(|index| &self.contents.files[index])
+            .map_err(|_| ArchiveError::MissingFile(file.to_string()))?;
+        let graph: serde::StackGraph = entry.graph.deserialize(&mut rkyv::Infallible).unwrap();
+        graph.load_into(&mut self.graph)?;
+        Ok(self.graph.get_file(file).expect("loaded file to exist"))
+    }
+
+    /// Load the partial paths from the archive that can extend the given partial path, and the
+    /// graphs of the files they refer to.
+    pub fn load_partial_path_extensions(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), ArchiveError> {
+        let end_node = &self.graph[path.end_node];
+        let indices = if end_node.is_root() {
+            let (symbols, has_variable) = root_path_query(&self.graph, &mut self.partials, path);
+            let root_paths = &self.contents.root_paths;
+            compatible_root_paths(root_paths, root_key, &symbols, has_variable)
+                .into_iter()
+                .flat_map(|index| root_paths[index].paths.iter().copied())
+                .collect()
+        } else if let Some(file) = end_node.id().file() {
+            self.node_path_indices(self.graph[file].name(), end_node.id().local_id())
+                .to_vec()
+        } else {
+            return Ok(());
+        };
+        for index in indices {
+            cancellation_flag.check("loading archived paths")?;
+            self.load_path(index)?;
+        }
+        Ok(())
+    }
+
+    /// Get the stack graph, partial paths arena, and path database for the currently loaded data.
+    pub fn get(&mut self) -> (&mut StackGraph, &mut PartialPaths, &mut Database) {
+        (&mut self.graph, &mut self.partials, &mut self.db)
+    }
+
+    fn load_path(&mut self, index: u32) -> Result<(), ArchiveError> {
+        if !self.loaded_paths.insert(index) {
+            return Ok(());
+        }
+        let entry = &self.contents.paths[index as usize];
+        for file in entry.files.iter() {
+            self.load_graph_for_file(file.as_str())?;
+        }
+        let path = entry
+            .path
+            .to_partial_path(&mut self.graph, &mut self.partials)?;
+        self.db
+            .add_partial_path(&self.graph, &mut self.partials, path);
+        Ok(())
+    }
+
+    fn node_path_indices(&self, file: &str, local_id: u32) -> &'a [u32] {
+        let node_paths = &self.contents.node_paths;
+        match node_paths.binary_search_by(|e| (e.file.as_str(), e.local_id).cmp(&(file, local_id)))
+        {
+            Ok(index) => node_paths[index].paths.as_slice(),
+            Err(_) => &[],
+        }
+    }
+}
+
+fn root_key(entry: &ArchivedRootPaths) -> (impl Iterator<Item = &str>, bool) {
+    (entry.symbols.iter().map(|s| s.as_str()), entry.has_variable)
+}
+
+impl PathLoader for ArchiveReader<'_> {
+    type Error = ArchiveError;
+
+    fn load_partial_path_extensions(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), ArchiveError> {
+        ArchiveReader::load_partial_path_extensions(self, path, cancellation_flag)
+    }
+
+    fn loaded(&mut self) -> (&StackGraph, &mut PartialPaths, &mut Database) {
+        (&self.graph, &mut self.partials, &mut self.db)
+    }
+
+    fn loaded_database(&self) -> &Database {
+        &self.db
+    }
+}
@@ -22,14 +22,16 @@
 //!
 //! Note that our arena implementation does not support deletion!  Any content that you add to a
 //! [`StackGraph`][] will live as long as the stack graph itself does.  The entire region of memory
-//! for each arena will be freed in a single operation when the stack graph is dropped.
+//! for each arena will be freed in a single operation when the stack graph is dropped.  The one
+//! exception are deque arenas, which can be compacted using a [`DequeCompaction`][], which copies
+//! the cells of the deques that are still in use into a fresh arena.
 //!
 //! [arena allocation]: https://en.wikipedia.org/wiki/Region-based_memory_management
 //! [`Arena`]: struct.Arena.html
+//! [`DequeCompaction`]: struct.DequeCompaction.html
 //! [`Handle`]: struct.Handle.html
 //! [`StackGraph`]: ../graph/struct.StackGraph.html
 
-use std::cell::Cell;
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::hash::Hasher;
@@ -162,6 +164,10 @@ unsafe impl<T> Sync for Handle<T> {}
 /// when the arena itself is dropped.
 pub struct Arena<T> {
     items: Vec<MaybeUninit<T>>,
+    // Counters for the instances that were cleared from the arena.  They are only updated when
+    // the arena is cleared, so that adding instances does not have any overhead.
+    cleared_count: usize,
+    high_water_mark: usize,
 }
 
 impl<T> Drop for Arena<T> {
@@ -178,6 +184,8 @@ impl<T> Arena<T> {
     pub fn new() -> Arena<T> {
         Arena {
             items: vec![MaybeUninit::uninit()],
+            cleared_count: 0,
+            high_water_mark: 0,
         }
     }
 
@@ -185,6 +193,9 @@ impl<T> Arena<T> {
     /// the arena are invalid.
     #[inline(always)]
     pub fn clear(&mut self) {
+        let live_count = self.live_count();
+        self.cleared_count += live_count;
+        self.high_water_mark = self.high_water_mark.max(live_count);
         self.items.truncate(1);
     }
 
@@ -202,6 +213,17 @@ impl<T> Arena<T> {
     pub fn get(&self, handle: Handle<T>) -> &T {
         unsafe { std::mem::transmute(&self.items[handle.as_usize()]) }
     }
+
+    /// Dereferences a handle to an instance owned by this arena, returning `None` if the handle
+    /// is out of range for this arena.
+    pub fn try_get(&self, handle: Handle<T>) -> Option<&T> {
+        if handle.as_usize() < self.items.len() {
+            Some(self.get(handle))
+        } else {
+            None
+        }
+    }
+
     ///
     /// Dereferences a handle to an instance owned by this arena, returning a mutable reference to
     /// it.
@@ -217,6 +239,15 @@ impl<T> Arena<T> {
             .map(|index| Handle::new(unsafe { NonZeroU32::new_unchecked(index as u32) }))
     }
 
+    /// Iterate over mutable references to the instances in this arena.
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = (Handle<T>, &mut T)> {
+        self.items.iter_mut().enumerate().skip(1).map(|(i, x)| {
+            (Handle::from_some(i as u32), unsafe {
+                &mut *(x.as_mut_ptr())
+            })
+        })
+    }
+
     /// Returns a pointer to this arena's storage.
     pub(crate) fn as_ptr(&self) -> *const T {
         self.items.as_ptr() as *const T
@@ -227,6 +258,55 @@ impl<T> Arena<T> {
     pub fn len(&self) -> usize {
         self.items.len()
     }
+
+    /// Returns the number of instances that were added since the arena was last cleared.
+    #[inline(always)]
+    fn live_count(&self) -> usize {
+        self.items.len() - 1
+    }
+
+    /// Returns allocation statistics for this arena.
+    pub fn stats(&self) -> ArenaStats {
+        let live = self.live_count();
+        ArenaStats {
+            allocated: self.cleared_count + live,
+            live,
+            high_water_mark: self.high_water_mark.max(live),
+        }
+    }
+
+    /// Resets the high-water mark of this arena to the number of instances that are currently
+    /// live.
+    pub fn reset_high_water_mark(&mut self) {
+        self.high_water_mark = 0;
+    }
+}
+
+/// Allocation statistics for an [`Arena`][].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ArenaStats {
+    /// The number of instances that were added to the arena during its lifetime.
+    pub allocated: usize,
+    /// The number of instances that were added since the arena was last cleared.  Because
+    /// instances are never removed individually, this is an upper bound on the number of
+    /// instances that are still in use.
+    pub live: usize,
+    /// The largest number of instances that were live at the same time.
+    pub high_water_mark: usize,
+}
+
+impl std::ops::AddAssign<Self> for ArenaStats {
+    fn add_assign(&mut self, rhs: Self) {
+        *self += &rhs;
+    }
+}
+
+impl std::ops::AddAssign<&Self> for ArenaStats {
+    fn add_assign(&mut self, rhs: &Self) {
+        self.allocated += rhs.allocated;
+        self.live += rhs.live;
+        self.high_water_mark += rhs.high_water_mark;
+    }
 }
 
 //-------------------------------------------------------------------------------------------------
@@ -338,6 +418,15 @@ impl<H, T> SupplementalArena<H, T> {
             .skip(1)
             .map(|(i, x)| (Handle::from_some(i as u32), unsafe { &*(x.as_ptr()) }))
     }
+
+    /// Iterate over mutable references to the items in this arena.
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = (Handle<H>, &mut T)> {
+        self.items.iter_mut().enumerate().skip(1).map(|(i, x)| {
+            (Handle::from_some(i as u32), unsafe {
+                &mut *(x.as_mut_ptr())
+            })
+        })
+    }
 }
 
 impl<H, T> SupplementalArena<H, T>
@@ -415,6 +504,15 @@ impl<T> HandleSet<T> {
         *bit = true;
     }
 
+    /// Adds a handle to this set, returning whether it was not in the set before.
+    pub fn insert(&mut self, handle: Handle<T>) -> bool {
+        if self.contains(handle) {
+            return false;
+        }
+        self.add(handle);
+        true
+    }
+
     /// Removes a handle from this set.
     pub fn remove(&mut self, handle: Handle<T>) {
         let index = handle.as_usize();
@@ -423,6 +521,55 @@ impl<T> HandleSet<T> {
         }
     }
 
+    /// Returns whether this set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.elements.not_any()
+    }
+
+    /// Returns the number of handles in this set.
+    pub fn count(&self) -> usize {
+        self.elements.count_ones()
+    }
+
+    /// Adds all handles of another set to this set.
+    pub fn union_with(&mut self, other: &HandleSet<T>) {
+        if self.elements.len() < other.elements.len() {
+            self.elements.resize(other.elements.len(), false);
+        }
+        let len = other.elements.len();
+        self.elements[..len] |= other.elements.as_bitslice();
+    }
+
+    /// Removes all handles from this set that are not in another set.
+    pub fn intersect_with(&mut self, other: &HandleSet<T>) {
+        let len = self.elements.len().min(other.elements.len());
+        self.elements[len..].fill(false);
+        self.elements[..len] &= &other.elements[..len];
+    }
+
+    /// Removes all handles of another set from this set.
+    pub fn difference_with(&mut self, other: &HandleSet<T>) {
+        for index in other.elements.iter_ones() {
+            if let Some(mut bit) = self.elements.get_mut(index) {
+                *bit = false;
+            }
+        }
+    }
+
+    /// Returns whether all handles of this set are in another set.
+    pub fn is_subset(&self, other: &HandleSet<T>) -> bool {
+        self.elements
+            .iter_ones()
+            .all(|index| other.elements.get(index).is_some_and(|bit| *bit))
+    }
+
+    /// Returns whether this set and another set have no handles in common.
+    pub fn is_disjoint(&self, other: &HandleSet<T>) -> bool {
+        self.elements
+            .iter_ones()
+            .all(|index| !other.elements.get(index).is_some_and(|bit| *bit))
+    }
+
     /// Returns an iterator of all of the handles in this set.
     pub fn iter(&self) -> impl Iterator<Item = Handle<T>> + '_ {
         self.elements
@@ -451,6 +598,191 @@ impl<T> Default for HandleSet<T> {
     }
 }
 
+impl<T> Clone for HandleSet<T> {
+    fn clone(&self) -> HandleSet<T> {
+        HandleSet {
+            elements: self.elements.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for HandleSet<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T> PartialEq for HandleSet<T> {
+    fn eq(&self, other: &HandleSet<T>) -> bool {
+        // The bit vectors can have different lengths for the same set of handles.
+        self.elements.iter_ones().eq(other.elements.iter_ones())
+    }
+}
+
+impl<T> Eq for HandleSet<T> {}
+
+impl<T> Extend<Handle<T>> for HandleSet<T> {
+    fn extend<I: IntoIterator<Item = Handle<T>>>(&mut self, iter: I) {
+        for handle in iter {
+            self.add(handle);
+        }
+    }
+}
+
+impl<T> std::iter::FromIterator<Handle<T>> for HandleSet<T> {
+    fn from_iter<I: IntoIterator<Item = Handle<T>>>(iter: I) -> HandleSet<T> {
+        let mut set = HandleSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Handle maps
+
+/// Maps handles to values, stored in a dense vector indexed by the handles.  This is more
+/// efficient than a hash map when many of the handles of an arena are in the map.
+pub struct HandleMap<T, V> {
+    values: Vec<Option<V>>,
+    len: usize,
+    _phantom: PhantomData<T>,
+}
+
+impl<T, V> HandleMap<T, V> {
+    /// Creates a new, empty handle map.
+    pub fn new() -> HandleMap<T, V> {
+        HandleMap::default()
+    }
+
+    /// Removes all entries from this handle map.
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.len = 0;
+    }
+
+    /// Returns the number of entries in this map.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether this map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns whether this map contains a value for a particular handle.
+    pub fn contains_key(&self, handle: Handle<T>) -> bool {
+        self.get(handle).is_some()
+    }
+
+    /// Returns the value for a particular handle, if there is one.
+    pub fn get(&self, handle: Handle<T>) -> Option<&V> {
+        self.values.get(handle.as_usize()).and_then(Option::as_ref)
+    }
+
+    /// Returns a mutable reference to the value for a particular handle, if there is one.
+    pub fn get_mut(&mut self, handle: Handle<T>) -> Option<&mut V> {
+        self.values
+            .get_mut(handle.as_usize())
+            .and_then(Option::as_mut)
+    }
+
+    /// Sets the value for a particular handle, returning the previous value, if any.
+    pub fn insert(&mut self, handle: Handle<T>, value: V) -> Option<V> {
+        let index = handle.as_usize();
+        if self.values.len() <= index {
+            self.values.resize_with(index + 1, || None);
+        }
+        let previous = self.values[index].replace(value);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    /// Returns a mutable reference to the value for a particular handle, inserting the result of
+    /// `f` first if there is no value yet.
+    pub fn get_or_insert_with<F>(&mut self, handle: Handle<T>, f: F) -> &mut V
+    where
+        F: FnOnce() -> V,
+    {
+        if !self.contains_key(handle) {
+            self.insert(handle, f());
+        }
+        self.values[handle.as_usize()].as_mut().unwrap()
+    }
+
+    /// Removes the value for a particular handle, returning it if there was one.
+    pub fn remove(&mut self, handle: Handle<T>) -> Option<V> {
+        let removed = self
+            .values
+            .get_mut(handle.as_usize())
+            .and_then(Option::take);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Returns the set of handles in this map.
+    pub fn keys(&self) -> HandleSet<T> {
+        self.iter().map(|(handle, _)| handle).collect()
+    }
+
+    /// Returns an iterator of all entries in this map, ordered by handle.
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<T>, &V)> + '_ {
+        self.values.iter().enumerate().filter_map(|(index, value)| {
+            value
+                .as_ref()
+                .map(|value| (Handle::from_some(index as u32), value))
+        })
+    }
+}
+
+impl<T, V> Default for HandleMap<T, V> {
+    fn default() -> HandleMap<T, V> {
+        HandleMap {
+            values: Vec::new(),
+            len: 0,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, V: Clone> Clone for HandleMap<T, V> {
+    fn clone(&self) -> HandleMap<T, V> {
+        HandleMap {
+            values: self.values.clone(),
+            len: self.len,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, V: std::fmt::Debug> std::fmt::Debug for HandleMap<T, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T, V> Index<Handle<T>> for HandleMap<T, V> {
+    type Output = V;
+    fn index(&self, handle: Handle<T>) -> &V {
+        self.get(handle).expect("no value for handle")
+    }
+}
+
+impl<T, V> std::iter::FromIterator<(Handle<T>, V)> for HandleMap<T, V> {
+    fn from_iter<I: IntoIterator<Item = (Handle<T>, V)>>(iter: I) -> HandleMap<T, V> {
+        let mut map = HandleMap::new();
+        for (handle, value) in iter {
+            map.insert(handle, value);
+        }
+        map
+    }
+}
+
 //-------------------------------------------------------------------------------------------------
 // Arena-allocated lists
 
@@ -538,6 +870,21 @@ impl<T> List<T> {
     }
 }
 
+impl<T> List<T> {
+    /// Returns the raw value of the handle of this list, which is only meaningful for the arena
+    /// that this list belongs to.
+    pub(crate) fn as_raw(&self) -> u32 {
+        self.cells.as_u32()
+    }
+
+    /// Recreates a list from a raw value returned by [`as_raw`][List::as_raw].
+    pub(crate) fn from_raw(raw: u32) -> Option<List<T>> {
+        NonZeroU32::new(raw).map(|index| List {
+            cells: Handle::new(index),
+        })
+    }
+}
+
 impl<T> List<T> {
     pub fn equals_with<F>(mut self, arena: &ListArena<T>, mut other: List<T>, mut eq: F) -> bool
     where
@@ -627,7 +974,7 @@ pub struct ReversibleList<T> {
 pub struct ReversibleListCell<T> {
     head: T,
     tail: Handle<ReversibleListCell<T>>,
-    reversed: Cell<Option<Handle<ReversibleListCell<T>>>>,
+    reversed: Option<Handle<ReversibleListCell<T>>>,
 }
 
 // An arena that's used to manage `ReversibleList<T>` instances.
@@ -661,7 +1008,7 @@ impl<T> ReversibleList<T> {
             // The empty list is already reversed.
             return true;
         }
-        arena.get(self.cells).reversed.get().is_some()
+        arena.get(self.cells).reversed.is_some()
     }
 
     /// Pushes a new element onto the front of this list.
@@ -701,7 +1048,7 @@ where
             return;
         }
         self.ensure_reversal_available(arena);
-        self.cells = arena.get(self.cells).reversed.get().unwrap();
+        self.cells = arena.get(self.cells).reversed.unwrap();
     }
 
     /// Ensures that the reversal of this list is available.  It can be useful to precalculate this
@@ -713,13 +1060,13 @@ where
             // The empty list is already reversed.
             return;
         }
-        if arena.get(self.cells).reversed.get().is_some() {
+        if arena.get(self.cells).reversed.is_some() {
             return;
         }
 
         // If not, reverse the list and cache the result.
         let new_reversed = ReversibleListCell::reverse(self.cells, arena);
-        arena.get(self.cells).reversed.set(Some(new_reversed));
+        arena.get_mut(self.cells).reversed = Some(new_reversed);
     }
 }
 
@@ -731,7 +1078,7 @@ impl<T> ReversibleList<T> {
             // The empty list is already reversed.
             return Ok(());
         }
-        self.cells = arena.get(self.cells).reversed.get().ok_or(())?;
+        self.cells = arena.get(self.cells).reversed.ok_or(())?;
         Ok(())
     }
 }
@@ -745,7 +1092,7 @@ impl<T> ReversibleListCell<T> {
         ReversibleListCell {
             head,
             tail,
-            reversed: Cell::new(reversed),
+            reversed,
         }
     }
 
@@ -1025,6 +1372,21 @@ where
         list.iter(arena)
     }
 
+    /// Appends the contents of this deque to a buffer, in a forwards direction.  Unlike
+    /// [`iter`][Self::iter], this only needs shared access to the arena, since it reverses the
+    /// copied elements instead of the deque.
+    pub fn copy_into<B>(&self, arena: &DequeArena<T>, buffer: &mut B)
+    where
+        T: Clone,
+        B: Extend<T> + AsMut<[T]>,
+    {
+        let start = buffer.as_mut().len();
+        buffer.extend(self.list.iter(arena).cloned());
+        if self.is_backwards() {
+            buffer.as_mut()[start..].reverse();
+        }
+    }
+
     /// Ensures that both deques are stored in the same direction.  It doesn't matter _which_
     /// direction, as long as they're the same, so do the minimum amount of work to bring this
     /// about.  (In particular, if we've already calculated the reversal of one of the deques,
@@ -1137,3 +1499,214 @@ impl<T> Clone for Deque<T> {
 }
 
 impl<T> Copy for Deque<T> {}
+
+//-------------------------------------------------------------------------------------------------
+// Deque compaction
+
+/// Compacts a [`DequeArena`][], by copying the cells of all deques that are still in use into a
+/// fresh arena.  Cells that are shared between deques are copied only once, and cached reversals
+/// are copied along with the cells that they belong to, so they don't have to be recalculated.
+///
+/// Every deque that is still in use must be passed to [`copy`][DequeCompaction::copy], which
+/// updates it in place to refer to the copied cells.  Once all deques have been copied,
+/// [`finish`][DequeCompaction::finish] replaces the contents of the original arena.  Any deque
+/// that was not copied is invalid afterwards.
+pub struct DequeCompaction<T> {
+    arena: DequeArena<T>,
+    forwarded: HandleMap<ReversibleListCell<T>, Handle<ReversibleListCell<T>>>,
+    pending: Vec<Handle<ReversibleListCell<T>>>,
+}
+
+impl<T> DequeCompaction<T>
+where
+    T: Clone,
+{
+    /// Creates a new compaction, which will copy deques into a fresh arena.
+    pub fn new() -> DequeCompaction<T> {
+        DequeCompaction {
+            arena: Deque::new_arena(),
+            forwarded: HandleMap::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Copies the cells of a deque, which must belong to `from`, into the compacted arena, and
+    /// updates the deque to refer to the copies.  `copy_element` creates the copy of each element
+    /// of the deque, which lets you compact any deques that are stored in the elements themselves.
+    pub fn copy<F>(&mut self, from: &DequeArena<T>, deque: &mut Deque<T>, mut copy_element: F)
+    where
+        F: FnMut(&T) -> T,
+    {
+        deque.list.cells = self.forward(from, deque.list.cells, &mut copy_element);
+        // The copied cells still refer to cells in the original arena, until we forward those as
+        // well.
+        while let Some(cell) = self.pending.pop() {
+            let tail = self.arena.get(cell).tail;
+            let tail = self.forward(from, tail, &mut copy_element);
+            let reversed = self.arena.get(cell).reversed;
+            let reversed = reversed.map(|reversed| self.forward(from, reversed, &mut copy_element));
+            let cell = self.arena.get_mut(cell);
+            cell.tail = tail;
+            cell.reversed = reversed;
+        }
+    }
+
+    fn forward<F>(
+        &mut self,
+        from: &DequeArena<T>,
+        cell: Handle<ReversibleListCell<T>>,
+        copy_element: &mut F,
+    ) -> Handle<ReversibleListCell<T>>
+    where
+        F: FnMut(&T) -> T,
+    {
+        if ReversibleListCell::is_empty_handle(cell) {
+            return cell;
+        }
+        if let Some(forwarded) = self.forwarded.get(cell) {
+            return *forwarded;
+        }
+        let original = from.get(cell);
+        let copy = self.arena.add(ReversibleListCell::new(
+            copy_element(&original.head),
+            original.tail,
+            original.reversed,
+        ));
+        self.forwarded.insert(cell, copy);
+        self.pending.push(copy);
+        copy
+    }
+
+    /// Replaces the contents of `arena` with the copied cells.  The allocation statistics of the
+    /// arena are kept, counting the original cells as cleared.
+    pub fn finish(self, arena: &mut DequeArena<T>) {
+        let mut compacted = self.arena;
+        let live = arena.live_count();
+        compacted.cleared_count = arena.cleared_count + live;
+        compacted.high_water_mark = arena.high_water_mark.max(live);
+        *arena = compacted;
+    }
+}
+
+impl<T> Default for DequeCompaction<T>
+where
+    T: Clone,
+{
+    fn default() -> DequeCompaction<T> {
+        DequeCompaction::new()
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Deque fingerprints
+
+/// Caches structural fingerprints of the deques in a [`DequeArena`][].  Two deques with equal
+/// contents always have the same fingerprint, regardless of the direction that they are stored
+/// in, so deques with different fingerprints can be known to be different without comparing them
+/// element by element.
+///
+/// The fingerprint of a deque is a polynomial hash of its elements.  We store it for each arena
+/// cell, for the list starting at that cell, so that it can be calculated from the cell's tail
+/// when an element is pushed, and is available for the tail when an element is popped.  The cache
+/// is only valid for the arena that it was created for, and must be cleared when that arena is
+/// cleared or compacted.
+pub struct DequeFingerprints<T> {
+    cells: SupplementalArena<ReversibleListCell<T>, CellFingerprint>,
+}
+
+// The fingerprints of the list starting at a cell.  `forwards` weighs the elements by their
+// position counted from the head of the list, `backwards` by their position counted from the end
+// of the list.  A forwards deque uses the former and a backwards deque the latter, so that both
+// agree for the same contents.  An all-zero value marks a cell whose fingerprint has not been
+// calculated yet.
+#[derive(Clone, Copy, Default)]
+struct CellFingerprint {
+    forwards: u64,
+    backwards: u64,
+    // FINGERPRINT_BASE raised to the length of the list
+    power: u64,
+}
+
+const FINGERPRINT_BASE: u64 = 0x100000001b3;
+
+const EMPTY_FINGERPRINT: CellFingerprint = CellFingerprint {
+    forwards: 0,
+    backwards: 0,
+    power: 1,
+};
+
+impl<T> DequeFingerprints<T> {
+    /// Creates a new, empty fingerprint cache.
+    pub fn new() -> DequeFingerprints<T> {
+        DequeFingerprints {
+            cells: SupplementalArena::new(),
+        }
+    }
+
+    /// Removes all cached fingerprints.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    /// Returns the fingerprint of a deque, which must belong to `arena`.  `hash_element` returns
+    /// the hash of a single element of the deque, which must be equal for equal elements.
+    pub fn fingerprint<F>(
+        &mut self,
+        arena: &DequeArena<T>,
+        deque: &Deque<T>,
+        hash_element: F,
+    ) -> u64
+    where
+        F: FnMut(&T) -> u64,
+    {
+        let fingerprint = self.cell_fingerprint(arena, deque.list.cells, hash_element);
+        match deque.direction {
+            DequeDirection::Forwards => fingerprint.forwards,
+            DequeDirection::Backwards => fingerprint.backwards,
+        }
+    }
+
+    fn cell_fingerprint<F>(
+        &mut self,
+        arena: &DequeArena<T>,
+        cell: Handle<ReversibleListCell<T>>,
+        mut hash_element: F,
+    ) -> CellFingerprint
+    where
+        F: FnMut(&T) -> u64,
+    {
+        // Find the longest prefix of the list whose fingerprints are not cached yet, and then
+        // calculate them from the back of that prefix to the front.
+        let mut uncached = Vec::new();
+        let mut current = cell;
+        let mut fingerprint = loop {
+            if ReversibleListCell::is_empty_handle(current) {
+                break EMPTY_FINGERPRINT;
+            }
+            match self.cells.get(current) {
+                Some(fingerprint) if fingerprint.power != 0 => break *fingerprint,
+                _ => {}
+            }
+            uncached.push(current);
+            current = arena.get(current).tail;
+        };
+        while let Some(current) = uncached.pop() {
+            let hash = hash_element(&arena.get(current).head);
+            fingerprint = CellFingerprint {
+                forwards: hash.wrapping_add(fingerprint.forwards.wrapping_mul(FINGERPRINT_BASE)),
+                backwards: hash
+                    .wrapping_mul(fingerprint.power)
+                    .wrapping_add(fingerprint.backwards),
+                power: fingerprint.power.wrapping_mul(FINGERPRINT_BASE),
+            };
+            *self.cells.get_mut_or_default(current) = fingerprint;
+        }
+        fingerprint
+    }
+}
+
+impl<T> Default for DequeFingerprints<T> {
+    fn default() -> DequeFingerprints<T> {
+        DequeFingerprints::new()
+    }
+}
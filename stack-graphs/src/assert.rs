@@ -9,16 +9,25 @@
 
 use itertools::Itertools;
 use lsp_positions::Position;
+#[cfg(feature = "source")]
+use lsp_positions::Span;
 
 use crate::arena::Handle;
 use crate::graph::File;
 use crate::graph::Node;
 use crate::graph::StackGraph;
 use crate::graph::Symbol;
+use crate::partial::remove_shadowed_paths;
 use crate::partial::PartialPath;
 use crate::partial::PartialPaths;
+use crate::partial::TieBreaker;
+#[cfg(feature = "source")]
+use crate::source::Excerpt;
+#[cfg(feature = "source")]
+use crate::source::SourceProvider;
 use crate::stitching::Database;
 use crate::stitching::DatabaseCandidates;
+use crate::stitching::EmptyScopeStack;
 use crate::stitching::ForwardPartialPathStitcher;
 use crate::stitching::StitcherConfig;
 use crate::CancellationError;
@@ -118,12 +127,8 @@ pub enum AssertionError {
     NoReferences {
         source: AssertionSource,
     },
-    IncorrectlyDefined {
-        source: AssertionSource,
-        references: Vec<Handle<Node>>,
-        missing_targets: Vec<AssertionTarget>,
-        unexpected_paths: Vec<PartialPath>,
-    },
+    // Incorrect definitions are boxed to keep results of this type small.
+    IncorrectlyDefined(Box<IncorrectlyDefined>),
     IncorrectDefinitions {
         source: AssertionSource,
         missing_symbols: Vec<Handle<Symbol>>,
@@ -134,15 +139,155 @@ pub enum AssertionError {
         missing_symbols: Vec<Handle<Symbol>>,
         unexpected_symbols: Vec<Handle<Symbol>>,
     },
+    /// Paths of the references jumped to scope with an empty scope stack. Only reported if
+    /// [denied][StitcherConfig::with_deny_empty_scope_stacks] by the stitcher configuration.
+    EmptyScopeStack {
+        source: AssertionSource,
+        references: Vec<Handle<Node>>,
+        paths: Vec<EmptyScopeStack>,
+    },
     Cancelled(CancellationError),
 }
 
+/// References at an assertion position that are not defined by the expected targets.
+#[derive(Clone)]
+pub struct IncorrectlyDefined {
+    pub source: AssertionSource,
+    pub references: Vec<Handle<Node>>,
+    pub missing_targets: Vec<AssertionTarget>,
+    pub unexpected_paths: Vec<PartialPath>,
+}
+
 impl From<CancellationError> for AssertionError {
     fn from(value: CancellationError) -> Self {
         Self::Cancelled(value)
     }
 }
 
+impl std::fmt::Display for AssertionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoReferences { .. } => write!(f, "no references at assertion position"),
+            Self::IncorrectlyDefined(_) => {
+                write!(f, "references are not defined by the expected targets")
+            }
+            Self::IncorrectDefinitions { .. } => {
+                write!(f, "definitions do not have the expected symbols")
+            }
+            Self::IncorrectReferences { .. } => {
+                write!(f, "references do not have the expected symbols")
+            }
+            Self::EmptyScopeStack { .. } => {
+                write!(f, "references jump to scope with an empty scope stack")
+            }
+            Self::Cancelled(_) => write!(f, "assertion was cancelled"),
+        }
+    }
+}
+
+#[cfg(feature = "source")]
+impl AssertionError {
+    /// Returns a displayable version of this error, which includes excerpts of the source code
+    /// at the assertion position and at any unexpected definitions, for the files whose source
+    /// text is supplied by the given provider.
+    pub fn display_with_source<'a>(
+        &'a self,
+        graph: &'a StackGraph,
+        source: &'a dyn SourceProvider,
+    ) -> impl std::fmt::Display + 'a {
+        struct Displayer<'a>(&'a AssertionError, &'a StackGraph, &'a dyn SourceProvider);
+        impl std::fmt::Display for Displayer<'_> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let (error, graph, source) = (self.0, self.1, self.2);
+                let assertion_source = match error {
+                    AssertionError::IncorrectlyDefined(error) => &error.source,
+                    AssertionError::NoReferences { source }
+                    | AssertionError::IncorrectDefinitions { source, .. }
+                    | AssertionError::IncorrectReferences { source, .. }
+                    | AssertionError::EmptyScopeStack { source, .. } => source,
+                    AssertionError::Cancelled(err) => return write!(f, "{}: {}", error, err),
+                };
+                write!(f, "{}: {}", assertion_source.display(graph), error)?;
+                let span = Span {
+                    start: assertion_source.position.clone(),
+                    end: assertion_source.position.clone(),
+                };
+                if let Some(excerpt) =
+                    Excerpt::from_span(source, graph[assertion_source.file].name(), &span)
+                {
+                    write!(f, "\n{}", excerpt)?;
+                }
+                if let AssertionError::IncorrectlyDefined(error) = error {
+                    for path in &error.unexpected_paths {
+                        let node = path.end_node;
+                        write!(f, "\nunexpected definition {}", node.display(graph))?;
+                        if let Some(excerpt) = Excerpt::from_node(graph, source, node) {
+                            write!(f, "\n{}", excerpt)?;
+                        }
+                    }
+                }
+                if let AssertionError::EmptyScopeStack { paths, .. } = error {
+                    for path in paths {
+                        write!(f, "\n{}", path)?;
+                        let node = path
+                            .node
+                            .to_node_id(graph)
+                            .ok()
+                            .and_then(|id| graph.node_for_id(id));
+                        if let Some(excerpt) =
+                            node.and_then(|node| Excerpt::from_node(graph, source, node))
+                        {
+                            write!(f, "\n{}", excerpt)?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+        Displayer(self, graph, source)
+    }
+}
+
+impl std::error::Error for AssertionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Cancelled(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+// Partial paths cannot be debug-formatted without their arenas, so we only show the variant and
+// the source of the assertion.
+impl std::fmt::Debug for AssertionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoReferences { source } => f
+                .debug_struct("NoReferences")
+                .field("source", source)
+                .finish(),
+            Self::IncorrectlyDefined(error) => f
+                .debug_struct("IncorrectlyDefined")
+                .field("source", &error.source)
+                .finish_non_exhaustive(),
+            Self::IncorrectDefinitions { source, .. } => f
+                .debug_struct("IncorrectDefinitions")
+                .field("source", source)
+                .finish_non_exhaustive(),
+            Self::IncorrectReferences { source, .. } => f
+                .debug_struct("IncorrectReferences")
+                .field("source", source)
+                .finish_non_exhaustive(),
+            Self::EmptyScopeStack { source, paths, .. } => f
+                .debug_struct("EmptyScopeStack")
+                .field("source", source)
+                .field("paths", paths)
+                .finish_non_exhaustive(),
+            Self::Cancelled(err) => f.debug_tuple("Cancelled").field(err).finish(),
+        }
+    }
+}
+
 impl Assertion {
     /// Run this assertion against the given graph, using the given paths object for path search.
     pub fn run(
@@ -152,6 +297,27 @@ impl Assertion {
         db: &mut Database,
         stitcher_config: StitcherConfig,
         cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), AssertionError> {
+        self.run_with_tie_breaker(
+            graph,
+            partials,
+            db,
+            stitcher_config,
+            None,
+            cancellation_flag,
+        )
+    }
+
+    /// Run this assertion against the given graph, like [`Self::run`][], using the given tie
+    /// breaker to decide between definitions that shadowing cannot decide between.
+    pub fn run_with_tie_breaker(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        db: &mut Database,
+        stitcher_config: StitcherConfig,
+        tie_breaker: Option<&dyn TieBreaker>,
+        cancellation_flag: &dyn CancellationFlag,
     ) -> Result<(), AssertionError> {
         match self {
             Self::Defined { source, targets } => self.run_defined(
@@ -161,6 +327,7 @@ impl Assertion {
                 source,
                 targets,
                 stitcher_config,
+                tie_breaker,
                 cancellation_flag,
             ),
             Self::Defines { source, symbols } => self.run_defines(graph, source, symbols),
@@ -168,6 +335,7 @@ impl Assertion {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn run_defined(
         &self,
         graph: &StackGraph,
@@ -176,6 +344,7 @@ impl Assertion {
         source: &AssertionSource,
         expected_targets: &Vec<AssertionTarget>,
         stitcher_config: StitcherConfig,
+        tie_breaker: Option<&dyn TieBreaker>,
         cancellation_flag: &dyn CancellationFlag,
     ) -> Result<(), AssertionError> {
         let references = source.iter_references(graph).collect::<Vec<_>>();
@@ -186,9 +355,10 @@ impl Assertion {
         }
 
         let mut actual_paths = Vec::new();
+        let mut empty_scope_stacks = Vec::new();
         for reference in &references {
             let mut reference_paths = Vec::new();
-            ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            let stats = ForwardPartialPathStitcher::find_all_complete_partial_paths(
                 &mut DatabaseCandidates::new(graph, partials, db),
                 vec![*reference],
                 stitcher_config,
@@ -197,14 +367,9 @@ impl Assertion {
                     reference_paths.push(p.clone());
                 },
             )?;
-            for reference_path in &reference_paths {
-                if reference_paths
-                    .iter()
-                    .all(|other| !other.shadows(partials, reference_path))
-                {
-                    actual_paths.push(reference_path.clone());
-                }
-            }
+            remove_shadowed_paths(graph, partials, &mut reference_paths, tie_breaker);
+            actual_paths.extend(reference_paths);
+            empty_scope_stacks.extend(stats.empty_scope_stacks);
         }
 
         let missing_targets = expected_targets
@@ -227,11 +392,20 @@ impl Assertion {
             .cloned()
             .collect::<Vec<_>>();
         if !missing_targets.is_empty() || !unexpected_paths.is_empty() {
-            return Err(AssertionError::IncorrectlyDefined {
+            return Err(AssertionError::IncorrectlyDefined(Box::new(
+                IncorrectlyDefined {
+                    source: source.clone(),
+                    references,
+                    missing_targets,
+                    unexpected_paths,
+                },
+            )));
+        }
+        if stitcher_config.deny_empty_scope_stacks() && !empty_scope_stacks.is_empty() {
+            return Err(AssertionError::EmptyScopeStack {
                 source: source.clone(),
                 references,
-                missing_targets,
-                unexpected_paths,
+                paths: empty_scope_stacks,
             });
         }
 
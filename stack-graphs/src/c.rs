@@ -199,6 +199,8 @@ pub extern "C" fn sg_stack_graph_symbols(graph: *const sg_stack_graph) -> sg_sym
 ///
 /// Each symbol must be a valid UTF-8 string.  If any symbol isn't valid UTF-8, it won't be added
 /// to the stack graph, and the corresponding entry in the output array will be the null handle.
+/// Symbols are not normalized, unless the graph was configured to normalize symbols when they are
+/// added.
 #[no_mangle]
 pub extern "C" fn sg_stack_graph_add_symbols(
     graph: *mut sg_stack_graph,
@@ -0,0 +1,60 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Defines the errors that the public API returns in checked mode, instead of panicking when an
+//! operation violates an internal invariant.
+//!
+//! This module requires the `checked` feature.
+//!
+//! Errors in the input of public operations, such as paths that do not resolve, stored data that
+//! cannot be decoded, or cancellation, are returned as errors by the operations themselves.  Some
+//! operations panic if an internal invariant is violated, for example when a handle from a
+//! different graph is used, or when the variables of partial paths overflow.  With the `checked`
+//! feature, these operations have `try_` variants that return an [`InvariantViolation`][]
+//! instead:
+//!
+//! - [`StackGraph::try_node`][crate::graph::StackGraph::try_node],
+//!   [`StackGraph::try_symbol`][crate::graph::StackGraph::try_symbol], and
+//!   [`StackGraph::try_file`][crate::graph::StackGraph::try_file] look up handles that might not
+//!   belong to the graph.
+//! - [`SymbolStackVariable::try_with_offset`][crate::partial::SymbolStackVariable::try_with_offset],
+//!   [`ScopeStackVariable::try_with_offset`][crate::partial::ScopeStackVariable::try_with_offset],
+//!   and [`PartialPath::try_ensure_no_overlapping_variables`][crate::partial::PartialPath::try_ensure_no_overlapping_variables]
+//!   offset variables that might overflow.
+//! - [`PartialPath::try_from_node`][crate::partial::PartialPath::try_from_node],
+//!   [`PartialPath::try_append`][crate::partial::PartialPath::try_append], and
+//!   [`PartialPath::try_concatenate`][crate::partial::PartialPath::try_concatenate] check both.
+//!   Errors of the operation itself are returned in the `Ok` value.
+//!
+//! Storing paths of a file that do not start in the file or at the root node returns
+//! [`StorageError::InvariantViolation`][crate::storage::StorageError::InvariantViolation].
+//!
+//! ```
+//! # use stack_graphs::graph::StackGraph;
+//! let graph = StackGraph::new();
+//! let mut other = StackGraph::new();
+//! let file = other.get_or_create_file("test.py");
+//! let symbol = other.add_symbol("x");
+//! let id = other.new_node_id(file);
+//! let node = other.add_pop_symbol_node(id, symbol, true).unwrap();
+//! assert!(graph.try_node(node).is_err());
+//! ```
+
+use thiserror::Error;
+
+/// An internal invariant that an operation would violate.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum InvariantViolation {
+    #[error("{0} handle does not belong to the graph")]
+    InvalidHandle(&'static str),
+    #[error("symbol stack variable overflow")]
+    SymbolStackVariableOverflow,
+    #[error("scope stack variable overflow")]
+    ScopeStackVariableOverflow,
+    #[error("path {0} must start in file {1} or at root")]
+    PathOutsideFile(String, String),
+}
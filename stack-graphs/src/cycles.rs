@@ -50,8 +50,9 @@ use crate::stitching::ToAppendable;
 
 /// Helps detect similar paths in the path-finding algorithm.
 pub struct SimilarPathDetector<P> {
-    paths: HashMap<PathKey, SmallVec<[P; 4]>>,
+    paths: HashMap<PathKey, SmallVec<[(u64, P); 4]>>,
     counts: Option<HashMap<PathKey, SmallVec<[usize; 4]>>>,
+    bucket_budget: Option<usize>,
 }
 
 #[doc(hidden)]
@@ -69,6 +70,7 @@ pub struct PathKey {
 pub trait HasPathKey: Clone {
     type Arena;
     fn key(&self) -> PathKey;
+    fn fingerprint(&self, arena: &mut Self::Arena) -> u64;
 }
 
 impl HasPathKey for PartialPath {
@@ -84,6 +86,10 @@ impl HasPathKey for PartialPath {
             scope_stack_postcondition_len: self.scope_stack_postcondition.len(),
         }
     }
+
+    fn fingerprint(&self, arena: &mut PartialPaths) -> u64 {
+        PartialPath::fingerprint(self, arena)
+    }
 }
 
 impl<P> SimilarPathDetector<P>
@@ -95,9 +101,19 @@ where
         SimilarPathDetector {
             paths: HashMap::new(),
             counts: None,
+            bucket_budget: None,
         }
     }
 
+    /// Sets the maximum number of paths that are kept for each combination of start and end node,
+    /// and pre- and postcondition lengths.  Once the bucket of a path is full, the path is only
+    /// processed if it is better than one of the paths already in the bucket, which it then
+    /// replaces.  This trades precision for speed on code where many paths are found that are
+    /// similar but not equal.  By default, buckets are unbounded.
+    pub fn set_bucket_budget(&mut self, bucket_budget: Option<usize>) {
+        self.bucket_budget = bucket_budget;
+    }
+
     /// Set whether to collect statistics for this similar path detector.
     pub fn set_collect_stats(&mut self, collect_stats: bool) {
         if !collect_stats {
@@ -108,8 +124,11 @@ where
     }
 
     /// Add a path, and determine whether we should process this path during the path-finding algorithm.
-    /// If we have seen a path with the same start and end node, and the same pre- and postcondition, then
-    /// we return false. Otherwise, we return true.
+    /// If we have seen a path with the same start and end node, and the same pre- and postcondition, or
+    /// if the bucket of the path is full, then we return true. Otherwise, we return false.
+    ///
+    /// Paths with different fingerprints are never similar, so `cmp` is only called for paths in the
+    /// bucket that have the same fingerprint as the new path.
     pub fn add_path<Cmp>(
         &mut self,
         _graph: &StackGraph,
@@ -121,6 +140,7 @@ where
         Cmp: Fn(&mut P::Arena, &P, &P) -> Option<Ordering>,
     {
         let key = path.key();
+        let fingerprint = path.fingerprint(arena);
 
         // Iterate through the bucket to determine if this paths is better than any already known
         // path. Note that the bucket might be modified during the loop if a path is removed which
@@ -132,12 +152,18 @@ where
             .map(move |cs| cs.entry(key).or_default());
         let mut idx = 0;
         let mut count = 0;
+        let mut replaced = false;
         while idx < possibly_similar_paths.len() {
-            let other_path = &mut possibly_similar_paths[idx];
+            let (other_fingerprint, other_path) = &possibly_similar_paths[idx];
+            if *other_fingerprint != fingerprint {
+                idx += 1;
+                continue;
+            }
             match cmp(arena, path, other_path) {
                 Some(Ordering::Less) => {
                     // the new path is better, remove the old one
                     possibly_similar_paths.remove(idx);
+                    replaced = true;
                     if let Some(possible_similar_counts) = possible_similar_counts.as_mut() {
                         count += possible_similar_counts[idx];
                         possible_similar_counts.remove(idx);
@@ -158,8 +184,17 @@ where
             }
         }
 
+        // this path is new, reject it if the budget for its bucket is exhausted
+        if !replaced
+            && self
+                .bucket_budget
+                .is_some_and(|max| possibly_similar_paths.len() >= max)
+        {
+            return true;
+        }
+
         // this path is either new or better, keep it
-        possibly_similar_paths.push(path.clone());
+        possibly_similar_paths.push((fingerprint, path.clone()));
         if let Some(possible_similar_counts) = possible_similar_counts {
             possible_similar_counts.push(count);
         }
@@ -314,6 +349,17 @@ impl<H> AppendingCycleDetector<H> {
             InternedOrHandle::Database(appendage),
         );
     }
+
+    /// Returns the raw value of the handle of this cycle detector, which is only meaningful for
+    /// the appendables that it was created with.
+    pub(crate) fn as_raw(&self) -> u32 {
+        self.appendages.as_raw()
+    }
+
+    /// Recreates a cycle detector from a raw value returned by [`as_raw`][Self::as_raw].
+    pub(crate) fn from_raw(raw: u32) -> Option<Self> {
+        List::from_raw(raw).map(|appendages| Self { appendages })
+    }
 }
 
 impl<H> AppendingCycleDetector<H>
@@ -350,6 +396,10 @@ where
         // (b) reserving all necessary space before adding elements, and (c) reusing the buffer
         // between loop iterations.
         let mut prefix_appendages = Vec::new();
+        // Repeating a cycle often yields a cyclic path with the same pre- and postconditions as a
+        // shorter one, whose cyclicity is then the same.  We remember the cyclic paths that we
+        // checked, and compare their fingerprints first, to avoid computing their joins again.
+        let mut checked_paths: Vec<(u64, PartialPath, Option<Cyclicity>)> = Vec::new();
         loop {
             // find cycle length
             let mut counting_appendages = remaining_appendages;
@@ -396,7 +446,20 @@ where
                 .unwrap_or_else(|| PartialPath::from_node(graph, partials, end_node));
             cyclic_path.append_to(graph, partials, &mut prefix_path)?;
             if prefix_path.edges.len() > 0 {
-                if let Some(cyclicity) = prefix_path.is_cyclic(graph, partials) {
+                let fingerprint = prefix_path.fingerprint(partials);
+                let checked = checked_paths
+                    .iter()
+                    .find(|(f, p, _)| *f == fingerprint && p.equals(partials, &prefix_path))
+                    .map(|(_, _, cyclicity)| *cyclicity);
+                let cyclicity = match checked {
+                    Some(cyclicity) => cyclicity,
+                    None => {
+                        let cyclicity = prefix_path.is_cyclic(graph, partials);
+                        checked_paths.push((fingerprint, prefix_path.clone(), cyclicity));
+                        cyclicity
+                    }
+                };
+                if let Some(cyclicity) = cyclicity {
                     cycles |= cyclicity;
                 }
             }
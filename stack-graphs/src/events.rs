@@ -0,0 +1,750 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Constructing stack graphs from a stream of events.
+//!
+//! Indexers that are not written in Rust can build a stack graph by sending a stream of
+//! [`GraphEvent`][]s to a Rust process, for example over standard input or a socket.  The process
+//! applies the events to a graph with a [`GraphEventConsumer`][], which validates every event
+//! before applying it, so that a malformed stream results in an error instead of an inconsistent
+//! graph.
+//!
+//! ``` no_run
+//! # use stack_graphs::events::GraphEventConsumer;
+//! # use stack_graphs::graph::StackGraph;
+//! let mut graph = StackGraph::new();
+//! GraphEventConsumer::new(&mut graph).consume(&mut std::io::stdin().lock())?;
+//! # Ok::<(), stack_graphs::events::GraphEventError>(())
+//! ```
+//!
+//! Files are numbered in the order in which they are added, starting at zero, and nodes are
+//! identified by the number of their file and their local ID in that file.  The singleton root and
+//! jump to scope nodes do not belong to a file, and have the local IDs [`ROOT_NODE_ID`][] and
+//! [`JUMP_TO_NODE_ID`][].
+//!
+//! ## Binary encoding
+//!
+//! A stream is a sequence of events, without any header.  All integers are little-endian.
+//! Strings are encoded as their length in bytes as a `u32`, followed by their UTF-8 bytes, and
+//! booleans as a single byte that is `0` or `1`.  Optional values are preceded by a byte that is
+//! `1` if the value is present, and `0` if it is not.
+//!
+//! A node ID is encoded as the `u32` number of its file, or `0xFFFFFFFF` for nodes that do not
+//! belong to a file, followed by the `u32` local ID.  A span is encoded as its start and end
+//! positions, each of which is encoded as eight `u64` values: the line, the UTF-8, UTF-16, and
+//! grapheme offsets of the column, and the start and end UTF-8 offsets of the containing and the
+//! trimmed line.
+//!
+//! Every event starts with a tag byte:
+//!
+//! | Tag | Event | Payload |
+//! |-----|-------|---------|
+//! | 1 | add file | name |
+//! | 2 | add node | node ID, node kind byte, kind-specific payload |
+//! | 3 | add edge | source node ID, sink node ID, precedence as `i32` |
+//! | 4 | set source info | node ID, span, optional syntax type string |
+//! | 5 | set is definition | node ID, is definition |
+//! | 6 | set is reference | node ID, is reference |
+//!
+//! The node kinds and their payloads are:
+//!
+//! | Kind | Node | Payload |
+//! |------|------|---------|
+//! | 1 | drop scopes | |
+//! | 2 | pop scoped symbol | symbol, optional namespace, is definition |
+//! | 3 | pop symbol | symbol, optional namespace, is definition |
+//! | 4 | push scoped symbol | symbol, optional namespace, scope node ID, is reference |
+//! | 5 | push symbol | symbol, optional namespace, is reference |
+//! | 6 | scope | is exported |
+
+use std::io::Read;
+use std::io::Write;
+
+use lsp_positions::Offset;
+use lsp_positions::Position;
+use lsp_positions::Span;
+use thiserror::Error;
+
+use crate::arena::Handle;
+use crate::graph::File;
+use crate::graph::Node;
+use crate::graph::NodeID;
+use crate::graph::StackGraph;
+use crate::graph::JUMP_TO_NODE_ID;
+use crate::graph::ROOT_NODE_ID;
+
+/// An event that adds to a stack graph.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GraphEvent {
+    AddFile {
+        name: String,
+    },
+    AddNode {
+        id: EventNodeID,
+        node: NewNode,
+    },
+    AddEdge {
+        source: EventNodeID,
+        sink: EventNodeID,
+        precedence: i32,
+    },
+    SetSourceInfo {
+        node: EventNodeID,
+        span: Span,
+        syntax_type: Option<String>,
+    },
+    /// Marks a pop node as a definition or not, after it was added.
+    SetIsDefinition {
+        node: EventNodeID,
+        is_definition: bool,
+    },
+    /// Marks a push node as a reference or not, after it was added.
+    SetIsReference {
+        node: EventNodeID,
+        is_reference: bool,
+    },
+}
+
+/// The kind and contents of a node added by a [`GraphEvent::AddNode`][] event.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NewNode {
+    DropScopes,
+    PopScopedSymbol {
+        symbol: String,
+        namespace: Option<String>,
+        is_definition: bool,
+    },
+    PopSymbol {
+        symbol: String,
+        namespace: Option<String>,
+        is_definition: bool,
+    },
+    PushScopedSymbol {
+        symbol: String,
+        namespace: Option<String>,
+        scope: EventNodeID,
+        is_reference: bool,
+    },
+    PushSymbol {
+        symbol: String,
+        namespace: Option<String>,
+        is_reference: bool,
+    },
+    Scope {
+        is_exported: bool,
+    },
+}
+
+/// Identifies a node in an event stream, by the number of its file and its local ID.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct EventNodeID {
+    pub file: Option<u32>,
+    pub local_id: u32,
+}
+
+impl EventNodeID {
+    pub fn new_in_file(file: u32, local_id: u32) -> Self {
+        Self {
+            file: Some(file),
+            local_id,
+        }
+    }
+
+    pub fn root() -> Self {
+        Self {
+            file: None,
+            local_id: ROOT_NODE_ID,
+        }
+    }
+
+    pub fn jump_to() -> Self {
+        Self {
+            file: None,
+            local_id: JUMP_TO_NODE_ID,
+        }
+    }
+}
+
+impl std::fmt::Display for EventNodeID {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.file {
+            Some(file) => write!(f, "#{}({})", file, self.local_id),
+            None => write!(f, "({})", self.local_id),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum GraphEventError {
+    #[error("duplicate file `{0}`")]
+    DuplicateFile(String),
+    #[error("duplicate node {0}")]
+    DuplicateNode(EventNodeID),
+    #[error("unknown file #{0}")]
+    UnknownFile(u32),
+    #[error("unknown node {0}")]
+    UnknownNode(EventNodeID),
+    #[error("invalid node ID {0}")]
+    InvalidNodeID(EventNodeID),
+    #[error("invalid event tag {0}")]
+    InvalidTag(u8),
+    #[error("node {0} cannot be a definition")]
+    NotADefinitionNode(EventNodeID),
+    #[error("node {0} cannot be a reference")]
+    NotAReferenceNode(EventNodeID),
+    #[error("invalid node kind {0}")]
+    InvalidNodeKind(u8),
+    #[error("invalid boolean {0}")]
+    InvalidBool(u8),
+    #[error("invalid string")]
+    InvalidString,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+//-------------------------------------------------------------------------------------------------
+// Binary encoding
+
+const ADD_FILE: u8 = 1;
+const ADD_NODE: u8 = 2;
+const ADD_EDGE: u8 = 3;
+const SET_SOURCE_INFO: u8 = 4;
+const SET_IS_DEFINITION: u8 = 5;
+const SET_IS_REFERENCE: u8 = 6;
+
+const DROP_SCOPES: u8 = 1;
+const POP_SCOPED_SYMBOL: u8 = 2;
+const POP_SYMBOL: u8 = 3;
+const PUSH_SCOPED_SYMBOL: u8 = 4;
+const PUSH_SYMBOL: u8 = 5;
+const SCOPE: u8 = 6;
+
+const NO_FILE: u32 = u32::MAX;
+
+impl GraphEvent {
+    /// Writes the binary encoding of this event.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        match self {
+            Self::AddFile { name } => {
+                w.write_all(&[ADD_FILE])?;
+                write_str(w, name)
+            }
+            Self::AddNode { id, node } => {
+                w.write_all(&[ADD_NODE])?;
+                write_node_id(w, id)?;
+                match node {
+                    NewNode::DropScopes => w.write_all(&[DROP_SCOPES]),
+                    NewNode::PopScopedSymbol {
+                        symbol,
+                        namespace,
+                        is_definition,
+                    } => {
+                        w.write_all(&[POP_SCOPED_SYMBOL])?;
+                        write_str(w, symbol)?;
+                        write_optional_str(w, namespace.as_deref())?;
+                        w.write_all(&[*is_definition as u8])
+                    }
+                    NewNode::PopSymbol {
+                        symbol,
+                        namespace,
+                        is_definition,
+                    } => {
+                        w.write_all(&[POP_SYMBOL])?;
+                        write_str(w, symbol)?;
+                        write_optional_str(w, namespace.as_deref())?;
+                        w.write_all(&[*is_definition as u8])
+                    }
+                    NewNode::PushScopedSymbol {
+                        symbol,
+                        namespace,
+                        scope,
+                        is_reference,
+                    } => {
+                        w.write_all(&[PUSH_SCOPED_SYMBOL])?;
+                        write_str(w, symbol)?;
+                        write_optional_str(w, namespace.as_deref())?;
+                        write_node_id(w, scope)?;
+                        w.write_all(&[*is_reference as u8])
+                    }
+                    NewNode::PushSymbol {
+                        symbol,
+                        namespace,
+                        is_reference,
+                    } => {
+                        w.write_all(&[PUSH_SYMBOL])?;
+                        write_str(w, symbol)?;
+                        write_optional_str(w, namespace.as_deref())?;
+                        w.write_all(&[*is_reference as u8])
+                    }
+                    NewNode::Scope { is_exported } => w.write_all(&[SCOPE, *is_exported as u8]),
+                }
+            }
+            Self::AddEdge {
+                source,
+                sink,
+                precedence,
+            } => {
+                w.write_all(&[ADD_EDGE])?;
+                write_node_id(w, source)?;
+                write_node_id(w, sink)?;
+                w.write_all(&precedence.to_le_bytes())
+            }
+            Self::SetSourceInfo {
+                node,
+                span,
+                syntax_type,
+            } => {
+                w.write_all(&[SET_SOURCE_INFO])?;
+                write_node_id(w, node)?;
+                write_position(w, &span.start)?;
+                write_position(w, &span.end)?;
+                write_optional_str(w, syntax_type.as_deref())
+            }
+            Self::SetIsDefinition {
+                node,
+                is_definition,
+            } => {
+                w.write_all(&[SET_IS_DEFINITION])?;
+                write_node_id(w, node)?;
+                w.write_all(&[*is_definition as u8])
+            }
+            Self::SetIsReference { node, is_reference } => {
+                w.write_all(&[SET_IS_REFERENCE])?;
+                write_node_id(w, node)?;
+                w.write_all(&[*is_reference as u8])
+            }
+        }
+    }
+
+    /// Reads the binary encoding of an event.  Returns `None` if the reader is at its end before
+    /// the start of an event.
+    pub fn read_from<R: Read>(r: &mut R) -> Result<Option<Self>, GraphEventError> {
+        let mut tag = [0u8];
+        if r.read(&mut tag)? == 0 {
+            return Ok(None);
+        }
+        let event = match tag[0] {
+            ADD_FILE => Self::AddFile { name: read_str(r)? },
+            ADD_NODE => {
+                let id = read_node_id(r)?;
+                let node = match read_u8(r)? {
+                    DROP_SCOPES => NewNode::DropScopes,
+                    POP_SCOPED_SYMBOL => NewNode::PopScopedSymbol {
+                        symbol: read_str(r)?,
+                        namespace: read_optional_str(r)?,
+                        is_definition: read_bool(r)?,
+                    },
+                    POP_SYMBOL => NewNode::PopSymbol {
+                        symbol: read_str(r)?,
+                        namespace: read_optional_str(r)?,
+                        is_definition: read_bool(r)?,
+                    },
+                    PUSH_SCOPED_SYMBOL => NewNode::PushScopedSymbol {
+                        symbol: read_str(r)?,
+                        namespace: read_optional_str(r)?,
+                        scope: read_node_id(r)?,
+                        is_reference: read_bool(r)?,
+                    },
+                    PUSH_SYMBOL => NewNode::PushSymbol {
+                        symbol: read_str(r)?,
+                        namespace: read_optional_str(r)?,
+                        is_reference: read_bool(r)?,
+                    },
+                    SCOPE => NewNode::Scope {
+                        is_exported: read_bool(r)?,
+                    },
+                    kind => return Err(GraphEventError::InvalidNodeKind(kind)),
+                };
+                Self::AddNode { id, node }
+            }
+            ADD_EDGE => Self::AddEdge {
+                source: read_node_id(r)?,
+                sink: read_node_id(r)?,
+                precedence: i32::from_le_bytes(read_bytes(r)?),
+            },
+            SET_SOURCE_INFO => Self::SetSourceInfo {
+                node: read_node_id(r)?,
+                span: Span {
+                    start: read_position(r)?,
+                    end: read_position(r)?,
+                },
+                syntax_type: read_optional_str(r)?,
+            },
+            SET_IS_DEFINITION => Self::SetIsDefinition {
+                node: read_node_id(r)?,
+                is_definition: read_bool(r)?,
+            },
+            SET_IS_REFERENCE => Self::SetIsReference {
+                node: read_node_id(r)?,
+                is_reference: read_bool(r)?,
+            },
+            tag => return Err(GraphEventError::InvalidTag(tag)),
+        };
+        Ok(Some(event))
+    }
+}
+
+fn write_str<W: Write>(w: &mut W, value: &str) -> std::io::Result<()> {
+    w.write_all(&(value.len() as u32).to_le_bytes())?;
+    w.write_all(value.as_bytes())
+}
+
+fn write_optional_str<W: Write>(w: &mut W, value: Option<&str>) -> std::io::Result<()> {
+    match value {
+        Some(value) => {
+            w.write_all(&[1])?;
+            write_str(w, value)
+        }
+        None => w.write_all(&[0]),
+    }
+}
+
+fn write_node_id<W: Write>(w: &mut W, value: &EventNodeID) -> std::io::Result<()> {
+    w.write_all(&value.file.unwrap_or(NO_FILE).to_le_bytes())?;
+    w.write_all(&value.local_id.to_le_bytes())
+}
+
+fn write_position<W: Write>(w: &mut W, value: &Position) -> std::io::Result<()> {
+    for value in [
+        value.line,
+        value.column.utf8_offset,
+        value.column.utf16_offset,
+        value.column.grapheme_offset,
+        value.containing_line.start,
+        value.containing_line.end,
+        value.trimmed_line.start,
+        value.trimmed_line.end,
+    ] {
+        w.write_all(&(value as u64).to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_bytes<R: Read, const N: usize>(r: &mut R) -> std::io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    r.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u8<R: Read>(r: &mut R) -> std::io::Result<u8> {
+    Ok(read_bytes::<R, 1>(r)?[0])
+}
+
+fn read_u32<R: Read>(r: &mut R) -> std::io::Result<u32> {
+    Ok(u32::from_le_bytes(read_bytes(r)?))
+}
+
+fn read_usize<R: Read>(r: &mut R) -> std::io::Result<usize> {
+    Ok(u64::from_le_bytes(read_bytes(r)?) as usize)
+}
+
+fn read_bool<R: Read>(r: &mut R) -> Result<bool, GraphEventError> {
+    match read_u8(r)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        value => Err(GraphEventError::InvalidBool(value)),
+    }
+}
+
+fn read_str<R: Read>(r: &mut R) -> Result<String, GraphEventError> {
+    let len = read_u32(r)? as usize;
+    let mut bytes = Vec::new();
+    r.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    String::from_utf8(bytes).map_err(|_| GraphEventError::InvalidString)
+}
+
+fn read_optional_str<R: Read>(r: &mut R) -> Result<Option<String>, GraphEventError> {
+    match read_bool(r)? {
+        true => Ok(Some(read_str(r)?)),
+        false => Ok(None),
+    }
+}
+
+fn read_node_id<R: Read>(r: &mut R) -> std::io::Result<EventNodeID> {
+    let file = read_u32(r)?;
+    Ok(EventNodeID {
+        file: (file != NO_FILE).then_some(file),
+        local_id: read_u32(r)?,
+    })
+}
+
+fn read_position<R: Read>(r: &mut R) -> std::io::Result<Position> {
+    Ok(Position {
+        line: read_usize(r)?,
+        column: Offset {
+            utf8_offset: read_usize(r)?,
+            utf16_offset: read_usize(r)?,
+            grapheme_offset: read_usize(r)?,
+        },
+        containing_line: read_usize(r)?..read_usize(r)?,
+        trimmed_line: read_usize(r)?..read_usize(r)?,
+    })
+}
+
+//-------------------------------------------------------------------------------------------------
+// Consuming events
+
+/// Applies events to a stack graph, after validating them.
+pub struct GraphEventConsumer<'a> {
+    graph: &'a mut StackGraph,
+    files: Vec<Handle<File>>,
+    event_count: usize,
+}
+
+impl<'a> GraphEventConsumer<'a> {
+    /// Creates a consumer that adds to the given graph.  Files that are already in the graph
+    /// cannot be added by events.
+    pub fn new(graph: &'a mut StackGraph) -> Self {
+        Self {
+            graph,
+            files: Vec::new(),
+            event_count: 0,
+        }
+    }
+
+    /// Returns the number of events that were applied.  If applying an event fails, this is the
+    /// index of the failing event in the stream.
+    pub fn event_count(&self) -> usize {
+        self.event_count
+    }
+
+    /// Reads events from the given reader and applies them, until the reader is at its end.
+    /// Returns the number of events that were applied.
+    pub fn consume<R: Read>(&mut self, r: &mut R) -> Result<usize, GraphEventError> {
+        let start = self.event_count;
+        while let Some(event) = GraphEvent::read_from(r)? {
+            self.apply(event)?;
+        }
+        Ok(self.event_count - start)
+    }
+
+    /// Validates an event and applies it to the graph.  The graph is not changed if the event is
+    /// invalid.
+    pub fn apply(&mut self, event: GraphEvent) -> Result<(), GraphEventError> {
+        match event {
+            GraphEvent::AddFile { name } => {
+                let file = self
+                    .graph
+                    .add_file(&name)
+                    .map_err(|_| GraphEventError::DuplicateFile(name))?;
+                self.files.push(file);
+            }
+            GraphEvent::AddNode { id, node } => {
+                let node_id = self.node_id(id)?;
+                if node_id.file().is_none() {
+                    return Err(GraphEventError::InvalidNodeID(id));
+                }
+                if self.graph.node_for_id(node_id).is_some() {
+                    return Err(GraphEventError::DuplicateNode(id));
+                }
+                let handle = match node {
+                    NewNode::DropScopes => self.graph.add_drop_scopes_node(node_id),
+                    NewNode::PopScopedSymbol {
+                        symbol,
+                        namespace,
+                        is_definition,
+                    } => {
+                        let symbol = self
+                            .graph
+                            .add_symbol_in_namespace(&symbol, namespace.as_deref());
+                        self.graph
+                            .add_pop_scoped_symbol_node(node_id, symbol, is_definition)
+                    }
+                    NewNode::PopSymbol {
+                        symbol,
+                        namespace,
+                        is_definition,
+                    } => {
+                        let symbol = self
+                            .graph
+                            .add_symbol_in_namespace(&symbol, namespace.as_deref());
+                        self.graph
+                            .add_pop_symbol_node(node_id, symbol, is_definition)
+                    }
+                    NewNode::PushScopedSymbol {
+                        symbol,
+                        namespace,
+                        scope,
+                        is_reference,
+                    } => {
+                        let scope = self.node_id(scope)?;
+                        let symbol = self
+                            .graph
+                            .add_symbol_in_namespace(&symbol, namespace.as_deref());
+                        self.graph
+                            .add_push_scoped_symbol_node(node_id, symbol, scope, is_reference)
+                    }
+                    NewNode::PushSymbol {
+                        symbol,
+                        namespace,
+                        is_reference,
+                    } => {
+                        let symbol = self
+                            .graph
+                            .add_symbol_in_namespace(&symbol, namespace.as_deref());
+                        self.graph
+                            .add_push_symbol_node(node_id, symbol, is_reference)
+                    }
+                    NewNode::Scope { is_exported } => {
+                        self.graph.add_scope_node(node_id, is_exported)
+                    }
+                };
+                handle.ok_or(GraphEventError::DuplicateNode(id))?;
+            }
+            GraphEvent::AddEdge {
+                source,
+                sink,
+                precedence,
+            } => {
+                let source = self.node(source)?;
+                let sink = self.node(sink)?;
+                self.graph.add_edge(source, sink, precedence);
+            }
+            GraphEvent::SetSourceInfo {
+                node,
+                span,
+                syntax_type,
+            } => {
+                let node = self.node(node)?;
+                let syntax_type = syntax_type.map(|s| self.graph.add_string(&s)).into();
+                let source_info = self.graph.source_info_mut(node);
+                source_info.span = span;
+                source_info.syntax_type = syntax_type;
+            }
+            GraphEvent::SetIsDefinition {
+                node: id,
+                is_definition,
+            } => {
+                let node = self.node(id)?;
+                if !self.graph[node].set_is_definition(is_definition) {
+                    return Err(GraphEventError::NotADefinitionNode(id));
+                }
+            }
+            GraphEvent::SetIsReference {
+                node: id,
+                is_reference,
+            } => {
+                let node = self.node(id)?;
+                if !self.graph[node].set_is_reference(is_reference) {
+                    return Err(GraphEventError::NotAReferenceNode(id));
+                }
+            }
+        }
+        self.event_count += 1;
+        Ok(())
+    }
+
+    /// Resolves an event node ID, which must refer to a file added by this consumer, or to one
+    /// of the singleton nodes.
+    fn node_id(&self, id: EventNodeID) -> Result<NodeID, GraphEventError> {
+        match id.file {
+            Some(file) => {
+                let handle = self
+                    .files
+                    .get(file as usize)
+                    .ok_or(GraphEventError::UnknownFile(file))?;
+                Ok(NodeID::new_in_file(*handle, id.local_id))
+            }
+            None if id.local_id == ROOT_NODE_ID => Ok(NodeID::root()),
+            None if id.local_id == JUMP_TO_NODE_ID => Ok(NodeID::jump_to()),
+            None => Err(GraphEventError::InvalidNodeID(id)),
+        }
+    }
+
+    /// Resolves an event node ID to an existing node.
+    fn node(&self, id: EventNodeID) -> Result<Handle<Node>, GraphEventError> {
+        let node_id = self.node_id(id)?;
+        self.graph
+            .node_for_id(node_id)
+            .ok_or(GraphEventError::UnknownNode(id))
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Producing events
+
+/// Returns the events that construct the given graph.  Applying them to an empty graph results
+/// in a graph with the same files, nodes, edges, spans, and syntax types.
+pub fn graph_events(graph: &StackGraph) -> Vec<GraphEvent> {
+    let mut events = Vec::new();
+    let files = graph.iter_files().collect::<Vec<_>>();
+    for file in &files {
+        events.push(GraphEvent::AddFile {
+            name: graph[*file].name().to_string(),
+        });
+    }
+    let event_node_id = |id: NodeID| EventNodeID {
+        file: id
+            .file()
+            .map(|file| files.iter().position(|f| *f == file).unwrap() as u32),
+        local_id: id.local_id(),
+    };
+    for handle in graph.iter_nodes() {
+        let node = &graph[handle];
+        let symbol = || graph[node.symbol().unwrap()].to_string();
+        let namespace = || {
+            graph
+                .symbol_namespace(node.symbol().unwrap())
+                .map(str::to_string)
+        };
+        let new_node = match node {
+            Node::DropScopes(_) => NewNode::DropScopes,
+            Node::PopScopedSymbol(node) => NewNode::PopScopedSymbol {
+                symbol: symbol(),
+                namespace: namespace(),
+                is_definition: node.is_definition,
+            },
+            Node::PopSymbol(node) => NewNode::PopSymbol {
+                symbol: symbol(),
+                namespace: namespace(),
+                is_definition: node.is_definition,
+            },
+            Node::PushScopedSymbol(node) => NewNode::PushScopedSymbol {
+                symbol: symbol(),
+                namespace: namespace(),
+                scope: event_node_id(node.scope),
+                is_reference: node.is_reference,
+            },
+            Node::PushSymbol(node) => NewNode::PushSymbol {
+                symbol: symbol(),
+                namespace: namespace(),
+                is_reference: node.is_reference,
+            },
+            Node::Scope(node) => NewNode::Scope {
+                is_exported: node.is_exported,
+            },
+            Node::JumpTo(_) | Node::Root(_) => continue,
+        };
+        events.push(GraphEvent::AddNode {
+            id: event_node_id(node.id()),
+            node: new_node,
+        });
+    }
+    for handle in graph.iter_nodes() {
+        if let Some(source_info) = graph.source_info(handle) {
+            events.push(GraphEvent::SetSourceInfo {
+                node: event_node_id(graph[handle].id()),
+                span: source_info.span.clone(),
+                syntax_type: source_info
+                    .syntax_type
+                    .into_option()
+                    .map(|s| graph[s].to_string()),
+            });
+        }
+        for edge in graph.outgoing_edges(handle) {
+            events.push(GraphEvent::AddEdge {
+                source: event_node_id(graph[edge.source].id()),
+                sink: event_node_id(graph[edge.sink].id()),
+                precedence: edge.precedence,
+            });
+        }
+    }
+    events
+}
@@ -0,0 +1,170 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Converts resolution results and database statistics to [Apache Arrow][] record batches, and
+//! writes them to [Parquet][] files, for analysis with data-science tools.
+//!
+//! This module requires the `arrow` feature.
+//!
+//! Resolution results, as [`ReferenceEdge`][]s, use the following schema.  Lines and columns are
+//! zero-based, and columns are expressed in UTF-8 bytes.  The symbol identifier is the
+//! [`SymbolId`][crate::graph::SymbolId] of the symbol, as an unsigned integer.
+//!
+//! | column              | type     |
+//! | ------------------- | -------- |
+//! | `reference_file`    | `Utf8`   |
+//! | `reference_line`    | `UInt64` |
+//! | `reference_column`  | `UInt64` |
+//! | `symbol`            | `Utf8`   |
+//! | `symbol_id`         | `UInt64` |
+//! | `definition_file`   | `Utf8`   |
+//! | `definition_line`   | `UInt64` |
+//! | `definition_column` | `UInt64` |
+//!
+//! Per-file statistics, as [`FileStats`][], use the following schema.  The status is one of
+//! `indexed`, `partial`, `error`, or `missing`, and the status reason is the reason a file is
+//! partial or failed, which is null otherwise.
+//!
+//! | column            | type             |
+//! | ----------------- | ---------------- |
+//! | `path`            | `Utf8`           |
+//! | `tag`             | `Utf8`           |
+//! | `status`          | `Utf8`           |
+//! | `status_reason`   | `Utf8`, nullable |
+//! | `node_count`      | `UInt64`         |
+//! | `edge_count`      | `UInt64`         |
+//! | `node_path_count` | `UInt64`         |
+//! | `root_path_count` | `UInt64`         |
+//! | `stored_size`     | `UInt64`         |
+//!
+//! [Apache Arrow]: https://arrow.apache.org/
+//! [Parquet]: https://parquet.apache.org/
+
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow_array::ArrayRef;
+use arrow_array::RecordBatch;
+use arrow_array::StringArray;
+use arrow_array::UInt64Array;
+use arrow_schema::ArrowError;
+use arrow_schema::DataType;
+use arrow_schema::Field;
+use arrow_schema::Schema;
+use arrow_schema::SchemaRef;
+use parquet::arrow::ArrowWriter;
+use parquet::errors::ParquetError;
+
+use crate::export::edges::ReferenceEdge;
+use crate::storage::FileStats;
+use crate::storage::FileStatus;
+
+/// Returns the schema of record batches of [`ReferenceEdge`][]s.
+pub fn reference_edge_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("reference_file", DataType::Utf8, false),
+        Field::new("reference_line", DataType::UInt64, false),
+        Field::new("reference_column", DataType::UInt64, false),
+        Field::new("symbol", DataType::Utf8, false),
+        Field::new("symbol_id", DataType::UInt64, false),
+        Field::new("definition_file", DataType::Utf8, false),
+        Field::new("definition_line", DataType::UInt64, false),
+        Field::new("definition_column", DataType::UInt64, false),
+    ]))
+}
+
+/// Returns a record batch with a row for every edge, in the [`reference_edge_schema`][].
+pub fn reference_edges_to_record_batch(edges: &[ReferenceEdge]) -> Result<RecordBatch, ArrowError> {
+    let strings = |f: fn(&ReferenceEdge) -> &str| -> ArrayRef {
+        Arc::new(edges.iter().map(f).map(Some).collect::<StringArray>())
+    };
+    let numbers = |f: fn(&ReferenceEdge) -> u64| -> ArrayRef {
+        Arc::new(edges.iter().map(f).map(Some).collect::<UInt64Array>())
+    };
+    RecordBatch::try_new(
+        reference_edge_schema(),
+        vec![
+            strings(|e| &e.reference_file),
+            numbers(|e| e.reference_line as u64),
+            numbers(|e| e.reference_column as u64),
+            strings(|e| &e.symbol),
+            numbers(|e| e.symbol_id.as_u64()),
+            strings(|e| &e.definition_file),
+            numbers(|e| e.definition_line as u64),
+            numbers(|e| e.definition_column as u64),
+        ],
+    )
+}
+
+/// Returns the schema of record batches of [`FileStats`][].
+pub fn file_stats_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("path", DataType::Utf8, false),
+        Field::new("tag", DataType::Utf8, false),
+        Field::new("status", DataType::Utf8, false),
+        Field::new("status_reason", DataType::Utf8, true),
+        Field::new("node_count", DataType::UInt64, false),
+        Field::new("edge_count", DataType::UInt64, false),
+        Field::new("node_path_count", DataType::UInt64, false),
+        Field::new("root_path_count", DataType::UInt64, false),
+        Field::new("stored_size", DataType::UInt64, false),
+    ]))
+}
+
+/// Returns a record batch with a row for every file, in the [`file_stats_schema`][].
+pub fn file_stats_to_record_batch(files: &[FileStats]) -> Result<RecordBatch, ArrowError> {
+    let numbers = |f: fn(&FileStats) -> u64| -> ArrayRef {
+        Arc::new(files.iter().map(f).map(Some).collect::<UInt64Array>())
+    };
+    let (status, status_reason): (Vec<_>, Vec<_>) = files
+        .iter()
+        .map(|file| match &file.status {
+            FileStatus::Missing => ("missing", None),
+            FileStatus::Indexed => ("indexed", None),
+            FileStatus::Partial(reason) => ("partial", Some(reason.as_str())),
+            FileStatus::Error(reason) => ("error", Some(reason.as_str())),
+        })
+        .unzip();
+    RecordBatch::try_new(
+        file_stats_schema(),
+        vec![
+            Arc::new(
+                files
+                    .iter()
+                    .map(|file| Some(file.path.to_string_lossy()))
+                    .collect::<StringArray>(),
+            ),
+            Arc::new(
+                files
+                    .iter()
+                    .map(|file| Some(file.tag.as_str()))
+                    .collect::<StringArray>(),
+            ),
+            Arc::new(StringArray::from(status)),
+            Arc::new(StringArray::from(status_reason)),
+            numbers(|f| f.node_count as u64),
+            numbers(|f| f.edge_count as u64),
+            numbers(|f| f.node_path_count as u64),
+            numbers(|f| f.root_path_count as u64),
+            numbers(|f| f.stored_size),
+        ],
+    )
+}
+
+/// Writes record batches with the given schema to a Parquet file.
+pub fn write_parquet<W, I>(writer: W, schema: SchemaRef, batches: I) -> Result<(), ParquetError>
+where
+    W: Write + Send,
+    I: IntoIterator<Item = RecordBatch>,
+{
+    let mut writer = ArrowWriter::try_new(writer, schema, None)?;
+    for batch in batches {
+        writer.write(&batch)?;
+    }
+    writer.close()?;
+    Ok(())
+}
@@ -0,0 +1,459 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Exports the definitions that all references in a database resolve to, as an edge list for
+//! offline analysis.
+//!
+//! An [`EdgeExporter`][] resolves the references of every indexed file in a database, using a
+//! [`SQLiteReaderPool`][] to resolve several files at the same time, and writes one
+//! [`ReferenceEdge`][] per pair of reference and definition.  Files are processed in order of
+//! their paths, and their edges are written in that order, so the output does not depend on the
+//! number of threads.
+//!
+//! Exports of large databases can be resumed.  While exporting, the exporter keeps a checkpoint
+//! file next to the output, which records the files whose edges have been written.  If an export
+//! is interrupted, running it again with the same output continues after the last completed file.
+//! The checkpoint file is removed when the export completes.  Parquet exports, which require the
+//! `arrow` feature, cannot be resumed, and start over if they are interrupted.
+//!
+//! Lines and columns are zero-based, and columns are expressed in UTF-8 bytes.
+
+use std::fs::OpenOptions;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use crate::arena::Handle;
+use crate::graph::Node;
+use crate::graph::StackGraph;
+use crate::graph::SymbolId;
+use crate::stitching::ForwardPartialPathStitcher;
+use crate::stitching::StitcherConfig;
+use crate::storage::FileStatus;
+use crate::storage::SQLiteReader;
+use crate::storage::SQLiteReaderPool;
+use crate::storage::StorageError;
+use crate::CancellationFlag;
+use crate::StackGraphError;
+
+use super::has_span;
+
+/// The number of files that every thread resolves per batch by default.  The edges of a batch
+/// are written, and the checkpoint updated, when all its files are resolved.
+const DEFAULT_FILES_PER_THREAD: usize = 8;
+
+/// The resolution of a reference to a definition.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ReferenceEdge {
+    pub reference_file: String,
+    pub reference_line: usize,
+    pub reference_column: usize,
+    /// The symbol of the reference.
+    pub symbol: String,
+    /// The stable identifier of the reference's symbol.
+    pub symbol_id: SymbolId,
+    pub definition_file: String,
+    pub definition_line: usize,
+    pub definition_column: usize,
+}
+
+impl ReferenceEdge {
+    /// Returns the edge for a complete path from a reference to a definition, or `None` if either
+    /// of them is not in a file or has no source span.
+    fn from_nodes(
+        graph: &StackGraph,
+        reference: Handle<Node>,
+        definition: Handle<Node>,
+    ) -> Option<Self> {
+        if !has_span(graph, reference) || !has_span(graph, definition) {
+            return None;
+        }
+        let symbol = graph[reference].symbol()?;
+        let reference_span = &graph.source_info(reference)?.span;
+        let definition_span = &graph.source_info(definition)?.span;
+        Some(Self {
+            reference_file: graph[graph[reference].file()?].name().to_string(),
+            reference_line: reference_span.start.line,
+            reference_column: reference_span.start.column.utf8_offset,
+            symbol: graph[symbol].to_string(),
+            symbol_id: graph.symbol_id(symbol),
+            definition_file: graph[graph[definition].file()?].name().to_string(),
+            definition_line: definition_span.start.line,
+            definition_column: definition_span.start.column.utf8_offset,
+        })
+    }
+}
+
+/// The output format of an edge export.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeFormat {
+    /// Comma-separated values, with a header row.
+    Csv,
+    /// One JSON object per line.
+    Jsonl,
+    /// A Parquet file, in the [schema][crate::export::arrow] of reference edges.
+    #[cfg(feature = "arrow")]
+    Parquet,
+}
+
+impl EdgeFormat {
+    const COLUMNS: [&'static str; 8] = [
+        "reference_file",
+        "reference_line",
+        "reference_column",
+        "symbol",
+        "symbol_id",
+        "definition_file",
+        "definition_line",
+        "definition_column",
+    ];
+
+    /// Returns the bytes that start an output file in this format.
+    fn header(&self) -> Vec<u8> {
+        match self {
+            Self::Csv => format!("{}\n", Self::COLUMNS.join(",")).into_bytes(),
+            Self::Jsonl => Vec::new(),
+            #[cfg(feature = "arrow")]
+            Self::Parquet => Vec::new(),
+        }
+    }
+
+    /// Appends the encoding of an edge to the output.
+    fn write_edge(&self, edge: &ReferenceEdge, output: &mut Vec<u8>) {
+        match self {
+            Self::Csv => {
+                let line = [
+                    csv_field(&edge.reference_file),
+                    edge.reference_line.to_string(),
+                    edge.reference_column.to_string(),
+                    csv_field(&edge.symbol),
+                    edge.symbol_id.to_string(),
+                    csv_field(&edge.definition_file),
+                    edge.definition_line.to_string(),
+                    edge.definition_column.to_string(),
+                ]
+                .join(",");
+                output.extend_from_slice(line.as_bytes());
+            }
+            Self::Jsonl => {
+                let value = serde_json::json!({
+                    "reference_file": edge.reference_file,
+                    "reference_line": edge.reference_line,
+                    "reference_column": edge.reference_column,
+                    "symbol": edge.symbol,
+                    "symbol_id": edge.symbol_id.to_string(),
+                    "definition_file": edge.definition_file,
+                    "definition_line": edge.definition_line,
+                    "definition_column": edge.definition_column,
+                });
+                output.extend_from_slice(value.to_string().as_bytes());
+            }
+            // Parquet output is encoded by an ArrowWriter in export_parquet
+            #[cfg(feature = "arrow")]
+            Self::Parquet => return,
+        }
+        output.push(b'\n');
+    }
+}
+
+/// Quotes a CSV field if it contains separators, quotes, or line breaks.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Statistics about an edge export.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EdgeExportStats {
+    /// The number of files whose references were resolved.
+    pub files: usize,
+    /// The number of files that were skipped, because their edges were written by an earlier,
+    /// interrupted export.
+    pub resumed_files: usize,
+    /// The number of edges that were written.
+    pub edges: usize,
+}
+
+/// Exports the resolutions of all references in a database.  See the [module
+/// documentation][self] for details.
+#[derive(Clone, Debug)]
+pub struct EdgeExporter {
+    format: EdgeFormat,
+    stitcher_config: StitcherConfig,
+    thread_count: usize,
+    batch_size: Option<usize>,
+}
+
+impl EdgeExporter {
+    /// Creates an exporter for the given format, which resolves references on a single thread.
+    pub fn new(format: EdgeFormat) -> Self {
+        Self {
+            format,
+            // always detect similar paths, we don't know the language configurations for the data in the database
+            stitcher_config: StitcherConfig::default().with_detect_similar_paths(true),
+            thread_count: 1,
+            batch_size: None,
+        }
+    }
+
+    /// Sets the configuration of the path stitcher that resolves references.
+    pub fn with_stitcher_config(mut self, stitcher_config: StitcherConfig) -> Self {
+        self.stitcher_config = stitcher_config;
+        self
+    }
+
+    /// Sets the number of threads that resolve references.
+    pub fn with_thread_count(mut self, thread_count: usize) -> Self {
+        self.thread_count = thread_count.max(1);
+        self
+    }
+
+    /// Sets the number of files that are resolved before their edges are written and the
+    /// checkpoint is updated.  Defaults to eight files per thread.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = Some(batch_size.max(1));
+        self
+    }
+
+    /// Returns the path of the checkpoint file that is kept while exporting to the given output.
+    pub fn checkpoint_path(output: &Path) -> PathBuf {
+        let mut path = output.as_os_str().to_os_string();
+        path.push(".checkpoint");
+        PathBuf::from(path)
+    }
+
+    /// Resolves all references in the database, and writes the edges to the given output.  If a
+    /// checkpoint of an earlier export to the same output exists, the export is resumed.
+    pub fn export(
+        &self,
+        pool: &SQLiteReaderPool,
+        output: &Path,
+        cancellation_flag: &(dyn CancellationFlag + Sync),
+    ) -> Result<EdgeExportStats, StackGraphError> {
+        #[cfg(feature = "arrow")]
+        if self.format == EdgeFormat::Parquet {
+            return self.export_parquet(pool, output, cancellation_flag);
+        }
+
+        let mut stats = EdgeExportStats::default();
+        let checkpoint_path = Self::checkpoint_path(output);
+        let header = self.format.header();
+
+        // Restore or start the output and the checkpoint.  The checkpoint lists the completed
+        // files, each with the length of the output after its edges.
+        let mut completed = std::collections::HashSet::new();
+        let mut output_file;
+        if checkpoint_path.exists() {
+            let mut length = header.len() as u64;
+            for line in BufReader::new(std::fs::File::open(&checkpoint_path)?).lines() {
+                let line = line?;
+                let (offset, file) = line
+                    .split_once('\t')
+                    .and_then(|(offset, file)| Some((offset.parse::<u64>().ok()?, file)))
+                    .ok_or_else(|| invalid_checkpoint(&checkpoint_path))?;
+                length = offset;
+                completed.insert(file.to_string());
+            }
+            output_file = OpenOptions::new().write(true).open(output)?;
+            if output_file.metadata()?.len() < length {
+                return Err(invalid_checkpoint(&checkpoint_path).into());
+            }
+            // drop the edges of files that were written after the last checkpoint
+            output_file.set_len(length)?;
+            output_file.seek(SeekFrom::End(0))?;
+        } else {
+            output_file = std::fs::File::create(output)?;
+            output_file.write_all(&header)?;
+            output_file.sync_data()?;
+            std::fs::File::create(&checkpoint_path)?;
+        }
+        let mut checkpoint = OpenOptions::new().append(true).open(&checkpoint_path)?;
+        let mut length = output_file.stream_position()?;
+
+        let files = self.files_to_export(pool)?;
+        let (resumed, files): (Vec<_>, Vec<_>) =
+            files.into_iter().partition(|file| completed.contains(file));
+        stats.resumed_files = resumed.len();
+
+        for batch in files.chunks(self.batch_size()) {
+            for (file, edges) in
+                batch
+                    .iter()
+                    .zip(self.resolve_batch(pool, batch, cancellation_flag)?)
+            {
+                let mut bytes = Vec::new();
+                for edge in &edges {
+                    self.format.write_edge(edge, &mut bytes);
+                }
+                output_file.write_all(&bytes)?;
+                output_file.sync_data()?;
+                length += bytes.len() as u64;
+                writeln!(checkpoint, "{}\t{}", length, file)?;
+                stats.files += 1;
+                stats.edges += edges.len();
+            }
+            checkpoint.sync_data()?;
+        }
+
+        drop(checkpoint);
+        std::fs::remove_file(&checkpoint_path)?;
+        Ok(stats)
+    }
+
+    /// Resolves all references in the database, and writes the edges to a Parquet file, with a
+    /// row group per batch of files.
+    #[cfg(feature = "arrow")]
+    fn export_parquet(
+        &self,
+        pool: &SQLiteReaderPool,
+        output: &Path,
+        cancellation_flag: &(dyn CancellationFlag + Sync),
+    ) -> Result<EdgeExportStats, StackGraphError> {
+        let mut stats = EdgeExportStats::default();
+        let files = self.files_to_export(pool)?;
+        let mut writer = parquet::arrow::ArrowWriter::try_new(
+            std::fs::File::create(output)?,
+            super::arrow::reference_edge_schema(),
+            None,
+        )?;
+        for batch in files.chunks(self.batch_size()) {
+            let edges = self
+                .resolve_batch(pool, batch, cancellation_flag)?
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>();
+            let record_batch = super::arrow::reference_edges_to_record_batch(&edges)?;
+            writer.write(&record_batch)?;
+            writer.flush()?;
+            stats.files += batch.len();
+            stats.edges += edges.len();
+        }
+        writer.close()?;
+        Ok(stats)
+    }
+
+    /// Returns the files in the database whose references are exported, ordered by path.  Files
+    /// that failed to index are skipped.
+    fn files_to_export(&self, pool: &SQLiteReaderPool) -> Result<Vec<String>, StorageError> {
+        let mut files = Vec::new();
+        let mut reader = pool.reader()?;
+        let mut entries = reader.list_all()?;
+        for entry in entries.try_iter()? {
+            let entry = entry?;
+            if !matches!(entry.status, FileStatus::Error(_)) {
+                files.push(entry.path.to_string_lossy().to_string());
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    fn batch_size(&self) -> usize {
+        self.batch_size
+            .unwrap_or(self.thread_count * DEFAULT_FILES_PER_THREAD)
+    }
+
+    /// Resolves the references in the given files, using several threads.  The edges are
+    /// returned in the order of the files.
+    fn resolve_batch(
+        &self,
+        pool: &SQLiteReaderPool,
+        files: &[String],
+        cancellation_flag: &(dyn CancellationFlag + Sync),
+    ) -> Result<Vec<Vec<ReferenceEdge>>, StorageError> {
+        let next_file = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let work = || -> Result<Vec<(usize, Vec<ReferenceEdge>)>, StorageError> {
+            let mut results = Vec::new();
+            let mut reader = pool.reader()?;
+            while !failed.load(Ordering::Relaxed) {
+                let index = next_file.fetch_add(1, Ordering::Relaxed);
+                let file = match files.get(index) {
+                    Some(file) => file,
+                    None => break,
+                };
+                let edges =
+                    edges_for_file(&mut reader, file, self.stitcher_config, cancellation_flag)
+                        .inspect_err(|_| failed.store(true, Ordering::Relaxed))?;
+                results.push((index, edges));
+            }
+            Ok(results)
+        };
+
+        let thread_results = std::thread::scope(|scope| {
+            let workers = (0..self.thread_count.min(files.len()).max(1))
+                .map(|_| scope.spawn(work))
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .map(|worker| match worker.join() {
+                    Ok(result) => result,
+                    Err(panic) => std::panic::resume_unwind(panic),
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let mut results = Vec::with_capacity(files.len());
+        for thread_result in thread_results {
+            results.extend(thread_result?);
+        }
+        results.sort_by_key(|(index, _)| *index);
+        Ok(results.into_iter().map(|(_, edges)| edges).collect())
+    }
+}
+
+/// Resolves the references in a file, and returns the resulting edges, sorted and without
+/// duplicates.  The reader loads the graph of the file, and of all files that its references
+/// resolve to.
+pub fn edges_for_file(
+    reader: &mut SQLiteReader,
+    file: &str,
+    config: StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<ReferenceEdge>, StorageError> {
+    cancellation_flag.check("resolving references of file")?;
+    let file = reader.load_graph_for_file(file)?;
+    let (graph, _, _) = reader.get();
+    let references = graph
+        .nodes_for_file(file)
+        .filter(|node| graph[*node].is_reference())
+        .collect::<Vec<_>>();
+    let mut edges = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        reader,
+        references,
+        config,
+        cancellation_flag,
+        |graph, _, path| {
+            edges.extend(ReferenceEdge::from_nodes(
+                graph,
+                path.start_node,
+                path.end_node,
+            ))
+        },
+    )?;
+    edges.sort();
+    edges.dedup();
+    Ok(edges)
+}
+
+fn invalid_checkpoint(path: &Path) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("invalid export checkpoint {}", path.display()),
+    )
+}
@@ -0,0 +1,262 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Writes resolved references as an [LSIF][] dump, in the line-delimited JSON format.
+//!
+//! Every definition gets a result set with a definition result.  References that resolve to a
+//! single definition share the result set of that definition, and all references of a definition
+//! are listed in a reference result.  References that resolve to several definitions get a result
+//! set of their own, whose definition result lists all of them.
+//!
+//! The result set of a definition also has a moniker in the `stack-graphs` scheme, whose
+//! identifier is the [stable identifier][crate::graph::SymbolId] of the definition's symbol, so
+//! that definitions can be joined against other indexes and symbol tables.
+//!
+//! [LSIF]: https://microsoft.github.io/language-server-protocol/specifications/lsif/0.4.0/specification/
+
+use serde_json::json;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+use crate::arena::Handle;
+use crate::graph::File;
+use crate::graph::Node;
+use crate::graph::StackGraph;
+
+use super::exported_definitions;
+use super::exported_references;
+use super::file_uri;
+use super::Resolutions;
+use super::TOOL_NAME;
+use super::TOOL_VERSION;
+
+/// The moniker scheme of the symbol identifiers of definitions.
+pub const MONIKER_SCHEME: &str = "stack-graphs";
+
+/// The version of the LSIF format that is written.
+pub const LSIF_VERSION: &str = "0.4.3";
+
+/// Writes an LSIF dump of the definitions and resolved references in the given files to the
+/// writer.  Definitions in other files that references resolve to are included as well.
+pub fn write_lsif<W: Write>(
+    graph: &StackGraph,
+    files: &[Handle<File>],
+    resolutions: &Resolutions,
+    project_root: &Path,
+    writer: W,
+) -> std::io::Result<()> {
+    let mut dump = Dump::new(graph, project_root, writer);
+    dump.vertex(
+        "metaData",
+        json!({
+            "version": LSIF_VERSION,
+            "projectRoot": file_uri(project_root),
+            "positionEncoding": "utf-16",
+            "toolInfo": { "name": TOOL_NAME, "version": TOOL_VERSION },
+        }),
+    )?;
+
+    let mut result_sets = HashMap::new();
+    let definitions = exported_definitions(graph, files, resolutions);
+    for definition in &definitions {
+        let range = dump.range(*definition)?;
+        let result_set = dump.vertex("resultSet", json!({}))?;
+        dump.edge("next", range, json!({ "inV": result_set }))?;
+        dump.definition_result(result_set, &[*definition])?;
+        if let Some(symbol) = graph[*definition].symbol() {
+            let moniker = dump.vertex(
+                "moniker",
+                json!({
+                    "scheme": MONIKER_SCHEME,
+                    "identifier": graph.symbol_id(symbol).to_string(),
+                    "kind": "export",
+                }),
+            )?;
+            dump.edge("moniker", result_set, json!({ "inV": moniker }))?;
+        }
+        result_sets.insert(*definition, result_set);
+    }
+
+    let mut references_of = HashMap::<Handle<Node>, Vec<Handle<Node>>>::new();
+    for reference in exported_references(graph, files, resolutions) {
+        let range = dump.range(reference)?;
+        let targets = resolutions
+            .definitions(reference)
+            .iter()
+            .filter(|d| result_sets.contains_key(*d))
+            .copied()
+            .collect::<Vec<_>>();
+        for target in &targets {
+            references_of.entry(*target).or_default().push(reference);
+        }
+        if let [target] = targets.as_slice() {
+            dump.edge("next", range, json!({ "inV": result_sets[target] }))?;
+        } else if !targets.is_empty() {
+            let result_set = dump.vertex("resultSet", json!({}))?;
+            dump.edge("next", range, json!({ "inV": result_set }))?;
+            dump.definition_result(result_set, &targets)?;
+        }
+    }
+
+    for definition in &definitions {
+        let references = match references_of.get(definition) {
+            Some(references) => references,
+            None => continue,
+        };
+        let reference_result = dump.vertex("referenceResult", json!({}))?;
+        dump.edge(
+            "textDocument/references",
+            result_sets[definition],
+            json!({ "inV": reference_result }),
+        )?;
+        dump.items(reference_result, &[*definition], Some("definitions"))?;
+        dump.items(reference_result, references, Some("references"))?;
+    }
+
+    dump.finish()
+}
+
+/// Assigns IDs to the elements of an LSIF dump, and keeps track of the documents and ranges that
+/// have been written.
+struct Dump<'a, W> {
+    graph: &'a StackGraph,
+    project_root: &'a Path,
+    writer: W,
+    next_id: usize,
+    documents: HashMap<Handle<File>, (usize, Vec<usize>)>,
+    ranges: HashMap<Handle<Node>, usize>,
+}
+
+impl<'a, W: Write> Dump<'a, W> {
+    fn new(graph: &'a StackGraph, project_root: &'a Path, writer: W) -> Self {
+        Self {
+            graph,
+            project_root,
+            writer,
+            next_id: 1,
+            documents: HashMap::new(),
+            ranges: HashMap::new(),
+        }
+    }
+
+    fn element(&mut self, type_: &str, label: &str, mut fields: Value) -> std::io::Result<usize> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let object = fields.as_object_mut().expect("fields must be an object");
+        object.insert("id".to_string(), id.into());
+        object.insert("type".to_string(), type_.into());
+        object.insert("label".to_string(), label.into());
+        serde_json::to_writer(&mut self.writer, &fields)?;
+        self.writer.write_all(b"\n")?;
+        Ok(id)
+    }
+
+    fn vertex(&mut self, label: &str, fields: Value) -> std::io::Result<usize> {
+        self.element("vertex", label, fields)
+    }
+
+    fn edge(&mut self, label: &str, out_v: usize, mut fields: Value) -> std::io::Result<usize> {
+        fields
+            .as_object_mut()
+            .expect("fields must be an object")
+            .insert("outV".to_string(), out_v.into());
+        self.element("edge", label, fields)
+    }
+
+    /// Returns the document vertex for a file, writing it first if necessary.
+    fn document(&mut self, file: Handle<File>) -> std::io::Result<usize> {
+        if let Some((document, _)) = self.documents.get(&file) {
+            return Ok(*document);
+        }
+        let uri = file_uri(&self.project_root.join(self.graph[file].name()));
+        let document = self.vertex("document", json!({ "uri": uri, "languageId": "" }))?;
+        self.documents.insert(file, (document, Vec::new()));
+        Ok(document)
+    }
+
+    /// Returns the range vertex for a node, writing it first if necessary.  The node must be
+    /// in a file and have a source span.
+    fn range(&mut self, node: Handle<Node>) -> std::io::Result<usize> {
+        if let Some(range) = self.ranges.get(&node) {
+            return Ok(*range);
+        }
+        let file = self.graph[node]
+            .file()
+            .expect("exported node must be in a file");
+        self.document(file)?;
+        let span = &self
+            .graph
+            .source_info(node)
+            .expect("exported node must have source info")
+            .span;
+        let range = self.vertex(
+            "range",
+            json!({
+                "start": { "line": span.start.line, "character": span.start.column.utf16_offset },
+                "end": { "line": span.end.line, "character": span.end.column.utf16_offset },
+            }),
+        )?;
+        self.ranges.insert(node, range);
+        self.documents.get_mut(&file).unwrap().1.push(range);
+        Ok(range)
+    }
+
+    /// Writes a definition result for the result set, listing the given definitions.
+    fn definition_result(
+        &mut self,
+        result_set: usize,
+        definitions: &[Handle<Node>],
+    ) -> std::io::Result<()> {
+        let definition_result = self.vertex("definitionResult", json!({}))?;
+        self.edge(
+            "textDocument/definition",
+            result_set,
+            json!({ "inV": definition_result }),
+        )?;
+        self.items(definition_result, definitions, None)
+    }
+
+    /// Writes item edges from the result to the ranges of the given nodes, one for each document.
+    fn items(
+        &mut self,
+        result: usize,
+        nodes: &[Handle<Node>],
+        property: Option<&str>,
+    ) -> std::io::Result<()> {
+        let mut by_document = Vec::<(usize, Vec<usize>)>::new();
+        for node in nodes {
+            let range = self.range(*node)?;
+            let document = self.document(self.graph[*node].file().unwrap())?;
+            match by_document.iter_mut().find(|(d, _)| *d == document) {
+                Some((_, ranges)) => ranges.push(range),
+                None => by_document.push((document, vec![range])),
+            }
+        }
+        for (document, ranges) in by_document {
+            let mut fields = json!({ "inVs": ranges, "document": document });
+            if let Some(property) = property {
+                fields["property"] = property.into();
+            }
+            self.edge("item", result, fields)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the edges from every document to the ranges it contains.
+    fn finish(mut self) -> std::io::Result<()> {
+        let mut documents = std::mem::take(&mut self.documents)
+            .into_values()
+            .collect::<Vec<_>>();
+        documents.sort();
+        for (document, ranges) in documents {
+            self.edge("contains", document, json!({ "inVs": ranges }))?;
+        }
+        self.writer.flush()
+    }
+}
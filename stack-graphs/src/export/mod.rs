@@ -0,0 +1,200 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Exports resolved references to the code navigation interchange formats [LSIF][] and [SCIP][].
+//!
+//! Both formats describe the documents of a project, the source ranges of definitions and
+//! references in them, and which definitions each reference resolves to.  The resolutions are
+//! collected in a [`Resolutions`][] value, typically from the complete paths found by a
+//! [`ForwardPartialPathStitcher`][crate::stitching::ForwardPartialPathStitcher]:
+//!
+//! ```
+//! # use stack_graphs::export::Resolutions;
+//! # use stack_graphs::graph::StackGraph;
+//! # use stack_graphs::partial::PartialPaths;
+//! # use stack_graphs::stitching::Database;
+//! # use stack_graphs::stitching::ForwardPartialPathStitcher;
+//! # use stack_graphs::stitching::GraphEdgeCandidates;
+//! # use stack_graphs::stitching::StitcherConfig;
+//! # use stack_graphs::NoCancellation;
+//! # let graph = StackGraph::new();
+//! # let mut partials = PartialPaths::new();
+//! # let references = Vec::new();
+//! let mut resolutions = Resolutions::new();
+//! ForwardPartialPathStitcher::find_all_complete_partial_paths(
+//!     &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+//!     references,
+//!     StitcherConfig::default(),
+//!     &NoCancellation,
+//!     |_, _, path| resolutions.add_path(path),
+//! )?;
+//! # Ok::<(), stack_graphs::CancellationError>(())
+//! ```
+//!
+//! The [`edges`][] module, which requires the `storage` feature, exports the resolutions of all
+//! references in a database as a flat edge list instead, for offline analysis.  The [`arrow`][]
+//! module, which requires the `arrow` feature, converts such results and database statistics to
+//! Arrow record batches and Parquet files.
+//!
+//! Only nodes with a non-empty source span are exported.  File names in the stack graph are
+//! interpreted as file system paths, relative to the project root if they are not absolute.
+//! Columns are expressed in UTF-16 code units.
+//!
+//! [LSIF]: https://microsoft.github.io/language-server-protocol/specifications/lsif/0.4.0/specification/
+//! [SCIP]: https://github.com/sourcegraph/scip
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::arena::Handle;
+use crate::graph::File;
+use crate::graph::Node;
+use crate::graph::StackGraph;
+use crate::partial::PartialPath;
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "storage")]
+pub mod edges;
+pub mod lsif;
+pub mod scip;
+
+static TOOL_NAME: &str = env!("CARGO_PKG_NAME");
+static TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The definitions that references resolve to.
+#[derive(Clone, Debug, Default)]
+pub struct Resolutions {
+    references: Vec<Handle<Node>>,
+    definitions: HashMap<Handle<Node>, Vec<Handle<Node>>>,
+}
+
+impl Resolutions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that the reference resolves to the definition.  Duplicates are ignored.
+    pub fn add(&mut self, reference: Handle<Node>, definition: Handle<Node>) {
+        let references = &mut self.references;
+        let definitions = self.definitions.entry(reference).or_insert_with(|| {
+            references.push(reference);
+            Vec::new()
+        });
+        if !definitions.contains(&definition) {
+            definitions.push(definition);
+        }
+    }
+
+    /// Records the resolution of a complete path, from its start to its end node.
+    pub fn add_path(&mut self, path: &PartialPath) {
+        self.add(path.start_node, path.end_node);
+    }
+
+    /// Returns the definitions the given reference resolves to.
+    pub fn definitions(&self, reference: Handle<Node>) -> &[Handle<Node>] {
+        self.definitions
+            .get(&reference)
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// Returns all resolved references, in the order they were first added.
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<Node>, &[Handle<Node>])> + '_ {
+        self.references
+            .iter()
+            .map(move |reference| (*reference, self.definitions(*reference)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.references.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.references.len()
+    }
+}
+
+/// Returns the definitions to export: all definitions in the given files, followed by the
+/// definitions in other files that references resolve to.  Nodes without a source span are left
+/// out.
+fn exported_definitions(
+    graph: &StackGraph,
+    files: &[Handle<File>],
+    resolutions: &Resolutions,
+) -> Vec<Handle<Node>> {
+    let mut definitions = Vec::new();
+    let mut seen = HashSet::new();
+    for file in files {
+        for node in graph.nodes_for_file(*file) {
+            if graph[node].is_definition() && has_span(graph, node) && seen.insert(node) {
+                definitions.push(node);
+            }
+        }
+    }
+    for (_, targets) in resolutions.iter() {
+        for target in targets {
+            if has_span(graph, *target) && seen.insert(*target) {
+                definitions.push(*target);
+            }
+        }
+    }
+    definitions
+}
+
+/// Returns the references to export: the resolved references in the given files.  Nodes without
+/// a source span are left out.
+fn exported_references(
+    graph: &StackGraph,
+    files: &[Handle<File>],
+    resolutions: &Resolutions,
+) -> Vec<Handle<Node>> {
+    let files = files.iter().copied().collect::<HashSet<_>>();
+    resolutions
+        .iter()
+        .map(|(reference, _)| reference)
+        .filter(|n| graph[*n].file().is_some_and(|f| files.contains(&f)) && has_span(graph, *n))
+        .collect()
+}
+
+/// Returns whether the node is in a file and has a non-empty source span.
+fn has_span(graph: &StackGraph, node: Handle<Node>) -> bool {
+    graph[node].file().is_some()
+        && graph.source_info(node).is_some_and(|si| {
+            si.span.start.line != si.span.end.line
+                || si.span.start.column.utf8_offset != si.span.end.column.utf8_offset
+        })
+}
+
+/// Returns the name of a file as a path relative to the project root, if it is inside the
+/// project root.
+fn relative_path<'a>(name: &'a str, project_root: &Path) -> &'a str {
+    Path::new(name)
+        .strip_prefix(project_root)
+        .ok()
+        .and_then(|p| p.to_str())
+        .unwrap_or(name)
+}
+
+/// Returns a `file://` URI for a path, percent-encoding characters that may not appear in URIs.
+fn file_uri(path: &Path) -> String {
+    let mut uri = "file://".to_string();
+    let path = path.to_string_lossy();
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(b as char)
+            }
+            _ => uri += &format!("%{:02X}", b),
+        }
+    }
+    uri
+}
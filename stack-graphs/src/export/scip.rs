@@ -0,0 +1,262 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Converts resolved references into a [SCIP][] index.
+//!
+//! The messages in this module are the subset of the SCIP schema that is needed to describe
+//! definitions and references, with the field numbers of `scip.proto`.  An index is encoded
+//! with the methods of the [`Message`][] trait.
+//!
+//! Every definition gets a global symbol of the form
+//!
+//! ```text
+//! stack-graphs . . . `<file>`/<symbol>(<local-id>).
+//! ```
+//!
+//! which is unique within the index, and is shared by the occurrences of the references that
+//! resolve to it.  References that resolve to several definitions have an occurrence for each of
+//! them.
+//!
+//! [SCIP]: https://github.com/sourcegraph/scip
+
+use std::collections::HashMap;
+use std::path::Path;
+
+pub use prost::Message;
+
+use crate::arena::Handle;
+use crate::graph::File;
+use crate::graph::Node;
+use crate::graph::StackGraph;
+
+use super::exported_definitions;
+use super::exported_references;
+use super::file_uri;
+use super::relative_path;
+use super::Resolutions;
+use super::TOOL_NAME;
+use super::TOOL_VERSION;
+
+/// The symbol role of occurrences that define their symbol.
+pub const SYMBOL_ROLE_DEFINITION: i32 = 0x1;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Index {
+    #[prost(message, optional, tag = "1")]
+    pub metadata: Option<Metadata>,
+    #[prost(message, repeated, tag = "2")]
+    pub documents: Vec<Document>,
+    #[prost(message, repeated, tag = "3")]
+    pub external_symbols: Vec<SymbolInformation>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Metadata {
+    #[prost(enumeration = "ProtocolVersion", tag = "1")]
+    pub version: i32,
+    #[prost(message, optional, tag = "2")]
+    pub tool_info: Option<ToolInfo>,
+    #[prost(string, tag = "3")]
+    pub project_root: String,
+    #[prost(enumeration = "TextEncoding", tag = "4")]
+    pub text_document_encoding: i32,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, prost::Enumeration)]
+#[repr(i32)]
+pub enum ProtocolVersion {
+    UnspecifiedProtocolVersion = 0,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, prost::Enumeration)]
+#[repr(i32)]
+pub enum TextEncoding {
+    UnspecifiedTextEncoding = 0,
+    Utf8 = 1,
+    Utf16 = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ToolInfo {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub version: String,
+    #[prost(string, repeated, tag = "3")]
+    pub arguments: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Document {
+    #[prost(string, tag = "4")]
+    pub language: String,
+    #[prost(string, tag = "1")]
+    pub relative_path: String,
+    #[prost(message, repeated, tag = "2")]
+    pub occurrences: Vec<Occurrence>,
+    #[prost(message, repeated, tag = "3")]
+    pub symbols: Vec<SymbolInformation>,
+    #[prost(enumeration = "PositionEncoding", tag = "6")]
+    pub position_encoding: i32,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, prost::Enumeration)]
+#[repr(i32)]
+pub enum PositionEncoding {
+    UnspecifiedPositionEncoding = 0,
+    Utf8CodeUnitOffsetFromLineStart = 1,
+    Utf16CodeUnitOffsetFromLineStart = 2,
+    Utf32CodeUnitOffsetFromLineStart = 3,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Occurrence {
+    /// The range as `[start_line, start_character, end_line, end_character]`, or as
+    /// `[start_line, start_character, end_character]` if the range is within a single line.
+    #[prost(int32, repeated, tag = "1")]
+    pub range: Vec<i32>,
+    #[prost(string, tag = "2")]
+    pub symbol: String,
+    #[prost(int32, tag = "3")]
+    pub symbol_roles: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SymbolInformation {
+    #[prost(string, tag = "1")]
+    pub symbol: String,
+    #[prost(string, tag = "6")]
+    pub display_name: String,
+}
+
+impl Index {
+    /// Returns a SCIP index of the definitions and resolved references in the given files.
+    /// Definitions in other files that references resolve to are listed as external symbols.
+    pub fn from_resolutions(
+        graph: &StackGraph,
+        files: &[Handle<File>],
+        resolutions: &Resolutions,
+        project_root: &Path,
+    ) -> Self {
+        let mut documents = files
+            .iter()
+            .map(|file| {
+                (
+                    *file,
+                    Document {
+                        relative_path: relative_path(graph[*file].name(), project_root).to_string(),
+                        position_encoding: PositionEncoding::Utf16CodeUnitOffsetFromLineStart
+                            as i32,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        let mut external_symbols = Vec::new();
+
+        let mut symbols = HashMap::new();
+        for definition in exported_definitions(graph, files, resolutions) {
+            let symbol = definition_symbol(graph, definition);
+            let information = SymbolInformation {
+                symbol: symbol.clone(),
+                display_name: graph[definition]
+                    .symbol()
+                    .map(|s| graph[s].to_string())
+                    .unwrap_or_default(),
+            };
+            match documents.get_mut(&graph[definition].file().unwrap()) {
+                Some(document) => {
+                    document.occurrences.push(Occurrence {
+                        range: occurrence_range(graph, definition),
+                        symbol: symbol.clone(),
+                        symbol_roles: SYMBOL_ROLE_DEFINITION,
+                    });
+                    document.symbols.push(information);
+                }
+                None => external_symbols.push(information),
+            }
+            symbols.insert(definition, symbol);
+        }
+
+        for reference in exported_references(graph, files, resolutions) {
+            let document = documents
+                .get_mut(&graph[reference].file().unwrap())
+                .expect("exported reference must be in an exported file");
+            for definition in resolutions.definitions(reference) {
+                if let Some(symbol) = symbols.get(definition) {
+                    document.occurrences.push(Occurrence {
+                        range: occurrence_range(graph, reference),
+                        symbol: symbol.clone(),
+                        symbol_roles: 0,
+                    });
+                }
+            }
+        }
+
+        Index {
+            metadata: Some(Metadata {
+                version: ProtocolVersion::UnspecifiedProtocolVersion as i32,
+                tool_info: Some(ToolInfo {
+                    name: TOOL_NAME.to_string(),
+                    version: TOOL_VERSION.to_string(),
+                    arguments: Vec::new(),
+                }),
+                project_root: file_uri(project_root),
+                text_document_encoding: TextEncoding::Utf8 as i32,
+            }),
+            documents: files
+                .iter()
+                .filter_map(|file| documents.remove(file))
+                .collect(),
+            external_symbols,
+        }
+    }
+}
+
+/// Returns the global symbol of a definition, which is unique within the index.
+fn definition_symbol(graph: &StackGraph, node: Handle<Node>) -> String {
+    let file = graph[node].file().expect("exported node must be in a file");
+    let name = graph[node]
+        .symbol()
+        .map(|s| graph[s].to_string())
+        .unwrap_or_default();
+    format!(
+        "stack-graphs . . . {}/{}({}).",
+        escape_name(graph[file].name()),
+        escape_name(&name),
+        graph[node].id().local_id(),
+    )
+}
+
+/// Returns a descriptor name, which is wrapped in backticks unless it is a simple identifier.
+fn escape_name(name: &str) -> String {
+    let is_simple = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-' | '$'));
+    if is_simple {
+        name.to_string()
+    } else {
+        format!("`{}`", name.replace('`', "``"))
+    }
+}
+
+fn occurrence_range(graph: &StackGraph, node: Handle<Node>) -> Vec<i32> {
+    let span = &graph
+        .source_info(node)
+        .expect("exported node must have source info")
+        .span;
+    let start_line = span.start.line as i32;
+    let start_character = span.start.column.utf16_offset as i32;
+    let end_line = span.end.line as i32;
+    let end_character = span.end.column.utf16_offset as i32;
+    if start_line == end_line {
+        vec![start_line, start_character, end_character]
+    } else {
+        vec![start_line, start_character, end_line, end_character]
+    }
+}
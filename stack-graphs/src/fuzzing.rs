@@ -0,0 +1,466 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Generates random stack graphs and edge sequences, and checks the invariants of appending,
+//! concatenating, and resolving paths on them.
+//!
+//! This module requires the `fuzzing` feature.  It is used by the [cargo-fuzz][] targets in the
+//! `fuzz` directory of the `stack-graphs` crate, which are run with:
+//!
+//! ``` sh
+//! $ cargo +nightly fuzz run append
+//! $ cargo +nightly fuzz run concatenate
+//! $ cargo +nightly fuzz run resolve
+//! ```
+//!
+//! Inputs are generated with [arbitrary][].  A [`GraphInput`][] describes the nodes and edges of a
+//! graph with a single file, and a [`WalkInput`][] describes a sequence of edges through such a
+//! graph.  The check functions panic if an invariant does not hold:
+//!
+//!   - [`check_append`][] appends the edges of a walk one by one, and checks that every appended
+//!     edge moves the end of the path, and that appending an edge gives the same pre- and
+//!     postconditions as concatenating the path with the partial path of that single edge.
+//!   - [`check_concatenate`][] splits a walk in three partial paths, and checks that concatenating
+//!     them is associative, whenever both orders of concatenation succeed.
+//!   - [`check_resolve`][] stitches all references of a graph, and checks that every resolved
+//!     path is complete.
+//!
+//! Paths are compared by their pre- and postconditions, after renumbering their symbol and scope
+//! stack variables in the order in which they appear, and by their edges.
+//!
+//! [arbitrary]: https://docs.rs/arbitrary/
+//! [cargo-fuzz]: https://github.com/rust-fuzz/cargo-fuzz
+
+use std::cell::Cell;
+
+use arbitrary::Arbitrary;
+use arbitrary::Unstructured;
+
+use crate::arena::Handle;
+use crate::graph::Edge;
+use crate::graph::Node;
+use crate::graph::NodeID;
+use crate::graph::StackGraph;
+use crate::partial::PartialPath;
+use crate::partial::PartialPaths;
+use crate::stitching::ForwardPartialPathStitcher;
+use crate::stitching::GraphEdgeCandidates;
+use crate::stitching::StitcherConfig;
+use crate::CancellationError;
+use crate::CancellationFlag;
+
+/// The maximum number of nodes in a generated graph, not counting the root and jump to nodes.
+const MAX_NODES: usize = 32;
+/// The maximum number of edges in a generated graph.
+const MAX_EDGES: usize = 64;
+/// The maximum number of edges in a generated walk.
+const MAX_STEPS: usize = 32;
+/// The maximum number of cancellation checks while resolving references, which bounds the number
+/// of phases for graphs whose paths do not terminate quickly.
+const MAX_RESOLVE_CHECKS: usize = 64;
+/// The maximum number of queued paths while resolving references, which bounds the work of every
+/// phase.
+const MAX_QUEUED_PATHS: usize = 256;
+
+/// The nodes and edges of a generated stack graph with a single file.
+#[derive(Clone, Debug)]
+pub struct GraphInput {
+    nodes: Vec<NodeInput>,
+    edges: Vec<EdgeInput>,
+}
+
+impl<'a> Arbitrary<'a> for GraphInput {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        // Lengths are chosen up front, instead of continuing lists with a probability of one
+        // half, so that graphs of all sizes are generated.
+        let node_count = u.int_in_range(0..=MAX_NODES)?;
+        let nodes = (0..node_count)
+            .map(|_| NodeInput::arbitrary(u))
+            .collect::<arbitrary::Result<_>>()?;
+        let edge_count = u.int_in_range(0..=MAX_EDGES)?;
+        let edges = (0..edge_count)
+            .map(|_| EdgeInput::arbitrary(u))
+            .collect::<arbitrary::Result<_>>()?;
+        Ok(Self { nodes, edges })
+    }
+}
+
+#[derive(Arbitrary, Clone, Debug)]
+enum NodeInput {
+    DropScopes,
+    PopScopedSymbol {
+        symbol: SymbolInput,
+        is_definition: bool,
+    },
+    PopSymbol {
+        symbol: SymbolInput,
+        is_definition: bool,
+    },
+    PushScopedSymbol {
+        symbol: SymbolInput,
+        scope: u8,
+        is_reference: bool,
+    },
+    PushSymbol {
+        symbol: SymbolInput,
+        is_reference: bool,
+    },
+    Scope {
+        is_exported: bool,
+    },
+}
+
+#[derive(Arbitrary, Clone, Copy, Debug)]
+enum SymbolInput {
+    A,
+    B,
+    C,
+}
+
+impl SymbolInput {
+    fn name(self) -> &'static str {
+        match self {
+            Self::A => "a",
+            Self::B => "b",
+            Self::C => "c",
+        }
+    }
+}
+
+/// An edge between nodes of a generated graph, which are identified by their index in the list
+/// of all nodes, starting with the root and jump to nodes.
+#[derive(Arbitrary, Clone, Debug)]
+struct EdgeInput {
+    source: u8,
+    sink: u8,
+    precedence: i8,
+}
+
+impl GraphInput {
+    /// Builds the graph, and returns it with the handles of all of its nodes, starting with the
+    /// root and jump to nodes.
+    pub fn build(&self) -> (StackGraph, Vec<Handle<Node>>) {
+        let mut graph = StackGraph::new();
+        let file = graph.get_or_create_file("test");
+        let inputs = &self.nodes;
+
+        // Pushed scopes must refer to exported scope nodes, which may come later in the file.
+        let exported_scopes = inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| matches!(node, NodeInput::Scope { is_exported: true }))
+            .map(|(index, _)| NodeID::new_in_file(file, index as u32))
+            .collect::<Vec<_>>();
+
+        let mut nodes = vec![StackGraph::root_node(), StackGraph::jump_to_node()];
+        for (index, input) in inputs.iter().enumerate() {
+            let id = NodeID::new_in_file(file, index as u32);
+            let node = match input {
+                NodeInput::DropScopes => graph.add_drop_scopes_node(id),
+                NodeInput::PopScopedSymbol {
+                    symbol,
+                    is_definition,
+                } => {
+                    let symbol = graph.add_symbol(symbol.name());
+                    graph.add_pop_scoped_symbol_node(id, symbol, *is_definition)
+                }
+                NodeInput::PopSymbol {
+                    symbol,
+                    is_definition,
+                } => {
+                    let symbol = graph.add_symbol(symbol.name());
+                    graph.add_pop_symbol_node(id, symbol, *is_definition)
+                }
+                NodeInput::PushScopedSymbol {
+                    symbol,
+                    scope,
+                    is_reference,
+                } => {
+                    let symbol = graph.add_symbol(symbol.name());
+                    match exported_scopes.get(*scope as usize % exported_scopes.len().max(1)) {
+                        Some(scope) => {
+                            graph.add_push_scoped_symbol_node(id, symbol, *scope, *is_reference)
+                        }
+                        None => graph.add_push_symbol_node(id, symbol, *is_reference),
+                    }
+                }
+                NodeInput::PushSymbol {
+                    symbol,
+                    is_reference,
+                } => {
+                    let symbol = graph.add_symbol(symbol.name());
+                    graph.add_push_symbol_node(id, symbol, *is_reference)
+                }
+                NodeInput::Scope { is_exported } => graph.add_scope_node(id, *is_exported),
+            };
+            nodes.push(node.expect("generated node IDs are unique"));
+        }
+
+        for edge in &self.edges {
+            let source = nodes[edge.source as usize % nodes.len()];
+            let sink = nodes[edge.sink as usize % nodes.len()];
+            // The jump to node has no outgoing edges, and the root node has no self edges.
+            if source == StackGraph::jump_to_node() || (source == sink && graph[source].is_root()) {
+                continue;
+            }
+            graph.add_edge(source, sink, edge.precedence as i32);
+        }
+        (graph, nodes)
+    }
+}
+
+/// A sequence of edges through a generated graph.  The walk starts at the given node, and every
+/// step picks one of the outgoing edges of the current node, until there are none.
+#[derive(Clone, Debug)]
+pub struct WalkInput {
+    graph: GraphInput,
+    start: u8,
+    steps: Vec<u8>,
+    splits: (u8, u8),
+}
+
+impl<'a> Arbitrary<'a> for WalkInput {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let graph = GraphInput::arbitrary(u)?;
+        let start = u8::arbitrary(u)?;
+        let splits = <(u8, u8)>::arbitrary(u)?;
+        let step_count = u.int_in_range(0..=MAX_STEPS)?;
+        let steps = u.bytes(step_count)?.to_vec();
+        Ok(Self {
+            graph,
+            start,
+            steps,
+            splits,
+        })
+    }
+}
+
+impl WalkInput {
+    /// Builds the graph, and returns it with the start node and edges of the walk.
+    pub fn build(&self) -> (StackGraph, Handle<Node>, Vec<Edge>) {
+        let (graph, nodes) = self.graph.build();
+        let start = nodes[self.start as usize % nodes.len()];
+        let mut edges = Vec::new();
+        let mut current = start;
+        for step in &self.steps {
+            let outgoing = graph.outgoing_edges(current).collect::<Vec<_>>();
+            if outgoing.is_empty() {
+                break;
+            }
+            let edge = outgoing[*step as usize % outgoing.len()];
+            edges.push(edge);
+            current = edge.sink;
+        }
+        (graph, start, edges)
+    }
+
+    /// Returns the two positions at which the walk is split into three parts.
+    fn split_points(&self, edge_count: usize) -> (usize, usize) {
+        let first = self.splits.0 as usize % (edge_count + 1);
+        let second = self.splits.1 as usize % (edge_count + 1);
+        (first.min(second), first.max(second))
+    }
+}
+
+/// Builds the partial path of the given edges, which start at the given node.  Returns `None` if
+/// any of the edges cannot be appended.
+fn partial_path_for_edges(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    start: Handle<Node>,
+    edges: &[Edge],
+) -> Option<PartialPath> {
+    let mut path = PartialPath::from_node(graph, partials, start);
+    for edge in edges {
+        path.append(graph, partials, *edge).ok()?;
+    }
+    Some(path)
+}
+
+/// Returns the pre- and postconditions of a path, with their variables renumbered in the order
+/// in which they appear, and the edges of the path.
+fn canonical_form(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    path: &PartialPath,
+) -> (String, Vec<(NodeID, i32)>) {
+    let display = path.display(graph, partials).to_string();
+    let mut symbol_variables = Vec::new();
+    let mut scope_variables = Vec::new();
+    let mut result = String::with_capacity(display.len());
+    let mut chars = display.chars().peekable();
+    while let Some(c) = chars.next() {
+        result.push(c);
+        let variables = match c {
+            '%' => &mut symbol_variables,
+            '$' => &mut scope_variables,
+            _ => continue,
+        };
+        let mut variable = String::new();
+        while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+            variable.push(digit);
+        }
+        let index = match variables.iter().position(|v| *v == variable) {
+            Some(index) => index,
+            None => {
+                variables.push(variable);
+                variables.len() - 1
+            }
+        };
+        result += &(index + 1).to_string();
+    }
+    let edges = path
+        .edges
+        .iter(partials)
+        .map(|edge| (edge.source_node_id, edge.precedence))
+        .collect();
+    (result, edges)
+}
+
+/// Appends the edges of a walk one by one, and checks the invariants of appending.  Panics if an
+/// invariant does not hold.
+pub fn check_append(input: &WalkInput) {
+    let (graph, start, edges) = input.build();
+    let mut partials = PartialPaths::new();
+    let mut path = PartialPath::from_node(&graph, &mut partials, start);
+    for edge in edges {
+        let mut concatenated = path.clone();
+        if path.append(&graph, &mut partials, edge).is_err() {
+            return;
+        }
+        assert_eq!(
+            start, path.start_node,
+            "appending moved the start of the path"
+        );
+        // Appending an edge to the jump to node jumps to the scope on top of the scope stack, if
+        // it is known.
+        if edge.sink != StackGraph::jump_to_node() || !graph[path.end_node].is_exported_scope() {
+            assert_eq!(edge.sink, path.end_node, "appending did not move the end");
+        }
+
+        let mut single = match partial_path_for_edges(&graph, &mut partials, edge.source, &[edge]) {
+            Some(single) => single,
+            None => panic!("edge can be appended to a path, but not to its source node"),
+        };
+        single.ensure_no_overlapping_variables(&mut partials, &concatenated);
+        if let Err(err) = concatenated.concatenate(&graph, &mut partials, &single) {
+            panic!("edge can be appended, but not concatenated: {}", err);
+        }
+        assert_eq!(
+            canonical_form(&graph, &mut partials, &path),
+            canonical_form(&graph, &mut partials, &concatenated),
+            "appending and concatenating an edge differ"
+        );
+    }
+}
+
+/// Splits a walk in three partial paths, and checks that concatenating them is associative.
+/// Panics if an invariant does not hold.
+pub fn check_concatenate(input: &WalkInput) {
+    let (graph, start, edges) = input.build();
+    let (first, second) = input.split_points(edges.len());
+    let mut partials = PartialPaths::new();
+    let node_at = |index: usize| match index {
+        0 => start,
+        _ if index == edges.len() => edges[index - 1].sink,
+        _ => edges[index].source,
+    };
+
+    let segments = [
+        (node_at(0), &edges[..first]),
+        (node_at(first), &edges[first..second]),
+        (node_at(second), &edges[second..]),
+    ];
+    let mut paths = Vec::new();
+    for (start, edges) in segments {
+        match partial_path_for_edges(&graph, &mut partials, start, edges) {
+            Some(path) => paths.push(path),
+            None => return,
+        }
+    }
+    let a = paths.remove(0);
+    let mut b = paths.remove(0);
+    let mut c = paths.remove(0);
+    b.ensure_no_overlapping_variables(&mut partials, &a);
+    c.ensure_no_overlapping_variables(&mut partials, &a);
+    c.ensure_no_overlapping_variables(&mut partials, &b);
+
+    let mut left = a.clone();
+    let left = left
+        .concatenate(&graph, &mut partials, &b)
+        .and_then(|_| left.concatenate(&graph, &mut partials, &c))
+        .map(|_| left);
+    let mut right = b.clone();
+    let right = right
+        .concatenate(&graph, &mut partials, &c)
+        .map(|_| right)
+        .and_then(|right| {
+            let mut path = a.clone();
+            path.concatenate(&graph, &mut partials, &right)
+                .map(|_| path)
+        });
+    if let (Ok(left), Ok(right)) = (left, right) {
+        assert_eq!(start, left.start_node);
+        assert_eq!(
+            canonical_form(&graph, &mut partials, &left),
+            canonical_form(&graph, &mut partials, &right),
+            "concatenation is not associative"
+        );
+    }
+}
+
+/// Stitches all references of a graph, and checks the invariants of the resolved paths.  Panics
+/// if an invariant does not hold.
+pub fn check_resolve(input: &GraphInput) {
+    let (graph, nodes) = input.build();
+    let references = nodes
+        .iter()
+        .copied()
+        .filter(|node| graph[*node].is_reference())
+        .collect::<Vec<_>>();
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    let _ = ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        references.iter().copied(),
+        StitcherConfig::default().with_max_queued_paths(Some(MAX_QUEUED_PATHS)),
+        &CheckLimit::new(MAX_RESOLVE_CHECKS),
+        |_, _, path| paths.push(path.clone()),
+    );
+    for path in paths {
+        assert!(
+            references.contains(&path.start_node),
+            "resolved path does not start at a reference"
+        );
+        assert!(path.is_complete(&graph), "resolved path is not complete");
+    }
+}
+
+/// A cancellation flag that cancels after a maximum number of checks.
+struct CheckLimit {
+    remaining: Cell<usize>,
+}
+
+impl CheckLimit {
+    fn new(checks: usize) -> Self {
+        Self {
+            remaining: Cell::new(checks),
+        }
+    }
+}
+
+impl CancellationFlag for CheckLimit {
+    fn check(&self, at: &'static str) -> Result<(), CancellationError> {
+        match self.remaining.get() {
+            0 => Err(CancellationError(at)),
+            remaining => {
+                self.remaining.set(remaining - 1);
+                Ok(())
+            }
+        }
+    }
+}
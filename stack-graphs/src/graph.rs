@@ -49,6 +49,8 @@
 //! [`Edge`]: struct.Edge.html
 //! [`File`]: struct.File.html
 
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::num::NonZeroU32;
@@ -57,12 +59,19 @@ use std::ops::IndexMut;
 
 use controlled_option::ControlledOption;
 use either::Either;
+use enumset::EnumSetType;
 use fxhash::FxHashMap;
 use smallvec::SmallVec;
+use thiserror::Error;
+use unicode_normalization::is_nfc;
+use unicode_normalization::UnicodeNormalization;
 
 use crate::arena::Arena;
 use crate::arena::Handle;
+use crate::arena::HandleSet;
 use crate::arena::SupplementalArena;
+#[cfg(feature = "checked")]
+use crate::checked::InvariantViolation;
 
 //-------------------------------------------------------------------------------------------------
 // String content
@@ -145,8 +154,10 @@ impl InternedStringContent {
     // 'static lifetime here.  As an extra precaution, this method is is marked as unsafe so that
     // we don't inadvertently call it from anywhere else in the crate.
     unsafe fn as_hash_key(&self) -> &'static str {
-        let bytes = std::slice::from_raw_parts(self.start, self.len);
-        std::str::from_utf8_unchecked(bytes)
+        unsafe {
+            let bytes = std::slice::from_raw_parts(self.start, self.len);
+            std::str::from_utf8_unchecked(bytes)
+        }
     }
 }
 
@@ -156,6 +167,16 @@ unsafe impl Sync for InternedStringContent {}
 //-------------------------------------------------------------------------------------------------
 // Symbols
 
+/// The symbol namespace of type symbols.
+pub const TYPE_SYMBOL_NAMESPACE: &str = "type";
+
+/// The symbol namespace of the positional type arguments of
+/// [generic instantiations][StackGraph::instantiate].
+pub const TYPE_ARGUMENT_NAMESPACE: &str = "type_argument";
+
+/// The symbol namespace of the names of [root domains][StackGraph::add_root_domain].
+pub const ROOT_DOMAIN_NAMESPACE: &str = "root_domain";
+
 /// A name that we are trying to resolve using stack graphs.
 ///
 /// This typically represents a portion of an identifier as it appears in the source language.  It
@@ -166,6 +187,23 @@ unsafe impl Sync for InternedStringContent {}
 /// We deduplicate `Symbol` instances in a `StackGraph` — that is, we ensure that there are never
 /// multiple `Symbol` instances with the same content.  That means that you can compare _handles_
 /// to symbols using simple equality, without having to dereference into the `StackGraph` arena.
+///
+/// A symbol can belong to a _namespace_, which lets languages distinguish, for instance, type
+/// names from value names with the same spelling.  Symbols with the same name in different
+/// namespaces are different symbols, and do not match each other.  Symbols in the
+/// [`TYPE_SYMBOL_NAMESPACE`][] are _type symbols_, which rules push to propagate the types of
+/// expressions through the graph.  The definitions of type symbols that an expression resolves to
+/// can be found with [`query::expression_types`][crate::query::expression_types].
+///
+/// Symbols are arbitrary UTF-8 strings.  By default they are stored exactly as given, and two
+/// symbols are the same if they consist of the same sequence of Unicode code points.  Identifiers
+/// that look the same, but are encoded differently — for instance, `é` as a single code point,
+/// or as `e` followed by a combining accent — are different symbols.  Languages whose identifiers
+/// are compared after Unicode normalization can either normalize symbols when they are added to
+/// the graph, using [`StackGraph::set_symbol_interning`][], or when they are matched, using
+/// [`StackGraph::set_symbol_normalization`][].  The former makes equivalent spellings the same
+/// symbol, and is the same on every platform, regardless of how source files were encoded; the
+/// latter keeps symbols as written, for display, while still resolving them consistently.
 #[repr(C)]
 pub struct Symbol {
     content: InternedStringContent,
@@ -183,11 +221,61 @@ impl PartialEq<&str> for Symbol {
     }
 }
 
+/// A stable identifier of a symbol, which only depends on the name and namespace of the symbol.
+/// Unlike symbol handles, which depend on the order in which symbols are added to a graph,
+/// identifiers are the same in every graph, index, and release, so external systems can use them
+/// as keys to join results against their own symbol tables. Identifiers are 64-bit hashes, and
+/// are displayed and parsed as 16 hexadecimal digits.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SymbolId(u64);
+
+impl SymbolId {
+    /// Returns the identifier of the symbol with the given name and namespace.
+    pub fn new(symbol: &str, namespace: Option<&str>) -> Self {
+        // 0xff never occurs in UTF-8, so it separates the namespace from the name unambiguously
+        let bytes = namespace
+            .into_iter()
+            .flat_map(|namespace| namespace.bytes().chain(std::iter::once(0xff)))
+            .chain(symbol.bytes());
+        Self(crate::utils::stable_hash(bytes))
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+impl From<u64> for SymbolId {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+impl std::fmt::Display for SymbolId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl std::str::FromStr for SymbolId {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s, 16).map(Self)
+    }
+}
+
 impl StackGraph {
     /// Adds a symbol to the stack graph, ensuring that there's only ever one copy of a particular
     /// symbol stored in the graph.
     pub fn add_symbol<S: AsRef<str> + ?Sized>(&mut self, symbol: &S) -> Handle<Symbol> {
-        let symbol = symbol.as_ref();
+        let symbol = self.symbol_interning.normalize(symbol.as_ref());
+        let handle = self.intern_symbol(&symbol);
+        self.add_normalized_symbol(handle);
+        handle
+    }
+
+    fn intern_symbol(&mut self, symbol: &str) -> Handle<Symbol> {
         if let Some(handle) = self.symbol_handles.get(symbol) {
             return *handle;
         }
@@ -199,6 +287,163 @@ impl StackGraph {
         handle
     }
 
+    /// Adds a symbol in the given namespace to the stack graph, ensuring that there's only ever
+    /// one copy of a particular symbol in a particular namespace stored in the graph.  The
+    /// symbol is different from the symbol with the same name that [`add_symbol`][Self::add_symbol]
+    /// returns, and from the symbols with the same name in other namespaces.
+    pub fn add_namespaced_symbol<S: AsRef<str> + ?Sized, N: AsRef<str> + ?Sized>(
+        &mut self,
+        symbol: &S,
+        namespace: &N,
+    ) -> Handle<Symbol> {
+        let unnamespaced = self.add_symbol(symbol);
+        let handle = self.intern_namespaced_symbol(unnamespaced, namespace.as_ref());
+        self.add_normalized_symbol(handle);
+        handle
+    }
+
+    fn intern_namespaced_symbol(
+        &mut self,
+        unnamespaced: Handle<Symbol>,
+        namespace: &str,
+    ) -> Handle<Symbol> {
+        let namespace = self.add_string(namespace);
+        if let Some(handle) = self
+            .namespaced_symbol_handles
+            .get(&(namespace, unnamespaced))
+        {
+            return *handle;
+        }
+
+        let content = &self.symbols.get(unnamespaced).content;
+        let content = InternedStringContent {
+            start: content.start,
+            len: content.len,
+        };
+        let handle = self.symbols.add(Symbol { content });
+        self.namespaced_symbol_handles
+            .insert((namespace, unnamespaced), handle);
+        self.symbol_namespaces[handle] = Some((namespace, unnamespaced));
+        handle
+    }
+
+    /// Adds a symbol with the given name and optional namespace.
+    pub fn add_symbol_in_namespace(
+        &mut self,
+        symbol: &str,
+        namespace: Option<&str>,
+    ) -> Handle<Symbol> {
+        match namespace {
+            Some(namespace) => self.add_namespaced_symbol(symbol, namespace),
+            None => self.add_symbol(symbol),
+        }
+    }
+
+    /// Adds a symbol with the same name and namespace as a symbol of another stack graph.
+    pub(crate) fn add_symbol_from(
+        &mut self,
+        other: &StackGraph,
+        symbol: Handle<Symbol>,
+    ) -> Handle<Symbol> {
+        self.add_symbol_in_namespace(&other[symbol], other.symbol_namespace(symbol))
+    }
+
+    /// Returns the namespace of a symbol, or `None` if the symbol does not belong to a namespace.
+    pub fn symbol_namespace(&self, symbol: Handle<Symbol>) -> Option<&str> {
+        let (namespace, _) = self.symbol_namespaces.get(symbol)?.as_ref()?;
+        Some(&self[*namespace])
+    }
+
+    /// Returns the stable identifier of a symbol, which is the same for symbols with the same
+    /// name and namespace in all graphs.
+    pub fn symbol_id(&self, symbol: Handle<Symbol>) -> SymbolId {
+        SymbolId::new(&self[symbol], self.symbol_namespace(symbol))
+    }
+
+    /// Adds a type symbol, which is a symbol in the [`TYPE_SYMBOL_NAMESPACE`][].
+    pub fn add_type_symbol<S: AsRef<str> + ?Sized>(&mut self, symbol: &S) -> Handle<Symbol> {
+        self.add_namespaced_symbol(symbol, TYPE_SYMBOL_NAMESPACE)
+    }
+
+    /// Returns whether a symbol is a type symbol, which is a symbol in the
+    /// [`TYPE_SYMBOL_NAMESPACE`][].
+    pub fn is_type_symbol(&self, symbol: Handle<Symbol>) -> bool {
+        self.symbol_namespace(symbol) == Some(TYPE_SYMBOL_NAMESPACE)
+    }
+
+    /// Returns the symbol with the same name as the given symbol that does not belong to a
+    /// namespace.
+    pub fn symbol_without_namespace(&self, symbol: Handle<Symbol>) -> Handle<Symbol> {
+        match self.symbol_namespaces.get(symbol).and_then(Option::as_ref) {
+            Some((_, unnamespaced)) => *unnamespaced,
+            None => symbol,
+        }
+    }
+
+    /// Returns how symbols are normalized when they are added to the graph.
+    pub fn symbol_interning(&self) -> SymbolNormalization {
+        self.symbol_interning
+    }
+
+    /// Sets how symbols are normalized when they are added to the graph.  Symbols with names
+    /// that normalize to the same string are the same symbol, and the graph only stores the
+    /// normalized name.  This applies to symbols that are added after calling this method, so it
+    /// should be called before any symbols are added.  Graphs and partial paths that are loaded
+    /// from serialized data are normalized as well.
+    pub fn set_symbol_interning(&mut self, normalization: SymbolNormalization) {
+        self.symbol_interning = normalization;
+    }
+
+    /// Returns how symbols are normalized before they are matched against each other.
+    pub fn symbol_normalization(&self) -> SymbolNormalization {
+        self.symbol_normalization
+    }
+
+    /// Sets how symbols are normalized before they are matched against each other.  Push and
+    /// pop nodes keep their symbols as written, but the symbol stacks of paths contain normalized
+    /// symbols, so that, for instance, a reference to `Foo` can resolve to a definition of `FOO`
+    /// when case folding is enabled.
+    ///
+    /// Partial paths that are stored or serialized contain normalized symbols.  Load them into a
+    /// stack graph with the same normalization as the one they were computed in.
+    pub fn set_symbol_normalization(&mut self, normalization: SymbolNormalization) {
+        self.symbol_normalization = normalization;
+        self.normalized_symbols = SupplementalArena::new();
+        let symbols = self.iter_symbols().collect::<Vec<_>>();
+        for symbol in symbols {
+            self.add_normalized_symbol(symbol);
+        }
+    }
+
+    /// Returns the symbol that is used in place of the given symbol when matching symbols.  This
+    /// is the symbol itself if the graph does not normalize symbols.
+    pub fn normalized_symbol(&self, symbol: Handle<Symbol>) -> Handle<Symbol> {
+        match self.normalized_symbols.get(symbol) {
+            Some(Some(normalized)) => *normalized,
+            _ => symbol,
+        }
+    }
+
+    /// Records the normalized symbol for the given symbol, if it differs from the symbol itself.
+    /// Normalized symbols are interned without being normalized again.
+    fn add_normalized_symbol(&mut self, symbol: Handle<Symbol>) {
+        if self.symbol_normalization.is_identity() {
+            return;
+        }
+        let normalized = match self.symbol_normalization.normalize(&self[symbol]) {
+            Cow::Borrowed(_) => return,
+            Cow::Owned(normalized) => normalized,
+        };
+        let namespace = self.symbol_namespace(symbol).map(str::to_owned);
+        let mut normalized = self.intern_symbol(&normalized);
+        if let Some(namespace) = namespace {
+            normalized = self.intern_namespaced_symbol(normalized, &namespace);
+        }
+        if normalized != symbol {
+            self.normalized_symbols[symbol] = Some(normalized);
+        }
+    }
+
     /// Returns an iterator over all of the handles of all of the symbols in this stack graph.
     /// (Note that because we're only returning _handles_, this iterator does not retain a
     /// reference to the `StackGraph`.)
@@ -207,6 +452,17 @@ impl StackGraph {
     }
 }
 
+#[cfg(feature = "checked")]
+impl StackGraph {
+    /// Returns the content of a symbol, or an error if the handle does not belong to this graph.
+    pub fn try_symbol(&self, handle: Handle<Symbol>) -> Result<&str, InvariantViolation> {
+        self.symbols
+            .try_get(handle)
+            .map(Symbol::as_str)
+            .ok_or(InvariantViolation::InvalidHandle("symbol"))
+    }
+}
+
 impl Index<Handle<Symbol>> for StackGraph {
     type Output = str;
     #[inline(always)]
@@ -223,6 +479,9 @@ pub struct DisplaySymbol<'a> {
 
 impl<'a> Display for DisplaySymbol<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(namespace) = self.graph.symbol_namespace(self.wrapped) {
+            write!(f, "{}:", namespace)?;
+        }
         write!(f, "{}", &self.graph[self.wrapped])
     }
 }
@@ -236,6 +495,60 @@ impl Handle<Symbol> {
     }
 }
 
+/// Determines how symbols are normalized, either when they are added to a stack graph, or before
+/// they are matched against each other.  By default, symbols are not normalized, and only match
+/// symbols that are spelled identically.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SymbolNormalization {
+    case_folding: bool,
+    unicode_nfc: bool,
+}
+
+impl SymbolNormalization {
+    /// Returns a normalization that leaves symbols unchanged.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn case_folding(&self) -> bool {
+        self.case_folding
+    }
+
+    /// Match symbols case-insensitively, by converting them to lowercase.
+    pub fn with_case_folding(mut self, case_folding: bool) -> Self {
+        self.case_folding = case_folding;
+        self
+    }
+
+    pub fn unicode_nfc(&self) -> bool {
+        self.unicode_nfc
+    }
+
+    /// Match symbols after converting them to Unicode Normalization Form C, so that symbols that
+    /// are canonically equivalent, but use different code points, match.
+    pub fn with_unicode_nfc(mut self, unicode_nfc: bool) -> Self {
+        self.unicode_nfc = unicode_nfc;
+        self
+    }
+
+    /// Returns whether this normalization leaves all symbols unchanged.
+    pub fn is_identity(&self) -> bool {
+        !self.case_folding && !self.unicode_nfc
+    }
+
+    /// Normalizes a symbol.  Returns a borrowed value if the symbol is already normalized.
+    pub fn normalize<'a>(&self, symbol: &'a str) -> Cow<'a, str> {
+        let mut result = Cow::Borrowed(symbol);
+        if self.case_folding && result.chars().any(|c| c.is_uppercase()) {
+            result = Cow::Owned(result.to_lowercase());
+        }
+        if self.unicode_nfc && !is_nfc(&result) {
+            result = Cow::Owned(result.nfc().collect());
+        }
+        result
+    }
+}
+
 //-------------------------------------------------------------------------------------------------
 // Interned strings
 
@@ -388,6 +701,16 @@ impl Display for File {
     }
 }
 
+#[cfg(feature = "checked")]
+impl StackGraph {
+    /// Returns a file, or an error if the handle does not belong to this graph.
+    pub fn try_file(&self, handle: Handle<File>) -> Result<&File, InvariantViolation> {
+        self.files
+            .try_get(handle)
+            .ok_or(InvariantViolation::InvalidHandle("file"))
+    }
+}
+
 impl Index<Handle<File>> for StackGraph {
     type Output = File;
     #[inline(always)]
@@ -544,6 +867,19 @@ pub enum Node {
     Scope(ScopeNode),
 }
 
+/// The kinds of nodes in a stack graph, without their contents.
+#[derive(Debug, EnumSetType, Hash)]
+pub enum NodeKind {
+    DropScopes,
+    JumpTo,
+    PopScopedSymbol,
+    PopSymbol,
+    PushScopedSymbol,
+    PushSymbol,
+    Root,
+    Scope,
+}
+
 impl Node {
     #[inline(always)]
     pub fn is_exported_scope(&self) -> bool {
@@ -571,6 +907,31 @@ impl Node {
         }
     }
 
+    /// Sets whether this node represents a definition in the source language.  Only pop nodes
+    /// can be definitions, but not every pop node has to be one: pop nodes that are internal
+    /// plumbing of a language's rules should not be marked, so that they are not reported as
+    /// definitions, and partial paths do not end at them.  Returns whether the flag was set.
+    pub fn set_is_definition(&mut self, is_definition: bool) -> bool {
+        match self {
+            Node::PopScopedSymbol(node) => node.is_definition = is_definition,
+            Node::PopSymbol(node) => node.is_definition = is_definition,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Sets whether this node represents a reference in the source language.  Only push nodes
+    /// can be references, but not every push node has to be one.  Returns whether the flag was
+    /// set.
+    pub fn set_is_reference(&mut self, is_reference: bool) -> bool {
+        match self {
+            Node::PushScopedSymbol(node) => node.is_reference = is_reference,
+            Node::PushSymbol(node) => node.is_reference = is_reference,
+            _ => return false,
+        }
+        true
+    }
+
     #[inline(always)]
     pub fn is_jump_to(&self) -> bool {
         matches!(self, Node::JumpTo(_))
@@ -607,6 +968,20 @@ impl Node {
         }
     }
 
+    /// Returns the kind of this node.
+    pub fn kind(&self) -> NodeKind {
+        match self {
+            Node::DropScopes(_) => NodeKind::DropScopes,
+            Node::JumpTo(_) => NodeKind::JumpTo,
+            Node::PopScopedSymbol(_) => NodeKind::PopScopedSymbol,
+            Node::PopSymbol(_) => NodeKind::PopSymbol,
+            Node::PushScopedSymbol(_) => NodeKind::PushScopedSymbol,
+            Node::PushSymbol(_) => NodeKind::PushSymbol,
+            Node::Root(_) => NodeKind::Root,
+            Node::Scope(_) => NodeKind::Scope,
+        }
+    }
+
     /// Returns the ID of this node.
     pub fn id(&self) -> NodeID {
         match self {
@@ -731,6 +1106,16 @@ impl Handle<Node> {
     }
 }
 
+#[cfg(feature = "checked")]
+impl StackGraph {
+    /// Returns a node, or an error if the handle does not belong to this graph.
+    pub fn try_node(&self, handle: Handle<Node>) -> Result<&Node, InvariantViolation> {
+        self.nodes
+            .try_get(handle)
+            .ok_or(InvariantViolation::InvalidHandle("node"))
+    }
+}
+
 impl Index<Handle<Node>> for StackGraph {
     type Output = Node;
     #[inline(always)]
@@ -1019,6 +1404,349 @@ impl StackGraph {
     }
 }
 
+/// The nodes created by [`StackGraph::add_push_scoped_symbol_with_scope`][].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PushScopedSymbolWithScope {
+    /// The _push scoped symbol_ node.
+    pub node: Handle<Node>,
+    /// The exported scope node that is attached to the scoped symbol.
+    pub scope: Handle<Node>,
+}
+
+/// An error that can occur when adding a _push scoped symbol_ node together with its scope.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+pub enum PushScopedSymbolError {
+    /// A node that should be connected to the new nodes belongs to another file.  Edges can only
+    /// connect nodes in the same file, or a node in a file and the root or jump to scope node.
+    #[error("node belongs to a different file")]
+    NodeInDifferentFile(Handle<Node>),
+}
+
+impl StackGraph {
+    /// Adds a _push scoped symbol_ node to the stack graph, together with the exported scope node
+    /// that is attached to the scoped symbol.  This is the pattern used to model, for instance,
+    /// function calls, where the attached scope gives access to the arguments, or generic types,
+    /// where it gives access to the type arguments.
+    ///
+    /// Edges are added from the new scope node to each of the `scope_members`, and from the new
+    /// push scoped symbol node to `target`, where resolution of the symbol continues.  All of
+    /// these nodes must belong to `file`, or be the root or jump to scope node.  Chains of
+    /// scoped symbols, such as `f(a)(b)`, can be built by passing the `node` returned by one call
+    /// as the `target` of the next.
+    pub fn add_push_scoped_symbol_with_scope(
+        &mut self,
+        file: Handle<File>,
+        symbol: Handle<Symbol>,
+        is_reference: bool,
+        scope_members: &[Handle<Node>],
+        target: Handle<Node>,
+    ) -> Result<PushScopedSymbolWithScope, PushScopedSymbolError> {
+        for &member in scope_members.iter().chain(std::iter::once(&target)) {
+            let node = &self[member];
+            if node.file().is_some_and(|f| f != file) {
+                return Err(PushScopedSymbolError::NodeInDifferentFile(member));
+            }
+        }
+
+        let scope_id = self.new_node_id(file);
+        let scope = self
+            .add_scope_node(scope_id, true)
+            .expect("new node ID is unused");
+        let node_id = self.new_node_id(file);
+        let node = self
+            .add_push_scoped_symbol_node(node_id, symbol, scope_id, is_reference)
+            .expect("new node ID is unused");
+
+        for &member in scope_members {
+            self.add_edge(scope, member, 0);
+        }
+        self.add_edge(node, target, 0);
+
+        Ok(PushScopedSymbolWithScope { node, scope })
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Generics
+
+/// The nodes created by [`StackGraph::add_generic_definition`][].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GenericDefinition {
+    /// The _pop scoped symbol_ node that defines the generic.
+    pub definition: Handle<Node>,
+    /// The scope node of the body of the generic.  Add edges from it to the members of the
+    /// generic.
+    pub body: Handle<Node>,
+    /// The scope node that defines the type parameters.  Add edges to it from references to the
+    /// type parameters in the body of the generic.
+    pub parameters: Handle<Node>,
+    /// The _pop symbol_ node of each type parameter, in order.
+    pub parameter_nodes: Vec<Handle<Node>>,
+}
+
+impl StackGraph {
+    /// Returns the symbol of the type argument at the given position, which is a symbol in the
+    /// [`TYPE_ARGUMENT_NAMESPACE`][].
+    pub fn type_argument_symbol(&mut self, index: usize) -> Handle<Symbol> {
+        self.add_namespaced_symbol(&index.to_string(), TYPE_ARGUMENT_NAMESPACE)
+    }
+
+    /// Adds the nodes of a generic definition, such as a generic class `List<T>`, to the stack
+    /// graph.  Generics are modeled with attached scopes: an [instantiation][Self::instantiate]
+    /// like `List<Foo>` pushes the scoped symbol `List`, with an attached scope that maps the
+    /// position of every type argument to the place where the argument resolves.  The definition
+    /// pops the scoped symbol, which makes the attached scopes the scope stack of the path, and
+    /// references to a type parameter `T` in the body look up the position of `T` in the scope
+    /// on top of the scope stack.
+    ///
+    /// The new nodes are connected as follows, where `#i` is the [symbol][Self::type_argument_symbol]
+    /// of the `i`th type argument:
+    ///
+    /// ``` text
+    /// definition (pop scoped `symbol`) -> body
+    /// parameters -> pop `parameters[i]` -> push `#i` -> jump to scope
+    /// ```
+    ///
+    /// Connect the definition to the scope in which the generic is visible, and add edges from
+    /// `body` to the members of the generic.  Type parameters resolve against the scope stack
+    /// that the definition sets up, so references to them must be reached from `body` without
+    /// passing other _pop scoped symbol_ nodes, which would replace the scope stack.  Inside
+    /// nested instantiations, such as `List<T>` in the body of another generic, they do resolve
+    /// correctly, because the instantiation attaches the current scope stack as well.
+    ///
+    /// ```
+    /// # use stack_graphs::graph::StackGraph;
+    /// let mut graph = StackGraph::new();
+    /// let file = graph.get_or_create_file("List.java");
+    /// let root = StackGraph::root_node();
+    ///
+    /// // class List<T> { ... }
+    /// let list = graph.add_symbol("List");
+    /// let t = graph.add_symbol("T");
+    /// let generic = graph.add_generic_definition(file, list, &[t]);
+    /// graph.add_edge(root, generic.definition, 0);
+    ///
+    /// // List<Foo>
+    /// let foo = graph.add_symbol("Foo");
+    /// let foo_id = graph.new_node_id(file);
+    /// let foo_ref = graph.add_push_symbol_node(foo_id, foo, false).unwrap();
+    /// graph.add_edge(foo_ref, root, 0);
+    /// let list_of_foo = graph
+    ///     .instantiate(file, list)
+    ///     .with_argument(foo_ref)
+    ///     .build(root)
+    ///     .unwrap();
+    /// # assert!(graph[list_of_foo.scope].is_exported_scope());
+    /// ```
+    pub fn add_generic_definition(
+        &mut self,
+        file: Handle<File>,
+        symbol: Handle<Symbol>,
+        parameters: &[Handle<Symbol>],
+    ) -> GenericDefinition {
+        let definition_id = self.new_node_id(file);
+        let definition = self
+            .add_pop_scoped_symbol_node(definition_id, symbol, true)
+            .expect("new node ID is unused");
+        let body_id = self.new_node_id(file);
+        let body = self
+            .add_scope_node(body_id, false)
+            .expect("new node ID is unused");
+        self.add_edge(definition, body, 0);
+
+        let parameters_id = self.new_node_id(file);
+        let parameters_scope = self
+            .add_scope_node(parameters_id, false)
+            .expect("new node ID is unused");
+        let mut parameter_nodes = Vec::with_capacity(parameters.len());
+        for (index, parameter) in parameters.iter().enumerate() {
+            let pop_id = self.new_node_id(file);
+            let pop = self
+                .add_pop_symbol_node(pop_id, *parameter, false)
+                .expect("new node ID is unused");
+            let argument = self.type_argument_symbol(index);
+            let push_id = self.new_node_id(file);
+            let push = self
+                .add_push_symbol_node(push_id, argument, false)
+                .expect("new node ID is unused");
+            self.add_edge(parameters_scope, pop, 0);
+            self.add_edge(pop, push, 0);
+            self.add_edge(push, Self::jump_to_node(), 0);
+            parameter_nodes.push(pop);
+        }
+
+        GenericDefinition {
+            definition,
+            body,
+            parameters: parameters_scope,
+            parameter_nodes,
+        }
+    }
+
+    /// Returns a builder for an instantiation of a generic, such as `List<Foo>`, with the
+    /// given symbol.  Add the type arguments with [`with_argument`][Instantiation::with_argument],
+    /// and the nodes with [`build`][Instantiation::build].  See
+    /// [`add_generic_definition`][Self::add_generic_definition] for how instantiations and
+    /// generic definitions fit together.
+    pub fn instantiate(&mut self, file: Handle<File>, symbol: Handle<Symbol>) -> Instantiation<'_> {
+        Instantiation {
+            graph: self,
+            file,
+            symbol,
+            is_reference: false,
+            arguments: Vec::new(),
+        }
+    }
+}
+
+/// A builder for the nodes of an instantiation of a generic, created by
+/// [`StackGraph::instantiate`][].
+pub struct Instantiation<'a> {
+    graph: &'a mut StackGraph,
+    file: Handle<File>,
+    symbol: Handle<Symbol>,
+    is_reference: bool,
+    arguments: Vec<Handle<Node>>,
+}
+
+impl Instantiation<'_> {
+    /// Adds the next type argument.  Resolution of the argument continues at the given node,
+    /// which is usually a reference to the argument type, or the node of another instantiation
+    /// for nested generics, such as `List<List<Foo>>`.
+    pub fn with_argument(mut self, argument: Handle<Node>) -> Self {
+        self.arguments.push(argument);
+        self
+    }
+
+    /// Marks the _push scoped symbol_ node of the instantiation as a reference.
+    pub fn with_reference(mut self, is_reference: bool) -> Self {
+        self.is_reference = is_reference;
+        self
+    }
+
+    /// Adds the nodes of the instantiation to the stack graph, with an edge from the _push scoped
+    /// symbol_ node to `target`, where resolution of the generic continues.  The attached scope
+    /// has an edge to a node that pops the [symbol][StackGraph::type_argument_symbol] of the
+    /// position of each type argument, which continues to the argument.  The arguments and
+    /// `target` must belong to the file of the instantiation, or be the root or jump to scope
+    /// node.
+    pub fn build(
+        self,
+        target: Handle<Node>,
+    ) -> Result<PushScopedSymbolWithScope, PushScopedSymbolError> {
+        let Self {
+            graph,
+            file,
+            symbol,
+            is_reference,
+            arguments,
+        } = self;
+        for &node in arguments.iter().chain(std::iter::once(&target)) {
+            if graph[node].file().is_some_and(|f| f != file) {
+                return Err(PushScopedSymbolError::NodeInDifferentFile(node));
+            }
+        }
+        let mut positions = Vec::with_capacity(arguments.len());
+        for (index, argument) in arguments.iter().enumerate() {
+            let symbol = graph.type_argument_symbol(index);
+            let id = graph.new_node_id(file);
+            let position = graph
+                .add_pop_symbol_node(id, symbol, false)
+                .expect("new node ID is unused");
+            graph.add_edge(position, *argument, 0);
+            positions.push(position);
+        }
+        graph.add_push_scoped_symbol_with_scope(file, symbol, is_reference, &positions, target)
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Root domains
+
+/// The nodes created by [`StackGraph::add_root_domain`][], which act as the root node of a root
+/// domain in a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RootDomain {
+    /// The _push symbol_ node that enters the root domain.  Add edges to it where a file would
+    /// otherwise add edges to the root node, such as from its imports.
+    pub entry: Handle<Node>,
+    /// The _pop symbol_ node that leaves the root domain.  Add edges from it where a file would
+    /// otherwise add edges from the root node, such as to its exports.
+    pub exit: Handle<Node>,
+}
+
+impl StackGraph {
+    /// Returns the symbol of the root domain with the given name, which is a symbol in the
+    /// [`ROOT_DOMAIN_NAMESPACE`][].
+    pub fn root_domain_symbol<S: AsRef<str> + ?Sized>(&mut self, domain: &S) -> Handle<Symbol> {
+        self.add_namespaced_symbol(domain, ROOT_DOMAIN_NAMESPACE)
+    }
+
+    /// Returns the name of the root domain of a symbol, or `None` if the symbol is not a
+    /// [root domain symbol][Self::root_domain_symbol].
+    pub fn root_domain(&self, symbol: Handle<Symbol>) -> Option<&str> {
+        if self.symbol_namespace(symbol) != Some(ROOT_DOMAIN_NAMESPACE) {
+            return None;
+        }
+        Some(&self[symbol])
+    }
+
+    /// Adds the nodes that connect a file to the root domain with the given name.  Root domains
+    /// isolate parts of a graph that would otherwise meet at the root node, such as the packages
+    /// of different package registries in a multi-repository graph.  Every root domain behaves
+    /// like a separate root node: paths that enter a root domain can only leave it in the same
+    /// root domain, and never reach exports to the root node itself, or to other root domains.
+    /// Files that do not use root domains connect to the root node as before, and are not
+    /// affected.
+    ///
+    /// Root domains are keyed slices of the singleton root node.  The entry node pushes the
+    /// [symbol][Self::root_domain_symbol] of the root domain before continuing to the root node,
+    /// and the exit node pops it after coming from the root node:
+    ///
+    /// ``` text
+    /// entry (push `domain`) -> root -> exit (pop `domain`)
+    /// ```
+    ///
+    /// The symbol of the root domain is therefore on top of the symbol stack whenever a path
+    /// crosses the root node, and partial paths from the root node are indexed by it, both by
+    /// [`Database`][crate::stitching::Database] and by
+    /// [`SQLiteReader`][crate::storage::SQLiteReader], which only loads the partial paths of the
+    /// root domain that a path enters.  Add the nodes once per file and root domain, and reuse
+    /// them for all edges of the file into and out of the root domain.
+    ///
+    /// ```
+    /// # use stack_graphs::graph::StackGraph;
+    /// let mut graph = StackGraph::new();
+    /// let file = graph.get_or_create_file("index.js");
+    /// let npm = graph.add_root_domain(file, "npm");
+    ///
+    /// // export const x = ...
+    /// let x = graph.add_symbol("x");
+    /// let x_id = graph.new_node_id(file);
+    /// let x_def = graph.add_pop_symbol_node(x_id, x, true).unwrap();
+    /// graph.add_edge(npm.exit, x_def, 0);
+    /// # assert_eq!(Some("npm"), graph[npm.exit].symbol().and_then(|s| graph.root_domain(s)));
+    /// ```
+    pub fn add_root_domain<S: AsRef<str> + ?Sized>(
+        &mut self,
+        file: Handle<File>,
+        domain: &S,
+    ) -> RootDomain {
+        let symbol = self.root_domain_symbol(domain);
+        let entry_id = self.new_node_id(file);
+        let entry = self
+            .add_push_symbol_node(entry_id, symbol, false)
+            .expect("new node ID is unused");
+        let exit_id = self.new_node_id(file);
+        let exit = self
+            .add_pop_symbol_node(exit_id, symbol, false)
+            .expect("new node ID is unused");
+        self.add_edge(entry, Self::root_node(), 0);
+        self.add_edge(Self::root_node(), exit, 0);
+        RootDomain { entry, exit }
+    }
+}
+
 impl PushScopedSymbolNode {
     pub fn display<'a>(&'a self, graph: &'a StackGraph) -> impl Display + 'a {
         DisplayPushScopedSymbolNode {
@@ -1292,9 +2020,68 @@ pub struct Edge {
     pub precedence: i32,
 }
 
+/// The direction in which edges are followed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeDirection {
+    /// Follow edges from their source to their sink.
+    Forward,
+    /// Follow edges from their sink to their source.
+    Backward,
+}
+
+/// The visibility class of an edge, for languages that restrict which symbols are visible across
+/// module boundaries.  The graph only records the class: whether a path can traverse an edge of a
+/// particular class is decided at query time by a
+/// [`VisibilityPolicy`][crate::stitching::VisibilityPolicy].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Visibility {
+    /// The edge can be traversed from anywhere.  This is the visibility of edges that have no
+    /// explicit visibility.
+    #[default]
+    Public,
+    /// The edge can be traversed from within the same package, module, or assembly.
+    Internal,
+    /// The edge can be traversed from within the same type and its subtypes.
+    Protected,
+    /// The edge can only be traversed from within the same type or file.
+    Private,
+}
+
+impl Visibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Internal => "internal",
+            Self::Protected => "protected",
+            Self::Private => "private",
+        }
+    }
+}
+
+impl std::fmt::Display for Visibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Visibility {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "public" => Ok(Self::Public),
+            "internal" => Ok(Self::Internal),
+            "protected" => Ok(Self::Protected),
+            "private" => Ok(Self::Private),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
 pub(crate) struct OutgoingEdge {
     sink: Handle<Node>,
     precedence: i32,
+    visibility: Visibility,
 }
 
 impl StackGraph {
@@ -1302,7 +2089,14 @@ impl StackGraph {
     pub fn add_edge(&mut self, source: Handle<Node>, sink: Handle<Node>, precedence: i32) {
         let edges = &mut self.outgoing_edges[source];
         if let Err(index) = edges.binary_search_by_key(&sink, |o| o.sink) {
-            edges.insert(index, OutgoingEdge { sink, precedence });
+            edges.insert(
+                index,
+                OutgoingEdge {
+                    sink,
+                    precedence,
+                    visibility: Visibility::Public,
+                },
+            );
             self.incoming_edges[sink] += Degree::One;
         }
     }
@@ -1320,6 +2114,31 @@ impl StackGraph {
         }
     }
 
+    /// Sets the visibility class of the given edge.
+    pub fn set_edge_visibility(
+        &mut self,
+        source: Handle<Node>,
+        sink: Handle<Node>,
+        visibility: Visibility,
+    ) {
+        let edges = &mut self.outgoing_edges[source];
+        if let Ok(index) = edges.binary_search_by_key(&sink, |o| o.sink) {
+            edges[index].visibility = visibility;
+        }
+    }
+
+    /// Returns the visibility class of the given edge.  Edges that do not exist are public.
+    pub fn edge_visibility(&self, source: Handle<Node>, sink: Handle<Node>) -> Visibility {
+        let edges = match self.outgoing_edges.get(source) {
+            Some(edges) => edges,
+            None => return Visibility::Public,
+        };
+        match edges.binary_search_by_key(&sink, |o| o.sink) {
+            Ok(index) => edges[index].visibility,
+            Err(_) => Visibility::Public,
+        }
+    }
+
     /// Returns an iterator of all of the edges that begin at a particular source node.
     pub fn outgoing_edges(&self, source: Handle<Node>) -> impl Iterator<Item = Edge> + '_ {
         match self.outgoing_edges.get(source) {
@@ -1334,7 +2153,187 @@ impl StackGraph {
 
     /// Returns the number of edges that end at a particular sink node.
     pub fn incoming_edge_degree(&self, sink: Handle<Node>) -> Degree {
-        self.incoming_edges[sink]
+        self.incoming_edges
+            .get(sink)
+            .copied()
+            .unwrap_or(Degree::Zero)
+    }
+
+    /// Attaches a symbol stack key to an edge into or out of the root node.  Paths can only
+    /// traverse the edge if their symbol stack starts with the symbols of the key.  This lets
+    /// path finding skip most of the edges out of the root node, instead of trying every one of
+    /// them.  Keys of edges that are not connected to the root node are ignored.  Keys only
+    /// constrain the names of symbols: the namespaces of the key's symbols are ignored.
+    pub fn set_root_edge_key(
+        &mut self,
+        source: Handle<Node>,
+        sink: Handle<Node>,
+        key: &[Handle<Symbol>],
+    ) {
+        if !self[source].is_root() && !self[sink].is_root() {
+            return;
+        }
+        let key = key
+            .iter()
+            .map(|symbol| self.symbol_without_namespace(*symbol))
+            .collect::<Box<[_]>>();
+        let keys = &mut self.root_edge_keys[source];
+        match keys.binary_search_by_key(&sink, |k| k.0) {
+            Ok(index) => keys[index].1 = key,
+            Err(index) => keys.insert(index, (sink, key)),
+        }
+    }
+
+    /// Returns the symbol stack key of an edge into or out of the root node, if it has one.  The
+    /// symbols of the key do not belong to a namespace.
+    pub fn root_edge_key(
+        &self,
+        source: Handle<Node>,
+        sink: Handle<Node>,
+    ) -> Option<&[Handle<Symbol>]> {
+        let keys = self.root_edge_keys.get(source)?;
+        match keys.binary_search_by_key(&sink, |k| k.0) {
+            Ok(index) => Some(&keys[index].1),
+            Err(_) => None,
+        }
+    }
+
+    /// Excludes symbols from the paths that pass through a scope node.  Paths can only reach the
+    /// node if the symbol on top of their symbol stack is not one of the excluded symbols.  Like
+    /// root edge keys, exclusions only constrain the names of symbols: the namespaces of the
+    /// excluded symbols are ignored.  Setting an empty list of symbols removes the exclusions of
+    /// the node.  Exclusions of nodes that are not scope nodes are ignored.
+    ///
+    /// Exclusions express wildcard re-exports, such as Python's `from m import *` or JavaScript's
+    /// `export * from "m"`, without enumerating the re-exported symbols.  Add a single edge from
+    /// the exported scope of the re-exporting module to the exported scope of the re-exported
+    /// module, and exclude the symbols that the wildcard does not re-export, such as private or
+    /// default exports, on the exported scope at the start of that edge.  Paths then look up any
+    /// symbol in the re-exported module, and only the symbols that are actually referenced are
+    /// ever considered.  The alternative, a pop and a push node for every re-exported symbol,
+    /// needs the re-exported symbols when the re-exporting file is indexed, and creates a fan-out
+    /// that grows with the size of the re-exported module.
+    ///
+    /// Exclusions should only be set on [exported scopes][ExportedScope].  Partial paths are split
+    /// at exported scopes, so that the symbol at the top of the symbol stack is known whenever a
+    /// path is extended through one.  On other nodes, a partial path might pass the node before
+    /// the symbol is known, and the exclusion is not applied.  [`lint`][crate::analysis::lint]
+    /// reports exclusions on internal scopes.
+    ///
+    /// [ExportedScope]: Node::is_exported_scope
+    pub fn set_excluded_symbols(&mut self, node: Handle<Node>, symbols: &[Handle<Symbol>]) {
+        if !matches!(self[node], Node::Scope(_)) {
+            return;
+        }
+        let mut symbols = symbols
+            .iter()
+            .map(|symbol| self.symbol_without_namespace(*symbol))
+            .collect::<Vec<_>>();
+        symbols.sort_unstable();
+        symbols.dedup();
+        self.excluded_symbols[node] = symbols.into_boxed_slice();
+    }
+
+    /// Returns the symbols that are excluded from the paths that pass through a node, if it has
+    /// any.  The symbols do not belong to a namespace.
+    pub fn excluded_symbols(&self, node: Handle<Node>) -> Option<&[Handle<Symbol>]> {
+        self.excluded_symbols
+            .get(node)
+            .map(|symbols| &symbols[..])
+            .filter(|symbols| !symbols.is_empty())
+    }
+
+    /// Returns whether a symbol is excluded from the paths that pass through a node.  Symbols are
+    /// compared by name, using the graph's [symbol normalization][Self::set_symbol_normalization].
+    pub fn is_excluded_symbol(&self, node: Handle<Node>, symbol: Handle<Symbol>) -> bool {
+        let excluded = match self.excluded_symbols(node) {
+            Some(excluded) => excluded,
+            None => return false,
+        };
+        let symbol = self.normalized_symbol(self.symbol_without_namespace(symbol));
+        excluded
+            .iter()
+            .any(|excluded| self.normalized_symbol(*excluded) == symbol)
+    }
+
+    /// Returns an iterator of all of the edges in the graph.
+    pub fn iter_edges(&self) -> impl Iterator<Item = Edge> + '_ {
+        self.iter_nodes()
+            .flat_map(move |source| self.outgoing_edges(source))
+    }
+
+    /// Returns the nodes that can be reached from any of the given nodes by following zero or
+    /// more edges in the given direction.  Only edges for which `filter` returns `true` are
+    /// followed.  Reachability only takes the edges of the graph into account, and ignores the
+    /// symbol and scope stacks.
+    ///
+    /// Because the graph does not store incoming edges, following edges backwards first collects
+    /// the incoming edges of all nodes, which takes time proportional to the size of the graph.
+    pub fn reachable_from<I, F>(
+        &self,
+        nodes: I,
+        direction: EdgeDirection,
+        filter: F,
+    ) -> HandleSet<Node>
+    where
+        I: IntoIterator<Item = Handle<Node>>,
+        F: Fn(&StackGraph, &Edge) -> bool,
+    {
+        let mut incoming_edges = SupplementalArena::<Node, Vec<Edge>>::new();
+        if direction == EdgeDirection::Backward {
+            for edge in self.iter_edges() {
+                incoming_edges[edge.sink].push(edge);
+            }
+        }
+
+        let mut reachable = HandleSet::new();
+        let mut queue = nodes.into_iter().collect::<Vec<_>>();
+        while let Some(node) = queue.pop() {
+            if reachable.contains(node) {
+                continue;
+            }
+            reachable.add(node);
+            match direction {
+                EdgeDirection::Forward => queue.extend(
+                    self.outgoing_edges(node)
+                        .filter(|edge| filter(self, edge))
+                        .map(|edge| edge.sink),
+                ),
+                EdgeDirection::Backward => queue.extend(
+                    incoming_edges
+                        .get(node)
+                        .into_iter()
+                        .flatten()
+                        .filter(|edge| filter(self, edge))
+                        .map(|edge| edge.source),
+                ),
+            }
+        }
+        reachable
+    }
+
+    /// Returns the total number of edges in the graph.
+    pub fn edge_count(&self) -> usize {
+        self.iter_nodes()
+            .map(|source| {
+                self.outgoing_edges
+                    .get(source)
+                    .map_or(0, |edges| edges.len())
+            })
+            .sum()
+    }
+
+    /// Returns an iterator of all of the edges that belong to a particular file.  These are the
+    /// edges whose source node belongs to the file, and the edges from the singleton _root_ node
+    /// whose sink node belongs to the file.
+    pub fn edges_for_file(&self, file: Handle<File>) -> impl Iterator<Item = Edge> + '_ {
+        let from_file = self
+            .nodes_for_file(file)
+            .flat_map(move |source| self.outgoing_edges(source));
+        let from_root = self
+            .outgoing_edges(StackGraph::root_node())
+            .filter(move |edge| self[edge.sink].file() == Some(file));
+        from_file.chain(from_root)
     }
 }
 
@@ -1438,6 +2437,38 @@ impl StackGraph {
     }
 }
 
+//-------------------------------------------------------------------------------------------------
+// Metadata
+
+impl StackGraph {
+    /// Returns the value of a metadata entry of this stack graph.
+    ///
+    /// Metadata is a set of key-value pairs that describe the graph as a whole, such as the
+    /// version of the tool and rules that created it, the commit it was created from, or when it
+    /// was created.  Metadata is serialized with the graph, so that stale or incompatible graphs
+    /// can be detected when they are loaded.
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(String::as_str)
+    }
+
+    /// Sets the value of a metadata entry of this stack graph, replacing any existing value.
+    pub fn set_metadata<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) {
+        self.metadata.insert(key.into(), value.into());
+    }
+
+    /// Removes a metadata entry of this stack graph, returning its value if it was set.
+    pub fn remove_metadata(&mut self, key: &str) -> Option<String> {
+        self.metadata.remove(key)
+    }
+
+    /// Returns an iterator over all metadata entries of this stack graph, ordered by key.
+    pub fn iter_metadata(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.metadata
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
 //-------------------------------------------------------------------------------------------------
 // Stack graphs
 
@@ -1471,11 +2502,23 @@ impl std::ops::AddAssign for Degree {
     }
 }
 
+/// The sink of an edge into or out of the root node, with the symbols that the symbol stack of
+/// paths traversing the edge must start with.
+type RootEdgeKey = (Handle<Node>, Box<[Handle<Symbol>]>);
+
 /// Contains all of the nodes and edges that make up a stack graph.
 pub struct StackGraph {
     interned_strings: InternedStringArena,
     pub(crate) symbols: Arena<Symbol>,
     symbol_handles: FxHashMap<&'static str, Handle<Symbol>>,
+    namespaced_symbol_handles: FxHashMap<SymbolNamespace, Handle<Symbol>>,
+    /// The namespace of every namespaced symbol, and the symbol with the same name without a
+    /// namespace.
+    symbol_namespaces: SupplementalArena<Symbol, Option<SymbolNamespace>>,
+    symbol_interning: SymbolNormalization,
+    symbol_normalization: SymbolNormalization,
+    /// The normalized symbol of every symbol that is not already normalized.
+    normalized_symbols: SupplementalArena<Symbol, Option<Handle<Symbol>>>,
     pub(crate) strings: Arena<InternedString>,
     string_handles: FxHashMap<&'static str, Handle<InternedString>>,
     pub(crate) files: Arena<File>,
@@ -1487,8 +2530,14 @@ pub struct StackGraph {
     incoming_edges: SupplementalArena<Node, Degree>,
     pub(crate) node_debug_info: SupplementalArena<Node, DebugInfo>,
     pub(crate) edge_debug_info: SupplementalArena<Node, SmallVec<[(Handle<Node>, DebugInfo); 4]>>,
+    root_edge_keys: SupplementalArena<Node, SmallVec<[RootEdgeKey; 1]>>,
+    excluded_symbols: SupplementalArena<Node, Box<[Handle<Symbol>]>>,
+    metadata: BTreeMap<String, String>,
 }
 
+/// The namespace of a namespaced symbol, and the symbol with the same name without a namespace.
+type SymbolNamespace = (Handle<InternedString>, Handle<Symbol>);
+
 impl StackGraph {
     /// Creates a new, initially empty stack graph.
     pub fn new() -> StackGraph {
@@ -1496,7 +2545,8 @@ impl StackGraph {
     }
 
     /// Copies the given stack graph into this stack graph. Panics if any of the files
-    /// in the other stack graph are already defined in the current one.
+    /// in the other stack graph are already defined in the current one.  The metadata of the
+    /// other graph is not copied.
     pub fn add_from_graph(
         &mut self,
         other: &StackGraph,
@@ -1547,7 +2597,7 @@ impl StackGraph {
                         ..
                     }) => PopScopedSymbolNode {
                         id: NodeID::new_in_file(file, id.local_id),
-                        symbol: self.add_symbol(&other[symbol]),
+                        symbol: self.add_symbol_from(other, symbol),
                         _scope: NodeID::default(),
                         is_definition: is_definition,
                     }
@@ -1559,7 +2609,7 @@ impl StackGraph {
                         ..
                     }) => PopSymbolNode {
                         id: NodeID::new_in_file(file, id.local_id),
-                        symbol: self.add_symbol(&other[symbol]),
+                        symbol: self.add_symbol_from(other, symbol),
                         _scope: NodeID::default(),
                         is_definition: is_definition,
                     }
@@ -1572,7 +2622,7 @@ impl StackGraph {
                         ..
                     }) => PushScopedSymbolNode {
                         id: NodeID::new_in_file(file, id.local_id),
-                        symbol: self.add_symbol(&other[symbol]),
+                        symbol: self.add_symbol_from(other, symbol),
                         scope: node_id(scope),
                         is_reference: is_reference,
                         _phantom: (),
@@ -1585,7 +2635,7 @@ impl StackGraph {
                         ..
                     }) => PushSymbolNode {
                         id: NodeID::new_in_file(file, id.local_id),
-                        symbol: self.add_symbol(&other[symbol]),
+                        symbol: self.add_symbol_from(other, symbol),
                         _scope: NodeID::default(),
                         is_reference: is_reference,
                     }
@@ -1638,14 +2688,31 @@ impl StackGraph {
                             .collect::<Vec<_>>(),
                     };
                 }
+                if let Some(excluded) = other.excluded_symbols(other_node) {
+                    let excluded = excluded
+                        .iter()
+                        .map(|symbol| self.add_symbol_from(other, *symbol))
+                        .collect::<Vec<_>>();
+                    self.set_excluded_symbols(node, &excluded);
+                }
             }
             for other_node in nodes.keys().cloned() {
                 for other_edge in other.outgoing_edges(other_node) {
-                    self.add_edge(
-                        nodes[&other_edge.source],
-                        nodes[&other_edge.sink],
-                        other_edge.precedence,
+                    let source = nodes[&other_edge.source];
+                    let sink = nodes[&other_edge.sink];
+                    self.add_edge(source, sink, other_edge.precedence);
+                    self.set_edge_visibility(
+                        source,
+                        sink,
+                        other.edge_visibility(other_edge.source, other_edge.sink),
                     );
+                    if let Some(key) = other.root_edge_key(other_edge.source, other_edge.sink) {
+                        let key = key
+                            .iter()
+                            .map(|symbol| self.add_symbol_from(other, *symbol))
+                            .collect::<Vec<_>>();
+                        self.set_root_edge_key(source, sink, &key);
+                    }
                 }
             }
         }
@@ -1663,6 +2730,11 @@ impl Default for StackGraph {
             interned_strings: InternedStringArena::new(),
             symbols: Arena::new(),
             symbol_handles: FxHashMap::default(),
+            namespaced_symbol_handles: FxHashMap::default(),
+            symbol_namespaces: SupplementalArena::new(),
+            symbol_interning: SymbolNormalization::default(),
+            symbol_normalization: SymbolNormalization::default(),
+            normalized_symbols: SupplementalArena::new(),
             strings: Arena::new(),
             string_handles: FxHashMap::default(),
             files: Arena::new(),
@@ -1674,6 +2746,9 @@ impl Default for StackGraph {
             incoming_edges: SupplementalArena::new(),
             node_debug_info: SupplementalArena::new(),
             edge_debug_info: SupplementalArena::new(),
+            root_edge_keys: SupplementalArena::new(),
+            excluded_symbols: SupplementalArena::new(),
+            metadata: BTreeMap::new(),
         }
     }
 }
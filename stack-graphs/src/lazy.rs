@@ -0,0 +1,142 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Shared implementation of readers that load graphs and partial paths on demand, such as the
+//! archive and `.sgdb` readers.
+//!
+//! A reader keeps an index of the stored paths: the paths that start at a node are looked up by
+//! the node, and the paths that start at the root node by the symbols of their symbol stack
+//! precondition.  Root paths are indexed by a sorted list of keys, each consisting of the symbols
+//! of the precondition and whether the precondition has a variable.
+
+use std::cmp::Ordering;
+
+use crate::arena::Handle;
+use crate::graph::Degree;
+use crate::graph::StackGraph;
+use crate::partial::PartialPath;
+use crate::partial::PartialPaths;
+use crate::stitching::Database;
+use crate::stitching::ForwardCandidates;
+use crate::CancellationFlag;
+
+/// A reader that loads the partial paths that can extend a path on demand into its own graph,
+/// partial paths arena, and database.  All such readers implement [`ForwardCandidates`][].
+pub trait PathLoader {
+    type Error;
+
+    /// Loads the partial paths that can extend the given path, and the graphs of the files they
+    /// refer to.
+    fn load_partial_path_extensions(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), Self::Error>;
+
+    /// Returns the graph, partial paths arena, and database of the loaded data.
+    fn loaded(&mut self) -> (&StackGraph, &mut PartialPaths, &mut Database);
+
+    /// Returns the database of the loaded data.
+    fn loaded_database(&self) -> &Database;
+}
+
+impl<L> ForwardCandidates<Handle<PartialPath>, PartialPath, Database, L::Error> for L
+where
+    L: PathLoader,
+{
+    fn load_forward_candidates(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), L::Error> {
+        self.load_partial_path_extensions(path, cancellation_flag)
+    }
+
+    fn get_forward_candidates<R>(&mut self, path: &PartialPath, result: &mut R)
+    where
+        R: std::iter::Extend<Handle<PartialPath>>,
+    {
+        let (graph, partials, db) = self.loaded();
+        db.find_candidate_partial_paths(graph, partials, path, result);
+    }
+
+    fn get_joining_candidate_degree(&self, path: &PartialPath) -> Degree {
+        self.loaded_database()
+            .get_incoming_path_degree(path.end_node)
+    }
+
+    fn get_graph_partials_and_db(&mut self) -> (&StackGraph, &mut PartialPaths, &Database) {
+        let (graph, partials, db) = self.loaded();
+        (graph, partials, db)
+    }
+}
+
+/// Returns the symbols of the symbol stack postcondition of a path that ends at the root node,
+/// and whether the postcondition has a variable.  These are used to look up the root paths that
+/// can extend the path with [`compatible_root_paths`][].
+pub(crate) fn root_path_query<'a>(
+    graph: &'a StackGraph,
+    partials: &mut PartialPaths,
+    path: &PartialPath,
+) -> (Vec<&'a str>, bool) {
+    let mut symbol_stack = path.symbol_stack_postcondition;
+    let has_variable = symbol_stack.has_variable();
+    let mut symbols = Vec::new();
+    while let Some(symbol) = symbol_stack.pop_front(partials) {
+        symbols.push(&graph[symbol.symbol]);
+    }
+    (symbols, has_variable)
+}
+
+/// Returns the indices of the entries of a root path index whose symbol stack precondition is
+/// compatible with the given symbol stack: paths with a variable whose symbols are a non-empty
+/// prefix of the given symbols, paths with exactly the given symbols, and, if the given symbol
+/// stack has a variable, paths whose symbols extend the given symbols.  The entries must be
+/// sorted by their key, which is returned by the `key` function.
+pub(crate) fn compatible_root_paths<'e, E, K, I>(
+    entries: &'e [E],
+    key: K,
+    symbols: &[&'e str],
+    has_variable: bool,
+) -> Vec<usize>
+where
+    K: Fn(&'e E) -> (I, bool),
+    I: Iterator<Item = &'e str>,
+{
+    let compare = |entry: &'e E, symbols: &[&'e str], has_variable: bool| -> Ordering {
+        let (entry_symbols, entry_has_variable) = key(entry);
+        entry_symbols
+            .cmp(symbols.iter().copied())
+            .then(entry_has_variable.cmp(&has_variable))
+    };
+    let find = |symbols: &[&'e str], has_variable: bool| {
+        entries
+            .binary_search_by(|entry| compare(entry, symbols, has_variable))
+            .ok()
+    };
+    let mut indices = Vec::new();
+    for length in 1..=symbols.len() {
+        indices.extend(find(&symbols[..length], true));
+    }
+    indices.extend(find(symbols, false));
+    if has_variable {
+        // the first entry whose symbols are greater than the given symbols
+        let start = entries
+            .binary_search_by(|entry| match key(entry).0.le(symbols.iter().copied()) {
+                true => Ordering::Less,
+                false => Ordering::Greater,
+            })
+            .unwrap_err();
+        indices.extend((start..entries.len()).take_while(|index| {
+            let mut entry_symbols = key(&entries[*index]).0;
+            symbols.iter().all(|s| entry_symbols.next() == Some(*s))
+        }));
+    }
+    indices.sort_unstable();
+    indices.dedup();
+    indices
+}
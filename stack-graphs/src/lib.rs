@@ -54,25 +54,104 @@
 //! reference to `A.foo` with a _single_ execution of the path-finding algorithm.  And most
 //! importantly, each “chunk” of the overall graph only depends on “local” information from the
 //! original source file.  (a.k.a., it’s incremental!)
+//!
+//! ## Crate features
+//!
+//! The core data structures and algorithms of this crate do not depend on any I/O, parsing, or
+//! serialization libraries.  Other subsystems are enabled with the following features:
+//!
+//! - `analysis`: linting graphs, and measuring and comparing resolutions, in the `analysis`
+//!   module.
+//! - `events`: building graphs from a stream of graph events, in the `events` module.
+//! - `query`: structural queries over the nodes of a graph, and hover information, in the `query`
+//!   module.
+//! - `snapshot`: normalized text dumps of graphs and paths for snapshot tests, in the `snapshot`
+//!   module.
+//! - `source`: excerpts of source code in visualizations and assertion failures, in the `source`
+//!   module.
+//! - `trace`: recording and replaying the decisions made during path stitching, in the `trace`
+//!   module.  This implies `source`.
+//! - `serde`: serialization of graphs and paths with [serde][], in the [`serde`][mod@serde]
+//!   module.
+//! - `schema`: JSON Schemas of the serialization formats, in the `serde::schema` module.
+//! - `bincode`: binary encoding of graphs and paths with [bincode][].
+//! - `archive`: a flat format for graphs and paths that is read without deserialization, in the
+//!   `archive` module.
+//! - `proto`: encoding of graphs and paths as protocol buffers, in the `proto` module.
+//! - `export`: exporting resolved references as LSIF dumps or SCIP indexes, in the `export`
+//!   module.
+//! - `arrow`: writing resolution results and database statistics as Arrow record batches and
+//!   Parquet files, in the `export::arrow` module.  This implies `export` and `storage`.
+//! - `storage`: storing graphs and paths in SQLite databases, in the `storage` module, or in
+//!   single append-only files, in the `sgdb` module.
+//! - `visualization`: rendering graphs and paths as HTML, in the `visualization` module.  This
+//!   implies `source` and `trace`.
+//! - `lsp-types`: conversion of query results into [`lsp_types`][lsp-types] values, in the `lsp`
+//!   module.
+//! - `checked`: `try_` variants of operations that return violated invariants as errors instead
+//!   of panicking, with the errors in the `checked` module.
+//! - `copious-debugging`: very verbose debug output during path stitching.
+//! - `fuzzing`: generating random graphs and checking the invariants of path operations on them,
+//!   in the `fuzzing` module, which is used by the fuzz targets of the crate.
+//!
+//! Stack graphs for source code are constructed from tree-sitter parse trees by the
+//! [`tree-sitter-stack-graphs`][tssg] crate, which also provides the command-line programs.
+//!
+//! [serde]: https://docs.rs/serde/
+//! [bincode]: https://docs.rs/bincode/
+//! [lsp-types]: https://docs.rs/lsp-types/
+//! [tssg]: https://docs.rs/tree-sitter-stack-graphs/
+
+#![forbid(unsafe_op_in_unsafe_fn)]
 
 use std::time::{Duration, Instant};
 
 use thiserror::Error;
 
+#[cfg(feature = "analysis")]
+pub mod analysis;
+#[cfg(feature = "archive")]
+pub mod archive;
 pub mod arena;
 pub mod assert;
 pub mod c;
+#[cfg(feature = "checked")]
+pub mod checked;
 pub mod cycles;
 #[macro_use]
 mod debugging;
+#[cfg(feature = "events")]
+pub mod events;
+#[cfg(feature = "export")]
+pub mod export;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
 pub mod graph;
+#[cfg(any(feature = "archive", feature = "storage"))]
+mod lazy;
+#[cfg(feature = "lsp-types")]
+pub mod lsp;
+pub mod observer;
 pub mod partial;
 pub mod paths;
+#[cfg(feature = "proto")]
+pub mod proto;
+#[cfg(feature = "query")]
+pub mod query;
 pub mod serde;
+#[cfg(feature = "storage")]
+pub mod sgdb;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+#[cfg(feature = "source")]
+pub mod source;
+mod spill;
 pub mod stats;
 pub mod stitching;
 #[cfg(feature = "storage")]
 pub mod storage;
+#[cfg(feature = "trace")]
+pub mod trace;
 pub(crate) mod utils;
 #[cfg(feature = "visualization")]
 pub mod visualization;
@@ -115,3 +194,52 @@ impl CancellationFlag for CancelAfterDuration {
 #[derive(Clone, Debug, Error)]
 #[error("Cancelled at \"{0}\"")]
 pub struct CancellationError(pub &'static str);
+
+/// An error from any of the subsystems of this crate.  All error types of the crate convert into
+/// this type, so that applications can use `?` across graph construction, path finding, and
+/// storage, and wrap it in error handling libraries such as `anyhow`.  The message and source of
+/// the original error are preserved, and the original error can be recovered by matching on the
+/// variant.
+#[derive(Debug, Error)]
+pub enum StackGraphError {
+    #[cfg(feature = "archive")]
+    #[error(transparent)]
+    Archive(#[from] archive::ArchiveError),
+    #[error(transparent)]
+    Assertion(Box<assert::AssertionError>),
+    #[error(transparent)]
+    Cancelled(#[from] CancellationError),
+    #[cfg(feature = "events")]
+    #[error(transparent)]
+    GraphEvent(#[from] events::GraphEventError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    PathResolution(#[from] paths::PathResolutionError),
+    #[cfg(feature = "proto")]
+    #[error(transparent)]
+    Proto(#[from] proto::Error),
+    #[error(transparent)]
+    PushScopedSymbol(#[from] graph::PushScopedSymbolError),
+    #[error(transparent)]
+    Serde(#[from] serde::Error),
+    #[cfg(feature = "storage")]
+    #[error(transparent)]
+    Storage(#[from] storage::StorageError),
+    #[cfg(feature = "serde_json")]
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[cfg(feature = "arrow")]
+    #[error(transparent)]
+    Arrow(#[from] arrow_schema::ArrowError),
+    #[cfg(feature = "arrow")]
+    #[error(transparent)]
+    Parquet(#[from] parquet::errors::ParquetError),
+}
+
+// Assertion errors are boxed to keep results of this type small.
+impl From<assert::AssertionError> for StackGraphError {
+    fn from(value: assert::AssertionError) -> Self {
+        Self::Assertion(Box::new(value))
+    }
+}
@@ -0,0 +1,104 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Converts stack graph nodes and query results into [`lsp_types`][] structures.
+//!
+//! File names in the stack graph are interpreted as file system paths, and must be absolute to
+//! be converted into URIs.  Columns are expressed in UTF-16 code units, as LSP requires.
+
+use lsp_types::Location;
+use lsp_types::SymbolInformation;
+use lsp_types::SymbolKind;
+use lsp_types::Url;
+
+use crate::arena::Handle;
+use crate::graph::Node;
+use crate::graph::StackGraph;
+use crate::partial::PartialPath;
+
+impl StackGraph {
+    /// Returns the LSP location of a node.  Returns `None` if the node has no source
+    /// information, or does not belong to a file with an absolute path.
+    pub fn lsp_location(&self, node: Handle<Node>) -> Option<Location> {
+        let file = self[node].file()?;
+        let source_info = self.source_info(node)?;
+        let uri = Url::from_file_path(self[file].name()).ok()?;
+        Some(Location {
+            uri,
+            range: (&source_info.span).into(),
+        })
+    }
+
+    /// Returns the LSP locations of the end nodes of the given paths, which for complete paths
+    /// are the definitions the paths resolve to.  Duplicate locations are removed.
+    pub fn lsp_locations_for_paths<'a, I>(&self, paths: I) -> Vec<Location>
+    where
+        I: IntoIterator<Item = &'a PartialPath>,
+    {
+        let mut locations: Vec<Location> = Vec::new();
+        for path in paths {
+            if let Some(location) = self.lsp_location(path.end_node) {
+                if !locations.contains(&location) {
+                    locations.push(location);
+                }
+            }
+        }
+        locations
+    }
+
+    /// Returns LSP symbol information for a definition or reference node.  The symbol kind is
+    /// derived from the node's syntax type, see [`lsp_symbol_kind`][], and the container name
+    /// is taken from the node's fully qualified name, if any.  Returns `None` if the node has no
+    /// symbol or no [location][Self::lsp_location].
+    pub fn lsp_symbol_information(&self, node: Handle<Node>) -> Option<SymbolInformation> {
+        let symbol = self[node].symbol()?;
+        let location = self.lsp_location(node)?;
+        let source_info = self.source_info(node)?;
+        let kind = source_info
+            .syntax_type
+            .into_option()
+            .map(|syntax_type| lsp_symbol_kind(&self[syntax_type]))
+            .unwrap_or(SymbolKind::VARIABLE);
+        let container_name = source_info
+            .fully_qualified_name
+            .into_option()
+            .map(|fqn| self[fqn].to_string());
+        // The deprecated field must still be initialized.
+        #[allow(deprecated)]
+        Some(SymbolInformation {
+            name: self[symbol].to_string(),
+            kind,
+            tags: None,
+            deprecated: None,
+            location,
+            container_name,
+        })
+    }
+}
+
+/// Maps a syntax type, as set by the `syntax_type` attribute in TSG rules, to an LSP symbol
+/// kind.  Unknown syntax types are mapped to [`SymbolKind::VARIABLE`][].
+pub fn lsp_symbol_kind(syntax_type: &str) -> SymbolKind {
+    match syntax_type {
+        "class" => SymbolKind::CLASS,
+        "constant" => SymbolKind::CONSTANT,
+        "constructor" => SymbolKind::CONSTRUCTOR,
+        "enum" => SymbolKind::ENUM,
+        "enum_member" => SymbolKind::ENUM_MEMBER,
+        "field" => SymbolKind::FIELD,
+        "function" => SymbolKind::FUNCTION,
+        "interface" => SymbolKind::INTERFACE,
+        "method" => SymbolKind::METHOD,
+        "module" => SymbolKind::MODULE,
+        "namespace" => SymbolKind::NAMESPACE,
+        "package" => SymbolKind::PACKAGE,
+        "property" => SymbolKind::PROPERTY,
+        "struct" => SymbolKind::STRUCT,
+        "type" | "type_parameter" => SymbolKind::TYPE_PARAMETER,
+        _ => SymbolKind::VARIABLE,
+    }
+}
@@ -0,0 +1,53 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Observe what happens during queries.
+//!
+//! Services that answer queries often want to export metrics about them, such as how many files
+//! are loaded per query, or how long stitching phases take. Implement [`QueryObserver`][] and
+//! register it with a [`ForwardPartialPathStitcher`][crate::stitching::ForwardPartialPathStitcher]
+//! or a [`SQLiteReader`][crate::storage::SQLiteReader] to receive a [`QueryEvent`][] for every
+//! step of a query.
+
+use std::time::Duration;
+
+use crate::stitching::EmptyScopeStack;
+
+/// An event that happened during a query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueryEvent<'a> {
+    /// The graph of a file was loaded from storage.
+    FileLoaded { file: &'a str },
+    /// Partial paths were loaded from storage, to extend paths ending in a node, or in the root
+    /// node if `from_root` is true.
+    PathsLoaded { from_root: bool, count: usize },
+    /// A path was extended. `candidates` is the number of candidate paths that were considered,
+    /// and `extensions` the number of candidates that could be appended to the path.
+    PathExtended {
+        at_root: bool,
+        candidates: usize,
+        extensions: usize,
+    },
+    /// A phase of the stitching algorithm completed. `queued_paths` is the number of paths that
+    /// were queued at the start of the phase, and `processed_paths` the number of candidates that
+    /// were considered during the phase.
+    PhaseCompleted {
+        queued_paths: usize,
+        processed_paths: usize,
+        duration: Duration,
+    },
+    /// A path was dropped, because it jumped to scope while its scope stack was empty. The event
+    /// is only reported if the stitcher
+    /// [collects these paths][crate::stitching::StitcherConfig::with_collect_empty_scope_stacks].
+    EmptyScopeStack { path: &'a EmptyScopeStack },
+}
+
+/// A trait for types that receive [`QueryEvent`][]s. Observers are shared between readers,
+/// stitchers, and threads, and must use interior mutability to record events.
+pub trait QueryObserver: Send + Sync {
+    fn observe(&self, event: &QueryEvent);
+}
@@ -42,10 +42,18 @@ use controlled_option::Niche;
 use enumset::EnumSetType;
 use smallvec::SmallVec;
 
+use crate::arena::ArenaStats;
 use crate::arena::Deque;
 use crate::arena::DequeArena;
+use crate::arena::DequeCompaction;
+use crate::arena::DequeFingerprints;
 use crate::arena::Handle;
+use crate::arena::ReversibleListCell;
+#[cfg(feature = "checked")]
+use crate::checked::InvariantViolation;
 use crate::graph::Edge;
+use crate::graph::EdgeDirection;
+use crate::graph::File;
 use crate::graph::Node;
 use crate::graph::NodeID;
 use crate::graph::StackGraph;
@@ -169,9 +177,27 @@ impl SymbolStackVariable {
     /// have non-overlapping sets of variables.  To do this, we find the maximum value of any
     /// variable on the left-hand side, and add this “offset” to the values of all of the variables
     /// on the right-hand side.
+    ///
+    /// Panics if the offset variable does not fit in a `u32`.
     pub fn with_offset(self, symbol_variable_offset: u32) -> SymbolStackVariable {
-        let offset_value = self.0.get() + symbol_variable_offset;
-        SymbolStackVariable(unsafe { NonZeroU32::new_unchecked(offset_value) })
+        SymbolStackVariable(
+            self.0
+                .checked_add(symbol_variable_offset)
+                .expect("symbol stack variable overflow"),
+        )
+    }
+
+    /// Applies an offset to this variable, or returns an error if the offset variable does not
+    /// fit in a `u32`.
+    #[cfg(feature = "checked")]
+    pub fn try_with_offset(
+        self,
+        symbol_variable_offset: u32,
+    ) -> Result<SymbolStackVariable, InvariantViolation> {
+        self.0
+            .checked_add(symbol_variable_offset)
+            .map(SymbolStackVariable)
+            .ok_or(InvariantViolation::SymbolStackVariableOverflow)
     }
 
     pub(crate) fn as_u32(self) -> u32 {
@@ -231,7 +257,11 @@ impl ScopeStackVariable {
     /// Creates a new scope stack variable that is fresher than all other variables in a partial
     /// path.  (You must calculate the maximum variable number already in use.)
     fn fresher_than(max_used: u32) -> ScopeStackVariable {
-        ScopeStackVariable(unsafe { NonZeroU32::new_unchecked(max_used + 1) })
+        ScopeStackVariable(
+            NonZeroU32::MIN
+                .checked_add(max_used)
+                .expect("scope stack variable overflow"),
+        )
     }
 
     /// Applies an offset to this variable.
@@ -240,9 +270,27 @@ impl ScopeStackVariable {
     /// have non-overlapping sets of variables.  To do this, we find the maximum value of any
     /// variable on the left-hand side, and add this “offset” to the values of all of the variables
     /// on the right-hand side.
+    ///
+    /// Panics if the offset variable does not fit in a `u32`.
     pub fn with_offset(self, scope_variable_offset: u32) -> ScopeStackVariable {
-        let offset_value = self.0.get() + scope_variable_offset;
-        ScopeStackVariable(unsafe { NonZeroU32::new_unchecked(offset_value) })
+        ScopeStackVariable(
+            self.0
+                .checked_add(scope_variable_offset)
+                .expect("scope stack variable overflow"),
+        )
+    }
+
+    /// Applies an offset to this variable, or returns an error if the offset variable does not
+    /// fit in a `u32`.
+    #[cfg(feature = "checked")]
+    pub fn try_with_offset(
+        self,
+        scope_variable_offset: u32,
+    ) -> Result<ScopeStackVariable, InvariantViolation> {
+        self.0
+            .checked_add(scope_variable_offset)
+            .map(ScopeStackVariable)
+            .ok_or(InvariantViolation::ScopeStackVariableOverflow)
     }
 
     pub(crate) fn as_u32(self) -> u32 {
@@ -280,6 +328,17 @@ impl TryFrom<u32> for ScopeStackVariable {
     }
 }
 
+//-------------------------------------------------------------------------------------------------
+// Fingerprints
+
+/// Mixes the bits of a value, so that similar inputs (such as consecutive handles) produce very
+/// different fingerprints.  This is the finalizer of the SplitMix64 generator.
+fn mix_fingerprint(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+    value ^ (value >> 31)
+}
+
 //-------------------------------------------------------------------------------------------------
 // Partial symbol stacks
 
@@ -432,6 +491,12 @@ impl DisplayWithPartialPaths for PartialScopedSymbol {
 
 /// A pattern that might match against a symbol stack.  Consists of a (possibly empty) list of
 /// partial scoped symbols, along with an optional symbol stack variable.
+///
+/// The symbols are stored in the deque arena of a [`PartialPaths`][], even for short stacks.
+/// This type has the same layout as `sg_partial_symbol_stack` in the C API, and C clients read
+/// the symbols of the partial paths in a database or path list from the arena's cell array, so
+/// short stacks cannot be stored inline without breaking the C API.  Pushing a symbol appends a
+/// cell to the arena, which does not allocate for each stack.
 #[repr(C)]
 #[derive(Clone, Copy, Niche)]
 pub struct PartialSymbolStack {
@@ -466,11 +531,62 @@ impl PartialSymbolStack {
         self.variable.is_some()
     }
 
+    /// Returns whether this partial symbol stack can start with the given symbols, ignoring any
+    /// attached scopes and the namespaces of the symbols, and comparing the symbols after the
+    /// graph's [symbol normalization][StackGraph::set_symbol_normalization].  If the stack runs
+    /// out of symbols, it can only start with the remaining symbols if it has a symbol stack
+    /// variable.
+    pub fn can_start_with(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        symbols: &[Handle<Symbol>],
+    ) -> bool {
+        let mut stack = self.iter(partials);
+        for symbol in symbols {
+            match stack.next() {
+                Some(top)
+                    if graph.normalized_symbol(graph.symbol_without_namespace(top.symbol))
+                        == graph.normalized_symbol(graph.symbol_without_namespace(*symbol)) => {}
+                Some(_) => return false,
+                None => return self.variable.is_some(),
+            }
+        }
+        true
+    }
+
+    /// Returns whether the symbol at the top of this partial symbol stack is excluded at the given
+    /// node.  If the top of the stack is not known, because the stack only consists of a symbol
+    /// stack variable, the symbol is not excluded.
+    pub fn is_excluded_at(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        node: Handle<Node>,
+    ) -> bool {
+        if graph.excluded_symbols(node).is_none() {
+            return false;
+        }
+        self.iter(partials)
+            .next()
+            .is_some_and(|top| graph.is_excluded_symbol(node, top.symbol))
+    }
+
     #[inline(always)]
     pub fn len(&self) -> usize {
         self.length as usize
     }
 
+    /// Returns the length of the longest attached scope list of any symbol in this partial symbol
+    /// stack, or 0 if no symbol has attached scopes.
+    pub fn max_attached_scope_list_length(&self, partials: &PartialPaths) -> usize {
+        self.iter_unordered(partials)
+            .filter_map(|symbol| symbol.scopes.into_option())
+            .map(|scopes| scopes.len())
+            .max()
+            .unwrap_or(0)
+    }
+
     /// Returns an empty partial symbol stack.
     pub fn empty() -> PartialSymbolStack {
         PartialSymbolStack {
@@ -603,7 +719,13 @@ impl PartialSymbolStack {
         // so, substitute that binding in.  If not, leave the variable as-is.
         let mut result = match self.variable.into_option() {
             Some(variable) => match symbol_bindings.get(variable) {
-                Some(bound) => bound,
+                // The attached scope lists of the bound symbols can end in variables of their
+                // own, which need the scope bindings applied as well.
+                Some(bound) => bound.apply_partial_bindings(
+                    partials,
+                    &PartialSymbolStackBindings::new(),
+                    scope_bindings,
+                )?,
                 None => PartialSymbolStack::from_variable(variable),
             },
             None => PartialSymbolStack::empty(),
@@ -634,6 +756,40 @@ impl PartialSymbolStack {
     ) -> Result<PartialSymbolStack, PathResolutionError> {
         let mut lhs = self;
 
+        // If neither stack contains any variables, they only unify if they have the same length
+        // and symbols, and the result is the stack itself.  Unless there are attached scopes,
+        // which might contain variables, we can check that directly on the arena cells, without
+        // popping symbols and building a new stack.
+        if lhs.variable.is_none() && rhs.variable.is_none() {
+            if lhs.length != rhs.length {
+                return Err(PathResolutionError::SymbolStackUnsatisfied);
+            }
+            let mut has_scopes = false;
+            let equal = lhs.symbols.equals_with(
+                &mut partials.partial_symbol_stacks,
+                rhs.symbols,
+                |lhs, rhs| {
+                    if lhs.symbol != rhs.symbol {
+                        return false;
+                    }
+                    match (lhs.scopes.into_option(), rhs.scopes.into_option()) {
+                        (None, None) => true,
+                        (Some(_), Some(_)) => {
+                            has_scopes = true;
+                            false
+                        }
+                        _ => false,
+                    }
+                },
+            );
+            if !has_scopes {
+                if !equal {
+                    return Err(PathResolutionError::SymbolStackUnsatisfied);
+                }
+                return Ok(lhs);
+            }
+        }
+
         // First, look at the shortest common prefix of lhs and rhs, and verify that they match.
         let mut head = Deque::empty();
         while lhs.contains_symbols() && rhs.contains_symbols() {
@@ -750,7 +906,51 @@ impl PartialSymbolStack {
         unreachable!();
     }
 
+    /// Returns a fingerprint of this partial symbol stack.  Equal stacks always have the same
+    /// fingerprint, so stacks with different fingerprints are known to be different.  The
+    /// fingerprints of a stack's contents are cached in `partials`, so this is cheap to call
+    /// repeatedly, and as elements are pushed and popped.
+    pub fn fingerprint(&self, partials: &mut PartialPaths) -> u64 {
+        let scope_fingerprints = &mut partials.scope_stack_fingerprints;
+        let scope_stacks = &partials.partial_scope_stacks;
+        let contents = partials.symbol_stack_fingerprints.fingerprint(
+            &partials.partial_symbol_stacks,
+            &self.symbols,
+            |symbol| {
+                let scopes = symbol.scopes.into_option().map_or(0, |scopes| {
+                    scopes.fingerprint_in(scope_fingerprints, scope_stacks) | 1
+                });
+                mix_fingerprint(symbol.symbol.as_u32() as u64) ^ scopes
+            },
+        );
+        mix_fingerprint(contents ^ self.variable.into_option().map_or(0, |v| v.as_u32() as u64))
+    }
+
+    /// Returns the symbols of this partial symbol stack, if none of them have attached scopes.
+    /// The symbols of such stacks can be compared as slices of handles, which needs neither
+    /// mutable access to the arena nor the reversal of either stack.
+    fn unscoped_symbols(&self, partials: &PartialPaths) -> Option<SmallVec<[Handle<Symbol>; 8]>> {
+        let mut symbols = SmallVec::<[PartialScopedSymbol; 8]>::new();
+        self.symbols
+            .copy_into(&partials.partial_symbol_stacks, &mut symbols);
+        if symbols.iter().any(|symbol| symbol.scopes.is_some()) {
+            return None;
+        }
+        Some(symbols.iter().map(|symbol| symbol.symbol).collect())
+    }
+
     pub fn equals(mut self, partials: &mut PartialPaths, mut other: PartialSymbolStack) -> bool {
+        if self.length != other.length || self.fingerprint(partials) != other.fingerprint(partials)
+        {
+            return false;
+        }
+        if let (Some(self_symbols), Some(other_symbols)) = (
+            self.unscoped_symbols(partials),
+            other.unscoped_symbols(partials),
+        ) {
+            return self_symbols == other_symbols
+                && self.variable.into_option() == other.variable.into_option();
+        }
         while let Some(self_symbol) = self.pop_front(partials) {
             if let Some(other_symbol) = other.pop_front(partials) {
                 if !self_symbol.equals(partials, &other_symbol) {
@@ -896,6 +1096,10 @@ impl DisplayWithPartialPaths for PartialSymbolStack {
 
 /// A pattern that might match against a scope stack.  Consists of a (possibly empty) list of
 /// exported scopes, along with an optional scope stack variable.
+///
+/// Like the symbols of a [`PartialSymbolStack`][], the scopes are stored in the deque arena of a
+/// [`PartialPaths`][], because this type has the same layout as `sg_partial_scope_stack` in the
+/// C API.
 #[repr(C)]
 #[derive(Clone, Copy, Niche)]
 pub struct PartialScopeStack {
@@ -1002,12 +1206,10 @@ impl PartialScopeStack {
         scope_bindings: &PartialScopeStackBindings,
     ) -> Result<PartialScopeStack, PathResolutionError> {
         // If this partial scope stack ends in a variable, see if we have a binding for it.  If so,
-        // substitute that binding in.  If not, leave the variable as-is.
+        // substitute that binding in, following any bindings of the variable at the end of the
+        // bound stack.  If not, leave the variable as-is.
         let mut result = match self.variable.into_option() {
-            Some(variable) => match scope_bindings.get(variable) {
-                Some(bound) => bound,
-                None => PartialScopeStack::from_variable(variable),
-            },
+            Some(variable) => scope_bindings.resolve(partials, variable)?,
             None => PartialScopeStack::empty(),
         };
 
@@ -1179,7 +1381,33 @@ impl PartialScopeStack {
         self.variable.into_option()
     }
 
+    /// Returns a fingerprint of this partial scope stack.  Equal stacks always have the same
+    /// fingerprint, so stacks with different fingerprints are known to be different.  The
+    /// fingerprints of a stack's contents are cached in `partials`, so this is cheap to call
+    /// repeatedly, and as elements are pushed and popped.
+    pub fn fingerprint(&self, partials: &mut PartialPaths) -> u64 {
+        self.fingerprint_in(
+            &mut partials.scope_stack_fingerprints,
+            &partials.partial_scope_stacks,
+        )
+    }
+
+    fn fingerprint_in(
+        &self,
+        fingerprints: &mut DequeFingerprints<Handle<Node>>,
+        arena: &DequeArena<Handle<Node>>,
+    ) -> u64 {
+        let contents = fingerprints.fingerprint(arena, &self.scopes, |node| {
+            mix_fingerprint(node.as_u32() as u64)
+        });
+        mix_fingerprint(contents ^ self.variable.into_option().map_or(0, |v| v.as_u32() as u64))
+    }
+
     pub fn equals(self, partials: &mut PartialPaths, other: PartialScopeStack) -> bool {
+        if self.length != other.length || self.fingerprint(partials) != other.fingerprint(partials)
+        {
+            return false;
+        }
         self.scopes
             .equals_with(&mut partials.partial_scope_stacks, other.scopes, |a, b| {
                 *a == *b
@@ -1405,6 +1633,48 @@ impl PartialScopeStackBindings {
         self.bindings[index - 1]
     }
 
+    /// Returns the partial scope stack that a particular scope stack variable stands for.  Unlike
+    /// [`get`][Self::get], this follows the bindings of the variable at the end of each bound
+    /// stack, so that a variable bound to a known prefix and another bound variable resolves to
+    /// the known prefix followed by that variable's binding.  Returns an error if the bindings
+    /// of several variables form a cycle, since no finite scope stack can satisfy them.
+    pub fn resolve(
+        &self,
+        partials: &mut PartialPaths,
+        variable: ScopeStackVariable,
+    ) -> Result<PartialScopeStack, PathResolutionError> {
+        let mut prefixes = SmallVec::<[PartialScopeStack; 4]>::new();
+        let mut tail = PartialScopeStack::from_variable(variable);
+        let mut current = variable;
+        while let Some(bound) = self.get(current) {
+            if prefixes.len() >= self.bindings.len() {
+                return Err(PathResolutionError::ScopeStackUnsatisfied);
+            }
+            prefixes.push(bound);
+            match bound.variable() {
+                // A variable bound to scopes followed by itself is a substitution that prepends
+                // those scopes, and does not refer to the variable's own binding.
+                Some(next) if next == current => break,
+                Some(next) => {
+                    tail = PartialScopeStack::from_variable(next);
+                    current = next;
+                }
+                None => {
+                    tail = PartialScopeStack::empty();
+                    break;
+                }
+            }
+        }
+
+        let mut result = tail;
+        for mut prefix in prefixes.into_iter().rev() {
+            while let Some(scope) = prefix.pop_back(partials) {
+                result.push_front(partials, scope);
+            }
+        }
+        Ok(result)
+    }
+
     /// Adds a new binding from a scope stack variable to the partial scope stack that it matched.  Returns
     /// an error if you try to bind a particular variable more than once.
     pub fn add(
@@ -1763,12 +2033,49 @@ impl PartialPath {
         }
     }
 
+    /// Creates a new empty partial path starting at a stack graph node, like
+    /// [`from_node`][Self::from_node], or returns an error if the node does not belong to the
+    /// graph.
+    #[cfg(feature = "checked")]
+    pub fn try_from_node(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        node: Handle<Node>,
+    ) -> Result<PartialPath, InvariantViolation> {
+        graph.try_node(node)?;
+        Ok(Self::from_node(graph, partials, node))
+    }
+
     /// Returns whether one path shadows another.  Note that shadowing is not commutative — if path
     /// A shadows path B, the reverse is not true.
     pub fn shadows(&self, partials: &mut PartialPaths, other: &PartialPath) -> bool {
         self.edges.shadows(partials, other.edges)
     }
 
+    /// Returns a fingerprint of the start and end nodes, and the pre- and postconditions of this
+    /// partial path.  Paths that are [equal][Self::equals] always have the same fingerprint, so it
+    /// can be used to find candidates for duplicate paths without comparing every pair of paths.
+    pub fn fingerprint(&self, partials: &mut PartialPaths) -> u64 {
+        [
+            self.start_node.as_u32() as u64,
+            self.end_node.as_u32() as u64,
+            self.symbol_stack_precondition.fingerprint(partials),
+            self.symbol_stack_postcondition.fingerprint(partials),
+            self.scope_stack_precondition.fingerprint(partials),
+            self.scope_stack_postcondition.fingerprint(partials),
+        ]
+        .iter()
+        .fold(0, |fingerprint, part| mix_fingerprint(fingerprint ^ part))
+    }
+
+    /// Returns whether this path is [equal][Self::equals] to another one, and has the same edges.
+    /// The [fingerprints][Self::fingerprint] of the paths are compared first.
+    pub fn is_identical(&self, partials: &mut PartialPaths, other: &PartialPath) -> bool {
+        self.fingerprint(partials) == other.fingerprint(partials)
+            && self.equals(partials, other)
+            && self.edges.equals(partials, other.edges)
+    }
+
     pub fn equals(&self, partials: &mut PartialPaths, other: &PartialPath) -> bool {
         self.start_node == other.start_node
             && self.end_node == other.end_node
@@ -1848,6 +2155,48 @@ impl PartialPath {
         graph[self.end_node].is_jump_to()
     }
 
+    /// Returns facts about the route this path takes through the graph, which can be used to
+    /// rank or filter resolved paths.  The `is_builtins` predicate identifies the files that
+    /// contain builtins.
+    pub fn quality<F>(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        is_builtins: F,
+    ) -> PathQuality
+    where
+        F: Fn(Handle<File>) -> bool,
+    {
+        let mut quality = PathQuality::default();
+        let mut max_precedence = None;
+        let mut nodes = vec![graph[self.start_node].id()];
+        for edge in self.edges.iter(partials) {
+            quality.length += 1;
+            max_precedence =
+                Some(max_precedence.map_or(edge.precedence, |p: i32| p.max(edge.precedence)));
+            if nodes.last() != Some(&edge.source_node_id) {
+                nodes.push(edge.source_node_id);
+            }
+        }
+        nodes.push(graph[self.end_node].id());
+        quality.max_precedence = max_precedence.unwrap_or(0);
+        let mut previous_file = None;
+        // Root and jump-to nodes do not belong to a file, and are skipped, so that passing
+        // through the root node from one file to another counts as a single crossing.
+        for file in nodes.into_iter().filter_map(|node| node.file()) {
+            if is_builtins(file) {
+                quality.via_builtins = true;
+            }
+            if previous_file
+                .replace(file)
+                .is_some_and(|previous| previous != file)
+            {
+                quality.file_crossings += 1;
+            }
+        }
+        quality
+    }
+
     /// Returns whether a partial path is cyclic---that is, it starts and ends at the same node,
     /// and its postcondition is compatible with its precondition.  If the path is cyclic, a
     /// tuple is returned indicating whether cycle requires strengthening the pre- or postcondition.
@@ -2053,6 +2402,120 @@ impl<'a> DisplayWithPartialPaths for &'a PartialPath {
     }
 }
 
+/// Facts about the route a partial path takes through a stack graph.  Returned by
+/// [`PartialPath::quality`][].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PathQuality {
+    /// The number of edges in the path.
+    pub length: usize,
+    /// The number of times the path moves from a node in one file to a node in another file.
+    pub file_crossings: usize,
+    /// Whether the path passes through any node in a builtins file.
+    pub via_builtins: bool,
+    /// The highest precedence of any edge in the path, or zero if the path has no edges.
+    pub max_precedence: i32,
+}
+
+impl PathQuality {
+    /// Returns a positive score for the path, where a higher score means that the path is more
+    /// likely to be the intended resolution.  Shorter paths, paths with fewer file crossings, and
+    /// paths with higher precedence score higher, and paths via builtins score lower.  Scores are
+    /// only meaningful relative to the scores of other paths for the same reference.
+    pub fn score(&self) -> f64 {
+        let precedence = self.max_precedence.clamp(-8, 8);
+        let mut score = 2f64.powi(precedence);
+        score /= 1.0 + self.file_crossings as f64;
+        score /= 1.0 + self.length as f64 / 16.0;
+        if self.via_builtins {
+            score /= 2.0;
+        }
+        score
+    }
+}
+
+/// A candidate resolution, ranked by [`rank_by_confidence`][].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RankedCandidate {
+    /// The index of the candidate in the list of qualities that was ranked.
+    pub index: usize,
+    /// The confidence that this candidate is the intended resolution, between zero and one.  The
+    /// confidences of all candidates sum to one.
+    pub confidence: f64,
+}
+
+/// Ranks candidate resolutions of the same reference by the [score][PathQuality::score] of their
+/// paths, most likely candidate first.  Candidates with equal scores keep their relative order.
+pub fn rank_by_confidence(qualities: &[PathQuality]) -> Vec<RankedCandidate> {
+    let scores = qualities.iter().map(|q| q.score()).collect::<Vec<_>>();
+    let total: f64 = scores.iter().sum();
+    let mut ranked = scores
+        .into_iter()
+        .enumerate()
+        .map(|(index, score)| RankedCandidate {
+            index,
+            confidence: score / total,
+        })
+        .collect::<Vec<_>>();
+    ranked.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    ranked
+}
+
+/// Decides between complete paths of the same reference that shadowing cannot decide between.
+/// Implemented by language layers that have their own disambiguation rules, such as the method
+/// resolution order of Python classes.
+pub trait TieBreaker: Send + Sync {
+    /// Compares two paths of which neither shadows the other.  Returns [`Ordering::Less`][] if
+    /// `left` should be preferred, [`Ordering::Greater`][] if `right` should be preferred, and
+    /// [`Ordering::Equal`][] if both should be kept.  The comparison must be consistent: if it
+    /// prefers one path over the other, it must do so regardless of the order of the arguments.
+    ///
+    /// [`Ordering::Less`]: std::cmp::Ordering::Less
+    /// [`Ordering::Greater`]: std::cmp::Ordering::Greater
+    /// [`Ordering::Equal`]: std::cmp::Ordering::Equal
+    fn break_tie(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        left: &PartialPath,
+        right: &PartialPath,
+    ) -> std::cmp::Ordering;
+}
+
+/// Removes the paths that are shadowed by another path in the list, which must all be complete
+/// paths of the same reference.  If a tie breaker is given, it is then asked to compare every
+/// pair of remaining paths, and paths for which another path is preferred are removed as well.
+/// The remaining paths keep their relative order.
+pub fn remove_shadowed_paths(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    paths: &mut Vec<PartialPath>,
+    tie_breaker: Option<&dyn TieBreaker>,
+) {
+    let shadowed = paths
+        .iter()
+        .map(|path| paths.iter().any(|other| other.shadows(partials, path)))
+        .collect::<Vec<_>>();
+    let mut shadowed = shadowed.into_iter();
+    paths.retain(|_| !shadowed.next().unwrap());
+
+    let tie_breaker = match tie_breaker {
+        Some(tie_breaker) => tie_breaker,
+        None => return,
+    };
+    let mut preferred_over = vec![false; paths.len()];
+    for i in 0..paths.len() {
+        for j in (i + 1)..paths.len() {
+            match tie_breaker.break_tie(graph, partials, &paths[i], &paths[j]) {
+                std::cmp::Ordering::Less => preferred_over[j] = true,
+                std::cmp::Ordering::Greater => preferred_over[i] = true,
+                std::cmp::Ordering::Equal => {}
+            }
+        }
+    }
+    let mut preferred_over = preferred_over.into_iter();
+    paths.retain(|_| !preferred_over.next().unwrap());
+}
+
 impl PartialPath {
     /// Modifies this partial path so that it has no symbol or scope stack variables in common with
     /// another partial path.
@@ -2081,6 +2544,25 @@ impl PartialPath {
             .with_offset(scope_variable_offset);
     }
 
+    /// Modifies this partial path so that it has no symbol or scope stack variables in common with
+    /// another partial path, or returns an error if the offset variables of this path do not fit
+    /// in a `u32`.  The path is unchanged if an error is returned.
+    #[cfg(feature = "checked")]
+    pub fn try_ensure_no_overlapping_variables(
+        &mut self,
+        partials: &mut PartialPaths,
+        other: &PartialPath,
+    ) -> Result<(), InvariantViolation> {
+        self.largest_symbol_stack_variable()
+            .checked_add(other.largest_symbol_stack_variable())
+            .ok_or(InvariantViolation::SymbolStackVariableOverflow)?;
+        self.largest_scope_stack_variable(partials)
+            .checked_add(other.largest_scope_stack_variable(partials))
+            .ok_or(InvariantViolation::ScopeStackVariableOverflow)?;
+        self.ensure_no_overlapping_variables(partials, other);
+        Ok(())
+    }
+
     /// Replaces stack variables in the precondition with empty stacks.
     pub fn eliminate_precondition_stack_variables(&mut self, partials: &mut PartialPaths) {
         let mut symbol_bindings = PartialSymbolStackBindings::new();
@@ -2122,16 +2604,120 @@ impl PartialPath {
 
     /// Attempts to append an edge to the end of a partial path.  If the edge is not a valid
     /// extension of this partial path, we return an error describing why.
+    ///
+    /// This is the same as [`append_forward`][Self::append_forward].
     pub fn append(
         &mut self,
         graph: &StackGraph,
         partials: &mut PartialPaths,
         edge: Edge,
+    ) -> Result<(), PathResolutionError> {
+        self.append_forward(graph, partials, edge)
+    }
+
+    /// Attempts to extend this partial path with each outgoing edge of its end node, and adds each
+    /// valid extension to `result`.  Unlike the stitcher's
+    /// [`find_minimal_partial_path_set_in_file`][crate::stitching::ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file],
+    /// this follows edges into any file, which is useful if you hold a fully merged graph and want
+    /// to write your own extension loop.
+    pub fn extend<R>(&self, graph: &StackGraph, partials: &mut PartialPaths, result: &mut R)
+    where
+        R: Extend<PartialPath>,
+    {
+        self.extend_with_filter(graph, partials, |_, _| true, result)
+    }
+
+    /// Attempts to extend this partial path with each outgoing edge of its end node that is
+    /// accepted by `filter`, and adds each valid extension to `result`.  Restricting the filter to
+    /// edges whose sink is in a particular file gives the same extensions that are used to find
+    /// the partial paths of that file.
+    pub fn extend_with_filter<F, R>(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        mut filter: F,
+        result: &mut R,
+    ) where
+        F: FnMut(&StackGraph, &Edge) -> bool,
+        R: Extend<PartialPath>,
+    {
+        for edge in graph.outgoing_edges(self.end_node) {
+            if !filter(graph, &edge) {
+                continue;
+            }
+            let mut new_path = self.clone();
+            // An edge that isn't a valid extension of this path is not an error; we just skip it.
+            if new_path.append(graph, partials, edge).is_err() {
+                continue;
+            }
+            result.extend(std::iter::once(new_path));
+        }
+    }
+
+    /// Attempts to extend a partial path with an edge, in the given direction.  Following edges
+    /// [forward][EdgeDirection::Forward] appends the edge to the end of the path, following them
+    /// [backward][EdgeDirection::Backward] prepends it to the start of the path.
+    pub fn append_in_direction(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        edge: Edge,
+        direction: EdgeDirection,
+    ) -> Result<(), PathResolutionError> {
+        match direction {
+            EdgeDirection::Forward => self.append_forward(graph, partials, edge),
+            EdgeDirection::Backward => self.append_backward(graph, partials, edge),
+        }
+    }
+
+    /// Ensures that the symbol stack of this partial path starts with the given symbol stack key
+    /// of an edge into or out of the root node.  If the symbol stack postcondition runs out of
+    /// symbols before the key does, the remaining symbols of the key are required in the
+    /// precondition, in the same way as when a symbol is popped off an empty postcondition.  This
+    /// makes sure that the path only joins with paths whose symbol stacks start with the full key.
+    fn require_symbol_stack_key(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        key: &[Handle<Symbol>],
+    ) -> Result<(), PathResolutionError> {
+        if !self
+            .symbol_stack_postcondition
+            .can_start_with(graph, partials, key)
+        {
+            return Err(PathResolutionError::RootEdgeKeyUnsatisfied);
+        }
+        let known = self.symbol_stack_postcondition.len().min(key.len());
+        for symbol in &key[known..] {
+            // As when popping a symbol, binding the postcondition variable to the symbol and a
+            // fresh variable amounts to pushing the symbol to the back of both stacks.
+            let symbol = PartialScopedSymbol {
+                symbol: graph.normalized_symbol(*symbol),
+                scopes: ControlledOption::none(),
+            };
+            self.symbol_stack_precondition.push_back(partials, symbol);
+            self.symbol_stack_postcondition.push_back(partials, symbol);
+        }
+        Ok(())
+    }
+
+    /// Attempts to append an edge to the end of a partial path.  The edge's source must be the
+    /// end node of the path.  If the edge is not a valid extension of this partial path, we
+    /// return an error describing why.
+    pub fn append_forward(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        edge: Edge,
     ) -> Result<(), PathResolutionError> {
         if edge.source != self.end_node {
             return Err(PathResolutionError::IncorrectSourceNode);
         }
 
+        if let Some(key) = graph.root_edge_key(edge.source, edge.sink) {
+            self.require_symbol_stack_key(graph, partials, key)?;
+        }
+
         graph[edge.sink].append_to_partial_stacks(
             graph,
             partials,
@@ -2151,7 +2737,74 @@ impl PartialPath {
         );
 
         self.resolve_from_postcondition(graph, partials)?;
+        self.ensure_no_excluded_symbol_at_end(graph, partials)?;
+
+        Ok(())
+    }
+
+    /// Ensures that the symbol at the top of the postcondition symbol stack is not
+    /// [excluded][StackGraph::set_excluded_symbols] at the end node of this partial path.
+    fn ensure_no_excluded_symbol_at_end(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+    ) -> Result<(), PathResolutionError> {
+        if self
+            .symbol_stack_postcondition
+            .is_excluded_at(graph, partials, self.end_node)
+        {
+            return Err(PathResolutionError::ExcludedSymbol);
+        }
+        Ok(())
+    }
+
+    /// Attempts to append an edge to the end of a partial path, like [`append`][Self::append], or
+    /// returns an error if the edge does not belong to the graph, or if a fresh scope stack
+    /// variable would overflow.
+    #[cfg(feature = "checked")]
+    pub fn try_append(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        edge: Edge,
+    ) -> Result<Result<(), PathResolutionError>, InvariantViolation> {
+        graph.try_node(edge.source)?;
+        if let Node::PopScopedSymbol(_) = graph.try_node(edge.sink)? {
+            Self::largest_scope_stack_variable_for_partial_stacks(
+                partials,
+                &self.symbol_stack_precondition,
+                &self.scope_stack_precondition,
+            )
+            .checked_add(1)
+            .ok_or(InvariantViolation::ScopeStackVariableOverflow)?;
+        }
+        Ok(self.append(graph, partials, edge))
+    }
+
+    /// Attempts to prepend an edge to the start of a partial path.  The edge's sink must be the
+    /// start node of the path.  If the edge is not a valid extension of this partial path, we
+    /// return an error describing why, and leave the path unchanged.
+    ///
+    /// The edge is lifted into a partial path of its own, using the same node semantics as
+    /// [`append_forward`][Self::append_forward], which is then concatenated with this path.  The
+    /// resulting pre- and postconditions are therefore the same as those of the path built by
+    /// appending the same edges forward.
+    pub fn append_backward(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        edge: Edge,
+    ) -> Result<(), PathResolutionError> {
+        if edge.sink != self.start_node {
+            return Err(PathResolutionError::IncorrectSourceNode);
+        }
 
+        let mut prefix = PartialPath::from_node(graph, partials, edge.source);
+        prefix.append_forward(graph, partials, edge)?;
+        let mut suffix = self.clone();
+        suffix.ensure_no_overlapping_variables(partials, &prefix);
+        prefix.concatenate(graph, partials, &suffix)?;
+        *self = prefix;
         Ok(())
     }
 
@@ -2246,8 +2899,9 @@ impl Node {
             Self::PopScopedSymbol(sink) => {
                 // Ideally we want to pop sink's scoped symbol off from top of the symbol stack
                 // postcondition.
+                let sink_symbol = graph.normalized_symbol(sink.symbol);
                 if let Some(top) = symbol_stack_postcondition.pop_front(partials) {
-                    if top.symbol != sink.symbol {
+                    if top.symbol != sink_symbol {
                         return Err(PathResolutionError::IncorrectPoppedSymbol);
                     }
                     let new_scope_stack = match top.scopes.into_option() {
@@ -2266,7 +2920,7 @@ impl Node {
                             scope_stack_precondition,
                         );
                     let precondition_symbol = PartialScopedSymbol {
-                        symbol: sink.symbol,
+                        symbol: sink_symbol,
                         scopes: ControlledOption::some(PartialScopeStack::from_variable(
                             scope_stack_variable,
                         )),
@@ -2287,8 +2941,9 @@ impl Node {
             }
             Self::PopSymbol(sink) => {
                 // Ideally we want to pop sink's symbol off from top of the symbol stack postcondition.
+                let sink_symbol = graph.normalized_symbol(sink.symbol);
                 if let Some(top) = symbol_stack_postcondition.pop_front(partials) {
-                    if top.symbol != sink.symbol {
+                    if top.symbol != sink_symbol {
                         return Err(PathResolutionError::IncorrectPoppedSymbol);
                     }
                     if top.scopes.is_some() {
@@ -2299,7 +2954,7 @@ impl Node {
                     // the _precondition_ to indicate that the symbol stack needs to contain this symbol
                     // in order to successfully use this partial path.
                     let precondition_symbol = PartialScopedSymbol {
-                        symbol: sink.symbol,
+                        symbol: sink_symbol,
                         scopes: ControlledOption::none(),
                     };
                     // We simply push to the precondition. The official procedure here
@@ -2318,7 +2973,7 @@ impl Node {
                 // The symbol stack postcondition is our representation of the path's symbol stack.
                 // Pushing the scoped symbol onto our postcondition indicates that using this partial
                 // path would push the scoped symbol onto the path's symbol stack.
-                let sink_symbol = sink.symbol;
+                let sink_symbol = graph.normalized_symbol(sink.symbol);
                 let sink_scope = graph
                     .node_for_id(sink.scope)
                     .ok_or(PathResolutionError::UnknownAttachedScope)?;
@@ -2334,7 +2989,7 @@ impl Node {
                 // The symbol stack postcondition is our representation of the path's symbol stack.
                 // Pushing the symbol onto our postcondition indicates that using this partial path
                 // would push the symbol onto the path's symbol stack.
-                let sink_symbol = sink.symbol;
+                let sink_symbol = graph.normalized_symbol(sink.symbol);
                 let postcondition_symbol = PartialScopedSymbol {
                     symbol: sink_symbol,
                     scopes: ControlledOption::none(),
@@ -2370,6 +3025,7 @@ impl Node {
     /// in the correct behavior for path concatenation.
     fn halfopen_closed_partial_precondition(
         &self,
+        graph: &StackGraph,
         partials: &mut PartialPaths,
         symbol_stack: &mut PartialSymbolStack,
         scope_stack: &mut PartialScopeStack,
@@ -2381,7 +3037,7 @@ impl Node {
                 let symbol = symbol_stack
                     .pop_front(partials)
                     .ok_or(PathResolutionError::EmptySymbolStack)?;
-                if symbol.symbol != node.symbol {
+                if symbol.symbol != graph.normalized_symbol(node.symbol) {
                     return Err(PathResolutionError::IncorrectPoppedSymbol);
                 }
                 *scope_stack = symbol.scopes.into_option().unwrap();
@@ -2390,7 +3046,7 @@ impl Node {
                 let symbol = symbol_stack
                     .pop_front(partials)
                     .ok_or(PathResolutionError::EmptySymbolStack)?;
-                if symbol.symbol != node.symbol {
+                if symbol.symbol != graph.normalized_symbol(node.symbol) {
                     return Err(PathResolutionError::IncorrectPoppedSymbol);
                 }
             }
@@ -2425,6 +3081,7 @@ impl Node {
     /// in the correct behavior for path concatenation.
     fn halfopen_closed_partial_postcondition(
         &self,
+        graph: &StackGraph,
         partials: &mut PartialPaths,
         symbol_stack: &mut PartialSymbolStack,
         _scope_stack: &mut PartialScopeStack,
@@ -2438,7 +3095,7 @@ impl Node {
                 let symbol = symbol_stack
                     .pop_front(partials)
                     .ok_or(PathResolutionError::EmptySymbolStack)?;
-                if symbol.symbol != node.symbol {
+                if symbol.symbol != graph.normalized_symbol(node.symbol) {
                     return Err(PathResolutionError::IncorrectPoppedSymbol);
                 }
             }
@@ -2446,7 +3103,7 @@ impl Node {
                 let symbol = symbol_stack
                     .pop_front(partials)
                     .ok_or(PathResolutionError::EmptySymbolStack)?;
-                if symbol.symbol != node.symbol {
+                if symbol.symbol != graph.normalized_symbol(node.symbol) {
                     return Err(PathResolutionError::IncorrectPoppedSymbol);
                 }
             }
@@ -2461,6 +3118,21 @@ impl Node {
 // Extending partial paths with partial paths
 
 impl PartialPath {
+    /// Attempts to append a partial path to this one, like [`concatenate`][Self::concatenate], or
+    /// returns an error if the nodes where the paths join do not belong to the graph.
+    #[cfg(feature = "checked")]
+    pub fn try_concatenate(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        rhs: &PartialPath,
+    ) -> Result<Result<(), PathResolutionError>, InvariantViolation> {
+        graph.try_node(self.end_node)?;
+        graph.try_node(rhs.start_node)?;
+        graph.try_node(rhs.end_node)?;
+        Ok(self.concatenate(graph, partials, rhs))
+    }
+
     /// Attempts to append a partial path to this one.  If the postcondition of the “left” partial path
     /// is not compatible with the precondition of the “right” path, we return an error describing why.
     ///
@@ -2479,6 +3151,12 @@ impl PartialPath {
 
         #[cfg_attr(not(feature = "copious-debugging"), allow(unused_mut))]
         let mut join = Self::compute_join(graph, partials, lhs, rhs)?;
+        if join
+            .unified_symbol_stack
+            .is_excluded_at(graph, partials, rhs.start_node)
+        {
+            return Err(PathResolutionError::ExcludedSymbol);
+        }
         #[cfg(feature = "copious-debugging")]
         {
             let unified_symbol_stack = join
@@ -2525,6 +3203,7 @@ impl PartialPath {
         lhs.end_node = rhs.end_node;
 
         lhs.resolve_from_postcondition(graph, partials)?;
+        lhs.ensure_no_excluded_symbol_at_end(graph, partials)?;
 
         Ok(())
     }
@@ -2546,32 +3225,18 @@ impl PartialPath {
         let mut lhs_scope_stack_postcondition = lhs.scope_stack_postcondition;
         let mut rhs_symbol_stack_precondition = rhs.symbol_stack_precondition;
         let mut rhs_scope_stack_precondition = rhs.scope_stack_precondition;
-        graph[lhs.end_node]
-            .halfopen_closed_partial_postcondition(
-                partials,
-                &mut lhs_symbol_stack_postcondition,
-                &mut lhs_scope_stack_postcondition,
-            )
-            .unwrap_or_else(|e| {
-                panic!(
-                    "failed to halfopen postcondition of {}: {:?}",
-                    lhs.display(graph, partials),
-                    e
-                );
-            });
-        graph[rhs.start_node]
-            .halfopen_closed_partial_precondition(
-                partials,
-                &mut rhs_symbol_stack_precondition,
-                &mut rhs_scope_stack_precondition,
-            )
-            .unwrap_or_else(|e| {
-                panic!(
-                    "failed to halfopen postcondition of {}: {:?}",
-                    rhs.display(graph, partials),
-                    e
-                );
-            });
+        graph[lhs.end_node].halfopen_closed_partial_postcondition(
+            graph,
+            partials,
+            &mut lhs_symbol_stack_postcondition,
+            &mut lhs_scope_stack_postcondition,
+        )?;
+        graph[rhs.start_node].halfopen_closed_partial_precondition(
+            graph,
+            partials,
+            &mut rhs_symbol_stack_precondition,
+            &mut rhs_scope_stack_precondition,
+        )?;
 
         let mut symbol_bindings = PartialSymbolStackBindings::new();
         let mut scope_bindings = PartialScopeStackBindings::new();
@@ -2597,7 +3262,6 @@ impl PartialPath {
 }
 
 struct Join {
-    #[cfg_attr(not(feature = "copious-debugging"), allow(dead_code))]
     pub unified_symbol_stack: PartialSymbolStack,
     #[cfg_attr(not(feature = "copious-debugging"), allow(dead_code))]
     pub unified_scope_stack: PartialScopeStack,
@@ -2614,6 +3278,8 @@ pub struct PartialPaths {
     pub(crate) partial_symbol_stacks: DequeArena<PartialScopedSymbol>,
     pub(crate) partial_scope_stacks: DequeArena<Handle<Node>>,
     pub(crate) partial_path_edges: DequeArena<PartialPathEdge>,
+    symbol_stack_fingerprints: DequeFingerprints<PartialScopedSymbol>,
+    scope_stack_fingerprints: DequeFingerprints<Handle<Node>>,
 }
 
 impl PartialPaths {
@@ -2622,13 +3288,131 @@ impl PartialPaths {
             partial_symbol_stacks: Deque::new_arena(),
             partial_scope_stacks: Deque::new_arena(),
             partial_path_edges: Deque::new_arena(),
+            symbol_stack_fingerprints: DequeFingerprints::new(),
+            scope_stack_fingerprints: DequeFingerprints::new(),
         }
     }
 
-    #[cfg_attr(not(feature = "storage"), allow(dead_code))]
     pub(crate) fn clear(&mut self) {
         self.partial_symbol_stacks.clear();
         self.partial_scope_stacks.clear();
         self.partial_path_edges.clear();
+        self.symbol_stack_fingerprints.clear();
+        self.scope_stack_fingerprints.clear();
+    }
+
+    /// Returns allocation statistics for the arenas that store the contents of partial paths.
+    /// Because deque cells are never freed individually, indexers can use these to detect
+    /// unbounded growth, and to decide how many files to process before starting over with a
+    /// fresh instance.
+    pub fn stats(&self) -> PartialPathsStats {
+        PartialPathsStats {
+            symbol_stack_cells: self.partial_symbol_stacks.stats(),
+            scope_stack_cells: self.partial_scope_stacks.stats(),
+            edge_cells: self.partial_path_edges.stats(),
+        }
+    }
+
+    /// Resets the high-water marks of all arenas, for example before processing the next file.
+    pub fn reset_high_water_marks(&mut self) {
+        self.partial_symbol_stacks.reset_high_water_mark();
+        self.partial_scope_stacks.reset_high_water_mark();
+        self.partial_path_edges.reset_high_water_mark();
+    }
+
+    /// Frees the arena cells that are not used by any of the given live partial paths.  This
+    /// copies the contents of the live paths into fresh arenas, updating the paths in place, and
+    /// is meant to be run during idle periods of long-lived processes, whose arenas would
+    /// otherwise only grow.
+    ///
+    /// All partial paths, symbol stacks, and scope stacks that were created with this instance,
+    /// and that are not part of the live paths, are invalid afterwards.  In particular, this must
+    /// not be called while a stitcher is using this instance.
+    pub fn compact<'a, I>(&mut self, live_paths: I) -> PartialPathsCompaction
+    where
+        I: IntoIterator<Item = &'a mut PartialPath>,
+    {
+        let before = self.stats();
+        let mut symbol_stacks = DequeCompaction::new();
+        let mut scope_stacks = DequeCompaction::new();
+        let mut edges = DequeCompaction::new();
+        for path in live_paths {
+            for stack in [
+                &mut path.symbol_stack_precondition,
+                &mut path.symbol_stack_postcondition,
+            ] {
+                symbol_stacks.copy(&self.partial_symbol_stacks, &mut stack.symbols, |symbol| {
+                    let mut symbol = *symbol;
+                    if let Some(mut scopes) = symbol.scopes.into_option() {
+                        scope_stacks.copy(&self.partial_scope_stacks, &mut scopes.scopes, |n| *n);
+                        symbol.scopes = ControlledOption::some(scopes);
+                    }
+                    symbol
+                });
+            }
+            for stack in [
+                &mut path.scope_stack_precondition,
+                &mut path.scope_stack_postcondition,
+            ] {
+                scope_stacks.copy(&self.partial_scope_stacks, &mut stack.scopes, |n| *n);
+            }
+            edges.copy(&self.partial_path_edges, &mut path.edges.edges, |e| *e);
+        }
+        symbol_stacks.finish(&mut self.partial_symbol_stacks);
+        scope_stacks.finish(&mut self.partial_scope_stacks);
+        edges.finish(&mut self.partial_path_edges);
+        self.symbol_stack_fingerprints.clear();
+        self.scope_stack_fingerprints.clear();
+        PartialPathsCompaction {
+            before,
+            after: self.stats(),
+        }
+    }
+}
+
+/// Allocation statistics for the arenas of a [`PartialPaths`][] instance.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PartialPathsStats {
+    pub symbol_stack_cells: ArenaStats,
+    pub scope_stack_cells: ArenaStats,
+    pub edge_cells: ArenaStats,
+}
+
+impl PartialPathsStats {
+    /// Returns the combined statistics of all arenas.
+    pub fn total(&self) -> ArenaStats {
+        let mut total = self.symbol_stack_cells;
+        total += self.scope_stack_cells;
+        total += self.edge_cells;
+        total
+    }
+
+    /// Returns the number of bytes that are used by the live cells of all arenas.
+    pub fn live_bytes(&self) -> usize {
+        self.symbol_stack_cells.live
+            * std::mem::size_of::<ReversibleListCell<PartialScopedSymbol>>()
+            + self.scope_stack_cells.live * std::mem::size_of::<ReversibleListCell<Handle<Node>>>()
+            + self.edge_cells.live * std::mem::size_of::<ReversibleListCell<PartialPathEdge>>()
+    }
+}
+
+/// The result of [compacting][PartialPaths::compact] a [`PartialPaths`][] instance.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PartialPathsCompaction {
+    /// The allocation statistics before the compaction.
+    pub before: PartialPathsStats,
+    /// The allocation statistics after the compaction.
+    pub after: PartialPathsStats,
+}
+
+impl PartialPathsCompaction {
+    /// Returns the number of arena cells that were freed.
+    pub fn freed_cells(&self) -> usize {
+        self.before.total().live - self.after.total().live
+    }
+
+    /// Returns the number of bytes that were freed.
+    pub fn freed_bytes(&self) -> usize {
+        self.before.live_bytes() - self.after.live_bytes()
     }
 }
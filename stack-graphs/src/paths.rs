@@ -17,48 +17,81 @@
 
 use std::collections::VecDeque;
 
+use thiserror::Error;
+
 /// Errors that can occur during the path resolution process.
-#[derive(Debug)]
+#[derive(Debug, Error)]
 pub enum PathResolutionError {
+    /// The path contains a symbol whose attached scope list is longer than the configured
+    /// maximum.
+    #[error("attached scope list is too long")]
+    AttachedScopeListTooLong,
     /// The path is cyclic, and the cycle is disallowed.
+    #[error("path contains a disallowed cycle")]
     DisallowedCycle,
+    /// The path contains an edge whose visibility class does not allow it to be traversed from
+    /// the file the path starts in.
+    #[error("edge is not visible from the querying file")]
+    EdgeNotVisible,
     /// The path contains a _jump to scope_ node, but there are no scopes on the scope stack to
     /// jump to.
+    #[error("cannot jump to scope with an empty scope stack")]
     EmptyScopeStack,
     /// The path contains a _pop symbol_ or _pop scoped symbol_ node, but there are no symbols on
     /// the symbol stack to pop off.
+    #[error("cannot pop symbol from an empty symbol stack")]
     EmptySymbolStack,
     /// The partial path contains multiple references to a scope stack variable, and those
     /// references can't unify on a single scope stack.
+    #[error("scope stack variables cannot be unified")]
     IncompatibleScopeStackVariables,
     /// The partial path contains multiple references to a symbol stack variable, and those
     /// references can't unify on a single symbol stack.
+    #[error("symbol stack variables cannot be unified")]
     IncompatibleSymbolStackVariables,
+    /// The path reaches a node at which the symbol on top of the symbol stack is
+    /// [excluded][crate::graph::StackGraph::set_excluded_symbols].
+    #[error("symbol on top of the symbol stack is excluded at a node")]
+    ExcludedSymbol,
     /// The partial path contains edges from multiple files.
+    #[error("path contains edges from multiple files")]
     IncorrectFile,
     /// The path contains a _pop symbol_ or _pop scoped symbol_ node, but the symbol at the top of
     /// the symbol stack does not match.
+    #[error("popped symbol does not match the top of the symbol stack")]
     IncorrectPoppedSymbol,
     /// The path contains an edge whose source node does not match the sink node of the preceding
     /// edge.
+    #[error("edge source node does not match the end of the path")]
     IncorrectSourceNode,
     /// The path contains a _pop scoped symbol_ node, but the symbol at the top of the symbol stack
     /// does not have an attached scope list to pop off.
+    #[error("symbol on top of the symbol stack has no attached scopes")]
     MissingAttachedScopeList,
+    /// The path contains an edge into or out of the root node, but the symbol stack does not
+    /// start with the symbol stack key of the edge.
+    #[error("symbol stack does not satisfy the key of a root edge")]
+    RootEdgeKeyUnsatisfied,
     /// The path's scope stack does not satisfy the partial path's scope stack precondition.
+    #[error("scope stack does not satisfy the precondition")]
     ScopeStackUnsatisfied,
     /// The path's symbol stack does not satisfy the partial path's symbol stack precondition.
+    #[error("symbol stack does not satisfy the precondition")]
     SymbolStackUnsatisfied,
     /// The partial path's postcondition references a symbol stack variable that isn't present in
     /// the precondition.
+    #[error("postcondition refers to an unbound symbol stack variable")]
     UnboundSymbolStackVariable,
     /// The partial path's postcondition references a scope stack variable that isn't present in
     /// the precondition.
+    #[error("postcondition refers to an unbound scope stack variable")]
     UnboundScopeStackVariable,
     /// The path contains a _pop symbol_ node, but the symbol at the top of the symbol stack has an
     /// attached scope list that we weren't expecting.
+    #[error("symbol on top of the symbol stack has unexpected attached scopes")]
     UnexpectedAttachedScopeList,
     /// A _push scoped symbol_ node referes to an exported scope node that doesn't exist.
+    #[error("attached scope node does not exist")]
     UnknownAttachedScope,
 }
 
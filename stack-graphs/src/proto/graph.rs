@@ -0,0 +1,560 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::convert::TryInto;
+
+use crate::serde;
+use crate::serde::Filter;
+
+use super::required;
+use super::Error;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StackGraph {
+    #[prost(string, repeated, tag = "1")]
+    pub files: Vec<String>,
+    #[prost(message, repeated, tag = "2")]
+    pub nodes: Vec<Node>,
+    #[prost(message, repeated, tag = "3")]
+    pub edges: Vec<Edge>,
+    #[prost(btree_map = "string, string", tag = "4")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl StackGraph {
+    pub fn from_graph(graph: &crate::graph::StackGraph) -> Self {
+        graph.to_serializable().into()
+    }
+
+    pub fn from_graph_filter(graph: &crate::graph::StackGraph, filter: &dyn Filter) -> Self {
+        graph.to_serializable_filter(filter).into()
+    }
+
+    pub fn load_into(self, graph: &mut crate::graph::StackGraph) -> Result<(), Error> {
+        serde::StackGraph::try_from(self)?.load_into(graph)?;
+        Ok(())
+    }
+}
+
+impl From<serde::StackGraph> for StackGraph {
+    fn from(value: serde::StackGraph) -> Self {
+        Self {
+            files: value.files.data,
+            nodes: value.nodes.data.into_iter().map(Node::from).collect(),
+            edges: value.edges.data.into_iter().map(Edge::from).collect(),
+            metadata: value.metadata.data,
+        }
+    }
+}
+
+impl TryFrom<StackGraph> for serde::StackGraph {
+    type Error = Error;
+
+    fn try_from(value: StackGraph) -> Result<Self, Self::Error> {
+        Ok(Self {
+            files: serde::Files { data: value.files },
+            nodes: serde::Nodes {
+                data: value
+                    .nodes
+                    .into_iter()
+                    .map(serde::Node::try_from)
+                    .collect::<Result<_, _>>()?,
+            },
+            edges: serde::Edges {
+                data: value
+                    .edges
+                    .into_iter()
+                    .map(serde::Edge::try_from)
+                    .collect::<Result<_, _>>()?,
+            },
+            metadata: serde::Metadata {
+                data: value.metadata,
+            },
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, prost::Enumeration)]
+#[repr(i32)]
+pub enum NodeKind {
+    Unspecified = 0,
+    DropScopes = 1,
+    JumpToScope = 2,
+    PopScopedSymbol = 3,
+    PopSymbol = 4,
+    PushScopedSymbol = 5,
+    PushSymbol = 6,
+    Root = 7,
+    Scope = 8,
+}
+
+/// A node of any kind.  The fields that do not apply to the node's kind are left empty.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Node {
+    #[prost(enumeration = "NodeKind", tag = "1")]
+    pub kind: i32,
+    #[prost(message, optional, tag = "2")]
+    pub id: Option<NodeID>,
+    #[prost(string, tag = "3")]
+    pub symbol: String,
+    #[prost(message, optional, tag = "4")]
+    pub scope: Option<NodeID>,
+    #[prost(bool, tag = "5")]
+    pub is_definition: bool,
+    #[prost(bool, tag = "6")]
+    pub is_reference: bool,
+    #[prost(bool, tag = "7")]
+    pub is_exported: bool,
+    #[prost(message, optional, tag = "8")]
+    pub source_info: Option<SourceInfo>,
+    #[prost(message, repeated, tag = "9")]
+    pub debug_info: Vec<DebugEntry>,
+    #[prost(string, optional, tag = "10")]
+    pub namespace: Option<String>,
+    #[prost(string, repeated, tag = "11")]
+    pub excluded_symbols: Vec<String>,
+}
+
+impl From<serde::Node> for Node {
+    fn from(value: serde::Node) -> Self {
+        let mut node = Node::default();
+        let (id, source_info, debug_info) = match value {
+            serde::Node::DropScopes {
+                id,
+                source_info,
+                debug_info,
+            } => {
+                node.set_kind(NodeKind::DropScopes);
+                (id, source_info, debug_info)
+            }
+            serde::Node::JumpToScope {
+                id,
+                source_info,
+                debug_info,
+            } => {
+                node.set_kind(NodeKind::JumpToScope);
+                (id, source_info, debug_info)
+            }
+            serde::Node::PopScopedSymbol {
+                id,
+                symbol,
+                namespace,
+                is_definition,
+                source_info,
+                debug_info,
+            } => {
+                node.set_kind(NodeKind::PopScopedSymbol);
+                node.symbol = symbol;
+                node.namespace = namespace;
+                node.is_definition = is_definition;
+                (id, source_info, debug_info)
+            }
+            serde::Node::PopSymbol {
+                id,
+                symbol,
+                namespace,
+                is_definition,
+                source_info,
+                debug_info,
+            } => {
+                node.set_kind(NodeKind::PopSymbol);
+                node.symbol = symbol;
+                node.namespace = namespace;
+                node.is_definition = is_definition;
+                (id, source_info, debug_info)
+            }
+            serde::Node::PushScopedSymbol {
+                id,
+                symbol,
+                namespace,
+                scope,
+                is_reference,
+                source_info,
+                debug_info,
+            } => {
+                node.set_kind(NodeKind::PushScopedSymbol);
+                node.symbol = symbol;
+                node.namespace = namespace;
+                node.scope = Some(scope.into());
+                node.is_reference = is_reference;
+                (id, source_info, debug_info)
+            }
+            serde::Node::PushSymbol {
+                id,
+                symbol,
+                namespace,
+                is_reference,
+                source_info,
+                debug_info,
+            } => {
+                node.set_kind(NodeKind::PushSymbol);
+                node.symbol = symbol;
+                node.namespace = namespace;
+                node.is_reference = is_reference;
+                (id, source_info, debug_info)
+            }
+            serde::Node::Root {
+                id,
+                source_info,
+                debug_info,
+            } => {
+                node.set_kind(NodeKind::Root);
+                (id, source_info, debug_info)
+            }
+            serde::Node::Scope {
+                id,
+                is_exported,
+                excluded_symbols,
+                source_info,
+                debug_info,
+            } => {
+                node.set_kind(NodeKind::Scope);
+                node.is_exported = is_exported;
+                node.excluded_symbols = excluded_symbols.unwrap_or_default();
+                (id, source_info, debug_info)
+            }
+        };
+        node.id = Some(id.into());
+        node.source_info = source_info.map(SourceInfo::from);
+        node.debug_info = from_debug_info(debug_info);
+        node
+    }
+}
+
+impl TryFrom<Node> for serde::Node {
+    type Error = Error;
+
+    fn try_from(value: Node) -> Result<Self, Self::Error> {
+        let kind =
+            NodeKind::try_from(value.kind).map_err(|_| Error::InvalidNodeKind(value.kind))?;
+        let id = required(value.id, "id")?.into();
+        let source_info = value
+            .source_info
+            .map(serde::SourceInfo::try_from)
+            .transpose()?;
+        let debug_info = to_debug_info(value.debug_info);
+        Ok(match kind {
+            NodeKind::Unspecified => return Err(Error::InvalidNodeKind(value.kind)),
+            NodeKind::DropScopes => Self::DropScopes {
+                id,
+                source_info,
+                debug_info,
+            },
+            NodeKind::JumpToScope => Self::JumpToScope {
+                id,
+                source_info,
+                debug_info,
+            },
+            NodeKind::PopScopedSymbol => Self::PopScopedSymbol {
+                id,
+                symbol: value.symbol,
+                namespace: value.namespace,
+                is_definition: value.is_definition,
+                source_info,
+                debug_info,
+            },
+            NodeKind::PopSymbol => Self::PopSymbol {
+                id,
+                symbol: value.symbol,
+                namespace: value.namespace,
+                is_definition: value.is_definition,
+                source_info,
+                debug_info,
+            },
+            NodeKind::PushScopedSymbol => Self::PushScopedSymbol {
+                id,
+                symbol: value.symbol,
+                namespace: value.namespace,
+                scope: required(value.scope, "scope")?.into(),
+                is_reference: value.is_reference,
+                source_info,
+                debug_info,
+            },
+            NodeKind::PushSymbol => Self::PushSymbol {
+                id,
+                symbol: value.symbol,
+                namespace: value.namespace,
+                is_reference: value.is_reference,
+                source_info,
+                debug_info,
+            },
+            NodeKind::Root => Self::Root {
+                id,
+                source_info,
+                debug_info,
+            },
+            NodeKind::Scope => Self::Scope {
+                id,
+                is_exported: value.is_exported,
+                excluded_symbols: Some(value.excluded_symbols).filter(|s| !s.is_empty()),
+                source_info,
+                debug_info,
+            },
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct NodeID {
+    #[prost(string, optional, tag = "1")]
+    pub file: Option<String>,
+    #[prost(uint32, tag = "2")]
+    pub local_id: u32,
+}
+
+impl From<serde::NodeID> for NodeID {
+    fn from(value: serde::NodeID) -> Self {
+        Self {
+            file: value.file,
+            local_id: value.local_id,
+        }
+    }
+}
+
+impl From<NodeID> for serde::NodeID {
+    fn from(value: NodeID) -> Self {
+        Self {
+            file: value.file,
+            local_id: value.local_id,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SourceInfo {
+    #[prost(message, optional, tag = "1")]
+    pub span: Option<Span>,
+    #[prost(string, optional, tag = "2")]
+    pub syntax_type: Option<String>,
+}
+
+impl From<serde::SourceInfo> for SourceInfo {
+    fn from(value: serde::SourceInfo) -> Self {
+        Self {
+            span: Some(value.span.into()),
+            syntax_type: value.syntax_type,
+        }
+    }
+}
+
+impl TryFrom<SourceInfo> for serde::SourceInfo {
+    type Error = Error;
+
+    fn try_from(value: SourceInfo) -> Result<Self, Self::Error> {
+        Ok(Self {
+            span: required(value.span, "span")?.try_into()?,
+            syntax_type: value.syntax_type,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Span {
+    #[prost(message, optional, tag = "1")]
+    pub start: Option<Position>,
+    #[prost(message, optional, tag = "2")]
+    pub end: Option<Position>,
+}
+
+impl From<lsp_positions::Span> for Span {
+    fn from(value: lsp_positions::Span) -> Self {
+        Self {
+            start: Some(value.start.into()),
+            end: Some(value.end.into()),
+        }
+    }
+}
+
+impl TryFrom<Span> for lsp_positions::Span {
+    type Error = Error;
+
+    fn try_from(value: Span) -> Result<Self, Self::Error> {
+        Ok(Self {
+            start: required(value.start, "start")?.try_into()?,
+            end: required(value.end, "end")?.try_into()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Position {
+    #[prost(uint64, tag = "1")]
+    pub line: u64,
+    #[prost(message, optional, tag = "2")]
+    pub column: Option<Offset>,
+    #[prost(message, optional, tag = "3")]
+    pub containing_line: Option<Range>,
+    #[prost(message, optional, tag = "4")]
+    pub trimmed_line: Option<Range>,
+}
+
+impl From<lsp_positions::Position> for Position {
+    fn from(value: lsp_positions::Position) -> Self {
+        Self {
+            line: value.line as u64,
+            column: Some(value.column.into()),
+            containing_line: Some(value.containing_line.into()),
+            trimmed_line: Some(value.trimmed_line.into()),
+        }
+    }
+}
+
+impl TryFrom<Position> for lsp_positions::Position {
+    type Error = Error;
+
+    fn try_from(value: Position) -> Result<Self, Self::Error> {
+        Ok(Self {
+            line: value.line as usize,
+            column: required(value.column, "column")?.into(),
+            containing_line: required(value.containing_line, "containing_line")?.into(),
+            trimmed_line: required(value.trimmed_line, "trimmed_line")?.into(),
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Offset {
+    #[prost(uint64, tag = "1")]
+    pub utf8_offset: u64,
+    #[prost(uint64, tag = "2")]
+    pub utf16_offset: u64,
+    #[prost(uint64, tag = "3")]
+    pub grapheme_offset: u64,
+}
+
+impl From<lsp_positions::Offset> for Offset {
+    fn from(value: lsp_positions::Offset) -> Self {
+        Self {
+            utf8_offset: value.utf8_offset as u64,
+            utf16_offset: value.utf16_offset as u64,
+            grapheme_offset: value.grapheme_offset as u64,
+        }
+    }
+}
+
+impl From<Offset> for lsp_positions::Offset {
+    fn from(value: Offset) -> Self {
+        Self {
+            utf8_offset: value.utf8_offset as usize,
+            utf16_offset: value.utf16_offset as usize,
+            grapheme_offset: value.grapheme_offset as usize,
+        }
+    }
+}
+
+/// A half-open range of offsets.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Range {
+    #[prost(uint64, tag = "1")]
+    pub start: u64,
+    #[prost(uint64, tag = "2")]
+    pub end: u64,
+}
+
+impl From<std::ops::Range<usize>> for Range {
+    fn from(value: std::ops::Range<usize>) -> Self {
+        Self {
+            start: value.start as u64,
+            end: value.end as u64,
+        }
+    }
+}
+
+impl From<Range> for std::ops::Range<usize> {
+    fn from(value: Range) -> Self {
+        value.start as usize..value.end as usize
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DebugEntry {
+    #[prost(string, tag = "1")]
+    pub key: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+}
+
+fn from_debug_info(value: Option<serde::DebugInfo>) -> Vec<DebugEntry> {
+    value
+        .map(|info| info.data)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| DebugEntry {
+            key: entry.key,
+            value: entry.value,
+        })
+        .collect()
+}
+
+fn to_debug_info(value: Vec<DebugEntry>) -> Option<serde::DebugInfo> {
+    if value.is_empty() {
+        return None;
+    }
+    Some(serde::DebugInfo {
+        data: value
+            .into_iter()
+            .map(|entry| serde::DebugEntry {
+                key: entry.key,
+                value: entry.value,
+            })
+            .collect(),
+    })
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Edge {
+    #[prost(message, optional, tag = "1")]
+    pub source: Option<NodeID>,
+    #[prost(message, optional, tag = "2")]
+    pub sink: Option<NodeID>,
+    #[prost(int32, tag = "3")]
+    pub precedence: i32,
+    #[prost(message, repeated, tag = "4")]
+    pub debug_info: Vec<DebugEntry>,
+    /// The symbol stack key of an edge into or out of the root node.
+    #[prost(message, optional, tag = "5")]
+    pub root_key: Option<SymbolStackKey>,
+    /// The visibility class of the edge, if it is not public.
+    #[prost(string, optional, tag = "6")]
+    pub visibility: Option<String>,
+}
+
+impl From<serde::Edge> for Edge {
+    fn from(value: serde::Edge) -> Self {
+        Self {
+            source: Some(value.source.into()),
+            sink: Some(value.sink.into()),
+            precedence: value.precedence,
+            debug_info: from_debug_info(value.debug_info),
+            root_key: value.root_key.map(|symbols| SymbolStackKey { symbols }),
+            visibility: value.visibility,
+        }
+    }
+}
+
+impl TryFrom<Edge> for serde::Edge {
+    type Error = Error;
+
+    fn try_from(value: Edge) -> Result<Self, Self::Error> {
+        Ok(Self {
+            source: required(value.source, "source")?.into(),
+            sink: required(value.sink, "sink")?.into(),
+            precedence: value.precedence,
+            debug_info: to_debug_info(value.debug_info),
+            root_key: value.root_key.map(|key| key.symbols),
+            visibility: value.visibility,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SymbolStackKey {
+    #[prost(string, repeated, tag = "1")]
+    pub symbols: Vec<String>,
+}
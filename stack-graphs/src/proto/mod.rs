@@ -0,0 +1,59 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Protocol buffer messages for stack graphs and partial paths.
+//!
+//! The messages in this module are an alternative wire format to the [`serde`][crate::serde]
+//! types, for consumers that only speak protobuf.  They are defined in `proto/stack_graphs.proto`
+//! in this crate, which can be used to generate readers in other languages.  Messages are encoded
+//! and decoded with the methods of the [`Message`][] trait:
+//!
+//! ```
+//! # use stack_graphs::graph::StackGraph;
+//! # use stack_graphs::proto;
+//! use stack_graphs::proto::Message;
+//!
+//! # let mut graph = StackGraph::new();
+//! # graph.get_or_create_file("test.py");
+//! let bytes = proto::StackGraph::from_graph(&graph).encode_to_vec();
+//!
+//! let mut loaded = StackGraph::new();
+//! proto::StackGraph::decode(bytes.as_slice())?.load_into(&mut loaded)?;
+//! # assert!(loaded.get_file("test.py").is_some());
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! The messages convert to and from the corresponding [`serde`][crate::serde] types, which
+//! implement the conversions to and from the in-memory types.
+
+use thiserror::Error;
+
+mod graph;
+mod partial;
+mod stitching;
+
+pub use graph::*;
+pub use partial::*;
+pub use stitching::*;
+
+pub use prost::DecodeError;
+pub use prost::Message;
+
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum Error {
+    #[error("missing field `{0}`")]
+    MissingField(&'static str),
+    #[error("`{0}` is an invalid node kind")]
+    InvalidNodeKind(i32),
+    #[error(transparent)]
+    Serde(#[from] crate::serde::Error),
+}
+
+/// Returns the value of a required message field.
+fn required<T>(value: Option<T>, field: &'static str) -> Result<T, Error> {
+    value.ok_or(Error::MissingField(field))
+}
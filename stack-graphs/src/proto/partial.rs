@@ -0,0 +1,226 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use std::convert::TryFrom;
+
+use crate::partial::PartialPaths;
+use crate::serde;
+
+use super::required;
+use super::Error;
+use super::NodeID;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PartialPath {
+    #[prost(message, optional, tag = "1")]
+    pub start_node: Option<NodeID>,
+    #[prost(message, optional, tag = "2")]
+    pub end_node: Option<NodeID>,
+    #[prost(message, optional, tag = "3")]
+    pub symbol_stack_precondition: Option<PartialSymbolStack>,
+    #[prost(message, optional, tag = "4")]
+    pub symbol_stack_postcondition: Option<PartialSymbolStack>,
+    #[prost(message, optional, tag = "5")]
+    pub scope_stack_precondition: Option<PartialScopeStack>,
+    #[prost(message, optional, tag = "6")]
+    pub scope_stack_postcondition: Option<PartialScopeStack>,
+    #[prost(message, repeated, tag = "7")]
+    pub edges: Vec<PartialPathEdge>,
+}
+
+impl PartialPath {
+    pub fn from_partial_path(
+        graph: &crate::graph::StackGraph,
+        partials: &mut PartialPaths,
+        value: &crate::partial::PartialPath,
+    ) -> Self {
+        serde::PartialPath::from_partial_path(graph, partials, value).into()
+    }
+
+    pub fn to_partial_path(
+        self,
+        graph: &mut crate::graph::StackGraph,
+        partials: &mut PartialPaths,
+    ) -> Result<crate::partial::PartialPath, Error> {
+        Ok(serde::PartialPath::try_from(self)?.to_partial_path(graph, partials)?)
+    }
+}
+
+impl From<serde::PartialPath> for PartialPath {
+    fn from(value: serde::PartialPath) -> Self {
+        Self {
+            start_node: Some(value.start_node.into()),
+            end_node: Some(value.end_node.into()),
+            symbol_stack_precondition: Some(value.symbol_stack_precondition.into()),
+            symbol_stack_postcondition: Some(value.symbol_stack_postcondition.into()),
+            scope_stack_precondition: Some(value.scope_stack_precondition.into()),
+            scope_stack_postcondition: Some(value.scope_stack_postcondition.into()),
+            edges: value
+                .edges
+                .edges
+                .into_iter()
+                .map(PartialPathEdge::from)
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<PartialPath> for serde::PartialPath {
+    type Error = Error;
+
+    fn try_from(value: PartialPath) -> Result<Self, Self::Error> {
+        Ok(Self {
+            start_node: required(value.start_node, "start_node")?.into(),
+            end_node: required(value.end_node, "end_node")?.into(),
+            symbol_stack_precondition: required(
+                value.symbol_stack_precondition,
+                "symbol_stack_precondition",
+            )?
+            .into(),
+            symbol_stack_postcondition: required(
+                value.symbol_stack_postcondition,
+                "symbol_stack_postcondition",
+            )?
+            .into(),
+            scope_stack_precondition: required(
+                value.scope_stack_precondition,
+                "scope_stack_precondition",
+            )?
+            .into(),
+            scope_stack_postcondition: required(
+                value.scope_stack_postcondition,
+                "scope_stack_postcondition",
+            )?
+            .into(),
+            edges: serde::PartialPathEdgeList {
+                edges: value
+                    .edges
+                    .into_iter()
+                    .map(serde::PartialPathEdge::try_from)
+                    .collect::<Result<_, _>>()?,
+            },
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PartialScopeStack {
+    #[prost(message, repeated, tag = "1")]
+    pub scopes: Vec<NodeID>,
+    #[prost(uint32, optional, tag = "2")]
+    pub variable: Option<u32>,
+}
+
+impl From<serde::PartialScopeStack> for PartialScopeStack {
+    fn from(value: serde::PartialScopeStack) -> Self {
+        Self {
+            scopes: value.scopes.into_iter().map(NodeID::from).collect(),
+            variable: value.variable.map(|v| v.0),
+        }
+    }
+}
+
+impl From<PartialScopeStack> for serde::PartialScopeStack {
+    fn from(value: PartialScopeStack) -> Self {
+        Self {
+            scopes: value.scopes.into_iter().map(serde::NodeID::from).collect(),
+            variable: value.variable.map(serde::ScopeStackVariable),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PartialSymbolStack {
+    #[prost(message, repeated, tag = "1")]
+    pub symbols: Vec<PartialScopedSymbol>,
+    #[prost(uint32, optional, tag = "2")]
+    pub variable: Option<u32>,
+}
+
+impl From<serde::PartialSymbolStack> for PartialSymbolStack {
+    fn from(value: serde::PartialSymbolStack) -> Self {
+        Self {
+            symbols: value
+                .symbols
+                .into_iter()
+                .map(PartialScopedSymbol::from)
+                .collect(),
+            variable: value.variable.map(|v| v.0),
+        }
+    }
+}
+
+impl From<PartialSymbolStack> for serde::PartialSymbolStack {
+    fn from(value: PartialSymbolStack) -> Self {
+        Self {
+            symbols: value
+                .symbols
+                .into_iter()
+                .map(serde::PartialScopedSymbol::from)
+                .collect(),
+            variable: value.variable.map(serde::SymbolStackVariable),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PartialScopedSymbol {
+    #[prost(string, tag = "1")]
+    pub symbol: String,
+    #[prost(message, optional, tag = "2")]
+    pub scopes: Option<PartialScopeStack>,
+    #[prost(string, optional, tag = "3")]
+    pub namespace: Option<String>,
+}
+
+impl From<serde::PartialScopedSymbol> for PartialScopedSymbol {
+    fn from(value: serde::PartialScopedSymbol) -> Self {
+        Self {
+            symbol: value.symbol,
+            scopes: value.scopes.map(PartialScopeStack::from),
+            namespace: value.namespace,
+        }
+    }
+}
+
+impl From<PartialScopedSymbol> for serde::PartialScopedSymbol {
+    fn from(value: PartialScopedSymbol) -> Self {
+        Self {
+            symbol: value.symbol,
+            namespace: value.namespace,
+            scopes: value.scopes.map(serde::PartialScopeStack::from),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PartialPathEdge {
+    #[prost(message, optional, tag = "1")]
+    pub source: Option<NodeID>,
+    #[prost(int32, tag = "2")]
+    pub precedence: i32,
+}
+
+impl From<serde::PartialPathEdge> for PartialPathEdge {
+    fn from(value: serde::PartialPathEdge) -> Self {
+        Self {
+            source: Some(value.source.into()),
+            precedence: value.precedence,
+        }
+    }
+}
+
+impl TryFrom<PartialPathEdge> for serde::PartialPathEdge {
+    type Error = Error;
+
+    fn try_from(value: PartialPathEdge) -> Result<Self, Self::Error> {
+        Ok(Self {
+            source: required(value.source, "source")?.into(),
+            precedence: value.precedence,
+        })
+    }
+}
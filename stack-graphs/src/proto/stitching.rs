@@ -0,0 +1,73 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use std::convert::TryFrom;
+
+use crate::graph::StackGraph;
+use crate::partial::PartialPaths;
+use crate::serde;
+use crate::serde::Filter;
+
+use super::Error;
+use super::PartialPath;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Database {
+    #[prost(message, repeated, tag = "1")]
+    pub paths: Vec<PartialPath>,
+}
+
+impl Database {
+    pub fn from_database(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        value: &crate::stitching::Database,
+    ) -> Self {
+        serde::Database::from_database(graph, partials, value).into()
+    }
+
+    pub fn from_database_filter(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        value: &crate::stitching::Database,
+        filter: &dyn Filter,
+    ) -> Self {
+        serde::Database::from_database_filter(graph, partials, value, filter).into()
+    }
+
+    pub fn load_into(
+        self,
+        graph: &mut StackGraph,
+        partials: &mut PartialPaths,
+        value: &mut crate::stitching::Database,
+    ) -> Result<(), Error> {
+        serde::Database::try_from(self)?.load_into(graph, partials, value)?;
+        Ok(())
+    }
+}
+
+impl From<serde::Database> for Database {
+    fn from(value: serde::Database) -> Self {
+        Self {
+            paths: value.paths.into_iter().map(PartialPath::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<Database> for serde::Database {
+    type Error = Error;
+
+    fn try_from(value: Database) -> Result<Self, Self::Error> {
+        Ok(Self {
+            paths: value
+                .paths
+                .into_iter()
+                .map(serde::PartialPath::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
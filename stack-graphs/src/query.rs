@@ -0,0 +1,419 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Structural queries over the nodes of a stack graph.
+//!
+//! Tools such as test harnesses for stack graph rules, or linters for rule authors, often need to
+//! inspect the shape of a graph: which definitions exist for a symbol, or which nodes can be
+//! reached from a reference.  Instead of writing traversals by hand, they can describe the nodes
+//! they are interested in with a [`NodeQuery`][], which is created with [`StackGraph::query`][]:
+//!
+//! ```
+//! # use stack_graphs::graph::NodeKind;
+//! # use stack_graphs::graph::StackGraph;
+//! # let mut graph = StackGraph::new();
+//! # let file = graph.get_or_create_file("test.py");
+//! # let x = graph.add_symbol("x");
+//! # let ref_id = graph.new_node_id(file);
+//! # let ref_x = graph.add_push_symbol_node(ref_id, x, true).unwrap();
+//! # let def_id = graph.new_node_id(file);
+//! # let def_x = graph.add_pop_symbol_node(def_id, x, true).unwrap();
+//! # graph.add_edge(ref_x, def_x, 0);
+//! let definitions = graph
+//!     .query()
+//!     .nodes(NodeKind::PopSymbol)
+//!     .with_symbol("x")
+//!     .reachable_from(ref_x)
+//!     .iter()
+//!     .collect::<Vec<_>>();
+//! assert_eq!(vec![def_x], definitions);
+//! ```
+//!
+//! Reachability only follows the edges of the graph, and does not take the symbol and scope
+//! stacks into account.  Use the path stitching algorithms to find the nodes that references
+//! actually resolve to.
+//!
+//! Editor integrations usually only know a file and a position in it.  [`reference_at`][] finds
+//! the reference node at a byte offset, using the source spans of the nodes, so that such callers
+//! do not have to map positions to nodes themselves.  [`hover`][] also resolves that reference,
+//! and collects what an editor shows when hovering over it: the span, syntax type, fully
+//! qualified name, and documentation of every definition it resolves to.  Documentation is taken
+//! from the [`DOCS_DEBUG_KEY`][] entry of the debug info of a definition.
+//!
+//! Rules that propagate types through the graph push [type symbols][StackGraph::add_type_symbol]
+//! at expression nodes, which resolve to the definitions of the types.  [`expression_types`][]
+//! finds these definitions for an expression node, and [`NodeQuery::type_symbols`][] selects the
+//! nodes with type symbols.
+
+use enumset::EnumSet;
+
+use crate::arena::Handle;
+use crate::arena::HandleSet;
+use crate::graph::EdgeDirection;
+use crate::graph::File;
+use crate::graph::InternedString;
+use crate::graph::Node;
+use crate::graph::NodeKind;
+use crate::graph::StackGraph;
+use crate::partial::PartialPath;
+use crate::stitching::Appendable;
+use crate::stitching::ForwardCandidates;
+use crate::stitching::ForwardPartialPathStitcher;
+use crate::stitching::StitcherConfig;
+use crate::stitching::ToAppendable;
+use crate::CancellationError;
+use crate::CancellationFlag;
+
+impl StackGraph {
+    /// Returns a query that matches all nodes of this graph, which can be narrowed down with the
+    /// methods of [`NodeQuery`][].
+    pub fn query(&self) -> NodeQuery<'_> {
+        NodeQuery {
+            graph: self,
+            kinds: EnumSet::empty(),
+            symbol: None,
+            file: None,
+            definitions: false,
+            references: false,
+            type_symbols: false,
+            reachable_from: Vec::new(),
+            filters: Vec::new(),
+        }
+    }
+}
+
+/// A query that selects nodes of a stack graph by their structure.  All conditions of a query must
+/// hold for a node to match.
+pub struct NodeQuery<'a> {
+    graph: &'a StackGraph,
+    kinds: EnumSet<NodeKind>,
+    symbol: Option<&'a str>,
+    file: Option<Handle<File>>,
+    definitions: bool,
+    references: bool,
+    type_symbols: bool,
+    reachable_from: Vec<Handle<Node>>,
+    filters: Vec<NodePredicate<'a>>,
+}
+
+type NodePredicate<'a> = Box<dyn Fn(&StackGraph, Handle<Node>) -> bool + 'a>;
+
+impl<'a> NodeQuery<'a> {
+    /// Only match nodes of the given kind.  Can be called multiple times to match nodes of any of
+    /// the given kinds.
+    pub fn nodes(mut self, kind: NodeKind) -> Self {
+        self.kinds |= kind;
+        self
+    }
+
+    /// Only match nodes with the given symbol.
+    pub fn with_symbol(mut self, symbol: &'a str) -> Self {
+        self.symbol = Some(symbol);
+        self
+    }
+
+    /// Only match nodes that belong to the given file.  The singleton _root_ and _jump to scope_
+    /// nodes belong to all files.
+    pub fn in_file(mut self, file: Handle<File>) -> Self {
+        self.file = Some(file);
+        self
+    }
+
+    /// Only match definitions.
+    pub fn definitions(mut self) -> Self {
+        self.definitions = true;
+        self
+    }
+
+    /// Only match references.
+    pub fn references(mut self) -> Self {
+        self.references = true;
+        self
+    }
+
+    /// Only match nodes with a [type symbol][StackGraph::is_type_symbol].
+    pub fn type_symbols(mut self) -> Self {
+        self.type_symbols = true;
+        self
+    }
+
+    /// Only match nodes that can be reached from the given node by following zero or more edges.
+    /// Can be called multiple times to match nodes that can be reached from any of the given
+    /// nodes.
+    pub fn reachable_from(mut self, node: Handle<Node>) -> Self {
+        self.reachable_from.push(node);
+        self
+    }
+
+    /// Only match nodes for which the given predicate holds.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&StackGraph, Handle<Node>) -> bool + 'a,
+    {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Returns an iterator over the matching nodes, in the order in which they were added to the
+    /// graph.
+    pub fn iter(self) -> impl Iterator<Item = Handle<Node>> + 'a {
+        let graph = self.graph;
+        let reachable = if self.reachable_from.is_empty() {
+            None
+        } else {
+            Some(self.reachable_nodes())
+        };
+        graph.iter_nodes().filter(move |handle| {
+            reachable.as_ref().is_none_or(|r| r.contains(*handle)) && self.matches(*handle)
+        })
+    }
+
+    /// Returns whether the given node matches all conditions of this query, except reachability.
+    fn matches(&self, handle: Handle<Node>) -> bool {
+        let node = &self.graph[handle];
+        if !self.kinds.is_empty() && !self.kinds.contains(node.kind()) {
+            return false;
+        }
+        if let Some(symbol) = self.symbol {
+            if node.symbol().is_none_or(|s| &self.graph[s] != symbol) {
+                return false;
+            }
+        }
+        if let Some(file) = self.file {
+            if !node.is_in_file(file) {
+                return false;
+            }
+        }
+        if self.definitions && !node.is_definition() {
+            return false;
+        }
+        if self.references && !node.is_reference() {
+            return false;
+        }
+        if self.type_symbols && node.symbol().is_none_or(|s| !self.graph.is_type_symbol(s)) {
+            return false;
+        }
+        self.filters.iter().all(|f| f(self.graph, handle))
+    }
+
+    /// Returns all nodes that can be reached from the start nodes of this query.
+    fn reachable_nodes(&self) -> HandleSet<Node> {
+        self.graph.reachable_from(
+            self.reachable_from.iter().copied(),
+            EdgeDirection::Forward,
+            |_, _| true,
+        )
+    }
+}
+
+/// How [`reference_at`][] chooses a reference for an offset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PositionMatch {
+    /// Only match references whose span covers the offset.  If several spans cover it, the
+    /// innermost one is chosen.
+    #[default]
+    Covering,
+    /// Match the reference whose span is nearest to the offset, if no span covers it.  An offset
+    /// directly after a span, such as a cursor at the end of an identifier, is nearest to it.
+    Nearest,
+}
+
+/// Returns the reference node at the given UTF-8 byte offset in the file with the given path, or
+/// `None` if the file does not exist, or no reference matches.  References without a source span
+/// are never matched.  Ties are broken in favor of smaller spans, and then of nodes that were
+/// added to the graph first.
+pub fn reference_at(
+    graph: &StackGraph,
+    file: &str,
+    offset: usize,
+    mode: PositionMatch,
+) -> Option<Handle<Node>> {
+    let file = graph.get_file(file)?;
+    graph
+        .query()
+        .references()
+        .in_file(file)
+        .iter()
+        .filter_map(|node| {
+            let range = graph.source_info(node)?.span.utf8_range();
+            if range.is_empty() {
+                return None;
+            }
+            let distance = if offset < range.start {
+                range.start - offset
+            } else {
+                offset.saturating_sub(range.end - 1)
+            };
+            if distance > 0 && mode == PositionMatch::Covering {
+                return None;
+            }
+            Some(((distance, range.len()), node))
+        })
+        .min_by_key(|(key, _)| *key)
+        .map(|(_, node)| node)
+}
+
+/// The key of the debug info entry that contains the documentation of a definition, such as a
+/// docstring.  Stack graph rules set it with the `debug_docs` attribute.
+pub const DOCS_DEBUG_KEY: &str = "docs";
+
+/// The information an editor shows when hovering over a reference.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hover {
+    /// The source span of the reference.
+    pub span: lsp_positions::Span,
+    /// The symbol of the reference.
+    pub symbol: String,
+    /// The definitions the reference resolves to, after shadowing, ordered by file and span.
+    pub definitions: Vec<HoverDefinition>,
+}
+
+/// A definition that a hovered reference resolves to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HoverDefinition {
+    /// The path of the file containing the definition.
+    pub file: String,
+    /// The source span of the definition.
+    pub span: lsp_positions::Span,
+    /// The symbol of the definition.
+    pub symbol: String,
+    /// The syntax type of the definition, such as `function` or `class`.
+    pub syntax_type: Option<String>,
+    /// The fully qualified name of the definition, if the stack graph rules computed one.
+    pub fully_qualified_name: Option<String>,
+    /// The documentation of the definition.
+    pub docs: Option<String>,
+}
+
+impl HoverDefinition {
+    fn of_node(graph: &StackGraph, node: Handle<Node>) -> Option<Self> {
+        let file = graph[node].file()?;
+        let symbol = graph[node].symbol()?;
+        let source_info = graph.source_info(node);
+        let string = |s: Option<Handle<InternedString>>| s.map(|s| graph[s].to_string());
+        Some(Self {
+            file: graph[file].name().to_string(),
+            span: source_info.map(|i| i.span.clone()).unwrap_or_default(),
+            symbol: graph[symbol].to_string(),
+            syntax_type: string(source_info.and_then(|i| i.syntax_type.into_option())),
+            fully_qualified_name: string(
+                source_info.and_then(|i| i.fully_qualified_name.into_option()),
+            ),
+            docs: graph.node_debug_info(node).and_then(|info| {
+                info.iter()
+                    .find(|e| &graph[e.key] == DOCS_DEBUG_KEY)
+                    .map(|e| graph[e.value].to_string())
+            }),
+        })
+    }
+}
+
+/// Finds the reference at the given UTF-8 byte offset in the file with the given path, as
+/// [`reference_at`][] does, resolves it, and returns the information to show when hovering over
+/// it.  Returns `None` if there is no reference at the offset.  The graph of the file must
+/// already be loaded, and all paths that might be needed must be available from the candidates.
+pub fn hover<H, A, Db, C, Err>(
+    candidates: &mut C,
+    file: &str,
+    offset: usize,
+    mode: PositionMatch,
+    config: StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Option<Hover>, Err>
+where
+    H: Clone,
+    A: Appendable,
+    Db: ToAppendable<H, A>,
+    C: ForwardCandidates<H, A, Db, Err>,
+    Err: std::convert::From<CancellationError>,
+{
+    let (graph, _, _) = candidates.get_graph_partials_and_db();
+    let reference = match reference_at(graph, file, offset, mode) {
+        Some(reference) => reference,
+        None => return Ok(None),
+    };
+
+    let mut paths = Vec::<PartialPath>::new();
+    ForwardPartialPathStitcher::<H>::find_all_complete_partial_paths(
+        candidates,
+        std::iter::once(reference),
+        config,
+        cancellation_flag,
+        |_, _, path| paths.push(path.clone()),
+    )?;
+
+    let (graph, partials, _) = candidates.get_graph_partials_and_db();
+    let mut definitions = Vec::new();
+    for path in &paths {
+        if paths.iter().any(|other| other.shadows(partials, path)) {
+            continue;
+        }
+        definitions.extend(HoverDefinition::of_node(graph, path.end_node));
+    }
+    definitions.sort_by(|a, b| a.file.cmp(&b.file).then_with(|| a.span.cmp(&b.span)));
+    definitions.dedup();
+
+    let symbol = graph[reference]
+        .symbol()
+        .map(|s| graph[s].to_string())
+        .unwrap_or_default();
+    Ok(Some(Hover {
+        span: graph
+            .source_info(reference)
+            .map(|i| i.span.clone())
+            .expect("matched reference to have a span"),
+        symbol,
+        definitions,
+    }))
+}
+
+/// Returns the definitions of type symbols that the given expression node resolves to, in the
+/// order of their handles.  These are the ends of the paths that start at the node with empty
+/// stacks, and end at a definition with a [type symbol][StackGraph::is_type_symbol] and an empty
+/// symbol stack.  Unlike references, expression nodes do not have to be marked as references to
+/// be resolved.  All paths that might be needed must be available from the candidates.
+pub fn expression_types<H, A, Db, C, Err>(
+    candidates: &mut C,
+    node: Handle<Node>,
+    config: StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<Handle<Node>>, Err>
+where
+    H: Clone,
+    A: Appendable,
+    Db: ToAppendable<H, A>,
+    C: ForwardCandidates<H, A, Db, Err>,
+    Err: std::convert::From<CancellationError>,
+{
+    let (graph, partials, _) = candidates.get_graph_partials_and_db();
+    let mut path = PartialPath::from_node(graph, partials, node);
+    path.eliminate_precondition_stack_variables(partials);
+    let mut stitcher = ForwardPartialPathStitcher::<H>::from_partial_paths(graph, partials, [path]);
+    config.apply(&mut stitcher);
+    stitcher.set_check_only_join_nodes(true);
+
+    let mut types = Vec::new();
+    while !stitcher.is_complete() {
+        cancellation_flag.check("finding expression types")?;
+        for path in stitcher.partial_paths_to_load() {
+            candidates.load_forward_candidates(path, cancellation_flag)?;
+        }
+        stitcher.process_next_phase(candidates, |_, _, _| true);
+        let (graph, _, _) = candidates.get_graph_partials_and_db();
+        for path in stitcher.previous_phase_partial_paths() {
+            let is_type = graph[path.end_node]
+                .symbol()
+                .is_some_and(|s| graph.is_type_symbol(s));
+            if is_type && path.ends_at_definition(graph) {
+                types.push(path.end_node);
+            }
+        }
+    }
+    types.sort();
+    types.dedup();
+    Ok(types)
+}
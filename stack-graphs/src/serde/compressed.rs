@@ -0,0 +1,399 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! A compressed encoding of partial paths.
+//!
+//! The partial paths of a file often have stacks and edge lists with long common prefixes, and
+//! refer to the same nodes and symbols many times.  In the compressed encoding, nodes and symbols
+//! are stored once in [`CompressionTables`][], and stacks and edge lists are stored as prefix
+//! trees, in which every entry extends the stack of its parent entry with a single element.
+//! A [`CompressedPartialPath`][] refers to its stacks by the index of their last entry, so that
+//! a common prefix is only stored once.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::Error;
+use super::NodeID;
+use super::PartialPath;
+use super::PartialPathEdge;
+use super::PartialPathEdgeList;
+use super::PartialScopeStack;
+use super::PartialScopedSymbol;
+use super::PartialSymbolStack;
+use super::ScopeStackVariable;
+use super::SymbolStackVariable;
+
+/// A set of partial paths, together with the tables that they refer to.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+pub struct CompressedPartialPaths {
+    tables: CompressionTables,
+    paths: Vec<CompressedPartialPath>,
+}
+
+impl CompressedPartialPaths {
+    pub fn from_partial_paths<'a, IP>(paths: IP) -> Self
+    where
+        IP: IntoIterator<Item = &'a PartialPath>,
+    {
+        let mut compressor = PathCompressor::new();
+        let paths = paths
+            .into_iter()
+            .map(|path| compressor.compress(path))
+            .collect();
+        Self {
+            tables: compressor.finish(),
+            paths,
+        }
+    }
+
+    pub fn to_partial_paths(&self) -> Result<Vec<PartialPath>, Error> {
+        self.paths
+            .iter()
+            .map(|path| self.tables.decompress(path))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+}
+
+/// The nodes, symbols, stacks, and edge lists that compressed partial paths refer to.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+pub struct CompressionTables {
+    nodes: Vec<NodeID>,
+    symbols: Vec<String>,
+    scope_stacks: PrefixTree<u32>,
+    symbol_stacks: PrefixTree<CompressedScopedSymbol>,
+    edge_lists: PrefixTree<CompressedPathEdge>,
+}
+
+impl CompressionTables {
+    /// Decompresses a path that was compressed into these tables.  Returns an error if the path
+    /// refers to entries that do not exist.
+    pub fn decompress(&self, path: &CompressedPartialPath) -> Result<PartialPath, Error> {
+        Ok(PartialPath {
+            start_node: self.node(path.start_node)?,
+            end_node: self.node(path.end_node)?,
+            symbol_stack_precondition: self.symbol_stack(&path.symbol_stack_precondition)?,
+            symbol_stack_postcondition: self.symbol_stack(&path.symbol_stack_postcondition)?,
+            scope_stack_precondition: self.scope_stack(&path.scope_stack_precondition)?,
+            scope_stack_postcondition: self.scope_stack(&path.scope_stack_postcondition)?,
+            edges: PartialPathEdgeList {
+                edges: self
+                    .edge_lists
+                    .get(path.edges)?
+                    .into_iter()
+                    .map(|edge| {
+                        Ok(PartialPathEdge {
+                            source: self.node(edge.source)?,
+                            precedence: edge.precedence,
+                        })
+                    })
+                    .collect::<Result<_, Error>>()?,
+            },
+        })
+    }
+
+    fn node(&self, index: u32) -> Result<NodeID, Error> {
+        self.nodes
+            .get(index as usize)
+            .cloned()
+            .ok_or(Error::InvalidCompressedIndex(index))
+    }
+
+    fn symbol(&self, index: u32) -> Result<String, Error> {
+        self.symbols
+            .get(index as usize)
+            .cloned()
+            .ok_or(Error::InvalidCompressedIndex(index))
+    }
+
+    fn scope_stack(&self, stack: &CompressedScopeStack) -> Result<PartialScopeStack, Error> {
+        Ok(PartialScopeStack {
+            scopes: self
+                .scope_stacks
+                .get(stack.scopes)?
+                .into_iter()
+                .map(|node| self.node(node))
+                .collect::<Result<_, Error>>()?,
+            variable: stack.variable.clone(),
+        })
+    }
+
+    fn symbol_stack(&self, stack: &CompressedSymbolStack) -> Result<PartialSymbolStack, Error> {
+        Ok(PartialSymbolStack {
+            symbols: self
+                .symbol_stacks
+                .get(stack.symbols)?
+                .into_iter()
+                .map(|symbol| {
+                    Ok(PartialScopedSymbol {
+                        symbol: self.symbol(symbol.symbol)?,
+                        namespace: symbol
+                            .namespace
+                            .map(|namespace| self.symbol(namespace))
+                            .transpose()?,
+                        scopes: symbol
+                            .scopes
+                            .as_ref()
+                            .map(|scopes| self.scope_stack(scopes))
+                            .transpose()?,
+                    })
+                })
+                .collect::<Result<_, Error>>()?,
+            variable: stack.variable.clone(),
+        })
+    }
+}
+
+/// Compresses partial paths, and collects the tables that the compressed paths refer to.
+#[derive(Default)]
+pub struct PathCompressor {
+    nodes: Interner<NodeID>,
+    symbols: Interner<String>,
+    scope_stacks: PrefixTreeBuilder<u32>,
+    symbol_stacks: PrefixTreeBuilder<CompressedScopedSymbol>,
+    edge_lists: PrefixTreeBuilder<CompressedPathEdge>,
+}
+
+impl PathCompressor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn compress(&mut self, path: &PartialPath) -> CompressedPartialPath {
+        let edges = path
+            .edges
+            .edges
+            .iter()
+            .map(|edge| CompressedPathEdge {
+                source: self.nodes.intern(&edge.source),
+                precedence: edge.precedence,
+            })
+            .collect::<Vec<_>>();
+        CompressedPartialPath {
+            start_node: self.nodes.intern(&path.start_node),
+            end_node: self.nodes.intern(&path.end_node),
+            symbol_stack_precondition: self.symbol_stack(&path.symbol_stack_precondition),
+            symbol_stack_postcondition: self.symbol_stack(&path.symbol_stack_postcondition),
+            scope_stack_precondition: self.scope_stack(&path.scope_stack_precondition),
+            scope_stack_postcondition: self.scope_stack(&path.scope_stack_postcondition),
+            edges: self.edge_lists.add(edges),
+        }
+    }
+
+    /// Returns the tables that the compressed paths refer to.
+    pub fn finish(self) -> CompressionTables {
+        CompressionTables {
+            nodes: self.nodes.values,
+            symbols: self.symbols.values,
+            scope_stacks: self.scope_stacks.tree,
+            symbol_stacks: self.symbol_stacks.tree,
+            edge_lists: self.edge_lists.tree,
+        }
+    }
+
+    fn scope_stack(&mut self, stack: &PartialScopeStack) -> CompressedScopeStack {
+        let scopes = stack
+            .scopes
+            .iter()
+            .map(|node| self.nodes.intern(node))
+            .collect::<Vec<_>>();
+        CompressedScopeStack {
+            scopes: self.scope_stacks.add(scopes),
+            variable: stack.variable.clone(),
+        }
+    }
+
+    fn symbol_stack(&mut self, stack: &PartialSymbolStack) -> CompressedSymbolStack {
+        let symbols = stack
+            .symbols
+            .iter()
+            .map(|symbol| CompressedScopedSymbol {
+                symbol: self.symbols.intern(&symbol.symbol),
+                namespace: symbol
+                    .namespace
+                    .as_ref()
+                    .map(|namespace| self.symbols.intern(namespace)),
+                scopes: symbol
+                    .scopes
+                    .as_ref()
+                    .map(|scopes| self.scope_stack(scopes)),
+            })
+            .collect::<Vec<_>>();
+        CompressedSymbolStack {
+            symbols: self.symbol_stacks.add(symbols),
+            variable: stack.variable.clone(),
+        }
+    }
+}
+
+/// A partial path whose nodes, symbols, stacks, and edges are stored in [`CompressionTables`][].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+pub struct CompressedPartialPath {
+    start_node: u32,
+    end_node: u32,
+    symbol_stack_precondition: CompressedSymbolStack,
+    symbol_stack_postcondition: CompressedSymbolStack,
+    scope_stack_precondition: CompressedScopeStack,
+    scope_stack_postcondition: CompressedScopeStack,
+    edges: u32,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+struct CompressedScopeStack {
+    scopes: u32,
+    variable: Option<ScopeStackVariable>,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+struct CompressedSymbolStack {
+    symbols: u32,
+    variable: Option<SymbolStackVariable>,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+struct CompressedScopedSymbol {
+    symbol: u32,
+    namespace: Option<u32>,
+    scopes: Option<CompressedScopeStack>,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+struct CompressedPathEdge {
+    source: u32,
+    precedence: i32,
+}
+
+/// A tree of stacks.  Stacks are identified by the index of their last entry plus one, and the
+/// empty stack by zero.  The parent of an entry always precedes it, which guarantees that the tree
+/// has no cycles.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+struct PrefixTree<T> {
+    entries: Vec<PrefixTreeEntry<T>>,
+}
+
+impl<T> Default for PrefixTree<T> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<T: Clone> PrefixTree<T> {
+    /// Returns the elements of a stack, from the root of the tree to the given entry.
+    fn get(&self, stack: u32) -> Result<Vec<T>, Error> {
+        let mut elements = Vec::new();
+        let mut current = stack;
+        while current != 0 {
+            let entry = self
+                .entries
+                .get(current as usize - 1)
+                .ok_or(Error::InvalidCompressedIndex(current))?;
+            if entry.parent >= current {
+                return Err(Error::InvalidCompressedIndex(entry.parent));
+            }
+            elements.push(entry.element.clone());
+            current = entry.parent;
+        }
+        elements.reverse();
+        Ok(elements)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+struct PrefixTreeEntry<T> {
+    parent: u32,
+    element: T,
+}
+
+struct PrefixTreeBuilder<T> {
+    tree: PrefixTree<T>,
+    entries: HashMap<(u32, T), u32>,
+}
+
+impl<T> Default for PrefixTreeBuilder<T> {
+    fn default() -> Self {
+        Self {
+            tree: PrefixTree::default(),
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Clone + Eq + Hash> PrefixTreeBuilder<T> {
+    /// Adds a stack to the tree, reusing the entries of its longest prefix that is already in the
+    /// tree, and returns the stack's identifier.
+    fn add<I: IntoIterator<Item = T>>(&mut self, elements: I) -> u32 {
+        let mut stack = 0;
+        for element in elements {
+            let entries = &mut self.tree.entries;
+            stack = *self
+                .entries
+                .entry((stack, element.clone()))
+                .or_insert_with(|| {
+                    entries.push(PrefixTreeEntry {
+                        parent: stack,
+                        element,
+                    });
+                    entries.len() as u32
+                });
+        }
+        stack
+    }
+}
+
+struct Interner<T> {
+    values: Vec<T>,
+    indices: HashMap<T, u32>,
+}
+
+impl<T> Default for Interner<T> {
+    fn default() -> Self {
+        Self {
+            values: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Clone + Eq + Hash> Interner<T> {
+    fn intern(&mut self, value: &T) -> u32 {
+        if let Some(index) = self.indices.get(value) {
+            return *index;
+        }
+        let index = self.values.len() as u32;
+        self.values.push(value.clone());
+        self.indices.insert(value.clone(), index);
+        index
+    }
+}
@@ -5,6 +5,7 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::collections::BTreeMap;
 use thiserror::Error;
 
 use crate::arena::Handle;
@@ -15,11 +16,22 @@ use super::NoFilter;
 
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct StackGraph {
     pub files: Files,
     pub nodes: Nodes,
     pub edges: Edges,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Metadata::is_empty")
+    )]
+    pub metadata: Metadata,
 }
 
 #[derive(Debug, Error, Eq, PartialEq)]
@@ -34,6 +46,10 @@ pub enum Error {
     InvalidStackVariable(u32),
     #[error("failed to locate node `{0}` in graph")]
     NodeNotFound(NodeID),
+    #[error("`{0}` is an invalid index into the tables of compressed paths")]
+    InvalidCompressedIndex(u32),
+    #[error("`{0}` is an invalid edge visibility")]
+    InvalidVisibility(String),
 }
 
 impl StackGraph {
@@ -46,10 +62,12 @@ impl StackGraph {
         let files = graph.filter_files(&filter);
         let nodes = graph.filter_nodes(&filter);
         let edges = graph.filter_edges(&filter);
+        let metadata = graph.serializable_metadata();
         Self {
             files,
             nodes,
             edges,
+            metadata,
         }
     }
 
@@ -57,9 +75,16 @@ impl StackGraph {
         self.load_files(graph)?;
         self.load_nodes(graph)?;
         self.load_edges(graph)?;
+        self.load_metadata(graph);
         Ok(())
     }
 
+    fn load_metadata(&self, graph: &mut crate::graph::StackGraph) {
+        for (key, value) in &self.metadata.data {
+            graph.set_metadata(key.as_str(), value.as_str());
+        }
+    }
+
     fn load_files(&self, graph: &mut crate::graph::StackGraph) -> Result<(), Error> {
         for file in self.files.data.iter() {
             graph
@@ -80,33 +105,36 @@ impl StackGraph {
                 Node::PopScopedSymbol {
                     id,
                     symbol,
+                    namespace,
                     is_definition,
                     ..
                 } => {
                     let node_id = id.to_node_id(graph)?;
-                    let symbol_handle = graph.add_symbol(&symbol);
+                    let symbol_handle = graph.add_symbol_in_namespace(symbol, namespace.as_deref());
                     graph.add_pop_scoped_symbol_node(node_id, symbol_handle, *is_definition)
                 }
                 Node::PopSymbol {
                     id,
                     symbol,
+                    namespace,
                     is_definition,
                     ..
                 } => {
                     let node_id = id.to_node_id(graph)?;
-                    let symbol_handle = graph.add_symbol(&symbol);
+                    let symbol_handle = graph.add_symbol_in_namespace(symbol, namespace.as_deref());
                     graph.add_pop_symbol_node(node_id, symbol_handle, *is_definition)
                 }
                 Node::PushScopedSymbol {
                     id,
                     symbol,
+                    namespace,
                     scope,
                     is_reference,
                     ..
                 } => {
                     let node_id = id.to_node_id(graph)?;
                     let scope_id = scope.to_node_id(graph)?;
-                    let symbol_handle = graph.add_symbol(&symbol);
+                    let symbol_handle = graph.add_symbol_in_namespace(symbol, namespace.as_deref());
                     graph.add_push_scoped_symbol_node(
                         node_id,
                         symbol_handle,
@@ -117,18 +145,30 @@ impl StackGraph {
                 Node::PushSymbol {
                     id,
                     symbol,
+                    namespace,
                     is_reference,
                     ..
                 } => {
                     let node_id = id.to_node_id(graph)?;
-                    let symbol_handle = graph.add_symbol(&symbol);
+                    let symbol_handle = graph.add_symbol_in_namespace(symbol, namespace.as_deref());
                     graph.add_push_symbol_node(node_id, symbol_handle, *is_reference)
                 }
                 Node::Scope {
-                    id, is_exported, ..
+                    id,
+                    is_exported,
+                    excluded_symbols,
+                    ..
                 } => {
                     let node_id = id.to_node_id(graph)?;
-                    graph.add_scope_node(node_id, *is_exported)
+                    let handle = graph.add_scope_node(node_id, *is_exported);
+                    if let (Some(handle), Some(excluded_symbols)) = (handle, excluded_symbols) {
+                        let excluded_symbols = excluded_symbols
+                            .iter()
+                            .map(|symbol| graph.add_symbol(symbol))
+                            .collect::<Vec<_>>();
+                        graph.set_excluded_symbols(handle, &excluded_symbols);
+                    }
+                    handle
                 }
                 Node::JumpToScope { .. } | Node::Root { .. } => None,
             };
@@ -171,6 +211,8 @@ impl StackGraph {
             sink,
             precedence,
             debug_info,
+            root_key,
+            visibility,
         } in &self.edges.data
         {
             let source_id = source.to_node_id(graph)?;
@@ -184,6 +226,17 @@ impl StackGraph {
                 .ok_or(Error::InvalidGlobalNodeID(sink.local_id))?;
 
             graph.add_edge(source_handle, sink_handle, *precedence);
+            if let Some(root_key) = root_key {
+                let root_key = root_key
+                    .iter()
+                    .map(|symbol| graph.add_symbol(symbol))
+                    .collect::<Vec<_>>();
+                graph.set_root_edge_key(source_handle, sink_handle, &root_key);
+            }
+            if let Some(visibility) = visibility {
+                let visibility = visibility.parse().map_err(Error::InvalidVisibility)?;
+                graph.set_edge_visibility(source_handle, sink_handle, visibility);
+            }
 
             // load debug-info of each node
             if let Some(debug_info) = debug_info {
@@ -200,6 +253,32 @@ impl StackGraph {
         }
         Ok(())
     }
+
+    /// Calls `f` for every string in this graph, except the metadata, in a fixed order that only
+    /// depends on the shape of the graph.  This allows the strings to be stored separately from
+    /// the graph, and be put back in the same places later.
+    #[cfg(feature = "storage")]
+    pub(crate) fn for_each_string_mut<F: FnMut(&mut String)>(&mut self, mut f: F) {
+        for file in &mut self.files.data {
+            f(file);
+        }
+        for node in &mut self.nodes.data {
+            node.for_each_string_mut(&mut f);
+        }
+        for edge in &mut self.edges.data {
+            edge.source.for_each_string_mut(&mut f);
+            edge.sink.for_each_string_mut(&mut f);
+            if let Some(debug_info) = &mut edge.debug_info {
+                debug_info.for_each_string_mut(&mut f);
+            }
+            for symbol in edge.root_key.iter_mut().flatten() {
+                f(symbol);
+            }
+            if let Some(visibility) = &mut edge.visibility {
+                f(visibility);
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
@@ -208,17 +287,53 @@ impl StackGraph {
     derive(serde::Deserialize, serde::Serialize),
     serde(transparent)
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct Files {
     pub data: Vec<String>,
 }
 
+/// The metadata entries of a stack graph.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(transparent)
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+pub struct Metadata {
+    pub data: BTreeMap<String, String>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 #[cfg_attr(
     feature = "serde",
     derive(serde::Deserialize, serde::Serialize),
     serde(transparent)
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct Nodes {
     pub data: Vec<Node>,
@@ -231,6 +346,12 @@ pub struct Nodes {
     derive(serde::Deserialize, serde::Serialize),
     serde(tag = "type", rename_all = "snake_case"),
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub enum Node {
     DropScopes {
@@ -248,6 +369,7 @@ pub enum Node {
     PopScopedSymbol {
         id: NodeID,
         symbol: String,
+        namespace: Option<String>,
         is_definition: bool,
         source_info: Option<SourceInfo>,
         debug_info: Option<DebugInfo>,
@@ -256,6 +378,7 @@ pub enum Node {
     PopSymbol {
         id: NodeID,
         symbol: String,
+        namespace: Option<String>,
         is_definition: bool,
         source_info: Option<SourceInfo>,
         debug_info: Option<DebugInfo>,
@@ -264,6 +387,7 @@ pub enum Node {
     PushScopedSymbol {
         id: NodeID,
         symbol: String,
+        namespace: Option<String>,
         scope: NodeID,
         is_reference: bool,
         source_info: Option<SourceInfo>,
@@ -273,6 +397,7 @@ pub enum Node {
     PushSymbol {
         id: NodeID,
         symbol: String,
+        namespace: Option<String>,
         is_reference: bool,
         source_info: Option<SourceInfo>,
         debug_info: Option<DebugInfo>,
@@ -287,12 +412,100 @@ pub enum Node {
     Scope {
         id: NodeID,
         is_exported: bool,
+        /// The symbols that are excluded from paths through the scope.
+        excluded_symbols: Option<Vec<String>>,
         source_info: Option<SourceInfo>,
         debug_info: Option<DebugInfo>,
     },
 }
 
 impl Node {
+    #[cfg(feature = "storage")]
+    fn for_each_string_mut<F: FnMut(&mut String)>(&mut self, f: &mut F) {
+        let (id, source_info, debug_info) = match self {
+            Self::DropScopes {
+                id,
+                source_info,
+                debug_info,
+            }
+            | Self::JumpToScope {
+                id,
+                source_info,
+                debug_info,
+            }
+            | Self::Root {
+                id,
+                source_info,
+                debug_info,
+            } => (id, source_info, debug_info),
+            Self::Scope {
+                id,
+                excluded_symbols,
+                source_info,
+                debug_info,
+                ..
+            } => {
+                for symbol in excluded_symbols.iter_mut().flatten() {
+                    f(symbol);
+                }
+                (id, source_info, debug_info)
+            }
+            Self::PopScopedSymbol {
+                id,
+                symbol,
+                namespace,
+                source_info,
+                debug_info,
+                ..
+            }
+            | Self::PopSymbol {
+                id,
+                symbol,
+                namespace,
+                source_info,
+                debug_info,
+                ..
+            }
+            | Self::PushSymbol {
+                id,
+                symbol,
+                namespace,
+                source_info,
+                debug_info,
+                ..
+            } => {
+                f(symbol);
+                if let Some(namespace) = namespace {
+                    f(namespace);
+                }
+                (id, source_info, debug_info)
+            }
+            Self::PushScopedSymbol {
+                id,
+                symbol,
+                namespace,
+                scope,
+                source_info,
+                debug_info,
+                ..
+            } => {
+                f(symbol);
+                if let Some(namespace) = namespace {
+                    f(namespace);
+                }
+                scope.for_each_string_mut(f);
+                (id, source_info, debug_info)
+            }
+        };
+        id.for_each_string_mut(f);
+        if let Some(syntax_type) = source_info.as_mut().and_then(|i| i.syntax_type.as_mut()) {
+            f(syntax_type);
+        }
+        if let Some(debug_info) = debug_info {
+            debug_info.for_each_string_mut(f);
+        }
+    }
+
     fn source_info(&self) -> Option<&SourceInfo> {
         match self {
             Self::DropScopes { source_info, .. } => source_info,
@@ -328,6 +541,12 @@ impl Node {
     serde_with::skip_serializing_none, // must come before derive
     derive(serde::Deserialize, serde::Serialize),
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct SourceInfo {
     pub span: lsp_positions::Span,
@@ -340,25 +559,53 @@ pub struct SourceInfo {
     derive(serde::Deserialize, serde::Serialize),
     serde(transparent)
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct DebugInfo {
     pub data: Vec<DebugEntry>,
 }
 
+impl DebugInfo {
+    #[cfg(feature = "storage")]
+    fn for_each_string_mut<F: FnMut(&mut String)>(&mut self, f: &mut F) {
+        for entry in &mut self.data {
+            f(&mut entry.key);
+            f(&mut entry.value);
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct DebugEntry {
     pub key: String,
     pub value: String,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 #[cfg_attr(
     feature = "serde",
     serde_with::skip_serializing_none, // must come before derive
     derive(serde::Deserialize, serde::Serialize),
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct NodeID {
     pub file: Option<String>,
@@ -366,6 +613,13 @@ pub struct NodeID {
 }
 
 impl NodeID {
+    #[cfg(feature = "storage")]
+    fn for_each_string_mut<F: FnMut(&mut String)>(&mut self, f: &mut F) {
+        if let Some(file) = &mut self.file {
+            f(file);
+        }
+    }
+
     pub fn from_node_id(graph: &crate::graph::StackGraph, value: crate::graph::NodeID) -> Self {
         Self {
             file: value.file().map(|f| graph[f].to_string()),
@@ -404,6 +658,45 @@ impl NodeID {
             .node_for_id(value)
             .ok_or_else(|| Error::NodeNotFound(self.clone()))?)
     }
+
+    #[cfg(feature = "storage")]
+    pub(crate) fn is_root(&self) -> bool {
+        self.file.is_none() && self.local_id == crate::graph::ROOT_NODE_ID
+    }
+}
+
+#[cfg(feature = "archive")]
+impl ArchivedNodeID {
+    pub fn to_node_id(
+        &self,
+        graph: &crate::graph::StackGraph,
+    ) -> Result<crate::graph::NodeID, Error> {
+        if let Some(file) = self.file.as_ref() {
+            let file = graph
+                .get_file(file)
+                .ok_or_else(|| Error::FileNotFound(file.to_string()))?;
+            Ok(crate::graph::NodeID::new_in_file(file, self.local_id))
+        } else if self.local_id == crate::graph::JUMP_TO_NODE_ID {
+            Ok(crate::graph::NodeID::jump_to())
+        } else if self.local_id == crate::graph::ROOT_NODE_ID {
+            Ok(crate::graph::NodeID::root())
+        } else {
+            Err(Error::InvalidGlobalNodeID(self.local_id))
+        }
+    }
+
+    pub fn to_node(
+        &self,
+        graph: &mut crate::graph::StackGraph,
+    ) -> Result<Handle<crate::graph::Node>, Error> {
+        let value = self.to_node_id(graph)?;
+        graph.node_for_id(value).ok_or_else(|| {
+            Error::NodeNotFound(NodeID {
+                file: self.file.as_ref().map(|file| file.to_string()),
+                local_id: self.local_id,
+            })
+        })
+    }
 }
 
 impl std::fmt::Display for NodeID {
@@ -421,6 +714,12 @@ impl std::fmt::Display for NodeID {
     derive(serde::Deserialize, serde::Serialize),
     serde(transparent)
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct Edges {
     pub data: Vec<Edge>,
@@ -432,12 +731,22 @@ pub struct Edges {
     serde_with::skip_serializing_none, // must come before derive
     derive(serde::Deserialize, serde::Serialize),
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct Edge {
     pub source: NodeID,
     pub sink: NodeID,
     pub precedence: i32,
     pub debug_info: Option<DebugInfo>,
+    /// The symbol stack key of an edge into or out of the root node.
+    pub root_key: Option<Vec<String>>,
+    /// The visibility class of the edge, if it is not public.
+    pub visibility: Option<String>,
 }
 
 impl crate::graph::StackGraph {
@@ -449,6 +758,15 @@ impl crate::graph::StackGraph {
         crate::serde::StackGraph::from_graph_filter(self, f)
     }
 
+    fn serializable_metadata(&self) -> Metadata {
+        Metadata {
+            data: self
+                .iter_metadata()
+                .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                .collect(),
+        }
+    }
+
     fn filter_files<'a>(&self, filter: &'a dyn Filter) -> Files {
         Files {
             data: self
@@ -517,6 +835,7 @@ impl crate::graph::StackGraph {
                         crate::graph::Node::PopScopedSymbol(node) => Node::PopScopedSymbol {
                             id,
                             symbol: self[node.symbol].to_owned(),
+                            namespace: self.symbol_namespace(node.symbol).map(str::to_owned),
                             is_definition: node.is_definition,
                             source_info,
                             debug_info,
@@ -524,6 +843,7 @@ impl crate::graph::StackGraph {
                         crate::graph::Node::PopSymbol(node) => Node::PopSymbol {
                             id,
                             symbol: self[node.symbol].to_owned(),
+                            namespace: self.symbol_namespace(node.symbol).map(str::to_owned),
                             is_definition: node.is_definition,
                             source_info,
                             debug_info,
@@ -531,6 +851,7 @@ impl crate::graph::StackGraph {
                         crate::graph::Node::PushScopedSymbol(node) => Node::PushScopedSymbol {
                             id,
                             symbol: self[node.symbol].to_owned(),
+                            namespace: self.symbol_namespace(node.symbol).map(str::to_owned),
                             scope: self.filter_node(filter, node.scope),
                             is_reference: node.is_reference,
                             source_info,
@@ -539,6 +860,7 @@ impl crate::graph::StackGraph {
                         crate::graph::Node::PushSymbol(node) => Node::PushSymbol {
                             id,
                             symbol: self[node.symbol].to_owned(),
+                            namespace: self.symbol_namespace(node.symbol).map(str::to_owned),
                             is_reference: node.is_reference,
                             source_info,
                             debug_info,
@@ -551,6 +873,12 @@ impl crate::graph::StackGraph {
                         crate::graph::Node::Scope(node) => Node::Scope {
                             id,
                             is_exported: node.is_exported,
+                            excluded_symbols: self.excluded_symbols(handle).map(|symbols| {
+                                symbols
+                                    .iter()
+                                    .map(|symbol| self[*symbol].to_string())
+                                    .collect()
+                            }),
                             source_info,
                             debug_info,
                         },
@@ -563,18 +891,20 @@ impl crate::graph::StackGraph {
     fn filter_edges<'a>(&self, filter: &'a dyn Filter) -> Edges {
         Edges {
             data: self
-                .iter_nodes()
-                .map(|source| {
-                    self.outgoing_edges(source)
-                        .filter(|e| filter.include_edge(self, &e.source, &e.sink))
-                        .map(|e| Edge {
-                            source: self.filter_node(filter, self[e.source].id()),
-                            sink: self.filter_node(filter, self[e.sink].id()),
-                            precedence: e.precedence,
-                            debug_info: self.filter_edge_debug_info(filter, e.source, e.sink),
-                        })
+                .iter_edges()
+                .filter(|e| filter.include_edge(self, &e.source, &e.sink))
+                .map(|e| Edge {
+                    source: self.filter_node(filter, self[e.source].id()),
+                    sink: self.filter_node(filter, self[e.sink].id()),
+                    precedence: e.precedence,
+                    debug_info: self.filter_edge_debug_info(filter, e.source, e.sink),
+                    root_key: self
+                        .root_edge_key(e.source, e.sink)
+                        .map(|key| key.iter().map(|symbol| self[*symbol].to_string()).collect()),
+                    visibility: Some(self.edge_visibility(e.source, e.sink))
+                        .filter(|visibility| *visibility != crate::graph::Visibility::Public)
+                        .map(|visibility| visibility.to_string()),
                 })
-                .flatten()
                 .collect::<Vec<_>>(),
         }
     }
@@ -5,11 +5,15 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+mod compressed;
 mod filter;
 mod graph;
 mod partial;
+#[cfg(feature = "schema")]
+pub mod schema;
 mod stitching;
 
+pub use compressed::*;
 pub use filter::*;
 pub use graph::*;
 pub use partial::*;
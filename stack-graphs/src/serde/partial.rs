@@ -12,6 +12,12 @@ use super::NodeID;
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct PartialPath {
     pub(crate) start_node: NodeID,
@@ -79,6 +85,53 @@ impl PartialPath {
             edges: self.edges.to_partial_path_edge_list(graph, partials)?,
         })
     }
+
+    /// Returns the symbols of the postcondition of this path, if the path ends at the root node
+    /// and the postcondition consists of unscoped symbols without namespaces, without a variable.
+    #[cfg(feature = "storage")]
+    pub(crate) fn unscoped_root_postcondition(&self) -> Option<Vec<&str>> {
+        if !self.end_node.is_root() || self.symbol_stack_postcondition.variable.is_some() {
+            return None;
+        }
+        self.symbol_stack_postcondition
+            .symbols
+            .iter()
+            .map(|s| (s.scopes.is_none() && s.namespace.is_none()).then_some(s.symbol.as_str()))
+            .collect()
+    }
+
+    /// Returns the IDs of all nodes this path refers to.
+    #[cfg(any(feature = "archive", feature = "storage", feature = "trace"))]
+    pub(crate) fn node_ids(&self) -> Vec<&NodeID> {
+        let mut nodes = vec![&self.start_node, &self.end_node];
+        nodes.extend(self.edges.edges.iter().map(|e| &e.source));
+        for scope_stack in [
+            &self.scope_stack_precondition,
+            &self.scope_stack_postcondition,
+        ] {
+            nodes.extend(scope_stack.scopes.iter());
+        }
+        for symbol_stack in [
+            &self.symbol_stack_precondition,
+            &self.symbol_stack_postcondition,
+        ] {
+            for symbol in &symbol_stack.symbols {
+                if let Some(scope_stack) = &symbol.scopes {
+                    nodes.extend(scope_stack.scopes.iter());
+                }
+            }
+        }
+        nodes
+    }
+
+    /// Returns the files of all nodes this path refers to.
+    #[cfg(any(feature = "archive", feature = "storage", feature = "trace"))]
+    pub(crate) fn files(&self) -> std::collections::BTreeSet<&str> {
+        self.node_ids()
+            .into_iter()
+            .filter_map(|n| n.file.as_deref())
+            .collect()
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -87,10 +140,16 @@ impl PartialPath {
     serde_with::skip_serializing_none, // must come before derive
     derive(serde::Deserialize, serde::Serialize),
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct PartialScopeStack {
     pub(crate) scopes: Vec<NodeID>,
-    variable: Option<ScopeStackVariable>,
+    pub(crate) variable: Option<ScopeStackVariable>,
 }
 
 impl PartialScopeStack {
@@ -131,14 +190,20 @@ impl PartialScopeStack {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 #[cfg_attr(
     feature = "serde",
     derive(serde::Deserialize, serde::Serialize),
     serde(transparent)
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
-pub struct ScopeStackVariable(u32);
+pub struct ScopeStackVariable(pub(crate) u32);
 
 impl ScopeStackVariable {
     pub fn from_scope_stack_variable(value: crate::partial::ScopeStackVariable) -> Self {
@@ -157,10 +222,16 @@ impl ScopeStackVariable {
     serde_with::skip_serializing_none, // must come before derive
     derive(serde::Deserialize, serde::Serialize),
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct PartialSymbolStack {
     pub(crate) symbols: Vec<PartialScopedSymbol>,
-    variable: Option<SymbolStackVariable>,
+    pub(crate) variable: Option<SymbolStackVariable>,
 }
 
 impl PartialSymbolStack {
@@ -203,14 +274,20 @@ impl PartialSymbolStack {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 #[cfg_attr(
     feature = "serde",
     derive(serde::Deserialize, serde::Serialize),
     serde(transparent)
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
-pub struct SymbolStackVariable(u32);
+pub struct SymbolStackVariable(pub(crate) u32);
 
 impl SymbolStackVariable {
     pub fn from_symbol_stack_variable(value: crate::partial::SymbolStackVariable) -> Self {
@@ -229,9 +306,16 @@ impl SymbolStackVariable {
     serde_with::skip_serializing_none, // must come before derive
     derive(serde::Deserialize, serde::Serialize),
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct PartialScopedSymbol {
-    symbol: String,
+    pub(crate) symbol: String,
+    pub(crate) namespace: Option<String>,
     pub(crate) scopes: Option<PartialScopeStack>,
 }
 
@@ -243,6 +327,7 @@ impl PartialScopedSymbol {
     ) -> Self {
         Self {
             symbol: graph[value.symbol].to_string(),
+            namespace: graph.symbol_namespace(value.symbol).map(str::to_owned),
             scopes: value.scopes.into_option().map(|scopes| {
                 PartialScopeStack::from_partial_scope_stack(graph, partials, &scopes)
             }),
@@ -255,7 +340,7 @@ impl PartialScopedSymbol {
         partials: &mut crate::partial::PartialPaths,
     ) -> Result<crate::partial::PartialScopedSymbol, Error> {
         Ok(crate::partial::PartialScopedSymbol {
-            symbol: graph.add_symbol(&self.symbol),
+            symbol: graph.add_symbol_in_namespace(&self.symbol, self.namespace.as_deref()),
             scopes: self
                 .scopes
                 .as_ref()
@@ -272,6 +357,12 @@ impl PartialScopedSymbol {
     derive(serde::Deserialize, serde::Serialize),
     serde(transparent)
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct PartialPathEdgeList {
     pub(crate) edges: Vec<PartialPathEdge>,
@@ -309,10 +400,16 @@ impl PartialPathEdgeList {
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "archive",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct PartialPathEdge {
     pub(crate) source: NodeID,
-    precedence: i32,
+    pub(crate) precedence: i32,
 }
 
 impl PartialPathEdge {
@@ -338,3 +435,118 @@ impl PartialPathEdge {
         })
     }
 }
+
+// The archived forms of partial paths are converted directly into the arenas of a graph, so that
+// paths can be loaded from an archive without deserializing them first.
+
+#[cfg(feature = "archive")]
+impl ArchivedPartialPath {
+    pub fn to_partial_path(
+        &self,
+        graph: &mut crate::graph::StackGraph,
+        partials: &mut PartialPaths,
+    ) -> Result<crate::partial::PartialPath, Error> {
+        Ok(crate::partial::PartialPath {
+            start_node: self.start_node.to_node(graph)?,
+            end_node: self.end_node.to_node(graph)?,
+            symbol_stack_precondition: self
+                .symbol_stack_precondition
+                .to_partial_symbol_stack(graph, partials)?,
+            symbol_stack_postcondition: self
+                .symbol_stack_postcondition
+                .to_partial_symbol_stack(graph, partials)?,
+            scope_stack_precondition: self
+                .scope_stack_precondition
+                .to_partial_scope_stack(graph, partials)?,
+            scope_stack_postcondition: self
+                .scope_stack_postcondition
+                .to_partial_scope_stack(graph, partials)?,
+            edges: self.edges.to_partial_path_edge_list(graph, partials)?,
+        })
+    }
+}
+
+#[cfg(feature = "archive")]
+impl ArchivedPartialScopeStack {
+    pub fn to_partial_scope_stack(
+        &self,
+        graph: &mut crate::graph::StackGraph,
+        partials: &mut PartialPaths,
+    ) -> Result<crate::partial::PartialScopeStack, Error> {
+        let mut value = match self.variable.as_ref() {
+            Some(variable) => crate::partial::PartialScopeStack::from_variable(
+                crate::partial::ScopeStackVariable::new(variable.0)
+                    .ok_or(Error::InvalidStackVariable(variable.0))?,
+            ),
+            None => crate::partial::PartialScopeStack::empty(),
+        };
+        for scope in self.scopes.iter() {
+            let scope = scope.to_node(graph)?;
+            value.push_back(partials, scope);
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(feature = "archive")]
+impl ArchivedPartialSymbolStack {
+    pub fn to_partial_symbol_stack(
+        &self,
+        graph: &mut crate::graph::StackGraph,
+        partials: &mut PartialPaths,
+    ) -> Result<crate::partial::PartialSymbolStack, Error> {
+        let mut value = match self.variable.as_ref() {
+            Some(variable) => crate::partial::PartialSymbolStack::from_variable(
+                crate::partial::SymbolStackVariable::new(variable.0)
+                    .ok_or(Error::InvalidStackVariable(variable.0))?,
+            ),
+            None => crate::partial::PartialSymbolStack::empty(),
+        };
+        for symbol in self.symbols.iter() {
+            let symbol = symbol.to_partial_scoped_symbol(graph, partials)?;
+            value.push_back(partials, symbol);
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(feature = "archive")]
+impl ArchivedPartialScopedSymbol {
+    pub fn to_partial_scoped_symbol(
+        &self,
+        graph: &mut crate::graph::StackGraph,
+        partials: &mut crate::partial::PartialPaths,
+    ) -> Result<crate::partial::PartialScopedSymbol, Error> {
+        Ok(crate::partial::PartialScopedSymbol {
+            symbol: graph.add_symbol_in_namespace(
+                &self.symbol,
+                self.namespace.as_ref().map(|namespace| namespace.as_str()),
+            ),
+            scopes: self
+                .scopes
+                .as_ref()
+                .map(|scopes| scopes.to_partial_scope_stack(graph, partials))
+                .transpose()?
+                .into(),
+        })
+    }
+}
+
+#[cfg(feature = "archive")]
+impl ArchivedPartialPathEdgeList {
+    pub fn to_partial_path_edge_list(
+        &self,
+        graph: &mut crate::graph::StackGraph,
+        partials: &mut PartialPaths,
+    ) -> Result<crate::partial::PartialPathEdgeList, Error> {
+        let mut value = crate::partial::PartialPathEdgeList::empty();
+        for edge in self.edges.iter() {
+            let edge = crate::partial::PartialPathEdge {
+                source_node_id: edge.source.to_node_id(graph)?,
+                precedence: edge.precedence,
+            };
+            value.push_back(partials, edge);
+        }
+        Ok(value)
+    }
+}
@@ -0,0 +1,34 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Machine-readable descriptions of the serialization formats.
+//!
+//! The schemas are derived from the serde types in this module, so they always describe the
+//! format that this version of the crate reads and writes.  Consumers in other languages can use
+//! them to generate readers for serialized graphs and paths.
+
+use schemars::schema::RootSchema;
+use schemars::schema_for;
+
+use super::Database;
+use super::PartialPath;
+use super::StackGraph;
+
+/// Returns the JSON Schema of a serialized [`StackGraph`][].
+pub fn stack_graph() -> RootSchema {
+    schema_for!(StackGraph)
+}
+
+/// Returns the JSON Schema of a serialized [`PartialPath`][].
+pub fn partial_path() -> RootSchema {
+    schema_for!(PartialPath)
+}
+
+/// Returns the JSON Schema of a serialized [`Database`][].
+pub fn database() -> RootSchema {
+    schema_for!(Database)
+}
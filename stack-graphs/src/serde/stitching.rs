@@ -20,8 +20,9 @@ use super::PartialPath;
     derive(serde::Deserialize, serde::Serialize),
     serde(transparent)
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Database {
-    paths: Vec<PartialPath>,
+    pub(crate) paths: Vec<PartialPath>,
 }
 
 impl Database {
@@ -0,0 +1,530 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Stores graphs and partial paths in a single, self-contained `.sgdb` file.
+//!
+//! An `.sgdb` file is easier to ship around than a SQLite database, for example as the artifact of
+//! a CI job.  The file is only ever appended to.  It consists of a header, followed by records
+//! that contain the graphs and paths of files, and a footer with an index of the records:
+//!
+//! ```text
+//! "SGDB" version:u32 record* index index-offset:u64 "SGDB"
+//! ```
+//!
+//! Paths are stored [compressed][crate::serde::CompressedPartialPath], with one set of
+//! [compression tables][crate::serde::CompressionTables] per file that is shared by all its paths.
+//!
+//! Reopening a file with an [`SgdbWriter`][] appends new records and a new footer, which replaces
+//! the index of the previous footer.  Records of files that are stored again are left in place,
+//! but are no longer referenced by the index.  An [`SgdbReader`][] reads the index from the last
+//! footer, and loads records on demand.  It implements [`StorageBackend`][], so queries can be
+//! answered from an `.sgdb` file in the same way as from a SQLite database.
+//!
+//! ```
+//! # use stack_graphs::graph::StackGraph;
+//! # use stack_graphs::partial::PartialPaths;
+//! # use stack_graphs::sgdb::SgdbReader;
+//! # use stack_graphs::sgdb::SgdbWriter;
+//! # let path = std::env::temp_dir().join(format!("doc-{}.sgdb", std::process::id()));
+//! # let mut graph = StackGraph::new();
+//! # let file = graph.get_or_create_file("test.py");
+//! # let mut partials = PartialPaths::new();
+//! let mut writer = SgdbWriter::open(&path)?;
+//! writer.store_result_for_file(&graph, file, "tag", &mut partials, &[])?;
+//! writer.finish()?;
+//!
+//! let mut reader = SgdbReader::open(&path)?;
+//! let file = reader.load_graph_for_file("test.py")?;
+//! # std::fs::remove_file(&path)?;
+//! # Ok::<(), stack_graphs::storage::StorageError>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::fs::OpenOptions;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+
+use crate::arena::Handle;
+use crate::graph::File;
+use crate::graph::StackGraph;
+use crate::lazy::compatible_root_paths;
+use crate::lazy::root_path_query;
+use crate::lazy::PathLoader;
+use crate::partial::PartialPath;
+use crate::partial::PartialPaths;
+use crate::serde;
+use crate::serde::CompressedPartialPath;
+use crate::serde::CompressionTables;
+use crate::serde::FileFilter;
+use crate::serde::PathCompressor;
+use crate::stitching::Database;
+use crate::storage::path_outside_file;
+use crate::storage::FileStatus;
+use crate::storage::Result;
+use crate::storage::StorageBackend;
+use crate::storage::StorageError;
+use crate::storage::BINCODE_CONFIG;
+use crate::CancellationFlag;
+
+/// The version of the file format.  Files of other versions are rejected.
+const VERSION: u32 = 2;
+
+const MAGIC: &[u8; 4] = b"SGDB";
+const HEADER_LEN: u64 = 8;
+const TRAILER_LEN: u64 = 12;
+
+/// The position of a record in the file.
+#[derive(Clone, Copy, bincode::Encode, bincode::Decode)]
+struct Location {
+    offset: u64,
+    length: u64,
+}
+
+#[derive(Default, bincode::Encode, bincode::Decode)]
+struct Index {
+    files: BTreeMap<String, FileEntry>,
+}
+
+#[derive(bincode::Encode, bincode::Decode)]
+struct FileEntry {
+    tag: String,
+    error: Option<String>,
+    /// The record containing the graph of the file.
+    graph: Location,
+    /// The record containing the compression tables of the paths of the file.
+    tables: Location,
+    /// The records containing the paths that start at a node of the file, by local ID.
+    node_paths: Vec<(u32, Location)>,
+    /// The records containing the paths of the file that start at the root node, by symbols of
+    /// their precondition, and whether the precondition has a variable.
+    root_paths: Vec<(Vec<String>, bool, Location)>,
+}
+
+/// The symbols of the precondition of a root path, and whether the precondition has a variable.
+type RootPathKey = (Vec<String>, bool);
+
+//-------------------------------------------------------------------------------------------------
+// Writing files
+
+/// Writer to store stack graphs and partial paths in an `.sgdb` file.
+///
+/// Stored data becomes visible to readers when the writer is [finished][Self::finish].  Dropping
+/// the writer finishes it as well, but ignores any errors.
+pub struct SgdbWriter {
+    file: BufWriter<std::fs::File>,
+    offset: u64,
+    index: Index,
+    finished: bool,
+}
+
+impl SgdbWriter {
+    /// Open a file for writing.  If the file does not exist, it is automatically created.  An
+    /// error is returned if the file exists but is not a valid `.sgdb` file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let offset = file.seek(SeekFrom::End(0))?;
+        let index = if offset == 0 {
+            file.write_all(MAGIC)?;
+            file.write_all(&VERSION.to_le_bytes())?;
+            Index::default()
+        } else {
+            read_index(&mut file)?
+        };
+        let offset = file.seek(SeekFrom::End(0))?;
+        Ok(Self {
+            file: BufWriter::new(file),
+            offset,
+            index,
+            finished: false,
+        })
+    }
+
+    /// Store the result of a successful file index.  Any data previously stored for the file is
+    /// replaced.
+    pub fn store_result_for_file<'a, IP>(
+        &mut self,
+        graph: &StackGraph,
+        file: Handle<File>,
+        tag: &str,
+        partials: &mut PartialPaths,
+        paths: IP,
+    ) -> Result<()>
+    where
+        IP: IntoIterator<Item = &'a PartialPath>,
+    {
+        copious_debugging!("--> Store result for {}", graph[file].name());
+        let file_graph = serde::StackGraph::from_graph_filter(graph, &FileFilter(file));
+        let graph_location = self.append(&file_graph)?;
+
+        let mut compressor = PathCompressor::new();
+        let mut node_paths = BTreeMap::<u32, Vec<CompressedPartialPath>>::new();
+        let mut root_paths = BTreeMap::<RootPathKey, Vec<CompressedPartialPath>>::new();
+        for path in paths {
+            let start_node = graph[path.start_node].id();
+            let serialized = serde::PartialPath::from_partial_path(graph, partials, path);
+            let compressed = compressor.compress(&serialized);
+            if start_node.is_root() {
+                let precondition = &serialized.symbol_stack_precondition;
+                let symbols = precondition
+                    .symbols
+                    .iter()
+                    .map(|s| s.symbol.clone())
+                    .collect();
+                root_paths
+                    .entry((symbols, precondition.variable.is_some()))
+                    .or_default()
+                    .push(compressed);
+            } else if start_node.is_in_file(file) {
+                node_paths
+                    .entry(start_node.local_id())
+                    .or_default()
+                    .push(compressed);
+            } else {
+                return Err(path_outside_file(graph, partials, path, file));
+            }
+        }
+
+        let mut entry = FileEntry {
+            tag: tag.to_string(),
+            error: None,
+            graph: graph_location,
+            tables: self.append(&compressor.finish())?,
+            node_paths: Vec::with_capacity(node_paths.len()),
+            root_paths: Vec::with_capacity(root_paths.len()),
+        };
+        for (local_id, paths) in node_paths {
+            entry.node_paths.push((local_id, self.append(&paths)?));
+        }
+        for ((symbols, has_variable), paths) in root_paths {
+            entry
+                .root_paths
+                .push((symbols, has_variable, self.append(&paths)?));
+        }
+        self.index
+            .files
+            .insert(graph[file].name().to_string(), entry);
+        Ok(())
+    }
+
+    /// Store an error, indicating that indexing this file failed.  Any data previously stored for
+    /// the file is replaced.
+    pub fn store_error_for_file(&mut self, file: &Path, tag: &str, error: &str) -> Result<()> {
+        copious_debugging!("--> Store error for {}", file.display());
+        let graph = self.append(&serde::StackGraph::default())?;
+        let tables = self.append(&CompressionTables::default())?;
+        self.index.files.insert(
+            file.to_string_lossy().to_string(),
+            FileEntry {
+                tag: tag.to_string(),
+                error: Some(error.to_string()),
+                graph,
+                tables,
+                node_paths: Vec::new(),
+                root_paths: Vec::new(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Remove the file from the index.  Returns whether the file was stored.
+    pub fn clean_file(&mut self, file: &Path) -> bool {
+        self.index
+            .files
+            .remove(file.to_string_lossy().as_ref())
+            .is_some()
+    }
+
+    /// Write the index, which makes all data stored by this writer visible to readers.
+    pub fn finish(mut self) -> Result<()> {
+        self.write_footer()
+    }
+
+    fn write_footer(&mut self) -> Result<()> {
+        self.finished = true;
+        let index_offset = self.offset;
+        let index = bincode::encode_to_vec(&self.index, BINCODE_CONFIG)?;
+        self.append_bytes(&index)?;
+        self.file.write_all(&index_offset.to_le_bytes())?;
+        self.file.write_all(MAGIC)?;
+        self.file.flush()?;
+        self.offset += TRAILER_LEN;
+        Ok(())
+    }
+
+    /// Appends a record, and returns its location.
+    fn append<T: bincode::Encode>(&mut self, value: &T) -> Result<Location> {
+        let bytes = bincode::encode_to_vec(value, BINCODE_CONFIG)?;
+        self.append_bytes(&bytes)
+    }
+
+    fn append_bytes(&mut self, bytes: &[u8]) -> Result<Location> {
+        self.file.write_all(bytes)?;
+        let location = Location {
+            offset: self.offset,
+            length: bytes.len() as u64,
+        };
+        self.offset += location.length;
+        Ok(location)
+    }
+}
+
+impl Drop for SgdbWriter {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.write_footer();
+        }
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Reading files
+
+/// Reader to load stack graphs and partial paths from an `.sgdb` file.  Records are read from the
+/// file when they are needed, so the file must not be removed while the reader is in use.
+pub struct SgdbReader {
+    file: std::fs::File,
+    index: Index,
+    /// The locations of root paths with the file they belong to, sorted by key.
+    root_paths: Vec<(RootPathKey, Vec<(String, Location)>)>,
+    /// The compression tables of the files whose paths have been loaded.
+    tables: HashMap<String, CompressionTables>,
+    loaded_node_paths: HashSet<(String, u32)>,
+    loaded_root_paths: HashSet<usize>,
+    graph: StackGraph,
+    partials: PartialPaths,
+    db: Database,
+}
+
+impl SgdbReader {
+    /// Open a file for reading.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(StorageError::MissingDatabase(
+                path.to_string_lossy().to_string(),
+            ));
+        }
+        let mut file = std::fs::File::open(path)?;
+        let index = read_index(&mut file)?;
+        let mut root_paths = BTreeMap::<_, Vec<_>>::new();
+        for (name, entry) in &index.files {
+            for (symbols, has_variable, location) in &entry.root_paths {
+                root_paths
+                    .entry((symbols.clone(), *has_variable))
+                    .or_default()
+                    .push((name.clone(), *location));
+            }
+        }
+        Ok(Self {
+            file,
+            index,
+            root_paths: root_paths.into_iter().collect(),
+            tables: HashMap::new(),
+            loaded_node_paths: HashSet::new(),
+            loaded_root_paths: HashSet::new(),
+            graph: StackGraph::new(),
+            partials: PartialPaths::new(),
+            db: Database::new(),
+        })
+    }
+
+    /// Returns the names of the stored files.
+    pub fn files(&self) -> impl Iterator<Item = &str> + '_ {
+        self.index.files.keys().map(String::as_str)
+    }
+
+    /// Get the file's status.  If a tag is provided, it must match or the file is reported
+    /// missing.
+    pub fn status_for_file(&self, file: &str, tag: Option<&str>) -> FileStatus {
+        match self.index.files.get(file) {
+            Some(entry) if tag.is_none() || tag == Some(entry.tag.as_str()) => match &entry.error {
+                Some(error) => FileStatus::Error(error.clone()),
+                None => FileStatus::Indexed,
+            },
+            _ => FileStatus::Missing,
+        }
+    }
+
+    /// Ensure the graph for the given file is loaded.
+    pub fn load_graph_for_file(&mut self, file: &str) -> Result<Handle<File>> {
+        if let Some(handle) = self.graph.get_file(file) {
+            return Ok(handle);
+        }
+        copious_debugging!(" * Load graph for {}", file);
+        let location = match self.index.files.get(file) {
+            Some(entry) => entry.graph,
+            None => return Err(StorageError::MissingFile(file.to_string())),
+        };
+        let file_graph: serde::StackGraph = read_record(&mut self.file, location)?;
+        file_graph.load_into(&mut self.graph)?;
+        Ok(self.graph.get_or_create_file(file))
+    }
+
+    /// Ensure all possible extensions for the given partial path are loaded.
+    pub fn load_partial_path_extensions(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        let end_node = &self.graph[path.end_node];
+        let mut locations = Vec::new();
+        if end_node.is_root() {
+            let (symbols, has_variable) = root_path_query(&self.graph, &mut self.partials, path);
+            for index in compatible_root_paths(&self.root_paths, root_key, &symbols, has_variable) {
+                if self.loaded_root_paths.insert(index) {
+                    locations.extend(self.root_paths[index].1.iter().cloned());
+                }
+            }
+        } else if let Some(file) = end_node.id().file() {
+            let name = self.graph[file].name().to_string();
+            let local_id = end_node.id().local_id();
+            if self.loaded_node_paths.insert((name.clone(), local_id)) {
+                if let Some(entry) = self.index.files.get(&name) {
+                    locations.extend(
+                        entry
+                            .node_paths
+                            .iter()
+                            .filter(|(id, _)| *id == local_id)
+                            .map(|(_, location)| (name.clone(), *location)),
+                    );
+                }
+            }
+        }
+        for (file, location) in locations {
+            cancellation_flag.check("loading paths")?;
+            self.load_graph_for_file(&file)?;
+            if !self.tables.contains_key(&file) {
+                let entry = &self.index.files[&file];
+                let tables = read_record(&mut self.file, entry.tables)?;
+                self.tables.insert(file.clone(), tables);
+            }
+            let paths: Vec<CompressedPartialPath> = read_record(&mut self.file, location)?;
+            for path in paths {
+                let path = self.tables[&file].decompress(&path)?;
+                for file in path.files() {
+                    self.load_graph_for_file(file)?;
+                }
+                let path = path.to_partial_path(&mut self.graph, &mut self.partials)?;
+                self.db
+                    .add_partial_path(&self.graph, &mut self.partials, path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the stack graph, partial paths arena, and path database for the currently loaded data.
+    pub fn get(&mut self) -> (&mut StackGraph, &mut PartialPaths, &mut Database) {
+        (&mut self.graph, &mut self.partials, &mut self.db)
+    }
+}
+
+fn root_key(entry: &(RootPathKey, Vec<(String, Location)>)) -> (impl Iterator<Item = &str>, bool) {
+    let ((symbols, has_variable), _) = entry;
+    (symbols.iter().map(String::as_str), *has_variable)
+}
+
+impl PathLoader for SgdbReader {
+    type Error = StorageError;
+
+    fn load_partial_path_extensions(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        SgdbReader::load_partial_path_extensions(self, path, cancellation_flag)
+    }
+
+    fn loaded(&mut self) -> (&StackGraph, &mut PartialPaths, &mut Database) {
+        (&self.graph, &mut self.partials, &mut self.db)
+    }
+
+    fn loaded_database(&self) -> &Database {
+        &self.db
+    }
+}
+
+impl StorageBackend for SgdbReader {
+    fn status_for_file(&mut self, file: &str, tag: Option<&str>) -> Result<FileStatus> {
+        Ok(SgdbReader::status_for_file(self, file, tag))
+    }
+
+    fn load_graph_for_file(&mut self, file: &str) -> Result<Handle<File>> {
+        SgdbReader::load_graph_for_file(self, file)
+    }
+
+    fn get(&mut self) -> (&mut StackGraph, &mut PartialPaths, &mut Database) {
+        SgdbReader::get(self)
+    }
+}
+
+/// Reads the index from the footer at the end of the file.
+fn read_index(file: &mut std::fs::File) -> Result<Index> {
+    let length = file.seek(SeekFrom::End(0))?;
+    if length < HEADER_LEN + TRAILER_LEN {
+        return Err(StorageError::InvalidDatabaseFile(
+            "file is too short".to_string(),
+        ));
+    }
+    let mut header = [0u8; HEADER_LEN as usize];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(StorageError::InvalidDatabaseFile(
+            "missing header".to_string(),
+        ));
+    }
+    let version = u32::from_le_bytes(header[4..].try_into().unwrap());
+    if version != VERSION {
+        return Err(StorageError::IncorrectVersion(version as usize));
+    }
+    let mut trailer = [0u8; TRAILER_LEN as usize];
+    file.seek(SeekFrom::Start(length - TRAILER_LEN))?;
+    file.read_exact(&mut trailer)?;
+    if &trailer[8..] != MAGIC {
+        return Err(StorageError::InvalidDatabaseFile(
+            "missing footer, the writer may not have finished".to_string(),
+        ));
+    }
+    let offset = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+    if offset < HEADER_LEN || offset > length - TRAILER_LEN {
+        return Err(StorageError::InvalidDatabaseFile(
+            "index offset out of bounds".to_string(),
+        ));
+    }
+    let location = Location {
+        offset,
+        length: length - TRAILER_LEN - offset,
+    };
+    read_record(file, location)
+}
+
+fn read_record<T: bincode::Decode<()>>(file: &mut std::fs::File, location: Location) -> Result<T> {
+    // check the location before allocating, so that corrupt files cannot cause huge allocations
+    let file_length = file.metadata()?.len();
+    let end = location.offset.checked_add(location.length);
+    if !matches!(end, Some(end) if end <= file_length) {
+        return Err(StorageError::InvalidDatabaseFile(
+            "record out of bounds".to_string(),
+        ));
+    }
+    let mut bytes = vec![0u8; location.length as usize];
+    file.seek(SeekFrom::Start(location.offset))?;
+    file.read_exact(&mut bytes)?;
+    let (value, _) = bincode::decode_from_slice(&bytes, BINCODE_CONFIG)?;
+    Ok(value)
+}
@@ -0,0 +1,154 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Normalized text dumps of stack graphs and partial paths, for snapshot testing.
+//!
+//! Language implementations often want to check that the stack graph and partial paths produced
+//! for a test file do not change unexpectedly, for example with [insta][] snapshots. Handles are
+//! not suitable for that, because they depend on the order in which files and nodes were added to
+//! the graph. The dumps produced here identify nodes by their file and local ID instead, and list
+//! all elements in sorted order, so that a dump only changes if the graph changes, and a small
+//! change to the graph results in a small change to the dump.
+//!
+//! The dump of a graph with a single reference resolving to a definition looks like this:
+//!
+//! ``` text
+//! files:
+//!   test.py
+//! nodes:
+//!   [root]
+//!   [jump to scope]
+//!   [test.py(1) reference x] at 0:0-0:1
+//!   [test.py(2) definition x] at 1:0-1:1
+//! edges:
+//!   test.py(1) -> test.py(2)
+//! paths:
+//!   <> () [test.py(1) reference x] -> [test.py(2) definition x] <> ()
+//! ```
+//!
+//! Line and column numbers are 0-based. Spans and syntax types are only listed if the node has
+//! source info, and edge precedences only if they are not zero.
+//!
+//! [insta]: https://insta.rs
+
+use std::fmt::Write;
+
+use crate::arena::Handle;
+use crate::graph::Node;
+use crate::graph::StackGraph;
+use crate::partial::PartialPaths;
+use crate::serde::Filter;
+use crate::serde::ImplicationFilter;
+use crate::stitching::Database;
+
+impl StackGraph {
+    /// Renders the graph, and the paths in the database, in a normalized text format that is
+    /// suitable for snapshot testing.
+    pub fn to_snapshot_string(
+        &self,
+        partials: &mut PartialPaths,
+        db: &Database,
+        filter: &dyn Filter,
+    ) -> String {
+        let filter = ImplicationFilter(filter);
+
+        let mut files = self
+            .iter_files()
+            .filter(|f| filter.include_file(self, f))
+            .map(|f| self[f].name().to_string())
+            .collect::<Vec<_>>();
+        files.sort();
+
+        let mut nodes = self
+            .iter_nodes()
+            .filter(|n| filter.include_node(self, n))
+            .map(|n| (self.node_sort_key(n), self.snapshot_node(n)))
+            .collect::<Vec<_>>();
+        nodes.sort();
+
+        let mut edges = Vec::new();
+        for edge in self.iter_edges() {
+            if !filter.include_edge(self, &edge.source, &edge.sink) {
+                continue;
+            }
+            let mut line = format!(
+                "{} -> {}",
+                self[edge.source].id().display(self),
+                self[edge.sink].id().display(self)
+            );
+            if edge.precedence != 0 {
+                let _ = write!(line, " (precedence {})", edge.precedence);
+            }
+            edges.push((
+                (
+                    self.node_sort_key(edge.source),
+                    self.node_sort_key(edge.sink),
+                ),
+                line,
+            ));
+        }
+        edges.sort();
+
+        let mut paths = Vec::new();
+        for handle in db.iter_partial_paths() {
+            let path = &db[handle];
+            if !filter.include_partial_path(self, partials, path) {
+                continue;
+            }
+            paths.push(path.display(self, partials).to_string());
+        }
+        paths.sort();
+
+        let mut snapshot = String::new();
+        // Writing to a String cannot fail, so we ignore the results below.
+        let _ = writeln!(snapshot, "files:");
+        for file in files {
+            let _ = writeln!(snapshot, "  {}", file);
+        }
+        let _ = writeln!(snapshot, "nodes:");
+        for (_, node) in nodes {
+            let _ = writeln!(snapshot, "  {}", node);
+        }
+        let _ = writeln!(snapshot, "edges:");
+        for (_, edge) in edges {
+            let _ = writeln!(snapshot, "  {}", edge);
+        }
+        let _ = writeln!(snapshot, "paths:");
+        for path in paths {
+            let _ = writeln!(snapshot, "  {}", path);
+        }
+        snapshot
+    }
+
+    /// Returns a key that orders nodes by file name and local ID, and puts the singleton nodes
+    /// first.
+    fn node_sort_key(&self, node: Handle<Node>) -> (Option<&str>, u32) {
+        let id = self[node].id();
+        (id.file().map(|f| self[f].name()), id.local_id())
+    }
+
+    fn snapshot_node(&self, node: Handle<Node>) -> String {
+        let mut line = node.display(self).to_string();
+        if let Some(source_info) = self.source_info(node) {
+            let span = &source_info.span;
+            if *span != lsp_positions::Span::default() {
+                let _ = write!(
+                    line,
+                    " at {}:{}-{}:{}",
+                    span.start.line,
+                    span.start.column.utf8_offset,
+                    span.end.line,
+                    span.end.column.utf8_offset
+                );
+            }
+            if let Some(syntax_type) = source_info.syntax_type.into_option() {
+                let _ = write!(line, " ({})", &self[syntax_type]);
+            }
+        }
+        line
+    }
+}
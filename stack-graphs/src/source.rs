@@ -0,0 +1,126 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Source text of the files in a stack graph.
+//!
+//! Stack graphs only record the spans of nodes, not the source text of the files they come from.
+//! Visualizations, assertion failures, and stitching traces can include excerpts of the source
+//! code around those spans, if they are given a [`SourceProvider`][] that supplies the source
+//! text of files. This crate never reads files itself, so it is up to the embedder to decide
+//! where the source text comes from, such as the file system, an editor buffer, or a database.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use lsp_positions::Span;
+
+use crate::arena::Handle;
+use crate::graph::Node;
+use crate::graph::StackGraph;
+
+/// Supplies the source text of files, by the file names used in stack graphs.
+pub trait SourceProvider {
+    /// Returns the source text of the given file, or `None` if it is not available.
+    fn source(&self, path: &str) -> Option<Cow<'_, str>>;
+}
+
+impl SourceProvider for HashMap<String, String> {
+    fn source(&self, path: &str) -> Option<Cow<'_, str>> {
+        self.get(path).map(|source| Cow::Borrowed(source.as_str()))
+    }
+}
+
+impl SourceProvider for BTreeMap<String, String> {
+    fn source(&self, path: &str) -> Option<Cow<'_, str>> {
+        self.get(path).map(|source| Cow::Borrowed(source.as_str()))
+    }
+}
+
+impl<F> SourceProvider for F
+where
+    F: Fn(&str) -> Option<String>,
+{
+    fn source(&self, path: &str) -> Option<Cow<'_, str>> {
+        self(path).map(Cow::Owned)
+    }
+}
+
+/// An excerpt of source text, consisting of the first line of a span, in which the span is
+/// marked. Spans that continue on later lines are marked until the end of the first line.
+///
+/// The excerpt is displayed as the line, prefixed with its line number, followed by a line that
+/// marks the span with carets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Excerpt {
+    /// The 1-based number of the line.
+    pub line_number: usize,
+    /// The text of the line, without the line ending.
+    pub line: String,
+    /// The character range of the span within the line.
+    pub columns: std::ops::Range<usize>,
+}
+
+impl Excerpt {
+    /// Returns the excerpt of the given span in the source text of the given file, or `None` if
+    /// the source text is not available, or does not contain the span.
+    pub fn from_span(source: &dyn SourceProvider, path: &str, span: &Span) -> Option<Excerpt> {
+        let text = source.source(path)?;
+        let line = text.lines().nth(span.start.line)?;
+        let start = char_column(line, span.start.column.utf8_offset);
+        let end = if span.end.line == span.start.line {
+            char_column(line, span.end.column.utf8_offset)
+        } else {
+            line.chars().count()
+        };
+        Some(Excerpt {
+            line_number: span.start.line + 1,
+            line: line.to_string(),
+            columns: start..end.max(start + 1),
+        })
+    }
+
+    /// Returns the excerpt of the span of the given node, or `None` if the node has no span, or
+    /// its source text is not available.
+    pub fn from_node(
+        graph: &StackGraph,
+        source: &dyn SourceProvider,
+        node: Handle<Node>,
+    ) -> Option<Excerpt> {
+        let file = graph[node].file()?;
+        let span = &graph.source_info(node)?.span;
+        Self::from_span(source, graph[file].name(), span)
+    }
+
+    /// Returns the line of the excerpt without leading and trailing whitespace, which is useful
+    /// for showing the excerpt in a single line.
+    pub fn trimmed_line(&self) -> &str {
+        self.line.trim()
+    }
+}
+
+impl std::fmt::Display for Excerpt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let number = self.line_number.to_string();
+        writeln!(f, "{} | {}", number, self.line)?;
+        write!(
+            f,
+            "{} | {}{}",
+            " ".repeat(number.len()),
+            " ".repeat(self.columns.start),
+            "^".repeat(self.columns.end - self.columns.start)
+        )
+    }
+}
+
+/// Returns the character column of the given byte offset in the line. Offsets past the end of
+/// the line are clamped to the end of the line.
+fn char_column(line: &str, utf8_offset: usize) -> usize {
+    line.char_indices()
+        .take_while(|(index, _)| *index < utf8_offset)
+        .count()
+}
@@ -0,0 +1,297 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Spilling queued partial paths to disk.
+//!
+//! The queue of a path stitcher can grow without bound on pathological graphs.  A [`PathSpill`][]
+//! moves queued paths that do not fit in the stitcher's memory budget into a file, and gives them
+//! back, in the same order, once there is room in the queue again.
+//!
+//! Spilled paths are encoded by value: the contents of their symbol stacks, scope stacks, and
+//! edges are copied into the spill file, and are allocated in the partial path arena again when the
+//! paths are read back.  The encoding still refers to the nodes, symbols, and files of the stack
+//! graph, and to the cycle detectors of the stitcher, so the spill file is only meaningful for the
+//! stitcher and graph that created it.  It is removed when the spill is dropped.
+
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use controlled_option::ControlledOption;
+
+use crate::arena::Handle;
+use crate::cycles::AppendingCycleDetector;
+use crate::graph::NodeID;
+use crate::partial::PartialPath;
+use crate::partial::PartialPathEdge;
+use crate::partial::PartialPathEdgeList;
+use crate::partial::PartialPaths;
+use crate::partial::PartialScopeStack;
+use crate::partial::PartialScopedSymbol;
+use crate::partial::PartialSymbolStack;
+use crate::partial::ScopeStackVariable;
+use crate::partial::SymbolStackVariable;
+
+/// Used to give the spill files of all stitchers in this process distinct names.
+static NEXT_SPILL_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A queued path of a stitcher: the path, its cycle detector, and whether it has split.
+pub(crate) type QueuedPath<H> = (PartialPath, AppendingCycleDetector<H>, bool);
+
+/// A first-in first-out store of queued paths in a file.
+pub(crate) struct PathSpill {
+    path: PathBuf,
+    file: File,
+    read_offset: u64,
+    write_offset: u64,
+    len: usize,
+}
+
+impl PathSpill {
+    /// Creates a new, empty spill in a new file in the given directory.
+    pub(crate) fn create(directory: &Path) -> std::io::Result<PathSpill> {
+        let path = directory.join(format!(
+            "stack-graphs-queue-{}-{}.spill",
+            std::process::id(),
+            NEXT_SPILL_ID.fetch_add(1, Ordering::Relaxed),
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(PathSpill {
+            path,
+            file,
+            read_offset: 0,
+            write_offset: 0,
+            len: 0,
+        })
+    }
+
+    /// Returns the number of paths in this spill.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Appends paths to the end of this spill.  Each entry consists of its number of words,
+    /// followed by the encoded path, its cycle detector, and whether it has split.
+    pub(crate) fn write<'a, H: 'a, I>(
+        &mut self,
+        partials: &mut PartialPaths,
+        paths: I,
+    ) -> std::io::Result<()>
+    where
+        I: IntoIterator<Item = &'a QueuedPath<H>>,
+    {
+        let mut words = Vec::new();
+        let mut bytes = Vec::new();
+        let mut count = 0;
+        for (path, cycle_detector, has_split) in paths {
+            words.clear();
+            encode_path(partials, path, &mut words);
+            words.push(cycle_detector.as_raw());
+            words.push(*has_split as u32);
+            bytes.extend((words.len() as u32).to_le_bytes());
+            bytes.extend(words.iter().flat_map(|word| word.to_le_bytes()));
+            count += 1;
+        }
+        self.file.seek(SeekFrom::Start(self.write_offset))?;
+        self.file.write_all(&bytes)?;
+        self.write_offset += bytes.len() as u64;
+        self.len += count;
+        Ok(())
+    }
+
+    /// Removes up to `count` paths from the front of this spill, and appends them to `result`.
+    pub(crate) fn read<H, E>(
+        &mut self,
+        partials: &mut PartialPaths,
+        count: usize,
+        result: &mut E,
+    ) -> std::io::Result<()>
+    where
+        E: std::iter::Extend<QueuedPath<H>>,
+    {
+        let count = count.min(self.len);
+        self.file.seek(SeekFrom::Start(self.read_offset))?;
+        let mut reader = BufReader::new(&mut self.file);
+        let mut bytes = Vec::new();
+        let mut read_bytes = 0;
+        for _ in 0..count {
+            let mut length = [0u8; 4];
+            reader.read_exact(&mut length)?;
+            bytes.resize(u32::from_le_bytes(length) as usize * 4, 0);
+            reader.read_exact(&mut bytes)?;
+            read_bytes += length.len() + bytes.len();
+            let mut words = bytes
+                .chunks_exact(4)
+                .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]));
+            let path = decode_path(partials, &mut words).ok_or_else(invalid_entry)?;
+            let cycle_detector = words
+                .next()
+                .and_then(AppendingCycleDetector::from_raw)
+                .ok_or_else(invalid_entry)?;
+            let has_split = words.next().ok_or_else(invalid_entry)? != 0;
+            result.extend(std::iter::once((path, cycle_detector, has_split)));
+        }
+        self.read_offset += read_bytes as u64;
+        self.len -= count;
+        if self.len == 0 {
+            // Reuse the file from the start, so that it does not grow beyond the largest number of
+            // paths that were spilled at the same time.
+            self.read_offset = 0;
+            self.write_offset = 0;
+            self.file.set_len(0)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PathSpill {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn invalid_entry() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid spilled path")
+}
+
+//-------------------------------------------------------------------------------------------------
+// Encoding
+
+fn encode_path(partials: &mut PartialPaths, path: &PartialPath, words: &mut Vec<u32>) {
+    words.push(path.start_node.as_u32());
+    words.push(path.end_node.as_u32());
+    encode_symbol_stack(partials, path.symbol_stack_precondition, words);
+    encode_symbol_stack(partials, path.symbol_stack_postcondition, words);
+    encode_scope_stack(partials, path.scope_stack_precondition, words);
+    encode_scope_stack(partials, path.scope_stack_postcondition, words);
+    words.push(path.edges.len() as u32);
+    for edge in path.edges.iter(partials) {
+        let node_id = edge.source_node_id;
+        words.push(node_id.file().map_or(0, |file| file.as_u32()));
+        words.push(node_id.local_id());
+        words.push(edge.precedence as u32);
+    }
+}
+
+fn encode_symbol_stack(
+    partials: &mut PartialPaths,
+    stack: PartialSymbolStack,
+    words: &mut Vec<u32>,
+) {
+    words.push(stack.variable().map_or(0, |variable| variable.as_u32()));
+    let symbols = stack.iter(partials).collect::<Vec<_>>();
+    words.push(symbols.len() as u32);
+    for symbol in symbols {
+        words.push(symbol.symbol.as_u32());
+        match symbol.scopes.into_option() {
+            Some(scopes) => {
+                words.push(1);
+                encode_scope_stack(partials, scopes, words);
+            }
+            None => words.push(0),
+        }
+    }
+}
+
+fn encode_scope_stack(partials: &mut PartialPaths, stack: PartialScopeStack, words: &mut Vec<u32>) {
+    words.push(stack.variable().map_or(0, |variable| variable.as_u32()));
+    words.push(stack.len() as u32);
+    words.extend(stack.iter_scopes(partials).map(|node| node.as_u32()));
+}
+
+fn decode_path<I>(partials: &mut PartialPaths, words: &mut I) -> Option<PartialPath>
+where
+    I: Iterator<Item = u32>,
+{
+    let start_node = decode_handle(words)?;
+    let end_node = decode_handle(words)?;
+    let symbol_stack_precondition = decode_symbol_stack(partials, words)?;
+    let symbol_stack_postcondition = decode_symbol_stack(partials, words)?;
+    let scope_stack_precondition = decode_scope_stack(partials, words)?;
+    let scope_stack_postcondition = decode_scope_stack(partials, words)?;
+    let mut edges = PartialPathEdgeList::empty();
+    for _ in 0..words.next()? {
+        let file = words.next()?;
+        let local_id = words.next()?;
+        let source_node_id = match NonZeroU32::new(file).map(Handle::new) {
+            Some(file) => NodeID::new_in_file(file, local_id),
+            None if local_id == NodeID::root().local_id() => NodeID::root(),
+            None if local_id == NodeID::jump_to().local_id() => NodeID::jump_to(),
+            None => return None,
+        };
+        let precedence = words.next()? as i32;
+        edges.push_back(
+            partials,
+            PartialPathEdge {
+                source_node_id,
+                precedence,
+            },
+        );
+    }
+    Some(PartialPath {
+        start_node,
+        end_node,
+        symbol_stack_precondition,
+        symbol_stack_postcondition,
+        scope_stack_precondition,
+        scope_stack_postcondition,
+        edges,
+    })
+}
+
+fn decode_symbol_stack<I>(partials: &mut PartialPaths, words: &mut I) -> Option<PartialSymbolStack>
+where
+    I: Iterator<Item = u32>,
+{
+    let mut stack = match SymbolStackVariable::new(words.next()?) {
+        Some(variable) => PartialSymbolStack::from_variable(variable),
+        None => PartialSymbolStack::empty(),
+    };
+    for _ in 0..words.next()? {
+        let symbol = decode_handle(words)?;
+        let scopes = match words.next()? {
+            0 => ControlledOption::none(),
+            _ => ControlledOption::some(decode_scope_stack(partials, words)?),
+        };
+        stack.push_back(partials, PartialScopedSymbol { symbol, scopes });
+    }
+    Some(stack)
+}
+
+fn decode_scope_stack<I>(partials: &mut PartialPaths, words: &mut I) -> Option<PartialScopeStack>
+where
+    I: Iterator<Item = u32>,
+{
+    let mut stack = match ScopeStackVariable::new(words.next()?) {
+        Some(variable) => PartialScopeStack::from_variable(variable),
+        None => PartialScopeStack::empty(),
+    };
+    for _ in 0..words.next()? {
+        stack.push_back(partials, decode_handle(words)?);
+    }
+    Some(stack)
+}
+
+fn decode_handle<T, I>(words: &mut I) -> Option<Handle<T>>
+where
+    I: Iterator<Item = u32>,
+{
+    NonZeroU32::new(words.next()?).map(Handle::new)
+}
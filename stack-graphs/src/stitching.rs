@@ -36,10 +36,14 @@
 //! [`PathStitcher`]: struct.PathStitcher.html
 
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::VecDeque;
 #[cfg(feature = "copious-debugging")]
 use std::fmt::Display;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
 
 use itertools::izip;
 use itertools::Itertools;
@@ -61,13 +65,27 @@ use crate::graph::File;
 use crate::graph::Node;
 use crate::graph::StackGraph;
 use crate::graph::Symbol;
+use crate::graph::Visibility;
+use crate::observer::QueryEvent;
+use crate::observer::QueryObserver;
 use crate::partial::Cyclicity;
 use crate::partial::PartialPath;
 use crate::partial::PartialPaths;
+use crate::partial::PartialPathsCompaction;
 use crate::partial::PartialSymbolStack;
 use crate::paths::Extend;
 use crate::paths::PathResolutionError;
+use crate::serde;
+use crate::spill::PathSpill;
 use crate::stats::FrequencyDistribution;
+#[cfg(feature = "trace")]
+use crate::trace::StitchingTrace;
+#[cfg(feature = "trace")]
+use crate::trace::TraceCandidate;
+#[cfg(feature = "trace")]
+use crate::trace::TraceEvent;
+#[cfg(feature = "trace")]
+use crate::trace::TraceOutcome;
 use crate::CancellationError;
 use crate::CancellationFlag;
 
@@ -97,6 +115,31 @@ pub trait Appendable {
         graph: &'a StackGraph,
         partials: &'a mut PartialPaths,
     ) -> Box<dyn std::fmt::Display + 'a>;
+
+    /// Returns whether all edges of this appendable are visible from the given file, according to
+    /// the given policy.
+    fn is_visible_from(
+        &self,
+        _graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        _file: Handle<File>,
+        _policy: &dyn VisibilityPolicy,
+    ) -> bool {
+        true
+    }
+
+    /// Return a representation of this appendable for stitching traces.
+    #[cfg(feature = "trace")]
+    fn to_trace_candidate(
+        &self,
+        graph: &StackGraph,
+        _partials: &mut PartialPaths,
+    ) -> TraceCandidate {
+        TraceCandidate::Other {
+            start_node: serde::NodeID::from_node(graph, self.start_node()),
+            end_node: serde::NodeID::from_node(graph, self.end_node()),
+        }
+    }
 }
 
 impl Appendable for Edge {
@@ -118,6 +161,16 @@ impl Appendable for Edge {
         self.sink
     }
 
+    fn is_visible_from(
+        &self,
+        graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        file: Handle<File>,
+        policy: &dyn VisibilityPolicy,
+    ) -> bool {
+        is_edge_visible_from(graph, self, file, policy)
+    }
+
     fn display<'a>(
         &'a self,
         graph: &'a StackGraph,
@@ -129,6 +182,19 @@ impl Appendable for Edge {
             self.sink.display(graph)
         ))
     }
+
+    #[cfg(feature = "trace")]
+    fn to_trace_candidate(
+        &self,
+        graph: &StackGraph,
+        _partials: &mut PartialPaths,
+    ) -> TraceCandidate {
+        TraceCandidate::Edge {
+            source: serde::NodeID::from_node(graph, self.source),
+            sink: serde::NodeID::from_node(graph, self.sink),
+            precedence: self.precedence,
+        }
+    }
 }
 
 impl Appendable for PartialPath {
@@ -152,6 +218,40 @@ impl Appendable for PartialPath {
         self.end_node
     }
 
+    fn is_visible_from(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        file: Handle<File>,
+        policy: &dyn VisibilityPolicy,
+    ) -> bool {
+        // Partial paths only record the source nodes of their edges.  The sink of an edge is the
+        // source of the next one, or the end of the path.
+        let mut edges = self.edges.iter(partials).peekable();
+        while let Some(edge) = edges.next() {
+            let source = match graph.node_for_id(edge.source_node_id) {
+                Some(source) => source,
+                None => continue,
+            };
+            let sink = match edges.peek() {
+                Some(next) => match graph.node_for_id(next.source_node_id) {
+                    Some(sink) => sink,
+                    None => continue,
+                },
+                None => self.end_node,
+            };
+            let edge = Edge {
+                source,
+                sink,
+                precedence: edge.precedence,
+            };
+            if !is_edge_visible_from(graph, &edge, file, policy) {
+                return false;
+            }
+        }
+        true
+    }
+
     fn display<'a>(
         &'a self,
         graph: &'a StackGraph,
@@ -159,6 +259,18 @@ impl Appendable for PartialPath {
     ) -> Box<dyn std::fmt::Display + 'a> {
         Box::new(self.display(graph, partials))
     }
+
+    #[cfg(feature = "trace")]
+    fn to_trace_candidate(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+    ) -> TraceCandidate {
+        TraceCandidate::PartialPath {
+            path: serde::PartialPath::from_partial_path(graph, partials, self),
+            provenance: None,
+        }
+    }
 }
 
 //-------------------------------------------------------------------------------------------------
@@ -175,6 +287,11 @@ where
     A: Appendable,
 {
     fn get_appendable<'a>(&'a self, handle: &'a H) -> &'a A;
+
+    /// Returns where the appendable for the handle came from, if known.
+    fn get_provenance(&self, _handle: &H) -> Option<&PathProvenance> {
+        None
+    }
 }
 
 //-------------------------------------------------------------------------------------------------
@@ -210,6 +327,49 @@ where
 
     /// Get the graph, partial path arena, and database backing this candidates instance.
     fn get_graph_partials_and_db(&mut self) -> (&StackGraph, &mut PartialPaths, &Db);
+
+    /// Get the observer that should be notified of stitching events when stitching with this
+    /// candidates instance, if any.
+    fn get_observer(&self) -> Option<Arc<dyn QueryObserver>> {
+        None
+    }
+
+    /// Get the visitor that decides which nodes paths are extended from when stitching with this
+    /// candidates instance, if any.
+    fn get_node_visitor(&self) -> Option<Arc<dyn NodeVisitor>> {
+        None
+    }
+
+    /// Get the policy that decides which edges are visible from the file a path starts in when
+    /// stitching with this candidates instance, if any.
+    fn get_visibility_policy(&self) -> Option<Arc<dyn VisibilityPolicy>> {
+        None
+    }
+}
+
+/// A trait for candidates that are loaded asynchronously, for example from a database that is
+/// accessed over the network.  Use it with
+/// [`ForwardPartialPathStitcher::find_all_complete_partial_paths_async`][], which awaits the
+/// loading of candidates between stitching phases, so that services do not block threads on I/O.
+///
+/// The synchronous [`ForwardCandidates::load_forward_candidates`][] method is not used by the
+/// async driver, and can keep its default implementation.  The returned futures are `Send`, so
+/// that the driver can run in a task of a multi-threaded runtime, for example with
+/// `tokio::spawn`.  [`AsyncSQLiteReader`][crate::storage::AsyncSQLiteReader] implements this
+/// trait for SQLite databases.
+pub trait AsyncForwardCandidates<H, A, Db, Err>: ForwardCandidates<H, A, Db, Err>
+where
+    A: Appendable,
+    Db: ToAppendable<H, A>,
+{
+    /// Load possible forward candidates for the given partial path into this candidates instance.
+    /// Is awaited before [`get_forward_candidates`][ForwardCandidates::get_forward_candidates] is
+    /// called for the path.
+    fn load_forward_candidates_async(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &(dyn CancellationFlag + Sync),
+    ) -> impl std::future::Future<Output = Result<(), Err>> + Send;
 }
 
 //-------------------------------------------------------------------------------------------------
@@ -221,6 +381,7 @@ pub struct GraphEdgeCandidates<'a> {
     partials: &'a mut PartialPaths,
     file: Option<Handle<File>>,
     edges: GraphEdges,
+    filters: PathFilters,
 }
 
 impl<'a> GraphEdgeCandidates<'a> {
@@ -234,8 +395,16 @@ impl<'a> GraphEdgeCandidates<'a> {
             partials,
             file,
             edges: GraphEdges,
+            filters: PathFilters::default(),
         }
     }
+
+    /// Restricts the edges that paths are extended with, and the nodes that they are extended
+    /// from, using the given filters.
+    pub fn with_filters(mut self, filters: PathFilters) -> Self {
+        self.filters = filters;
+        self
+    }
 }
 
 impl ForwardCandidates<Edge, Edge, GraphEdges, CancellationError> for GraphEdgeCandidates<'_> {
@@ -243,9 +412,22 @@ impl ForwardCandidates<Edge, Edge, GraphEdges, CancellationError> for GraphEdgeC
     where
         R: std::iter::Extend<Edge>,
     {
-        result.extend(self.graph.outgoing_edges(path.end_node).filter(|e| {
-            self.file
-                .map_or(true, |file| self.graph[e.sink].is_in_file(file))
+        let graph = self.graph;
+        let file = self.file;
+        let edge_filter = self.filters.edge_filter.as_deref();
+        let partials = &mut *self.partials;
+        result.extend(graph.outgoing_edges(path.end_node).filter(|e| {
+            file.is_none_or(|file| graph[e.sink].is_in_file(file))
+                && edge_filter
+                    .map(|filter| filter.follows_edge(graph, e))
+                    .unwrap_or(true)
+                && graph.root_edge_key(e.source, e.sink).is_none_or(|key| {
+                    path.symbol_stack_postcondition
+                        .can_start_with(graph, partials, key)
+                })
+                && !path
+                    .symbol_stack_postcondition
+                    .is_excluded_at(graph, partials, e.sink)
         }));
     }
 
@@ -256,6 +438,14 @@ impl ForwardCandidates<Edge, Edge, GraphEdges, CancellationError> for GraphEdgeC
     fn get_graph_partials_and_db(&mut self) -> (&StackGraph, &mut PartialPaths, &GraphEdges) {
         (self.graph, self.partials, &self.edges)
     }
+
+    fn get_node_visitor(&self) -> Option<Arc<dyn NodeVisitor>> {
+        self.filters.node_visitor.clone()
+    }
+
+    fn get_visibility_policy(&self) -> Option<Arc<dyn VisibilityPolicy>> {
+        self.filters.visibility_policy.clone()
+    }
 }
 
 /// A dummy type to act as the "database" for graph edges. Its [`ToAppendable`] implementation
@@ -268,6 +458,175 @@ impl ToAppendable<Edge, Edge> for GraphEdges {
     }
 }
 
+//-------------------------------------------------------------------------------------------------
+// Path filters
+
+/// Decides which edges of a stack graph paths are extended with, for example to skip edges that
+/// are only relevant for tests, or that cross visibility boundaries recorded in the edges'
+/// metadata.
+pub trait EdgeFilter: Send + Sync {
+    /// Returns whether paths can be extended with the given edge.
+    fn follows_edge(&self, graph: &StackGraph, edge: &Edge) -> bool;
+}
+
+impl<F> EdgeFilter for F
+where
+    F: Fn(&StackGraph, &Edge) -> bool + Send + Sync,
+{
+    fn follows_edge(&self, graph: &StackGraph, edge: &Edge) -> bool {
+        self(graph, edge)
+    }
+}
+
+/// Is called for the end node of every path before the stitcher extends it, and decides whether
+/// the path is extended at all.  Paths that are not extended are still visited, if they are
+/// complete.
+pub trait NodeVisitor: Send + Sync {
+    /// Returns whether paths ending in the given node are extended.
+    fn visit_node(&self, graph: &StackGraph, node: Handle<Node>) -> bool;
+}
+
+impl<F> NodeVisitor for F
+where
+    F: Fn(&StackGraph, Handle<Node>) -> bool + Send + Sync,
+{
+    fn visit_node(&self, graph: &StackGraph, node: Handle<Node>) -> bool {
+        self(graph, node)
+    }
+}
+
+/// Decides which edges with a [visibility class][Visibility] other than public can be traversed
+/// by paths that start in a particular file.  The policy is enforced by the stitcher, for any
+/// candidates instance that [provides it][ForwardCandidates::get_visibility_policy].  It is not
+/// called for public edges, or for paths that do not start in a file, such as the partial paths
+/// from the root node that are computed when indexing.
+pub trait VisibilityPolicy: Send + Sync {
+    /// Returns whether paths starting in `querying_file` can traverse the given edge, which has
+    /// the given visibility class.
+    fn is_visible(
+        &self,
+        graph: &StackGraph,
+        querying_file: Handle<File>,
+        edge: &Edge,
+        visibility: Visibility,
+    ) -> bool;
+}
+
+fn is_edge_visible_from(
+    graph: &StackGraph,
+    edge: &Edge,
+    file: Handle<File>,
+    policy: &dyn VisibilityPolicy,
+) -> bool {
+    let visibility = graph.edge_visibility(edge.source, edge.sink);
+    visibility == Visibility::Public || policy.is_visible(graph, file, edge, visibility)
+}
+
+/// A visibility policy that only lets paths traverse non-public edges in the file they start in.
+/// The file of an edge is the file of its source node, or of its sink node for edges out of the
+/// root node.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SameFileVisibilityPolicy;
+
+impl VisibilityPolicy for SameFileVisibilityPolicy {
+    fn is_visible(
+        &self,
+        graph: &StackGraph,
+        querying_file: Handle<File>,
+        edge: &Edge,
+        _visibility: Visibility,
+    ) -> bool {
+        let file = graph[edge.source]
+            .file()
+            .or_else(|| graph[edge.sink].file());
+        file == Some(querying_file)
+    }
+}
+
+/// Custom restrictions on the search for paths, so that embedders can prune it without
+/// reimplementing the stitching loops.  The [edge filter][EdgeFilter] is applied by
+/// [`GraphEdgeCandidates`][], and the [node visitor][NodeVisitor] and
+/// [visibility policy][VisibilityPolicy] by the stitcher, for any candidates instance that
+/// [provides them][ForwardCandidates::get_node_visitor].
+#[derive(Clone, Default)]
+pub struct PathFilters {
+    edge_filter: Option<Arc<dyn EdgeFilter>>,
+    node_visitor: Option<Arc<dyn NodeVisitor>>,
+    visibility_policy: Option<Arc<dyn VisibilityPolicy>>,
+}
+
+impl PathFilters {
+    pub fn with_edge_filter(mut self, edge_filter: Arc<dyn EdgeFilter>) -> Self {
+        self.edge_filter = Some(edge_filter);
+        self
+    }
+
+    pub fn with_node_visitor(mut self, node_visitor: Arc<dyn NodeVisitor>) -> Self {
+        self.node_visitor = Some(node_visitor);
+        self
+    }
+
+    pub fn with_visibility_policy(mut self, visibility_policy: Arc<dyn VisibilityPolicy>) -> Self {
+        self.visibility_policy = Some(visibility_policy);
+        self
+    }
+
+    pub fn edge_filter(&self) -> Option<&dyn EdgeFilter> {
+        self.edge_filter.as_deref()
+    }
+
+    pub fn node_visitor(&self) -> Option<&dyn NodeVisitor> {
+        self.node_visitor.as_deref()
+    }
+
+    pub fn visibility_policy(&self) -> Option<&dyn VisibilityPolicy> {
+        self.visibility_policy.as_deref()
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Partial path policies
+
+/// Decides which partial paths are kept when finding the partial paths of a file, and when adding
+/// partial paths to a database.  Pipelines that need to retain different paths than the
+/// [default][DefaultPartialPathPolicy], for example to also keep paths that end at certain scope
+/// nodes, can implement this trait, and use
+/// [`find_minimal_partial_path_set_in_file_with_policy`][ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file_with_policy]
+/// and [`Database::add_partial_path_with_policy`][].
+pub trait PartialPathPolicy {
+    /// Returns whether a partial path is as complete as it can be.  Such paths are not extended
+    /// any further, and are kept if they are also [productive][Self::is_productive].
+    fn is_complete_as_possible(&self, graph: &StackGraph, path: &PartialPath) -> bool;
+
+    /// Returns whether a complete-as-possible partial path is worth keeping.  The default
+    /// implementation keeps all of them.
+    fn is_productive(
+        &self,
+        _graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        _path: &PartialPath,
+    ) -> bool {
+        true
+    }
+
+    /// Returns whether a partial path is kept.
+    fn retains(&self, graph: &StackGraph, partials: &mut PartialPaths, path: &PartialPath) -> bool {
+        self.is_complete_as_possible(graph, path) && self.is_productive(graph, partials, path)
+    }
+}
+
+/// The default partial path policy, which keeps paths that start at an endpoint, and end at an
+/// endpoint or a jump to scope node.  These are the paths that are needed to cover all complete
+/// paths from references to definitions.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultPartialPathPolicy;
+
+impl PartialPathPolicy for DefaultPartialPathPolicy {
+    fn is_complete_as_possible(&self, graph: &StackGraph, path: &PartialPath) -> bool {
+        path.starts_at_endpoint(graph) && (path.ends_at_endpoint(graph) || path.ends_in_jump(graph))
+    }
+}
+
 //-------------------------------------------------------------------------------------------------
 // Databases
 
@@ -293,6 +652,24 @@ pub struct Database {
     root_paths_by_precondition_without_variable:
         SupplementalArena<SymbolStackKeyCell, Vec<Handle<PartialPath>>>,
     incoming_paths: SupplementalArena<Node, Degree>,
+    provenance: SupplementalArena<PartialPath, Option<Arc<PathProvenance>>>,
+    removed_paths: HandleSet<PartialPath>,
+    paths_by_fingerprint: HashMap<u64, Vec<Handle<PartialPath>>>,
+}
+
+/// Where a partial path in a [`Database`][] came from.  Lets clients identify paths that were
+/// produced by a stale index, and explain which index produced a path that led to a wrong result.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Deserialize, ::serde::Serialize))]
+pub struct PathProvenance {
+    /// The file whose index produced the path.
+    pub file: String,
+    /// The tag of the file content that was indexed, if known.
+    pub tag: Option<String>,
+    /// When the file was indexed, in seconds since the Unix epoch, if known.
+    pub indexed_at: Option<u64>,
+    /// The version of the rules that produced the path, if known.
+    pub rules_version: Option<String>,
 }
 
 impl Database {
@@ -308,6 +685,9 @@ impl Database {
             root_paths_by_precondition_with_variable: SupplementalArena::new(),
             root_paths_by_precondition_without_variable: SupplementalArena::new(),
             incoming_paths: SupplementalArena::new(),
+            provenance: SupplementalArena::new(),
+            removed_paths: HandleSet::new(),
+            paths_by_fingerprint: HashMap::new(),
         }
     }
 
@@ -324,6 +704,9 @@ impl Database {
         self.root_paths_by_precondition_with_variable.clear();
         self.root_paths_by_precondition_without_variable.clear();
         self.incoming_paths.clear();
+        self.provenance.clear();
+        self.removed_paths.clear();
+        self.paths_by_fingerprint.clear();
     }
 
     /// Adds a partial path to this database.  We do not deduplicate partial paths in any way; it's
@@ -346,7 +729,12 @@ impl Database {
             path.display(graph, partials)
         );
         let symbol_stack_precondition = path.symbol_stack_precondition;
+        let fingerprint = path.fingerprint(partials);
         let handle = self.partial_paths.add(path);
+        self.paths_by_fingerprint
+            .entry(fingerprint)
+            .or_default()
+            .push(handle);
 
         // If the partial path starts at the root node, index it by its symbol stack precondition.
         if graph[start_node].is_root() {
@@ -377,6 +765,153 @@ impl Database {
         handle
     }
 
+    /// Adds a partial path to this database, together with where it came from.  Provenance is
+    /// shared between all paths produced by the same index of a file.
+    pub fn add_partial_path_with_provenance(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        path: PartialPath,
+        provenance: Arc<PathProvenance>,
+    ) -> Handle<PartialPath> {
+        let handle = self.add_partial_path(graph, partials, path);
+        *self.provenance.get_mut_or_default(handle) = Some(provenance);
+        handle
+    }
+
+    /// Returns where the given partial path came from, if it was added with provenance.
+    pub fn provenance(&self, handle: Handle<PartialPath>) -> Option<&PathProvenance> {
+        self.provenance.get(handle)?.as_deref()
+    }
+
+    /// Returns the handle of a partial path in this database that is [identical][PartialPath::is_identical]
+    /// to the given one, if there is one.  Only the paths with the same
+    /// [fingerprint][PartialPath::fingerprint] are compared, so this can be used to deduplicate
+    /// paths before [adding][Self::add_partial_path] them.
+    pub fn find_partial_path(
+        &self,
+        partials: &mut PartialPaths,
+        path: &PartialPath,
+    ) -> Option<Handle<PartialPath>> {
+        let fingerprint = path.fingerprint(partials);
+        self.paths_by_fingerprint
+            .get(&fingerprint)?
+            .iter()
+            .copied()
+            .find(|handle| self.partial_paths.get(*handle).is_identical(partials, path))
+    }
+
+    /// Adds a partial path to this database if the given policy retains it.  Returns `None` if the
+    /// path was not added.
+    pub fn add_partial_path_with_policy(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        path: PartialPath,
+        policy: &dyn PartialPathPolicy,
+    ) -> Option<Handle<PartialPath>> {
+        if !policy.retains(graph, partials, &path) {
+            return None;
+        }
+        Some(self.add_partial_path(graph, partials, path))
+    }
+
+    /// Removes the partial paths of a file from this database: all paths that start or end in the
+    /// file, or pass through any of its nodes.  The paths are removed from all indexes, so that
+    /// they are no longer returned as candidates or by [`iter_partial_paths`][Self::iter_partial_paths],
+    /// and the file's nodes are no longer local.  Handles of the remaining paths stay valid.
+    /// Returns the number of paths that were removed.
+    ///
+    /// The locality of the nodes of other files is not recomputed, because at query time it is
+    /// [marked][Self::mark_local_node] from storage instead of derived from the paths in the
+    /// database.  Nodes that only the removed paths made non-local therefore stay non-local.  At
+    /// index time, call [`find_local_nodes`][Self::find_local_nodes] afterwards to recompute it.
+    ///
+    /// This is meant for incremental re-indexing, where the paths of a changed file are replaced by
+    /// newly computed ones without rebuilding the database.
+    pub fn remove_file(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        file: Handle<File>,
+    ) -> usize {
+        let in_file = |node: Handle<Node>| graph[node].file() == Some(file);
+        let mut removed = HandleSet::new();
+        let mut removed_end_nodes = HandleSet::new();
+        for handle in self.iter_partial_paths() {
+            let path = &self.partial_paths.get(handle);
+            if in_file(path.start_node)
+                || in_file(path.end_node)
+                || path
+                    .edges
+                    .iter(partials)
+                    .any(|e| e.source_node_id.file() == Some(file))
+            {
+                removed.add(handle);
+                removed_end_nodes.add(path.end_node);
+            }
+        }
+        if removed.is_empty() {
+            return 0;
+        }
+
+        for (_, paths) in self.paths_by_start_node.iter_mut() {
+            paths.retain(|p| !removed.contains(*p));
+        }
+        for index in [
+            &mut self.root_paths_by_precondition_prefix,
+            &mut self.root_paths_by_precondition_with_variable,
+            &mut self.root_paths_by_precondition_without_variable,
+        ] {
+            for (_, paths) in index.iter_mut() {
+                paths.retain(|p| !removed.contains(*p));
+            }
+        }
+        for handle in removed.iter() {
+            if let Some(provenance) = self.provenance.get_mut(handle) {
+                *provenance = None;
+            }
+        }
+        for paths in self.paths_by_fingerprint.values_mut() {
+            paths.retain(|p| !removed.contains(*p));
+        }
+        self.removed_paths.union_with(&removed);
+
+        // Degrees cannot be decremented, so they are recounted for the affected end nodes.
+        for node in removed_end_nodes.iter() {
+            self.incoming_paths[node] = Degree::Zero;
+        }
+        for (_, path) in Self::live_partial_paths(&self.partial_paths, &self.removed_paths) {
+            if removed_end_nodes.contains(path.end_node) {
+                self.incoming_paths[path.end_node] += Degree::One;
+            }
+        }
+
+        let local_nodes = self
+            .local_nodes
+            .iter()
+            .filter(|node| in_file(*node))
+            .collect::<Vec<_>>();
+        for node in local_nodes {
+            self.local_nodes.remove(node);
+        }
+
+        removed.count()
+    }
+
+    /// Frees the arena cells of `partials` that are not used by the partial paths in this
+    /// database, such as the cells of removed paths, or of intermediate paths that were created
+    /// while stitching.  See [`PartialPaths::compact`][] for when it is safe to call this.
+    pub fn compact(&mut self, partials: &mut PartialPaths) -> PartialPathsCompaction {
+        let removed_paths = &self.removed_paths;
+        partials.compact(
+            self.partial_paths
+                .iter_mut()
+                .filter(|(handle, _)| !removed_paths.contains(*handle))
+                .map(|(_, path)| path),
+        )
+    }
+
     /// Find all partial paths in this database that start at the given path's end node.
     /// If the end node is the root node, returns paths with a symbol stack precondition
     /// that are compatible with the path's symbol stack post condition.
@@ -535,7 +1070,10 @@ impl Database {
 
     /// Returns the number of paths in this database that share the given end node.
     pub fn get_incoming_path_degree(&self, end_node: Handle<Node>) -> Degree {
-        self.incoming_paths[end_node]
+        self.incoming_paths
+            .get(end_node)
+            .copied()
+            .unwrap_or(Degree::Zero)
     }
 
     /// Determines which nodes in the stack graph are “local”, taking into account the partial
@@ -550,9 +1088,9 @@ impl Database {
         // Assume that any node that is the start or end of a partial path is local to this file
         // until we see a path connecting the root node to it (in either direction).
         self.local_nodes.clear();
-        for handle in self.iter_partial_paths() {
-            self.local_nodes.add(self[handle].start_node);
-            self.local_nodes.add(self[handle].end_node);
+        for (_, path) in Self::live_partial_paths(&self.partial_paths, &self.removed_paths) {
+            self.local_nodes.add(path.start_node);
+            self.local_nodes.add(path.end_node);
         }
 
         // The root node and jump-to-scope node are the most obvious non-local nodes.
@@ -570,9 +1108,9 @@ impl Database {
         let mut keep_checking = true;
         while keep_checking {
             keep_checking = false;
-            for handle in self.iter_partial_paths() {
-                let start_node = self[handle].start_node;
-                let end_node = self[handle].end_node;
+            for (_, path) in Self::live_partial_paths(&self.partial_paths, &self.removed_paths) {
+                let start_node = path.start_node;
+                let end_node = path.end_node;
 
                 // First check forwards paths, where non-localness propagates from the start node
                 // of each path.
@@ -614,11 +1152,22 @@ impl Database {
         self.local_nodes.contains(node)
     }
 
-    /// Returns an iterator over all of the handles of all of the partial paths in this database.
-    /// (Note that because we're only returning _handles_, this iterator does not retain a
-    /// reference to the `Database`.)
-    pub fn iter_partial_paths(&self) -> impl Iterator<Item = Handle<PartialPath>> {
-        self.partial_paths.iter_handles()
+    /// Returns an iterator over all of the handles of all of the partial paths in this database,
+    /// skipping the paths of [removed files][Self::remove_file].
+    pub fn iter_partial_paths(&self) -> impl Iterator<Item = Handle<PartialPath>> + '_ {
+        Self::live_partial_paths(&self.partial_paths, &self.removed_paths).map(|(handle, _)| handle)
+    }
+
+    /// Returns the partial paths that have not been removed.  This borrows only the fields it
+    /// needs, so that other fields can be updated while iterating.
+    fn live_partial_paths<'a>(
+        partial_paths: &'a Arena<PartialPath>,
+        removed_paths: &'a HandleSet<PartialPath>,
+    ) -> impl Iterator<Item = (Handle<PartialPath>, &'a PartialPath)> + 'a {
+        partial_paths
+            .iter_handles()
+            .filter(move |handle| !removed_paths.contains(*handle))
+            .map(move |handle| (handle, partial_paths.get(handle)))
     }
 
     pub fn ensure_both_directions(&mut self, partials: &mut PartialPaths) {
@@ -648,12 +1197,18 @@ impl ToAppendable<Handle<PartialPath>, PartialPath> for Database {
     fn get_appendable<'a>(&'a self, handle: &'a Handle<PartialPath>) -> &'a PartialPath {
         &self[*handle]
     }
+
+    fn get_provenance(&self, handle: &Handle<PartialPath>) -> Option<&PathProvenance> {
+        self.provenance(*handle)
+    }
 }
 
 pub struct DatabaseCandidates<'a> {
     graph: &'a StackGraph,
     partials: &'a mut PartialPaths,
     database: &'a mut Database,
+    node_visitor: Option<Arc<dyn NodeVisitor>>,
+    visibility_policy: Option<Arc<dyn VisibilityPolicy>>,
 }
 
 impl<'a> DatabaseCandidates<'a> {
@@ -666,8 +1221,23 @@ impl<'a> DatabaseCandidates<'a> {
             graph,
             partials,
             database,
+            node_visitor: None,
+            visibility_policy: None,
         }
     }
+
+    /// Only extends paths from the nodes that the given visitor accepts.
+    pub fn with_node_visitor(mut self, node_visitor: Arc<dyn NodeVisitor>) -> Self {
+        self.node_visitor = Some(node_visitor);
+        self
+    }
+
+    /// Only extends paths with edges that are visible from the file the path starts in,
+    /// according to the given policy.
+    pub fn with_visibility_policy(mut self, visibility_policy: Arc<dyn VisibilityPolicy>) -> Self {
+        self.visibility_policy = Some(visibility_policy);
+        self
+    }
 }
 
 impl ForwardCandidates<Handle<PartialPath>, PartialPath, Database, CancellationError>
@@ -688,6 +1258,14 @@ impl ForwardCandidates<Handle<PartialPath>, PartialPath, Database, CancellationE
     fn get_graph_partials_and_db(&mut self) -> (&StackGraph, &mut PartialPaths, &Database) {
         (self.graph, self.partials, self.database)
     }
+
+    fn get_node_visitor(&self) -> Option<Arc<dyn NodeVisitor>> {
+        self.node_visitor.clone()
+    }
+
+    fn get_visibility_policy(&self) -> Option<Arc<dyn VisibilityPolicy>> {
+        self.visibility_policy.clone()
+    }
 }
 
 /// The key type that we use to find partial paths that start from the root node and have a
@@ -776,21 +1354,17 @@ struct DisplaySymbolStackKey<'a>(SymbolStackKey, &'a StackGraph, &'a Database);
 #[cfg(feature = "copious-debugging")]
 impl<'a> Display for DisplaySymbolStackKey<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        // Use a recursive function to print the contents of the key out in reverse order.
-        fn display_one(
-            mut key: SymbolStackKey,
-            graph: &StackGraph,
-            db: &Database,
-            f: &mut std::fmt::Formatter,
-        ) -> std::fmt::Result {
-            let last = match key.pop_back(db) {
-                Some(last) => last,
-                None => return Ok(()),
-            };
-            display_one(key, graph, db, f)?;
-            last.display(graph).fmt(f)
+        // The symbols are stored in reverse order, so we collect them before printing, instead of
+        // using a recursive function, which could exhaust the stack for very long keys.
+        let mut key = self.0;
+        let mut symbols = Vec::new();
+        while let Some(symbol) = key.pop_back(self.2) {
+            symbols.push(symbol);
+        }
+        for symbol in symbols.into_iter().rev() {
+            symbol.display(self.1).fmt(f)?;
         }
-        display_one(self.0, self.1, self.2, f)
+        Ok(())
     }
 }
 
@@ -839,10 +1413,22 @@ pub struct ForwardPartialPathStitcher<H> {
     ),
     appended_paths: Appendables<H>,
     similar_path_detector: Option<SimilarPathDetector<PartialPath>>,
+    similar_path_budget: Option<usize>,
     check_only_join_nodes: bool,
     max_work_per_phase: usize,
+    max_queued_paths: Option<usize>,
+    max_attached_scope_list_length: Option<usize>,
+    queue_spill_directory: Option<PathBuf>,
+    queue_spill: Option<PathSpill>,
+    queue_spill_error: Option<std::io::Error>,
+    // A lower bound on the number of edges of the spilled paths
+    queue_spill_min_path_length: Option<usize>,
     initial_paths: usize,
     stats: Option<Stats>,
+    #[cfg(feature = "trace")]
+    trace: Option<StitchingTrace>,
+    empty_scope_stacks: Option<Vec<EmptyScopeStack>>,
+    observer: Option<Arc<dyn QueryObserver>>,
     #[cfg(feature = "copious-debugging")]
     phase_number: usize,
 }
@@ -877,12 +1463,26 @@ impl<H> ForwardPartialPathStitcher<H> {
             appended_paths,
             // By default, all paths are checked for similarity
             similar_path_detector: Some(SimilarPathDetector::new()),
+            // By default, there's no bound on the number of paths kept per similar path bucket
+            similar_path_budget: None,
             // By default, all nodes are checked for cycles and (if enabled) similarity
             check_only_join_nodes: false,
             // By default, there's no artificial bound on the amount of work done per phase
             max_work_per_phase: usize::MAX,
+            // By default, there's no bound on the number of queued paths
+            max_queued_paths: None,
+            // By default, there's no bound on the length of attached scope lists
+            max_attached_scope_list_length: None,
+            queue_spill_directory: None,
+            queue_spill: None,
+            queue_spill_error: None,
+            queue_spill_min_path_length: None,
             initial_paths,
             stats: None,
+            #[cfg(feature = "trace")]
+            trace: None,
+            empty_scope_stacks: None,
+            observer: None,
             #[cfg(feature = "copious-debugging")]
             phase_number: 1,
         }
@@ -898,10 +1498,23 @@ impl<H> ForwardPartialPathStitcher<H> {
         } else if self.similar_path_detector.is_none() {
             let mut similar_path_detector = SimilarPathDetector::new();
             similar_path_detector.set_collect_stats(self.stats.is_some());
+            similar_path_detector.set_bucket_budget(self.similar_path_budget);
             self.similar_path_detector = Some(similar_path_detector);
         }
     }
 
+    /// Sets the maximum number of paths that similar path detection keeps for each combination of
+    /// start and end node, and pre- and postcondition lengths.  Paths that do not fit in their
+    /// bucket are not extended, unless they are better than a path already in the bucket.  This
+    /// trades precision for speed on pathological code.  Has no effect if similar path detection
+    /// is disabled.  By default, there is no bound.
+    pub fn set_similar_path_budget(&mut self, similar_path_budget: Option<usize>) {
+        self.similar_path_budget = similar_path_budget;
+        if let Some(similar_path_detector) = &mut self.similar_path_detector {
+            similar_path_detector.set_bucket_budget(similar_path_budget);
+        }
+    }
+
     /// Sets whether all nodes are checked for cycles and (if enabled) similar paths, or only nodes with multiple
     /// incoming candidates. Checking only join nodes is **unsafe** unless the database of candidates is stable
     /// between all stitching phases. If paths are added to the database from one phase to another, for example if
@@ -919,6 +1532,53 @@ impl<H> ForwardPartialPathStitcher<H> {
         self.max_work_per_phase = max_work_per_phase;
     }
 
+    /// Sets the maximum number of paths that are kept in memory in the queue at the start of each
+    /// phase of the algorithm.  If a spill directory is set with [`set_queue_spill_directory`][],
+    /// the excess paths are written to disk, and are queued again in later phases, once there is
+    /// room in the queue.  Otherwise, the excess paths are dropped, which trades precision for
+    /// bounded memory use on pathological graphs.  The initial paths are never spilled or dropped.
+    /// By default, there is no bound.
+    ///
+    /// [`set_queue_spill_directory`]: #method.set_queue_spill_directory
+    pub fn set_max_queued_paths(&mut self, max_queued_paths: Option<usize>) {
+        self.max_queued_paths = max_queued_paths;
+    }
+
+    /// Sets the maximum length of the attached scope lists of the symbols in a path's symbol stack
+    /// postcondition.  Extensions that exceed the maximum are rejected with
+    /// [`PathResolutionError::AttachedScopeListTooLong`][], which bounds the memory used for
+    /// scoped symbols whose attached scope lists grow without bound, as they do for recursive
+    /// calls.  This trades precision for bounded memory use.  By default, there is no bound.
+    pub fn set_max_attached_scope_list_length(
+        &mut self,
+        max_attached_scope_list_length: Option<usize>,
+    ) {
+        self.max_attached_scope_list_length = max_attached_scope_list_length;
+    }
+
+    /// Sets the directory in which paths that exceed the [maximum number of queued
+    /// paths][set_max_queued_paths] are spilled.  The spill file is created when paths are first
+    /// spilled, and removed when the stitcher is dropped.  Has no effect if the number of queued
+    /// paths is not bounded.
+    ///
+    /// Spilled paths are queued again at the end of a later phase, without appearing in
+    /// [`previous_phase_partial_paths`][].  If you load candidates between phases, load them for
+    /// [`partial_paths_to_load`][], which includes those paths.
+    ///
+    /// [set_max_queued_paths]: #method.set_max_queued_paths
+    /// [`previous_phase_partial_paths`]: #method.previous_phase_partial_paths
+    /// [`partial_paths_to_load`]: #method.partial_paths_to_load
+    pub fn set_queue_spill_directory(&mut self, queue_spill_directory: Option<PathBuf>) {
+        self.queue_spill_directory = queue_spill_directory;
+    }
+
+    /// Returns the error that occurred while spilling queued paths to disk, if any.  After an
+    /// error, no more paths are spilled, and excess paths are kept in memory instead.  If the
+    /// error occurred while reading spilled paths back, those paths are lost.
+    pub fn queue_spill_error(&self) -> Option<&std::io::Error> {
+        self.queue_spill_error.as_ref()
+    }
+
     /// Sets whether to collect statistics during stitching.
     pub fn set_collect_stats(&mut self, collect_stats: bool) {
         if !collect_stats {
@@ -933,13 +1593,60 @@ impl<H> ForwardPartialPathStitcher<H> {
         }
     }
 
+    /// Sets whether to record a trace of all decisions made during stitching.
+    #[cfg(feature = "trace")]
+    pub fn set_collect_trace(&mut self, collect_trace: bool) {
+        if !collect_trace {
+            self.trace = None;
+        } else if self.trace.is_none() {
+            self.trace = Some(StitchingTrace::new());
+        }
+    }
+
+    /// Returns the trace recorded so far, if trace recording is enabled, and clears it.
+    #[cfg(feature = "trace")]
+    pub fn take_trace(&mut self) -> Option<StitchingTrace> {
+        self.trace.as_mut().map(std::mem::take)
+    }
+
+    /// Sets whether to collect the paths that are dropped because they jump to scope while their
+    /// scope stack is empty.  These paths are otherwise dropped silently, like all paths that
+    /// cannot be extended.  Collected paths are reported to the [observer][set_observer], and
+    /// returned as part of the statistics.
+    ///
+    /// [set_observer]: #method.set_observer
+    pub fn set_collect_empty_scope_stacks(&mut self, collect_empty_scope_stacks: bool) {
+        if !collect_empty_scope_stacks {
+            self.empty_scope_stacks = None;
+        } else if self.empty_scope_stacks.is_none() {
+            self.empty_scope_stacks = Some(Vec::new());
+        }
+    }
+
+    /// Returns the paths that were dropped because they jumped to scope with an empty scope stack,
+    /// if collecting them is enabled, and clears them.
+    pub fn take_empty_scope_stacks(&mut self) -> Option<Vec<EmptyScopeStack>> {
+        self.empty_scope_stacks.as_mut().map(std::mem::take)
+    }
+
+    /// Sets the observer that is notified of every extended path and completed phase.
+    pub fn set_observer(&mut self, observer: Arc<dyn QueryObserver>) {
+        self.observer = Some(observer);
+    }
+
     pub fn into_stats(mut self) -> Stats {
         if let (Some(stats), Some(similar_path_detector)) =
             (&mut self.stats, self.similar_path_detector)
         {
             stats.similar_paths_stats = similar_path_detector.stats();
         }
-        self.stats.unwrap_or_default()
+        let mut stats = self.stats.unwrap_or_default();
+        #[cfg(feature = "trace")]
+        {
+            stats.trace = self.trace;
+        }
+        stats.empty_scope_stacks = self.empty_scope_stacks.unwrap_or_default();
+        stats
     }
 }
 
@@ -950,6 +1657,18 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
         self.next_iteration.0.iter()
     }
 
+    /// Returns an iterator of all of the partial paths that the next phase of the algorithm might
+    /// extend, and whose candidates must therefore be loaded before it.  These are the [paths of
+    /// the previous phase][Self::previous_phase_partial_paths], together with the paths that are
+    /// still queued, such as paths that were [spilled][Self::set_queue_spill_directory] and
+    /// queued again.
+    pub fn partial_paths_to_load(&self) -> impl Iterator<Item = &PartialPath> + '_ {
+        self.next_iteration
+            .0
+            .iter()
+            .chain(self.queue.iter().map(|(path, _, _)| path))
+    }
+
     /// Returns a slice of all of the (possibly incomplete) partial paths that were encountered
     /// during the most recent phase of the algorithm.
     pub fn previous_phase_partial_paths_slice(&mut self) -> &[PartialPath] {
@@ -979,6 +1698,14 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
         Db: ToAppendable<H, A>,
         C: ForwardCandidates<H, A, Db, Err>,
     {
+        if let Some(node_visitor) = candidates.get_node_visitor() {
+            let (graph, _, _) = candidates.get_graph_partials_and_db();
+            if !node_visitor.visit_node(graph, partial_path.end_node) {
+                copious_debugging!("    Not extending path to pruned node");
+                return 0;
+            }
+        }
+
         let check_cycle = !self.check_only_join_nodes
             || partial_path.start_node == partial_path.end_node
             || candidates.get_joining_candidate_degree(partial_path) == Degree::Multiple;
@@ -991,24 +1718,32 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
             // or the current end node has multiple incoming edges. If neither of these hold, the path cannot end in a cycle.
             let has_precondition_variables = partial_path.symbol_stack_precondition.has_variable()
                 || partial_path.scope_stack_precondition.has_variable();
-            let cycles = cycle_detector
-                .is_cyclic(graph, partials, db, &mut self.appended_paths)
-                .expect("cyclic test failed when stitching partial paths");
-            let cyclic = match has_precondition_variables {
-                // If the precondition has no variables, we allow cycles that strengthen the
-                // precondition, because we know they cannot strengthen the precondition of
-                // the overall path.
-                false => !cycles
-                    .into_iter()
-                    .all(|c| c == Cyclicity::StrengthensPrecondition),
-                // If the precondition has variables, do not allow any cycles, not even those
-                // that strengthen the precondition. This is more strict than necessary. Better
-                // might be to disallow precondition strengthening cycles only if they would
-                // strengthen the overall path precondition.
-                true => !cycles.is_empty(),
-            };
+            let cyclic =
+                match cycle_detector.is_cyclic(graph, partials, db, &mut self.appended_paths) {
+                    // If the precondition has no variables, we allow cycles that strengthen the
+                    // precondition, because we know they cannot strengthen the precondition of
+                    // the overall path.
+                    Ok(cycles) if !has_precondition_variables => !cycles
+                        .into_iter()
+                        .all(|c| c == Cyclicity::StrengthensPrecondition),
+                    // If the precondition has variables, do not allow any cycles, not even those
+                    // that strengthen the precondition. This is more strict than necessary. Better
+                    // might be to disallow precondition strengthening cycles only if they would
+                    // strengthen the overall path precondition.
+                    Ok(cycles) => !cycles.is_empty(),
+                    // The cycle cannot be rebuilt on its own, for example because it jumps to a scope
+                    // that is only known from the rest of the path. We cannot tell what the cycle
+                    // does to the stacks, so we treat it as disallowed, to guarantee termination.
+                    Err(_) => true,
+                };
             if cyclic {
                 copious_debugging!("      is discontinued: cyclic");
+                #[cfg(feature = "trace")]
+                if let Some(trace) = &mut self.trace {
+                    trace.events.push(TraceEvent::Cyclic {
+                        path: serde::PartialPath::from_partial_path(graph, partials, partial_path),
+                    });
+                }
                 return 0;
             }
         }
@@ -1016,12 +1751,27 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
         // find candidates to append
         self.candidates.clear();
         candidates.get_forward_candidates(partial_path, &mut self.candidates);
+        let visibility_policy = candidates.get_visibility_policy();
         let (graph, partials, db) = candidates.get_graph_partials_and_db();
+        // Visibility is decided relative to the file the path starts in, so it is not enforced
+        // for paths that start outside of any file.
+        let visibility = visibility_policy
+            .as_deref()
+            .and_then(|policy| Some((graph[partial_path.start_node].file()?, policy)));
 
         // try to extend path with candidates
         let candidate_count = self.candidates.len();
         self.extensions.clear();
         self.extensions.reserve(candidate_count);
+        // The trace events of accepted candidates, which are updated if the extension turns out
+        // to have too many similar paths.
+        #[cfg(feature = "trace")]
+        let mut accepted_trace_events = Vec::new();
+        #[cfg(feature = "trace")]
+        let traced_path = self
+            .trace
+            .as_ref()
+            .map(|_| serde::PartialPath::from_partial_path(graph, partials, partial_path));
         for candidate in &self.candidates {
             let appendable = db.get_appendable(candidate);
             copious_debugging!("      with {}", appendable.display(graph, partials));
@@ -1030,9 +1780,69 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
             let mut new_cycle_detector = cycle_detector.clone();
             // If there are errors concatenating these partial paths, or resolving the resulting
             // partial path, just skip the extension — it's not a fatal error.
+            let result = appendable
+                .append_to(graph, partials, &mut new_partial_path)
+                .and_then(|()| match visibility {
+                    Some((file, policy))
+                        if !appendable.is_visible_from(graph, partials, file, policy) =>
+                    {
+                        Err(PathResolutionError::EdgeNotVisible)
+                    }
+                    _ => Ok(()),
+                })
+                .and_then(|()| match self.max_attached_scope_list_length {
+                    Some(max_length)
+                        if new_partial_path
+                            .symbol_stack_postcondition
+                            .max_attached_scope_list_length(partials)
+                            > max_length =>
+                    {
+                        Err(PathResolutionError::AttachedScopeListTooLong)
+                    }
+                    _ => Ok(()),
+                });
+            #[cfg(feature = "trace")]
+            if let (Some(trace), Some(path)) = (&mut self.trace, &traced_path) {
+                let outcome = match &result {
+                    Ok(()) => {
+                        accepted_trace_events.push(trace.events.len());
+                        TraceOutcome::Accepted {
+                            extension: serde::PartialPath::from_partial_path(
+                                graph,
+                                partials,
+                                &new_partial_path,
+                            ),
+                        }
+                    }
+                    Err(err) => TraceOutcome::Invalid {
+                        reason: format!("{:?}", err),
+                    },
+                };
+                let mut trace_candidate = appendable.to_trace_candidate(graph, partials);
+                if let TraceCandidate::PartialPath { provenance, .. } = &mut trace_candidate {
+                    *provenance = db.get_provenance(candidate).cloned().map(Box::new);
+                }
+                trace.events.push(TraceEvent::Candidate {
+                    path: path.clone(),
+                    candidate: Box::new(trace_candidate),
+                    outcome: Box::new(outcome),
+                });
+            }
+            if let (Err(PathResolutionError::EmptyScopeStack), Some(empty_scope_stacks)) =
+                (&result, &mut self.empty_scope_stacks)
+            {
+                let empty_scope_stack =
+                    EmptyScopeStack::from_partial_path(graph, partials, &new_partial_path);
+                if let Some(observer) = &self.observer {
+                    observer.observe(&QueryEvent::EmptyScopeStack {
+                        path: &empty_scope_stack,
+                    });
+                }
+                empty_scope_stacks.push(empty_scope_stack);
+            }
             #[cfg_attr(not(feature = "copious-debugging"), allow(unused_variables))]
             {
-                if let Err(err) = appendable.append_to(graph, partials, &mut new_partial_path) {
+                if let Err(err) = result {
                     copious_debugging!("        is invalid: {:?}", err);
                     continue;
                 }
@@ -1047,7 +1857,9 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
         self.next_iteration.0.reserve(extension_count);
         self.next_iteration.1.reserve(extension_count);
         self.next_iteration.2.reserve(extension_count);
-        for (new_partial_path, new_cycle_detector) in self.extensions.drain(..) {
+        #[cfg_attr(not(feature = "trace"), allow(unused_variables))]
+        for (index, (new_partial_path, new_cycle_detector)) in self.extensions.drain(..).enumerate()
+        {
             let check_similar_path = new_has_split
                 && (!self.check_only_join_nodes
                     || candidates.get_joining_candidate_degree(&new_partial_path)
@@ -1078,6 +1890,21 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
                             new_partial_path.display(graph, partials)
                         );
                         copious_debugging!("        is rejected: too many similar");
+                        #[cfg(feature = "trace")]
+                        if let Some(trace) = &mut self.trace {
+                            if let TraceEvent::Candidate { outcome, .. } =
+                                &mut trace.events[accepted_trace_events[index]]
+                            {
+                                let rejected = TraceOutcome::Invalid {
+                                    reason: String::new(),
+                                };
+                                if let TraceOutcome::Accepted { extension } =
+                                    std::mem::replace(outcome.as_mut(), rejected)
+                                {
+                                    **outcome = TraceOutcome::TooManySimilar { extension };
+                                }
+                            }
+                        }
                         continue;
                     }
                 }
@@ -1104,12 +1931,114 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
                 stats.terminal_path_lengh.record(partial_path.edges.len());
             }
         }
+        if let Some(observer) = &self.observer {
+            let (graph, _, _) = candidates.get_graph_partials_and_db();
+            observer.observe(&QueryEvent::PathExtended {
+                at_root: graph[partial_path.end_node].is_root(),
+                candidates: candidate_count,
+                extensions: extension_count,
+            });
+        }
         candidate_count
     }
 
+    /// Returns a lower bound on the number of edges of the partial paths that the stitcher still
+    /// has to extend, or `None` if there are no such paths.  Extensions found in later phases have
+    /// at least as many edges as this bound.
+    pub fn min_pending_path_length(&self) -> Option<usize> {
+        self.queue
+            .iter()
+            .map(|(path, _, _)| path)
+            .chain(self.next_iteration.0.iter())
+            .map(|path| path.edges.len())
+            .chain(
+                self.queue_spill
+                    .as_ref()
+                    .filter(|spill| spill.len() > 0)
+                    .and(self.queue_spill_min_path_length),
+            )
+            .min()
+    }
+
     /// Returns whether the algorithm has completed.
     pub fn is_complete(&self) -> bool {
-        self.queue.is_empty() && self.next_iteration.0.is_empty()
+        self.queue.is_empty()
+            && self.next_iteration.0.is_empty()
+            && self
+                .queue_spill
+                .as_ref()
+                .is_none_or(|spill| spill.len() == 0)
+    }
+
+    /// Returns the maximum number of queued paths, if the queue is bounded.  The initial paths are
+    /// at the front of the queue, and are never spilled or dropped.
+    fn queue_bound(&self) -> Option<usize> {
+        self.max_queued_paths
+            .map(|max_queued_paths| max_queued_paths.max(self.initial_paths_in_queue))
+    }
+
+    /// Ensures that the queue does not hold more paths than allowed, by spilling or dropping the
+    /// excess paths.
+    fn bound_queue(&mut self, partials: &mut PartialPaths) {
+        let max_queued_paths = match self.queue_bound() {
+            Some(max_queued_paths) => max_queued_paths,
+            None => return,
+        };
+        if self.queue.len() > max_queued_paths {
+            let excess = self.queue.split_off(max_queued_paths);
+            if self.queue_spill_error.is_some() {
+                self.queue.extend(excess);
+            } else if let Some(directory) = &self.queue_spill_directory {
+                let spill = match &mut self.queue_spill {
+                    Some(spill) => Ok(spill),
+                    None => {
+                        PathSpill::create(directory).map(|spill| self.queue_spill.insert(spill))
+                    }
+                };
+                match spill.and_then(|spill| spill.write(partials, &excess)) {
+                    Ok(()) => {
+                        self.queue_spill_min_path_length = excess
+                            .iter()
+                            .map(|(path, _, _)| path.edges.len())
+                            .chain(self.queue_spill_min_path_length)
+                            .min();
+                        if let Some(stats) = &mut self.stats {
+                            stats.spilled_paths += excess.len();
+                        }
+                    }
+                    Err(err) => {
+                        self.queue_spill_error = Some(err);
+                        self.queue.extend(excess);
+                    }
+                }
+            } else if let Some(stats) = &mut self.stats {
+                stats.dropped_paths += excess.len();
+            }
+        }
+    }
+
+    /// Moves spilled paths back into the queue, if the paths that are queued for the next phase
+    /// leave room for them.  This happens at the end of a phase, so that the candidates for the
+    /// refilled paths can be loaded together with those for the [paths of the
+    /// phase][Self::partial_paths_to_load].
+    fn refill_queue(&mut self, partials: &mut PartialPaths) {
+        let max_queued_paths = match self.queue_bound() {
+            Some(max_queued_paths) => max_queued_paths,
+            None => return,
+        };
+        let queued_paths = self.queue.len() + self.next_iteration.0.len();
+        if let Some(spill) = &mut self.queue_spill {
+            if spill.len() > 0 && queued_paths < max_queued_paths {
+                let room = max_queued_paths - queued_paths;
+                if let Err(err) = spill.read(partials, room, &mut self.queue) {
+                    self.queue_spill_error = Some(err);
+                    self.queue_spill = None;
+                    self.queue_spill_min_path_length = None;
+                } else if spill.len() == 0 {
+                    self.queue_spill_min_path_length = None;
+                }
+            }
+        }
     }
 
     /// Runs the next phase of the algorithm.  We will have built up a set of incomplete partial
@@ -1132,13 +2061,26 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
         E: Fn(&StackGraph, &mut PartialPaths, &PartialPath) -> bool,
     {
         copious_debugging!("==> Start phase {}", self.phase_number);
+        let start = self.observer.as_ref().map(|_| Instant::now());
         self.queue.extend(izip!(
             self.next_iteration.0.drain(..),
             self.next_iteration.1.drain(..),
             self.next_iteration.2.drain(..),
         ));
+        self.bound_queue(candidates.get_graph_partials_and_db().1);
+        let queued_paths = self.queue.len();
         if let Some(stats) = &mut self.stats {
-            stats.queued_paths_per_phase.record(self.queue.len());
+            stats.queued_paths_per_phase.record(queued_paths);
+        }
+        #[cfg(feature = "trace")]
+        if let Some(trace) = &mut self.trace {
+            let (graph, partials, _) = candidates.get_graph_partials_and_db();
+            let paths = self
+                .queue
+                .iter()
+                .map(|(path, _, _)| serde::PartialPath::from_partial_path(graph, partials, path))
+                .collect();
+            trace.events.push(TraceEvent::Phase { paths });
         }
         let mut work_performed = 0;
         while let Some((partial_path, cycle_detector, has_split)) = self.queue.pop_front() {
@@ -1154,6 +2096,12 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
                     "    Do not extend {}",
                     partial_path.display(graph, partials)
                 );
+                #[cfg(feature = "trace")]
+                if let Some(trace) = &mut self.trace {
+                    trace.events.push(TraceEvent::NotExtended {
+                        path: serde::PartialPath::from_partial_path(graph, partials, &partial_path),
+                    });
+                }
                 continue;
             }
             work_performed += self.extend(candidates, &partial_path, cycle_detector, has_split);
@@ -1161,9 +2109,17 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
                 break;
             }
         }
+        self.refill_queue(candidates.get_graph_partials_and_db().1);
         if let Some(stats) = &mut self.stats {
             stats.processed_paths_per_phase.record(work_performed);
         }
+        if let (Some(observer), Some(start)) = (&self.observer, start) {
+            observer.observe(&QueryEvent::PhaseCompleted {
+                queued_paths,
+                processed_paths: work_performed,
+                duration: start.elapsed(),
+            });
+        }
 
         #[cfg(feature = "copious-debugging")]
         {
@@ -1202,20 +2158,103 @@ impl ForwardPartialPathStitcher<Edge> {
         file: Handle<File>,
         config: StitcherConfig,
         cancellation_flag: &dyn CancellationFlag,
-        mut visit: F,
+        visit: F,
     ) -> Result<Stats, CancellationError>
     where
         F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
     {
-        fn as_complete_as_necessary(graph: &StackGraph, path: &PartialPath) -> bool {
-            path.starts_at_endpoint(graph)
-                && (path.ends_at_endpoint(graph) || path.ends_in_jump(graph))
-        }
+        Self::find_minimal_partial_path_set_in_file_with_policy(
+            graph,
+            partials,
+            file,
+            config,
+            &DefaultPartialPathPolicy,
+            cancellation_flag,
+            visit,
+        )
+    }
 
+    /// Finds the partial paths in a file that are retained by the given policy, calling the
+    /// `visit` closure for each one.  Paths are extended until the policy considers them
+    /// [complete as possible][PartialPathPolicy::is_complete_as_possible], and only visited if
+    /// the policy also considers them [productive][PartialPathPolicy::is_productive].  With the
+    /// [default policy][DefaultPartialPathPolicy], this is the same as
+    /// [`find_minimal_partial_path_set_in_file`][Self::find_minimal_partial_path_set_in_file].
+    pub fn find_minimal_partial_path_set_in_file_with_policy<F>(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        file: Handle<File>,
+        config: StitcherConfig,
+        policy: &dyn PartialPathPolicy,
+        cancellation_flag: &dyn CancellationFlag,
+        visit: F,
+    ) -> Result<Stats, CancellationError>
+    where
+        F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
+    {
+        Self::find_minimal_partial_path_set_in_file_with_filters(
+            graph,
+            partials,
+            file,
+            config,
+            policy,
+            &PathFilters::default(),
+            cancellation_flag,
+            visit,
+        )
+    }
+
+    /// Finds the partial paths in a file that are retained by the given policy, like
+    /// [`find_minimal_partial_path_set_in_file_with_policy`][Self::find_minimal_partial_path_set_in_file_with_policy],
+    /// but only extends paths with the edges, and from the nodes, that the given filters accept.
+    #[allow(clippy::too_many_arguments)]
+    pub fn find_minimal_partial_path_set_in_file_with_filters<F>(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        file: Handle<File>,
+        config: StitcherConfig,
+        policy: &dyn PartialPathPolicy,
+        filters: &PathFilters,
+        cancellation_flag: &dyn CancellationFlag,
+        visit: F,
+    ) -> Result<Stats, CancellationError>
+    where
+        F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
+    {
+        Self::find_minimal_partial_path_set_in_file_inner(
+            graph,
+            partials,
+            file,
+            config,
+            policy,
+            filters,
+            &HandleSet::new(),
+            cancellation_flag,
+            visit,
+        )
+    }
+
+    /// Finds a minimal set of partial paths in a file, not starting at any of the `excluded`
+    /// nodes.
+    #[allow(clippy::too_many_arguments)]
+    fn find_minimal_partial_path_set_in_file_inner<F>(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        file: Handle<File>,
+        config: StitcherConfig,
+        policy: &dyn PartialPathPolicy,
+        filters: &PathFilters,
+        excluded: &HandleSet<Node>,
+        cancellation_flag: &dyn CancellationFlag,
+        mut visit: F,
+    ) -> Result<Stats, CancellationError>
+    where
+        F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
+    {
         let initial_paths = graph
             .nodes_for_file(file)
             .chain(std::iter::once(StackGraph::root_node()))
-            .filter(|node| graph[*node].is_endpoint())
+            .filter(|node| graph[*node].is_endpoint() && !excluded.contains(*node))
             .map(|node| PartialPath::from_node(graph, partials, node))
             .collect::<Vec<_>>();
         let mut stitcher =
@@ -1224,16 +2263,36 @@ impl ForwardPartialPathStitcher<Edge> {
         stitcher.set_check_only_join_nodes(true);
 
         let mut accepted_path_length = FrequencyDistribution::default();
+        // Accepted paths by number of edges, which are held back until no shorter paths can be
+        // found, if paths are visited in length order.
+        let mut held_back_paths = BTreeMap::<usize, Vec<PartialPath>>::new();
         while !stitcher.is_complete() {
             cancellation_flag.check("finding complete partial paths")?;
             stitcher.process_next_phase(
-                &mut GraphEdgeCandidates::new(graph, partials, Some(file)),
-                |g, _ps, p| !as_complete_as_necessary(g, p),
+                &mut GraphEdgeCandidates::new(graph, partials, Some(file))
+                    .with_filters(filters.clone()),
+                |g, _ps, p| !policy.is_complete_as_possible(g, p),
             );
             for path in stitcher.previous_phase_partial_paths() {
-                if as_complete_as_necessary(graph, path) {
+                if policy.retains(graph, partials, path) {
                     accepted_path_length.record(path.edges.len());
-                    visit(graph, partials, path);
+                    if config.length_ordered_visits() {
+                        held_back_paths
+                            .entry(path.edges.len())
+                            .or_default()
+                            .push(path.clone());
+                    } else {
+                        visit(graph, partials, path);
+                    }
+                }
+            }
+            let min_pending_path_length = stitcher.min_pending_path_length();
+            while let Some(entry) = held_back_paths.first_entry() {
+                if min_pending_path_length.is_some_and(|min_length| *entry.key() > min_length) {
+                    break;
+                }
+                for path in entry.remove() {
+                    visit(graph, partials, &path);
                 }
             }
         }
@@ -1243,6 +2302,92 @@ impl ForwardPartialPathStitcher<Edge> {
             ..stitcher.into_stats()
         })
     }
+
+    /// Finds a minimal set of partial paths in a file, like
+    /// [`find_minimal_partial_path_set_in_file`][Self::find_minimal_partial_path_set_in_file],
+    /// but first checks the nodes of the file for construction inconsistencies.  Every
+    /// inconsistent node is reported to the `diagnostics` closure, and no paths start at or go
+    /// through it, so that the paths for the rest of the file are still found.
+    pub fn find_minimal_partial_path_set_in_file_with_diagnostics<D, F>(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        file: Handle<File>,
+        config: StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+        mut diagnostics: D,
+        visit: F,
+    ) -> Result<Stats, CancellationError>
+    where
+        D: FnMut(&StackGraph, Handle<Node>, NodeInconsistency),
+        F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
+    {
+        let mut inconsistent = HandleSet::new();
+        for node in graph.nodes_for_file(file) {
+            if let Some(inconsistency) = NodeInconsistency::of_node(graph, node) {
+                diagnostics(graph, node, inconsistency);
+                inconsistent.add(node);
+            }
+        }
+        let inconsistent = Arc::new(inconsistent);
+        let filters = PathFilters::default()
+            .with_edge_filter(Arc::new({
+                let inconsistent = inconsistent.clone();
+                move |_: &StackGraph, edge: &Edge| !inconsistent.contains(edge.sink)
+            }))
+            .with_node_visitor(Arc::new({
+                let inconsistent = inconsistent.clone();
+                move |_: &StackGraph, node: Handle<Node>| !inconsistent.contains(node)
+            }));
+        Self::find_minimal_partial_path_set_in_file_inner(
+            graph,
+            partials,
+            file,
+            config,
+            &DefaultPartialPathPolicy,
+            &filters,
+            &inconsistent,
+            cancellation_flag,
+            visit,
+        )
+    }
+}
+
+/// An inconsistency in the construction of a node, which the path finder cannot handle, reported
+/// by [`ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file_with_diagnostics`][].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeInconsistency {
+    /// A _push scoped symbol_ node refers to an attached scope node that does not exist.
+    UnknownAttachedScope,
+    /// A _push scoped symbol_ node refers to an attached scope node that is not an exported
+    /// scope node.
+    UnexportedAttachedScope(Handle<Node>),
+}
+
+impl NodeInconsistency {
+    /// Returns the inconsistency of the given node, if it has one.
+    pub fn of_node(graph: &StackGraph, node: Handle<Node>) -> Option<Self> {
+        match &graph[node] {
+            Node::PushScopedSymbol(node) => match graph.node_for_id(node.scope) {
+                None => Some(Self::UnknownAttachedScope),
+                Some(scope) if !graph[scope].is_exported_scope() => {
+                    Some(Self::UnexportedAttachedScope(scope))
+                }
+                Some(_) => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for NodeInconsistency {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::UnknownAttachedScope => write!(f, "attached scope node does not exist"),
+            Self::UnexportedAttachedScope(_) => {
+                write!(f, "attached scope node is not an exported scope")
+            }
+        }
+    }
 }
 
 impl<H: Clone> ForwardPartialPathStitcher<H> {
@@ -1271,6 +2416,83 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
         C: ForwardCandidates<H, A, Db, Err>,
         F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
         Err: std::convert::From<CancellationError>,
+    {
+        let mut stitcher = Self::for_complete_partial_paths(candidates, starting_nodes, config);
+        let mut accepted_path_length = FrequencyDistribution::default();
+        while !stitcher.is_complete() {
+            cancellation_flag.check("finding complete partial paths")?;
+            for path in stitcher.partial_paths_to_load() {
+                candidates.load_forward_candidates(path, cancellation_flag)?;
+            }
+            stitcher.process_next_phase_and_visit_complete_paths(
+                candidates,
+                &mut accepted_path_length,
+                &mut visit,
+            );
+        }
+
+        Ok(Stats {
+            accepted_path_length,
+            ..stitcher.into_stats()
+        })
+    }
+
+    /// Finds all complete partial paths that are reachable from a set of starting nodes, like
+    /// [`find_all_complete_partial_paths`][], but awaits the loading of candidates between
+    /// stitching phases, instead of blocking the thread.  This makes it possible to stitch paths
+    /// from a database that is accessed over the network in an async runtime, such as tokio.
+    /// The returned future is `Send` if the candidates, the visitor, and the starting nodes are,
+    /// so that it can be spawned on a multi-threaded runtime.
+    ///
+    /// [`find_all_complete_partial_paths`]: #method.find_all_complete_partial_paths
+    pub async fn find_all_complete_partial_paths_async<I, F, A, Db, C, Err>(
+        candidates: &mut C,
+        starting_nodes: I,
+        config: StitcherConfig,
+        cancellation_flag: &(dyn CancellationFlag + Sync),
+        mut visit: F,
+    ) -> Result<Stats, Err>
+    where
+        I: IntoIterator<Item = Handle<Node>>,
+        A: Appendable,
+        Db: ToAppendable<H, A>,
+        C: AsyncForwardCandidates<H, A, Db, Err>,
+        F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
+        Err: std::convert::From<CancellationError>,
+    {
+        let mut stitcher = Self::for_complete_partial_paths(candidates, starting_nodes, config);
+        let mut accepted_path_length = FrequencyDistribution::default();
+        while !stitcher.is_complete() {
+            cancellation_flag.check("finding complete partial paths")?;
+            for path in stitcher.partial_paths_to_load() {
+                candidates
+                    .load_forward_candidates_async(path, cancellation_flag)
+                    .await?;
+            }
+            stitcher.process_next_phase_and_visit_complete_paths(
+                candidates,
+                &mut accepted_path_length,
+                &mut visit,
+            );
+        }
+
+        Ok(Stats {
+            accepted_path_length,
+            ..stitcher.into_stats()
+        })
+    }
+
+    /// Creates a stitcher that finds complete partial paths starting at the given references.
+    pub(crate) fn for_complete_partial_paths<I, A, Db, C, Err>(
+        candidates: &mut C,
+        starting_nodes: I,
+        config: StitcherConfig,
+    ) -> Self
+    where
+        I: IntoIterator<Item = Handle<Node>>,
+        A: Appendable,
+        Db: ToAppendable<H, A>,
+        C: ForwardCandidates<H, A, Db, Err>,
     {
         let (graph, partials, _) = candidates.get_graph_partials_and_db();
         let initial_paths = starting_nodes
@@ -1286,30 +2508,184 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
             ForwardPartialPathStitcher::from_partial_paths(graph, partials, initial_paths);
         config.apply(&mut stitcher);
         stitcher.set_check_only_join_nodes(true);
+        if let Some(observer) = candidates.get_observer() {
+            stitcher.set_observer(observer);
+        }
+        stitcher
+    }
 
-        let mut accepted_path_length = FrequencyDistribution::default();
-        while !stitcher.is_complete() {
-            cancellation_flag.check("finding complete partial paths")?;
-            for path in stitcher.previous_phase_partial_paths() {
-                candidates.load_forward_candidates(path, cancellation_flag)?;
-            }
-            stitcher.process_next_phase(candidates, |_, _, _| true);
-            let (graph, partials, _) = candidates.get_graph_partials_and_db();
-            for path in stitcher.previous_phase_partial_paths() {
-                if path.is_complete(graph) {
-                    accepted_path_length.record(path.edges.len());
-                    visit(graph, partials, path);
-                }
+    /// Processes the next phase, and calls `visit` on the complete paths it found.  The
+    /// candidates for the phase must already be loaded.
+    fn process_next_phase_and_visit_complete_paths<F, A, Db, C, Err>(
+        &mut self,
+        candidates: &mut C,
+        accepted_path_length: &mut FrequencyDistribution<usize>,
+        visit: &mut F,
+    ) where
+        A: Appendable,
+        Db: ToAppendable<H, A>,
+        C: ForwardCandidates<H, A, Db, Err>,
+        F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
+    {
+        self.process_next_phase(candidates, |_, _, _| true);
+        let (graph, partials, _) = candidates.get_graph_partials_and_db();
+        for path in self.previous_phase_partial_paths() {
+            if path.is_complete(graph) {
+                accepted_path_length.record(path.edges.len());
+                visit(graph, partials, path);
             }
         }
+    }
+}
 
-        Ok(Stats {
-            accepted_path_length,
-            ..stitcher.into_stats()
+/// The number of references that a thread of the parallel stitcher processes at a time.
+const PARALLEL_STITCHING_CHUNK_SIZE: usize = 16;
+
+/// The results of a thread of the parallel stitcher: the results per processed chunk, identified
+/// by the chunk's index, and the thread's statistics.
+type ThreadResults<R> = (Vec<(usize, Vec<R>)>, Stats);
+
+impl ForwardPartialPathStitcher<Edge> {
+    /// Finds all complete partial paths that are reachable from a set of references, using
+    /// multiple threads.  This is meant for batch resolution of all references in a graph, which
+    /// must contain all files that the references might resolve to.
+    ///
+    /// The references are processed in small chunks.  Threads take the next unprocessed chunk
+    /// whenever they finish one, so that the work is balanced even if some references are much
+    /// more expensive to resolve than others.  Each thread has its own [`PartialPaths`][] arena,
+    /// stitcher, and similar path detector, and only shares the read-only graph with the others.
+    ///
+    /// Partial paths cannot leave the thread that created them, so the `visit` closure is called
+    /// on each complete path on the thread that found it, and must convert it into a result that
+    /// can be sent to other threads.  The results are returned in an order that only depends on
+    /// the order of the references, and not on the number of threads, and the statistics of all
+    /// threads are aggregated.  If the cancellation flag is triggered, all threads stop, and an error is
+    /// returned.
+    pub fn find_all_complete_partial_paths_in_parallel<F, R>(
+        graph: &StackGraph,
+        references: &[Handle<Node>],
+        config: StitcherConfig,
+        thread_count: usize,
+        cancellation_flag: &(dyn CancellationFlag + Sync),
+        visit: F,
+    ) -> Result<ParallelStitchingResults<R>, CancellationError>
+    where
+        F: Fn(&StackGraph, &mut PartialPaths, &PartialPath) -> R + Sync,
+        R: Send,
+    {
+        let chunks = references
+            .chunks(PARALLEL_STITCHING_CHUNK_SIZE)
+            .collect::<Vec<_>>();
+        let next_chunk = std::sync::atomic::AtomicUsize::new(0);
+        let failed = std::sync::atomic::AtomicBool::new(false);
+        let work = || -> Result<ThreadResults<R>, CancellationError> {
+            let mut partials = PartialPaths::new();
+            let mut chunk_results = Vec::new();
+            let mut stats = Stats::default();
+            while !failed.load(std::sync::atomic::Ordering::Relaxed) {
+                let index = next_chunk.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                let chunk = match chunks.get(index) {
+                    Some(chunk) => chunk,
+                    None => break,
+                };
+                let mut results = Vec::new();
+                let chunk_stats = Self::find_all_complete_partial_paths(
+                    &mut GraphEdgeCandidates::new(graph, &mut partials, None),
+                    chunk.iter().copied(),
+                    config,
+                    cancellation_flag,
+                    |graph, partials, path| results.push(visit(graph, partials, path)),
+                )
+                .inspect_err(|_| failed.store(true, std::sync::atomic::Ordering::Relaxed))?;
+                stats += chunk_stats;
+                chunk_results.push((index, results));
+                // All paths of this chunk have been converted into results, so we can reuse the
+                // arena for the next chunk.
+                partials.clear();
+            }
+            Ok((chunk_results, stats))
+        };
+
+        let thread_results = std::thread::scope(|scope| {
+            let workers = (0..thread_count.max(1))
+                .map(|_| scope.spawn(work))
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .map(|worker| match worker.join() {
+                    Ok(result) => result,
+                    Err(panic) => std::panic::resume_unwind(panic),
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let mut chunk_results = Vec::with_capacity(chunks.len());
+        let mut stats = Stats::default();
+        for thread_result in thread_results {
+            let (thread_chunk_results, thread_stats) = thread_result?;
+            chunk_results.extend(thread_chunk_results);
+            stats += thread_stats;
+        }
+        chunk_results.sort_by_key(|(index, _)| *index);
+        Ok(ParallelStitchingResults {
+            results: chunk_results
+                .into_iter()
+                .flat_map(|(_, results)| results)
+                .collect(),
+            stats,
         })
     }
 }
 
+/// The results of [`ForwardPartialPathStitcher::find_all_complete_partial_paths_in_parallel`][].
+#[derive(Clone, Debug, Default)]
+pub struct ParallelStitchingResults<R> {
+    /// The results of the `visit` closure for all complete paths.
+    pub results: Vec<R>,
+    /// The aggregated statistics of all threads.
+    pub stats: Stats,
+}
+
+/// A path that was dropped during stitching, because it reached a _jump to scope_ node while its
+/// scope stack was empty. Such paths usually point at a mistake in the stack graph construction
+/// rules, for example a missing _push scoped symbol_ or _scope_ node. A stitcher records them when
+/// enabled with [`StitcherConfig::with_collect_empty_scope_stacks`][].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Deserialize, ::serde::Serialize))]
+pub struct EmptyScopeStack {
+    /// The dropped path, which ends in the _jump to scope_ node.
+    pub path: serde::PartialPath,
+    /// The node that jumped to scope, which is the last node of the path before the _jump to
+    /// scope_ node.
+    pub node: serde::NodeID,
+}
+
+impl EmptyScopeStack {
+    pub(crate) fn from_partial_path(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        path: &PartialPath,
+    ) -> Self {
+        let path = serde::PartialPath::from_partial_path(graph, partials, path);
+        let node = path
+            .edges
+            .edges
+            .last()
+            .map_or_else(|| path.start_node.clone(), |edge| edge.source.clone());
+        Self { path, node }
+    }
+}
+
+impl std::fmt::Display for EmptyScopeStack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "empty scope stack when jumping from {} in {} -> {}",
+            self.node, self.path.start_node, self.path.end_node
+        )
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct Stats {
     /// The distribution of the number of initial paths
@@ -1336,6 +2712,16 @@ pub struct Stats {
     pub node_visits: FrequencyDistribution<crate::graph::NodeID>,
     /// The distribution of the number of similar paths between node pairs.
     pub similar_paths_stats: SimilarPathStats,
+    /// The number of queued paths that were spilled to disk
+    pub spilled_paths: usize,
+    /// The number of queued paths that were dropped because the queue was full
+    pub dropped_paths: usize,
+    /// The trace of all stitching decisions, if trace recording was enabled.
+    #[cfg(feature = "trace")]
+    pub trace: Option<StitchingTrace>,
+    /// The paths that were dropped because they jumped to scope with an empty scope stack, if
+    /// collecting them was enabled.
+    pub empty_scope_stacks: Vec<EmptyScopeStack>,
 }
 
 impl std::ops::AddAssign<Self> for Stats {
@@ -1352,6 +2738,16 @@ impl std::ops::AddAssign<Self> for Stats {
         self.root_visits += rhs.root_visits;
         self.node_visits += rhs.node_visits;
         self.similar_paths_stats += rhs.similar_paths_stats;
+        self.spilled_paths += rhs.spilled_paths;
+        self.dropped_paths += rhs.dropped_paths;
+        #[cfg(feature = "trace")]
+        if let Some(trace) = rhs.trace {
+            self.trace
+                .get_or_insert_with(StitchingTrace::new)
+                .events
+                .extend(trace.events);
+        }
+        self.empty_scope_stacks.extend(rhs.empty_scope_stacks);
     }
 }
 
@@ -1368,6 +2764,17 @@ impl std::ops::AddAssign<&Self> for Stats {
         self.root_visits += rhs.root_visits;
         self.node_visits += &rhs.node_visits;
         self.similar_paths_stats += &rhs.similar_paths_stats;
+        self.spilled_paths += rhs.spilled_paths;
+        self.dropped_paths += rhs.dropped_paths;
+        #[cfg(feature = "trace")]
+        if let Some(trace) = &rhs.trace {
+            self.trace
+                .get_or_insert_with(StitchingTrace::new)
+                .events
+                .extend(trace.events.iter().cloned());
+        }
+        self.empty_scope_stacks
+            .extend(rhs.empty_scope_stacks.iter().cloned());
     }
 }
 
@@ -1378,6 +2785,23 @@ pub struct StitcherConfig {
     detect_similar_paths: bool,
     /// Collect statistics about path stitching.
     collect_stats: bool,
+    /// Record a trace of all stitching decisions.
+    #[cfg(feature = "trace")]
+    collect_trace: bool,
+    /// Maximum number of paths kept per similar path bucket.
+    similar_path_budget: Option<usize>,
+    /// Maximum number of paths kept in memory in the queue.
+    max_queued_paths: Option<usize>,
+    /// Spill queued paths that exceed the maximum to disk, instead of dropping them.
+    spill_queue_to_disk: bool,
+    /// Maximum length of the attached scope lists in a path.
+    max_attached_scope_list_length: Option<usize>,
+    /// Visit the paths of a file in nondecreasing order of their number of edges.
+    length_ordered_visits: bool,
+    /// Collect the paths that jump to scope with an empty scope stack.
+    collect_empty_scope_stacks: bool,
+    /// Treat paths that jump to scope with an empty scope stack as errors in assertions.
+    deny_empty_scope_stacks: bool,
 }
 
 impl StitcherConfig {
@@ -1390,6 +2814,70 @@ impl StitcherConfig {
         self
     }
 
+    pub fn similar_path_budget(&self) -> Option<usize> {
+        self.similar_path_budget
+    }
+
+    /// Bound the number of paths that similar path detection keeps for each combination of start
+    /// and end node, and pre- and postcondition lengths. See
+    /// [`ForwardPartialPathStitcher::set_similar_path_budget`][].
+    pub fn with_similar_path_budget(mut self, similar_path_budget: Option<usize>) -> Self {
+        self.similar_path_budget = similar_path_budget;
+        self
+    }
+
+    pub fn max_queued_paths(&self) -> Option<usize> {
+        self.max_queued_paths
+    }
+
+    /// Bound the number of paths that are kept in memory in the queue. See
+    /// [`ForwardPartialPathStitcher::set_max_queued_paths`][].
+    pub fn with_max_queued_paths(mut self, max_queued_paths: Option<usize>) -> Self {
+        self.max_queued_paths = max_queued_paths;
+        self
+    }
+
+    pub fn max_attached_scope_list_length(&self) -> Option<usize> {
+        self.max_attached_scope_list_length
+    }
+
+    /// Bound the length of the attached scope lists in a path. See
+    /// [`ForwardPartialPathStitcher::set_max_attached_scope_list_length`][].
+    pub fn with_max_attached_scope_list_length(
+        mut self,
+        max_attached_scope_list_length: Option<usize>,
+    ) -> Self {
+        self.max_attached_scope_list_length = max_attached_scope_list_length;
+        self
+    }
+
+    pub fn spill_queue_to_disk(&self) -> bool {
+        self.spill_queue_to_disk
+    }
+
+    /// Spill queued paths that exceed the maximum number of queued paths to the system's temporary
+    /// directory, instead of dropping them. See
+    /// [`ForwardPartialPathStitcher::set_queue_spill_directory`][].
+    pub fn with_spill_queue_to_disk(mut self, spill_queue_to_disk: bool) -> Self {
+        self.spill_queue_to_disk = spill_queue_to_disk;
+        self
+    }
+
+    pub fn length_ordered_visits(&self) -> bool {
+        self.length_ordered_visits
+    }
+
+    /// Guarantee that [`ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file`][] and
+    /// its variants visit paths in nondecreasing order of their number of edges, so that consumers
+    /// that only need the shortest path to a node can stop looking for that node early.  Paths are
+    /// found one edge at a time, so they are usually visited in this order anyway, but not when
+    /// queued paths are spilled to disk.  Accepted paths are then held back in memory, until no
+    /// shorter paths can be found.
+    pub fn with_length_ordered_visits(mut self, length_ordered_visits: bool) -> Self {
+        self.length_ordered_visits = length_ordered_visits;
+        self
+    }
+
     pub fn collect_stats(&self) -> bool {
         self.collect_stats
     }
@@ -1398,12 +2886,57 @@ impl StitcherConfig {
         self.collect_stats = collect_stats;
         self
     }
+
+    #[cfg(feature = "trace")]
+    pub fn collect_trace(&self) -> bool {
+        self.collect_trace
+    }
+
+    /// Record a trace of all stitching decisions, which is returned as part of the stitching
+    /// statistics.
+    #[cfg(feature = "trace")]
+    pub fn with_collect_trace(mut self, collect_trace: bool) -> Self {
+        self.collect_trace = collect_trace;
+        self
+    }
+
+    pub fn collect_empty_scope_stacks(&self) -> bool {
+        self.collect_empty_scope_stacks || self.deny_empty_scope_stacks
+    }
+
+    /// Collect the paths that are dropped because they jump to scope while their scope stack is
+    /// empty, which are returned as part of the stitching statistics. See
+    /// [`ForwardPartialPathStitcher::set_collect_empty_scope_stacks`][].
+    pub fn with_collect_empty_scope_stacks(mut self, collect_empty_scope_stacks: bool) -> Self {
+        self.collect_empty_scope_stacks = collect_empty_scope_stacks;
+        self
+    }
+
+    pub fn deny_empty_scope_stacks(&self) -> bool {
+        self.deny_empty_scope_stacks
+    }
+
+    /// Fail [assertions][crate::assert::Assertion] if any path for the asserted references jumps
+    /// to scope while its scope stack is empty, even if the references resolve to the expected
+    /// definitions. This is meant for validation runs, such as tests of stack graph construction
+    /// rules. Implies [`with_collect_empty_scope_stacks`][Self::with_collect_empty_scope_stacks].
+    pub fn with_deny_empty_scope_stacks(mut self, deny_empty_scope_stacks: bool) -> Self {
+        self.deny_empty_scope_stacks = deny_empty_scope_stacks;
+        self
+    }
 }
 
 impl StitcherConfig {
-    fn apply<H>(&self, stitcher: &mut ForwardPartialPathStitcher<H>) {
+    pub(crate) fn apply<H>(&self, stitcher: &mut ForwardPartialPathStitcher<H>) {
+        stitcher.set_similar_path_budget(self.similar_path_budget);
         stitcher.set_similar_path_detection(self.detect_similar_paths);
         stitcher.set_collect_stats(self.collect_stats);
+        #[cfg(feature = "trace")]
+        stitcher.set_collect_trace(self.collect_trace);
+        stitcher.set_collect_empty_scope_stacks(self.collect_empty_scope_stacks());
+        stitcher.set_max_queued_paths(self.max_queued_paths);
+        stitcher.set_max_attached_scope_list_length(self.max_attached_scope_list_length);
+        stitcher.set_queue_spill_directory(self.spill_queue_to_disk.then(std::env::temp_dir));
     }
 }
 
@@ -1412,6 +2945,15 @@ impl Default for StitcherConfig {
         Self {
             detect_similar_paths: true,
             collect_stats: false,
+            #[cfg(feature = "trace")]
+            collect_trace: false,
+            similar_path_budget: None,
+            max_queued_paths: None,
+            spill_queue_to_disk: false,
+            max_attached_scope_list_length: None,
+            length_ordered_visits: false,
+            collect_empty_scope_stacks: false,
+            deny_empty_scope_stacks: false,
         }
     }
 }
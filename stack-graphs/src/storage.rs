@@ -7,63 +7,227 @@
 
 use bincode::error::DecodeError;
 use bincode::error::EncodeError;
+use controlled_option::ControlledOption;
 use itertools::Itertools;
 use rusqlite::functions::FunctionFlags;
+use rusqlite::types::FromSqlError;
 use rusqlite::types::ValueRef;
 use rusqlite::Connection;
 use rusqlite::OptionalExtension;
 use rusqlite::Params;
 use rusqlite::Statement;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::future::Future;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::panic::AssertUnwindSafe;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::task::Poll;
+use std::task::Waker;
+use std::thread::JoinHandle;
 use thiserror::Error;
 
 use crate::arena::Handle;
+use crate::arena::HandleSet;
+#[cfg(feature = "checked")]
+use crate::checked::InvariantViolation;
 use crate::graph::Degree;
 use crate::graph::File;
 use crate::graph::Node;
+use crate::graph::NodeID;
 use crate::graph::StackGraph;
+use crate::graph::Symbol;
+use crate::observer::QueryEvent;
+use crate::observer::QueryObserver;
 use crate::partial::PartialPath;
 use crate::partial::PartialPaths;
+use crate::partial::PartialScopedSymbol;
 use crate::partial::PartialSymbolStack;
 use crate::serde;
 use crate::serde::FileFilter;
+use crate::serde::Filter;
+use crate::stitching::AsyncForwardCandidates;
 use crate::stitching::Database;
 use crate::stitching::ForwardCandidates;
+use crate::stitching::ForwardPartialPathStitcher;
+use crate::stitching::PathProvenance;
+use crate::stitching::StitcherConfig;
+use crate::stitching::VisibilityPolicy;
+use crate::utils::stable_hash;
 use crate::CancellationError;
 use crate::CancellationFlag;
+use crate::NoCancellation;
 
-const VERSION: usize = 6;
+/// The database version written by this version of the library.  Databases with an older
+/// version can be upgraded using [`migrate`][].
+pub const VERSION: usize = 24;
 
+// Data for a file is stored as an entry, consisting of the graph and the partial paths of the
+// file. The files table maps files in a revision to their entry. Successfully indexed entries are
+// keyed by file path and tag, which is expected to be a hash of the file content, so that all
+// revisions, and all repositories, containing the same file content refer to the same entry.
+// The path is part of the key, because stored graphs and paths refer to their file by name.
+// The files that refer to an entry are its references: storing a file whose entry is shared with
+// other files adds a reference to it without changing its data, and an entry is only replaced in
+// place, or removed, when no other file refers to it anymore.
+// Entries whose paths are incomplete, for example because path finding timed out, record the
+// reason in the partial column. The metadata of the file graph is also stored in the metadata
+// column, so that it can be listed without decoding the graph. The file group from the metadata
+// is stored in its own column, so that the other files of a group can be found when one is loaded.
+//
+// A file can have several variants, such as the graphs for different build configurations. The
+// default variant is the empty string. Each variant of a file is mapped to its own entry, and
+// entries of different variants of the same file content are stored separately. Readers select an
+// ordered set of variants, and the selected_files view contains, for every file, the first
+// selected variant that exists for it, or the default variant if none of them does.
+//
+// Pre-stitched paths are complete paths from the root for popular symbol stacks of a revision.
+// They span many files, so they belong to the revision instead of an entry.
+//
+// Resolved references are the complete paths from references in selected files of a revision to
+// their definitions, which are precomputed so that queries for these references do not have to
+// stitch paths. Like pre-stitched paths, they belong to the revision.
+//
+// Both kinds of artifacts record their dependencies: the files whose paths were candidates while
+// stitching them, and the patterns of the root path symbol stacks that were looked up. When a file
+// of the revision is stored or cleaned, only the artifacts that used paths of the file, or that
+// looked up root paths matching one of the file's new root paths, are removed.
+//
+// The checkpoint of a revision records the files, and their tags, that were completed by an
+// indexing run that has not finished yet, so that an interrupted run can be resumed.
+//
+// The strings of stored graphs, such as file names and symbols, are stored once in the strings
+// table, which is shared by all entries. A stored graph refers to the strings it uses by their
+// id. Strings that are not used by any entry anymore are removed by garbage collection.
+//
+// A library database is a sealed database with summaries of a dependency package, such as the
+// contents of a node_modules package. Its files belong to the default revision, and only have
+// root paths, which are the complete paths from the root through the library to its definitions,
+// or back to the root. The graphs of its files only contain the nodes these paths refer to. The
+// library table records the name and version of the library, and is empty for all other
+// databases.
 const SCHEMA: &str = r#"
         CREATE TABLE metadata (
             version INTEGER NOT NULL
         ) STRICT;
+        CREATE TABLE files (
+            repo      TEXT NOT NULL,
+            commit_id TEXT NOT NULL,
+            file      TEXT NOT NULL,
+            variant   TEXT NOT NULL DEFAULT '',
+            entry     INTEGER NOT NULL,
+            PRIMARY KEY(repo, commit_id, file, variant),
+            FOREIGN KEY(entry) REFERENCES graphs(entry)
+        ) STRICT;
         CREATE TABLE graphs (
-            file   TEXT PRIMARY KEY,
-            tag    TEXT NOT NULL,
-            error  TEXT,
-            value  BLOB NOT NULL
+            entry  INTEGER PRIMARY KEY,
+            file   TEXT NOT NULL,
+            tag     TEXT NOT NULL,
+            error   TEXT,
+            partial TEXT,
+            value   BLOB NOT NULL,
+            metadata BLOB,
+            file_group TEXT,
+            variant TEXT NOT NULL DEFAULT '',
+            indexed_at INTEGER
         ) STRICT;
         CREATE TABLE file_paths (
-            file     TEXT NOT NULL,
+            entry    INTEGER NOT NULL,
             local_id INTEGER NOT NULL,
             value    BLOB NOT NULL,
-            FOREIGN KEY(file) REFERENCES graphs(file)
+            FOREIGN KEY(entry) REFERENCES graphs(entry)
         ) STRICT;
         CREATE TABLE root_paths (
-            file         TEXT NOT NULL,
+            entry        INTEGER NOT NULL,
+            symbol_stack TEXT NOT NULL,
+            value        BLOB NOT NULL,
+            FOREIGN KEY(entry) REFERENCES graphs(entry)
+        ) STRICT;
+        CREATE TABLE prestitched_symbol_stacks (
+            id           INTEGER PRIMARY KEY,
+            repo         TEXT NOT NULL,
+            commit_id    TEXT NOT NULL,
             symbol_stack TEXT NOT NULL,
+            UNIQUE(repo, commit_id, symbol_stack)
+        ) STRICT;
+        CREATE TABLE prestitched_paths (
+            symbol_stack INTEGER NOT NULL,
             value        BLOB NOT NULL,
-            FOREIGN KEY(file) REFERENCES graphs(file)
+            FOREIGN KEY(symbol_stack) REFERENCES prestitched_symbol_stacks(id)
+        ) STRICT;
+        CREATE TABLE prestitched_dependencies (
+            symbol_stack    INTEGER NOT NULL,
+            dependency_file TEXT,
+            root_pattern    TEXT,
+            FOREIGN KEY(symbol_stack) REFERENCES prestitched_symbol_stacks(id)
+        ) STRICT;
+        CREATE TABLE checkpoint_files (
+            repo      TEXT NOT NULL,
+            commit_id TEXT NOT NULL,
+            file      TEXT NOT NULL,
+            tag       TEXT NOT NULL,
+            PRIMARY KEY(repo, commit_id, file)
+        ) STRICT;
+        CREATE TABLE strings (
+            id    INTEGER PRIMARY KEY,
+            value TEXT NOT NULL UNIQUE
+        ) STRICT;
+        CREATE TABLE resolved_references (
+            repo      TEXT NOT NULL,
+            commit_id TEXT NOT NULL,
+            file      TEXT NOT NULL,
+            local_id  INTEGER NOT NULL,
+            value     BLOB NOT NULL,
+            PRIMARY KEY(repo, commit_id, file, local_id)
+        ) STRICT;
+        CREATE TABLE resolved_reference_dependencies (
+            repo            TEXT NOT NULL,
+            commit_id       TEXT NOT NULL,
+            file            TEXT NOT NULL,
+            local_id        INTEGER NOT NULL,
+            dependency_file TEXT,
+            root_pattern    TEXT
+        ) STRICT;
+        CREATE TABLE library (
+            name    TEXT NOT NULL,
+            version TEXT NOT NULL DEFAULT ''
         ) STRICT;
     "#;
 
 const INDEXES: &str = r#"
-        CREATE INDEX IF NOT EXISTS idx_graphs_file ON graphs(file);
-        CREATE INDEX IF NOT EXISTS idx_file_paths_local_id ON file_paths(file, local_id);
+        CREATE INDEX IF NOT EXISTS idx_files_entry ON files(entry);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_graphs_key ON graphs(file, tag, variant) WHERE error IS NULL;
+        CREATE INDEX IF NOT EXISTS idx_graphs_file_group ON graphs(file_group) WHERE file_group IS NOT NULL;
+        CREATE INDEX IF NOT EXISTS idx_file_paths_local_id ON file_paths(entry, local_id);
         CREATE INDEX IF NOT EXISTS idx_root_paths_symbol_stack ON root_paths(symbol_stack);
+        CREATE INDEX IF NOT EXISTS idx_root_paths_entry ON root_paths(entry);
+        CREATE INDEX IF NOT EXISTS idx_prestitched_paths_symbol_stack ON prestitched_paths(symbol_stack);
+        CREATE INDEX IF NOT EXISTS idx_prestitched_dependencies_symbol_stack ON prestitched_dependencies(symbol_stack);
+        CREATE INDEX IF NOT EXISTS idx_resolved_reference_dependencies_reference ON resolved_reference_dependencies(repo, commit_id, file, local_id);
+    "#;
+
+// The files of a revision as seen by readers, with one variant per file, according to the
+// variant_rank function of the connection.
+const VIEWS: &str = r#"
+        CREATE TEMP VIEW IF NOT EXISTS selected_files AS
+            SELECT f.repo, f.commit_id, f.file, f.variant, f.entry FROM files f
+            WHERE variant_rank(f.variant) IS NOT NULL AND NOT EXISTS (
+                SELECT 1 FROM files o
+                WHERE o.repo = f.repo AND o.commit_id = f.commit_id AND o.file = f.file
+                  AND variant_rank(o.variant) < variant_rank(f.variant)
+            );
     "#;
 
 const PRAGMAS: &str = r#"
@@ -82,6 +246,22 @@ pub enum StorageError {
     IncorrectVersion(usize),
     #[error("database does not exist {0}")]
     MissingDatabase(String),
+    #[error("file {0} is not stored in the database")]
+    MissingFile(String),
+    #[error("invalid database file: {0}")]
+    InvalidDatabaseFile(String),
+    #[error("cannot copy files of a revision to itself")]
+    SameRevision,
+    #[error("cannot migrate database from version {0} to version {1}")]
+    UnsupportedMigration(usize, usize),
+    #[error("`{0}` is an invalid reference into the strings of a stored graph")]
+    InvalidStringReference(u32),
+    #[error("database {0} is a sealed library, which cannot be written")]
+    SealedLibrary(String),
+    #[error("database {0} is not a library")]
+    NotALibrary(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
     #[error(transparent)]
     Rusqlite(#[from] rusqlite::Error),
     #[error(transparent)]
@@ -90,6 +270,9 @@ pub enum StorageError {
     SerializeFail(#[from] EncodeError),
     #[error(transparent)]
     DeserializeFail(#[from] DecodeError),
+    #[cfg(feature = "checked")]
+    #[error(transparent)]
+    InvariantViolation(#[from] InvariantViolation),
 }
 
 pub type Result<T> = std::result::Result<T, StorageError>;
@@ -100,13 +283,69 @@ impl From<CancellationError> for StorageError {
     }
 }
 
+/// A revision of a repository. In a multi-tenant database, every file belongs to a revision, and
+/// readers and writers operate on a single revision. Databases that store a single source tree
+/// use the default revision, which has an empty repository and commit.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Revision {
+    pub repo: String,
+    pub commit: String,
+}
+
+impl Revision {
+    pub fn new<R: Into<String>, C: Into<String>>(repo: R, commit: C) -> Self {
+        Self {
+            repo: repo.into(),
+            commit: commit.into(),
+        }
+    }
+
+    fn params(&self) -> [&str; 2] {
+        [&self.repo, &self.commit]
+    }
+}
+
+/// Returns the error for a path that is stored for a file, but does not start in the file or at
+/// the root node.  This violates an invariant of the storage backends, which panics unless the
+/// `checked` feature is enabled.
+pub(crate) fn path_outside_file(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    path: &PartialPath,
+    file: Handle<File>,
+) -> StorageError {
+    let path = path.display(graph, partials).to_string();
+    let file = graph[file].name().to_string();
+    #[cfg(feature = "checked")]
+    return InvariantViolation::PathOutsideFile(path, file).into();
+    #[cfg(not(feature = "checked"))]
+    panic!(
+        "added path {} must start in given file {} or at root",
+        path, file
+    );
+}
+
 /// The status of a file in the database.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum FileStatus {
     Missing,
     Indexed,
+    /// The file was indexed, but its paths are incomplete for the given reason.
+    Partial(String),
     Error(String),
 }
 
+impl FileStatus {
+    /// Returns the status for the error and partial columns of an indexed file.
+    fn from_columns(error: ValueRef, partial: ValueRef) -> rusqlite::Result<Self> {
+        Ok(match (error.as_str_or_null()?, partial.as_str_or_null()?) {
+            (Some(error), _) => Self::Error(error.to_string()),
+            (None, Some(reason)) => Self::Partial(reason.to_string()),
+            (None, None) => Self::Indexed,
+        })
+    }
+}
+
 impl<'a> From<ValueRef<'a>> for FileStatus {
     fn from(value: ValueRef<'a>) -> Self {
         match value {
@@ -126,52 +365,252 @@ pub struct FileEntry {
     pub path: PathBuf,
     pub tag: String,
     pub status: FileStatus,
+    /// The metadata of the file's graph.  Empty if indexing the file failed.
+    pub metadata: BTreeMap<String, String>,
 }
 
-/// An iterator over a query returning rows with (path,tag,error) tuples.
-pub struct Files<'a, P: Params>(Statement<'a>, P);
+/// An iterator over a query returning rows with (path,tag,error,partial,metadata) tuples. For sharded
+/// databases, the query is executed on every shard.
+pub struct Files<'a, P: Params>(Vec<Statement<'a>>, P);
 
 impl<'a, P: Params + Clone> Files<'a, P> {
     pub fn try_iter<'b>(&'b mut self) -> Result<impl Iterator<Item = Result<FileEntry>> + 'b> {
-        let entries = self.0.query_map(self.1.clone(), |r| {
-            Ok(FileEntry {
-                path: PathBuf::from(r.get::<_, String>(0)?),
-                tag: r.get::<_, String>(1)?,
-                status: r.get_ref(2)?.into(),
-            })
-        })?;
-        let entries = entries.map(|r| -> Result<FileEntry> { Ok(r?) });
+        let Files(stmts, params) = self;
+        let mut entries = Vec::with_capacity(stmts.len());
+        for stmt in stmts.iter_mut() {
+            entries.push(stmt.query_map(params.clone(), file_entry_from_row)?);
+        }
+        let entries = entries
+            .into_iter()
+            .flatten()
+            .map(|r| -> Result<FileEntry> { Ok(r?) });
         Ok(entries)
     }
 }
 
+fn file_entry_from_row(r: &rusqlite::Row) -> rusqlite::Result<FileEntry> {
+    Ok(FileEntry {
+        path: PathBuf::from(r.get::<_, String>(0)?),
+        tag: r.get::<_, String>(1)?,
+        status: FileStatus::from_columns(r.get_ref(2)?, r.get_ref(3)?)?,
+        metadata: metadata_from_column(r.get_ref(4)?)?,
+    })
+}
+
+/// Metadata key for the group of a file. Files in the same revision whose graphs have the same
+/// value for this key form a group, such as a C header and its source file, which are treated as
+/// a single unit: [`SQLiteReader`][] always loads the graphs of all files in a group together.
+/// Paths through the files of a group are therefore always found together, and precedence
+/// between them is resolved as if they were in a single file.
+pub const FILE_GROUP_METADATA: &str = "file_group";
+
+/// Graph metadata key for the version of the rules that produced a file's graph and paths.  It is
+/// reported as the [rules version][PathProvenance::rules_version] of the file's paths, when the
+/// reader [collects provenance][SQLiteReader::with_collect_provenance].
+pub const RULES_VERSION_METADATA: &str = "rules_version";
+
+/// Returns the current time in seconds since the Unix epoch, which is stored as the time an
+/// entry was indexed.
+fn now_as_unix_seconds() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// Decodes the metadata column of an entry.
+fn metadata_from_column(value: ValueRef) -> rusqlite::Result<BTreeMap<String, String>> {
+    match value {
+        ValueRef::Null => Ok(BTreeMap::new()),
+        ValueRef::Blob(value) => {
+            let (metadata, _): (serde::Metadata, usize) =
+                bincode::decode_from_slice(value, BINCODE_CONFIG).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        4,
+                        rusqlite::types::Type::Blob,
+                        Box::new(e),
+                    )
+                })?;
+            Ok(metadata.data)
+        }
+        _ => Err(FromSqlError::InvalidType.into()),
+    }
+}
+
+/// A file graph as stored in the value column of the graphs table.  The strings of the graph are
+/// stored in the strings table, and replaced by empty strings in the stored graph.
+#[derive(bincode::Encode, bincode::Decode)]
+struct StoredGraph {
+    /// The ids of the distinct strings of the graph in the strings table.
+    strings: Vec<i64>,
+    /// The index in `strings` of every string of the graph, in the order in which they are
+    /// visited by [`serde::StackGraph::for_each_string_mut`].
+    references: Vec<u32>,
+    graph: serde::StackGraph,
+}
+
+/// The prefix of a [`StoredGraph`][], which can be decoded without decoding the graph.
+#[derive(bincode::Decode)]
+struct StoredGraphStrings {
+    strings: Vec<i64>,
+}
+
+/// Encodes a file graph for the graphs table, adding the strings it uses to the strings table.
+fn encode_graph(conn: &Connection, mut graph: serde::StackGraph) -> Result<Vec<u8>> {
+    let (strings, references) = take_graph_strings(conn, &mut graph)?;
+    let stored = StoredGraph {
+        strings,
+        references,
+        graph,
+    };
+    Ok(bincode::encode_to_vec(&stored, BINCODE_CONFIG)?)
+}
+
+/// Takes the strings out of a file graph, and adds them to the strings table.  Returns the ids of
+/// the distinct strings, and the index of every string in those ids.
+fn take_graph_strings(
+    conn: &Connection,
+    graph: &mut serde::StackGraph,
+) -> Result<(Vec<i64>, Vec<u32>)> {
+    let mut indices = HashMap::new();
+    let mut distinct = Vec::new();
+    let mut references = Vec::new();
+    graph.for_each_string_mut(|string| {
+        let index = *indices
+            .entry(std::mem::take(string))
+            .or_insert_with_key(|string| {
+                distinct.push(string.clone());
+                distinct.len() as u32 - 1
+            });
+        references.push(index);
+    });
+    let mut select = conn.prepare_cached("SELECT id FROM strings WHERE value = ?")?;
+    let mut insert = conn.prepare_cached("INSERT INTO strings (value) VALUES (?)")?;
+    let mut strings = Vec::with_capacity(distinct.len());
+    for string in &distinct {
+        let id = match select.query_row([string], |r| r.get(0)).optional()? {
+            Some(id) => id,
+            None => insert.insert([string])?,
+        };
+        strings.push(id);
+    }
+    Ok((strings, references))
+}
+
+/// Decodes a file graph from the graphs table, restoring its strings from the strings table.
+/// Every distinct string of the graph is read from the strings table only once.
+fn decode_graph(conn: &Connection, value: &[u8]) -> Result<serde::StackGraph> {
+    let (stored, _): (StoredGraph, usize) = bincode::decode_from_slice(value, BINCODE_CONFIG)?;
+    let StoredGraph {
+        strings,
+        references,
+        mut graph,
+    } = stored;
+    let mut stmt = conn.prepare_cached("SELECT value FROM strings WHERE id = ?")?;
+    let strings = strings
+        .into_iter()
+        .map(|id| stmt.query_row([id], |r| r.get::<_, String>(0)))
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut references = references.into_iter();
+    let mut invalid = None;
+    graph.for_each_string_mut(|string| {
+        let index = references.next().unwrap_or(u32::MAX);
+        match strings.get(index as usize) {
+            Some(value) => *string = value.clone(),
+            None => invalid = invalid.or(Some(index)),
+        }
+    });
+    if let Some(index) = invalid {
+        return Err(StorageError::InvalidStringReference(index));
+    }
+    Ok(graph)
+}
+
 /// Writer to store stack graphs and partial paths in a SQLite database.
 pub struct SQLiteWriter {
     conn: Connection,
+    /// The path of the database, or the URI of a shared in-memory database, which is used to open
+    /// the connections of readers that stitch paths on behalf of the writer.
+    path: PathBuf,
+    revision: Revision,
+    variant: String,
 }
 
 impl SQLiteWriter {
     /// Open an in-memory database.
     pub fn open_in_memory() -> Result<Self> {
-        let mut conn = Connection::open_in_memory()?;
+        // The in-memory database is shared, so that readers that stitch paths on behalf of the
+        // writer can open their own connection to it. It is named uniquely, so that writers do
+        // not see each other's data. The database exists as long as the writer's connection does.
+        static NEXT_DATABASE: AtomicUsize = AtomicUsize::new(0);
+        let path = PathBuf::from(format!(
+            "file:stack-graphs-{}-{}?mode=memory&cache=shared",
+            std::process::id(),
+            NEXT_DATABASE.fetch_add(1, Ordering::Relaxed),
+        ));
+        let mut conn = Connection::open(&path)?;
         Self::init(&mut conn)?;
         init_indexes(&mut conn)?;
-        Ok(Self { conn })
+        init_views(&conn)?;
+        Ok(Self {
+            conn,
+            path,
+            revision: Revision::default(),
+            variant: String::new(),
+        })
     }
 
     /// Open a file database.  If the file does not exist, it is automatically created.
-    /// An error is returned if the database version is not supported.
+    /// An error is returned if the database version is not supported, or if the database is a
+    /// sealed library.
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let is_new = !path.as_ref().exists();
+        let path = path.as_ref();
+        let is_new = !path.exists();
         let mut conn = Connection::open(path)?;
         set_pragmas_and_functions(&conn)?;
         if is_new {
             Self::init(&mut conn)?;
         } else {
             check_version(&conn)?;
+            if read_library(&conn)?.is_some() {
+                return Err(StorageError::SealedLibrary(
+                    path.to_string_lossy().to_string(),
+                ));
+            }
         }
         init_indexes(&mut conn)?;
-        Ok(Self { conn })
+        init_views(&conn)?;
+        Ok(Self {
+            conn,
+            path: path.to_path_buf(),
+            revision: Revision::default(),
+            variant: String::new(),
+        })
+    }
+
+    /// Use this writer for the given revision. All files written, cleaned, or queried belong to
+    /// this revision.
+    pub fn with_revision(mut self, revision: Revision) -> Self {
+        self.revision = revision;
+        self
+    }
+
+    /// Returns the revision this writer operates on.
+    pub fn revision(&self) -> &Revision {
+        &self.revision
+    }
+
+    /// Use this writer for the given variant of files, such as the name of a build configuration.
+    /// All files written, cleaned, or queried by file name belong to this variant. The default
+    /// variant is the empty string. Files of other variants are kept when files are stored.
+    pub fn with_variant<V: Into<String>>(mut self, variant: V) -> Result<Self> {
+        self.variant = variant.into();
+        select_variants(&self.conn, vec![self.variant.clone()])?;
+        Ok(self)
+    }
+
+    /// Returns the variant this writer operates on.
+    pub fn variant(&self) -> &str {
+        &self.variant
     }
 
     /// Create database tables and write metadata.
@@ -183,30 +622,34 @@ impl SQLiteWriter {
         Ok(())
     }
 
-    /// Clean all data from the database.
+    /// Clean all data of the writer's revision from the database.
     pub fn clean_all(&mut self) -> Result<usize> {
         let tx = self.conn.transaction()?;
-        let count = Self::clean_all_inner(&tx)?;
+        let count = Self::clean_all_inner(&tx, &self.revision)?;
         tx.commit()?;
         Ok(count)
     }
 
-    /// Clean all data from the database.
+    /// Clean all data of the given revision from the database.
     ///
     /// This is an inner method, which does not wrap individual SQL statements in a transaction.
-    fn clean_all_inner(conn: &Connection) -> Result<usize> {
-        {
-            let mut stmt = conn.prepare_cached("DELETE FROM file_paths")?;
-            stmt.execute([])?;
-        }
-        {
-            let mut stmt = conn.prepare_cached("DELETE FROM root_paths")?;
-            stmt.execute([])?;
-        }
+    fn clean_all_inner(conn: &Connection, revision: &Revision) -> Result<usize> {
+        let entries = {
+            let mut stmt =
+                conn.prepare_cached("SELECT entry FROM files WHERE repo = ? AND commit_id = ?")?;
+            let entries = stmt
+                .query_map(revision.params(), |r| r.get::<_, i64>(0))?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            entries
+        };
         let count = {
-            let mut stmt = conn.prepare_cached("DELETE FROM graphs")?;
-            stmt.execute([])?
+            let mut stmt =
+                conn.prepare_cached("DELETE FROM files WHERE repo = ? AND commit_id = ?")?;
+            stmt.execute(revision.params())?
         };
+        release_entries(conn, entries)?;
+        remove_prestitched_paths(conn, revision)?;
+        remove_resolved_references(conn, revision)?;
         Ok(count)
     }
 
@@ -214,7 +657,7 @@ impl SQLiteWriter {
     /// that file is cleaned.
     pub fn clean_file(&mut self, file: &Path) -> Result<usize> {
         let tx = self.conn.transaction()?;
-        let count = Self::clean_file_inner(&tx, file)?;
+        let count = Self::clean_file_inner(&tx, &self.revision, &self.variant, file)?;
         tx.commit()?;
         Ok(count)
     }
@@ -222,20 +665,30 @@ impl SQLiteWriter {
     /// Clean file data from the database.
     ///
     /// This is an inner method, which does not wrap individual SQL statements in a transaction.
-    fn clean_file_inner(conn: &Connection, file: &Path) -> Result<usize> {
+    fn clean_file_inner(
+        conn: &Connection,
+        revision: &Revision,
+        variant: &str,
+        file: &Path,
+    ) -> Result<usize> {
         let file = file.to_string_lossy();
-        {
-            let mut stmt = conn.prepare_cached("DELETE FROM file_paths WHERE file=?")?;
-            stmt.execute([&file])?;
-        }
-        {
-            let mut stmt = conn.prepare_cached("DELETE FROM root_paths WHERE file=?")?;
-            stmt.execute([&file])?;
-        }
+        let params = (&revision.repo, &revision.commit, &file, variant);
+        let entry = {
+            let mut stmt = conn.prepare_cached(
+                "SELECT entry FROM files WHERE repo = ? AND commit_id = ? AND file = ? AND variant = ?",
+            )?;
+            stmt.query_row(params, |r| r.get::<_, i64>(0)).optional()?
+        };
         let count = {
-            let mut stmt = conn.prepare_cached("DELETE FROM graphs WHERE file=?")?;
-            stmt.execute([&file])?
+            let mut stmt = conn.prepare_cached(
+                "DELETE FROM files WHERE repo = ? AND commit_id = ? AND file = ? AND variant = ?",
+            )?;
+            stmt.execute(params)?
         };
+        release_entries(conn, entry)?;
+        if count > 0 {
+            invalidate_artifacts_for_file(conn, revision, &file, None)?;
+        }
         Ok(count)
     }
 
@@ -243,7 +696,12 @@ impl SQLiteWriter {
     /// is cleaned.
     pub fn clean_file_or_directory(&mut self, file_or_directory: &Path) -> Result<usize> {
         let tx = self.conn.transaction()?;
-        let count = Self::clean_file_or_directory_inner(&tx, file_or_directory)?;
+        let count = Self::clean_file_or_directory_inner(
+            &tx,
+            &self.revision,
+            &self.variant,
+            file_or_directory,
+        )?;
         tx.commit()?;
         Ok(count)
     }
@@ -252,30 +710,153 @@ impl SQLiteWriter {
     /// is cleaned.
     ///
     /// This is an inner method, which does not wrap individual SQL statements in a transaction.
-    fn clean_file_or_directory_inner(conn: &Connection, file_or_directory: &Path) -> Result<usize> {
+    fn clean_file_or_directory_inner(
+        conn: &Connection,
+        revision: &Revision,
+        variant: &str,
+        file_or_directory: &Path,
+    ) -> Result<usize> {
         let file_or_directory = file_or_directory.to_string_lossy();
-        {
-            let mut stmt =
-                conn.prepare_cached("DELETE FROM file_paths WHERE path_descendant_of(file, ?)")?;
-            stmt.execute([&file_or_directory])?;
+        let params = (
+            &revision.repo,
+            &revision.commit,
+            &file_or_directory,
+            variant,
+        );
+        let (files, entries): (Vec<_>, Vec<_>) = {
+            let mut stmt = conn.prepare_cached(
+                "SELECT file, entry FROM files WHERE repo = ? AND commit_id = ? AND path_descendant_of(file, ?) AND variant = ?",
+            )?;
+            let entries = stmt
+                .query_map(params, |r| {
+                    Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?))
+                })?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            entries.into_iter().unzip()
+        };
+        let count = {
+            let mut stmt = conn.prepare_cached(
+                "DELETE FROM files WHERE repo = ? AND commit_id = ? AND path_descendant_of(file, ?) AND variant = ?",
+            )?;
+            stmt.execute(params)?
+        };
+        release_entries(conn, entries)?;
+        for file in files {
+            invalidate_artifacts_for_file(conn, revision, &file, None)?;
         }
+        Ok(count)
+    }
+
+    /// Remove all files of the given revision from the database, without removing their data.
+    /// This is cheaper than [`clean_all`][Self::clean_all], which removes data that is not used
+    /// anymore immediately. Use [`collect_garbage`][Self::collect_garbage] to remove data that is
+    /// not used by any remaining revision.
+    pub fn remove_revision(&mut self, revision: &Revision) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let count = {
+            let mut stmt =
+                tx.prepare_cached("DELETE FROM files WHERE repo = ? AND commit_id = ?")?;
+            stmt.execute(revision.params())?
+        };
+        remove_prestitched_paths(&tx, revision)?;
+        remove_resolved_references(&tx, revision)?;
         {
             let mut stmt =
-                conn.prepare_cached("DELETE FROM root_paths WHERE path_descendant_of(file, ?)")?;
-            stmt.execute([&file_or_directory])?;
+                tx.prepare_cached("DELETE FROM checkpoint_files WHERE repo = ? AND commit_id = ?")?;
+            stmt.execute(revision.params())?;
+        }
+        tx.commit()?;
+        Ok(count)
+    }
+
+    /// Remove all data that is not used by any file in any revision, including strings that are
+    /// not used by any remaining entry. If `dry_run` is true, the data is counted but not removed.
+    pub fn collect_garbage(&mut self, dry_run: bool) -> Result<GarbageStats> {
+        let tx = self.conn.transaction()?;
+        let mut stats = GarbageStats::default();
+        for (table, count) in [
+            ("graphs", &mut stats.entries),
+            ("file_paths", &mut stats.node_paths),
+            ("root_paths", &mut stats.root_paths),
+        ] {
+            let unused = format!(
+                "FROM {0} WHERE NOT EXISTS (SELECT 1 FROM files WHERE files.entry = {0}.entry)",
+                table
+            );
+            *count = if dry_run {
+                tx.query_row(&format!("SELECT COUNT(*) {}", unused), [], |r| r.get(0))?
+            } else {
+                tx.execute(&format!("DELETE {}", unused), [])?
+            };
+        }
+        stats.strings = Self::collect_unused_strings_inner(&tx, dry_run)?;
+        tx.commit()?;
+        Ok(stats)
+    }
+
+    /// Remove the strings that are not used by the graph of any entry that is used by a file, and
+    /// return their number. If `dry_run` is true, the strings are counted but not removed.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn collect_unused_strings_inner(conn: &Connection, dry_run: bool) -> Result<usize> {
+        let mut used = HashSet::new();
+        let mut stmt = conn.prepare_cached(
+            "SELECT value FROM graphs WHERE EXISTS (SELECT 1 FROM files WHERE files.entry = graphs.entry)",
+        )?;
+        for value in stmt.query_map([], |r| r.get::<_, Vec<u8>>(0))? {
+            let (stored, _): (StoredGraphStrings, usize) =
+                bincode::decode_from_slice(&value?, BINCODE_CONFIG)?;
+            used.extend(stored.strings);
+        }
+        let mut unused = Vec::new();
+        let mut stmt = conn.prepare_cached("SELECT id FROM strings")?;
+        for id in stmt.query_map([], |r| r.get::<_, i64>(0))? {
+            let id = id?;
+            if !used.contains(&id) {
+                unused.push(id);
+            }
+        }
+        if !dry_run {
+            let mut stmt = conn.prepare_cached("DELETE FROM strings WHERE id = ?")?;
+            for id in &unused {
+                stmt.execute([id])?;
+            }
+        }
+        Ok(unused.len())
+    }
+
+    /// Make all files of the given base revision part of the writer's revision, replacing any
+    /// files the writer's revision had. File data is shared between the revisions, and not
+    /// copied. Only files that changed between the revisions have to be stored afterwards.
+    /// Returns the number of files in the revision.
+    pub fn copy_files_from_revision(&mut self, base: &Revision) -> Result<usize> {
+        if base == &self.revision {
+            return Err(StorageError::SameRevision);
         }
+        let tx = self.conn.transaction()?;
+        Self::clean_all_inner(&tx, &self.revision)?;
         let count = {
-            let mut stmt =
-                conn.prepare_cached("DELETE FROM graphs WHERE path_descendant_of(file, ?)")?;
-            stmt.execute([&file_or_directory])?
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO files (repo, commit_id, file, variant, entry)
+                 SELECT ?, ?, file, variant, entry FROM files WHERE repo = ? AND commit_id = ?",
+            )?;
+            stmt.execute((
+                &self.revision.repo,
+                &self.revision.commit,
+                &base.repo,
+                &base.commit,
+            ))?
         };
+        tx.commit()?;
         Ok(count)
     }
 
-    /// Store an error, indicating that indexing this file failed.
+    /// Store an error, indicating that indexing this file failed. Any data previously stored
+    /// for the file is replaced.
     pub fn store_error_for_file(&mut self, file: &Path, tag: &str, error: &str) -> Result<()> {
         let tx = self.conn.transaction()?;
-        Self::store_error_for_file_inner(&tx, file, tag, error)?;
+        Self::clean_file_inner(&tx, &self.revision, &self.variant, file)?;
+        Self::store_error_for_file_inner(&tx, &self.revision, &self.variant, file, tag, error)?;
         tx.commit()?;
         Ok(())
     }
@@ -285,16 +866,46 @@ impl SQLiteWriter {
     /// This is an inner method, which does not wrap individual SQL statements in a transaction.
     fn store_error_for_file_inner(
         conn: &Connection,
+        revision: &Revision,
+        variant: &str,
         file: &Path,
         tag: &str,
         error: &str,
     ) -> Result<()> {
         copious_debugging!("--> Store error for {}", file.display());
-        let mut stmt = conn
-            .prepare_cached("INSERT INTO graphs (file, tag, error, value) VALUES (?, ?, ?, ?)")?;
-        let graph = crate::serde::StackGraph::default();
-        let serialized = bincode::encode_to_vec(&graph, BINCODE_CONFIG)?;
-        stmt.execute((&file.to_string_lossy(), tag, error, serialized))?;
+        let file = file.to_string_lossy();
+        let mut stmt = conn.prepare_cached(
+            "INSERT INTO graphs (file, tag, error, value, variant, indexed_at) VALUES (?, ?, ?, ?, ?, ?)",
+        )?;
+        let serialized = encode_graph(conn, crate::serde::StackGraph::default())?;
+        stmt.execute((
+            &file,
+            tag,
+            error,
+            serialized,
+            variant,
+            now_as_unix_seconds(),
+        ))?;
+        let entry = conn.last_insert_rowid();
+        Self::store_file_entry_inner(conn, revision, variant, &file, entry)?;
+        Ok(())
+    }
+
+    /// Map the file in the given revision to the entry.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn store_file_entry_inner(
+        conn: &Connection,
+        revision: &Revision,
+        variant: &str,
+        file: &str,
+        entry: i64,
+    ) -> Result<()> {
+        let mut stmt = conn.prepare_cached(
+            "INSERT INTO files (repo, commit_id, file, variant, entry) VALUES (?, ?, ?, ?, ?)",
+        )?;
+        stmt.execute((&revision.repo, &revision.commit, file, variant, entry))?;
+        invalidate_artifacts_for_file(conn, revision, file, Some(entry))?;
         Ok(())
     }
 
@@ -310,562 +921,4276 @@ impl SQLiteWriter {
     where
         IP: IntoIterator<Item = &'a PartialPath>,
     {
-        let path = Path::new(graph[file].name());
-        let tx = self.conn.transaction()?;
-        Self::clean_file_inner(&tx, path)?;
-        Self::store_graph_for_file_inner(&tx, graph, file, tag)?;
-        Self::store_partial_paths_for_file_inner(&tx, graph, file, partials, paths)?;
-        tx.commit()?;
-        Ok(())
+        self.store_result_for_file_inner(graph, file, tag, partials, paths, None)
     }
 
-    /// Store the file graph.
-    ///
-    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
-    fn store_graph_for_file_inner(
-        conn: &Connection,
+    /// Store the result of a file index that did not find all paths, for example because path
+    /// finding timed out. The file is usable, but has status [`FileStatus::Partial`][] with the
+    /// given reason, so that it can be identified and re-indexed later.
+    pub fn store_partial_result_for_file<'a, IP>(
+        &mut self,
         graph: &StackGraph,
         file: Handle<File>,
         tag: &str,
-    ) -> Result<()> {
-        let file_str = graph[file].name();
-        copious_debugging!("--> Store graph for {}", file_str);
-        let mut stmt =
-            conn.prepare_cached("INSERT INTO graphs (file, tag, value) VALUES (?, ?, ?)")?;
-        let graph = serde::StackGraph::from_graph_filter(graph, &FileFilter(file));
-        let serialized = bincode::encode_to_vec(&graph, BINCODE_CONFIG)?;
-        stmt.execute((file_str, tag, &serialized))?;
+        partials: &mut PartialPaths,
+        paths: IP,
+        reason: &str,
+    ) -> Result<()>
+    where
+        IP: IntoIterator<Item = &'a PartialPath>,
+    {
+        self.store_result_for_file_inner(graph, file, tag, partials, paths, Some(reason))
+    }
+
+    fn store_result_for_file_inner<'a, IP>(
+        &mut self,
+        graph: &StackGraph,
+        file: Handle<File>,
+        tag: &str,
+        partials: &mut PartialPaths,
+        paths: IP,
+        partial: Option<&str>,
+    ) -> Result<()>
+    where
+        IP: IntoIterator<Item = &'a PartialPath>,
+    {
+        let path = Path::new(graph[file].name());
+        let tx = self.conn.transaction()?;
+        Self::clean_file_inner(&tx, &self.revision, &self.variant, path)?;
+        let entry = match Self::find_entry_inner(&tx, graph[file].name(), tag, &self.variant)? {
+            Some(entry) if is_entry_used(&tx, entry)? => {
+                // Other files have the same file content, and still use the data of the entry,
+                // so the file only adds a reference to it.
+                entry
+            }
+            Some(entry) => {
+                // No other file refers to the entry. We replace its data, instead of assuming it
+                // is identical, so that re-indexing a file always stores fresh data.
+                Self::clean_entry_inner(&tx, entry)?;
+                Self::update_graph_for_file_inner(&tx, graph, file, entry)?;
+                Self::store_entry_data_inner(&tx, graph, file, entry, partials, paths, partial)?;
+                entry
+            }
+            None => {
+                let entry = Self::store_graph_for_file_inner(&tx, graph, file, tag, &self.variant)?;
+                Self::store_entry_data_inner(&tx, graph, file, entry, partials, paths, partial)?;
+                entry
+            }
+        };
+        Self::store_file_entry_inner(
+            &tx,
+            &self.revision,
+            &self.variant,
+            graph[file].name(),
+            entry,
+        )?;
+        tx.commit()?;
         Ok(())
     }
 
-    /// Store the file partial paths.
+    /// Store the partial paths of a file, and whether they are incomplete, in the given entry.
     ///
     /// This is an inner method, which does not wrap individual SQL statements in a transaction.
-    fn store_partial_paths_for_file_inner<'a, IP>(
+    fn store_entry_data_inner<'a, IP>(
         conn: &Connection,
         graph: &StackGraph,
         file: Handle<File>,
+        entry: i64,
         partials: &mut PartialPaths,
         paths: IP,
+        partial: Option<&str>,
     ) -> Result<()>
     where
         IP: IntoIterator<Item = &'a PartialPath>,
     {
-        let file_str = graph[file].name();
-        let mut node_stmt =
-            conn.prepare_cached("INSERT INTO file_paths (file, local_id, value) VALUES (?, ?, ?)")?;
-        let mut root_stmt = conn.prepare_cached(
-            "INSERT INTO root_paths (file, symbol_stack, value) VALUES (?, ?, ?)",
-        )?;
-        #[cfg_attr(not(feature = "copious-debugging"), allow(unused))]
-        let mut node_path_count = 0usize;
-        #[cfg_attr(not(feature = "copious-debugging"), allow(unused))]
-        let mut root_path_count = 0usize;
-        for path in paths {
-            copious_debugging!(
-                "--> Add {} partial path {}",
-                file_str,
-                path.display(graph, partials)
-            );
-            let start_node = graph[path.start_node].id();
-            if start_node.is_root() {
-                copious_debugging!(
-                    " * Add as root path with symbol stack {}",
-                    path.symbol_stack_precondition.display(graph, partials),
-                );
-                let symbol_stack = path.symbol_stack_precondition.storage_key(graph, partials);
-                let path = serde::PartialPath::from_partial_path(graph, partials, path);
-                let serialized = bincode::encode_to_vec(&path, BINCODE_CONFIG)?;
-                root_stmt.execute((file_str, symbol_stack, serialized))?;
-                root_path_count += 1;
-            } else if start_node.is_in_file(file) {
-                copious_debugging!(
-                    " * Add as node path from node {}",
-                    path.start_node.display(graph),
-                );
-                let path = serde::PartialPath::from_partial_path(graph, partials, path);
-                let serialized = bincode::encode_to_vec(&path, BINCODE_CONFIG)?;
-                node_stmt.execute((file_str, path.start_node.local_id, serialized))?;
-                node_path_count += 1;
-            } else {
-                panic!(
-                    "added path {} must start in given file {} or at root",
-                    path.display(graph, partials),
-                    graph[file].name()
-                );
-            }
-            copious_debugging!(
-                " * Added {} node paths and {} root paths",
-                node_path_count,
-                root_path_count,
-            );
-        }
+        Self::store_partial_paths_for_file_inner(conn, graph, file, entry, partials, paths)?;
+        let mut stmt = conn.prepare_cached("UPDATE graphs SET partial = ? WHERE entry = ?")?;
+        stmt.execute((partial, entry))?;
         Ok(())
     }
 
-    /// Get the file's status in the database. If a tag is provided, it must match or the file
-    /// is reported missing.
-    pub fn status_for_file(&mut self, file: &str, tag: Option<&str>) -> Result<FileStatus> {
-        status_for_file(&self.conn, file, tag)
+    /// Make the file with the given tag part of the writer's revision, if the database already
+    /// contains a successfully indexed entry for it, for example because another revision or
+    /// repository contains the same file content. Returns whether the file was found, in which
+    /// case it does not need to be indexed.
+    pub fn reuse_stored_file(&mut self, file: &Path, tag: &str) -> Result<bool> {
+        let file = file.to_string_lossy();
+        let tx = self.conn.transaction()?;
+        let entry = match Self::find_entry_inner(&tx, &file, tag, &self.variant)? {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+        let current = {
+            let mut stmt = tx.prepare_cached(
+                "SELECT entry FROM files WHERE repo = ? AND commit_id = ? AND file = ? AND variant = ?",
+            )?;
+            stmt.query_row(
+                (
+                    &self.revision.repo,
+                    &self.revision.commit,
+                    &file,
+                    &self.variant,
+                ),
+                |r| r.get::<_, i64>(0),
+            )
+            .optional()?
+        };
+        if current == Some(entry) {
+            return Ok(true);
+        }
+        Self::clean_file_inner(&tx, &self.revision, &self.variant, Path::new(file.as_ref()))?;
+        Self::store_file_entry_inner(&tx, &self.revision, &self.variant, &file, entry)?;
+        tx.commit()?;
+        Ok(true)
     }
 
-    /// Convert this writer into a reader for the same database.
-    pub fn into_reader(self) -> SQLiteReader {
-        SQLiteReader {
-            conn: self.conn,
-            loaded_graphs: HashSet::new(),
-            loaded_node_paths: HashSet::new(),
-            loaded_root_paths: HashSet::new(),
-            graph: StackGraph::new(),
-            partials: PartialPaths::new(),
-            db: Database::new(),
-            stats: Stats::default(),
-        }
+    /// Returns statistics about how much file data is shared in this database.
+    pub fn deduplication_stats(&self) -> Result<DeduplicationStats> {
+        deduplication_stats(std::slice::from_ref(&self.conn))
     }
-}
 
-/// Reader to load stack graphs and partial paths from a SQLite database.
-pub struct SQLiteReader {
-    conn: Connection,
-    loaded_graphs: HashSet<String>,
-    loaded_node_paths: HashSet<Handle<Node>>,
-    loaded_root_paths: HashSet<String>,
-    graph: StackGraph,
-    partials: PartialPaths,
-    db: Database,
-    stats: Stats,
-}
+    /// Record in the checkpoint of the writer's revision that the file with the given tag was
+    /// completed by the current indexing run.  Should be called after the result or error for the
+    /// file is stored.
+    pub fn checkpoint_file(&mut self, file: &Path, tag: &str) -> Result<()> {
+        let file = file.to_string_lossy();
+        let mut stmt = self.conn.prepare_cached(
+            "INSERT OR REPLACE INTO checkpoint_files (repo, commit_id, file, tag) VALUES (?, ?, ?, ?)",
+        )?;
+        stmt.execute((&self.revision.repo, &self.revision.commit, &file, tag))?;
+        Ok(())
+    }
 
-impl SQLiteReader {
-    /// Open a file database.
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        if !path.as_ref().exists() {
-            return Err(StorageError::MissingDatabase(
-                path.as_ref().to_string_lossy().to_string(),
-            ));
+    /// Returns whether the checkpoint of the writer's revision records that the file with the
+    /// given tag was completed, and its result or error is still stored.  A resumed indexing run
+    /// can skip such files.
+    pub fn is_file_checkpointed(&mut self, file: &Path, tag: &str) -> Result<bool> {
+        let file = file.to_string_lossy();
+        if status_for_file(&self.conn, &self.revision, &file, Some(tag))? == FileStatus::Missing {
+            return Ok(false);
         }
-        let mut conn = Connection::open(path)?;
-        set_pragmas_and_functions(&conn)?;
-        check_version(&conn)?;
-        init_indexes(&mut conn)?;
-        Ok(Self {
-            conn,
-            loaded_graphs: HashSet::new(),
-            loaded_node_paths: HashSet::new(),
-            loaded_root_paths: HashSet::new(),
-            graph: StackGraph::new(),
-            partials: PartialPaths::new(),
-            db: Database::new(),
-            stats: Stats::default(),
-        })
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT 1 FROM checkpoint_files WHERE repo = ? AND commit_id = ? AND file = ? AND tag = ?",
+        )?;
+        let checkpointed = stmt
+            .query_row(
+                (&self.revision.repo, &self.revision.commit, &file, tag),
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        Ok(checkpointed)
     }
 
-    /// Clear all data that has been loaded into this reader instance.
-    /// After this call, all existing handles from this reader are invalid.
-    pub fn clear(&mut self) {
-        self.loaded_graphs.clear();
-        self.graph = StackGraph::new();
+    /// Clear the checkpoint of the writer's revision, which should be done when an indexing run
+    /// starts from scratch, or when it finishes.  Returns the number of files that were recorded
+    /// in the checkpoint.
+    pub fn clear_checkpoint(&mut self) -> Result<usize> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("DELETE FROM checkpoint_files WHERE repo = ? AND commit_id = ?")?;
+        let count = stmt.execute(self.revision.params())?;
+        Ok(count)
+    }
 
-        self.loaded_node_paths.clear();
-        self.loaded_root_paths.clear();
-        self.partials.clear();
-        self.db.clear();
+    /// Find the successfully indexed entry for the given file, tag, and variant.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn find_entry_inner(
+        conn: &Connection,
+        file: &str,
+        tag: &str,
+        variant: &str,
+    ) -> Result<Option<i64>> {
+        let mut stmt = conn.prepare_cached(
+            "SELECT entry FROM graphs WHERE file = ? AND tag = ? AND variant = ? AND error IS NULL",
+        )?;
+        Ok(stmt
+            .query_row([file, tag, variant], |r| r.get::<_, i64>(0))
+            .optional()?)
+    }
 
-        self.stats.clear();
+    /// Remove the partial paths of an entry, so that they can be stored again.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn clean_entry_inner(conn: &Connection, entry: i64) -> Result<()> {
+        for table in ["file_paths", "root_paths"] {
+            let mut stmt =
+                conn.prepare_cached(&format!("DELETE FROM {} WHERE entry = ?", table))?;
+            stmt.execute([entry])?;
+        }
+        Ok(())
     }
 
-    /// Clear path data that has been loaded into this reader instance.
-    /// After this call, all node handles remain valid, but all path data
-    /// is invalid.
-    pub fn clear_paths(&mut self) {
-        self.loaded_node_paths.clear();
-        self.loaded_root_paths.clear();
-        self.partials.clear();
-        self.db.clear();
+    /// Replace the file graph of an existing entry.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn update_graph_for_file_inner(
+        conn: &Connection,
+        graph: &StackGraph,
+        file: Handle<File>,
+        entry: i64,
+    ) -> Result<()> {
+        copious_debugging!("--> Update graph for {}", graph[file].name());
+        let mut stmt = conn.prepare_cached(
+            "UPDATE graphs SET value = ?, metadata = ?, file_group = ?, indexed_at = ? WHERE entry = ?",
+        )?;
+        let graph = serde::StackGraph::from_graph_filter(graph, &FileFilter(file));
+        let metadata = bincode::encode_to_vec(&graph.metadata, BINCODE_CONFIG)?;
+        let file_group = graph.metadata.data.get(FILE_GROUP_METADATA).cloned();
+        let serialized = encode_graph(conn, graph)?;
+        stmt.execute((
+            &serialized,
+            &metadata,
+            file_group,
+            now_as_unix_seconds(),
+            entry,
+        ))?;
+        Ok(())
+    }
 
-        self.stats.clear_paths();
+    /// Store the file graph, and return the entry it is stored as.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn store_graph_for_file_inner(
+        conn: &Connection,
+        graph: &StackGraph,
+        file: Handle<File>,
+        tag: &str,
+        variant: &str,
+    ) -> Result<i64> {
+        let file_str = graph[file].name();
+        copious_debugging!("--> Store graph for {}", file_str);
+        let mut stmt = conn.prepare_cached(
+            "INSERT INTO graphs (file, tag, value, metadata, file_group, variant, indexed_at) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )?;
+        let graph = serde::StackGraph::from_graph_filter(graph, &FileFilter(file));
+        let metadata = bincode::encode_to_vec(&graph.metadata, BINCODE_CONFIG)?;
+        let file_group = graph.metadata.data.get(FILE_GROUP_METADATA).cloned();
+        let serialized = encode_graph(conn, graph)?;
+        stmt.execute((
+            file_str,
+            tag,
+            &serialized,
+            &metadata,
+            file_group,
+            variant,
+            now_as_unix_seconds(),
+        ))?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Store the file partial paths.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn store_partial_paths_for_file_inner<'a, IP>(
+        conn: &Connection,
+        graph: &StackGraph,
+        file: Handle<File>,
+        entry: i64,
+        partials: &mut PartialPaths,
+        paths: IP,
+    ) -> Result<()>
+    where
+        IP: IntoIterator<Item = &'a PartialPath>,
+    {
+        #[cfg_attr(not(feature = "copious-debugging"), allow(unused))]
+        let file_str = graph[file].name();
+        let mut node_stmt = conn
+            .prepare_cached("INSERT INTO file_paths (entry, local_id, value) VALUES (?, ?, ?)")?;
+        let mut root_stmt = conn.prepare_cached(
+            "INSERT INTO root_paths (entry, symbol_stack, value) VALUES (?, ?, ?)",
+        )?;
+        #[cfg_attr(not(feature = "copious-debugging"), allow(unused))]
+        let mut node_path_count = 0usize;
+        #[cfg_attr(not(feature = "copious-debugging"), allow(unused))]
+        let mut root_path_count = 0usize;
+        for path in paths {
+            copious_debugging!(
+                "--> Add {} partial path {}",
+                file_str,
+                path.display(graph, partials)
+            );
+            let start_node = graph[path.start_node].id();
+            if start_node.is_root() {
+                copious_debugging!(
+                    " * Add as root path with symbol stack {}",
+                    path.symbol_stack_precondition.display(graph, partials),
+                );
+                let symbol_stack = path.symbol_stack_precondition.storage_key(graph, partials);
+                let path = serde::PartialPath::from_partial_path(graph, partials, path);
+                let serialized = bincode::encode_to_vec(&path, BINCODE_CONFIG)?;
+                root_stmt.execute((entry, symbol_stack, serialized))?;
+                root_path_count += 1;
+            } else if start_node.is_in_file(file) {
+                copious_debugging!(
+                    " * Add as node path from node {}",
+                    path.start_node.display(graph),
+                );
+                let path = serde::PartialPath::from_partial_path(graph, partials, path);
+                let serialized = bincode::encode_to_vec(&path, BINCODE_CONFIG)?;
+                node_stmt.execute((entry, path.start_node.local_id, serialized))?;
+                node_path_count += 1;
+            } else {
+                return Err(path_outside_file(graph, partials, path, file));
+            }
+            copious_debugging!(
+                " * Added {} node paths and {} root paths",
+                node_path_count,
+                root_path_count,
+            );
+        }
+        Ok(())
     }
 
     /// Get the file's status in the database. If a tag is provided, it must match or the file
     /// is reported missing.
-    pub fn status_for_file<T: AsRef<str>>(
-        &mut self,
-        file: &str,
-        tag: Option<T>,
-    ) -> Result<FileStatus> {
-        status_for_file(&self.conn, file, tag)
+    pub fn status_for_file(&mut self, file: &str, tag: Option<&str>) -> Result<FileStatus> {
+        status_for_file(&self.conn, &self.revision, file, tag)
     }
 
-    /// Returns a [`Files`][] value that can be used to iterate over all files in the database.
-    pub fn list_all<'a>(&'a mut self) -> Result<Files<'a, ()>> {
-        self.conn
-            .prepare("SELECT file, tag, error FROM graphs")
-            .map(|stmt| Files(stmt, ()))
-            .map_err(|e| e.into())
+    /// Returns a [`Files`][] value that can be used to iterate over all files in the database
+    /// for which indexing failed.
+    pub fn failed_files<'a>(&'a mut self) -> Result<Files<'a, [String; 2]>> {
+        failed_files(std::slice::from_ref(&self.conn), &self.revision)
     }
 
-    /// Returns a [`Files`][] value that can be used to iterate over all descendants of a
-    /// file or directory in the database.
-    pub fn list_file_or_directory<'a>(
-        &'a self,
-        file_or_directory: &Path,
-    ) -> Result<Files<'a, [String; 1]>> {
-        Self::list_file_or_directory_inner(&self.conn, file_or_directory)
+    /// Returns a [`Files`][] value that can be used to iterate over all files in the database
+    /// that were partially indexed.
+    pub fn partial_files<'a>(&'a mut self) -> Result<Files<'a, [String; 2]>> {
+        partial_files(std::slice::from_ref(&self.conn), &self.revision)
     }
 
-    fn list_file_or_directory_inner<'a>(
-        conn: &'a Connection,
-        file_or_directory: &Path,
-    ) -> Result<Files<'a, [String; 1]>> {
-        let file_or_directory = file_or_directory.to_string_lossy().to_string();
-        conn.prepare("SELECT file, tag, error FROM graphs WHERE path_descendant_of(file, ?)")
-            .map(|stmt| Files(stmt, [file_or_directory]))
-            .map_err(|e| e.into())
+    /// Returns a reader of the writer's revision, which is used as the source of candidate paths
+    /// when stitching on behalf of the writer. The reader has its own connection to the writer's
+    /// database, and selects the default variants of files, because stitched paths are only used
+    /// by readers of the default variants.
+    fn stitching_reader(&self) -> Result<SQLiteReader> {
+        let conn = Connection::open(&self.path)?;
+        set_pragmas_and_functions(&conn)?;
+        init_views(&conn)?;
+        Ok(SQLiteReader::from_connections(
+            vec![conn],
+            self.revision.clone(),
+        ))
     }
 
-    /// Ensure the graph for the given file is loaded.
-    pub fn load_graph_for_file(&mut self, file: &str) -> Result<Handle<File>> {
-        Self::load_graph_for_file_inner(
-            file,
-            &mut self.graph,
-            &mut self.loaded_graphs,
-            &self.conn,
-            &mut self.stats,
-        )
-    }
+    /// Pre-stitch the paths from the root for the symbol stacks that references in the writer's
+    /// revision look up most often, such as the names of widely imported modules. At most
+    /// `max_symbol_stacks` symbol stacks are pre-stitched. The resulting paths lead from the root
+    /// all the way to definitions, and are stored with the revision. Readers use them instead of
+    /// stitching the individual root paths for these symbol stacks at query time.
+    ///
+    /// The dependencies of the pre-stitched paths for every symbol stack are stored with them. When
+    /// files of the revision are stored or cleaned later, only the pre-stitched paths that depend
+    /// on them are removed. Any pre-stitched paths of the revision are replaced by this call.
+    /// Returns the number of stored paths.
+    pub fn prestitch_root_paths(
+        &mut self,
+        max_symbol_stacks: usize,
+        config: StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        remove_prestitched_paths(&tx, &self.revision)?;
+        let symbol_stacks =
+            Self::popular_root_symbol_stacks_inner(&tx, &self.revision, max_symbol_stacks)?;
+        tx.commit()?;
+        if symbol_stacks.is_empty() {
+            return Ok(0);
+        }
 
-    fn load_graph_for_file_inner(
-        file: &str,
-        graph: &mut StackGraph,
-        loaded_graphs: &mut HashSet<String>,
-        conn: &Connection,
-        stats: &mut Stats,
-    ) -> Result<Handle<File>> {
-        copious_debugging!("--> Load graph for {}", file);
-        if !loaded_graphs.insert(file.to_string()) {
-            copious_debugging!(" * Already loaded");
-            stats.file_cached += 1;
-            return Ok(graph.get_file(file).expect("loaded file to exist"));
+        let symbol_stacks = self.stitching_reader()?.prestitch_root_paths(
+            &symbol_stacks,
+            config,
+            cancellation_flag,
+        )?;
+
+        let tx = self.conn.transaction()?;
+        let mut count = 0usize;
+        {
+            let mut symbol_stack_stmt = tx.prepare_cached(
+                "INSERT INTO prestitched_symbol_stacks (repo, commit_id, symbol_stack) VALUES (?, ?, ?)",
+            )?;
+            let mut path_stmt = tx.prepare_cached(
+                "INSERT INTO prestitched_paths (symbol_stack, value) VALUES (?, ?)",
+            )?;
+            let mut dependency_stmt = tx.prepare_cached(
+                "INSERT INTO prestitched_dependencies (symbol_stack, dependency_file, root_pattern) VALUES (?, ?, ?)",
+            )?;
+            for (symbol_stack, paths, dependencies) in symbol_stacks {
+                symbol_stack_stmt.execute((
+                    &self.revision.repo,
+                    &self.revision.commit,
+                    &symbol_stack,
+                ))?;
+                let id = tx.last_insert_rowid();
+                for value in paths {
+                    path_stmt.execute((id, value))?;
+                    count += 1;
+                }
+                for (file, root_pattern) in dependencies.rows() {
+                    dependency_stmt.execute((id, file, root_pattern))?;
+                }
+            }
         }
-        copious_debugging!(" * Load from database");
-        stats.file_loads += 1;
-        let mut stmt = conn.prepare_cached("SELECT value FROM graphs WHERE file = ?")?;
-        let value = stmt.query_row([file], |row| row.get::<_, Vec<u8>>(0))?;
-        let (file_graph, _): (serde::StackGraph, usize) =
-            bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
-        file_graph.load_into(graph)?;
-        Ok(graph.get_file(file).expect("loaded file to exist"))
+        tx.commit()?;
+        Ok(count)
     }
 
-    pub fn load_graphs_for_file_or_directory(
+    /// Resolve all references in the given files of the writer's revision, and store the complete
+    /// paths from each reference to its definitions in the database. This is meant for hot files,
+    /// whose references are queried often. Readers of a single database serve queries for these
+    /// references from the stored paths, see [`SQLiteReader::load_resolved_paths`][], instead of
+    /// stitching paths at query time.
+    ///
+    /// Resolved references depend on other files of the revision. Their dependencies are stored
+    /// with them, and a resolved reference is removed when a file it depends on is stored or
+    /// cleaned. Returns the number of stored references.
+    pub fn resolve_references_in_files<I, P>(
         &mut self,
-        file_or_directory: &Path,
+        files: I,
+        config: StitcherConfig,
         cancellation_flag: &dyn CancellationFlag,
-    ) -> Result<()> {
-        for file in Self::list_file_or_directory_inner(&self.conn, file_or_directory)?.try_iter()? {
-            cancellation_flag.check("loading graphs")?;
-            let file = file?;
-            Self::load_graph_for_file_inner(
-                &file.path.to_string_lossy(),
-                &mut self.graph,
-                &mut self.loaded_graphs,
-                &self.conn,
-                &mut self.stats,
+    ) -> Result<usize>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let files = files
+            .into_iter()
+            .map(|file| file.as_ref().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+
+        let references = self.stitching_reader()?.resolve_references_in_files(
+            &files,
+            config,
+            cancellation_flag,
+        )?;
+
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT OR REPLACE INTO resolved_references (repo, commit_id, file, local_id, value)
+                 VALUES (?, ?, ?, ?, ?)",
+            )?;
+            let mut remove_dependencies_stmt = tx.prepare_cached(
+                "DELETE FROM resolved_reference_dependencies
+                 WHERE repo = ? AND commit_id = ? AND file = ? AND local_id = ?",
             )?;
+            let mut dependency_stmt = tx.prepare_cached(
+                "INSERT INTO resolved_reference_dependencies
+                 (repo, commit_id, file, local_id, dependency_file, root_pattern)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+            )?;
+            for (file, local_id, value, dependencies) in &references {
+                let key = (&self.revision.repo, &self.revision.commit, file, local_id);
+                stmt.execute((key.0, key.1, key.2, key.3, value))?;
+                remove_dependencies_stmt.execute(key)?;
+                for (dependency_file, root_pattern) in dependencies.rows() {
+                    dependency_stmt.execute((
+                        key.0,
+                        key.1,
+                        key.2,
+                        key.3,
+                        dependency_file,
+                        root_pattern,
+                    ))?;
+                }
+            }
         }
-        Ok(())
+        tx.commit()?;
+        Ok(references.len())
     }
 
-    /// Ensure the paths starting a the given node are loaded.
-    fn load_paths_for_node(
+    /// Seal the files of the writer's revision into a database for the given library, which is
+    /// created at the given path. The revision is expected to contain a single version of a
+    /// dependency package, such as a node_modules package or a crate, and nothing else.
+    ///
+    /// The library only contains summaries of the package: the paths from the root through the
+    /// package, stitched until they reach a definition or leave the package through the root
+    /// again. The graphs of the library files only contain the nodes these paths refer to, and no
+    /// edges. Applications link libraries into their readers with [`SQLiteReader::with_library`][],
+    /// so that the paths of a dependency are stitched once, instead of for every application
+    /// that uses it. Paths that leave the package by jumping to a scope of the caller are not
+    /// summarized. Library databases are read-only, and cannot be opened by writers.
+    ///
+    /// Returns the number of stored summary paths.
+    pub fn seal_library<P: AsRef<Path>>(
         &mut self,
-        node: Handle<Node>,
+        library: &Library,
+        path: P,
+        config: StitcherConfig,
         cancellation_flag: &dyn CancellationFlag,
-    ) -> Result<()> {
-        copious_debugging!(" * Load extensions from node {}", node.display(&self.graph));
-        if !self.loaded_node_paths.insert(node) {
-            copious_debugging!("   > Already loaded");
-            self.stats.node_path_cached += 1;
-            return Ok(());
+    ) -> Result<usize> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Err(StorageError::Io(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("library database {} already exists", path.display()),
+            )));
         }
-        self.stats.node_path_loads += 1;
-        let id = self.graph[node].id();
-        let file = id.file().expect("file node required");
-        let file = self.graph[file].name();
-        let mut stmt = self
-            .conn
-            .prepare_cached("SELECT file,value from file_paths WHERE file = ? AND local_id = ?")?;
-        let paths = stmt.query_map((file, id.local_id()), |row| {
-            let file = row.get::<_, String>(0)?;
-            let value = row.get::<_, Vec<u8>>(1)?;
-            Ok((file, value))
-        })?;
-        #[cfg_attr(not(feature = "copious-debugging"), allow(unused))]
+
+        let files = self
+            .stitching_reader()?
+            .summarize_library(config, cancellation_flag)?;
+
+        let mut writer = SQLiteWriter::open(path)?;
+        let tx = writer.conn.transaction()?;
         let mut count = 0usize;
-        for path in paths {
-            cancellation_flag.check("loading node paths")?;
-            let (file, value) = path?;
-            Self::load_graph_for_file_inner(
-                &file,
-                &mut self.graph,
-                &mut self.loaded_graphs,
-                &self.conn,
-                &mut self.stats,
+        {
+            let mut graph_stmt = tx.prepare_cached(
+                "INSERT INTO graphs (file, tag, value, metadata, indexed_at) VALUES (?, ?, ?, ?, ?)",
+            )?;
+            let mut root_stmt = tx.prepare_cached(
+                "INSERT INTO root_paths (entry, symbol_stack, value) VALUES (?, ?, ?)",
             )?;
+            for file in files {
+                let metadata = bincode::encode_to_vec(&file.graph.metadata, BINCODE_CONFIG)?;
+                let value = encode_graph(&tx, file.graph)?;
+                graph_stmt.execute((
+                    &file.file,
+                    &file.tag,
+                    &value,
+                    &metadata,
+                    now_as_unix_seconds(),
+                ))?;
+                let entry = tx.last_insert_rowid();
+                Self::store_file_entry_inner(&tx, &Revision::default(), "", &file.file, entry)?;
+                for (symbol_stack, value) in file.root_paths {
+                    root_stmt.execute((entry, symbol_stack, value))?;
+                    count += 1;
+                }
+            }
+        }
+        tx.execute(
+            "INSERT INTO library (name, version) VALUES (?, ?)",
+            [&library.name, &library.version],
+        )?;
+        tx.commit()?;
+        Ok(count)
+    }
+
+    /// Find the symbol stacks that are most often looked up from the root, by counting the stored
+    /// paths of the revision that end in the root node, per symbol stack.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn popular_root_symbol_stacks_inner(
+        conn: &Connection,
+        revision: &Revision,
+        max_symbol_stacks: usize,
+    ) -> Result<Vec<Vec<String>>> {
+        let mut stmt = conn.prepare_cached(
+            "SELECT p.value FROM file_paths p JOIN files f ON f.entry = p.entry
+             WHERE f.repo = ? AND f.commit_id = ? AND f.variant = ''",
+        )?;
+        let mut demand = HashMap::<Vec<String>, usize>::new();
+        for value in stmt.query_map(revision.params(), |r| r.get::<_, Vec<u8>>(0))? {
             let (path, _): (serde::PartialPath, usize) =
-                bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
-            let path = path.to_partial_path(&mut self.graph, &mut self.partials)?;
-            copious_debugging!(
-                "   > Loaded {}",
-                path.display(&self.graph, &mut self.partials)
-            );
-            self.db
-                .add_partial_path(&self.graph, &mut self.partials, path);
-            count += 1;
+                bincode::decode_from_slice(&value?, BINCODE_CONFIG)?;
+            match path.unscoped_root_postcondition() {
+                Some(symbols) if !symbols.is_empty() => {
+                    let symbols = symbols.into_iter().map(String::from).collect();
+                    *demand.entry(symbols).or_default() += 1;
+                }
+                _ => {}
+            }
         }
-        copious_debugging!("   > Loaded {}", count);
-        Ok(())
+        let mut demand = demand.into_iter().collect::<Vec<_>>();
+        demand.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+        Ok(demand
+            .into_iter()
+            .take(max_symbol_stacks)
+            .map(|(symbols, _)| symbols)
+            .collect())
     }
 
-    /// Ensure the paths starting at the root and matching the given symbol stack are loaded.
-    fn load_paths_for_root(
-        &mut self,
-        symbol_stack: PartialSymbolStack,
-        cancellation_flag: &dyn CancellationFlag,
-    ) -> Result<()> {
-        copious_debugging!(
-            " * Load extensions from root with symbol stack {}",
-            symbol_stack.display(&self.graph, &mut self.partials)
+    /// Convert this writer into a reader for the same database and revision. The reader selects
+    /// the default variants of files, see [`SQLiteReader::with_variants`][] to select others.
+    pub fn into_reader(self) -> SQLiteReader {
+        select_default_variants(std::slice::from_ref(&self.conn))
+            .expect("variants to be selectable on an idle connection");
+        SQLiteReader::from_connections(vec![self.conn], self.revision)
+    }
+}
+
+/// A library, which is a version of a dependency package that is stored in a library database.
+/// See [`SQLiteWriter::seal_library`][].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Library {
+    pub name: String,
+    pub version: String,
+}
+
+impl Library {
+    pub fn new<N: Into<String>, V: Into<String>>(name: N, version: V) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Library {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}", self.name, self.version)
+    }
+}
+
+/// Decides which of the linked libraries paths can continue into, when several versions of a
+/// dependency are linked into a [`SQLiteReader`][]. The selector is consulted when a path leaves
+/// a file through the root, and the reader offers the paths from the root into a library as
+/// candidates. The querying file is the file the path was in before it reached the root, so that
+/// paths through a library select the dependencies of that library. Paths that reach the root
+/// without leaving a file, and candidates from the reader's own databases, are not filtered.
+pub trait DependencySelector: Send + Sync {
+    /// Returns whether paths leaving `querying_file` through the root can continue into the
+    /// given library.
+    fn select(&self, querying_file: &str, library: &Library) -> bool;
+}
+
+/// A dependency selector that uses the dependencies declared by package manifests. Every
+/// package is identified by its directory, and declares the versions of the libraries it depends
+/// on. Files belong to the package with the closest directory that contains them. Paths from
+/// files outside of any package, and paths into libraries that their package does not declare,
+/// can continue into all versions of a library.
+#[derive(Clone, Debug, Default)]
+pub struct ManifestDependencySelector {
+    packages: BTreeMap<PathBuf, HashMap<String, String>>,
+}
+
+impl ManifestDependencySelector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the package in the given directory, with the given library names and versions it
+    /// depends on. The dependencies of a package that was added before are replaced.
+    pub fn add_package<P, I, N, V>(&mut self, directory: P, dependencies: I)
+    where
+        P: Into<PathBuf>,
+        I: IntoIterator<Item = (N, V)>,
+        N: Into<String>,
+        V: Into<String>,
+    {
+        self.packages.insert(
+            directory.into(),
+            dependencies
+                .into_iter()
+                .map(|(name, version)| (name.into(), version.into()))
+                .collect(),
         );
-        let mut stmt = self.conn.prepare_cached(
-            "SELECT file,value from root_paths WHERE symbol_stack LIKE ? ESCAPE ?",
-        )?;
-        let (symbol_stack_patterns, escape) =
-            symbol_stack.storage_key_patterns(&self.graph, &mut self.partials);
-        for symbol_stack in symbol_stack_patterns {
-            copious_debugging!(
-                " * Load extensions from root with prefix symbol stack {}",
-                symbol_stack
-            );
-            if !self.loaded_root_paths.insert(symbol_stack.clone()) {
-                copious_debugging!("   > Already loaded");
-                self.stats.root_path_cached += 1;
-                continue;
+    }
+}
+
+impl DependencySelector for ManifestDependencySelector {
+    fn select(&self, querying_file: &str, library: &Library) -> bool {
+        let package = Path::new(querying_file)
+            .ancestors()
+            .skip(1)
+            .find_map(|directory| self.packages.get(directory));
+        match package.and_then(|dependencies| dependencies.get(&library.name)) {
+            Some(version) => version == &library.version,
+            None => true,
+        }
+    }
+}
+
+/// A file of a library database, with its summary paths.
+struct LibraryFile {
+    file: String,
+    tag: String,
+    graph: serde::StackGraph,
+    root_paths: Vec<(String, Vec<u8>)>,
+}
+
+/// Filter that includes the given nodes of a single file, and no edges.
+struct LibraryFileFilter<'a> {
+    file: Handle<File>,
+    nodes: &'a HashSet<NodeID>,
+}
+
+impl Filter for LibraryFileFilter<'_> {
+    fn include_file(&self, _graph: &StackGraph, file: &Handle<File>) -> bool {
+        *file == self.file
+    }
+
+    fn include_node(&self, graph: &StackGraph, node: &Handle<Node>) -> bool {
+        self.nodes.contains(&graph[*node].id())
+    }
+
+    fn include_edge(
+        &self,
+        _graph: &StackGraph,
+        _source: &Handle<Node>,
+        _sink: &Handle<Node>,
+    ) -> bool {
+        false
+    }
+
+    fn include_partial_path(
+        &self,
+        _graph: &StackGraph,
+        _paths: &PartialPaths,
+        _path: &PartialPath,
+    ) -> bool {
+        true
+    }
+}
+
+/// Reader to load stack graphs and partial paths from a SQLite database, or from a set of database
+/// shards that are queried together.
+pub struct SQLiteReader {
+    conns: Connections,
+    libraries: Connections,
+    linked_libraries: Vec<Library>,
+    library_paths: HashMap<Handle<PartialPath>, usize>,
+    dependency_selector: Option<Arc<dyn DependencySelector>>,
+    pool: Option<Arc<PoolConnections>>,
+    revision: Revision,
+    variants: Vec<String>,
+    loaded_graphs: HashSet<String>,
+    loaded_node_paths: HandleSet<Node>,
+    loaded_root_paths: HashSet<String>,
+    prestitched_root_paths: HashMap<String, Option<Vec<Handle<PartialPath>>>>,
+    dependencies: Option<ArtifactDependencies>,
+    observer: Option<Arc<dyn QueryObserver>>,
+    visibility_policy: Option<Arc<dyn VisibilityPolicy>>,
+    provenance: Option<HashMap<i64, Arc<PathProvenance>>>,
+    graph: StackGraph,
+    partials: PartialPaths,
+    db: Database,
+    stats: Stats,
+}
+
+impl SQLiteReader {
+    /// Open a file database.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_shards(std::iter::once(path))
+    }
+
+    /// Open a database consisting of the given shards. Queries are answered using the data from
+    /// all shards. Every file must be stored in at most one shard.
+    pub fn open_shards<I, P>(paths: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut conns = Vec::new();
+        for path in paths {
+            conns.push(Self::open_connection(path.as_ref())?);
+        }
+        if conns.is_empty() {
+            return Err(StorageError::MissingDatabase("no shards".to_string()));
+        }
+        Ok(Self::from_connections(conns, Revision::default()))
+    }
+
+    fn from_connections(conns: Vec<Connection>, revision: Revision) -> Self {
+        Self {
+            conns: Connections::new(conns),
+            libraries: Connections::new(Vec::new()),
+            linked_libraries: Vec::new(),
+            library_paths: HashMap::new(),
+            dependency_selector: None,
+            pool: None,
+            revision,
+            variants: Vec::new(),
+            loaded_graphs: HashSet::new(),
+            loaded_node_paths: HandleSet::new(),
+            loaded_root_paths: HashSet::new(),
+            prestitched_root_paths: HashMap::new(),
+            dependencies: None,
+            observer: None,
+            visibility_policy: None,
+            provenance: None,
+            graph: StackGraph::new(),
+            partials: PartialPaths::new(),
+            db: Database::new(),
+            stats: Stats::default(),
+        }
+    }
+
+    /// Link the library database at the given path, which was created by
+    /// [`SQLiteWriter::seal_library`][], into this reader. Paths from the root are loaded from
+    /// the linked libraries as well, so that queries resolve into the library without stitching
+    /// its paths. Libraries are opened read-only, and are not listed with the files of the
+    /// reader. The files of a library must not be stored in the reader's databases as well.
+    ///
+    /// Several versions of the same library can be linked, in which case a
+    /// [`DependencySelector`][] decides which of them paths continue into, see
+    /// [`with_dependency_selector`][Self::with_dependency_selector]. The files of different
+    /// versions must have different paths.
+    ///
+    /// Pre-stitched paths and resolved references do not include the definitions in libraries,
+    /// and are therefore not used by readers with linked libraries. Any data that was already
+    /// loaded is cleared.
+    pub fn with_library<P: AsRef<Path>>(mut self, path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let conn = Self::open_connection(path)?;
+        let library = read_library(&conn)?
+            .ok_or_else(|| StorageError::NotALibrary(path.to_string_lossy().to_string()))?;
+        conn.execute_batch("PRAGMA query_only = true;")?;
+        self.clear();
+        self.libraries.get_mut().push(conn);
+        self.linked_libraries.push(library);
+        Ok(self)
+    }
+
+    /// Returns the libraries linked into this reader.
+    pub fn libraries(&self) -> &[Library] {
+        &self.linked_libraries
+    }
+
+    /// Use the given selector to decide which of the linked libraries paths can continue into.
+    /// Without a selector, paths continue into all linked libraries.
+    pub fn with_dependency_selector(
+        mut self,
+        dependency_selector: Arc<dyn DependencySelector>,
+    ) -> Self {
+        self.dependency_selector = Some(dependency_selector);
+        self
+    }
+
+    /// Use this reader for the given revision. Only files of this revision are loaded. Any data
+    /// that was already loaded is cleared.
+    pub fn with_revision(mut self, revision: Revision) -> Self {
+        self.clear();
+        self.revision = revision;
+        self
+    }
+
+    /// Returns the revision this reader operates on.
+    pub fn revision(&self) -> &Revision {
+        &self.revision
+    }
+
+    /// Use this reader for the given variants of files, such as the names of the active build
+    /// configurations, in order of preference. For every file, the first of the variants that
+    /// exists for the file is loaded, or the default variant if none of them does. Any data that
+    /// was already loaded is cleared.
+    ///
+    /// Pre-stitched paths are computed for the default variants, and are not used by readers that
+    /// select other variants.
+    pub fn with_variants<I, V>(mut self, variants: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        self.clear();
+        self.variants = variants.into_iter().map(Into::into).collect();
+        let mut selected = self.variants.clone();
+        selected.push(String::new());
+        for conn in self.conns.get_mut().iter() {
+            select_variants(conn, selected.clone())?;
+        }
+        Ok(self)
+    }
+
+    /// Returns the variants this reader selects, in order of preference, not including the
+    /// default variant.
+    pub fn variants(&self) -> &[String] {
+        &self.variants
+    }
+
+    /// Notify the given observer of the files and paths this reader loads. The observer is also
+    /// notified of stitching events when this reader is used as the candidates of a stitcher.
+    pub fn with_observer(mut self, observer: Arc<dyn QueryObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Only let paths traverse non-public edges if the given policy allows it, when this reader
+    /// is used as the candidates of a stitcher.
+    pub fn with_visibility_policy(mut self, visibility_policy: Arc<dyn VisibilityPolicy>) -> Self {
+        self.visibility_policy = Some(visibility_policy);
+        self
+    }
+
+    /// Record the [provenance][Database::provenance] of the paths this reader loads, so that
+    /// stitching traces and clients can tell which index produced a path.  Pre-stitched paths,
+    /// which combine paths from several files, have no provenance.
+    pub fn with_collect_provenance(mut self, collect_provenance: bool) -> Self {
+        self.provenance = collect_provenance.then(HashMap::new);
+        self
+    }
+
+    /// Returns the provenance of the entry, which is read from the database once per entry.
+    fn provenance_for_entry(
+        conns: &[Connection],
+        provenance: &mut HashMap<i64, Arc<PathProvenance>>,
+        entry: i64,
+    ) -> Result<Arc<PathProvenance>> {
+        if let Some(provenance) = provenance.get(&entry) {
+            return Ok(provenance.clone());
+        }
+        let mut result = None;
+        for conn in conns {
+            let mut stmt = conn.prepare_cached(
+                "SELECT file, tag, indexed_at, metadata FROM graphs WHERE entry = ?",
+            )?;
+            result = stmt
+                .query_row([entry], |row| {
+                    let metadata = metadata_from_column(row.get_ref(3)?)?;
+                    Ok(PathProvenance {
+                        file: row.get(0)?,
+                        tag: Some(row.get(1)?),
+                        indexed_at: row.get::<_, Option<i64>>(2)?.map(|t| t as u64),
+                        rules_version: metadata.get(RULES_VERSION_METADATA).cloned(),
+                    })
+                })
+                .optional()?;
+            if result.is_some() {
+                break;
             }
-            self.stats.root_path_loads += 1;
-            let paths = stmt.query_map([symbol_stack, escape.clone()], |row| {
-                let file = row.get::<_, String>(0)?;
-                let value = row.get::<_, Vec<u8>>(1)?;
-                Ok((file, value))
-            })?;
-            #[cfg_attr(not(feature = "copious-debugging"), allow(unused))]
-            let mut count = 0usize;
-            for path in paths {
-                cancellation_flag.check("loading root paths")?;
-                let (file, value) = path?;
-                Self::load_graph_for_file_inner(
-                    &file,
-                    &mut self.graph,
-                    &mut self.loaded_graphs,
-                    &self.conn,
-                    &mut self.stats,
-                )?;
-                let (path, _): (serde::PartialPath, usize) =
-                    bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
-                let path = path.to_partial_path(&mut self.graph, &mut self.partials)?;
-                copious_debugging!(
-                    "   > Loaded {}",
-                    path.display(&self.graph, &mut self.partials)
-                );
-                self.db
-                    .add_partial_path(&self.graph, &mut self.partials, path);
-                count += 1;
+        }
+        let result = Arc::new(result.unwrap_or_default());
+        provenance.insert(entry, result.clone());
+        Ok(result)
+    }
+
+    /// Returns statistics about how much file data is shared in this database.
+    pub fn deduplication_stats(&self) -> Result<DeduplicationStats> {
+        deduplication_stats(&self.conns.lock())
+    }
+
+    /// Returns aggregate statistics about the files of this reader's revision, such as the
+    /// number of nodes, edges, and paths stored for each file, and how often each symbol occurs.
+    pub fn database_stats(&self) -> Result<DatabaseStats> {
+        let mut stats = DatabaseStats::default();
+        for conn in self.conns.lock().iter() {
+            stats.size_on_disk += conn.query_row(
+                "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+                [],
+                |r| r.get::<_, u64>(0),
+            )?;
+            let mut stmt = conn.prepare_cached(
+                "SELECT f.file, g.tag, g.error, g.partial, g.metadata, g.value,
+                        (SELECT COUNT(*) FROM file_paths p WHERE p.entry = g.entry),
+                        (SELECT COUNT(*) FROM root_paths p WHERE p.entry = g.entry),
+                        LENGTH(g.value)
+                          + IFNULL((SELECT SUM(LENGTH(p.value)) FROM file_paths p WHERE p.entry = g.entry), 0)
+                          + IFNULL((SELECT SUM(LENGTH(p.value)) FROM root_paths p WHERE p.entry = g.entry), 0)
+                 FROM selected_files f JOIN graphs g ON g.entry = f.entry
+                 WHERE f.repo = ? AND f.commit_id = ?",
+            )?;
+            let mut rows = stmt.query((&self.revision.repo, &self.revision.commit))?;
+            while let Some(row) = rows.next()? {
+                let entry = file_entry_from_row(row)?;
+                let value = row.get::<_, Vec<u8>>(5)?;
+                let file_graph = decode_graph(conn, &value)?;
+                let mut node_count = 0;
+                for node in &file_graph.nodes.data {
+                    if !matches!(
+                        node,
+                        serde::Node::Root { .. } | serde::Node::JumpToScope { .. }
+                    ) {
+                        node_count += 1;
+                    }
+                    match node {
+                        serde::Node::PopScopedSymbol { symbol, .. }
+                        | serde::Node::PopSymbol { symbol, .. }
+                        | serde::Node::PushScopedSymbol { symbol, .. }
+                        | serde::Node::PushSymbol { symbol, .. } => {
+                            *stats.symbols.entry(symbol.clone()).or_default() += 1;
+                        }
+                        _ => {}
+                    }
+                }
+                stats.files.push(FileStats {
+                    path: entry.path,
+                    tag: entry.tag,
+                    status: entry.status,
+                    node_count,
+                    edge_count: file_graph.edges.data.len(),
+                    node_path_count: row.get(6)?,
+                    root_path_count: row.get(7)?,
+                    stored_size: row.get(8)?,
+                });
             }
-            copious_debugging!("   > Loaded {}", count);
+        }
+        stats.files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(stats)
+    }
+
+    /// Starts reading from a consistent snapshot of the database, so that a batch of queries sees
+    /// the same data, even if files are written to the database in the meantime.  The returned
+    /// [`ReadSnapshot`][] dereferences to this reader, and is used to run the queries.  The
+    /// snapshot is released when it is ended or dropped, after which the reader sees the latest
+    /// data again.
+    ///
+    /// All data loaded into this reader is cleared first, so that the graphs and paths used by
+    /// the queries all come from the snapshot.  After this call, all existing handles from this
+    /// reader are invalid.
+    pub fn begin_read(&mut self) -> Result<ReadSnapshot<'_>> {
+        self.clear();
+        if let Err(err) = Self::begin_read_inner(self.conns.get_mut()) {
+            let _ = Self::end_read_inner(self.conns.get_mut());
+            return Err(err);
+        }
+        Ok(ReadSnapshot { reader: self })
+    }
+
+    fn begin_read_inner(conns: &[Connection]) -> Result<()> {
+        for conn in conns {
+            conn.execute_batch("BEGIN DEFERRED")?;
+            // A deferred transaction only takes its snapshot when it first reads the database.
+            conn.query_row("SELECT version FROM metadata", [], |_| Ok(()))?;
         }
         Ok(())
     }
 
-    /// Ensure all possible extensions for the given partial path are loaded.
-    pub fn load_partial_path_extensions(
-        &mut self,
-        path: &PartialPath,
-        cancellation_flag: &dyn CancellationFlag,
-    ) -> Result<()> {
-        copious_debugging!(
-            "--> Load extensions for {}",
-            path.display(&self.graph, &mut self.partials)
-        );
-        let end_node = self.graph[path.end_node].id();
-        if self.graph[path.end_node].file().is_some() {
-            self.load_paths_for_node(path.end_node, cancellation_flag)?;
-        } else if end_node.is_root() {
-            self.load_paths_for_root(path.symbol_stack_postcondition, cancellation_flag)?;
-        }
-        Ok(())
+    fn end_read_inner(conns: &[Connection]) -> Result<()> {
+        for conn in conns {
+            if !conn.is_autocommit() {
+                conn.execute_batch("COMMIT")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the data version of the database, with an element for every shard.  The version
+    /// changes whenever another connection commits a change to the database, so that results
+    /// derived from the database can be invalidated.
+    pub fn data_version(&self) -> Result<Vec<i64>> {
+        let conns = self.conns.lock();
+        let mut versions = Vec::with_capacity(conns.len());
+        for conn in conns.iter() {
+            versions.push(conn.query_row("PRAGMA data_version", [], |r| r.get(0))?);
+        }
+        Ok(versions)
+    }
+
+    /// Returns all revisions that have files in the database.
+    pub fn revisions(&self) -> Result<Vec<Revision>> {
+        let mut revisions = HashSet::new();
+        for conn in self.conns.lock().iter() {
+            let mut stmt = conn.prepare_cached("SELECT DISTINCT repo, commit_id FROM files")?;
+            for revision in stmt.query_map([], |r| {
+                Ok(Revision::new(
+                    r.get::<_, String>(0)?,
+                    r.get::<_, String>(1)?,
+                ))
+            })? {
+                revisions.insert(revision?);
+            }
+        }
+        let mut revisions = revisions.into_iter().collect::<Vec<_>>();
+        revisions.sort_by(|a, b| (&a.repo, &a.commit).cmp(&(&b.repo, &b.commit)));
+        Ok(revisions)
+    }
+
+    fn open_connection(path: &Path) -> Result<Connection> {
+        let mut conn = open_existing(path)?;
+        check_version(&conn)?;
+        init_indexes(&mut conn)?;
+        init_views(&conn)?;
+        Ok(conn)
+    }
+
+    /// Clear all data that has been loaded into this reader instance.
+    /// After this call, all existing handles from this reader are invalid.
+    pub fn clear(&mut self) {
+        self.loaded_graphs.clear();
+        self.graph = StackGraph::new();
+
+        self.loaded_node_paths.clear();
+        self.loaded_root_paths.clear();
+        self.prestitched_root_paths.clear();
+        self.library_paths.clear();
+        if let Some(provenance) = &mut self.provenance {
+            provenance.clear();
+        }
+        self.partials.clear();
+        self.db.clear();
+
+        self.stats.clear();
+    }
+
+    /// Clear path data that has been loaded into this reader instance.
+    /// After this call, all node handles remain valid, but all path data
+    /// is invalid.
+    pub fn clear_paths(&mut self) {
+        self.loaded_node_paths.clear();
+        self.loaded_root_paths.clear();
+        self.prestitched_root_paths.clear();
+        self.library_paths.clear();
+        self.partials.clear();
+        self.db.clear();
+
+        self.stats.clear_paths();
+    }
+
+    /// Get the file's status in the database. If a tag is provided, it must match or the file
+    /// is reported missing.
+    pub fn status_for_file<T: AsRef<str>>(
+        &mut self,
+        file: &str,
+        tag: Option<T>,
+    ) -> Result<FileStatus> {
+        for conn in self.conns.get_mut().iter() {
+            match status_for_file(conn, &self.revision, file, tag.as_ref())? {
+                FileStatus::Missing => continue,
+                status => return Ok(status),
+            }
+        }
+        Ok(FileStatus::Missing)
+    }
+
+    /// Returns a [`Files`][] value that can be used to iterate over all files in the database
+    /// for which indexing failed.
+    pub fn failed_files<'a>(&'a mut self) -> Result<Files<'a, [String; 2]>> {
+        failed_files(self.conns.get_mut(), &self.revision)
+    }
+
+    /// Returns a [`Files`][] value that can be used to iterate over all files in the database
+    /// that were partially indexed.
+    pub fn partial_files<'a>(&'a mut self) -> Result<Files<'a, [String; 2]>> {
+        partial_files(self.conns.get_mut(), &self.revision)
+    }
+
+    /// Returns a [`Files`][] value that can be used to iterate over all files in the database.
+    pub fn list_all<'a>(&'a mut self) -> Result<Files<'a, [String; 2]>> {
+        let stmts = self
+            .conns
+            .get_mut()
+            .iter()
+            .map(|conn| {
+                conn.prepare(
+                    "SELECT f.file, g.tag, g.error, g.partial, g.metadata FROM selected_files f JOIN graphs g ON g.entry = f.entry
+                     WHERE f.repo = ? AND f.commit_id = ?",
+                )
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(Files(
+            stmts,
+            [self.revision.repo.clone(), self.revision.commit.clone()],
+        ))
+    }
+
+    /// Returns a [`Files`][] value that can be used to iterate over all descendants of a
+    /// file or directory in the database.
+    pub fn list_file_or_directory<'a>(
+        &'a mut self,
+        file_or_directory: &Path,
+    ) -> Result<Files<'a, [String; 3]>> {
+        Self::list_file_or_directory_inner(self.conns.get_mut(), &self.revision, file_or_directory)
+    }
+
+    fn list_file_or_directory_inner<'a>(
+        conns: &'a [Connection],
+        revision: &Revision,
+        file_or_directory: &Path,
+    ) -> Result<Files<'a, [String; 3]>> {
+        let file_or_directory = file_or_directory.to_string_lossy().to_string();
+        let stmts = conns
+            .iter()
+            .map(|conn| {
+                conn.prepare(
+                    "SELECT f.file, g.tag, g.error, g.partial, g.metadata FROM selected_files f JOIN graphs g ON g.entry = f.entry
+                     WHERE f.repo = ? AND f.commit_id = ? AND path_descendant_of(f.file, ?)",
+                )
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(Files(
+            stmts,
+            [
+                revision.repo.clone(),
+                revision.commit.clone(),
+                file_or_directory,
+            ],
+        ))
+    }
+
+    /// Ensure the graph for the given file is loaded, together with the graphs of the other files
+    /// in its group, if it belongs to one. See [`FILE_GROUP_METADATA`][].
+    pub fn load_graph_for_file(&mut self, file: &str) -> Result<Handle<File>> {
+        Self::load_graph_for_file_inner(
+            file,
+            &mut self.graph,
+            &mut self.loaded_graphs,
+            self.conns.get_mut(),
+            &self.revision,
+            &mut self.stats,
+            self.observer.as_deref(),
+        )
+    }
+
+    fn load_graph_for_file_inner(
+        file: &str,
+        graph: &mut StackGraph,
+        loaded_graphs: &mut HashSet<String>,
+        conns: &[Connection],
+        revision: &Revision,
+        stats: &mut Stats,
+        observer: Option<&dyn QueryObserver>,
+    ) -> Result<Handle<File>> {
+        copious_debugging!("--> Load graph for {}", file);
+        if !loaded_graphs.insert(file.to_string()) {
+            copious_debugging!(" * Already loaded");
+            stats.file_cached += 1;
+            return Ok(graph.get_file(file).expect("loaded file to exist"));
+        }
+        copious_debugging!(" * Load from database");
+        stats.file_loads += 1;
+        let mut value = None;
+        for conn in conns {
+            let mut stmt = conn.prepare_cached(
+                "SELECT g.value, g.file_group FROM selected_files f JOIN graphs g ON g.entry = f.entry
+                 WHERE f.repo = ? AND f.commit_id = ? AND f.file = ?",
+            )?;
+            value = stmt
+                .query_row((&revision.repo, &revision.commit, file), |row| {
+                    Ok((
+                        row.get::<_, Vec<u8>>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        conn,
+                    ))
+                })
+                .optional()?;
+            if value.is_some() {
+                break;
+            }
+        }
+        let (value, file_group, conn) = value.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        let file_graph = decode_graph(conn, &value)?;
+        file_graph.load_into(graph)?;
+        if let Some(observer) = observer {
+            observer.observe(&QueryEvent::FileLoaded { file });
+        }
+        if let Some(file_group) = file_group {
+            copious_debugging!(" * Load group {}", file_group);
+            let mut members = Vec::new();
+            for conn in conns {
+                let mut stmt = conn.prepare_cached(
+                    "SELECT f.file FROM selected_files f JOIN graphs g ON g.entry = f.entry
+                     WHERE f.repo = ? AND f.commit_id = ? AND g.file_group = ?",
+                )?;
+                let rows = stmt
+                    .query_map((&revision.repo, &revision.commit, &file_group), |row| {
+                        row.get::<_, String>(0)
+                    })?;
+                for member in rows {
+                    members.push(member?);
+                }
+            }
+            for member in members {
+                if !loaded_graphs.contains(&member) {
+                    Self::load_graph_for_file_inner(
+                        &member,
+                        graph,
+                        loaded_graphs,
+                        conns,
+                        revision,
+                        stats,
+                        observer,
+                    )?;
+                }
+            }
+        }
+        Ok(graph.get_file(file).expect("loaded file to exist"))
+    }
+
+    pub fn load_graphs_for_file_or_directory(
+        &mut self,
+        file_or_directory: &Path,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        let conns: &[Connection] = self.conns.get_mut();
+        for file in Self::list_file_or_directory_inner(conns, &self.revision, file_or_directory)?
+            .try_iter()?
+        {
+            cancellation_flag.check("loading graphs")?;
+            let file = file?;
+            Self::load_graph_for_file_inner(
+                &file.path.to_string_lossy(),
+                &mut self.graph,
+                &mut self.loaded_graphs,
+                conns,
+                &self.revision,
+                &mut self.stats,
+                self.observer.as_deref(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Ensure the paths starting a the given node are loaded.
+    fn load_paths_for_node(
+        &mut self,
+        node: Handle<Node>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        copious_debugging!(" * Load extensions from node {}", node.display(&self.graph));
+        if !self.loaded_node_paths.insert(node) {
+            copious_debugging!("   > Already loaded");
+            self.stats.node_path_cached += 1;
+            return Ok(());
+        }
+        self.stats.node_path_loads += 1;
+        let id = self.graph[node].id();
+        let file = id.file().expect("file node required");
+        let file = self.graph[file].name().to_string();
+        let mut count = 0usize;
+        let conns: &[Connection] = self.conns.get_mut();
+        for conn in conns {
+            let mut stmt = conn.prepare_cached(
+                "SELECT f.file, p.value, f.entry FROM selected_files f JOIN file_paths p ON p.entry = f.entry
+                 WHERE f.repo = ? AND f.commit_id = ? AND f.file = ? AND p.local_id = ?",
+            )?;
+            let params = (
+                &self.revision.repo,
+                &self.revision.commit,
+                &file,
+                id.local_id(),
+            );
+            let paths = stmt.query_map(params, |row| {
+                let file = row.get::<_, String>(0)?;
+                let value = row.get::<_, Vec<u8>>(1)?;
+                let entry = row.get::<_, i64>(2)?;
+                Ok((file, value, entry))
+            })?;
+            for path in paths {
+                cancellation_flag.check("loading node paths")?;
+                let (file, value, entry) = path?;
+                Self::load_graph_for_file_inner(
+                    &file,
+                    &mut self.graph,
+                    &mut self.loaded_graphs,
+                    conns,
+                    &self.revision,
+                    &mut self.stats,
+                    self.observer.as_deref(),
+                )?;
+                let (path, _): (serde::PartialPath, usize) =
+                    bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+                let path = path.to_partial_path(&mut self.graph, &mut self.partials)?;
+                copious_debugging!(
+                    "   > Loaded {}",
+                    path.display(&self.graph, &mut self.partials)
+                );
+                Self::add_loaded_path(
+                    conns,
+                    &mut self.provenance,
+                    &self.graph,
+                    &mut self.partials,
+                    &mut self.db,
+                    entry,
+                    path,
+                )?;
+                count += 1;
+            }
+        }
+        copious_debugging!("   > Loaded {}", count);
+        if let Some(observer) = &self.observer {
+            observer.observe(&QueryEvent::PathsLoaded {
+                from_root: false,
+                count,
+            });
+        }
+        Ok(())
+    }
+
+    /// Add a path that was loaded from the given entry to the database, with its provenance if
+    /// provenance is collected.  Paths that are already in the database are not
+    /// added again.
+    fn add_loaded_path(
+        conns: &[Connection],
+        provenance: &mut Option<HashMap<i64, Arc<PathProvenance>>>,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        db: &mut Database,
+        entry: i64,
+        path: PartialPath,
+    ) -> Result<()> {
+        if db.find_partial_path(partials, &path).is_some() {
+            return Ok(());
+        }
+        match provenance {
+            Some(provenance) => {
+                let provenance = Self::provenance_for_entry(conns, provenance, entry)?;
+                db.add_partial_path_with_provenance(graph, partials, path, provenance);
+            }
+            None => {
+                db.add_partial_path(graph, partials, path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Ensure the paths starting at the root and matching the given symbol stack are loaded.
+    fn load_paths_for_root(
+        &mut self,
+        symbol_stack: PartialSymbolStack,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        copious_debugging!(
+            " * Load extensions from root with symbol stack {}",
+            symbol_stack.display(&self.graph, &mut self.partials)
+        );
+        if let Some(dependencies) = &mut self.dependencies {
+            let (symbol_stack_patterns, _) =
+                symbol_stack.storage_key_patterns(&self.graph, &mut self.partials);
+            dependencies.root_patterns.extend(symbol_stack_patterns);
+        }
+        if self.load_prestitched_paths_for_root(symbol_stack, cancellation_flag)? {
+            return Ok(());
+        }
+        let (symbol_stack_patterns, escape) =
+            symbol_stack.storage_key_patterns(&self.graph, &mut self.partials);
+        let conns: &[Connection] = self.conns.get_mut();
+        for symbol_stack in symbol_stack_patterns {
+            copious_debugging!(
+                " * Load extensions from root with prefix symbol stack {}",
+                symbol_stack
+            );
+            if !self.loaded_root_paths.insert(symbol_stack.clone()) {
+                copious_debugging!("   > Already loaded");
+                self.stats.root_path_cached += 1;
+                continue;
+            }
+            self.stats.root_path_loads += 1;
+            let mut count = 0usize;
+            for conn in conns {
+                let mut stmt = conn.prepare_cached(
+                    "SELECT f.file, p.value, f.entry FROM root_paths p JOIN selected_files f ON f.entry = p.entry
+                     WHERE f.repo = ? AND f.commit_id = ? AND p.symbol_stack LIKE ? ESCAPE ?",
+                )?;
+                let params = [
+                    &self.revision.repo,
+                    &self.revision.commit,
+                    &symbol_stack,
+                    &escape,
+                ];
+                let paths = stmt.query_map(params, |row| {
+                    let file = row.get::<_, String>(0)?;
+                    let value = row.get::<_, Vec<u8>>(1)?;
+                    let entry = row.get::<_, i64>(2)?;
+                    Ok((file, value, entry))
+                })?;
+                for path in paths {
+                    cancellation_flag.check("loading root paths")?;
+                    let (file, value, entry) = path?;
+                    Self::load_graph_for_file_inner(
+                        &file,
+                        &mut self.graph,
+                        &mut self.loaded_graphs,
+                        conns,
+                        &self.revision,
+                        &mut self.stats,
+                        self.observer.as_deref(),
+                    )?;
+                    let (path, _): (serde::PartialPath, usize) =
+                        bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+                    let path = path.to_partial_path(&mut self.graph, &mut self.partials)?;
+                    copious_debugging!(
+                        "   > Loaded {}",
+                        path.display(&self.graph, &mut self.partials)
+                    );
+                    Self::add_loaded_path(
+                        conns,
+                        &mut self.provenance,
+                        &self.graph,
+                        &mut self.partials,
+                        &mut self.db,
+                        entry,
+                        path,
+                    )?;
+                    count += 1;
+                }
+            }
+            count += Self::load_library_paths_for_root(
+                self.libraries.get_mut(),
+                &symbol_stack,
+                &escape,
+                &mut self.graph,
+                &mut self.partials,
+                &mut self.db,
+                &mut self.library_paths,
+                &mut self.loaded_graphs,
+                &mut self.stats,
+                self.observer.as_deref(),
+                cancellation_flag,
+            )?;
+            copious_debugging!("   > Loaded {}", count);
+            if let Some(observer) = &self.observer {
+                observer.observe(&QueryEvent::PathsLoaded {
+                    from_root: true,
+                    count,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Load the summary paths of the linked libraries whose symbol stack matches the given
+    /// pattern, together with the graphs of all library files they refer to. The library of every
+    /// loaded path is recorded by its index in `library_paths`. Returns the number of loaded paths.
+    #[allow(clippy::too_many_arguments)]
+    fn load_library_paths_for_root(
+        libraries: &[Connection],
+        symbol_stack: &str,
+        escape: &str,
+        graph: &mut StackGraph,
+        partials: &mut PartialPaths,
+        db: &mut Database,
+        library_paths: &mut HashMap<Handle<PartialPath>, usize>,
+        loaded_graphs: &mut HashSet<String>,
+        stats: &mut Stats,
+        observer: Option<&dyn QueryObserver>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<usize> {
+        let revision = Revision::default();
+        let mut count = 0usize;
+        for (index, library) in libraries.iter().enumerate() {
+            stats.library_root_path_loads += 1;
+            let mut stmt = library.prepare_cached(
+                "SELECT p.value FROM root_paths p JOIN selected_files f ON f.entry = p.entry
+                 WHERE f.repo = '' AND f.commit_id = '' AND p.symbol_stack LIKE ? ESCAPE ?",
+            )?;
+            let paths = stmt.query_map([symbol_stack, escape], |row| row.get::<_, Vec<u8>>(0))?;
+            for value in paths {
+                cancellation_flag.check("loading library paths")?;
+                let (path, _): (serde::PartialPath, usize) =
+                    bincode::decode_from_slice(&value?, BINCODE_CONFIG)?;
+                for file in path.files() {
+                    Self::load_graph_for_file_inner(
+                        file,
+                        graph,
+                        loaded_graphs,
+                        std::slice::from_ref(library),
+                        &revision,
+                        stats,
+                        observer,
+                    )?;
+                }
+                let path = path.to_partial_path(graph, partials)?;
+                copious_debugging!("   > Loaded library path {}", path.display(graph, partials));
+                let handle = db.add_partial_path(graph, partials, path);
+                library_paths.insert(handle, index);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Ensure the pre-stitched paths starting at the root and matching the given symbol stack are
+    /// loaded, if the revision has them. Returns whether pre-stitched paths are available, in
+    /// which case the stitcher uses them instead of the individual root paths.
+    ///
+    /// Pre-stitching is done per database, and misses paths that span several shards. Pre-stitched
+    /// paths are therefore not used when reading from more than one shard. They are also not used
+    /// when other than the default variants are selected, or when libraries are linked.
+    fn load_prestitched_paths_for_root(
+        &mut self,
+        symbol_stack: PartialSymbolStack,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<bool> {
+        if self.conns.get_mut().len() != 1
+            || !self.variants.is_empty()
+            || !self.libraries.get_mut().is_empty()
+        {
+            return Ok(false);
+        }
+        let symbol_stack = match symbol_stack.prestitched_key(&self.graph, &mut self.partials) {
+            Some(symbol_stack) => symbol_stack,
+            None => return Ok(false),
+        };
+        if let Some(paths) = self.prestitched_root_paths.get(&symbol_stack) {
+            if paths.is_some() {
+                copious_debugging!("   > Already loaded pre-stitched paths");
+                self.stats.root_path_cached += 1;
+            }
+            return Ok(paths.is_some());
+        }
+        let conns: &[Connection] = self.conns.get_mut();
+        let conn = &conns[0];
+        let id = {
+            let mut stmt = conn.prepare_cached(
+                "SELECT id FROM prestitched_symbol_stacks
+                 WHERE repo = ? AND commit_id = ? AND symbol_stack = ?",
+            )?;
+            stmt.query_row(
+                [&self.revision.repo, &self.revision.commit, &symbol_stack],
+                |r| r.get::<_, i64>(0),
+            )
+            .optional()?
+        };
+        let id = match id {
+            Some(id) => id,
+            None => {
+                self.prestitched_root_paths.insert(symbol_stack, None);
+                return Ok(false);
+            }
+        };
+        self.stats.prestitched_root_path_loads += 1;
+        let mut stmt =
+            conn.prepare_cached("SELECT value FROM prestitched_paths WHERE symbol_stack = ?")?;
+        let paths = stmt.query_map([id], |r| r.get::<_, Vec<u8>>(0))?;
+        let mut handles = Vec::new();
+        for value in paths {
+            cancellation_flag.check("loading pre-stitched root paths")?;
+            let (path, _): (serde::PartialPath, usize) =
+                bincode::decode_from_slice(&value?, BINCODE_CONFIG)?;
+            for file in path.files() {
+                Self::load_graph_for_file_inner(
+                    file,
+                    &mut self.graph,
+                    &mut self.loaded_graphs,
+                    conns,
+                    &self.revision,
+                    &mut self.stats,
+                    self.observer.as_deref(),
+                )?;
+            }
+            let path = path.to_partial_path(&mut self.graph, &mut self.partials)?;
+            copious_debugging!(
+                "   > Loaded pre-stitched {}",
+                path.display(&self.graph, &mut self.partials)
+            );
+            handles.push(
+                self.db
+                    .add_partial_path(&self.graph, &mut self.partials, path),
+            );
+        }
+        copious_debugging!("   > Loaded {} pre-stitched", handles.len());
+        if let Some(observer) = &self.observer {
+            observer.observe(&QueryEvent::PathsLoaded {
+                from_root: true,
+                count: handles.len(),
+            });
+        }
+        self.prestitched_root_paths
+            .insert(symbol_stack, Some(handles));
+        Ok(true)
+    }
+
+    /// Stitch the paths from the root to definitions for the given symbol stacks. Returns the
+    /// storage key, serialized paths, and dependencies for every symbol stack.
+    fn prestitch_root_paths(
+        &mut self,
+        symbol_stacks: &[Vec<String>],
+        config: StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Vec<PrestitchedSymbolStack>> {
+        let mut result = Vec::new();
+        for symbols in symbol_stacks {
+            let mut symbol_stack = PartialSymbolStack::empty();
+            for symbol in symbols {
+                let symbol = self.graph.add_symbol(symbol);
+                let symbol = PartialScopedSymbol {
+                    symbol: self.graph.normalized_symbol(symbol),
+                    scopes: ControlledOption::none(),
+                };
+                symbol_stack.push_back(&mut self.partials, symbol);
+            }
+            self.dependencies = Some(ArtifactDependencies::default());
+            let paths = self.prestitch_symbol_stack(symbol_stack, config, cancellation_flag);
+            let dependencies = self.dependencies.take().unwrap_or_default();
+            result.push((
+                symbol_stack.storage_key(&self.graph, &mut self.partials),
+                paths?,
+                dependencies,
+            ));
+        }
+        Ok(result)
+    }
+
+    /// Stitch the paths from the root to definitions for the given symbol stack. Returns the
+    /// serialized paths.
+    fn prestitch_symbol_stack(
+        &mut self,
+        symbol_stack: PartialSymbolStack,
+        config: StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Vec<Vec<u8>>> {
+        let mut seed =
+            PartialPath::from_node(&self.graph, &mut self.partials, StackGraph::root_node());
+        seed.symbol_stack_precondition = symbol_stack;
+        seed.symbol_stack_postcondition = symbol_stack;
+
+        let mut stitcher =
+            ForwardPartialPathStitcher::from_partial_paths(&self.graph, &mut self.partials, [seed]);
+        stitcher.set_similar_path_detection(config.detect_similar_paths());
+        stitcher.set_check_only_join_nodes(true);
+        let mut paths = Vec::new();
+        while !stitcher.is_complete() {
+            cancellation_flag.check("pre-stitching root paths")?;
+            for path in stitcher.previous_phase_partial_paths() {
+                self.load_partial_path_extensions(path, cancellation_flag)?;
+            }
+            stitcher.process_next_phase(self, |_, _, _| true);
+            for path in stitcher.previous_phase_partial_paths() {
+                if path.ends_at_definition(&self.graph) {
+                    let path = serde::PartialPath::from_partial_path(
+                        &self.graph,
+                        &mut self.partials,
+                        path,
+                    );
+                    paths.push(bincode::encode_to_vec(&path, BINCODE_CONFIG)?);
+                }
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Stitch the summary paths of a library from the root paths of all files, until they reach a
+    /// definition or the root. Returns the stored form of every library file that summary paths
+    /// refer to.
+    fn summarize_library(
+        &mut self,
+        config: StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Vec<LibraryFile>> {
+        let mut seeds = Vec::new();
+        {
+            let conns: &[Connection] = self.conns.get_mut();
+            let rows = {
+                let mut stmt = conns[0].prepare_cached(
+                    "SELECT f.file, p.value FROM root_paths p JOIN selected_files f ON f.entry = p.entry
+                     WHERE f.repo = ? AND f.commit_id = ?",
+                )?;
+                let rows = stmt.query_map(self.revision.params(), |r| {
+                    Ok((r.get::<_, String>(0)?, r.get::<_, Vec<u8>>(1)?))
+                })?;
+                rows.collect::<rusqlite::Result<Vec<_>>>()?
+            };
+            for (file, value) in rows {
+                cancellation_flag.check("loading library root paths")?;
+                Self::load_graph_for_file_inner(
+                    &file,
+                    &mut self.graph,
+                    &mut self.loaded_graphs,
+                    conns,
+                    &self.revision,
+                    &mut self.stats,
+                    self.observer.as_deref(),
+                )?;
+                let (path, _): (serde::PartialPath, usize) =
+                    bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+                seeds.push(path.to_partial_path(&mut self.graph, &mut self.partials)?);
+            }
+        }
+
+        let is_summary = |graph: &StackGraph, path: &PartialPath| {
+            path.ends_at_definition(graph) || graph[path.end_node].id().is_root()
+        };
+        let mut summaries = seeds
+            .iter()
+            .filter(|path| is_summary(&self.graph, path))
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut stitcher =
+            ForwardPartialPathStitcher::from_partial_paths(&self.graph, &mut self.partials, seeds);
+        stitcher.set_similar_path_detection(config.detect_similar_paths());
+        stitcher.set_check_only_join_nodes(true);
+        while !stitcher.is_complete() {
+            cancellation_flag.check("summarizing library")?;
+            for path in stitcher.previous_phase_partial_paths() {
+                // Paths that leave the library through the root are continued by other summaries,
+                // so the root paths are never loaded.
+                if !self.graph[path.end_node].id().is_root() {
+                    self.load_partial_path_extensions(path, cancellation_flag)?;
+                }
+            }
+            stitcher
+                .process_next_phase(self, |graph, _, path| !graph[path.end_node].id().is_root());
+            for path in stitcher.previous_phase_partial_paths() {
+                if is_summary(&self.graph, path) {
+                    summaries.push(path.clone());
+                }
+            }
+        }
+
+        let mut files = BTreeMap::<String, Vec<(String, Vec<u8>)>>::new();
+        let mut nodes = HashSet::new();
+        let mut seen = HashMap::<u64, Vec<&PartialPath>>::new();
+        for path in &summaries {
+            let same_fingerprint = seen
+                .entry(path.fingerprint(&mut self.partials))
+                .or_default();
+            if same_fingerprint
+                .iter()
+                .any(|other| other.is_identical(&mut self.partials, path))
+            {
+                continue;
+            }
+            same_fingerprint.push(path);
+            let symbol_stack = path
+                .symbol_stack_precondition
+                .storage_key(&self.graph, &mut self.partials);
+            let path = serde::PartialPath::from_partial_path(&self.graph, &mut self.partials, path);
+            let value = bincode::encode_to_vec(&path, BINCODE_CONFIG)?;
+            for node in path.node_ids() {
+                nodes.insert(node.to_node_id(&self.graph)?);
+            }
+            let file = match &path.end_node.file {
+                Some(file) => file.clone(),
+                None => match path.files().into_iter().next() {
+                    Some(file) => file.to_string(),
+                    None => continue,
+                },
+            };
+            for other in path.files() {
+                files.entry(other.to_string()).or_default();
+            }
+            files.entry(file).or_default().push((symbol_stack, value));
+        }
+
+        let mut result = Vec::new();
+        let conns: &[Connection] = self.conns.get_mut();
+        for (file, root_paths) in files {
+            let tag = {
+                let mut stmt = conns[0].prepare_cached(
+                    "SELECT g.tag FROM selected_files f JOIN graphs g ON g.entry = f.entry
+                     WHERE f.repo = ? AND f.commit_id = ? AND f.file = ?",
+                )?;
+                stmt.query_row((&self.revision.repo, &self.revision.commit, &file), |r| {
+                    r.get::<_, String>(0)
+                })?
+            };
+            let handle = self.graph.get_file(&file).expect("loaded file to exist");
+            let mut graph = serde::StackGraph::from_graph_filter(
+                &self.graph,
+                &LibraryFileFilter {
+                    file: handle,
+                    nodes: &nodes,
+                },
+            );
+            graph.metadata = serde::Metadata::default();
+            result.push(LibraryFile {
+                file,
+                tag,
+                graph,
+                root_paths,
+            });
+        }
+        Ok(result)
+    }
+
+    /// Resolve all references in the given files. Returns the file, local ID, serialized paths,
+    /// and dependencies for every reference. Shadowed paths are not included.
+    fn resolve_references_in_files(
+        &mut self,
+        files: &[String],
+        config: StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Vec<ResolvedReference>> {
+        let mut result = Vec::new();
+        for file in files {
+            let file = self.load_graph_for_file(file)?;
+            let references = self
+                .graph
+                .nodes_for_file(file)
+                .filter(|node| self.graph[*node].is_reference())
+                .collect::<Vec<_>>();
+            for reference in references {
+                cancellation_flag.check("resolving references")?;
+                let mut paths = Vec::new();
+                let mut dependencies = ArtifactDependencies::default();
+                dependencies
+                    .files
+                    .insert(self.graph[file].name().to_string());
+                self.dependencies = Some(dependencies);
+                let stitched = ForwardPartialPathStitcher::find_all_complete_partial_paths(
+                    self,
+                    std::iter::once(reference),
+                    config,
+                    cancellation_flag,
+                    |_, _, path| paths.push(path.clone()),
+                );
+                let dependencies = self.dependencies.take().unwrap_or_default();
+                stitched?;
+                let (graph, partials, _) = self.get();
+                let mut actual_paths = Vec::new();
+                for path in &paths {
+                    if !paths.iter().any(|other| other.shadows(partials, path)) {
+                        actual_paths
+                            .push(serde::PartialPath::from_partial_path(graph, partials, path));
+                    }
+                }
+                result.push((
+                    graph[file].name().to_string(),
+                    graph[reference].id().local_id(),
+                    bincode::encode_to_vec(&actual_paths, BINCODE_CONFIG)?,
+                    dependencies,
+                ));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Load the paths from the given reference to its definitions, if they were stored with
+    /// [`SQLiteWriter::resolve_references_in_files`][]. The graphs of all files the paths go
+    /// through are loaded. Returns `None` if the reference was not resolved in advance, in which
+    /// case its paths have to be stitched instead.
+    ///
+    /// Like pre-stitched paths, resolved references are not used when reading from more than one
+    /// shard, when other than the default variants are selected, or when libraries are linked.
+    pub fn load_resolved_paths(
+        &mut self,
+        reference: Handle<Node>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Option<Vec<PartialPath>>> {
+        if self.conns.get_mut().len() != 1
+            || !self.variants.is_empty()
+            || !self.libraries.get_mut().is_empty()
+        {
+            return Ok(None);
+        }
+        let id = self.graph[reference].id();
+        let file = match id.file() {
+            Some(file) => self.graph[file].name().to_string(),
+            None => return Ok(None),
+        };
+        let conns: &[Connection] = self.conns.get_mut();
+        let conn = &conns[0];
+        let value = {
+            let mut stmt = conn.prepare_cached(
+                "SELECT value FROM resolved_references
+                 WHERE repo = ? AND commit_id = ? AND file = ? AND local_id = ?",
+            )?;
+            stmt.query_row(
+                (
+                    &self.revision.repo,
+                    &self.revision.commit,
+                    &file,
+                    id.local_id(),
+                ),
+                |r| r.get::<_, Vec<u8>>(0),
+            )
+            .optional()?
+        };
+        let value = match value {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        self.stats.resolved_reference_loads += 1;
+        let (paths, _): (Vec<serde::PartialPath>, usize) =
+            bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+        let mut result = Vec::new();
+        for path in paths {
+            cancellation_flag.check("loading resolved paths")?;
+            for file in path.files() {
+                Self::load_graph_for_file_inner(
+                    file,
+                    &mut self.graph,
+                    &mut self.loaded_graphs,
+                    conns,
+                    &self.revision,
+                    &mut self.stats,
+                    self.observer.as_deref(),
+                )?;
+            }
+            result.push(path.to_partial_path(&mut self.graph, &mut self.partials)?);
+        }
+        Ok(Some(result))
+    }
+
+    /// Ensure all possible extensions for the given partial path are loaded.
+    pub fn load_partial_path_extensions(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        copious_debugging!(
+            "--> Load extensions for {}",
+            path.display(&self.graph, &mut self.partials)
+        );
+        let end_node = self.graph[path.end_node].id();
+        if self.graph[path.end_node].file().is_some() {
+            self.load_paths_for_node(path.end_node, cancellation_flag)?;
+        } else if end_node.is_root() {
+            self.load_paths_for_root(path.symbol_stack_postcondition, cancellation_flag)?;
+        }
+        Ok(())
+    }
+
+    /// Get the stack graph, partial paths arena, and path database for the currently loaded data.
+    pub fn get(&mut self) -> (&mut StackGraph, &mut PartialPaths, &mut Database) {
+        (&mut self.graph, &mut self.partials, &mut self.db)
+    }
+
+    /// Return stats about this database reader.
+    pub fn stats(&self) -> Stats {
+        self.stats.clone()
+    }
+}
+
+// Methods for computing keys and patterns for a symbol stack. The format of a storage key is:
+//
+//     has-var GS ( symbol (US symbol)* )?
+//
+// where has-var is "V" if the symbol stack has a variable, "X" otherwise.  Keys only contain the
+// names of symbols, so symbols with the same name in different namespaces have the same keys.
+// Paths loaded for the wrong namespace are rejected when they are joined.  The exception are the
+// symbols of root domains, which are prefixed with GS, so that only the paths of the root domain
+// that a path enters are loaded.
+impl PartialSymbolStack {
+    /// Returns a string representation of this symbol stack for indexing in the database.
+    fn storage_key(self, graph: &StackGraph, partials: &mut PartialPaths) -> String {
+        let mut key = String::new();
+        match self.has_variable() {
+            true => key += "V\u{241E}",
+            false => key += "X\u{241E}",
+        }
+        key += &self
+            .iter(partials)
+            .map(|s| storage_key_symbol(graph, s.symbol))
+            .join("\u{241F}");
+        key
+    }
+
+    /// Returns the storage key of this symbol stack for looking up pre-stitched paths. Only
+    /// symbol stacks without a variable, attached scopes, and namespaces are pre-stitched.
+    fn prestitched_key(self, graph: &StackGraph, partials: &mut PartialPaths) -> Option<String> {
+        if self.has_variable()
+            || self
+                .iter(partials)
+                .any(|s| s.scopes.is_some() || graph.symbol_namespace(s.symbol).is_some())
+        {
+            return None;
+        }
+        Some(self.storage_key(graph, partials))
+    }
+
+    /// Returns string representations for all prefixes of this symbol stack for querying the
+    /// index in the database.
+    fn storage_key_patterns(
+        mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+    ) -> (Vec<String>, String) {
+        let mut key_patterns = Vec::new();
+        let mut symbols = String::new();
+        while let Some(symbol) = self.pop_front(partials) {
+            if !symbols.is_empty() {
+                symbols += "\u{241F}";
+            }
+            let symbol = storage_key_symbol(graph, symbol.symbol)
+                .replace("%", "\\%")
+                .replace("_", "\\_")
+                .to_string();
+            symbols += &symbol;
+            // patterns for paths matching a prefix of this stack
+            key_patterns.push("V\u{241E}".to_string() + &symbols);
+        }
+        // pattern for paths matching exactly this stack
+        key_patterns.push("X\u{241E}".to_string() + &symbols);
+        if self.has_variable() {
+            // patterns for paths for which this stack is a prefix
+            key_patterns.push("_\u{241E}".to_string() + &symbols + "\u{241F}%");
+        }
+        (key_patterns, "\\".to_string())
+    }
+}
+
+/// Returns the representation of a symbol in storage keys.
+fn storage_key_symbol(graph: &StackGraph, symbol: Handle<Symbol>) -> Cow<'_, str> {
+    match graph.root_domain(symbol) {
+        Some(domain) => Cow::Owned(format!("\u{241E}{}", domain)),
+        None => Cow::Borrowed(&graph[symbol]),
+    }
+}
+
+impl SQLiteReader {
+    /// Finds the candidates to extend the given path with, from pre-stitched paths if there are
+    /// any, or from the loaded paths, filtered by the dependency selector.
+    fn find_forward_candidates<R>(&mut self, path: &PartialPath, result: &mut R)
+    where
+        R: std::iter::Extend<Handle<PartialPath>>,
+    {
+        if !self.prestitched_root_paths.is_empty() && self.graph[path.end_node].is_root() {
+            if let Some(Some(paths)) = path
+                .symbol_stack_postcondition
+                .prestitched_key(&self.graph, &mut self.partials)
+                .and_then(|symbol_stack| self.prestitched_root_paths.get(&symbol_stack))
+            {
+                result.extend(paths.iter().copied());
+                return;
+            }
+        }
+        let selector = match &self.dependency_selector {
+            Some(selector)
+                if !self.library_paths.is_empty() && self.graph[path.end_node].is_root() =>
+            {
+                selector
+            }
+            _ => {
+                self.db
+                    .find_candidate_partial_paths(&self.graph, &mut self.partials, path, result);
+                return;
+            }
+        };
+        let mut candidates = Vec::new();
+        self.db.find_candidate_partial_paths(
+            &self.graph,
+            &mut self.partials,
+            path,
+            &mut candidates,
+        );
+        let mut edges = path.edges;
+        let querying_file = edges
+            .pop_back(&mut self.partials)
+            .and_then(|edge| edge.source_node_id.file())
+            .map(|file| self.graph[file].name());
+        result.extend(candidates.into_iter().filter(|candidate| {
+            match (querying_file, self.library_paths.get(candidate)) {
+                (Some(file), Some(index)) => selector.select(file, &self.linked_libraries[*index]),
+                _ => true,
+            }
+        }));
+    }
+}
+
+impl ForwardCandidates<Handle<PartialPath>, PartialPath, Database, StorageError> for SQLiteReader {
+    fn load_forward_candidates(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> std::result::Result<(), StorageError> {
+        self.load_partial_path_extensions(path, cancellation_flag)
+    }
+
+    fn get_forward_candidates<R>(&mut self, path: &PartialPath, result: &mut R)
+    where
+        R: std::iter::Extend<Handle<PartialPath>>,
+    {
+        if self.dependencies.is_none() {
+            self.find_forward_candidates(path, result);
+            return;
+        }
+        let mut candidates = Vec::new();
+        self.find_forward_candidates(path, &mut candidates);
+        if let Some(dependencies) = &mut self.dependencies {
+            for candidate in &candidates {
+                dependencies.add_path_files(&self.graph, &self.partials, &self.db[*candidate]);
+            }
+        }
+        result.extend(candidates);
+    }
+
+    fn get_joining_candidate_degree(&self, path: &PartialPath) -> Degree {
+        self.db.get_incoming_path_degree(path.end_node)
+    }
+
+    fn get_graph_partials_and_db(&mut self) -> (&StackGraph, &mut PartialPaths, &Database) {
+        (&self.graph, &mut self.partials, &self.db)
+    }
+
+    fn get_observer(&self) -> Option<Arc<dyn QueryObserver>> {
+        self.observer.clone()
+    }
+
+    fn get_visibility_policy(&self) -> Option<Arc<dyn VisibilityPolicy>> {
+        self.visibility_policy.clone()
+    }
+}
+
+impl Drop for SQLiteReader {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.release(std::mem::take(self.conns.get_mut()));
+        }
+    }
+}
+
+/// A reader that loads partial paths on a separate thread, so that it can be used with
+/// [`ForwardPartialPathStitcher::find_all_complete_partial_paths_async`][], for example in a
+/// tokio task, without blocking the threads of the async runtime on database queries.
+///
+/// While a load is in progress, the wrapped reader is moved to the loading thread, and returned
+/// when the load completes.  The cancellation flag is checked before every load, but a load that
+/// has started runs to completion.  Dropping a pending load waits for it to complete.
+pub struct AsyncSQLiteReader {
+    reader: Option<SQLiteReader>,
+    requests: Option<mpsc::Sender<LoadRequest>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+struct LoadRequest {
+    reader: SQLiteReader,
+    path: PartialPath,
+    slot: Arc<LoadSlot>,
+}
+
+type LoadResult = std::thread::Result<Result<()>>;
+
+/// The slot in which the loading thread returns the reader and the result of a load.
+#[derive(Default)]
+struct LoadSlot {
+    state: Mutex<LoadState>,
+    completed: Condvar,
+}
+
+#[derive(Default)]
+struct LoadState {
+    result: Option<(SQLiteReader, LoadResult)>,
+    waker: Option<Waker>,
+}
+
+impl LoadSlot {
+    fn complete(&self, reader: SQLiteReader, result: LoadResult) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.result = Some((reader, result));
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.completed.notify_all();
+    }
+}
+
+impl AsyncSQLiteReader {
+    /// Wrap a reader, and start the thread that runs its loads.
+    pub fn new(reader: SQLiteReader) -> Result<Self> {
+        let (requests, receiver) = mpsc::channel::<LoadRequest>();
+        let worker = std::thread::Builder::new()
+            .name("stack-graphs-loader".to_string())
+            .spawn(move || {
+                for mut request in receiver {
+                    let result = Self::load(&mut request.reader, &request.path);
+                    request.slot.complete(request.reader, result);
+                }
+            })?;
+        Ok(Self {
+            reader: Some(reader),
+            requests: Some(requests),
+            worker: Some(worker),
+        })
+    }
+
+    fn load(reader: &mut SQLiteReader, path: &PartialPath) -> LoadResult {
+        // Panics are returned to the task that awaits the load, so that the reader is not lost.
+        std::panic::catch_unwind(AssertUnwindSafe(|| {
+            reader.load_partial_path_extensions(path, &NoCancellation)
+        }))
+    }
+
+    /// Return the wrapped reader.
+    pub fn reader(&self) -> &SQLiteReader {
+        self.reader.as_ref().expect("reader is loading")
+    }
+
+    /// Return the wrapped reader.
+    pub fn reader_mut(&mut self) -> &mut SQLiteReader {
+        self.reader.as_mut().expect("reader is loading")
+    }
+
+    /// Stop the loading thread and return the wrapped reader.
+    pub fn into_inner(mut self) -> SQLiteReader {
+        self.reader.take().expect("reader is loading")
+    }
+
+    fn start_load(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Arc<LoadSlot>> {
+        cancellation_flag.check("loading partial paths")?;
+        let slot = Arc::new(LoadSlot::default());
+        let request = LoadRequest {
+            reader: self.reader.take().expect("reader is loading"),
+            path: path.clone(),
+            slot: slot.clone(),
+        };
+        if let Err(mpsc::SendError(mut request)) = self.requests.as_ref().unwrap().send(request) {
+            // The loading thread is gone, so load on this thread instead.
+            let result = Self::load(&mut request.reader, &request.path);
+            slot.complete(request.reader, result);
+        }
+        Ok(slot)
+    }
+}
+
+impl Drop for AsyncSQLiteReader {
+    fn drop(&mut self) {
+        self.requests.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A load that is in progress.  The reader is put back when the load completes, or when the load
+/// is dropped.
+struct PendingLoad<'a> {
+    reader: &'a mut AsyncSQLiteReader,
+    slot: Option<Arc<LoadSlot>>,
+}
+
+impl Future for PendingLoad<'_> {
+    type Output = Result<()>;
+
+    fn poll(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        let slot = this.slot.as_ref().expect("load is polled after completion");
+        let mut state = slot.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (reader, result) = match state.result.take() {
+            Some(completed) => completed,
+            None => {
+                state.waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+        };
+        drop(state);
+        this.slot = None;
+        this.reader.reader = Some(reader);
+        match result {
+            Ok(result) => Poll::Ready(result),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl Drop for PendingLoad<'_> {
+    fn drop(&mut self) {
+        let slot = match self.slot.take() {
+            Some(slot) => slot,
+            None => return,
+        };
+        let mut state = slot.state.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if let Some((reader, _)) = state.result.take() {
+                self.reader.reader = Some(reader);
+                return;
+            }
+            state = slot
+                .completed
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+impl AsyncForwardCandidates<Handle<PartialPath>, PartialPath, Database, StorageError>
+    for AsyncSQLiteReader
+{
+    fn load_forward_candidates_async(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &(dyn CancellationFlag + Sync),
+    ) -> impl Future<Output = Result<()>> + Send {
+        let slot = self.start_load(path, cancellation_flag);
+        async move {
+            PendingLoad {
+                reader: self,
+                slot: Some(slot?),
+            }
+            .await
+        }
+    }
+}
+
+impl ForwardCandidates<Handle<PartialPath>, PartialPath, Database, StorageError>
+    for AsyncSQLiteReader
+{
+    fn load_forward_candidates(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> std::result::Result<(), StorageError> {
+        self.reader_mut()
+            .load_forward_candidates(path, cancellation_flag)
+    }
+
+    fn get_forward_candidates<R>(&mut self, path: &PartialPath, result: &mut R)
+    where
+        R: std::iter::Extend<Handle<PartialPath>>,
+    {
+        self.reader_mut().get_forward_candidates(path, result)
+    }
+
+    fn get_joining_candidate_degree(&self, path: &PartialPath) -> Degree {
+        self.reader().get_joining_candidate_degree(path)
+    }
+
+    fn get_graph_partials_and_db(&mut self) -> (&StackGraph, &mut PartialPaths, &Database) {
+        self.reader_mut().get_graph_partials_and_db()
+    }
+
+    fn get_observer(&self) -> Option<Arc<dyn QueryObserver>> {
+        self.reader().get_observer()
+    }
+
+    fn get_visibility_policy(&self) -> Option<Arc<dyn VisibilityPolicy>> {
+        self.reader().get_visibility_policy()
+    }
+}
+
+/// A consistent snapshot of a database, started by [`SQLiteReader::begin_read`][].  The snapshot
+/// dereferences to the reader, so that all queries of the reader can be run against it.  The
+/// snapshot is released when it is [ended][Self::end] or dropped.
+pub struct ReadSnapshot<'a> {
+    reader: &'a mut SQLiteReader,
+}
+
+impl ReadSnapshot<'_> {
+    /// Releases the snapshot, and reports any error in doing so, which is ignored when the
+    /// snapshot is dropped instead.
+    pub fn end(self) -> Result<()> {
+        SQLiteReader::end_read_inner(self.reader.conns.get_mut())
+    }
+}
+
+impl Deref for ReadSnapshot<'_> {
+    type Target = SQLiteReader;
+
+    fn deref(&self) -> &SQLiteReader {
+        self.reader
+    }
+}
+
+impl DerefMut for ReadSnapshot<'_> {
+    fn deref_mut(&mut self) -> &mut SQLiteReader {
+        self.reader
+    }
+}
+
+impl Drop for ReadSnapshot<'_> {
+    fn drop(&mut self) {
+        let _ = SQLiteReader::end_read_inner(self.reader.conns.get_mut());
+    }
+}
+
+/// The connections of a reader, one for every shard.  A connection cannot be used by several
+/// threads at once, so the connections are guarded by a mutex, which makes readers `Sync`.
+/// Methods that have mutable access to the reader use the connections without locking.
+struct Connections(Mutex<Vec<Connection>>);
+
+impl Connections {
+    fn new(conns: Vec<Connection>) -> Self {
+        Self(Mutex::new(conns))
+    }
+
+    fn get_mut(&mut self) -> &mut Vec<Connection> {
+        // A panic while the connections are in use does not leave them in an invalid state.
+        self.0.get_mut().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Connection>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A pool of connections to a database, or to a set of database shards, that hands out
+/// [`SQLiteReader`][]s.  Every reader has its own connections, and its own loaded graph and
+/// paths, so that many threads can run queries at the same time.  Databases are opened in WAL
+/// mode, so readers do not block each other, and are not blocked by a writer.  The connections
+/// of a dropped reader are returned to the pool and reused by the next reader, which saves
+/// opening the database and preparing statements again.
+///
+/// The pool can be cloned cheaply, and clones share their connections.
+#[derive(Clone)]
+pub struct SQLiteReaderPool {
+    conns: Arc<PoolConnections>,
+}
+
+struct PoolConnections {
+    paths: Vec<PathBuf>,
+    idle: Mutex<Vec<Vec<Connection>>>,
+}
+
+impl PoolConnections {
+    fn acquire(&self) -> Result<Vec<Connection>> {
+        if let Some(conns) = self.idle.lock().unwrap_or_else(|e| e.into_inner()).pop() {
+            return Ok(conns);
+        }
+        self.paths
+            .iter()
+            .map(|path| SQLiteReader::open_connection(path))
+            .collect()
+    }
+
+    fn release(&self, conns: Vec<Connection>) {
+        self.idle
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(conns);
+    }
+}
+
+impl SQLiteReaderPool {
+    /// Open a pool for a file database.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_shards(std::iter::once(path))
+    }
+
+    /// Open a pool for a database consisting of the given shards.  An error is returned if any
+    /// of the shards does not exist, or has an unsupported version.
+    pub fn open_shards<I, P>(paths: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let paths = paths
+            .into_iter()
+            .map(|p| p.as_ref().to_path_buf())
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return Err(StorageError::MissingDatabase("no shards".to_string()));
+        }
+        let conns = PoolConnections {
+            paths,
+            idle: Mutex::new(Vec::new()),
+        };
+        let initial = conns.acquire()?;
+        conns.release(initial);
+        Ok(Self {
+            conns: Arc::new(conns),
+        })
+    }
+
+    /// Returns a reader using connections from this pool.  Idle connections are reused, and new
+    /// connections are opened if there are none.  The reader uses the default revision.
+    pub fn reader(&self) -> Result<SQLiteReader> {
+        let conns = self.conns.acquire()?;
+        select_default_variants(&conns)?;
+        let mut reader = SQLiteReader::from_connections(conns, Revision::default());
+        reader.pool = Some(self.conns.clone());
+        Ok(reader)
+    }
+
+    /// Returns the number of idle connection sets in this pool.  Every set has a connection for
+    /// each shard.
+    pub fn idle_count(&self) -> usize {
+        self.conns
+            .idle
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+}
+
+/// Readers of stored graphs and paths, so that queries can be answered from any of the storage
+/// formats.  Implemented by [`SQLiteReader`][], and by the reader of the append-only
+/// [`sgdb`][crate::sgdb] format.  Loading candidates for the path stitcher is done through the
+/// [`ForwardCandidates`][] implementation.
+pub trait StorageBackend:
+    ForwardCandidates<Handle<PartialPath>, PartialPath, Database, StorageError>
+{
+    /// Get the file's status.  If a tag is provided, it must match or the file is reported
+    /// missing.
+    fn status_for_file(&mut self, file: &str, tag: Option<&str>) -> Result<FileStatus>;
+
+    /// Ensure the graph for the given file is loaded.
+    fn load_graph_for_file(&mut self, file: &str) -> Result<Handle<File>>;
+
+    /// Get the stack graph, partial paths arena, and path database for the currently loaded data.
+    fn get(&mut self) -> (&mut StackGraph, &mut PartialPaths, &mut Database);
+}
+
+impl StorageBackend for SQLiteReader {
+    fn status_for_file(&mut self, file: &str, tag: Option<&str>) -> Result<FileStatus> {
+        SQLiteReader::status_for_file(self, file, tag)
+    }
+
+    fn load_graph_for_file(&mut self, file: &str) -> Result<Handle<File>> {
+        SQLiteReader::load_graph_for_file(self, file)
+    }
+
+    fn get(&mut self) -> (&mut StackGraph, &mut PartialPaths, &mut Database) {
+        SQLiteReader::get(self)
+    }
+}
+
+/// Strategy to assign files to the shards of a [`ShardedDatabase`][].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShardingStrategy {
+    /// Assign files to the given number of shards, based on a hash of their path. The hash is
+    /// stable, so files are assigned to the same shard across runs.
+    Hash(usize),
+    /// Assign files to shards based on the first `depth` directory components of their path
+    /// relative to `root`. All files outside `root` are assigned to a single shard.
+    Directory { root: PathBuf, depth: usize },
+}
+
+/// A database that is split over several SQLite files, called shards, in a directory.
+///
+/// Every shard is a regular database, written by its own [`SQLiteWriter`][]. Writers for
+/// different shards do not share a connection, so shards can be written in parallel, for example
+/// by grouping files with [`partition_files`][Self::partition_files] and indexing every group in
+/// a separate thread. Use [`open_reader`][Self::open_reader] to query all shards together.
+#[derive(Clone, Debug)]
+pub struct ShardedDatabase {
+    directory: PathBuf,
+    strategy: ShardingStrategy,
+}
+
+impl ShardedDatabase {
+    const EXTENSION: &'static str = "sqlite";
+
+    /// Create a sharded database in the given directory.
+    pub fn new<P: Into<PathBuf>>(directory: P, strategy: ShardingStrategy) -> Self {
+        Self {
+            directory: directory.into(),
+            strategy,
+        }
+    }
+
+    /// Returns the directory containing the shards.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Returns the name of the shard the given file is assigned to.
+    pub fn shard_for_file(&self, file: &Path) -> String {
+        match &self.strategy {
+            ShardingStrategy::Hash(shards) => {
+                let hash = stable_hash(file.to_string_lossy().bytes());
+                format!("hash-{}", hash % (*shards).max(1) as u64)
+            }
+            ShardingStrategy::Directory { root, depth } => {
+                let dir = match file.parent().and_then(|p| p.strip_prefix(root).ok()) {
+                    Some(dir) => dir,
+                    None => return "other".to_string(),
+                };
+                let mut name = "dir".to_string();
+                for component in dir.components().take(*depth) {
+                    name.push('-');
+                    name.extend(component.as_os_str().to_string_lossy().chars().map(|c| {
+                        if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+                            c
+                        } else {
+                            '_'
+                        }
+                    }));
+                }
+                name
+            }
+        }
+    }
+
+    /// Returns the path of the database file for the given shard.
+    pub fn shard_path(&self, shard: &str) -> PathBuf {
+        self.directory
+            .join(format!("{}.{}", shard, Self::EXTENSION))
+    }
+
+    /// Returns the paths of all existing shards, in a stable order.
+    pub fn shard_paths(&self) -> Result<Vec<PathBuf>> {
+        if !self.directory.exists() {
+            return Err(StorageError::MissingDatabase(
+                self.directory.to_string_lossy().to_string(),
+            ));
+        }
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path.is_file() && path.extension().is_some_and(|e| e == Self::EXTENSION) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Group the given files by the shard they are assigned to.
+    pub fn partition_files<I, P>(&self, files: I) -> BTreeMap<String, Vec<PathBuf>>
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        let mut shards = BTreeMap::<_, Vec<_>>::new();
+        for file in files {
+            let file = file.into();
+            shards
+                .entry(self.shard_for_file(&file))
+                .or_default()
+                .push(file);
+        }
+        shards
+    }
+
+    /// Open a writer for the given shard, creating the shard if it does not exist yet.
+    pub fn open_writer(&self, shard: &str) -> Result<SQLiteWriter> {
+        std::fs::create_dir_all(&self.directory)?;
+        SQLiteWriter::open(self.shard_path(shard))
+    }
+
+    /// Open a writer for the shard the given file is assigned to.
+    pub fn open_writer_for_file(&self, file: &Path) -> Result<SQLiteWriter> {
+        self.open_writer(&self.shard_for_file(file))
+    }
+
+    /// Open a reader that federates queries over all existing shards.
+    pub fn open_reader(&self) -> Result<SQLiteReader> {
+        SQLiteReader::open_shards(self.shard_paths()?)
+    }
+}
+
+/// Statistics about the data removed, or that would be removed, by garbage collection.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GarbageStats {
+    /// The number of unused entries.
+    pub entries: usize,
+    /// The number of partial paths starting at a node in an unused entry.
+    pub node_paths: usize,
+    /// The number of partial paths starting at the root in an unused entry.
+    pub root_paths: usize,
+    /// The number of strings that are not used by any remaining entry.
+    pub strings: usize,
+}
+
+/// Aggregate statistics about the files of a revision in a database.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DatabaseStats {
+    /// Statistics for every file of the revision, ordered by path.
+    pub files: Vec<FileStats>,
+    /// The size in bytes of the database files, summed over all shards.
+    pub size_on_disk: u64,
+    /// The number of push and pop nodes for each symbol, over all files of the revision.
+    pub symbols: BTreeMap<String, usize>,
+}
+
+impl DatabaseStats {
+    /// The total number of nodes in all files.
+    pub fn node_count(&self) -> usize {
+        self.files.iter().map(|f| f.node_count).sum()
+    }
+
+    /// The total number of edges in all files.
+    pub fn edge_count(&self) -> usize {
+        self.files.iter().map(|f| f.edge_count).sum()
+    }
+
+    /// The total number of partial paths in all files.
+    pub fn path_count(&self) -> usize {
+        self.files.iter().map(|f| f.path_count()).sum()
+    }
+
+    /// Returns the `n` files with the largest stored size, largest first.
+    pub fn largest_files(&self, n: usize) -> Vec<&FileStats> {
+        let mut files = self.files.iter().collect::<Vec<_>>();
+        files.sort_by(|a, b| b.stored_size.cmp(&a.stored_size).then(a.path.cmp(&b.path)));
+        files.truncate(n);
+        files
+    }
+
+    /// Returns the `n` most frequent symbols and their number of occurrences, most frequent first.
+    pub fn most_frequent_symbols(&self, n: usize) -> Vec<(&str, usize)> {
+        let mut symbols = self
+            .symbols
+            .iter()
+            .map(|(symbol, count)| (symbol.as_str(), *count))
+            .collect::<Vec<_>>();
+        symbols.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        symbols.truncate(n);
+        symbols
+    }
+}
+
+/// Statistics about a single file in a database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileStats {
+    pub path: PathBuf,
+    pub tag: String,
+    pub status: FileStatus,
+    /// The number of nodes in the file's graph, excluding the singleton root and jump-to nodes.
+    pub node_count: usize,
+    /// The number of edges in the file's graph.
+    pub edge_count: usize,
+    /// The number of stored partial paths starting at a node in the file.
+    pub node_path_count: usize,
+    /// The number of stored partial paths starting at the root.
+    pub root_path_count: usize,
+    /// The number of bytes used to store the file's graph and paths.
+    pub stored_size: u64,
+}
+
+impl FileStats {
+    /// The total number of partial paths stored for the file.
+    pub fn path_count(&self) -> usize {
+        self.node_path_count + self.root_path_count
+    }
+}
+
+/// Statistics about data shared between files in a database.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeduplicationStats {
+    /// The number of files in all revisions.
+    pub file_count: usize,
+    /// The number of entries storing file data.
+    pub entry_count: usize,
+}
+
+impl DeduplicationStats {
+    /// The number of files that did not need their own entry, because they share it with files
+    /// in other revisions.
+    pub fn shared_file_count(&self) -> usize {
+        self.file_count.saturating_sub(self.entry_count)
+    }
+}
+
+impl std::ops::AddAssign for DeduplicationStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.file_count += rhs.file_count;
+        self.entry_count += rhs.entry_count;
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub file_loads: usize,
+    pub file_cached: usize,
+    pub root_path_loads: usize,
+    pub root_path_cached: usize,
+    pub node_path_loads: usize,
+    pub node_path_cached: usize,
+    pub prestitched_root_path_loads: usize,
+    pub resolved_reference_loads: usize,
+    pub library_root_path_loads: usize,
+}
+
+impl Stats {
+    fn clear(&mut self) {
+        *self = Stats::default();
+    }
+
+    fn clear_paths(&mut self) {
+        *self = Stats {
+            file_loads: self.file_loads,
+            file_cached: self.file_cached,
+            ..Stats::default()
+        }
+    }
+}
+
+/// Check if the database has the version supported by this library version.
+fn check_version(conn: &Connection) -> Result<()> {
+    let version = read_version(conn)?;
+    if version != VERSION {
+        return Err(StorageError::IncorrectVersion(version));
+    }
+    Ok(())
+}
+
+fn read_version(conn: &Connection) -> Result<usize> {
+    Ok(conn.query_row("SELECT version FROM metadata", [], |r| r.get::<_, usize>(0))?)
+}
+
+fn read_library(conn: &Connection) -> Result<Option<Library>> {
+    Ok(conn
+        .query_row("SELECT name, version FROM library", [], |r| {
+            Ok(Library::new(r.get::<_, String>(0)?, r.get::<_, String>(1)?))
+        })
+        .optional()?)
+}
+
+fn set_pragmas_and_functions(conn: &Connection) -> Result<()> {
+    conn.execute_batch(PRAGMAS)?;
+    conn.create_scalar_function(
+        "path_descendant_of",
+        2,
+        FunctionFlags::SQLITE_DETERMINISTIC | FunctionFlags::SQLITE_UTF8,
+        move |ctx| {
+            assert_eq!(ctx.len(), 2, "called with unexpected number of arguments");
+            let path = PathBuf::from(ctx.get::<String>(0)?);
+            let parent = PathBuf::from(ctx.get::<String>(1)?);
+            let result = path.starts_with(&parent);
+            Ok(result)
+        },
+    )?;
+    Ok(())
+}
+
+/// Create the views of a connection to a database of the current version, which select the
+/// default variants of files.
+fn init_views(conn: &Connection) -> Result<()> {
+    select_variants(conn, vec![String::new()])?;
+    conn.execute_batch(VIEWS)?;
+    Ok(())
+}
+
+/// Select the given variants of files, in order of preference, in the selected_files view of
+/// the connection.
+fn select_variants(conn: &Connection, variants: Vec<String>) -> Result<()> {
+    conn.create_scalar_function("variant_rank", 1, FunctionFlags::SQLITE_UTF8, move |ctx| {
+        assert_eq!(ctx.len(), 1, "called with unexpected number of arguments");
+        let variant = ctx.get::<String>(0)?;
+        Ok(variants
+            .iter()
+            .position(|v| v == &variant)
+            .map(|i| i as i64))
+    })?;
+    Ok(())
+}
+
+/// Select the default variants of files in the selected_files views of the connections.
+fn select_default_variants(conns: &[Connection]) -> Result<()> {
+    for conn in conns {
+        select_variants(conn, vec![String::new()])?;
+    }
+    Ok(())
+}
+
+fn init_indexes(conn: &mut Connection) -> Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute_batch(INDEXES)?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn status_for_file<T: AsRef<str>>(
+    conn: &Connection,
+    revision: &Revision,
+    file: &str,
+    tag: Option<T>,
+) -> Result<FileStatus> {
+    let result = if let Some(tag) = tag {
+        let mut stmt = conn.prepare_cached(
+            "SELECT g.error, g.partial FROM selected_files f JOIN graphs g ON g.entry = f.entry
+             WHERE f.repo = ? AND f.commit_id = ? AND f.file = ? AND g.tag = ?",
+        )?;
+        stmt.query_row(
+            [&revision.repo, &revision.commit, file, tag.as_ref()],
+            |r| FileStatus::from_columns(r.get_ref(0)?, r.get_ref(1)?),
+        )
+        .optional()?
+        .unwrap_or(FileStatus::Missing)
+    } else {
+        let mut stmt = conn.prepare_cached(
+            "SELECT g.error, g.partial FROM selected_files f JOIN graphs g ON g.entry = f.entry
+             WHERE f.repo = ? AND f.commit_id = ? AND f.file = ?",
+        )?;
+        stmt.query_row([&revision.repo, &revision.commit, file], |r| {
+            FileStatus::from_columns(r.get_ref(0)?, r.get_ref(1)?)
+        })
+        .optional()?
+        .unwrap_or(FileStatus::Missing)
+    };
+    Ok(result)
+}
+
+fn failed_files<'a>(
+    conns: &'a [Connection],
+    revision: &Revision,
+) -> Result<Files<'a, [String; 2]>> {
+    let stmts = conns
+        .iter()
+        .map(|conn| {
+            conn.prepare(
+                "SELECT f.file, g.tag, g.error, g.partial, g.metadata FROM selected_files f JOIN graphs g ON g.entry = f.entry
+                 WHERE f.repo = ? AND f.commit_id = ? AND g.error IS NOT NULL",
+            )
+        })
+        .collect::<std::result::Result<_, _>>()?;
+    Ok(Files(
+        stmts,
+        [revision.repo.clone(), revision.commit.clone()],
+    ))
+}
+
+fn partial_files<'a>(
+    conns: &'a [Connection],
+    revision: &Revision,
+) -> Result<Files<'a, [String; 2]>> {
+    let stmts = conns
+        .iter()
+        .map(|conn| {
+            conn.prepare(
+                "SELECT f.file, g.tag, g.error, g.partial, g.metadata FROM selected_files f JOIN graphs g ON g.entry = f.entry
+                 WHERE f.repo = ? AND f.commit_id = ? AND g.error IS NULL AND g.partial IS NOT NULL",
+            )
+        })
+        .collect::<std::result::Result<_, _>>()?;
+    Ok(Files(
+        stmts,
+        [revision.repo.clone(), revision.commit.clone()],
+    ))
+}
+
+fn deduplication_stats(conns: &[Connection]) -> Result<DeduplicationStats> {
+    let mut stats = DeduplicationStats::default();
+    for conn in conns {
+        stats += conn.query_row(
+            "SELECT (SELECT COUNT(*) FROM files), (SELECT COUNT(*) FROM graphs)",
+            [],
+            |r| {
+                Ok(DeduplicationStats {
+                    file_count: r.get(0)?,
+                    entry_count: r.get(1)?,
+                })
+            },
+        )?;
+    }
+    Ok(stats)
+}
+
+/// Delete the pre-stitched paths of the given revision.
+fn remove_prestitched_paths(conn: &Connection, revision: &Revision) -> Result<()> {
+    for table in ["prestitched_paths", "prestitched_dependencies"] {
+        let mut stmt = conn.prepare_cached(&format!(
+            "DELETE FROM {} WHERE symbol_stack IN
+             (SELECT id FROM prestitched_symbol_stacks WHERE repo = ? AND commit_id = ?)",
+            table
+        ))?;
+        stmt.execute(revision.params())?;
+    }
+    let mut stmt = conn
+        .prepare_cached("DELETE FROM prestitched_symbol_stacks WHERE repo = ? AND commit_id = ?")?;
+    stmt.execute(revision.params())?;
+    Ok(())
+}
+
+/// Delete the resolved references of the given revision.
+fn remove_resolved_references(conn: &Connection, revision: &Revision) -> Result<()> {
+    for table in ["resolved_references", "resolved_reference_dependencies"] {
+        let mut stmt = conn.prepare_cached(&format!(
+            "DELETE FROM {} WHERE repo = ? AND commit_id = ?",
+            table
+        ))?;
+        stmt.execute(revision.params())?;
+    }
+    Ok(())
+}
+
+/// The condition for artifact dependencies, in a table aliased as `d`, on the given file. They
+/// depend on the file if they used its paths, or if they looked up root paths that match one of
+/// the root paths of the given entry.
+const DEPENDS_ON_FILE: &str = r#"(d.dependency_file = ?3 OR EXISTS (
+    SELECT 1 FROM root_paths p WHERE p.entry = ?4 AND p.symbol_stack LIKE d.root_pattern ESCAPE '\'
+))"#;
+
+/// Delete the pre-stitched paths and resolved references of the given revision that depend on
+/// the given file. The entry, if any, is the new entry of the file, whose root paths may be
+/// looked up by artifacts that did not use the file before.
+fn invalidate_artifacts_for_file(
+    conn: &Connection,
+    revision: &Revision,
+    file: &str,
+    entry: Option<i64>,
+) -> Result<()> {
+    let params = (&revision.repo, &revision.commit, file, entry);
+
+    let symbol_stacks = {
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT DISTINCT s.id FROM prestitched_symbol_stacks s
+             JOIN prestitched_dependencies d ON d.symbol_stack = s.id
+             WHERE s.repo = ?1 AND s.commit_id = ?2 AND {}",
+            DEPENDS_ON_FILE
+        ))?;
+        let ids = stmt
+            .query_map(params, |r| r.get::<_, i64>(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        ids
+    };
+    for table in ["prestitched_paths", "prestitched_dependencies"] {
+        let mut stmt =
+            conn.prepare_cached(&format!("DELETE FROM {} WHERE symbol_stack = ?", table))?;
+        for id in &symbol_stacks {
+            stmt.execute([id])?;
+        }
+    }
+    let mut stmt = conn.prepare_cached("DELETE FROM prestitched_symbol_stacks WHERE id = ?")?;
+    for id in &symbol_stacks {
+        stmt.execute([id])?;
+    }
+
+    let references = {
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT DISTINCT d.file, d.local_id FROM resolved_reference_dependencies d
+             WHERE d.repo = ?1 AND d.commit_id = ?2 AND {}",
+            DEPENDS_ON_FILE
+        ))?;
+        let references = stmt
+            .query_map(params, |r| {
+                Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        references
+    };
+    for table in ["resolved_references", "resolved_reference_dependencies"] {
+        let mut stmt = conn.prepare_cached(&format!(
+            "DELETE FROM {} WHERE repo = ? AND commit_id = ? AND file = ? AND local_id = ?",
+            table
+        ))?;
+        for (file, local_id) in &references {
+            stmt.execute((&revision.repo, &revision.commit, file, local_id))?;
+        }
+    }
+    Ok(())
+}
+
+/// The storage key, serialized paths, and dependencies of a pre-stitched symbol stack.
+type PrestitchedSymbolStack = (String, Vec<Vec<u8>>, ArtifactDependencies);
+
+/// The file, local ID, serialized paths, and dependencies of a resolved reference.
+type ResolvedReference = (String, u32, Vec<u8>, ArtifactDependencies);
+
+/// The dependencies of a pre-stitched symbol stack or resolved reference, which are recorded by
+/// the reader while stitching it.
+#[derive(Debug, Default)]
+struct ArtifactDependencies {
+    /// The files of all paths that were candidates to extend a path.
+    files: BTreeSet<String>,
+    /// The patterns of all symbol stacks for which paths from the root were looked up.
+    root_patterns: BTreeSet<String>,
+}
+
+impl ArtifactDependencies {
+    /// Adds the files of the nodes of the given path.
+    fn add_path_files(&mut self, graph: &StackGraph, partials: &PartialPaths, path: &PartialPath) {
+        let nodes = [path.start_node, path.end_node];
+        let files = nodes.iter().filter_map(|node| graph[*node].file()).chain(
+            path.edges
+                .iter_unordered(partials)
+                .filter_map(|edge| edge.source_node_id.file()),
+        );
+        for file in files {
+            if !self.files.contains(graph[file].name()) {
+                self.files.insert(graph[file].name().to_string());
+            }
+        }
+    }
+
+    /// Returns the dependencies as rows of a dependency file and a root pattern, exactly one of
+    /// which is set.
+    fn rows(&self) -> impl Iterator<Item = (Option<&str>, Option<&str>)> {
+        self.files
+            .iter()
+            .map(|file| (Some(file.as_str()), None))
+            .chain(
+                self.root_patterns
+                    .iter()
+                    .map(|pattern| (None, Some(pattern.as_str()))),
+            )
+    }
+}
+
+/// Returns whether any file of any revision refers to the given entry.
+fn is_entry_used(conn: &Connection, entry: i64) -> Result<bool> {
+    let mut stmt = conn.prepare_cached("SELECT EXISTS (SELECT 1 FROM files WHERE entry = ?)")?;
+    Ok(stmt.query_row([entry], |r| r.get::<_, bool>(0))?)
+}
+
+/// Delete the data of the given entries, if no file refers to them anymore.
+fn release_entries<I: IntoIterator<Item = i64>>(conn: &Connection, entries: I) -> Result<()> {
+    for entry in entries {
+        if is_entry_used(conn, entry)? {
+            continue;
+        }
+        for table in ["file_paths", "root_paths", "graphs"] {
+            let mut stmt =
+                conn.prepare_cached(&format!("DELETE FROM {} WHERE entry = ?", table))?;
+            stmt.execute([entry])?;
+        }
+    }
+    Ok(())
+}
+
+//-------------------------------------------------------------------------------------------------
+// Migrations
+
+/// The oldest database version that can be migrated.
+const OLDEST_MIGRATABLE_VERSION: usize = 6;
+
+/// A migration step, which upgrades a database to the next version.
+type Migration = fn(&Connection) -> Result<()>;
+
+/// The migration steps.  The step at index `i` upgrades a database from version
+/// `OLDEST_MIGRATABLE_VERSION + i` to the next version.
+const MIGRATIONS: [Migration; VERSION - OLDEST_MIGRATABLE_VERSION] = [
+    migrate_to_file_entries,
+    migrate_to_shared_entries,
+    migrate_to_prestitched_paths,
+    migrate_to_root_edge_keys,
+    migrate_to_checkpoints,
+    migrate_to_partial_entries,
+    migrate_to_symbol_namespaces,
+    migrate_to_graph_metadata,
+    migrate_to_file_groups,
+    migrate_to_file_variants,
+    migrate_to_index_times,
+    migrate_to_edge_visibility,
+    migrate_to_string_table,
+    migrate_to_resolved_references,
+    migrate_to_excluded_symbols,
+    migrate_to_libraries,
+    migrate_to_library_versions,
+    migrate_to_artifact_dependencies,
+];
+
+/// Returns the version of the database at the given path.
+pub fn database_version<P: AsRef<Path>>(path: P) -> Result<usize> {
+    let conn = open_existing(path.as_ref())?;
+    read_version(&conn)
+}
+
+/// Returns the library, if the database at the given path is a library database created by
+/// [`SQLiteWriter::seal_library`][].
+pub fn database_library<P: AsRef<Path>>(path: P) -> Result<Option<Library>> {
+    let conn = open_existing(path.as_ref())?;
+    check_version(&conn)?;
+    read_library(&conn)
+}
+
+/// Upgrade the database at the given path to the given version, which cannot be newer than
+/// [`VERSION`][].  Stored graphs and paths are kept, so that files do not have to be indexed
+/// again.  Files of databases that predate revisions are moved to the default revision.  Returns
+/// the version the database had before the upgrade.
+///
+/// All steps are applied in a single transaction, so the database is left unchanged if any of
+/// them fails.  Migrating a database to the version it already has does nothing.
+pub fn migrate<P: AsRef<Path>>(path: P, to_version: usize) -> Result<usize> {
+    let mut conn = open_existing(path.as_ref())?;
+    let tx = conn.transaction()?;
+    let from_version = read_version(&tx)?;
+    if from_version == to_version {
+        return Ok(from_version);
+    }
+    if from_version < OLDEST_MIGRATABLE_VERSION || from_version > to_version || to_version > VERSION
+    {
+        return Err(StorageError::UnsupportedMigration(from_version, to_version));
+    }
+    for version in from_version..to_version {
+        copious_debugging!("--> Migrate database from version {}", version);
+        MIGRATIONS[version - OLDEST_MIGRATABLE_VERSION](&tx)?;
+    }
+    tx.execute("UPDATE metadata SET version = ?", [to_version])?;
+    tx.commit()?;
+    if to_version == VERSION {
+        init_indexes(&mut conn)?;
+    }
+    Ok(from_version)
+}
+
+fn open_existing(path: &Path) -> Result<Connection> {
+    if !path.exists() {
+        return Err(StorageError::MissingDatabase(
+            path.to_string_lossy().to_string(),
+        ));
+    }
+    let conn = Connection::open(path)?;
+    set_pragmas_and_functions(&conn)?;
+    Ok(conn)
+}
+
+/// Version 7 stores data in entries, which are mapped to the files of a revision.
+fn migrate_to_file_entries(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        ALTER TABLE graphs RENAME TO old_graphs;
+        ALTER TABLE file_paths RENAME TO old_file_paths;
+        ALTER TABLE root_paths RENAME TO old_root_paths;
+        CREATE TABLE files (
+            repo      TEXT NOT NULL,
+            commit_id TEXT NOT NULL,
+            file      TEXT NOT NULL,
+            entry     INTEGER NOT NULL,
+            PRIMARY KEY(repo, commit_id, file),
+            FOREIGN KEY(entry) REFERENCES graphs(entry)
+        ) STRICT;
+        CREATE TABLE graphs (
+            entry  INTEGER PRIMARY KEY,
+            file   TEXT NOT NULL,
+            tag    TEXT NOT NULL,
+            error  TEXT,
+            value  BLOB NOT NULL
+        ) STRICT;
+        CREATE TABLE file_paths (
+            entry    INTEGER NOT NULL,
+            local_id INTEGER NOT NULL,
+            value    BLOB NOT NULL,
+            FOREIGN KEY(entry) REFERENCES graphs(entry)
+        ) STRICT;
+        CREATE TABLE root_paths (
+            entry        INTEGER NOT NULL,
+            symbol_stack TEXT NOT NULL,
+            value        BLOB NOT NULL,
+            FOREIGN KEY(entry) REFERENCES graphs(entry)
+        ) STRICT;
+        INSERT INTO graphs (file, tag, error, value)
+            SELECT file, tag, error, value FROM old_graphs ORDER BY file;
+        INSERT INTO files (repo, commit_id, file, entry)
+            SELECT '', '', file, entry FROM graphs;
+        INSERT INTO file_paths (entry, local_id, value)
+            SELECT g.entry, p.local_id, p.value FROM old_file_paths p JOIN graphs g ON g.file = p.file;
+        INSERT INTO root_paths (entry, symbol_stack, value)
+            SELECT g.entry, p.symbol_stack, p.value FROM old_root_paths p JOIN graphs g ON g.file = p.file;
+        DROP TABLE old_file_paths;
+        DROP TABLE old_root_paths;
+        DROP TABLE old_graphs;
+        "#,
+    )?;
+    Ok(())
+}
+
+/// Version 8 stores successfully indexed entries once per file path and tag.  Duplicate entries
+/// are merged into the oldest one.
+fn migrate_to_shared_entries(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TEMPORARY TABLE shared_entries AS
+            SELECT g.entry AS entry, MIN(s.entry) AS shared
+            FROM graphs g JOIN graphs s ON s.file = g.file AND s.tag = g.tag
+            WHERE g.error IS NULL AND s.error IS NULL
+            GROUP BY g.entry;
+        DELETE FROM shared_entries WHERE entry = shared;
+        UPDATE files SET entry = (SELECT shared FROM shared_entries WHERE shared_entries.entry = files.entry)
+            WHERE entry IN (SELECT entry FROM shared_entries);
+        DELETE FROM file_paths WHERE entry IN (SELECT entry FROM shared_entries);
+        DELETE FROM root_paths WHERE entry IN (SELECT entry FROM shared_entries);
+        DELETE FROM graphs WHERE entry IN (SELECT entry FROM shared_entries);
+        DROP TABLE shared_entries;
+        "#,
+    )?;
+    Ok(())
+}
+
+/// Version 9 stores pre-stitched paths for revisions.
+fn migrate_to_prestitched_paths(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE prestitched_symbol_stacks (
+            id           INTEGER PRIMARY KEY,
+            repo         TEXT NOT NULL,
+            commit_id    TEXT NOT NULL,
+            symbol_stack TEXT NOT NULL,
+            UNIQUE(repo, commit_id, symbol_stack)
+        ) STRICT;
+        CREATE TABLE prestitched_paths (
+            symbol_stack INTEGER NOT NULL,
+            value        BLOB NOT NULL,
+            FOREIGN KEY(symbol_stack) REFERENCES prestitched_symbol_stacks(id)
+        ) STRICT;
+        "#,
+    )?;
+    Ok(())
+}
+
+/// Version 10 stores the symbol stack keys of edges into and out of the root node with the
+/// edges of a graph.  Stored graphs are converted to the new encoding, without keys.
+fn migrate_to_root_edge_keys(conn: &Connection) -> Result<()> {
+    let mut select = conn.prepare("SELECT entry, value FROM graphs")?;
+    let mut update = conn.prepare("UPDATE graphs SET value = ? WHERE entry = ?")?;
+    let mut rows = select.query([])?;
+    while let Some(row) = rows.next()? {
+        let entry = row.get::<_, i64>(0)?;
+        let value = row.get::<_, Vec<u8>>(1)?;
+        let (graph, _): (StackGraphV9, usize) = bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+        let graph = StackGraphV12 {
+            files: graph.files,
+            nodes: graph.nodes,
+            edges: EdgesV17 {
+                data: graph
+                    .edges
+                    .data
+                    .into_iter()
+                    .map(|edge| EdgeV17 {
+                        source: edge.source,
+                        sink: edge.sink,
+                        precedence: edge.precedence,
+                        debug_info: edge.debug_info,
+                        root_key: None,
+                    })
+                    .collect(),
+            },
+        };
+        let serialized = bincode::encode_to_vec(&graph, BINCODE_CONFIG)?;
+        update.execute((serialized, entry))?;
     }
+    Ok(())
+}
 
-    /// Get the stack graph, partial paths arena, and path database for the currently loaded data.
-    pub fn get(&mut self) -> (&mut StackGraph, &mut PartialPaths, &mut Database) {
-        (&mut self.graph, &mut self.partials, &mut self.db)
+/// A stack graph in the encoding of database versions before 10.
+#[derive(bincode::Decode)]
+struct StackGraphV9 {
+    files: serde::Files,
+    nodes: NodesV12,
+    edges: EdgesV9,
+}
+
+#[derive(bincode::Decode)]
+struct EdgesV9 {
+    data: Vec<EdgeV9>,
+}
+
+#[derive(bincode::Decode)]
+struct EdgeV9 {
+    source: serde::NodeID,
+    sink: serde::NodeID,
+    precedence: i32,
+    debug_info: Option<serde::DebugInfo>,
+}
+
+/// Version 11 records checkpoints of interrupted indexing runs.
+fn migrate_to_checkpoints(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE checkpoint_files (
+            repo      TEXT NOT NULL,
+            commit_id TEXT NOT NULL,
+            file      TEXT NOT NULL,
+            tag       TEXT NOT NULL,
+            PRIMARY KEY(repo, commit_id, file)
+        ) STRICT;
+        "#,
+    )?;
+    Ok(())
+}
+
+/// Version 12 records why the paths of an entry are incomplete.
+fn migrate_to_partial_entries(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE graphs ADD COLUMN partial TEXT;")?;
+    Ok(())
+}
+
+/// Version 13 stores the namespaces of symbols with the nodes and partial paths.  Stored graphs
+/// and paths are converted to the new encoding, without namespaces.
+fn migrate_to_symbol_namespaces(conn: &Connection) -> Result<()> {
+    let mut select = conn.prepare("SELECT rowid, value FROM graphs")?;
+    let mut update = conn.prepare("UPDATE graphs SET value = ? WHERE rowid = ?")?;
+    let mut rows = select.query([])?;
+    while let Some(row) = rows.next()? {
+        let rowid = row.get::<_, i64>(0)?;
+        let value = row.get::<_, Vec<u8>>(1)?;
+        let (graph, _): (StackGraphV12, usize) =
+            bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+        let graph = StackGraphV13 {
+            files: graph.files,
+            nodes: NodesV20 {
+                data: graph.nodes.data.into_iter().map(NodeV20::from).collect(),
+            },
+            edges: graph.edges,
+        };
+        let serialized = bincode::encode_to_vec(&graph, BINCODE_CONFIG)?;
+        update.execute((serialized, rowid))?;
     }
 
-    /// Return stats about this database reader.
-    pub fn stats(&self) -> Stats {
-        self.stats.clone()
+    for table in ["file_paths", "root_paths", "prestitched_paths"] {
+        let mut select = conn.prepare(&format!("SELECT rowid, value FROM {}", table))?;
+        let mut update =
+            conn.prepare(&format!("UPDATE {} SET value = ? WHERE rowid = ?", table))?;
+        let mut rows = select.query([])?;
+        while let Some(row) = rows.next()? {
+            let rowid = row.get::<_, i64>(0)?;
+            let value = row.get::<_, Vec<u8>>(1)?;
+            let (path, _): (PartialPathV12, usize) =
+                bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+            let serialized =
+                bincode::encode_to_vec(serde::PartialPath::from(path), BINCODE_CONFIG)?;
+            update.execute((serialized, rowid))?;
+        }
     }
+    Ok(())
 }
 
-// Methods for computing keys and patterns for a symbol stack. The format of a storage key is:
-//
-//     has-var GS ( symbol (US symbol)* )?
-//
-// where has-var is "V" if the symbol stack has a variable, "X" otherwise.
-impl PartialSymbolStack {
-    /// Returns a string representation of this symbol stack for indexing in the database.
-    fn storage_key(self, graph: &StackGraph, partials: &mut PartialPaths) -> String {
-        let mut key = String::new();
-        match self.has_variable() {
-            true => key += "V\u{241E}",
-            false => key += "X\u{241E}",
+/// A stack graph in the encoding of database versions 10 to 12, whose symbols have no
+/// namespaces.
+#[derive(bincode::Encode, bincode::Decode)]
+struct StackGraphV12 {
+    files: serde::Files,
+    nodes: NodesV12,
+    edges: EdgesV17,
+}
+
+#[derive(bincode::Encode, bincode::Decode)]
+struct NodesV12 {
+    data: Vec<NodeV12>,
+}
+
+#[derive(bincode::Encode, bincode::Decode)]
+enum NodeV12 {
+    DropScopes {
+        id: serde::NodeID,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    JumpToScope {
+        id: serde::NodeID,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    PopScopedSymbol {
+        id: serde::NodeID,
+        symbol: String,
+        is_definition: bool,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    PopSymbol {
+        id: serde::NodeID,
+        symbol: String,
+        is_definition: bool,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    PushScopedSymbol {
+        id: serde::NodeID,
+        symbol: String,
+        scope: serde::NodeID,
+        is_reference: bool,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    PushSymbol {
+        id: serde::NodeID,
+        symbol: String,
+        is_reference: bool,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    Root {
+        id: serde::NodeID,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    Scope {
+        id: serde::NodeID,
+        is_exported: bool,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+}
+
+impl From<NodeV12> for NodeV20 {
+    fn from(value: NodeV12) -> Self {
+        match value {
+            NodeV12::DropScopes {
+                id,
+                source_info,
+                debug_info,
+            } => Self::DropScopes {
+                id,
+                source_info,
+                debug_info,
+            },
+            NodeV12::JumpToScope {
+                id,
+                source_info,
+                debug_info,
+            } => Self::JumpToScope {
+                id,
+                source_info,
+                debug_info,
+            },
+            NodeV12::PopScopedSymbol {
+                id,
+                symbol,
+                is_definition,
+                source_info,
+                debug_info,
+            } => Self::PopScopedSymbol {
+                id,
+                symbol,
+                namespace: None,
+                is_definition,
+                source_info,
+                debug_info,
+            },
+            NodeV12::PopSymbol {
+                id,
+                symbol,
+                is_definition,
+                source_info,
+                debug_info,
+            } => Self::PopSymbol {
+                id,
+                symbol,
+                namespace: None,
+                is_definition,
+                source_info,
+                debug_info,
+            },
+            NodeV12::PushScopedSymbol {
+                id,
+                symbol,
+                scope,
+                is_reference,
+                source_info,
+                debug_info,
+            } => Self::PushScopedSymbol {
+                id,
+                symbol,
+                namespace: None,
+                scope,
+                is_reference,
+                source_info,
+                debug_info,
+            },
+            NodeV12::PushSymbol {
+                id,
+                symbol,
+                is_reference,
+                source_info,
+                debug_info,
+            } => Self::PushSymbol {
+                id,
+                symbol,
+                namespace: None,
+                is_reference,
+                source_info,
+                debug_info,
+            },
+            NodeV12::Root {
+                id,
+                source_info,
+                debug_info,
+            } => Self::Root {
+                id,
+                source_info,
+                debug_info,
+            },
+            NodeV12::Scope {
+                id,
+                is_exported,
+                source_info,
+                debug_info,
+            } => Self::Scope {
+                id,
+                is_exported,
+                source_info,
+                debug_info,
+            },
         }
-        key += &self
-            .iter(partials)
-            .map(|s| &graph[s.symbol])
-            .join("\u{241F}");
-        key
     }
+}
 
-    /// Returns string representations for all prefixes of this symbol stack for querying the
-    /// index in the database.
-    fn storage_key_patterns(
-        mut self,
-        graph: &StackGraph,
-        partials: &mut PartialPaths,
-    ) -> (Vec<String>, String) {
-        let mut key_patterns = Vec::new();
-        let mut symbols = String::new();
-        while let Some(symbol) = self.pop_front(partials) {
-            if !symbols.is_empty() {
-                symbols += "\u{241F}";
-            }
-            let symbol = graph[symbol.symbol]
-                .replace("%", "\\%")
-                .replace("_", "\\_")
-                .to_string();
-            symbols += &symbol;
-            // patterns for paths matching a prefix of this stack
-            key_patterns.push("V\u{241E}".to_string() + &symbols);
-        }
-        // pattern for paths matching exactly this stack
-        key_patterns.push("X\u{241E}".to_string() + &symbols);
-        if self.has_variable() {
-            // patterns for paths for which this stack is a prefix
-            key_patterns.push("_\u{241E}".to_string() + &symbols + "\u{241F}%");
+/// A partial path in the encoding of database versions before 13, whose symbols have no
+/// namespaces.
+#[derive(bincode::Decode)]
+struct PartialPathV12 {
+    start_node: serde::NodeID,
+    end_node: serde::NodeID,
+    symbol_stack_precondition: PartialSymbolStackV12,
+    symbol_stack_postcondition: PartialSymbolStackV12,
+    scope_stack_precondition: serde::PartialScopeStack,
+    scope_stack_postcondition: serde::PartialScopeStack,
+    edges: serde::PartialPathEdgeList,
+}
+
+#[derive(bincode::Decode)]
+struct PartialSymbolStackV12 {
+    symbols: Vec<PartialScopedSymbolV12>,
+    variable: Option<serde::SymbolStackVariable>,
+}
+
+#[derive(bincode::Decode)]
+struct PartialScopedSymbolV12 {
+    symbol: String,
+    scopes: Option<serde::PartialScopeStack>,
+}
+
+impl From<PartialPathV12> for serde::PartialPath {
+    fn from(value: PartialPathV12) -> Self {
+        Self {
+            start_node: value.start_node,
+            end_node: value.end_node,
+            symbol_stack_precondition: value.symbol_stack_precondition.into(),
+            symbol_stack_postcondition: value.symbol_stack_postcondition.into(),
+            scope_stack_precondition: value.scope_stack_precondition,
+            scope_stack_postcondition: value.scope_stack_postcondition,
+            edges: value.edges,
         }
-        (key_patterns, "\\".to_string())
     }
 }
 
-impl ForwardCandidates<Handle<PartialPath>, PartialPath, Database, StorageError> for SQLiteReader {
-    fn load_forward_candidates(
-        &mut self,
-        path: &PartialPath,
-        cancellation_flag: &dyn CancellationFlag,
-    ) -> std::result::Result<(), StorageError> {
-        self.load_partial_path_extensions(path, cancellation_flag)
+impl From<PartialSymbolStackV12> for serde::PartialSymbolStack {
+    fn from(value: PartialSymbolStackV12) -> Self {
+        Self {
+            symbols: value
+                .symbols
+                .into_iter()
+                .map(|symbol| serde::PartialScopedSymbol {
+                    symbol: symbol.symbol,
+                    namespace: None,
+                    scopes: symbol.scopes,
+                })
+                .collect(),
+            variable: value.variable,
+        }
     }
+}
 
-    fn get_forward_candidates<R>(&mut self, path: &PartialPath, result: &mut R)
-    where
-        R: std::iter::Extend<Handle<PartialPath>>,
-    {
-        self.db
-            .find_candidate_partial_paths(&self.graph, &mut self.partials, path, result);
+/// Version 14 stores the metadata of graphs.  Stored graphs are converted to the new encoding,
+/// without metadata.
+fn migrate_to_graph_metadata(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE graphs ADD COLUMN metadata BLOB;")?;
+    let mut select = conn.prepare("SELECT rowid, value FROM graphs")?;
+    let mut update = conn.prepare("UPDATE graphs SET value = ? WHERE rowid = ?")?;
+    let mut rows = select.query([])?;
+    while let Some(row) = rows.next()? {
+        let rowid = row.get::<_, i64>(0)?;
+        let value = row.get::<_, Vec<u8>>(1)?;
+        let (graph, _): (StackGraphV13, usize) =
+            bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+        let graph = StackGraphV17 {
+            files: graph.files,
+            nodes: graph.nodes,
+            edges: graph.edges,
+            metadata: serde::Metadata::default(),
+        };
+        let serialized = bincode::encode_to_vec(&graph, BINCODE_CONFIG)?;
+        update.execute((serialized, rowid))?;
     }
+    Ok(())
+}
 
-    fn get_joining_candidate_degree(&self, path: &PartialPath) -> Degree {
-        self.db.get_incoming_path_degree(path.end_node)
-    }
+/// A stack graph in the encoding of database version 13, which has no metadata.
+#[derive(bincode::Encode, bincode::Decode)]
+struct StackGraphV13 {
+    files: serde::Files,
+    nodes: NodesV20,
+    edges: EdgesV17,
+}
 
-    fn get_graph_partials_and_db(&mut self) -> (&StackGraph, &mut PartialPaths, &Database) {
-        (&self.graph, &mut self.partials, &self.db)
+/// Version 15 stores the file group of graphs in its own column.  The column is filled from the
+/// stored metadata of the graphs.
+fn migrate_to_file_groups(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE graphs ADD COLUMN file_group TEXT;")?;
+    let mut select = conn.prepare("SELECT rowid, metadata FROM graphs")?;
+    let mut update = conn.prepare("UPDATE graphs SET file_group = ? WHERE rowid = ?")?;
+    let mut rows = select.query([])?;
+    while let Some(row) = rows.next()? {
+        let rowid = row.get::<_, i64>(0)?;
+        let metadata = metadata_from_column(row.get_ref(1)?)?;
+        if let Some(file_group) = metadata.get(FILE_GROUP_METADATA) {
+            update.execute((file_group, rowid))?;
+        }
     }
+    Ok(())
 }
 
-#[derive(Clone, Debug, Default)]
-pub struct Stats {
-    pub file_loads: usize,
-    pub file_cached: usize,
-    pub root_path_loads: usize,
-    pub root_path_cached: usize,
-    pub node_path_loads: usize,
-    pub node_path_cached: usize,
+/// Version 16 stores variants of files.  Existing files and graphs become the default variant.
+fn migrate_to_file_variants(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        ALTER TABLE graphs ADD COLUMN variant TEXT NOT NULL DEFAULT '';
+        DROP INDEX IF EXISTS idx_graphs_key;
+        DROP INDEX IF EXISTS idx_files_entry;
+        ALTER TABLE files RENAME TO old_files;
+        CREATE TABLE files (
+            repo      TEXT NOT NULL,
+            commit_id TEXT NOT NULL,
+            file      TEXT NOT NULL,
+            variant   TEXT NOT NULL DEFAULT '',
+            entry     INTEGER NOT NULL,
+            PRIMARY KEY(repo, commit_id, file, variant),
+            FOREIGN KEY(entry) REFERENCES graphs(entry)
+        ) STRICT;
+        INSERT INTO files (repo, commit_id, file, entry)
+            SELECT repo, commit_id, file, entry FROM old_files;
+        DROP TABLE old_files;
+        "#,
+    )?;
+    Ok(())
 }
 
-impl Stats {
-    fn clear(&mut self) {
-        *self = Stats::default();
+/// Version 17 stores when each entry was indexed.  Existing entries have no index time.
+fn migrate_to_index_times(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        ALTER TABLE graphs ADD COLUMN indexed_at INTEGER;
+        "#,
+    )?;
+    Ok(())
+}
+
+/// Version 18 stores the visibility classes of edges with the edges of a graph.  Stored graphs
+/// are converted to the new encoding, in which all edges are public.
+fn migrate_to_edge_visibility(conn: &Connection) -> Result<()> {
+    let mut select = conn.prepare("SELECT rowid, value FROM graphs")?;
+    let mut update = conn.prepare("UPDATE graphs SET value = ? WHERE rowid = ?")?;
+    let mut rows = select.query([])?;
+    while let Some(row) = rows.next()? {
+        let rowid = row.get::<_, i64>(0)?;
+        let value = row.get::<_, Vec<u8>>(1)?;
+        let (graph, _): (StackGraphV17, usize) =
+            bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+        let graph = StackGraphV20 {
+            files: graph.files,
+            nodes: graph.nodes,
+            edges: serde::Edges {
+                data: graph
+                    .edges
+                    .data
+                    .into_iter()
+                    .map(|edge| serde::Edge {
+                        source: edge.source,
+                        sink: edge.sink,
+                        precedence: edge.precedence,
+                        debug_info: edge.debug_info,
+                        root_key: edge.root_key,
+                        visibility: None,
+                    })
+                    .collect(),
+            },
+            metadata: graph.metadata,
+        };
+        let serialized = bincode::encode_to_vec(&graph, BINCODE_CONFIG)?;
+        update.execute((serialized, rowid))?;
     }
+    Ok(())
+}
 
-    fn clear_paths(&mut self) {
-        *self = Stats {
-            file_loads: self.file_loads,
-            file_cached: self.file_cached,
-            ..Stats::default()
-        }
+/// A stack graph in the encoding of database versions 14 to 17, whose edges have no visibility.
+#[derive(bincode::Encode, bincode::Decode)]
+struct StackGraphV17 {
+    files: serde::Files,
+    nodes: NodesV20,
+    edges: EdgesV17,
+    metadata: serde::Metadata,
+}
+
+#[derive(bincode::Encode, bincode::Decode)]
+struct EdgesV17 {
+    data: Vec<EdgeV17>,
+}
+
+#[derive(bincode::Encode, bincode::Decode)]
+struct EdgeV17 {
+    source: serde::NodeID,
+    sink: serde::NodeID,
+    precedence: i32,
+    debug_info: Option<serde::DebugInfo>,
+    root_key: Option<Vec<String>>,
+}
+
+/// Version 19 stores the strings of graphs once in the strings table, which graphs refer to.
+fn migrate_to_string_table(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE strings (
+            id    INTEGER PRIMARY KEY,
+            value TEXT NOT NULL UNIQUE
+        ) STRICT;
+        "#,
+    )?;
+    let mut select = conn.prepare("SELECT rowid, value FROM graphs")?;
+    let mut update = conn.prepare("UPDATE graphs SET value = ? WHERE rowid = ?")?;
+    let mut rows = select.query([])?;
+    while let Some(row) = rows.next()? {
+        let rowid = row.get::<_, i64>(0)?;
+        let value = row.get::<_, Vec<u8>>(1)?;
+        let (graph, _): (StackGraphV20, usize) =
+            bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+        let mut graph = serde::StackGraph::from(graph);
+        let (strings, references) = take_graph_strings(conn, &mut graph)?;
+        let stored = StoredGraphV20 {
+            strings,
+            references,
+            graph: graph.into(),
+        };
+        let serialized = bincode::encode_to_vec(&stored, BINCODE_CONFIG)?;
+        update.execute((serialized, rowid))?;
     }
+    Ok(())
 }
 
-/// Check if the database has the version supported by this library version.
-fn check_version(conn: &Connection) -> Result<()> {
-    let version = conn.query_row("SELECT version FROM metadata", [], |r| r.get::<_, usize>(0))?;
-    if version != VERSION {
-        return Err(StorageError::IncorrectVersion(version));
+/// Version 20 stores the resolved references of selected files of a revision.
+fn migrate_to_resolved_references(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE resolved_references (
+            repo      TEXT NOT NULL,
+            commit_id TEXT NOT NULL,
+            file      TEXT NOT NULL,
+            local_id  INTEGER NOT NULL,
+            value     BLOB NOT NULL,
+            PRIMARY KEY(repo, commit_id, file, local_id)
+        ) STRICT;
+        "#,
+    )?;
+    Ok(())
+}
+
+/// Version 21 stores the excluded symbols of scope nodes.  Stored graphs are converted to the new
+/// encoding, without excluded symbols.
+fn migrate_to_excluded_symbols(conn: &Connection) -> Result<()> {
+    let mut select = conn.prepare("SELECT rowid, value FROM graphs")?;
+    let mut update = conn.prepare("UPDATE graphs SET value = ? WHERE rowid = ?")?;
+    let mut rows = select.query([])?;
+    while let Some(row) = rows.next()? {
+        let rowid = row.get::<_, i64>(0)?;
+        let value = row.get::<_, Vec<u8>>(1)?;
+        let (stored, _): (StoredGraphV20, usize) =
+            bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+        let stored = StoredGraph {
+            strings: stored.strings,
+            references: stored.references,
+            graph: stored.graph.into(),
+        };
+        let serialized = bincode::encode_to_vec(&stored, BINCODE_CONFIG)?;
+        update.execute((serialized, rowid))?;
     }
     Ok(())
 }
 
-fn set_pragmas_and_functions(conn: &Connection) -> Result<()> {
-    conn.execute_batch(PRAGMAS)?;
-    conn.create_scalar_function(
-        "path_descendant_of",
-        2,
-        FunctionFlags::SQLITE_DETERMINISTIC | FunctionFlags::SQLITE_UTF8,
-        move |ctx| {
-            assert_eq!(ctx.len(), 2, "called with unexpected number of arguments");
-            let path = PathBuf::from(ctx.get::<String>(0)?);
-            let parent = PathBuf::from(ctx.get::<String>(1)?);
-            let result = path.starts_with(&parent);
-            Ok(result)
-        },
+/// Version 22 records the name of library databases.
+fn migrate_to_libraries(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE library (
+            name TEXT NOT NULL
+        ) STRICT;
+        "#,
     )?;
     Ok(())
 }
 
-fn init_indexes(conn: &mut Connection) -> Result<()> {
-    let tx = conn.transaction()?;
-    tx.execute_batch(INDEXES)?;
-    tx.commit()?;
+/// Version 23 records the version of library databases.
+fn migrate_to_library_versions(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        ALTER TABLE library ADD COLUMN version TEXT NOT NULL DEFAULT '';
+        "#,
+    )?;
     Ok(())
 }
 
-fn status_for_file<T: AsRef<str>>(
-    conn: &Connection,
-    file: &str,
-    tag: Option<T>,
-) -> Result<FileStatus> {
-    let result = if let Some(tag) = tag {
-        let mut stmt =
-            conn.prepare_cached("SELECT error FROM graphs WHERE file = ? AND tag = ?")?;
-        stmt.query_row([file, tag.as_ref()], |r| r.get_ref(0).map(FileStatus::from))
-            .optional()?
-            .unwrap_or(FileStatus::Missing)
-    } else {
-        let mut stmt = conn.prepare_cached("SELECT status FROM graphs WHERE file = ?")?;
-        stmt.query_row([file], |r| r.get_ref(0).map(FileStatus::from))
-            .optional()?
-            .unwrap_or(FileStatus::Missing)
-    };
-    Ok(result)
+/// Version 24 records the dependencies of pre-stitched paths and resolved references, so that they
+/// can be invalidated when the files they depend on change.  Existing pre-stitched paths and
+/// resolved references have no recorded dependencies, and are removed.
+fn migrate_to_artifact_dependencies(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        DELETE FROM prestitched_paths;
+        DELETE FROM prestitched_symbol_stacks;
+        DELETE FROM resolved_references;
+        CREATE TABLE prestitched_dependencies (
+            symbol_stack    INTEGER NOT NULL,
+            dependency_file TEXT,
+            root_pattern    TEXT,
+            FOREIGN KEY(symbol_stack) REFERENCES prestitched_symbol_stacks(id)
+        ) STRICT;
+        CREATE TABLE resolved_reference_dependencies (
+            repo            TEXT NOT NULL,
+            commit_id       TEXT NOT NULL,
+            file            TEXT NOT NULL,
+            local_id        INTEGER NOT NULL,
+            dependency_file TEXT,
+            root_pattern    TEXT
+        ) STRICT;
+        "#,
+    )?;
+    Ok(())
+}
+
+/// A stored graph in the encoding of database versions 19 and 20.
+#[derive(bincode::Encode, bincode::Decode)]
+struct StoredGraphV20 {
+    strings: Vec<i64>,
+    references: Vec<u32>,
+    graph: StackGraphV20,
+}
+
+/// A stack graph in the encoding of database versions 18 to 20, whose scope nodes have no
+/// excluded symbols.  The strings of the graph are visited in the same order as those of the
+/// new encoding, because the new encoding only adds strings for excluded symbols.
+#[derive(bincode::Encode, bincode::Decode)]
+struct StackGraphV20 {
+    files: serde::Files,
+    nodes: NodesV20,
+    edges: serde::Edges,
+    metadata: serde::Metadata,
+}
+
+impl From<StackGraphV20> for serde::StackGraph {
+    fn from(value: StackGraphV20) -> Self {
+        Self {
+            files: value.files,
+            nodes: serde::Nodes {
+                data: value
+                    .nodes
+                    .data
+                    .into_iter()
+                    .map(serde::Node::from)
+                    .collect(),
+            },
+            edges: value.edges,
+            metadata: value.metadata,
+        }
+    }
+}
+
+impl From<serde::StackGraph> for StackGraphV20 {
+    fn from(value: serde::StackGraph) -> Self {
+        Self {
+            files: value.files,
+            nodes: NodesV20 {
+                data: value.nodes.data.into_iter().map(NodeV20::from).collect(),
+            },
+            edges: value.edges,
+            metadata: value.metadata,
+        }
+    }
+}
+
+#[derive(bincode::Encode, bincode::Decode)]
+struct NodesV20 {
+    data: Vec<NodeV20>,
+}
+
+#[derive(bincode::Encode, bincode::Decode)]
+enum NodeV20 {
+    DropScopes {
+        id: serde::NodeID,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    JumpToScope {
+        id: serde::NodeID,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    PopScopedSymbol {
+        id: serde::NodeID,
+        symbol: String,
+        namespace: Option<String>,
+        is_definition: bool,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    PopSymbol {
+        id: serde::NodeID,
+        symbol: String,
+        namespace: Option<String>,
+        is_definition: bool,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    PushScopedSymbol {
+        id: serde::NodeID,
+        symbol: String,
+        namespace: Option<String>,
+        scope: serde::NodeID,
+        is_reference: bool,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    PushSymbol {
+        id: serde::NodeID,
+        symbol: String,
+        namespace: Option<String>,
+        is_reference: bool,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    Root {
+        id: serde::NodeID,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    Scope {
+        id: serde::NodeID,
+        is_exported: bool,
+        source_info: Option<serde::SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+}
+
+impl From<NodeV20> for serde::Node {
+    fn from(value: NodeV20) -> Self {
+        match value {
+            NodeV20::DropScopes {
+                id,
+                source_info,
+                debug_info,
+            } => Self::DropScopes {
+                id,
+                source_info,
+                debug_info,
+            },
+            NodeV20::JumpToScope {
+                id,
+                source_info,
+                debug_info,
+            } => Self::JumpToScope {
+                id,
+                source_info,
+                debug_info,
+            },
+            NodeV20::PopScopedSymbol {
+                id,
+                symbol,
+                namespace,
+                is_definition,
+                source_info,
+                debug_info,
+            } => Self::PopScopedSymbol {
+                id,
+                symbol,
+                namespace,
+                is_definition,
+                source_info,
+                debug_info,
+            },
+            NodeV20::PopSymbol {
+                id,
+                symbol,
+                namespace,
+                is_definition,
+                source_info,
+                debug_info,
+            } => Self::PopSymbol {
+                id,
+                symbol,
+                namespace,
+                is_definition,
+                source_info,
+                debug_info,
+            },
+            NodeV20::PushScopedSymbol {
+                id,
+                symbol,
+                namespace,
+                scope,
+                is_reference,
+                source_info,
+                debug_info,
+            } => Self::PushScopedSymbol {
+                id,
+                symbol,
+                namespace,
+                scope,
+                is_reference,
+                source_info,
+                debug_info,
+            },
+            NodeV20::PushSymbol {
+                id,
+                symbol,
+                namespace,
+                is_reference,
+                source_info,
+                debug_info,
+            } => Self::PushSymbol {
+                id,
+                symbol,
+                namespace,
+                is_reference,
+                source_info,
+                debug_info,
+            },
+            NodeV20::Root {
+                id,
+                source_info,
+                debug_info,
+            } => Self::Root {
+                id,
+                source_info,
+                debug_info,
+            },
+            NodeV20::Scope {
+                id,
+                is_exported,
+                source_info,
+                debug_info,
+            } => Self::Scope {
+                id,
+                is_exported,
+                excluded_symbols: None,
+                source_info,
+                debug_info,
+            },
+        }
+    }
+}
+
+impl From<serde::Node> for NodeV20 {
+    fn from(value: serde::Node) -> Self {
+        match value {
+            serde::Node::DropScopes {
+                id,
+                source_info,
+                debug_info,
+            } => Self::DropScopes {
+                id,
+                source_info,
+                debug_info,
+            },
+            serde::Node::JumpToScope {
+                id,
+                source_info,
+                debug_info,
+            } => Self::JumpToScope {
+                id,
+                source_info,
+                debug_info,
+            },
+            serde::Node::PopScopedSymbol {
+                id,
+                symbol,
+                namespace,
+                is_definition,
+                source_info,
+                debug_info,
+            } => Self::PopScopedSymbol {
+                id,
+                symbol,
+                namespace,
+                is_definition,
+                source_info,
+                debug_info,
+            },
+            serde::Node::PopSymbol {
+                id,
+                symbol,
+                namespace,
+                is_definition,
+                source_info,
+                debug_info,
+            } => Self::PopSymbol {
+                id,
+                symbol,
+                namespace,
+                is_definition,
+                source_info,
+                debug_info,
+            },
+            serde::Node::PushScopedSymbol {
+                id,
+                symbol,
+                namespace,
+                scope,
+                is_reference,
+                source_info,
+                debug_info,
+            } => Self::PushScopedSymbol {
+                id,
+                symbol,
+                namespace,
+                scope,
+                is_reference,
+                source_info,
+                debug_info,
+            },
+            serde::Node::PushSymbol {
+                id,
+                symbol,
+                namespace,
+                is_reference,
+                source_info,
+                debug_info,
+            } => Self::PushSymbol {
+                id,
+                symbol,
+                namespace,
+                is_reference,
+                source_info,
+                debug_info,
+            },
+            serde::Node::Root {
+                id,
+                source_info,
+                debug_info,
+            } => Self::Root {
+                id,
+                source_info,
+                debug_info,
+            },
+            serde::Node::Scope {
+                id,
+                is_exported,
+                source_info,
+                debug_info,
+                ..
+            } => Self::Scope {
+                id,
+                is_exported,
+                source_info,
+                debug_info,
+            },
+        }
+    }
 }
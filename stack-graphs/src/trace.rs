@@ -0,0 +1,440 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Traces of the decisions made during path stitching.
+//!
+//! A stitcher records a trace when enabled with
+//! [`ForwardPartialPathStitcher::set_collect_trace`][crate::stitching::ForwardPartialPathStitcher::set_collect_trace]
+//! or [`StitcherConfig::with_collect_trace`][crate::stitching::StitcherConfig::with_collect_trace].
+//! The trace lists every candidate that was considered for extending a path, and whether the
+//! candidate was accepted, or why it was rejected. Traces refer to nodes by their IDs instead of
+//! handles, so that they can be serialized, for example to JSON, and inspected independently of
+//! the stitcher. Traces can be replayed in the HTML visualization of a stack graph.
+//!
+//! To reproduce stitching problems offline, a trace can be stored together with the part of the
+//! stack graph it refers to in a [`TraceRecording`][]. A [`TraceReplay`][] steps through the
+//! recorded decisions, re-executes every candidate extension on the recorded subgraph, and
+//! reports the decisions whose outcome differs from the recorded one.
+
+use std::collections::BTreeSet;
+
+use crate::graph::StackGraph;
+use crate::partial::PartialPaths;
+use crate::paths::PathResolutionError;
+use crate::serde::Error;
+use crate::serde::NodeID;
+use crate::serde::PartialPath;
+use crate::source::Excerpt;
+use crate::source::SourceProvider;
+use crate::stitching::Appendable;
+use crate::stitching::PathProvenance;
+
+/// A trace of the decisions made by a stitcher, in the order they were made.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct StitchingTrace {
+    pub events: Vec<TraceEvent>,
+}
+
+impl StitchingTrace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the files of all nodes this trace refers to.
+    pub fn files(&self) -> BTreeSet<&str> {
+        let mut files = BTreeSet::new();
+        for event in &self.events {
+            match event {
+                TraceEvent::Phase { paths } => {
+                    for path in paths {
+                        files.extend(path.files());
+                    }
+                }
+                TraceEvent::NotExtended { path } | TraceEvent::Cyclic { path } => {
+                    files.extend(path.files());
+                }
+                TraceEvent::Candidate {
+                    path,
+                    candidate,
+                    outcome,
+                } => {
+                    files.extend(path.files());
+                    match candidate.as_ref() {
+                        TraceCandidate::Edge { source, sink, .. } => {
+                            files.extend(source.file.as_deref());
+                            files.extend(sink.file.as_deref());
+                        }
+                        TraceCandidate::PartialPath { path, .. } => files.extend(path.files()),
+                        TraceCandidate::Other {
+                            start_node,
+                            end_node,
+                        } => {
+                            files.extend(start_node.file.as_deref());
+                            files.extend(end_node.file.as_deref());
+                        }
+                    }
+                    match outcome.as_ref() {
+                        TraceOutcome::Accepted { extension }
+                        | TraceOutcome::TooManySimilar { extension } => {
+                            files.extend(extension.files())
+                        }
+                        TraceOutcome::Invalid { .. } => {}
+                    }
+                }
+            }
+        }
+        files
+    }
+}
+
+/// A decision made by a stitcher.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum TraceEvent {
+    /// A phase started with the given queued paths. The queue of the first phase contains the
+    /// initial paths of the stitcher.
+    Phase { paths: Vec<PartialPath> },
+    /// The path was not extended, because the phase's `extend_while` predicate rejected it.
+    NotExtended { path: PartialPath },
+    /// The path was not extended, because it is cyclic.
+    Cyclic { path: PartialPath },
+    /// The candidate was considered for extending the path.
+    Candidate {
+        path: PartialPath,
+        candidate: Box<TraceCandidate>,
+        outcome: Box<TraceOutcome>,
+    },
+}
+
+/// A candidate that was considered for extending a path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum TraceCandidate {
+    /// A graph edge.
+    Edge {
+        source: NodeID,
+        sink: NodeID,
+        precedence: i32,
+    },
+    /// A partial path, and where it came from, if known.
+    PartialPath {
+        path: PartialPath,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "Option::is_none")
+        )]
+        provenance: Option<Box<PathProvenance>>,
+    },
+    /// Any other appendable, of which only the start and end nodes are known.
+    Other {
+        start_node: NodeID,
+        end_node: NodeID,
+    },
+}
+
+/// The outcome of considering a candidate for extending a path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum TraceOutcome {
+    /// The candidate was appended, resulting in the extension, which is queued for the next phase.
+    Accepted { extension: PartialPath },
+    /// The candidate could not be appended, because the path does not satisfy its preconditions.
+    /// The reason is the name of the [`PathResolutionError`][crate::paths::PathResolutionError].
+    Invalid { reason: String },
+    /// The candidate was appended, but the extension was dropped, because too many similar paths
+    /// were found already.
+    TooManySimilar { extension: PartialPath },
+}
+
+impl std::fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Phase { paths } => write!(f, "phase with {} queued paths", paths.len()),
+            Self::NotExtended { path } => write!(f, "not extended {}", path.display_nodes()),
+            Self::Cyclic { path } => write!(f, "cyclic {}", path.display_nodes()),
+            Self::Candidate {
+                path,
+                candidate,
+                outcome,
+            } => write!(
+                f,
+                "extend {} with {}: {}",
+                path.display_nodes(),
+                candidate,
+                outcome
+            ),
+        }
+    }
+}
+
+impl TraceEvent {
+    /// Returns a displayable version of this event, which includes an excerpt of the source code
+    /// at the end of the path the event is about, if the node exists in the given graph, and its
+    /// source text is supplied by the given provider.
+    pub fn display_with_source<'a>(
+        &'a self,
+        graph: &'a StackGraph,
+        source: &'a dyn SourceProvider,
+    ) -> impl std::fmt::Display + 'a {
+        struct Displayer<'a>(&'a TraceEvent, &'a StackGraph, &'a dyn SourceProvider);
+        impl std::fmt::Display for Displayer<'_> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let (event, graph, source) = (self.0, self.1, self.2);
+                write!(f, "{}", event)?;
+                let path = match event {
+                    TraceEvent::Phase { .. } => return Ok(()),
+                    TraceEvent::NotExtended { path }
+                    | TraceEvent::Cyclic { path }
+                    | TraceEvent::Candidate { path, .. } => path,
+                };
+                let excerpt = path
+                    .end_node
+                    .to_node_id(graph)
+                    .ok()
+                    .and_then(|id| graph.node_for_id(id))
+                    .and_then(|node| Excerpt::from_node(graph, source, node));
+                if let Some(excerpt) = excerpt {
+                    write!(f, "\n{}", excerpt)?;
+                }
+                Ok(())
+            }
+        }
+        Displayer(self, graph, source)
+    }
+}
+
+impl std::fmt::Display for TraceCandidate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Edge { source, sink, .. } => write!(f, "edge {} -> {}", source, sink),
+            Self::PartialPath { path, provenance } => {
+                write!(f, "path {}", path.display_nodes())?;
+                if let Some(provenance) = provenance {
+                    write!(f, " (indexed from {}", provenance.file)?;
+                    if let Some(rules_version) = &provenance.rules_version {
+                        write!(f, " with rules {}", rules_version)?;
+                    }
+                    if let Some(indexed_at) = provenance.indexed_at {
+                        write!(f, " at {}", indexed_at)?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            Self::Other {
+                start_node,
+                end_node,
+            } => write!(f, "{} -> {}", start_node, end_node),
+        }
+    }
+}
+
+impl std::fmt::Display for TraceOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Accepted { extension } => write!(f, "accepted {}", extension.display_nodes()),
+            Self::Invalid { reason } => write!(f, "invalid ({})", reason),
+            Self::TooManySimilar { extension } => {
+                write!(f, "too many similar {}", extension.display_nodes())
+            }
+        }
+    }
+}
+
+impl PartialPath {
+    fn display_nodes(&self) -> String {
+        format!("{} -> {}", self.start_node, self.end_node)
+    }
+}
+
+impl TraceOutcome {
+    fn from_result(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        result: Result<crate::partial::PartialPath, PathResolutionError>,
+    ) -> Self {
+        match result {
+            Ok(extension) => Self::Accepted {
+                extension: PartialPath::from_partial_path(graph, partials, &extension),
+            },
+            Err(err) => Self::Invalid {
+                reason: format!("{:?}", err),
+            },
+        }
+    }
+
+    /// Returns whether this outcome, as replayed, agrees with the recorded outcome. Similar path
+    /// detection is not replayed, so a replayed acceptance agrees with a recorded rejection
+    /// because of too many similar paths, if the extensions are the same.
+    fn agrees_with(&self, recorded: &TraceOutcome) -> bool {
+        match (self, recorded) {
+            (Self::Accepted { extension }, TraceOutcome::TooManySimilar { extension: other }) => {
+                extension == other
+            }
+            _ => self == recorded,
+        }
+    }
+}
+
+/// A stitching trace, together with the part of the stack graph it refers to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct TraceRecording {
+    pub graph: crate::serde::StackGraph,
+    pub trace: StitchingTrace,
+}
+
+impl TraceRecording {
+    /// Creates a recording of the given trace, which includes all files of the graph the trace
+    /// refers to.
+    pub fn new(graph: &StackGraph, trace: StitchingTrace) -> Self {
+        let files = trace.files();
+        let filter = |g: &StackGraph, f: &crate::arena::Handle<crate::graph::File>| {
+            files.contains(g[*f].name())
+        };
+        let graph = crate::serde::StackGraph::from_graph_filter(graph, &filter);
+        Self { graph, trace }
+    }
+}
+
+/// Steps through the decisions of a recorded stitching trace.
+///
+/// Every candidate event is re-executed, by appending the recorded candidate to the recorded
+/// path, and the replayed outcome is compared to the recorded one. Other events, such as phases
+/// and cyclic paths, are reported as recorded. Replaying a trace is deterministic, and does not
+/// require access to the database the trace was recorded from.
+pub struct TraceReplay {
+    graph: StackGraph,
+    partials: PartialPaths,
+    events: std::vec::IntoIter<TraceEvent>,
+    index: usize,
+}
+
+impl TraceReplay {
+    /// Creates a replay of the given recording, by loading the recorded subgraph.
+    pub fn new(recording: TraceRecording) -> Result<Self, Error> {
+        let mut graph = StackGraph::new();
+        recording.graph.load_into(&mut graph)?;
+        Ok(Self {
+            graph,
+            partials: PartialPaths::new(),
+            events: recording.trace.events.into_iter(),
+            index: 0,
+        })
+    }
+
+    /// Returns the stack graph the trace is replayed on.
+    pub fn graph(&self) -> &StackGraph {
+        &self.graph
+    }
+
+    /// Replays the next event of the trace, or returns `None` if all events have been replayed.
+    pub fn step(&mut self) -> Option<Result<ReplayStep, Error>> {
+        let event = self.events.next()?;
+        let index = self.index;
+        self.index += 1;
+        let replayed = match &event {
+            TraceEvent::Candidate {
+                path, candidate, ..
+            } => match self.replay_candidate(path, candidate) {
+                Ok(replayed) => replayed,
+                Err(err) => return Some(Err(err)),
+            },
+            _ => None,
+        };
+        Some(Ok(ReplayStep {
+            index,
+            event,
+            replayed,
+        }))
+    }
+
+    /// Replays all remaining events of the trace, and returns the steps whose replayed outcome
+    /// differs from the recorded one.
+    pub fn divergences(&mut self) -> Result<Vec<ReplayStep>, Error> {
+        let mut divergences = Vec::new();
+        while let Some(step) = self.step() {
+            let step = step?;
+            if step.diverges() {
+                divergences.push(step);
+            }
+        }
+        Ok(divergences)
+    }
+
+    fn replay_candidate(
+        &mut self,
+        path: &PartialPath,
+        candidate: &TraceCandidate,
+    ) -> Result<Option<TraceOutcome>, Error> {
+        let graph = &mut self.graph;
+        let partials = &mut self.partials;
+        let mut path = path.to_partial_path(graph, partials)?;
+        let result = match candidate {
+            TraceCandidate::Edge {
+                source,
+                sink,
+                precedence,
+            } => {
+                let edge = crate::graph::Edge {
+                    source: source.to_node(graph)?,
+                    sink: sink.to_node(graph)?,
+                    precedence: *precedence,
+                };
+                edge.append_to(graph, partials, &mut path)
+            }
+            TraceCandidate::PartialPath {
+                path: candidate, ..
+            } => {
+                let candidate = candidate.to_partial_path(graph, partials)?;
+                candidate.append_to(graph, partials, &mut path)
+            }
+            TraceCandidate::Other { .. } => return Ok(None),
+        };
+        Ok(Some(TraceOutcome::from_result(
+            graph,
+            partials,
+            result.map(|()| path),
+        )))
+    }
+}
+
+/// A replayed event of a stitching trace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayStep {
+    /// The index of the event in the trace.
+    pub index: usize,
+    /// The recorded event.
+    pub event: TraceEvent,
+    /// The outcome of re-executing the event, if it is a candidate event that can be replayed.
+    pub replayed: Option<TraceOutcome>,
+}
+
+impl ReplayStep {
+    /// Returns whether the replayed outcome differs from the recorded outcome.
+    pub fn diverges(&self) -> bool {
+        match (&self.event, &self.replayed) {
+            (TraceEvent::Candidate { outcome, .. }, Some(replayed)) => {
+                !replayed.agrees_with(outcome)
+            }
+            _ => false,
+        }
+    }
+}
@@ -5,6 +5,17 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+/// Returns the 64-bit FNV-1a hash of the given bytes. Unlike the standard library hasher, the
+/// hash is stable across platforms and releases, so it can be persisted.
+pub(crate) fn stable_hash<I: IntoIterator<Item = u8>>(bytes: I) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
 pub(crate) fn equals_option<A, B, F>(a: Option<A>, b: Option<B>, mut eq: F) -> bool
 where
     F: FnMut(A, B) -> bool,
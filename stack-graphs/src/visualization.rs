@@ -5,6 +5,10 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::fmt::Write;
+
 use serde_json::Error;
 
 use crate::arena::Handle;
@@ -14,7 +18,10 @@ use crate::graph::StackGraph;
 use crate::partial::PartialPath;
 use crate::partial::PartialPaths;
 use crate::serde::Filter;
+use crate::source::Excerpt;
+use crate::source::SourceProvider;
 use crate::stitching::Database;
+use crate::trace::StitchingTrace;
 
 static CSS: &'static str = include_str!("visualization/visualization.css");
 static D3: &'static str = include_str!("visualization/d3.min.js");
@@ -34,10 +41,64 @@ impl StackGraph {
         partials: &mut PartialPaths,
         db: &mut Database,
         filter: &dyn Filter,
+    ) -> Result<String, Error> {
+        self.to_html_string_inner(title, partials, db, filter, None, None)
+    }
+
+    /// Renders the graph as an HTML page, like [`to_html_string`][Self::to_html_string], in which
+    /// the given stitching trace can be stepped through.
+    pub fn to_html_string_with_trace(
+        &self,
+        title: &str,
+        partials: &mut PartialPaths,
+        db: &mut Database,
+        filter: &dyn Filter,
+        trace: &StitchingTrace,
+    ) -> Result<String, Error> {
+        self.to_html_string_inner(title, partials, db, filter, Some(trace), None)
+    }
+
+    /// Renders the graph as an HTML page, like [`to_html_string`][Self::to_html_string], which
+    /// shows the source line of nodes, for the files whose source text is supplied by the given
+    /// provider. If a stitching trace is given, it can be stepped through.
+    pub fn to_html_string_with_source(
+        &self,
+        title: &str,
+        partials: &mut PartialPaths,
+        db: &mut Database,
+        filter: &dyn Filter,
+        trace: Option<&StitchingTrace>,
+        source: &dyn SourceProvider,
+    ) -> Result<String, Error> {
+        self.to_html_string_inner(title, partials, db, filter, trace, Some(source))
+    }
+
+    fn to_html_string_inner(
+        &self,
+        title: &str,
+        partials: &mut PartialPaths,
+        db: &mut Database,
+        filter: &dyn Filter,
+        trace: Option<&StitchingTrace>,
+        source: Option<&dyn SourceProvider>,
     ) -> Result<String, Error> {
         let filter = VisualizationFilter(filter);
         let graph = serde_json::to_string(&self.to_serializable_filter(&filter))?;
         let paths = serde_json::to_string(&db.to_serializable_filter(self, partials, &filter))?;
+        let trace = serde_json::to_string(&trace)?;
+        let mut sources = BTreeMap::new();
+        if let Some(source) = source {
+            for file in self.iter_files() {
+                if !filter.include_file(self, &file) {
+                    continue;
+                }
+                let name = self[file].name();
+                if let Some(text) = source.source(name) {
+                    sources.insert(name, text);
+                }
+            }
+        }
+        let sources = serde_json::to_string(&sources)?;
         let html = format!(
             r#"
 <!DOCTYPE html>
@@ -89,7 +150,7 @@ impl StackGraph {
   </div>
   <script type="text/javascript">
     const container = d3.select("\#container");
-    new StackGraph(container, graph, paths, {{ version: "{PKG} {VERSION}" }});
+    new StackGraph(container, graph, paths, {{ version: "{PKG} {VERSION}", trace: {trace}, sources: {sources} }});
   </script>
 </body>
 
@@ -98,6 +159,125 @@ impl StackGraph {
         );
         Ok(html)
     }
+
+    /// Renders the graph in Graphviz DOT format.  Edges that are part of any of the paths in the
+    /// database are highlighted.
+    pub fn to_dot_string(
+        &self,
+        title: &str,
+        partials: &mut PartialPaths,
+        db: &Database,
+        filter: &dyn Filter,
+    ) -> String {
+        self.to_dot_string_inner(title, partials, db, filter, None)
+    }
+
+    /// Renders the graph in Graphviz DOT format, like [`to_dot_string`][Self::to_dot_string],
+    /// where the labels of nodes include their source line, for the files whose source text is
+    /// supplied by the given provider.
+    pub fn to_dot_string_with_source(
+        &self,
+        title: &str,
+        partials: &mut PartialPaths,
+        db: &Database,
+        filter: &dyn Filter,
+        source: &dyn SourceProvider,
+    ) -> String {
+        self.to_dot_string_inner(title, partials, db, filter, Some(source))
+    }
+
+    fn to_dot_string_inner(
+        &self,
+        title: &str,
+        partials: &mut PartialPaths,
+        db: &Database,
+        filter: &dyn Filter,
+        source: Option<&dyn SourceProvider>,
+    ) -> String {
+        let filter = VisualizationFilter(filter);
+
+        let mut highlighted = HashSet::new();
+        for handle in db.iter_partial_paths() {
+            let path = &db[handle];
+            if !filter.include_partial_path(self, partials, path) {
+                continue;
+            }
+            let mut previous = None;
+            for edge in path.edges.iter(partials) {
+                let node = match self.node_for_id(edge.source_node_id) {
+                    Some(node) => node,
+                    None => continue,
+                };
+                if let Some(previous) = previous.replace(node) {
+                    highlighted.insert((previous, node));
+                }
+            }
+            if let Some(previous) = previous {
+                highlighted.insert((previous, path.end_node));
+            }
+        }
+
+        let mut dot = String::new();
+        // Writing to a String cannot fail, so we ignore the results below.
+        let _ = writeln!(dot, "digraph \"{}\" {{", escape_dot(title));
+        let _ = writeln!(dot, "  node [shape=box, fontname=monospace];");
+        for node in self.iter_nodes() {
+            if !filter.include_node(self, &node) {
+                continue;
+            }
+            let mut label = escape_dot(&node.display(self).to_string());
+            if let Some(excerpt) = source.and_then(|source| Excerpt::from_node(self, source, node))
+            {
+                label.push_str("\\n");
+                label.push_str(&escape_dot(excerpt.trimmed_line()));
+            }
+            let shape = if self[node].is_definition() || self[node].is_reference() {
+                "box, style=bold"
+            } else if self[node].is_root() || self[node].is_jump_to() {
+                "circle"
+            } else {
+                "box"
+            };
+            let _ = writeln!(
+                dot,
+                "  n{} [label=\"{}\", shape={}];",
+                node.as_u32(),
+                label,
+                shape
+            );
+        }
+        for edge in self.iter_edges() {
+            if !filter.include_node(self, &edge.source)
+                || !filter.include_node(self, &edge.sink)
+                || !filter.include_edge(self, &edge.source, &edge.sink)
+            {
+                continue;
+            }
+            let mut attrs = Vec::new();
+            if edge.precedence != 0 {
+                attrs.push(format!("label=\"{}\"", edge.precedence));
+            }
+            if highlighted.contains(&(edge.source, edge.sink)) {
+                attrs.push("color=red, penwidth=2".to_string());
+            }
+            let _ = write!(
+                dot,
+                "  n{} -> n{}",
+                edge.source.as_u32(),
+                edge.sink.as_u32()
+            );
+            if !attrs.is_empty() {
+                let _ = write!(dot, " [{}]", attrs.join(", "));
+            }
+            let _ = writeln!(dot, ";");
+        }
+        let _ = writeln!(dot, "}}");
+        dot
+    }
+}
+
+fn escape_dot(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
 }
 
 struct VisualizationFilter<'a>(&'a dyn Filter);
@@ -24,6 +24,9 @@ class StackGraph {
 
         this.graph = graph;
         this.paths = paths;
+        this.trace = metadata?.trace ?? null;
+        this.sources = metadata?.sources ?? {};
+        this.trace_step = -1;
         this.cleanup_data();
         this.compute_data();
 
@@ -54,6 +57,7 @@ class StackGraph {
         this.compute_file_data();
         this.compute_node_data();
         this.compute_path_data();
+        this.compute_trace_data();
     }
 
     compute_file_data() {
@@ -73,37 +77,53 @@ class StackGraph {
     }
 
     compute_path_data() {
-        const jumps = {};
+        this.jumps = {};
         for (let path of this.paths) {
-            const node_ids = path.edges.map((e) => e.source);
-            node_ids.push(path.end_node);
-            const nodes = {};
-            const edges = {};
-            nodes[this.node_id_to_str(node_ids[0])] = {
+            this.compute_path_derived(path);
+            this.compute_path_stacks(path);
+            this.N[this.ID[this.node_id_to_str(path.start_node)]].paths.push(path);
+        }
+    }
+
+    compute_path_derived(path) {
+        const node_ids = path.edges.map((e) => e.source);
+        node_ids.push(path.end_node);
+        const nodes = {};
+        const edges = {};
+        nodes[this.node_id_to_str(node_ids[0])] = {
+            stacks: [],
+        };
+        for (let i = 1; i < node_ids.length; i++) {
+            const source = node_ids[i - 1];
+            const sink = node_ids[i];
+            const edge_id = this.edge_to_id_str({ source, sink });
+            edges[edge_id] = {};
+            nodes[this.node_id_to_str(sink)] = {
                 stacks: [],
             };
-            for (let i = 1; i < node_ids.length; i++) {
-                const source = node_ids[i - 1];
-                const sink = node_ids[i];
-                const edge_id = this.edge_to_id_str({ source, sink });
-                edges[edge_id] = {};
-                nodes[this.node_id_to_str(sink)] = {
-                    stacks: [],
-                };
-                // create jump edges, which are not part of the graph
-                if (this.N[this.ID[this.node_id_to_str(source)]].type === 'jump_to_scope' && jumps[edge_id] !== true) {
-                    jumps[edge_id] = true;
-                    this.graph.edges.push({
-                        precedence: 0,
-                        source,
-                        sink,
-                        is_jump: true,
-                    });
-                }
+            // create jump edges, which are not part of the graph
+            const source_node = this.N[this.ID[this.node_id_to_str(source)]];
+            if (source_node?.type === 'jump_to_scope' && this.jumps[edge_id] !== true) {
+                this.jumps[edge_id] = true;
+                this.graph.edges.push({
+                    precedence: 0,
+                    source,
+                    sink,
+                    is_jump: true,
+                });
+            }
+        }
+        path.derived = { nodes, edges };
+    }
+
+    compute_trace_data() {
+        if (this.trace === null) {
+            return;
+        }
+        for (const event of this.trace.events) {
+            for (const path of this.trace_event_paths(event)) {
+                this.compute_path_derived(path);
             }
-            path.derived = { nodes, edges };
-            this.compute_path_stacks(path);
-            this.N[this.ID[this.node_id_to_str(path.start_node)]].paths.push(path);
         }
     }
 
@@ -177,6 +197,7 @@ class StackGraph {
         this.render_help();
         this.render_tooltip();
         this.render_legend();
+        this.render_trace();
         this.render_graph();
 
         // pan & zoom
@@ -193,6 +214,7 @@ class StackGraph {
         // global key events
         d3.select(window).on("keyup", (e) => {
             this.paths_keypress(e);
+            this.trace_keypress(e);
             this.tooltip_keypress(e);
             this.help_keypress(e);
         })
@@ -551,6 +573,15 @@ class StackGraph {
 
     paths_highlight(node, path) {
         const paths = (path !== undefined) ? [node.paths[path]] : node.paths;
+        this.highlight_paths(paths);
+    }
+
+    paths_nolight(node, path) {
+        const paths = (path !== undefined) ? [node.paths[path]] : node.paths;
+        this.nolight_paths(paths);
+    }
+
+    highlight_paths(paths) {
         const nodes = {};
         const edges = {};
         for (let path of paths) {
@@ -583,8 +614,7 @@ class StackGraph {
         }
     }
 
-    paths_nolight(node, path) {
-        const paths = (path !== undefined) ? [node.paths[path]] : node.paths;
+    nolight_paths(paths) {
         for (let path of paths) {
             for (let node_id in path.derived.nodes) {
                 const g = d3.select(this.id_selector(node_id));
@@ -598,6 +628,108 @@ class StackGraph {
         }
     }
 
+    // ------------------------------------------------------------------------------------------------
+    // Trace
+    //
+
+    trace_event_paths(event) {
+        switch (event.type) {
+            case "phase":
+                return [];
+            case "candidate":
+                switch (event.outcome.type) {
+                    case "accepted":
+                    case "too_many_similar":
+                        return [event.outcome.extension];
+                    default:
+                        if (event.candidate.type === "partial_path") {
+                            return [event.path, event.candidate.path];
+                        }
+                        return [event.path];
+                }
+            default:
+                return [event.path];
+        }
+    }
+
+    render_trace() {
+        if (this.trace === null) {
+            return;
+        }
+        const trace = d3.select('body').append('div')
+            .attr('id', 'sg-trace');
+        trace.append("h1").text("Trace");
+        trace.append("div").attr("class", "sg-trace-content");
+        this.trace_update();
+    }
+
+    trace_keypress(e) {
+        if (this.trace === null || this.paths_lock !== null) {
+            return;
+        }
+        let step = this.trace_step;
+        switch (e.keyCode) {
+            case 188: // ,
+                step = Math.max(-1, step - 1);
+                break;
+            case 190: // .
+                step = Math.min(this.trace.events.length - 1, step + 1);
+                break;
+            default:
+                return;
+        }
+        if (this.trace_step >= 0) {
+            this.nolight_paths(this.trace_event_paths(this.trace.events[this.trace_step]));
+        }
+        this.trace_step = step;
+        if (this.trace_step >= 0) {
+            this.highlight_paths(this.trace_event_paths(this.trace.events[this.trace_step]));
+        }
+        this.trace_update();
+    }
+
+    trace_update() {
+        const content = d3.select('#sg-trace .sg-trace-content');
+        content.selectAll("*").remove();
+        const count = this.trace.events.length;
+        if (this.trace_step < 0) {
+            content.append("p").text(`${count} steps, press . to start`);
+            return;
+        }
+        const event = this.trace.events[this.trace_step];
+        content.append("p").text(`step ${this.trace_step + 1} of ${count}: ${event.type.replaceAll("_", " ")}`);
+        const rows = content.append("ul");
+        switch (event.type) {
+            case "phase":
+                rows.append("li").text(`${event.paths.length} queued paths`);
+                break;
+            case "candidate":
+                rows.append("li").text(`path ${this.trace_path_to_str(event.path)}`);
+                rows.append("li").text(`candidate ${this.trace_candidate_to_str(event.candidate)}`);
+                rows.append("li").text(`outcome ${event.outcome.type.replaceAll("_", " ")}`
+                    + (event.outcome.reason ? `: ${event.outcome.reason}` : ""));
+                break;
+            default:
+                rows.append("li").text(`path ${this.trace_path_to_str(event.path)}`);
+                break;
+        }
+    }
+
+    trace_path_to_str(path) {
+        return `${this.node_id_to_str(path.start_node)} -> ${this.node_id_to_str(path.end_node)}`;
+    }
+
+    trace_candidate_to_str(candidate) {
+        switch (candidate.type) {
+            case "edge":
+                return `edge ${this.node_id_to_str(candidate.source)} -> ${this.node_id_to_str(candidate.sink)}`;
+            case "partial_path":
+                return `path ${this.trace_path_to_str(candidate.path)}`;
+            default:
+                return `${this.node_id_to_str(candidate.start_node)} -> ${this.node_id_to_str(candidate.end_node)}`;
+        }
+    }
+
     // ------------------------------------------------------------------------------------------------
     // Tooltip
     //
@@ -756,6 +888,10 @@ class StackGraph {
         }
         if (this.node_has_source_info(node)) {
             tooltip.add_row("location", this.source_info_to_str(node.source_info));
+            const source_line = this.node_source_line(node);
+            if (source_line !== null) {
+                tooltip.add_row("source", source_line);
+            }
         }
         if (node.paths.length > 0) {
             tooltip.add_row("outgoing paths", `${node.paths.length}`);
@@ -867,6 +1003,15 @@ class StackGraph {
             Path selection ends after cycling through all paths by clicking the node, or by pressing the <kbd>esc</kbd> key.
         `);
 
+        if (this.trace !== null) {
+            help_content.append("h1").text("Trace");
+            help_content.append("p").html(`
+                Step through the recorded stitching trace using the keys <kbd>.</kbd> for the next step, and <kbd>,</kbd> for the previous step.
+                The paths involved in the current step are highlighted.
+                Stepping is disabled while a path is selected.
+            `);
+        }
+
         help_content.append("p").attr("class", "sg-help-meta").html(`
             Toggle visibility of this help anytime by pressing <kbd>h</kbd>.
         `);
@@ -986,6 +1131,15 @@ class StackGraph {
         return `line ${line + 1} column ${column + 1}`;
     }
 
+    node_source_line(node) {
+        if (!node.id.hasOwnProperty('file') || !this.sources.hasOwnProperty(node.id.file)) {
+            return null;
+        }
+        const lines = this.sources[node.id.file].split(/\r?\n/);
+        const line = lines[node.source_info.span.start.line];
+        return line === undefined ? null : line.trim();
+    }
+
     source_info_is_empty(source_info) {
         return source_info.span.start.line === 0
             && source_info.span.start.column.utf8_offset === 0
@@ -0,0 +1,496 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use lsp_positions::Span;
+use lsp_positions::SpanCalculator;
+use pretty_assertions::assert_eq;
+use stack_graphs::analysis::call_graph;
+use stack_graphs::analysis::compare_resolutions;
+use stack_graphs::analysis::coverage;
+use stack_graphs::analysis::lint;
+use stack_graphs::analysis::resolutions;
+use stack_graphs::analysis::unresolved_references;
+#[cfg(feature = "serde")]
+use stack_graphs::analysis::Call;
+#[cfg(feature = "serde")]
+use stack_graphs::analysis::CallGraph;
+use stack_graphs::analysis::CoverageCounts;
+use stack_graphs::analysis::Lint;
+use stack_graphs::analysis::ResolutionChange;
+use stack_graphs::analysis::ResolutionComparison;
+use stack_graphs::analysis::Resolutions;
+#[cfg(feature = "serde")]
+use stack_graphs::analysis::SourceLocation;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
+
+use crate::util::*;
+
+fn lints(graph: &StackGraph) -> Vec<(Lint, String)> {
+    lint(graph)
+        .into_iter()
+        .map(|d| (d.lint, d.node.display(graph).to_string()))
+        .collect()
+}
+
+#[test]
+fn well_formed_graph_has_no_diagnostics() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let root = StackGraph::root_node();
+    let reference = create_push_symbol_node(&mut graph, file, "x", true);
+    let definition = create_pop_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (reference, root));
+    create_edge(&mut graph, (root, definition));
+
+    assert_eq!(Vec::<(Lint, String)>::new(), lints(&graph));
+}
+
+#[test]
+fn finds_unreachable_pop_nodes() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let root = StackGraph::root_node();
+    let reference = create_push_symbol_node(&mut graph, file, "x", true);
+    let scope = create_scope_node(&mut graph, file, false);
+    let definition = create_pop_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (reference, root));
+    create_edge(&mut graph, (scope, definition));
+
+    assert_eq!(
+        vec![(
+            Lint::UnreachablePopNode,
+            definition.display(&graph).to_string()
+        )],
+        lints(&graph)
+    );
+}
+
+#[test]
+fn pop_nodes_reachable_from_attached_scopes_are_not_reported() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let root = StackGraph::root_node();
+    let scope = create_scope_node(&mut graph, file, true);
+    let scope_id = graph[scope].id();
+    let reference = create_push_scoped_symbol_node(&mut graph, file, "f", scope_id, true);
+    let function = create_pop_scoped_symbol_node(&mut graph, file, "f", true);
+    let parameter = create_pop_symbol_node(&mut graph, file, "x", true);
+    let argument = create_push_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (reference, root));
+    create_edge(&mut graph, (root, function));
+    create_edge(&mut graph, (scope, parameter));
+    create_edge(&mut graph, (argument, root));
+
+    assert_eq!(Vec::<(Lint, String)>::new(), lints(&graph));
+}
+
+#[test]
+fn finds_dead_end_push_nodes() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let root = StackGraph::root_node();
+    let reference = create_push_symbol_node(&mut graph, file, "x", true);
+    let definition = create_pop_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (root, definition));
+
+    assert_eq!(
+        vec![(Lint::DeadEndPushNode, reference.display(&graph).to_string())],
+        lints(&graph)
+    );
+}
+
+#[test]
+fn finds_unused_exported_scopes() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let scope = create_scope_node(&mut graph, file, true);
+    create_scope_node(&mut graph, file, false);
+
+    assert_eq!(
+        vec![(Lint::UnusedExportedScope, scope.display(&graph).to_string())],
+        lints(&graph)
+    );
+}
+
+#[test]
+fn finds_excluded_symbols_on_internal_scopes() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let root = StackGraph::root_node();
+    let exported = create_scope_node(&mut graph, file, true);
+    let internal = create_scope_node(&mut graph, file, false);
+    create_edge(&mut graph, (root, exported));
+    create_edge(&mut graph, (root, internal));
+    let exported_id = graph[exported].id();
+    let push = create_push_scoped_symbol_node(&mut graph, file, "x", exported_id, false);
+    let pop = create_pop_scoped_symbol_node(&mut graph, file, "x", false);
+    create_edge(&mut graph, (push, pop));
+    create_edge(&mut graph, (root, push));
+    let excluded = graph.add_symbol("_x");
+    graph.set_excluded_symbols(exported, &[excluded]);
+    graph.set_excluded_symbols(internal, &[excluded]);
+
+    assert_eq!(
+        vec![(
+            Lint::ExcludedSymbolsOnInternalScope,
+            internal.display(&graph).to_string()
+        )],
+        lints(&graph)
+    );
+}
+
+#[test]
+fn finds_eager_reexports() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let root = StackGraph::root_node();
+    let exports = create_scope_node(&mut graph, file, false);
+    create_edge(&mut graph, (root, exports));
+    let mut pops = Vec::new();
+    for i in 0..8 {
+        let symbol = format!("x{}", i);
+        let pop = create_pop_symbol_node(&mut graph, file, &symbol, false);
+        let push = create_push_symbol_node(&mut graph, file, &symbol, false);
+        create_edge(&mut graph, (exports, pop));
+        create_edge(&mut graph, (pop, push));
+        create_edge(&mut graph, (push, root));
+        pops.push(pop);
+    }
+
+    let diagnostics = lint(&graph);
+    assert_eq!(1, diagnostics.len());
+    assert_eq!(Lint::EagerReexport, diagnostics[0].lint);
+    assert_eq!(exports, diagnostics[0].node);
+    assert_eq!(pops, diagnostics[0].related);
+}
+
+#[test]
+fn finds_unpopped_symbols() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let root = StackGraph::root_node();
+    let first = create_push_symbol_node(&mut graph, file, "x", true);
+    let second = create_push_symbol_node(&mut graph, file, "x", true);
+    let definition = create_pop_symbol_node(&mut graph, file, "y", true);
+    create_edge(&mut graph, (first, root));
+    create_edge(&mut graph, (second, root));
+    create_edge(&mut graph, (root, definition));
+
+    let diagnostics = lint(&graph);
+    assert_eq!(1, diagnostics.len());
+    assert_eq!(Lint::UnpoppedSymbol, diagnostics[0].lint);
+    assert_eq!(first, diagnostics[0].node);
+    assert_eq!(vec![second], diagnostics[0].related);
+}
+
+#[test]
+fn finds_scoped_symbol_mismatches() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let root = StackGraph::root_node();
+    let reference = create_push_symbol_node(&mut graph, file, "f", true);
+    let definition = create_pop_scoped_symbol_node(&mut graph, file, "f", true);
+    create_edge(&mut graph, (reference, root));
+    create_edge(&mut graph, (root, definition));
+
+    assert_eq!(
+        vec![(
+            Lint::ScopedSymbolMismatch,
+            reference.display(&graph).to_string()
+        )],
+        lints(&graph)
+    );
+}
+
+#[test]
+fn finds_unresolved_references() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let root = StackGraph::root_node();
+    let resolved = create_push_symbol_node(&mut graph, file, "x", true);
+    let dead_end = create_push_symbol_node(&mut graph, file, "y", true);
+    let undefined = create_push_symbol_node(&mut graph, file, "z", true);
+    let definition = create_pop_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (resolved, root));
+    create_edge(&mut graph, (undefined, root));
+    create_edge(&mut graph, (root, definition));
+
+    let mut partials = PartialPaths::new();
+    let unresolved = unresolved_references(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .unwrap();
+
+    let references = unresolved.iter().map(|u| u.reference).collect::<Vec<_>>();
+    assert_eq!(vec![dead_end, undefined], references);
+    assert_eq!(
+        vec![Lint::DeadEndPushNode, Lint::UnpoppedSymbol],
+        unresolved[0]
+            .diagnostics
+            .iter()
+            .map(|d| d.lint)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(dead_end, unresolved[0].longest_path.end_node);
+    assert_eq!(
+        vec![Lint::UnpoppedSymbol],
+        unresolved[1]
+            .diagnostics
+            .iter()
+            .map(|d| d.lint)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(root, unresolved[1].longest_path.end_node);
+}
+
+#[test]
+fn coverage_counts_references_per_file_and_syntax_type() {
+    let mut graph = StackGraph::new();
+    let main = graph.get_or_create_file("main.py");
+    let other = graph.get_or_create_file("other.py");
+    let root = StackGraph::root_node();
+    let resolved = create_push_symbol_node(&mut graph, main, "x", true);
+    let unresolved = create_push_symbol_node(&mut graph, main, "y", true);
+    let excluded = create_push_symbol_node(&mut graph, other, "z", true);
+    let definition = create_pop_symbol_node(&mut graph, other, "x", true);
+    create_edge(&mut graph, (resolved, root));
+    create_edge(&mut graph, (unresolved, root));
+    create_edge(&mut graph, (excluded, root));
+    create_edge(&mut graph, (root, definition));
+    let call = graph.add_string("call");
+    graph.source_info_mut(unresolved).syntax_type = call.into();
+
+    let mut partials = PartialPaths::new();
+    let unresolved_refs = unresolved_references(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .unwrap();
+    let report = coverage(&graph, [main], &unresolved_refs);
+
+    let half = CoverageCounts {
+        references: 2,
+        resolved: 1,
+    };
+    assert_eq!(half, report.total);
+    assert_eq!(0.5, report.total.resolved_ratio());
+    assert_eq!(
+        vec![("main.py".to_string(), half)],
+        report.files.into_iter().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![
+            (
+                "".to_string(),
+                CoverageCounts {
+                    references: 1,
+                    resolved: 1
+                }
+            ),
+            (
+                "call".to_string(),
+                CoverageCounts {
+                    references: 1,
+                    resolved: 0
+                }
+            ),
+        ],
+        report.syntax_types.into_iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn coverage_report_is_written_as_csv() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("a,b.py");
+    create_push_symbol_node(&mut graph, file, "x", true);
+
+    let mut partials = PartialPaths::new();
+    let unresolved = unresolved_references(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .unwrap();
+    let report = coverage(&graph, [file], &unresolved);
+
+    assert_eq!(
+        "kind,name,references,resolved,resolved_ratio\n\
+         total,,1,0,0.0000\n\
+         file,\"a,b.py\",1,0,0.0000\n\
+         syntax_type,,1,0,0.0000\n",
+        report.to_csv()
+    );
+}
+
+/// Builds a graph with references to x, y, and z on the first three lines, which resolve to
+/// definitions of the given symbols on the given lines.
+fn resolution_graph(definitions: &[(&str, usize)]) -> StackGraph {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let root = StackGraph::root_node();
+    for (line, symbol) in ["x", "y", "z"].iter().enumerate() {
+        let reference = create_push_symbol_node(&mut graph, file, symbol, true);
+        graph.source_info_mut(reference).span.start.line = line;
+        create_edge(&mut graph, (reference, root));
+    }
+    for (symbol, line) in definitions {
+        let definition = create_pop_symbol_node(&mut graph, file, symbol, true);
+        graph.source_info_mut(definition).span.start.line = *line;
+        create_edge(&mut graph, (root, definition));
+    }
+    graph
+}
+
+fn resolutions_of(graph: &StackGraph) -> Resolutions {
+    let file = graph.get_file("test.py").unwrap();
+    let mut partials = PartialPaths::new();
+    resolutions(
+        &mut GraphEdgeCandidates::new(graph, &mut partials, None),
+        [file],
+        StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .unwrap()
+}
+
+#[test]
+fn compares_resolutions_of_references() {
+    let old = resolutions_of(&resolution_graph(&[("x", 10), ("z", 11)]));
+    let new = resolutions_of(&resolution_graph(&[("x", 12), ("y", 13)]));
+    assert_eq!(3, old.len());
+    assert_eq!(
+        vec!["test.py:11:1 x"],
+        old.values()
+            .flatten()
+            .take(1)
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+    );
+
+    let comparison = compare_resolutions(&old, &new);
+    assert_eq!(3, comparison.references);
+    assert_eq!(
+        vec![
+            ("x", ResolutionChange::Changed),
+            ("y", ResolutionChange::Gained),
+            ("z", ResolutionChange::Lost),
+        ],
+        comparison
+            .differences
+            .iter()
+            .map(|d| (d.reference.symbol.as_str(), d.change))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(13, comparison.differences[0].new[0].line);
+    assert_eq!(1, comparison.count(ResolutionChange::Lost));
+
+    assert_eq!(
+        ResolutionComparison {
+            references: 3,
+            differences: vec![],
+        },
+        compare_resolutions(&old, &old)
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn resolution_comparison_is_written_as_json() {
+    let old = resolutions_of(&resolution_graph(&[]));
+    let new = resolutions_of(&resolution_graph(&[("y", 10)]));
+    let json = serde_json::to_value(compare_resolutions(&old, &new)).unwrap();
+    assert_eq!(
+        serde_json::json!({
+            "references": 3,
+            "differences": [{
+                "reference": { "file": "test.py", "line": 2, "column": 1, "symbol": "y" },
+                "change": "gained",
+                "old": [],
+                "new": [{ "file": "test.py", "line": 11, "column": 1, "symbol": "y" }],
+            }],
+        }),
+        json
+    );
+}
+
+#[test]
+fn call_graph_links_definitions_to_references_in_their_bodies() {
+    let source = "def f():\n    g()\ndef g(): pass\ng()\n";
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let mut calculator = SpanCalculator::new(source);
+    let mut span = |range: std::ops::Range<usize>| Span {
+        start: calculator.for_utf8_offset_in_string(range.start),
+        end: calculator.for_utf8_offset_in_string(range.end),
+    };
+    let f = create_pop_symbol_node(&mut graph, file, "f", true);
+    graph.source_info_mut(f).span = span(4..5);
+    graph.source_info_mut(f).definiens_span = span(0..17);
+    let g = create_pop_symbol_node(&mut graph, file, "g", true);
+    graph.source_info_mut(g).span = span(21..22);
+    graph.source_info_mut(g).definiens_span = span(17..31);
+    for start in [13, 31] {
+        let reference = create_push_symbol_node(&mut graph, file, "g", true);
+        graph.source_info_mut(reference).span = span(start..start + 1);
+        graph.add_edge(reference, g, 0);
+    }
+
+    let mut partials = PartialPaths::new();
+    let calls = call_graph(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        [file],
+        StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .unwrap();
+    assert_eq!(
+        vec![("test.py:1:5 f".to_string(), "test.py:3:5 g".to_string())],
+        calls
+            .calls
+            .iter()
+            .map(|c| (c.caller.to_string(), c.callee.to_string()))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        "digraph \"calls\" {\n  node [shape=box, fontname=monospace];\n  \"test.py:1:5 f\" -> \"test.py:3:5 g\";\n}\n",
+        calls.to_dot_string("calls")
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn call_graph_is_written_as_json() {
+    let location = |line, symbol: &str| SourceLocation {
+        file: "test.py".to_string(),
+        line,
+        column: 1,
+        symbol: symbol.to_string(),
+    };
+    let calls = CallGraph {
+        calls: vec![Call {
+            caller: location(1, "f"),
+            callee: location(3, "g"),
+        }],
+    };
+    assert_eq!(
+        serde_json::json!({
+            "calls": [{
+                "caller": { "file": "test.py", "line": 1, "column": 1, "symbol": "f" },
+                "callee": { "file": "test.py", "line": 3, "column": 1, "symbol": "g" },
+            }],
+        }),
+        serde_json::to_value(&calls).unwrap()
+    );
+}
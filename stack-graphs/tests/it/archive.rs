@@ -0,0 +1,249 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use std::collections::BTreeSet;
+
+use itertools::Itertools;
+use pretty_assertions::assert_eq;
+use stack_graphs::archive::ArchiveBuilder;
+use stack_graphs::archive::ArchiveError;
+use stack_graphs::archive::ArchiveReader;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::Database;
+use stack_graphs::stitching::DatabaseCandidates;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
+
+use crate::test_graphs;
+use crate::util::create_partial_path_and_edges;
+use crate::util::create_pop_symbol_node;
+use crate::util::create_push_symbol_node;
+
+fn test_foo_bar_root_candidate_paths(symbols: &[&str], variable: bool) -> usize {
+    let bytes = {
+        let mut graph = StackGraph::new();
+        let file = graph.add_file("test1").unwrap();
+        let mut partials = PartialPaths::new();
+
+        let r = StackGraph::root_node();
+        let pop_foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+        let pop_bar = create_pop_symbol_node(&mut graph, file, "bar", true);
+
+        let path_with_variable =
+            create_partial_path_and_edges(&mut graph, &mut partials, &[r, pop_foo, pop_bar])
+                .unwrap();
+
+        let mut path_without_variable = path_with_variable.clone();
+        path_without_variable.eliminate_precondition_stack_variables(&mut partials);
+
+        let mut builder = ArchiveBuilder::new();
+        builder.add_file_graph(&graph, file);
+        builder.add_partial_path(&graph, &mut partials, &path_with_variable);
+        builder.add_partial_path(&graph, &mut partials, &path_without_variable);
+        builder.to_bytes().unwrap()
+    };
+    let mut reader = ArchiveReader::new(&bytes).unwrap();
+
+    let (graph, partials, _) = reader.get();
+    let file = graph.add_file("test2").unwrap();
+
+    let r = StackGraph::root_node();
+    let refs = symbols
+        .iter()
+        .map(|r| create_push_symbol_node(graph, file, r, true))
+        .chain(std::iter::once(r))
+        .collect_vec();
+    let mut path = create_partial_path_and_edges(graph, partials, &refs).unwrap();
+    if !variable {
+        path.eliminate_precondition_stack_variables(partials);
+    }
+
+    reader
+        .load_partial_path_extensions(&path, &NoCancellation)
+        .unwrap();
+
+    let (graph, partials, db) = reader.get();
+    let mut results = Vec::new();
+    db.find_candidate_partial_paths_from_root(
+        graph,
+        partials,
+        Some(path.symbol_stack_postcondition),
+        &mut results,
+    );
+    results.len()
+}
+
+#[test]
+fn can_find_root_candidates_in_archive() {
+    assert_eq!(2, test_foo_bar_root_candidate_paths(&["bar", "foo"], true));
+    assert_eq!(2, test_foo_bar_root_candidate_paths(&["bar", "foo"], false));
+    assert_eq!(
+        1,
+        test_foo_bar_root_candidate_paths(&["quz", "bar", "foo"], true)
+    );
+    assert_eq!(
+        1,
+        test_foo_bar_root_candidate_paths(&["quz", "bar", "foo"], false)
+    );
+    assert_eq!(2, test_foo_bar_root_candidate_paths(&["foo"], true));
+    assert_eq!(0, test_foo_bar_root_candidate_paths(&["foo"], false));
+}
+
+fn complete_paths_in_database(graph: &StackGraph, file: &str) -> BTreeSet<String> {
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .unwrap();
+    }
+    let file = graph.get_file(file).unwrap();
+    let references = graph
+        .iter_nodes()
+        .filter(|n| graph[*n].is_reference() && graph[*n].is_in_file(file))
+        .collect_vec();
+    let mut results = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(graph, &mut partials, &mut db),
+        references,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, p| results.push(p.clone()),
+    )
+    .unwrap();
+    results
+        .into_iter()
+        .map(|p| p.display(graph, &mut partials).to_string())
+        .collect()
+}
+
+fn complete_paths_in_archive(graph: &StackGraph, file: &str) -> BTreeSet<String> {
+    let mut partials = PartialPaths::new();
+    let mut builder = ArchiveBuilder::new();
+    for file in graph.iter_files() {
+        builder.add_file_graph(graph, file);
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| builder.add_partial_path(graph, partials, path),
+        )
+        .unwrap();
+    }
+    let bytes = builder.to_bytes().unwrap();
+
+    let mut reader = ArchiveReader::new(&bytes).unwrap();
+    let file = reader.load_graph_for_file(file).unwrap();
+    let (graph, _, _) = reader.get();
+    let references = graph
+        .iter_nodes()
+        .filter(|n| graph[*n].is_reference() && graph[*n].is_in_file(file))
+        .collect_vec();
+    let mut results = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut reader,
+        references,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, p| results.push(p.clone()),
+    )
+    .unwrap();
+    let (graph, partials, _) = reader.get();
+    results
+        .into_iter()
+        .map(|p| p.display(graph, partials).to_string())
+        .collect()
+}
+
+#[test]
+fn archive_finds_same_paths_as_database() {
+    let graph: StackGraph = test_graphs::sequenced_import_star::new();
+    let expected = complete_paths_in_database(&graph, "main.py");
+    assert!(!expected.is_empty());
+    assert_eq!(expected, complete_paths_in_archive(&graph, "main.py"));
+
+    let graph: StackGraph = test_graphs::class_field_through_function_parameter::new();
+    assert_eq!(
+        complete_paths_in_database(&graph, "main.py"),
+        complete_paths_in_archive(&graph, "main.py")
+    );
+}
+
+#[test]
+fn archived_paths_can_be_read_without_loading() {
+    let graph: StackGraph = test_graphs::sequenced_import_star::new();
+    let mut partials = PartialPaths::new();
+    let mut builder = ArchiveBuilder::new();
+    let mut count = 0;
+    for file in graph.iter_files() {
+        builder.add_file_graph(&graph, file);
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                builder.add_partial_path(graph, partials, path);
+                count += 1;
+            },
+        )
+        .unwrap();
+    }
+    let bytes = builder.to_bytes().unwrap();
+
+    let mut reader = ArchiveReader::new(&bytes).unwrap();
+    assert_eq!(count, reader.path_count());
+    assert_eq!(
+        vec!["a.py", "b.py", "main.py"],
+        reader.files().collect_vec()
+    );
+    let node = graph
+        .iter_nodes()
+        .find(|n| graph[*n].is_reference() && graph[*n].symbol().is_some())
+        .unwrap();
+    let id = graph[node].id();
+    let archived = reader
+        .archived_paths_for_node(graph[id.file().unwrap()].name(), id.local_id())
+        .count();
+    assert!(archived > 0);
+    let (graph, _, _) = reader.get();
+    assert_eq!(0, graph.iter_files().count());
+}
+
+#[test]
+fn invalid_archives_are_rejected() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let mut builder = ArchiveBuilder::new();
+    builder.add_file_graph(&graph, file);
+    let bytes = builder.to_bytes().unwrap();
+
+    let truncated = &bytes[..bytes.len() - 8];
+    assert!(matches!(
+        ArchiveReader::new(truncated),
+        Err(ArchiveError::Invalid(_))
+    ));
+    assert!(matches!(
+        ArchiveReader::new(&bytes)
+            .unwrap()
+            .load_graph_for_file("missing.py"),
+        Err(ArchiveError::MissingFile(_))
+    ));
+}
@@ -6,8 +6,12 @@
 // ------------------------------------------------------------------------------------------------
 
 use stack_graphs::arena::Arena;
+use stack_graphs::arena::ArenaStats;
 use stack_graphs::arena::Deque;
 use stack_graphs::arena::DequeArena;
+use stack_graphs::arena::DequeCompaction;
+use stack_graphs::arena::HandleMap;
+use stack_graphs::arena::HandleSet;
 use stack_graphs::arena::List;
 use stack_graphs::arena::ListArena;
 use stack_graphs::arena::ReversibleList;
@@ -230,6 +234,47 @@ fn can_compare_deques() {
     assert_eq!(deque1.cmp(&mut arena, deque10), Ordering::Less);
 }
 
+#[test]
+fn can_compact_deques() {
+    let mut arena = Deque::new_arena();
+    let mut shared = Deque::empty();
+    shared.push_front(&mut arena, 1);
+    shared.push_front(&mut arena, 2);
+    shared.push_front(&mut arena, 3);
+    let mut extended = shared;
+    extended.push_front(&mut arena, 4);
+    let mut garbage = shared;
+    garbage.push_back(&mut arena, 5);
+    garbage.push_back(&mut arena, 6);
+    shared.ensure_backwards(&mut arena);
+    shared.ensure_forwards(&mut arena);
+    let before = arena.stats();
+
+    let mut compaction = DequeCompaction::new();
+    compaction.copy(&arena, &mut shared, |x| *x);
+    compaction.copy(&arena, &mut extended, |x| *x);
+    compaction.finish(&mut arena);
+
+    // The shared cells are copied once, together with the cached reversal of `shared`.
+    assert_eq!(7, arena.stats().live);
+    assert_eq!(before.allocated + 7, arena.stats().allocated);
+    assert_eq!(
+        vec![3, 2, 1],
+        shared.iter_reused(&arena).copied().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![1, 2, 3],
+        shared
+            .iter_reversed_reused(&arena)
+            .copied()
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![4, 3, 2, 1],
+        extended.iter(&mut arena).copied().collect::<Vec<_>>()
+    );
+}
+
 #[test]
 fn can_use_arena_after_clear() {
     let mut a = Arena::new();
@@ -254,3 +299,119 @@ fn can_use_supplemental_arena_after_clear() {
     x[h] = 7;
     assert_eq!(Some(7), x.get(h).cloned());
 }
+
+#[test]
+fn can_track_arena_stats() {
+    let mut a = Arena::new();
+    assert_eq!(ArenaStats::default(), a.stats());
+
+    a.add(1u8);
+    a.add(2);
+    a.add(3);
+    assert_eq!(
+        ArenaStats {
+            allocated: 3,
+            live: 3,
+            high_water_mark: 3
+        },
+        a.stats()
+    );
+
+    a.clear();
+    a.add(4);
+    assert_eq!(
+        ArenaStats {
+            allocated: 4,
+            live: 1,
+            high_water_mark: 3
+        },
+        a.stats()
+    );
+
+    a.reset_high_water_mark();
+    assert_eq!(
+        ArenaStats {
+            allocated: 4,
+            live: 1,
+            high_water_mark: 1
+        },
+        a.stats()
+    );
+}
+
+#[test]
+fn can_combine_handle_sets() {
+    let mut arena = Arena::new();
+    let handles = (0..6).map(|i| arena.add(i)).collect::<Vec<_>>();
+    let set = |indices: &[usize]| {
+        indices
+            .iter()
+            .map(|i| handles[*i])
+            .collect::<HandleSet<_>>()
+    };
+
+    let a = set(&[0, 1, 2]);
+    let b = set(&[2, 3, 5]);
+    assert_eq!(3, a.count());
+    assert!(!a.is_empty());
+    assert!(HandleSet::<u32>::new().is_empty());
+
+    let mut union = a.clone();
+    union.union_with(&b);
+    assert_eq!(set(&[0, 1, 2, 3, 5]), union);
+
+    let mut intersection = b.clone();
+    intersection.intersect_with(&a);
+    assert_eq!(set(&[2]), intersection);
+
+    let mut difference = a.clone();
+    difference.difference_with(&b);
+    assert_eq!(set(&[0, 1]), difference);
+
+    assert!(difference.is_subset(&a));
+    assert!(!a.is_subset(&difference));
+    assert!(difference.is_disjoint(&b));
+    assert!(!a.is_disjoint(&b));
+
+    // sets with different capacities can still be equal
+    let mut shrunk = set(&[0, 5]);
+    shrunk.remove(handles[5]);
+    assert_eq!(set(&[0]), shrunk);
+
+    let mut inserted = HandleSet::new();
+    assert!(inserted.insert(handles[4]));
+    assert!(!inserted.insert(handles[4]));
+}
+
+#[test]
+fn can_use_handle_maps() {
+    let mut arena = Arena::new();
+    let h1 = arena.add(1);
+    let h2 = arena.add(2);
+    let h3 = arena.add(3);
+
+    let mut map = HandleMap::new();
+    assert!(map.is_empty());
+    assert_eq!(None, map.insert(h3, "three"));
+    assert_eq!(None, map.insert(h1, "one"));
+    assert_eq!(Some("one"), map.insert(h1, "uno"));
+    assert_eq!(2, map.len());
+    assert_eq!(Some(&"uno"), map.get(h1));
+    assert_eq!(None, map.get(h2));
+    assert_eq!("three", map[h3]);
+    assert_eq!(
+        vec![(h1, &"uno"), (h3, &"three")],
+        map.iter().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        [h1, h3].iter().copied().collect::<HandleSet<_>>(),
+        map.keys()
+    );
+
+    *map.get_or_insert_with(h2, || "two") = "dos";
+    assert_eq!(Some(&"dos"), map.get(h2));
+    assert_eq!(Some("uno"), map.remove(h1));
+    assert_eq!(None, map.remove(h1));
+    assert!(!map.contains_key(h1));
+    assert_eq!(2, map.len());
+}
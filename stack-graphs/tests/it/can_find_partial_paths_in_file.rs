@@ -10,31 +10,40 @@ use std::collections::BTreeSet;
 use pretty_assertions::assert_eq;
 use stack_graphs::graph::StackGraph;
 use stack_graphs::partial::PartialPaths;
-use stack_graphs::stitching::{ForwardPartialPathStitcher, StitcherConfig};
+use stack_graphs::stitching::{ForwardPartialPathStitcher, Stats, StitcherConfig};
 use stack_graphs::NoCancellation;
 
 use crate::test_graphs;
 
 fn check_partial_paths_in_file(graph: &StackGraph, file: &str, expected_paths: &[&str]) {
+    let results = find_partial_paths_in_file(graph, file, StitcherConfig::default()).0;
+    let expected_paths = expected_paths
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<BTreeSet<_>>();
+    assert_eq!(expected_paths, results, "failed in file {}", file);
+}
+
+fn find_partial_paths_in_file(
+    graph: &StackGraph,
+    file: &str,
+    config: StitcherConfig,
+) -> (BTreeSet<String>, Stats) {
     let file = graph.get_file(file).expect("Missing file");
     let mut partials = PartialPaths::new();
     let mut results = BTreeSet::new();
-    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+    let stats = ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
         graph,
         &mut partials,
         file,
-        StitcherConfig::default(),
+        config,
         &NoCancellation,
         |graph, partials, path| {
             results.insert(path.display(graph, partials).to_string());
         },
     )
     .expect("should never be cancelled");
-    let expected_paths = expected_paths
-        .iter()
-        .map(|s| s.to_string())
-        .collect::<BTreeSet<_>>();
-    assert_eq!(expected_paths, results, "failed in file {}", graph[file]);
+    (results, stats)
 }
 
 pub(crate) static CLASS_FIELD_THROUGH_FUNCTION_PARAMETER_MAIN_PATHS: &[&str] = &[
@@ -209,3 +218,85 @@ fn sequenced_import_star() {
     check_partial_paths_in_file(&graph, "a.py", SEQUENCED_IMPORT_STAR_A_PATHS);
     check_partial_paths_in_file(&graph, "b.py", SEQUENCED_IMPORT_STAR_B_PATHS);
 }
+
+#[test]
+fn can_spill_queued_paths_to_disk() {
+    let graph = test_graphs::class_field_through_function_parameter::new();
+    let config = StitcherConfig::default()
+        .with_collect_stats(true)
+        .with_max_queued_paths(Some(1))
+        .with_spill_queue_to_disk(true);
+    for (file, expected_paths) in [
+        ("main.py", CLASS_FIELD_THROUGH_FUNCTION_PARAMETER_MAIN_PATHS),
+        ("a.py", CLASS_FIELD_THROUGH_FUNCTION_PARAMETER_A_PATHS),
+        ("b.py", CLASS_FIELD_THROUGH_FUNCTION_PARAMETER_B_PATHS),
+    ] {
+        let (results, stats) = find_partial_paths_in_file(&graph, file, config);
+        let expected_paths = expected_paths
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<BTreeSet<_>>();
+        assert_eq!(expected_paths, results, "failed in file {}", file);
+        assert_eq!(0, stats.dropped_paths, "failed in file {}", file);
+    }
+    let (_, stats) = find_partial_paths_in_file(&graph, "main.py", config);
+    assert!(stats.spilled_paths > 0);
+}
+
+#[test]
+fn can_drop_queued_paths() {
+    let graph = test_graphs::class_field_through_function_parameter::new();
+    let config = StitcherConfig::default()
+        .with_collect_stats(true)
+        .with_max_queued_paths(Some(1));
+    let (results, stats) = find_partial_paths_in_file(&graph, "main.py", config);
+    let expected_paths = CLASS_FIELD_THROUGH_FUNCTION_PARAMETER_MAIN_PATHS
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<BTreeSet<_>>();
+    assert!(results.is_subset(&expected_paths));
+    assert!(stats.dropped_paths > 0);
+    assert_eq!(0, stats.spilled_paths);
+}
+
+#[test]
+fn can_visit_paths_in_length_order() {
+    let graph: StackGraph = test_graphs::class_field_through_function_parameter::new();
+    let config = StitcherConfig::default()
+        .with_max_queued_paths(Some(1))
+        .with_spill_queue_to_disk(true)
+        .with_length_ordered_visits(true);
+    for (file, expected_paths) in [
+        ("main.py", CLASS_FIELD_THROUGH_FUNCTION_PARAMETER_MAIN_PATHS),
+        ("a.py", CLASS_FIELD_THROUGH_FUNCTION_PARAMETER_A_PATHS),
+        ("b.py", CLASS_FIELD_THROUGH_FUNCTION_PARAMETER_B_PATHS),
+    ] {
+        let file_handle = graph.get_file(file).expect("Missing file");
+        let mut partials = PartialPaths::new();
+        let mut results = BTreeSet::new();
+        let mut lengths = Vec::new();
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file_handle,
+            config,
+            &NoCancellation,
+            |graph, partials, path| {
+                results.insert(path.display(graph, partials).to_string());
+                lengths.push(path.edges.len());
+            },
+        )
+        .expect("should never be cancelled");
+        let expected_paths = expected_paths
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<BTreeSet<_>>();
+        assert_eq!(expected_paths, results, "failed in file {}", file);
+        assert!(
+            lengths.windows(2).all(|pair| pair[0] <= pair[1]),
+            "failed in file {}: {:?}",
+            file,
+            lengths
+        );
+    }
+}
@@ -0,0 +1,163 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use stack_graphs::checked::InvariantViolation;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPath;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::partial::PartialSymbolStack;
+use stack_graphs::partial::ScopeStackVariable;
+use stack_graphs::partial::SymbolStackVariable;
+use stack_graphs::paths::PathResolutionError;
+
+use crate::util::create_pop_symbol_node;
+use crate::util::create_push_symbol_node;
+
+#[test]
+fn checked_lookups_reject_handles_of_other_graphs() {
+    let graph = StackGraph::new();
+    let mut other = StackGraph::new();
+    let file = other.get_or_create_file("test");
+    let node = create_pop_symbol_node(&mut other, file, "a", true);
+    let symbol = other.add_symbol("a");
+
+    assert!(other.try_node(node).unwrap().is_definition());
+    assert_eq!("a", other.try_symbol(symbol).unwrap());
+    assert_eq!("test", other.try_file(file).unwrap().name());
+
+    assert_eq!(
+        Err(InvariantViolation::InvalidHandle("node")),
+        graph.try_node(node).map(|_| ())
+    );
+    assert_eq!(
+        Err(InvariantViolation::InvalidHandle("symbol")),
+        graph.try_symbol(symbol)
+    );
+    assert_eq!(
+        Err(InvariantViolation::InvalidHandle("file")),
+        graph.try_file(file).map(|_| ())
+    );
+}
+
+#[test]
+fn checked_path_operations_return_their_results() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test");
+    let a = create_push_symbol_node(&mut graph, file, "a", true);
+    let b = create_pop_symbol_node(&mut graph, file, "a", true);
+    graph.add_edge(a, b, 0);
+    let edge = graph.outgoing_edges(a).next().unwrap();
+    let mut partials = PartialPaths::new();
+
+    let mut path = PartialPath::try_from_node(&graph, &mut partials, b).unwrap();
+    assert!(matches!(
+        path.try_append(&graph, &mut partials, edge),
+        Ok(Err(PathResolutionError::IncorrectSourceNode))
+    ));
+
+    let mut path = PartialPath::try_from_node(&graph, &mut partials, a).unwrap();
+    assert!(matches!(
+        path.try_append(&graph, &mut partials, edge),
+        Ok(Ok(()))
+    ));
+}
+
+#[test]
+fn checked_path_operations_reject_nodes_of_other_graphs() {
+    let graph = StackGraph::new();
+    let mut other = StackGraph::new();
+    let file = other.get_or_create_file("test");
+    let a = create_push_symbol_node(&mut other, file, "a", true);
+    let b = create_pop_symbol_node(&mut other, file, "a", true);
+    other.add_edge(a, b, 0);
+    let edge = other.outgoing_edges(a).next().unwrap();
+    let mut partials = PartialPaths::new();
+
+    assert!(matches!(
+        PartialPath::try_from_node(&graph, &mut partials, a),
+        Err(InvariantViolation::InvalidHandle("node"))
+    ));
+
+    let mut path = PartialPath::from_node(&other, &mut partials, a);
+    assert!(matches!(
+        path.try_append(&graph, &mut partials, edge),
+        Err(InvariantViolation::InvalidHandle("node"))
+    ));
+
+    let mut lhs = PartialPath::from_node(&graph, &mut partials, StackGraph::root_node());
+    let rhs = PartialPath::from_node(&other, &mut partials, b);
+    assert!(matches!(
+        lhs.try_concatenate(&graph, &mut partials, &rhs),
+        Err(InvariantViolation::InvalidHandle("node"))
+    ));
+}
+
+#[test]
+fn overflowing_variables_are_violated_invariants() {
+    let symbol_variable = SymbolStackVariable::new(u32::MAX - 1).unwrap();
+    assert_eq!(
+        SymbolStackVariable::new(u32::MAX),
+        symbol_variable.try_with_offset(1).ok()
+    );
+    assert_eq!(
+        Err(InvariantViolation::SymbolStackVariableOverflow),
+        symbol_variable.try_with_offset(2)
+    );
+
+    let scope_variable = ScopeStackVariable::new(u32::MAX - 1).unwrap();
+    assert_eq!(
+        ScopeStackVariable::new(u32::MAX),
+        scope_variable.try_with_offset(1).ok()
+    );
+    assert_eq!(
+        Err(InvariantViolation::ScopeStackVariableOverflow),
+        scope_variable.try_with_offset(2)
+    );
+}
+
+#[test]
+fn overlapping_variables_that_overflow_are_violated_invariants() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test");
+    let a = create_push_symbol_node(&mut graph, file, "a", true);
+    let mut partials = PartialPaths::new();
+
+    let mut path = PartialPath::from_node(&graph, &mut partials, a);
+    let mut other = PartialPath::from_node(&graph, &mut partials, a);
+    other.symbol_stack_precondition =
+        PartialSymbolStack::from_variable(SymbolStackVariable::new(u32::MAX).unwrap());
+
+    let before = path.clone();
+    assert_eq!(
+        Err(InvariantViolation::SymbolStackVariableOverflow),
+        path.try_ensure_no_overlapping_variables(&mut partials, &other)
+    );
+    assert!(path.equals(&mut partials, &before));
+}
+
+#[cfg(feature = "storage")]
+#[test]
+fn storing_paths_outside_of_file_is_a_violated_invariant() {
+    use stack_graphs::storage::SQLiteWriter;
+    use stack_graphs::storage::StorageError;
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test");
+    let other_file = graph.get_or_create_file("other");
+    let a = create_push_symbol_node(&mut graph, other_file, "a", true);
+    let mut partials = PartialPaths::new();
+    let path = PartialPath::from_node(&graph, &mut partials, a);
+
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let result = writer.store_result_for_file(&graph, file, "tag", &mut partials, vec![&path]);
+    assert!(matches!(
+        result,
+        Err(StorageError::InvariantViolation(
+            InvariantViolation::PathOutsideFile(_, _)
+        ))
+    ));
+}
@@ -0,0 +1,89 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use pretty_assertions::assert_eq;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::serde;
+use stack_graphs::serde::CompressedPartialPaths;
+use stack_graphs::serde::CompressionTables;
+use stack_graphs::serde::PathCompressor;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
+
+use crate::test_graphs;
+
+fn serialized_partial_paths(graph: &StackGraph) -> Vec<serde::PartialPath> {
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |g, ps, p| paths.push(serde::PartialPath::from_partial_path(g, ps, p)),
+        )
+        .unwrap();
+    }
+    paths
+}
+
+#[test]
+fn compressed_partial_paths_round_trip() {
+    for graph in [
+        test_graphs::class_field_through_function_parameter::new(),
+        test_graphs::cyclic_imports_python::new(),
+        test_graphs::sequenced_import_star::new(),
+    ] {
+        let paths = serialized_partial_paths(&graph);
+        assert!(!paths.is_empty());
+        let compressed = CompressedPartialPaths::from_partial_paths(&paths);
+        assert_eq!(paths.len(), compressed.len());
+        assert_eq!(paths, compressed.to_partial_paths().unwrap());
+    }
+}
+
+#[cfg(feature = "storage")]
+#[test]
+fn compressed_partial_paths_are_smaller() {
+    use stack_graphs::storage::BINCODE_CONFIG;
+
+    let graph = test_graphs::class_field_through_function_parameter::new();
+    let paths = serialized_partial_paths(&graph);
+    let compressed = CompressedPartialPaths::from_partial_paths(&paths);
+    let plain_size = bincode::encode_to_vec(&paths, BINCODE_CONFIG)
+        .unwrap()
+        .len();
+    let compressed_size = bincode::encode_to_vec(&compressed, BINCODE_CONFIG)
+        .unwrap()
+        .len();
+    assert!(
+        compressed_size < plain_size,
+        "compressed size {} is not smaller than plain size {}",
+        compressed_size,
+        plain_size
+    );
+}
+
+#[test]
+fn paths_referring_to_missing_entries_are_rejected() {
+    let graph = test_graphs::simple::new();
+    let paths = serialized_partial_paths(&graph);
+    let mut compressor = PathCompressor::new();
+    let compressed = compressor.compress(&paths[0]);
+    assert_eq!(
+        paths[0],
+        compressor.finish().decompress(&compressed).unwrap()
+    );
+    assert!(matches!(
+        CompressionTables::default().decompress(&compressed),
+        Err(serde::Error::InvalidCompressedIndex(_))
+    ));
+}
@@ -9,6 +9,7 @@ use enumset::enum_set;
 use stack_graphs::arena::Handle;
 use stack_graphs::cycles::Appendables;
 use stack_graphs::cycles::AppendingCycleDetector;
+use stack_graphs::cycles::SimilarPathDetector;
 use stack_graphs::graph::StackGraph;
 use stack_graphs::partial::Cyclicity;
 use stack_graphs::partial::PartialPath;
@@ -18,6 +19,8 @@ use stack_graphs::stitching::ForwardPartialPathStitcher;
 use stack_graphs::stitching::GraphEdges;
 use stack_graphs::stitching::StitcherConfig;
 use stack_graphs::CancelAfterDuration;
+use std::cell::Cell;
+use std::cmp::Ordering;
 use std::time::Duration;
 
 use crate::util::*;
@@ -209,6 +212,35 @@ fn finding_simple_identity_cycle_is_detected() {
     }
 }
 
+#[test]
+fn repeated_identity_cycle_is_detected() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let r = StackGraph::root_node();
+    let s = create_scope_node(&mut graph, file, false);
+    let foo_ref = create_push_symbol_node(&mut graph, file, "foo", false);
+    let foo_def = create_pop_symbol_node(&mut graph, file, "foo", false);
+
+    let mut partials = PartialPaths::new();
+    let mut edges = Appendables::new();
+    let mut cd = AppendingCycleDetector::new();
+    let db = &GraphEdges;
+    for _ in 0..3 {
+        for edge in &[
+            edge(r, foo_ref, 0),
+            edge(foo_ref, s, 0),
+            edge(s, foo_def, 0),
+            edge(foo_def, r, 0),
+        ] {
+            cd.append(&mut edges, *edge);
+        }
+        assert_eq!(
+            enum_set![Cyclicity::StrengthensPostcondition],
+            cd.is_cyclic(&graph, &mut partials, db, &mut edges).unwrap()
+        );
+    }
+}
+
 #[test]
 fn stitching_simple_identity_cycle_is_detected() {
     let mut graph = StackGraph::new();
@@ -364,3 +396,79 @@ fn appending_eliminating_cycle_terminates() {
         assert_eq!(1, path_count);
     }
 }
+
+// ----------------------------------------------------------------------------
+// similar path budget
+
+#[test]
+fn similar_path_budget_limits_paths_per_bucket() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let r = StackGraph::root_node();
+    let x_ref = create_push_symbol_node(&mut graph, file, "x", true);
+    for symbol in ["a", "b", "c"] {
+        let push = create_push_symbol_node(&mut graph, file, symbol, false);
+        graph.add_edge(x_ref, push, 0);
+        graph.add_edge(push, r, 0);
+    }
+
+    let find_root_paths = |partials: &mut PartialPaths, config: StitcherConfig| {
+        let mut root_path_count = 0usize;
+        let cancellation_flag = CancelAfterDuration::new(TEST_TIMEOUT);
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            partials,
+            file,
+            config,
+            &cancellation_flag,
+            |_, _, p| {
+                if p.end_node == r {
+                    root_path_count += 1;
+                }
+            },
+        )
+        .unwrap();
+        root_path_count
+    };
+
+    let mut partials = PartialPaths::new();
+    assert_eq!(3, find_root_paths(&mut partials, StitcherConfig::default()));
+    assert_eq!(
+        1,
+        find_root_paths(
+            &mut partials,
+            StitcherConfig::default().with_similar_path_budget(Some(1))
+        )
+    );
+}
+
+// ----------------------------------------------------------------------------
+// similar path detection
+
+#[test]
+fn similar_paths_are_only_compared_if_fingerprints_match() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let s = create_scope_node(&mut graph, file, false);
+    let t = create_scope_node(&mut graph, file, false);
+    let foo_def = create_pop_symbol_node(&mut graph, file, "foo", false);
+    let bar_def = create_pop_symbol_node(&mut graph, file, "bar", false);
+
+    let mut partials = PartialPaths::new();
+    let foo = create_partial_path_and_edges(&mut graph, &mut partials, &[s, foo_def, t]).unwrap();
+    let bar = create_partial_path_and_edges(&mut graph, &mut partials, &[s, bar_def, t]).unwrap();
+
+    let comparisons = Cell::new(0);
+    let cmp = |ps: &mut PartialPaths, left: &PartialPath, right: &PartialPath| {
+        comparisons.set(comparisons.get() + 1);
+        left.equals(ps, right).then_some(Ordering::Equal)
+    };
+    let mut detector = SimilarPathDetector::new();
+    assert!(!detector.add_path(&graph, &mut partials, &foo, cmp));
+    // Both paths have the same start and end nodes and condition lengths, but differ in their
+    // symbol stacks.
+    assert!(!detector.add_path(&graph, &mut partials, &bar, cmp));
+    assert_eq!(0, comparisons.get());
+    assert!(detector.add_path(&graph, &mut partials, &foo.clone(), cmp));
+    assert_eq!(1, comparisons.get());
+}
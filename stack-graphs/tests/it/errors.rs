@@ -0,0 +1,91 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use std::time::Duration;
+
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPath;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::paths::PathResolutionError;
+use stack_graphs::CancelAfterDuration;
+use stack_graphs::CancellationFlag;
+use stack_graphs::StackGraphError;
+
+use crate::util::create_pop_symbol_node;
+use crate::util::create_push_symbol_node;
+
+fn append_unconnected_edge() -> Result<(), StackGraphError> {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test");
+    let a = create_push_symbol_node(&mut graph, file, "a", true);
+    let b = create_pop_symbol_node(&mut graph, file, "a", true);
+    graph.add_edge(a, b, 0);
+    let edge = graph.outgoing_edges(a).next().unwrap();
+    let mut partials = PartialPaths::new();
+    let mut path = PartialPath::from_node(&graph, &mut partials, b);
+    path.append(&graph, &mut partials, edge)?;
+    Ok(())
+}
+
+#[test]
+fn path_resolution_errors_convert_into_stack_graph_error() {
+    let err = append_unconnected_edge().unwrap_err();
+    assert!(matches!(
+        err,
+        StackGraphError::PathResolution(PathResolutionError::IncorrectSourceNode)
+    ));
+    assert_eq!(
+        "edge source node does not match the end of the path",
+        err.to_string()
+    );
+}
+
+#[test]
+fn cancellation_errors_convert_into_boxed_errors() {
+    fn cancelled() -> Result<(), Box<dyn std::error::Error>> {
+        let flag = CancelAfterDuration::new(Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(1));
+        flag.check("testing").map_err(StackGraphError::from)?;
+        Ok(())
+    }
+    let err = cancelled().unwrap_err();
+    assert!(err.downcast_ref::<StackGraphError>().is_some());
+    assert_eq!("Cancelled at \"testing\"", err.to_string());
+}
+
+#[cfg(feature = "storage")]
+#[test]
+fn storage_errors_convert_into_stack_graph_error() {
+    fn open_missing() -> Result<(), StackGraphError> {
+        stack_graphs::storage::SQLiteReader::open("/nonexistent/stack-graphs.sqlite")?;
+        Ok(())
+    }
+    let err = open_missing().unwrap_err();
+    assert!(matches!(err, StackGraphError::Storage(_)));
+}
+
+#[test]
+fn cancelled_assertions_report_the_cancellation_as_source() {
+    use std::error::Error;
+    let err =
+        stack_graphs::assert::AssertionError::from(stack_graphs::CancellationError("testing"));
+    assert_eq!("assertion was cancelled", err.to_string());
+    let source = err.source().expect("missing source");
+    assert_eq!("Cancelled at \"testing\"", source.to_string());
+    let err = StackGraphError::from(err);
+    assert!(matches!(err, StackGraphError::Assertion(_)));
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn json_errors_convert_into_stack_graph_error() {
+    fn parse() -> Result<serde_json::Value, StackGraphError> {
+        Ok(serde_json::from_str("{")?)
+    }
+    let err = parse().unwrap_err();
+    assert!(matches!(err, StackGraphError::Json(_)));
+}
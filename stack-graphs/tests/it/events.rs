@@ -0,0 +1,208 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use pretty_assertions::assert_eq;
+use stack_graphs::events::graph_events;
+use stack_graphs::events::EventNodeID;
+use stack_graphs::events::GraphEvent;
+use stack_graphs::events::GraphEventConsumer;
+use stack_graphs::events::GraphEventError;
+use stack_graphs::events::NewNode;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::StackGraph;
+
+use crate::test_graphs;
+
+fn encode(events: &[GraphEvent]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for event in events {
+        event.write_to(&mut bytes).unwrap();
+    }
+    bytes
+}
+
+#[test]
+fn can_build_graph_from_event_stream() {
+    let graph: StackGraph = test_graphs::class_field_through_function_parameter::new();
+    let events = graph_events(&graph);
+    let bytes = encode(&events);
+
+    let mut built = StackGraph::new();
+    let count = GraphEventConsumer::new(&mut built)
+        .consume(&mut bytes.as_slice())
+        .unwrap();
+    assert_eq!(events.len(), count);
+    assert_eq!(graph.to_serializable(), built.to_serializable());
+}
+
+#[test]
+fn events_round_trip_through_binary_encoding() {
+    let graph: StackGraph = test_graphs::simple::new();
+    let events = graph_events(&graph);
+    let bytes = encode(&events);
+
+    let mut reader = bytes.as_slice();
+    let mut decoded = Vec::new();
+    while let Some(event) = GraphEvent::read_from(&mut reader).unwrap() {
+        decoded.push(event);
+    }
+    assert_eq!(events, decoded);
+}
+
+#[test]
+fn events_preserve_symbol_namespaces() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let type_x = graph.add_namespaced_symbol("x", "type");
+    let value_x = graph.add_namespaced_symbol("x", "value");
+    let ref_id = graph.new_node_id(file);
+    let reference = graph.add_push_symbol_node(ref_id, type_x, true).unwrap();
+    let def_id = graph.new_node_id(file);
+    let definition = graph.add_pop_symbol_node(def_id, value_x, true).unwrap();
+    graph.add_edge(reference, definition, 0);
+    let bytes = encode(&graph_events(&graph));
+
+    let mut built = StackGraph::new();
+    GraphEventConsumer::new(&mut built)
+        .consume(&mut bytes.as_slice())
+        .unwrap();
+    assert_eq!(graph.to_serializable(), built.to_serializable());
+}
+
+#[test]
+fn invalid_events_are_rejected() {
+    let mut graph = StackGraph::new();
+    let mut consumer = GraphEventConsumer::new(&mut graph);
+    consumer
+        .apply(GraphEvent::AddFile {
+            name: "test.py".to_string(),
+        })
+        .unwrap();
+    let scope = GraphEvent::AddNode {
+        id: EventNodeID::new_in_file(0, 1),
+        node: NewNode::Scope { is_exported: false },
+    };
+    consumer.apply(scope.clone()).unwrap();
+
+    assert!(matches!(
+        consumer.apply(GraphEvent::AddFile {
+            name: "test.py".to_string(),
+        }),
+        Err(GraphEventError::DuplicateFile(_))
+    ));
+    assert!(matches!(
+        consumer.apply(scope),
+        Err(GraphEventError::DuplicateNode(_))
+    ));
+    assert!(matches!(
+        consumer.apply(GraphEvent::AddNode {
+            id: EventNodeID::new_in_file(1, 1),
+            node: NewNode::DropScopes,
+        }),
+        Err(GraphEventError::UnknownFile(1))
+    ));
+    assert!(matches!(
+        consumer.apply(GraphEvent::AddNode {
+            id: EventNodeID::root(),
+            node: NewNode::Scope { is_exported: true },
+        }),
+        Err(GraphEventError::InvalidNodeID(_))
+    ));
+    assert!(matches!(
+        consumer.apply(GraphEvent::AddEdge {
+            source: EventNodeID::new_in_file(0, 1),
+            sink: EventNodeID::new_in_file(0, 2),
+            precedence: 0,
+        }),
+        Err(GraphEventError::UnknownNode(_))
+    ));
+    assert_eq!(2, consumer.event_count());
+    assert_eq!(
+        1,
+        graph
+            .iter_nodes()
+            .filter(|n| matches!(graph[*n], Node::Scope(_)))
+            .count()
+    );
+}
+
+#[test]
+fn malformed_streams_are_rejected() {
+    let bytes = encode(&[GraphEvent::AddFile {
+        name: "test.py".to_string(),
+    }]);
+
+    let mut graph = StackGraph::new();
+    let truncated = &bytes[..bytes.len() - 1];
+    assert!(matches!(
+        GraphEventConsumer::new(&mut graph).consume(&mut &truncated[..]),
+        Err(GraphEventError::Io(_))
+    ));
+
+    let mut graph = StackGraph::new();
+    assert!(matches!(
+        GraphEventConsumer::new(&mut graph).consume(&mut &[42u8][..]),
+        Err(GraphEventError::InvalidTag(42))
+    ));
+}
+
+#[test]
+fn can_change_node_flags_with_events() {
+    let events = [
+        GraphEvent::AddFile {
+            name: "test.py".to_string(),
+        },
+        GraphEvent::AddNode {
+            id: EventNodeID::new_in_file(0, 1),
+            node: NewNode::PopSymbol {
+                symbol: "x".to_string(),
+                namespace: None,
+                is_definition: true,
+            },
+        },
+        GraphEvent::AddNode {
+            id: EventNodeID::new_in_file(0, 2),
+            node: NewNode::PushSymbol {
+                symbol: "x".to_string(),
+                namespace: None,
+                is_reference: false,
+            },
+        },
+        GraphEvent::SetIsDefinition {
+            node: EventNodeID::new_in_file(0, 1),
+            is_definition: false,
+        },
+        GraphEvent::SetIsReference {
+            node: EventNodeID::new_in_file(0, 2),
+            is_reference: true,
+        },
+    ];
+    let bytes = encode(&events);
+
+    let mut graph = StackGraph::new();
+    let mut consumer = GraphEventConsumer::new(&mut graph);
+    consumer.consume(&mut bytes.as_slice()).unwrap();
+    assert!(matches!(
+        consumer.apply(GraphEvent::SetIsReference {
+            node: EventNodeID::new_in_file(0, 1),
+            is_reference: true,
+        }),
+        Err(GraphEventError::NotAReferenceNode(_))
+    ));
+    assert!(matches!(
+        consumer.apply(GraphEvent::SetIsDefinition {
+            node: EventNodeID::root(),
+            is_definition: true,
+        }),
+        Err(GraphEventError::NotADefinitionNode(_))
+    ));
+
+    let file = graph.get_file("test.py").unwrap();
+    let nodes = graph.nodes_for_file(file).collect::<Vec<_>>();
+    assert!(!graph[nodes[0]].is_definition());
+    assert!(graph[nodes[1]].is_reference());
+}
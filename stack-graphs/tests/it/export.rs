@@ -0,0 +1,405 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use lsp_positions::SpanCalculator;
+use stack_graphs::arena::Handle;
+use stack_graphs::export::lsif::write_lsif;
+use stack_graphs::export::scip;
+use stack_graphs::export::scip::Message;
+use stack_graphs::export::Resolutions;
+use stack_graphs::graph::File;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
+use std::path::Path;
+
+use crate::util::create_pop_symbol_node;
+use crate::util::create_push_symbol_node;
+
+fn set_span(graph: &mut StackGraph, node: Handle<Node>, source: &str, name: &str) {
+    let mut calculator = SpanCalculator::new(source);
+    let start = source.find(name).unwrap();
+    let source_info = graph.source_info_mut(node);
+    source_info.span.start = calculator.for_utf8_offset_in_string(start);
+    source_info.span.end = calculator.for_utf8_offset_in_string(start + name.len());
+}
+
+/// Creates a graph where a reference to `foo` in main.py resolves to its definition in lib.py.
+fn create_graph() -> (StackGraph, Handle<File>, Handle<Node>, Handle<Node>) {
+    let mut graph = StackGraph::new();
+    let root = StackGraph::root_node();
+
+    let lib = graph.add_file("/src/lib.py").unwrap();
+    let definition = create_pop_symbol_node(&mut graph, lib, "foo", true);
+    set_span(&mut graph, definition, "def foo(): pass\n", "foo");
+    graph.add_edge(root, definition, 0);
+
+    let main = graph.add_file("/src/main.py").unwrap();
+    let reference = create_push_symbol_node(&mut graph, main, "foo", true);
+    set_span(&mut graph, reference, "import lib\nfoo()\n", "foo");
+    graph.add_edge(reference, root, 0);
+
+    (graph, main, reference, definition)
+}
+
+fn resolve(graph: &StackGraph, reference: Handle<Node>) -> Resolutions {
+    let mut partials = PartialPaths::new();
+    let mut resolutions = Resolutions::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(graph, &mut partials, None),
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| resolutions.add_path(path),
+    )
+    .unwrap();
+    resolutions
+}
+
+#[test]
+fn can_export_resolved_references_to_lsif() {
+    let (graph, main, reference, definition) = create_graph();
+    let resolutions = resolve(&graph, reference);
+    assert_eq!(&[definition], resolutions.definitions(reference));
+
+    let mut output = Vec::new();
+    write_lsif(
+        &graph,
+        &[main],
+        &resolutions,
+        Path::new("/src"),
+        &mut output,
+    )
+    .unwrap();
+    let elements = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+        .collect::<Vec<_>>();
+    let find = |label: &str| {
+        elements
+            .iter()
+            .filter(|e| e["label"] == label)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!("file:///src", find("metaData")[0]["projectRoot"]);
+    let documents = find("document");
+    assert_eq!(2, documents.len());
+    assert_eq!("file:///src/lib.py", documents[0]["uri"]);
+    assert_eq!("file:///src/main.py", documents[1]["uri"]);
+
+    // the reference range shares the result set of the definition range
+    let ranges = find("range");
+    assert_eq!(2, ranges.len());
+    assert_eq!(1, ranges[1]["start"]["line"]);
+    let next = find("next");
+    assert_eq!(2, next.len());
+    assert_eq!(ranges[0]["id"], next[0]["outV"]);
+    assert_eq!(ranges[1]["id"], next[1]["outV"]);
+    assert_eq!(next[0]["inV"], next[1]["inV"]);
+
+    let references = find("item")
+        .into_iter()
+        .filter(|e| e["property"] == "references")
+        .collect::<Vec<_>>();
+    assert_eq!(1, references.len());
+    assert_eq!(serde_json::json!([ranges[1]["id"]]), references[0]["inVs"]);
+    assert_eq!(2, find("contains").len());
+
+    // the definition has a moniker with the stable identifier of its symbol
+    let monikers = find("moniker");
+    assert_eq!(2, monikers.len());
+    assert_eq!("stack-graphs", monikers[0]["scheme"]);
+    let symbol = graph[definition].symbol().unwrap();
+    assert_eq!(
+        graph.symbol_id(symbol).to_string(),
+        monikers[0]["identifier"]
+    );
+    assert_eq!(monikers[0]["id"], monikers[1]["inV"]);
+    assert_eq!(next[0]["inV"], monikers[1]["outV"]);
+
+    // every edge refers to elements that were written before it
+    for (i, element) in elements.iter().enumerate() {
+        assert_eq!(i + 1, element["id"].as_u64().unwrap() as usize);
+        if element["type"] == "edge" {
+            assert!(element["outV"].as_u64().unwrap() < element["id"].as_u64().unwrap());
+        }
+    }
+}
+
+#[test]
+fn can_export_resolved_references_to_scip() {
+    let (graph, main, reference, definition) = create_graph();
+    let resolutions = resolve(&graph, reference);
+
+    let index = scip::Index::from_resolutions(&graph, &[main], &resolutions, Path::new("/src"));
+    let index = scip::Index::decode(index.encode_to_vec().as_slice()).unwrap();
+
+    let symbol = format!(
+        "stack-graphs . . . `/src/lib.py`/foo({}).",
+        graph[definition].id().local_id()
+    );
+    assert_eq!("file:///src", index.metadata.unwrap().project_root);
+    assert_eq!(1, index.documents.len());
+    assert_eq!("main.py", index.documents[0].relative_path);
+    assert_eq!(
+        vec![scip::Occurrence {
+            range: vec![1, 0, 3],
+            symbol: symbol.clone(),
+            symbol_roles: 0,
+        }],
+        index.documents[0].occurrences
+    );
+    assert_eq!(
+        vec![scip::SymbolInformation {
+            symbol,
+            display_name: "foo".to_string(),
+        }],
+        index.external_symbols
+    );
+}
+
+#[test]
+fn definitions_in_exported_files_are_exported_without_references() {
+    let (graph, _, _, definition) = create_graph();
+    let lib = graph[definition].file().unwrap();
+
+    let index =
+        scip::Index::from_resolutions(&graph, &[lib], &Resolutions::new(), Path::new("/src"));
+    assert_eq!(1, index.documents.len());
+    let occurrences = &index.documents[0].occurrences;
+    assert_eq!(1, occurrences.len());
+    assert_eq!(scip::SYMBOL_ROLE_DEFINITION, occurrences[0].symbol_roles);
+    assert_eq!(1, index.documents[0].symbols.len());
+    assert!(index.external_symbols.is_empty());
+}
+
+/// Stores the graph of [`create_graph`][], with another reference to `foo` in a file whose name
+/// needs quoting in CSV, in a new database.
+#[cfg(feature = "storage")]
+fn create_edges_database(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "stack-graphs-edges-{}-{}.sqlite",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let (mut graph, main, reference, definition) = create_graph();
+    let other = graph.add_file("/src/a,b.py").unwrap();
+    let other_reference = create_push_symbol_node(&mut graph, other, "foo", true);
+    set_span(&mut graph, other_reference, "x = foo\n", "foo");
+    graph.add_edge(other_reference, StackGraph::root_node(), 0);
+    let lib = graph[definition].file().unwrap();
+
+    let mut writer = stack_graphs::storage::SQLiteWriter::open(&path).unwrap();
+    let mut partials = PartialPaths::new();
+    for (file, nodes) in [
+        (lib, vec![StackGraph::root_node(), definition]),
+        (main, vec![reference, StackGraph::root_node()]),
+        (other, vec![other_reference, StackGraph::root_node()]),
+    ] {
+        let path =
+            crate::util::create_partial_path_and_edges(&mut graph, &mut partials, &nodes).unwrap();
+        writer
+            .store_result_for_file(&graph, file, "", &mut partials, &[path])
+            .unwrap();
+    }
+    path
+}
+
+#[cfg(feature = "storage")]
+#[test]
+fn can_export_reference_edges_in_parallel() {
+    use stack_graphs::export::edges::EdgeExporter;
+    use stack_graphs::export::edges::EdgeFormat;
+    use stack_graphs::graph::SymbolId;
+    use stack_graphs::storage::SQLiteReaderPool;
+
+    let db_path = create_edges_database("parallel");
+    let pool = SQLiteReaderPool::open(&db_path).unwrap();
+    let output = db_path.with_extension("csv");
+    let stats = EdgeExporter::new(EdgeFormat::Csv)
+        .with_thread_count(2)
+        .with_batch_size(1)
+        .export(&pool, &output, &NoCancellation)
+        .unwrap();
+    assert_eq!(3, stats.files);
+    assert_eq!(2, stats.edges);
+    assert!(!EdgeExporter::checkpoint_path(&output).exists());
+
+    let id = SymbolId::new("foo", None);
+    assert_eq!(
+        format!(
+            "reference_file,reference_line,reference_column,symbol,symbol_id,definition_file,definition_line,definition_column\n\
+             \"/src/a,b.py\",0,4,foo,{id},/src/lib.py,0,4\n\
+             /src/main.py,1,0,foo,{id},/src/lib.py,0,4\n"
+        ),
+        std::fs::read_to_string(&output).unwrap()
+    );
+
+    let _ = std::fs::remove_file(&output);
+    let _ = std::fs::remove_file(&db_path);
+}
+
+#[cfg(feature = "storage")]
+#[test]
+fn can_resume_interrupted_reference_edge_export() {
+    use stack_graphs::export::edges::EdgeExporter;
+    use stack_graphs::export::edges::EdgeFormat;
+    use stack_graphs::storage::SQLiteReaderPool;
+
+    let db_path = create_edges_database("resume");
+    let pool = SQLiteReaderPool::open(&db_path).unwrap();
+    let output = db_path.with_extension("jsonl");
+    let exporter = EdgeExporter::new(EdgeFormat::Jsonl);
+    exporter.export(&pool, &output, &NoCancellation).unwrap();
+    let expected = std::fs::read_to_string(&output).unwrap();
+    let first_line = expected.lines().next().unwrap();
+    assert_eq!(
+        "/src/a,b.py",
+        serde_json::from_str::<serde_json::Value>(first_line).unwrap()["reference_file"]
+    );
+
+    // simulate an export that was interrupted while writing the edges of the second file
+    let checkpoint = EdgeExporter::checkpoint_path(&output);
+    std::fs::write(
+        &checkpoint,
+        format!("{}\t/src/a,b.py\n", first_line.len() + 1),
+    )
+    .unwrap();
+    std::fs::write(&output, format!("{}\n{{\"partial", first_line)).unwrap();
+
+    let stats = exporter.export(&pool, &output, &NoCancellation).unwrap();
+    assert_eq!(1, stats.resumed_files);
+    assert_eq!(2, stats.files);
+    assert_eq!(1, stats.edges);
+    assert_eq!(expected, std::fs::read_to_string(&output).unwrap());
+    assert!(!checkpoint.exists());
+
+    let _ = std::fs::remove_file(&output);
+    let _ = std::fs::remove_file(&db_path);
+}
+
+#[cfg(feature = "arrow")]
+fn read_parquet(path: &Path) -> Vec<arrow_array::RecordBatch> {
+    parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(
+        std::fs::File::open(path).unwrap(),
+    )
+    .unwrap()
+    .build()
+    .unwrap()
+    .collect::<Result<Vec<_>, _>>()
+    .unwrap()
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn can_export_reference_edges_to_parquet() {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::UInt64Type;
+    use stack_graphs::export::arrow::reference_edge_schema;
+    use stack_graphs::export::edges::EdgeExporter;
+    use stack_graphs::export::edges::EdgeFormat;
+    use stack_graphs::graph::SymbolId;
+    use stack_graphs::storage::SQLiteReaderPool;
+
+    let db_path = create_edges_database("parquet");
+    let pool = SQLiteReaderPool::open(&db_path).unwrap();
+    let output = db_path.with_extension("parquet");
+    let stats = EdgeExporter::new(EdgeFormat::Parquet)
+        .with_thread_count(2)
+        .with_batch_size(2)
+        .export(&pool, &output, &NoCancellation)
+        .unwrap();
+    assert_eq!(3, stats.files);
+    assert_eq!(2, stats.edges);
+
+    let batches = read_parquet(&output);
+    let mut rows = Vec::new();
+    for batch in &batches {
+        assert_eq!(reference_edge_schema(), batch.schema());
+        let reference_files = batch
+            .column_by_name("reference_file")
+            .unwrap()
+            .as_string::<i32>();
+        let symbol_ids = batch
+            .column_by_name("symbol_id")
+            .unwrap()
+            .as_primitive::<UInt64Type>();
+        let definition_columns = batch
+            .column_by_name("definition_column")
+            .unwrap()
+            .as_primitive::<UInt64Type>();
+        for i in 0..batch.num_rows() {
+            rows.push((
+                reference_files.value(i).to_string(),
+                symbol_ids.value(i),
+                definition_columns.value(i),
+            ));
+        }
+    }
+    let id = SymbolId::new("foo", None).as_u64();
+    assert_eq!(
+        vec![
+            ("/src/a,b.py".to_string(), id, 4),
+            ("/src/main.py".to_string(), id, 4),
+        ],
+        rows
+    );
+
+    let _ = std::fs::remove_file(&output);
+    let _ = std::fs::remove_file(&db_path);
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn can_write_file_stats_to_parquet() {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::UInt64Type;
+    use stack_graphs::export::arrow::file_stats_schema;
+    use stack_graphs::export::arrow::file_stats_to_record_batch;
+    use stack_graphs::export::arrow::write_parquet;
+    use stack_graphs::storage::SQLiteReader;
+
+    let db_path = create_edges_database("file-stats");
+    let stats = SQLiteReader::open(&db_path)
+        .unwrap()
+        .database_stats()
+        .unwrap();
+    let batch = file_stats_to_record_batch(&stats.files).unwrap();
+    assert_eq!(3, batch.num_rows());
+
+    let output = db_path.with_extension("parquet");
+    write_parquet(
+        std::fs::File::create(&output).unwrap(),
+        file_stats_schema(),
+        [batch],
+    )
+    .unwrap();
+    let batches = read_parquet(&output);
+    assert_eq!(1, batches.len());
+    let batch = &batches[0];
+    assert_eq!(file_stats_schema(), batch.schema());
+    let paths = batch.column_by_name("path").unwrap().as_string::<i32>();
+    assert_eq!("/src/a,b.py", paths.value(0));
+    let status = batch.column_by_name("status").unwrap().as_string::<i32>();
+    assert_eq!("indexed", status.value(0));
+    assert!(batch.column_by_name("status_reason").unwrap().is_null(0));
+    let node_counts = batch
+        .column_by_name("node_count")
+        .unwrap()
+        .as_primitive::<UInt64Type>();
+    assert_eq!(stats.files[0].node_count as u64, node_counts.value(0));
+
+    let _ = std::fs::remove_file(&output);
+    let _ = std::fs::remove_file(&db_path);
+}
@@ -0,0 +1,56 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use arbitrary::Arbitrary;
+use arbitrary::Unstructured;
+use stack_graphs::fuzzing::check_append;
+use stack_graphs::fuzzing::check_concatenate;
+use stack_graphs::fuzzing::check_resolve;
+
+/// The number of generated inputs each invariant is checked on.
+const INPUTS: u64 = 500;
+
+/// Generates inputs from deterministic pseudo-random bytes, so that failures are reproducible.
+fn generated_inputs<T>() -> impl Iterator<Item = T>
+where
+    T: for<'a> Arbitrary<'a>,
+{
+    (1..=INPUTS).filter_map(|seed| {
+        // xorshift64
+        let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        let bytes = (0..512)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect::<Vec<_>>();
+        T::arbitrary_take_rest(Unstructured::new(&bytes)).ok()
+    })
+}
+
+#[test]
+fn appending_edges_is_consistent_with_concatenation() {
+    for input in generated_inputs() {
+        check_append(&input);
+    }
+}
+
+#[test]
+fn concatenating_partial_paths_is_associative() {
+    for input in generated_inputs() {
+        check_concatenate(&input);
+    }
+}
+
+#[test]
+fn resolved_paths_are_complete() {
+    for input in generated_inputs() {
+        check_resolve(&input);
+    }
+}
@@ -5,13 +5,32 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::borrow::Cow;
 use std::collections::HashSet;
 
 use maplit::hashset;
+use stack_graphs::arena::Handle;
+use stack_graphs::arena::HandleSet;
+use stack_graphs::graph::Degree;
+use stack_graphs::graph::Edge;
+use stack_graphs::graph::EdgeDirection;
+use stack_graphs::graph::File;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::PushScopedSymbolError;
 use stack_graphs::graph::StackGraph;
+use stack_graphs::graph::SymbolId;
+use stack_graphs::graph::SymbolNormalization;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::Database;
+use stack_graphs::stitching::DatabaseCandidates;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
 
 use crate::test_graphs;
 use crate::test_graphs::CreateStackGraph;
+use crate::util::*;
 
 #[test]
 fn can_create_symbols() {
@@ -68,6 +87,346 @@ fn can_display_symbols() {
     assert_eq!(symbols, vec!["a", "b", "c"]);
 }
 
+#[test]
+fn can_create_namespaced_symbols() {
+    let mut graph = StackGraph::new();
+    let a = graph.add_symbol("a");
+    let value_a1 = graph.add_namespaced_symbol("a", "value");
+    let value_a2 = graph.add_namespaced_symbol("a", "value");
+    let type_a = graph.add_namespaced_symbol("a", "type");
+    // Symbols are only equal if they have the same name and namespace
+    assert_eq!(value_a1, value_a2);
+    assert_ne!(a, value_a1);
+    assert_ne!(value_a1, type_a);
+    // but they still have the same name
+    assert_eq!(&graph[a], &graph[value_a1]);
+    assert_eq!(&graph[a], &graph[type_a]);
+    assert_eq!(None, graph.symbol_namespace(a));
+    assert_eq!(Some("value"), graph.symbol_namespace(value_a1));
+    assert_eq!(Some("type"), graph.symbol_namespace(type_a));
+    assert_eq!(a, graph.symbol_without_namespace(type_a));
+    assert_eq!("type:a", type_a.display(&graph).to_string());
+    assert_eq!("a", a.display(&graph).to_string());
+}
+
+#[test]
+fn namespaced_symbols_only_match_in_same_namespace() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let root = StackGraph::root_node();
+    let type_x = graph.add_namespaced_symbol("x", "type");
+    let value_x = graph.add_namespaced_symbol("x", "value");
+    let x = graph.add_symbol("x");
+
+    let ref_id = graph.new_node_id(file);
+    let reference = graph.add_push_symbol_node(ref_id, type_x, true).unwrap();
+    graph.add_edge(reference, root, 0);
+    let mut definitions = Vec::new();
+    for symbol in [type_x, value_x, x] {
+        let def_id = graph.new_node_id(file);
+        let definition = graph.add_pop_symbol_node(def_id, symbol, true).unwrap();
+        graph.add_edge(root, definition, 0);
+        definitions.push(definition);
+    }
+
+    let mut partials = PartialPaths::new();
+    let mut complete_paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, p| complete_paths.push(p.end_node),
+    )
+    .unwrap();
+    assert_eq!(vec![definitions[0]], complete_paths);
+}
+
+#[test]
+fn can_normalize_symbols() {
+    let normalization = SymbolNormalization::none();
+    assert!(normalization.is_identity());
+    assert_eq!("Foo", normalization.normalize("Foo"));
+
+    let normalization = SymbolNormalization::none().with_case_folding(true);
+    assert_eq!("foo", normalization.normalize("Foo"));
+    assert!(matches!(normalization.normalize("foo"), Cow::Borrowed(_)));
+    assert_eq!("straße", normalization.normalize("STRAßE"));
+
+    let normalization = SymbolNormalization::none().with_unicode_nfc(true);
+    assert_eq!("caf\u{e9}", normalization.normalize("cafe\u{301}"));
+    assert!(matches!(
+        normalization.normalize("caf\u{e9}"),
+        Cow::Borrowed(_)
+    ));
+}
+
+/// Creates a graph where module `b` re-exports all symbols of module `a` except `_y`, and a
+/// reference to `b.<reference>`.  Returns whether the reference resolves, both when stitching graph
+/// edges and when stitching partial paths from a database.
+fn resolve_through_wildcard_reexport(reference: &str) -> (bool, bool) {
+    let mut graph = StackGraph::new();
+    let root = StackGraph::root_node();
+
+    let a_file = graph.get_or_create_file("a.py");
+    let a_module = create_pop_symbol_node(&mut graph, a_file, "a", false);
+    let a_exports = create_scope_node(&mut graph, a_file, true);
+    graph.add_edge(root, a_module, 0);
+    graph.add_edge(a_module, a_exports, 0);
+    for symbol in ["x", "_y"] {
+        let definition = create_pop_symbol_node(&mut graph, a_file, symbol, true);
+        graph.add_edge(a_exports, definition, 0);
+    }
+
+    let b_file = graph.get_or_create_file("b.py");
+    let b_module = create_pop_symbol_node(&mut graph, b_file, "b", false);
+    let b_exports = create_scope_node(&mut graph, b_file, true);
+    let wildcard = create_scope_node(&mut graph, b_file, true);
+    let import_a = create_push_symbol_node(&mut graph, b_file, "a", false);
+    graph.add_edge(root, b_module, 0);
+    graph.add_edge(b_module, b_exports, 0);
+    graph.add_edge(b_exports, wildcard, 0);
+    graph.add_edge(wildcard, import_a, 0);
+    graph.add_edge(import_a, root, 0);
+    let excluded = graph.add_symbol("_y");
+    graph.set_excluded_symbols(wildcard, &[excluded]);
+
+    let c_file = graph.get_or_create_file("c.py");
+    let reference = create_push_symbol_node(&mut graph, c_file, reference, true);
+    let import_b = create_push_symbol_node(&mut graph, c_file, "b", false);
+    graph.add_edge(reference, import_b, 0);
+    graph.add_edge(import_b, root, 0);
+
+    let mut partials = PartialPaths::new();
+    let mut from_graph = false;
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, _| from_graph = true,
+    )
+    .unwrap();
+
+    let mut db = Database::new();
+    for file in [a_file, b_file, c_file] {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .unwrap();
+    }
+    let mut from_database = false;
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, _| from_database = true,
+    )
+    .unwrap();
+
+    (from_graph, from_database)
+}
+
+#[test]
+fn wildcard_reexports_resolve_included_symbols() {
+    assert_eq!((true, true), resolve_through_wildcard_reexport("x"));
+}
+
+#[test]
+fn wildcard_reexports_do_not_resolve_excluded_symbols() {
+    assert_eq!((false, false), resolve_through_wildcard_reexport("_y"));
+}
+
+#[test]
+fn excluded_symbols_ignore_namespaces_and_non_scope_nodes() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let scope = create_scope_node(&mut graph, file, true);
+    let definition = create_pop_symbol_node(&mut graph, file, "x", true);
+    let x = graph.add_symbol("x");
+    let type_x = graph.add_type_symbol("x");
+
+    graph.set_excluded_symbols(scope, &[type_x]);
+    graph.set_excluded_symbols(definition, &[x]);
+    assert_eq!(Some(&[x][..]), graph.excluded_symbols(scope));
+    assert!(graph.is_excluded_symbol(scope, type_x));
+    assert_eq!(None, graph.excluded_symbols(definition));
+
+    graph.set_excluded_symbols(scope, &[]);
+    assert_eq!(None, graph.excluded_symbols(scope));
+}
+
+/// Creates a graph with a reference to `reference`, and a definition for each of `definitions`,
+/// in separate files.  Returns the definitions that the reference resolves to, both when
+/// stitching graph edges and when stitching partial paths from a database.
+fn resolve_with_normalization(
+    normalization: SymbolNormalization,
+    reference: &str,
+    definitions: &[&str],
+) -> (Vec<usize>, Vec<usize>) {
+    let mut graph = StackGraph::new();
+    let root = StackGraph::root_node();
+    let ref_file = graph.get_or_create_file("ref.py");
+    let reference = create_push_symbol_node(&mut graph, ref_file, reference, true);
+    graph.add_edge(reference, root, 0);
+    let def_file = graph.get_or_create_file("def.py");
+    let definitions = definitions
+        .iter()
+        .map(|symbol| {
+            let definition = create_pop_symbol_node(&mut graph, def_file, symbol, true);
+            graph.add_edge(root, definition, 0);
+            definition
+        })
+        .collect::<Vec<_>>();
+    graph.set_symbol_normalization(normalization);
+    let index_of = |node| definitions.iter().position(|d| *d == node).unwrap();
+
+    let mut partials = PartialPaths::new();
+    let mut from_graph = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, p| from_graph.push(index_of(p.end_node)),
+    )
+    .unwrap();
+    from_graph.sort();
+
+    let mut db = Database::new();
+    for file in [ref_file, def_file] {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .unwrap();
+    }
+    let mut from_database = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, p| from_database.push(index_of(p.end_node)),
+    )
+    .unwrap();
+    from_database.sort();
+
+    (from_graph, from_database)
+}
+
+#[test]
+fn symbols_match_exactly_without_normalization() {
+    let results = resolve_with_normalization(
+        SymbolNormalization::none(),
+        "Foo",
+        &["FOO", "foo", "Foo", "bar"],
+    );
+    assert_eq!((vec![2], vec![2]), results);
+}
+
+#[test]
+fn symbols_match_case_insensitively_with_case_folding() {
+    let results = resolve_with_normalization(
+        SymbolNormalization::none().with_case_folding(true),
+        "Foo",
+        &["FOO", "foo", "Foo", "bar"],
+    );
+    assert_eq!((vec![0, 1, 2], vec![0, 1, 2]), results);
+}
+
+#[test]
+fn symbols_match_canonically_equivalent_symbols_with_unicode_nfc() {
+    let results = resolve_with_normalization(
+        SymbolNormalization::none().with_unicode_nfc(true),
+        "caf\u{e9}",
+        &["cafe\u{301}", "caf\u{e9}", "CAF\u{c9}"],
+    );
+    assert_eq!((vec![0, 1], vec![0, 1]), results);
+}
+
+#[test]
+fn normalized_symbols_keep_their_spelling_and_namespace() {
+    let mut graph = StackGraph::new();
+    graph.set_symbol_normalization(SymbolNormalization::none().with_case_folding(true));
+    let foo = graph.add_namespaced_symbol("Foo", "type");
+    let normalized = graph.normalized_symbol(foo);
+    assert_ne!(foo, normalized);
+    assert_eq!("Foo", &graph[foo]);
+    assert_eq!("type:foo", normalized.display(&graph).to_string());
+    assert_eq!(normalized, graph.normalized_symbol(normalized));
+    assert_eq!(normalized, graph.add_namespaced_symbol("foo", "type"));
+
+    graph.set_symbol_normalization(SymbolNormalization::none());
+    assert_eq!(foo, graph.normalized_symbol(foo));
+}
+
+#[test]
+fn non_ascii_symbols_are_stored_as_written() {
+    let mut graph = StackGraph::new();
+    let composed = graph.add_symbol("caf\u{e9}");
+    let decomposed = graph.add_symbol("cafe\u{301}");
+    let greek = graph.add_symbol("λx");
+    assert_ne!(composed, decomposed);
+    assert_eq!("caf\u{e9}", &graph[composed]);
+    assert_eq!("cafe\u{301}", &graph[decomposed]);
+    assert_eq!("λx", &graph[greek]);
+    assert_eq!(composed, graph.add_symbol("caf\u{e9}"));
+}
+
+#[test]
+fn can_normalize_symbols_when_interning() {
+    let mut graph = StackGraph::new();
+    graph.set_symbol_interning(SymbolNormalization::none().with_unicode_nfc(true));
+    let composed = graph.add_symbol("caf\u{e9}");
+    let decomposed = graph.add_symbol("cafe\u{301}");
+    assert_eq!(composed, decomposed);
+    assert_eq!("caf\u{e9}", &graph[decomposed]);
+    assert_eq!(
+        graph.add_namespaced_symbol("caf\u{e9}", "type"),
+        graph.add_namespaced_symbol("cafe\u{301}", "type")
+    );
+    // Case is preserved unless case folding is enabled as well
+    assert_ne!(composed, graph.add_symbol("CAF\u{c9}"));
+
+    graph.set_symbol_interning(
+        SymbolNormalization::none()
+            .with_unicode_nfc(true)
+            .with_case_folding(true),
+    );
+    assert_eq!(composed, graph.add_symbol("CAFE\u{301}"));
+}
+
+#[test]
+fn can_set_graph_metadata() {
+    let mut graph = StackGraph::new();
+    assert_eq!(None, graph.metadata("version"));
+    graph.set_metadata("version", "1");
+    graph.set_metadata("commit", "abc123");
+    graph.set_metadata("version", "2");
+    assert_eq!(Some("2"), graph.metadata("version"));
+    assert_eq!(
+        vec![("commit", "abc123"), ("version", "2")],
+        graph.iter_metadata().collect::<Vec<_>>()
+    );
+    assert_eq!(Some("abc123".to_string()), graph.remove_metadata("commit"));
+    assert_eq!(None, graph.metadata("commit"));
+}
+
 #[test]
 fn can_create_strings() {
     let mut graph = StackGraph::new();
@@ -159,6 +518,44 @@ fn can_add_and_remove_edges() {
     );
 }
 
+#[test]
+fn can_iterate_all_edges() {
+    let mut graph = StackGraph::new();
+    let file1 = graph.get_or_create_file("test1.py");
+    let file2 = graph.get_or_create_file("test2.py");
+    let root = StackGraph::root_node();
+    let h1 = graph.internal_scope(file1, 0);
+    let h2 = graph.internal_scope(file1, 1);
+    let h3 = graph.internal_scope(file2, 0);
+    graph.add_edge(h1, h2, 0);
+    graph.add_edge(h2, root, 0);
+    graph.add_edge(root, h3, 1);
+    graph.add_edge(h3, h1, 0);
+
+    assert_eq!(4, graph.edge_count());
+    assert_eq!(
+        graph
+            .iter_edges()
+            .map(|edge| (edge.source, edge.sink, edge.precedence))
+            .collect::<HashSet<_>>(),
+        hashset! { (h1, h2, 0), (h2, root, 0), (root, h3, 1), (h3, h1, 0) }
+    );
+    assert_eq!(
+        graph
+            .edges_for_file(file1)
+            .map(|edge| (edge.source, edge.sink))
+            .collect::<HashSet<_>>(),
+        hashset! { (h1, h2), (h2, root) }
+    );
+    assert_eq!(
+        graph
+            .edges_for_file(file2)
+            .map(|edge| (edge.source, edge.sink))
+            .collect::<HashSet<_>>(),
+        hashset! { (root, h3), (h3, h1) }
+    );
+}
+
 #[test]
 fn singleton_nodes_have_correct_ids() {
     let graph = StackGraph::new();
@@ -196,3 +593,452 @@ fn can_add_graph_to_empty_graph() {
         );
     }
 }
+
+#[test]
+fn can_add_push_scoped_symbol_with_scope() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let other_file = graph.get_or_create_file("other.py");
+    let root = StackGraph::root_node();
+    let jump_to = StackGraph::jump_to_node();
+
+    // f(x) where the called function resolves `x` in the scope of its call site
+    let ref_x = create_push_symbol_node(&mut graph, file, "x", true);
+    let arg_x = create_pop_symbol_node(&mut graph, file, "x", true);
+    let f = graph.add_symbol("f");
+    let call = graph
+        .add_push_scoped_symbol_with_scope(file, f, false, &[arg_x], root)
+        .unwrap();
+    graph.add_edge(ref_x, call.node, 0);
+    let def_f = create_pop_scoped_symbol_node(&mut graph, file, "f", true);
+    graph.add_edge(root, def_f, 0);
+    graph.add_edge(def_f, jump_to, 0);
+
+    assert!(graph[call.scope].is_exported_scope());
+    assert_eq!(
+        graph[call.node].scope(),
+        Some(graph[call.scope].id()),
+        "scoped symbol should have the new scope attached"
+    );
+    assert_eq!(
+        vec![arg_x],
+        graph
+            .outgoing_edges(call.scope)
+            .map(|e| e.sink)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![root],
+        graph
+            .outgoing_edges(call.node)
+            .map(|e| e.sink)
+            .collect::<Vec<_>>()
+    );
+
+    let mut partials = PartialPaths::new();
+    let mut complete_paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![ref_x],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, p| complete_paths.push(p.clone()),
+    )
+    .unwrap();
+    assert_eq!(1, complete_paths.len());
+    assert_eq!(arg_x, complete_paths[0].end_node);
+
+    let other_node = create_scope_node(&mut graph, other_file, false);
+    assert_eq!(
+        Err(PushScopedSymbolError::NodeInDifferentFile(other_node)),
+        graph.add_push_scoped_symbol_with_scope(file, f, false, &[other_node], root)
+    );
+}
+
+/// Adds a member `name` to the body of a type, whose type resolves at `type_node`, if any.
+/// Members are looked up with a `.` symbol, followed by their name.
+fn add_member(
+    graph: &mut StackGraph,
+    file: Handle<File>,
+    body: Handle<Node>,
+    name: &str,
+    type_node: Option<Handle<Node>>,
+) -> Handle<Node> {
+    let dot = create_pop_symbol_node(graph, file, ".", false);
+    let member = create_pop_symbol_node(graph, file, name, true);
+    graph.add_edge(body, dot, 0);
+    graph.add_edge(dot, member, 0);
+    if let Some(type_node) = type_node {
+        graph.add_edge(member, type_node, 0);
+    }
+    member
+}
+
+/// Creates a graph for the following program.  Returns the definition of `y`, and the definitions
+/// that the reference to `y` resolves to, both when stitching graph edges and when stitching
+/// partial paths from a database.
+///
+/// ``` text
+/// class List<T> { value: T }
+/// class A<U> { f: List<U> }
+/// class Foo { y }
+/// A<Foo>.f.value.y
+/// ```
+fn resolve_through_generics() -> (Handle<Node>, Vec<Handle<Node>>, Vec<Handle<Node>>) {
+    let mut graph = StackGraph::new();
+    let root = StackGraph::root_node();
+
+    let list_file = graph.get_or_create_file("list.py");
+    let list = graph.add_symbol("List");
+    let t = graph.add_symbol("T");
+    let list_def = graph.add_generic_definition(list_file, list, &[t]);
+    graph.add_edge(root, list_def.definition, 0);
+    let ref_t = create_push_symbol_node(&mut graph, list_file, "T", false);
+    graph.add_edge(ref_t, list_def.parameters, 0);
+    add_member(&mut graph, list_file, list_def.body, "value", Some(ref_t));
+
+    let a_file = graph.get_or_create_file("a.py");
+    let a = graph.add_symbol("A");
+    let u = graph.add_symbol("U");
+    let a_def = graph.add_generic_definition(a_file, a, &[u]);
+    graph.add_edge(root, a_def.definition, 0);
+    let ref_u = create_push_symbol_node(&mut graph, a_file, "U", false);
+    graph.add_edge(ref_u, a_def.parameters, 0);
+    let list_u = graph
+        .instantiate(a_file, list)
+        .with_argument(ref_u)
+        .build(root)
+        .unwrap();
+    add_member(&mut graph, a_file, a_def.body, "f", Some(list_u.node));
+
+    let foo_file = graph.get_or_create_file("foo.py");
+    let foo_def = create_pop_symbol_node(&mut graph, foo_file, "Foo", true);
+    let foo_body = create_scope_node(&mut graph, foo_file, false);
+    graph.add_edge(root, foo_def, 0);
+    graph.add_edge(foo_def, foo_body, 0);
+    let y = add_member(&mut graph, foo_file, foo_body, "y", None);
+
+    let use_file = graph.get_or_create_file("use.py");
+    let ref_foo = create_push_symbol_node(&mut graph, use_file, "Foo", false);
+    graph.add_edge(ref_foo, root, 0);
+    let a_foo = graph
+        .instantiate(use_file, a)
+        .with_argument(ref_foo)
+        .build(root)
+        .unwrap();
+    let mut reference = a_foo.node;
+    for symbol in [".", "f", ".", "value", ".", "y"] {
+        let push = create_push_symbol_node(&mut graph, use_file, symbol, symbol == "y");
+        graph.add_edge(push, reference, 0);
+        reference = push;
+    }
+
+    let mut partials = PartialPaths::new();
+    let mut from_graph = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, p| from_graph.push(p.end_node),
+    )
+    .unwrap();
+
+    let mut db = Database::new();
+    for file in [list_file, a_file, foo_file, use_file] {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .unwrap();
+    }
+    let mut from_database = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, p| from_database.push(p.end_node),
+    )
+    .unwrap();
+
+    (y, from_graph, from_database)
+}
+
+#[test]
+fn can_resolve_through_nested_generic_instantiations() {
+    let (y, from_graph, from_database) = resolve_through_generics();
+    assert_eq!(vec![y], from_graph);
+    assert_eq!(vec![y], from_database);
+}
+
+#[test]
+fn can_add_generic_definition() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let list = graph.add_symbol("List");
+    let k = graph.add_symbol("K");
+    let v = graph.add_symbol("V");
+    let generic = graph.add_generic_definition(file, list, &[k, v]);
+
+    assert!(graph[generic.definition].is_definition());
+    assert_eq!(
+        vec![generic.body],
+        graph
+            .outgoing_edges(generic.definition)
+            .map(|e| e.sink)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        generic.parameter_nodes,
+        graph
+            .outgoing_edges(generic.parameters)
+            .map(|e| e.sink)
+            .collect::<Vec<_>>()
+    );
+    let pushed = generic
+        .parameter_nodes
+        .iter()
+        .flat_map(|pop| graph.outgoing_edges(*pop))
+        .map(|e| e.sink)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![graph.type_argument_symbol(0), graph.type_argument_symbol(1)],
+        pushed
+            .iter()
+            .map(|push| graph[*push].symbol().unwrap())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn root_domains_isolate_resolution() {
+    let mut graph = StackGraph::new();
+    let root = StackGraph::root_node();
+    let mut definitions = Vec::new();
+    let mut files = Vec::new();
+    for (name, domain) in [
+        ("npm.js", Some("npm")),
+        ("pypi.py", Some("pypi")),
+        ("plain.js", None),
+    ] {
+        let file = graph.get_or_create_file(name);
+        let exit = match domain {
+            Some(domain) => graph.add_root_domain(file, domain).exit,
+            None => root,
+        };
+        let definition = create_pop_symbol_node(&mut graph, file, "x", true);
+        graph.add_edge(exit, definition, 0);
+        definitions.push(definition);
+        files.push(file);
+    }
+    let main = graph.get_or_create_file("main.js");
+    let npm = graph.add_root_domain(main, "npm");
+    let domain_reference = create_push_symbol_node(&mut graph, main, "x", true);
+    graph.add_edge(domain_reference, npm.entry, 0);
+    let plain_reference = create_push_symbol_node(&mut graph, main, "x", true);
+    graph.add_edge(plain_reference, root, 0);
+    files.push(main);
+
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in files {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .unwrap();
+    }
+    for (reference, expected) in [
+        (domain_reference, definitions[0]),
+        (plain_reference, definitions[2]),
+    ] {
+        let mut from_graph = Vec::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+            vec![reference],
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, p| from_graph.push(p.end_node),
+        )
+        .unwrap();
+        assert_eq!(vec![expected], from_graph);
+
+        let mut from_database = Vec::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+            vec![reference],
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, p| from_database.push(p.end_node),
+        )
+        .unwrap();
+        assert_eq!(vec![expected], from_database);
+    }
+}
+
+#[test]
+fn symbol_ids_are_stable() {
+    let mut graph = StackGraph::new();
+    let foo = graph.add_symbol("foo");
+    let type_foo = graph.add_type_symbol("foo");
+    assert_eq!("dcb27518fed9d577", graph.symbol_id(foo).to_string());
+    assert_ne!(graph.symbol_id(foo), graph.symbol_id(type_foo));
+    assert_eq!(
+        SymbolId::new("foo", Some("type")),
+        graph.symbol_id(type_foo)
+    );
+
+    // identifiers do not depend on the order in which symbols are added
+    let mut other = StackGraph::new();
+    other.add_symbol("bar");
+    let type_foo_in_other = other.add_type_symbol("foo");
+    assert_eq!(
+        graph.symbol_id(type_foo),
+        other.symbol_id(type_foo_in_other)
+    );
+
+    let id = graph.symbol_id(type_foo);
+    assert_eq!(Ok(id), id.to_string().parse::<SymbolId>());
+    assert!("not-hex".parse::<SymbolId>().is_err());
+}
+
+#[test]
+fn can_add_root_domain() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.js");
+    let domain = graph.add_root_domain(file, "npm");
+    let symbol = graph[domain.entry].symbol().unwrap();
+    assert_eq!(Some(symbol), graph[domain.exit].symbol());
+    assert_eq!(Some("npm"), graph.root_domain(symbol));
+    assert_eq!(symbol, graph.root_domain_symbol("npm"));
+    let plain = graph.add_symbol("npm");
+    assert_ne!(symbol, plain);
+    assert_eq!(None, graph.root_domain(plain));
+    assert_eq!(
+        vec![StackGraph::root_node()],
+        graph
+            .outgoing_edges(domain.entry)
+            .map(|e| e.sink)
+            .collect::<Vec<_>>()
+    );
+    assert!(graph
+        .outgoing_edges(StackGraph::root_node())
+        .any(|e| e.sink == domain.exit));
+}
+
+#[test]
+fn instantiation_arguments_must_belong_to_file() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let other_file = graph.get_or_create_file("other.py");
+    let list = graph.add_symbol("List");
+    let other_node = create_push_symbol_node(&mut graph, other_file, "Foo", false);
+    let node_count = graph.iter_nodes().count();
+    assert_eq!(
+        Err(PushScopedSymbolError::NodeInDifferentFile(other_node)),
+        graph
+            .instantiate(file, list)
+            .with_argument(other_node)
+            .build(StackGraph::root_node())
+    );
+    assert_eq!(node_count, graph.iter_nodes().count());
+}
+
+#[test]
+fn can_compute_reachable_nodes() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let a = create_scope_node(&mut graph, file, false);
+    let b = create_scope_node(&mut graph, file, false);
+    let c = create_scope_node(&mut graph, file, false);
+    let d = create_scope_node(&mut graph, file, false);
+    let e = create_scope_node(&mut graph, file, false);
+    graph.add_edge(a, b, 0);
+    graph.add_edge(b, c, 1);
+    graph.add_edge(c, a, 0);
+    graph.add_edge(d, c, 0);
+
+    let all_edges = |_: &StackGraph, _: &Edge| true;
+    let results = |set: HandleSet<Node>| set.iter().collect::<HashSet<_>>();
+
+    assert_eq!(
+        hashset! { a, b, c },
+        results(graph.reachable_from([a], EdgeDirection::Forward, all_edges))
+    );
+    assert_eq!(
+        hashset! { a, b, c, d },
+        results(graph.reachable_from([c], EdgeDirection::Backward, all_edges))
+    );
+    assert_eq!(
+        hashset! { d, e },
+        results(graph.reachable_from([d, e], EdgeDirection::Backward, all_edges))
+    );
+    assert_eq!(
+        hashset! { a, b },
+        results(
+            graph.reachable_from([a], EdgeDirection::Forward, |_, edge| {
+                edge.precedence == 0
+            })
+        )
+    );
+}
+
+#[test]
+fn definition_and_reference_flags_can_be_changed() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let reference = create_push_symbol_node(&mut graph, file, "x", false);
+    let plumbing = create_pop_symbol_node(&mut graph, file, "x", true);
+    let definition = create_pop_symbol_node(&mut graph, file, "x", true);
+    let scope = create_scope_node(&mut graph, file, false);
+    graph.add_edge(reference, plumbing, 0);
+    graph.add_edge(reference, definition, 0);
+
+    assert!(graph[reference].set_is_reference(true));
+    assert!(graph[plumbing].set_is_definition(false));
+    assert!(!graph[scope].set_is_definition(true));
+    assert!(!graph[scope].set_is_reference(true));
+    assert!(!graph[reference].set_is_definition(true));
+    assert!(graph[reference].is_reference());
+    assert!(!graph[plumbing].is_definition());
+    assert!(!graph[plumbing].is_endpoint());
+    assert!(!graph[scope].is_definition());
+
+    let mut partials = PartialPaths::new();
+    let mut complete_paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, p| complete_paths.push(p.end_node),
+    )
+    .unwrap();
+    assert_eq!(vec![definition], complete_paths);
+}
+
+#[test]
+fn nodes_without_incoming_edges_have_degree_zero() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let a = create_scope_node(&mut graph, file, false);
+    let b = create_scope_node(&mut graph, file, false);
+    graph.add_edge(a, b, 0);
+    let c = create_scope_node(&mut graph, file, true);
+    assert_eq!(Degree::Zero, graph.incoming_edge_degree(a));
+    assert_eq!(Degree::One, graph.incoming_edge_degree(b));
+    assert_eq!(Degree::Zero, graph.incoming_edge_degree(c));
+}
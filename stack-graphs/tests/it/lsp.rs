@@ -0,0 +1,58 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use lsp_positions::SpanCalculator;
+use lsp_types::SymbolKind;
+use lsp_types::Url;
+use stack_graphs::graph::StackGraph;
+
+use crate::util::create_pop_symbol_node;
+
+#[test]
+fn can_convert_definition_to_symbol_information() {
+    let source = "# ✨\ndef foo(): pass\n";
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("/src/test.py").unwrap();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let function = graph.add_string("function");
+    let fqn = graph.add_string("test.foo");
+
+    let mut calculator = SpanCalculator::new(source);
+    let start = source.find("foo").unwrap();
+    let source_info = graph.source_info_mut(foo);
+    source_info.span.start = calculator.for_utf8_offset_in_string(start);
+    source_info.span.end = calculator.for_utf8_offset_in_string(start + 3);
+    source_info.syntax_type = function.into();
+    source_info.fully_qualified_name = fqn.into();
+
+    let location = graph.lsp_location(foo).unwrap();
+    assert_eq!(Url::parse("file:///src/test.py").unwrap(), location.uri);
+    assert_eq!(
+        (1, 4, 1, 7),
+        (
+            location.range.start.line,
+            location.range.start.character,
+            location.range.end.line,
+            location.range.end.character
+        )
+    );
+
+    let symbol = graph.lsp_symbol_information(foo).unwrap();
+    assert_eq!("foo", symbol.name);
+    assert_eq!(SymbolKind::FUNCTION, symbol.kind);
+    assert_eq!(Some("test.foo".to_string()), symbol.container_name);
+    assert_eq!(location, symbol.location);
+}
+
+#[test]
+fn node_without_absolute_file_path_has_no_location() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test.py").unwrap();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    graph.source_info_mut(foo);
+    assert_eq!(None, graph.lsp_location(foo));
+}
@@ -10,6 +10,10 @@
 
 pub mod test_graphs;
 
+#[cfg(feature = "analysis")]
+mod analysis;
+#[cfg(feature = "archive")]
+mod archive;
 mod arena;
 mod c;
 mod can_create_graph;
@@ -19,13 +23,39 @@ mod can_find_partial_paths_in_file;
 mod can_find_root_partial_paths_in_database;
 mod can_jump_to_definition;
 mod can_jump_to_definition_with_forward_partial_path_stitching;
+#[cfg(feature = "checked")]
+mod checked;
+mod compressed;
 mod cycles;
+mod errors;
+#[cfg(feature = "events")]
+mod events;
+#[cfg(feature = "export")]
+mod export;
+#[cfg(feature = "fuzzing")]
+mod fuzzing;
 mod graph;
+#[cfg(feature = "lsp-types")]
+mod lsp;
 mod partial;
+#[cfg(feature = "proto")]
+mod proto;
+#[cfg(feature = "query")]
+mod query;
 #[cfg(feature = "serde")]
 mod serde;
+#[cfg(feature = "storage")]
+mod sgdb;
+#[cfg(feature = "snapshot")]
+mod snapshot;
+#[cfg(feature = "source")]
+mod source;
 mod stats;
 mod stitching;
 #[cfg(feature = "storage")]
 mod storage;
+#[cfg(feature = "trace")]
+mod trace;
 mod util;
+#[cfg(feature = "visualization")]
+mod visualization;
@@ -5,18 +5,24 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use stack_graphs::arena::ArenaStats;
 use stack_graphs::arena::Handle;
+use stack_graphs::graph::EdgeDirection;
 use stack_graphs::graph::Node;
 use stack_graphs::graph::NodeID;
 use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::rank_by_confidence;
+use stack_graphs::partial::remove_shadowed_paths;
 use stack_graphs::partial::PartialPath;
 use stack_graphs::partial::PartialPathEdgeList;
 use stack_graphs::partial::PartialPaths;
 use stack_graphs::partial::PartialScopeStack;
 use stack_graphs::partial::PartialScopeStackBindings;
 use stack_graphs::partial::PartialSymbolStackBindings;
+use stack_graphs::partial::PathQuality;
 use stack_graphs::partial::ScopeStackVariable;
 use stack_graphs::partial::SymbolStackVariable;
+use stack_graphs::partial::TieBreaker;
 use stack_graphs::paths::PathResolutionError;
 use stack_graphs::stitching::Database;
 
@@ -324,6 +330,103 @@ fn can_unify_partial_symbol_stacks() -> Result<(), PathResolutionError> {
         "{$1 => ()}",
     )?;
 
+    // Attached scope lists with a known prefix and a variable tail
+    let scope_var2 = Some(ScopeStackVariable::new(2).unwrap());
+    let scopes_10_var1: NiceScopeStack = (&[10], scope_var1);
+    let scopes_10_11: NiceScopeStack = (&[10, 11], None);
+    let scopes_11_var2: NiceScopeStack = (&[11], scope_var2);
+    let scopes_var2: NiceScopeStack = (&[], scope_var2);
+    let a_10_var1 = ("a", Some(scopes_10_var1));
+    let a_10_11 = ("a", Some(scopes_10_11));
+    let a_11_var2 = ("a", Some(scopes_11_var2));
+    let a_var2 = ("a", Some(scopes_var2));
+
+    verify(
+        (&[a_10_var1], None),
+        (&[a_10_11], None),
+        "a/([file(10)],[file(11)])",
+        "{}",
+        "{$1 => ([file(11)])}",
+    )?;
+    verify(
+        (&[a_10_var1], None),
+        (&[a_var2], None),
+        "a/([file(10)],$1)",
+        "{}",
+        "{$2 => ([file(10)],$1)}",
+    )?;
+    verify(
+        (&[a_10_var1], var1),
+        (&[a_10_var1], None),
+        "a/([file(10)],$1),%1",
+        "{%1 => <>}",
+        "{$1 => ($1)}",
+    )?;
+    verify_not((&[a_10_var1], None), (&[a_11_var2], None))?;
+    verify_not((&[a_10_var1], None), (&[a_empty], None))?;
+
+    Ok(())
+}
+
+#[test]
+fn can_apply_chained_partial_scope_stack_bindings() -> Result<(), PathResolutionError> {
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+
+    let var1 = ScopeStackVariable::new(1).unwrap();
+    let var2 = ScopeStackVariable::new(2).unwrap();
+    let var3 = ScopeStackVariable::new(3).unwrap();
+
+    // $1 => [10],$2 and $2 => [11],$3, so that $1 stands for [10],[11],$3
+    let mut scope_bindings = PartialScopeStackBindings::new();
+    let bound1 = create_scope_stack(&mut graph, &mut partials, (&[10], Some(var2)));
+    scope_bindings.add(&mut partials, var1, bound1)?;
+    let bound2 = create_scope_stack(&mut graph, &mut partials, (&[11], Some(var3)));
+    scope_bindings.add(&mut partials, var2, bound2)?;
+
+    let scopes = create_scope_stack(&mut graph, &mut partials, (&[12], Some(var1)));
+    let scopes = scopes.apply_partial_bindings(&mut partials, &scope_bindings)?;
+    assert_eq!(
+        "[file(12)],[file(10)],[file(11)],$3",
+        scopes.display(&graph, &mut partials).to_string()
+    );
+
+    // Attached scope lists inside bound symbol stacks are resolved as well
+    let mut symbol_bindings = PartialSymbolStackBindings::new();
+    let bound = create_symbol_stack(
+        &mut graph,
+        &mut partials,
+        (&[("a", Some((&[12], Some(var1))))], None),
+    );
+    symbol_bindings.add(
+        &mut partials,
+        SymbolStackVariable::new(1).unwrap(),
+        bound,
+        &mut scope_bindings,
+    )?;
+    let symbols = create_symbol_stack(
+        &mut graph,
+        &mut partials,
+        (&[], Some(SymbolStackVariable::new(1).unwrap())),
+    );
+    let symbols =
+        symbols.apply_partial_bindings(&mut partials, &symbol_bindings, &scope_bindings)?;
+    assert_eq!(
+        "a/([file(12)],[file(10)],[file(11)],$3)",
+        symbols.display(&graph, &mut partials).to_string()
+    );
+
+    // Cyclic bindings cannot be satisfied by any finite scope stack
+    let mut scope_bindings = PartialScopeStackBindings::new();
+    let bound1 = create_scope_stack(&mut graph, &mut partials, (&[10], Some(var2)));
+    scope_bindings.add(&mut partials, var1, bound1)?;
+    let bound2 = create_scope_stack(&mut graph, &mut partials, (&[11], Some(var1)));
+    scope_bindings.add(&mut partials, var2, bound2)?;
+    let scopes = PartialScopeStack::from_variable(var1);
+    assert!(scopes
+        .apply_partial_bindings(&mut partials, &scope_bindings)
+        .is_err());
+
     Ok(())
 }
 
@@ -651,6 +754,88 @@ fn can_append_edges() -> Result<(), PathResolutionError> {
     Ok(())
 }
 
+#[test]
+fn can_append_edges_backward() -> Result<(), PathResolutionError> {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").expect("");
+    let scope0 = create_scope_node(&mut graph, file, false);
+    let scope1 = create_scope_node(&mut graph, file, false);
+    let foo_ref = create_push_symbol_node(&mut graph, file, "foo", false);
+    let foo_def = create_pop_symbol_node(&mut graph, file, "foo", false);
+    let bar_ref = create_push_symbol_node(&mut graph, file, "bar", false);
+    let bar_def = create_pop_symbol_node(&mut graph, file, "bar", false);
+    let exported_scope = create_scope_node(&mut graph, file, true);
+    let exported_scope_id = graph[exported_scope].id();
+    let baz_ref = create_push_scoped_symbol_node(&mut graph, file, "baz", exported_scope_id, false);
+    let baz_def = create_pop_scoped_symbol_node(&mut graph, file, "baz", false);
+    let drop_scopes = create_drop_scopes_node(&mut graph, file);
+
+    // Builds the path by prepending its edges, starting from its last node.
+    fn run_backward(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        nodes: NicePartialPath,
+    ) -> Result<PartialPath, PathResolutionError> {
+        let mut path = PartialPath::from_node(graph, partials, *nodes.last().unwrap());
+        for (source, sink) in nodes.iter().rev().skip(1).zip(nodes.iter().rev()) {
+            path.append_in_direction(
+                graph,
+                partials,
+                edge(*source, *sink, 0),
+                EdgeDirection::Backward,
+            )?;
+        }
+        Ok(path)
+    }
+
+    fn verify(graph: &mut StackGraph, nodes: NicePartialPath, expected: &str) {
+        let mut partials = PartialPaths::new();
+        let forward = create_partial_path_and_edges(graph, &mut partials, nodes).expect("");
+        let backward = run_backward(graph, &mut partials, nodes).expect("");
+        assert_eq!(expected, forward.display(graph, &mut partials).to_string());
+        assert_eq!(expected, backward.display(graph, &mut partials).to_string());
+    }
+
+    fn verify_not(graph: &mut StackGraph, nodes: NicePartialPath) {
+        let mut partials = PartialPaths::new();
+        assert!(create_partial_path_and_edges(graph, &mut partials, nodes).is_err());
+        assert!(run_backward(graph, &mut partials, nodes).is_err());
+    }
+
+    verify(
+        &mut graph,
+        &[foo_ref, scope0, foo_def],
+        "<%1> ($1) [test(2) push foo] -> [test(3) pop foo] <%1> ($1)",
+    );
+    verify(
+        &mut graph,
+        &[foo_def, scope0, bar_ref],
+        "<foo,%1> ($1) [test(3) pop foo] -> [test(4) push bar] <bar,%1> ($1)",
+    );
+    verify(
+        &mut graph,
+        &[foo_def, scope0, baz_ref, scope1, baz_def],
+        "<foo,%1> ($1) [test(3) pop foo] -> [test(8) pop scoped baz] <%1> ([test(6)],$1)",
+    );
+    verify(
+        &mut graph,
+        &[foo_ref, scope0, drop_scopes, scope1, foo_def],
+        "<%1> ($1) [test(2) push foo] -> [test(3) pop foo] <%1> ()",
+    );
+    verify_not(&mut graph, &[foo_ref, scope0, bar_def]);
+    verify_not(&mut graph, &[baz_ref, scope0, bar_ref, scope1, foo_def]);
+
+    // The edge must end at the start of the path
+    let mut partials = PartialPaths::new();
+    let mut path = PartialPath::from_node(&graph, &mut partials, foo_def);
+    assert!(matches!(
+        path.append_backward(&graph, &mut partials, edge(foo_ref, scope0, 0)),
+        Err(PathResolutionError::IncorrectSourceNode)
+    ));
+
+    Ok(())
+}
+
 #[test]
 fn can_append_edges_without_precondition_variables() -> Result<(), PathResolutionError> {
     let mut graph = StackGraph::new();
@@ -964,3 +1149,333 @@ fn can_resolve_to_node() -> Result<(), PathResolutionError> {
 
     Ok(())
 }
+
+fn append_edges(
+    graph: &mut StackGraph,
+    partials: &mut PartialPaths,
+    edges: &[(Handle<Node>, Handle<Node>, i32)],
+) -> PartialPath {
+    let mut path = PartialPath::from_node(graph, partials, edges[0].0);
+    for (source, sink, precedence) in edges {
+        graph.add_edge(*source, *sink, *precedence);
+        path.append(graph, partials, edge(*source, *sink, *precedence))
+            .expect("edge should be appendable");
+    }
+    path
+}
+
+#[test]
+fn can_compute_path_quality() {
+    let mut graph = StackGraph::new();
+    let a = graph.get_or_create_file("a.py");
+    let b = graph.get_or_create_file("b.py");
+    let builtins = graph.get_or_create_file("<builtins>");
+    let mut partials = PartialPaths::new();
+
+    let root = StackGraph::root_node();
+    let ref_x = create_push_symbol_node(&mut graph, a, "x", true);
+    let local_def_x = create_pop_symbol_node(&mut graph, a, "x", true);
+    let builtins_scope = create_scope_node(&mut graph, builtins, false);
+    let def_x = create_pop_symbol_node(&mut graph, b, "x", true);
+    let is_builtins = |file| file == builtins;
+
+    let local = append_edges(&mut graph, &mut partials, &[(ref_x, local_def_x, -1)]);
+    let quality = local.quality(&graph, &mut partials, is_builtins);
+    assert_eq!(1, quality.length);
+    assert_eq!(0, quality.file_crossings);
+    assert!(!quality.via_builtins);
+    assert_eq!(-1, quality.max_precedence);
+
+    let cross_file = append_edges(
+        &mut graph,
+        &mut partials,
+        &[
+            (ref_x, root, 0),
+            (root, builtins_scope, 2),
+            (builtins_scope, def_x, 1),
+        ],
+    );
+    let quality = cross_file.quality(&graph, &mut partials, is_builtins);
+    assert_eq!(3, quality.length);
+    assert_eq!(2, quality.file_crossings);
+    assert!(quality.via_builtins);
+    assert_eq!(2, quality.max_precedence);
+}
+
+#[test]
+fn can_rank_candidates_by_confidence() {
+    let local = PathQuality {
+        length: 2,
+        ..Default::default()
+    };
+    let cross_file = PathQuality {
+        length: 6,
+        file_crossings: 1,
+        ..Default::default()
+    };
+    let via_builtins = PathQuality {
+        length: 6,
+        file_crossings: 1,
+        via_builtins: true,
+        ..Default::default()
+    };
+    let preferred = PathQuality {
+        length: 6,
+        file_crossings: 1,
+        max_precedence: 2,
+        ..Default::default()
+    };
+
+    let ranked = rank_by_confidence(&[via_builtins, cross_file, local]);
+    assert_eq!(
+        vec![2, 1, 0],
+        ranked.iter().map(|r| r.index).collect::<Vec<_>>()
+    );
+    let total: f64 = ranked.iter().map(|r| r.confidence).sum();
+    assert!((total - 1.0).abs() < 1e-9);
+
+    let ranked = rank_by_confidence(&[local, preferred]);
+    assert_eq!(1, ranked[0].index);
+
+    let ranked = rank_by_confidence(&[cross_file]);
+    assert_eq!(1.0, ranked[0].confidence);
+}
+
+struct PreferDefinition(Handle<Node>);
+
+impl TieBreaker for PreferDefinition {
+    fn break_tie(
+        &self,
+        _graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        left: &PartialPath,
+        right: &PartialPath,
+    ) -> std::cmp::Ordering {
+        if left.end_node == self.0 {
+            std::cmp::Ordering::Less
+        } else if right.end_node == self.0 {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    }
+}
+
+#[test]
+fn can_remove_shadowed_paths() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let mut partials = PartialPaths::new();
+
+    let ref_x = create_push_symbol_node(&mut graph, file, "x", true);
+    let scope = create_scope_node(&mut graph, file, false);
+    let def_a = create_pop_symbol_node(&mut graph, file, "x", true);
+    let def_b = create_pop_symbol_node(&mut graph, file, "x", true);
+    let def_c = create_pop_symbol_node(&mut graph, file, "x", true);
+    let a = append_edges(
+        &mut graph,
+        &mut partials,
+        &[(ref_x, scope, 0), (scope, def_a, 1)],
+    );
+    let b = append_edges(
+        &mut graph,
+        &mut partials,
+        &[(ref_x, scope, 0), (scope, def_b, 0)],
+    );
+    let c = append_edges(
+        &mut graph,
+        &mut partials,
+        &[(ref_x, scope, 0), (scope, def_c, 0)],
+    );
+    let end_nodes = |paths: &[PartialPath]| paths.iter().map(|p| p.end_node).collect::<Vec<_>>();
+
+    let mut paths = vec![b.clone(), a, c.clone()];
+    remove_shadowed_paths(&graph, &mut partials, &mut paths, None);
+    assert_eq!(vec![def_a], end_nodes(&paths));
+
+    let mut paths = vec![b.clone(), c.clone()];
+    remove_shadowed_paths(&graph, &mut partials, &mut paths, None);
+    assert_eq!(vec![def_b, def_c], end_nodes(&paths));
+
+    let mut paths = vec![b, c];
+    let tie_breaker = PreferDefinition(def_c);
+    remove_shadowed_paths(&graph, &mut partials, &mut paths, Some(&tie_breaker));
+    assert_eq!(vec![def_c], end_nodes(&paths));
+}
+
+#[test]
+fn can_track_partial_paths_arena_stats() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let mut partials = PartialPaths::new();
+    assert_eq!(ArenaStats::default(), partials.stats().total());
+
+    let ref_x = create_push_symbol_node(&mut graph, file, "x", true);
+    let scope = create_scope_node(&mut graph, file, false);
+    let def_x = create_pop_symbol_node(&mut graph, file, "x", true);
+    append_edges(
+        &mut graph,
+        &mut partials,
+        &[(ref_x, scope, 0), (scope, def_x, 0)],
+    );
+
+    let stats = partials.stats();
+    assert!(stats.edge_cells.allocated > 0);
+    assert!(stats.symbol_stack_cells.allocated > 0);
+    assert_eq!(stats.edge_cells.live, stats.edge_cells.high_water_mark);
+    let total = stats.total();
+    assert_eq!(
+        stats.symbol_stack_cells.allocated
+            + stats.scope_stack_cells.allocated
+            + stats.edge_cells.allocated,
+        total.allocated
+    );
+}
+
+#[test]
+fn can_fingerprint_partial_stacks() {
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    let var1 = Some(SymbolStackVariable::new(1).unwrap());
+    let empty_scopes: NiceScopeStack = (&[], None);
+    let a = ("a", None);
+    let b = ("b", None);
+    let a_empty = ("a", Some(empty_scopes));
+
+    let mut ab = create_symbol_stack(&mut graph, &mut partials, (&[a, b], None));
+    let ab_fingerprint = ab.fingerprint(&mut partials);
+    // Build the same stack in the other direction.
+    let mut ab_forwards = create_symbol_stack(&mut graph, &mut partials, (&[], None));
+    for symbol in ab.iter(&mut partials).collect::<Vec<_>>().into_iter().rev() {
+        ab_forwards.push_front(&mut partials, symbol);
+    }
+    assert_eq!(ab_fingerprint, ab_forwards.fingerprint(&mut partials));
+    assert!(ab.equals(&mut partials, ab_forwards));
+
+    for other in [
+        create_symbol_stack(&mut graph, &mut partials, (&[b, a], None)),
+        create_symbol_stack(&mut graph, &mut partials, (&[a, b], var1)),
+        create_symbol_stack(&mut graph, &mut partials, (&[a_empty, b], None)),
+        create_symbol_stack(&mut graph, &mut partials, (&[a], None)),
+    ] {
+        assert_ne!(ab_fingerprint, other.fingerprint(&mut partials));
+        assert!(!ab.equals(&mut partials, other));
+    }
+
+    // Popping an element yields the fingerprint of the remaining stack.
+    ab.pop_front(&mut partials);
+    let b_only = create_symbol_stack(&mut graph, &mut partials, (&[b], None));
+    assert_eq!(
+        b_only.fingerprint(&mut partials),
+        ab.fingerprint(&mut partials)
+    );
+
+    let scopes_12 = create_scope_stack(&mut graph, &mut partials, (&[1, 2], None));
+    let scopes_12_again = create_scope_stack(&mut graph, &mut partials, (&[1, 2], None));
+    let scopes_21 = create_scope_stack(&mut graph, &mut partials, (&[2, 1], None));
+    assert_eq!(
+        scopes_12.fingerprint(&mut partials),
+        scopes_12_again.fingerprint(&mut partials)
+    );
+    assert_ne!(
+        scopes_12.fingerprint(&mut partials),
+        scopes_21.fingerprint(&mut partials)
+    );
+}
+
+#[test]
+fn can_unify_fully_known_partial_symbol_stacks() {
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    let scopes_var1: NiceScopeStack = (&[], Some(ScopeStackVariable::new(1).unwrap()));
+    let scopes_1: NiceScopeStack = (&[1], None);
+    let a = ("a", None);
+    let b = ("b", None);
+
+    let mut unify = |lhs: NiceSymbolStack, rhs: NiceSymbolStack, reverse_rhs: bool| {
+        let lhs = create_symbol_stack(&mut graph, &mut partials, lhs);
+        let mut rhs = create_symbol_stack(&mut graph, &mut partials, rhs);
+        if reverse_rhs {
+            // Rebuild the stack by pushing onto the front, so that it is stored in the other
+            // direction.
+            let symbols = rhs.iter(&mut partials).collect::<Vec<_>>();
+            rhs = create_symbol_stack(&mut graph, &mut partials, (&[], None));
+            for symbol in symbols.into_iter().rev() {
+                rhs.push_front(&mut partials, symbol);
+            }
+        }
+        let mut symbol_bindings = PartialSymbolStackBindings::new();
+        let mut scope_bindings = PartialScopeStackBindings::new();
+        lhs.unify(
+            &mut partials,
+            rhs,
+            &mut symbol_bindings,
+            &mut scope_bindings,
+        )
+        .map(|unified| unified.display(&graph, &mut partials).to_string())
+        .map_err(|_| ())
+    };
+
+    for reverse_rhs in [false, true] {
+        assert_eq!(
+            Ok("ab".to_string()),
+            unify((&[a, b], None), (&[a, b], None), reverse_rhs)
+        );
+        assert_eq!(
+            Err(()),
+            unify((&[a, b], None), (&[b, a], None), reverse_rhs)
+        );
+        assert_eq!(Err(()), unify((&[a, b], None), (&[a], None), reverse_rhs));
+        assert_eq!(
+            Err(()),
+            unify(
+                (&[a, b], None),
+                (&[("a", Some(scopes_1)), b], None),
+                reverse_rhs
+            )
+        );
+        // Attached scopes can contain variables, which need to be unified.
+        assert_eq!(
+            Ok("a/([file(1)])b".to_string()),
+            unify(
+                (&[("a", Some(scopes_var1)), b], None),
+                (&[("a", Some(scopes_1)), b], None),
+                reverse_rhs
+            )
+        );
+    }
+}
+
+#[test]
+fn can_extend_partial_paths_across_files() {
+    let mut graph = StackGraph::new();
+    let a = graph.add_file("a").unwrap();
+    let b = graph.add_file("b").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let ref_x = create_push_symbol_node(&mut graph, a, "x", true);
+    let def_x_a = create_pop_symbol_node(&mut graph, a, "x", true);
+    let def_y_a = create_pop_symbol_node(&mut graph, a, "y", true);
+    let def_x_b = create_pop_symbol_node(&mut graph, b, "x", true);
+    graph.add_edge(ref_x, def_x_a, 0);
+    graph.add_edge(ref_x, def_y_a, 0);
+    graph.add_edge(ref_x, def_x_b, 0);
+    let path = PartialPath::from_node(&graph, &mut partials, ref_x);
+
+    // The edge to `y` is not a valid extension, and is skipped.
+    let mut extensions = Vec::new();
+    path.extend(&graph, &mut partials, &mut extensions);
+    let mut end_nodes = extensions.iter().map(|p| p.end_node).collect::<Vec<_>>();
+    end_nodes.sort();
+    assert_eq!(vec![def_x_a, def_x_b], end_nodes);
+
+    let mut extensions = Vec::new();
+    path.extend_with_filter(
+        &graph,
+        &mut partials,
+        |graph, edge| graph[edge.sink].is_in_file(a),
+        &mut extensions,
+    );
+    let end_nodes = extensions.iter().map(|p| p.end_node).collect::<Vec<_>>();
+    assert_eq!(vec![def_x_a], end_nodes);
+}
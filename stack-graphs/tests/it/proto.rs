@@ -0,0 +1,112 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::proto;
+use stack_graphs::proto::Message;
+use stack_graphs::stitching::Database;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
+
+use crate::test_graphs;
+
+#[test]
+fn can_round_trip_graph() {
+    let graph: StackGraph = test_graphs::simple::new();
+    let bytes = proto::StackGraph::from_graph(&graph).encode_to_vec();
+
+    let mut loaded = StackGraph::new();
+    proto::StackGraph::decode(bytes.as_slice())
+        .expect("Cannot decode graph")
+        .load_into(&mut loaded)
+        .expect("Cannot load graph");
+    assert_eq!(graph.to_serializable(), loaded.to_serializable());
+}
+
+#[test]
+fn can_round_trip_graph_metadata() {
+    let mut graph: StackGraph = test_graphs::simple::new();
+    graph.set_metadata("indexer_version", "1.2.3");
+    let bytes = proto::StackGraph::from_graph(&graph).encode_to_vec();
+
+    let mut loaded = StackGraph::new();
+    proto::StackGraph::decode(bytes.as_slice())
+        .expect("Cannot decode graph")
+        .load_into(&mut loaded)
+        .expect("Cannot load graph");
+    assert_eq!(Some("1.2.3"), loaded.metadata("indexer_version"));
+}
+
+#[test]
+fn can_round_trip_symbol_namespaces() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let type_x = graph.add_namespaced_symbol("x", "type");
+    let value_x = graph.add_namespaced_symbol("x", "value");
+    let ref_id = graph.new_node_id(file);
+    let reference = graph.add_push_symbol_node(ref_id, type_x, true).unwrap();
+    let def_id = graph.new_node_id(file);
+    let definition = graph.add_pop_symbol_node(def_id, value_x, true).unwrap();
+    graph.add_edge(reference, definition, 0);
+    let bytes = proto::StackGraph::from_graph(&graph).encode_to_vec();
+
+    let mut loaded = StackGraph::new();
+    proto::StackGraph::decode(bytes.as_slice())
+        .expect("Cannot decode graph")
+        .load_into(&mut loaded)
+        .expect("Cannot load graph");
+    assert_eq!(graph.to_serializable(), loaded.to_serializable());
+}
+
+#[test]
+fn can_round_trip_partial_paths() {
+    let graph: StackGraph = test_graphs::simple::new();
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |g, ps, p| {
+                db.add_partial_path(g, ps, p.clone());
+            },
+        )
+        .expect("Expect path finding to work");
+    }
+    let bytes = proto::Database::from_database(&graph, &mut partials, &db).encode_to_vec();
+
+    let mut loaded_graph = StackGraph::new();
+    proto::StackGraph::from_graph(&graph)
+        .load_into(&mut loaded_graph)
+        .expect("Cannot load graph");
+    let mut loaded_partials = PartialPaths::new();
+    let mut loaded_db = Database::new();
+    proto::Database::decode(bytes.as_slice())
+        .expect("Cannot decode paths")
+        .load_into(&mut loaded_graph, &mut loaded_partials, &mut loaded_db)
+        .expect("Cannot load paths");
+    assert_eq!(
+        db.to_serializable(&graph, &mut partials),
+        loaded_db.to_serializable(&loaded_graph, &mut loaded_partials),
+    );
+}
+
+#[test]
+fn missing_fields_are_reported() {
+    let mut message = proto::StackGraph::from_graph(&test_graphs::simple::new());
+    message.edges[0].sink = None;
+    let mut graph = StackGraph::new();
+    assert_eq!(
+        Err(proto::Error::MissingField("sink")),
+        message.load_into(&mut graph)
+    );
+}
@@ -0,0 +1,213 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use lsp_positions::SpanCalculator;
+use stack_graphs::graph::Degree;
+use stack_graphs::graph::NodeKind;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::query::expression_types;
+use stack_graphs::query::hover;
+use stack_graphs::query::reference_at;
+use stack_graphs::query::HoverDefinition;
+use stack_graphs::query::PositionMatch;
+use stack_graphs::query::DOCS_DEBUG_KEY;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
+
+use crate::util::*;
+
+#[test]
+fn can_query_nodes_by_structure() {
+    let mut graph = StackGraph::new();
+    let a = graph.get_or_create_file("a.py");
+    let b = graph.get_or_create_file("b.py");
+    let root = StackGraph::root_node();
+
+    let ref_x = create_push_symbol_node(&mut graph, a, "x", true);
+    let scope = create_scope_node(&mut graph, a, false);
+    let def_x = create_pop_symbol_node(&mut graph, a, "x", true);
+    let def_y = create_pop_symbol_node(&mut graph, a, "y", true);
+    let unreachable_def_x = create_pop_symbol_node(&mut graph, a, "x", true);
+    let other_def_x = create_pop_symbol_node(&mut graph, b, "x", true);
+    graph.add_edge(ref_x, scope, 0);
+    graph.add_edge(scope, def_x, 0);
+    graph.add_edge(scope, def_y, 0);
+    graph.add_edge(scope, root, 0);
+    graph.add_edge(root, other_def_x, 0);
+
+    let results = |query: stack_graphs::query::NodeQuery| query.iter().collect::<Vec<_>>();
+
+    assert_eq!(
+        vec![def_x, unreachable_def_x, other_def_x],
+        results(graph.query().nodes(NodeKind::PopSymbol).with_symbol("x"))
+    );
+    assert_eq!(
+        vec![def_x, unreachable_def_x],
+        results(graph.query().definitions().with_symbol("x").in_file(a))
+    );
+    assert_eq!(
+        vec![def_x, other_def_x],
+        results(
+            graph
+                .query()
+                .definitions()
+                .with_symbol("x")
+                .reachable_from(ref_x)
+        )
+    );
+    assert_eq!(
+        vec![root, ref_x, scope],
+        results(
+            graph
+                .query()
+                .nodes(NodeKind::Root)
+                .nodes(NodeKind::Scope)
+                .nodes(NodeKind::PushSymbol)
+                .reachable_from(ref_x)
+        )
+    );
+    assert_eq!(vec![ref_x], results(graph.query().references()));
+    assert_eq!(
+        vec![unreachable_def_x],
+        results(
+            graph
+                .query()
+                .definitions()
+                .filter(|g, n| g.incoming_edge_degree(n) == Degree::Zero)
+        )
+    );
+}
+
+#[test]
+fn can_find_reference_at_offset() {
+    let source = "x = foo.bar(y)\n";
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("a.py");
+    let mut calculator = SpanCalculator::new(source);
+    let mut reference = |graph: &mut StackGraph, text: &str| {
+        let node = create_push_symbol_node(graph, file, text, true);
+        let start = source.find(text).unwrap();
+        let source_info = graph.source_info_mut(node);
+        source_info.span.start = calculator.for_utf8_offset_in_string(start);
+        source_info.span.end = calculator.for_utf8_offset_in_string(start + text.len());
+        node
+    };
+    let call = reference(&mut graph, "foo.bar(y)");
+    let foo = reference(&mut graph, "foo");
+    let y = reference(&mut graph, "y");
+    create_push_symbol_node(&mut graph, file, "z", true);
+
+    let at = |offset, mode| reference_at(&graph, "a.py", offset, mode);
+    assert_eq!(Some(foo), at(5, PositionMatch::Covering));
+    assert_eq!(Some(call), at(9, PositionMatch::Covering));
+    assert_eq!(Some(y), at(12, PositionMatch::Covering));
+    assert_eq!(None, at(0, PositionMatch::Covering));
+    assert_eq!(Some(foo), at(0, PositionMatch::Nearest));
+    assert_eq!(Some(call), at(14, PositionMatch::Nearest));
+    assert_eq!(
+        None,
+        reference_at(&graph, "b.py", 5, PositionMatch::Nearest)
+    );
+}
+
+#[test]
+fn can_hover_over_reference() {
+    let source = "def foo(): pass\nfoo()\n";
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("a.py");
+    let mut calculator = SpanCalculator::new(source);
+    let definition = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let reference = create_push_symbol_node(&mut graph, file, "foo", true);
+    for (node, start) in [(definition, 4), (reference, 16)] {
+        let span = lsp_positions::Span {
+            start: calculator.for_utf8_offset_in_string(start),
+            end: calculator.for_utf8_offset_in_string(start + 3),
+        };
+        graph.source_info_mut(node).span = span;
+    }
+    let function = graph.add_string("function");
+    let fqn = graph.add_string("a.foo");
+    let docs_key = graph.add_string(DOCS_DEBUG_KEY);
+    let docs = graph.add_string("Does nothing.");
+    graph.source_info_mut(definition).syntax_type = function.into();
+    graph.source_info_mut(definition).fully_qualified_name = fqn.into();
+    graph.node_debug_info_mut(definition).add(docs_key, docs);
+    graph.add_edge(reference, definition, 0);
+
+    let mut partials = PartialPaths::new();
+    let mut hover_at = |offset| {
+        hover(
+            &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+            "a.py",
+            offset,
+            PositionMatch::Covering,
+            StitcherConfig::default(),
+            &NoCancellation,
+        )
+        .unwrap()
+    };
+    assert_eq!(None, hover_at(0));
+    let result = hover_at(17).unwrap();
+    assert_eq!("foo", result.symbol);
+    assert_eq!(16..19, result.span.utf8_range());
+    assert_eq!(
+        vec![HoverDefinition {
+            file: "a.py".to_string(),
+            span: graph.source_info(definition).unwrap().span.clone(),
+            symbol: "foo".to_string(),
+            syntax_type: Some("function".to_string()),
+            fully_qualified_name: Some("a.foo".to_string()),
+            docs: Some("Does nothing.".to_string()),
+        }],
+        result.definitions
+    );
+}
+
+#[test]
+fn can_find_types_of_expression() {
+    let mut graph = StackGraph::new();
+    let a = graph.get_or_create_file("a.py");
+    let b = graph.get_or_create_file("b.py");
+    let root = StackGraph::root_node();
+    let int_type = graph.add_type_symbol("int");
+    let int_value = graph.add_symbol("int");
+    assert!(graph.is_type_symbol(int_type));
+    assert!(!graph.is_type_symbol(int_value));
+
+    let expression = create_scope_node(&mut graph, a, false);
+    let push_id = graph.new_node_id(a);
+    let push_type = graph
+        .add_push_symbol_node(push_id, int_type, false)
+        .unwrap();
+    let type_id = graph.new_node_id(b);
+    let type_definition = graph.add_pop_symbol_node(type_id, int_type, true).unwrap();
+    let value_id = graph.new_node_id(b);
+    let value_definition = graph
+        .add_pop_symbol_node(value_id, int_value, true)
+        .unwrap();
+    graph.add_edge(expression, push_type, 0);
+    graph.add_edge(push_type, root, 0);
+    graph.add_edge(root, type_definition, 0);
+    graph.add_edge(root, value_definition, 0);
+
+    assert_eq!(
+        vec![push_type, type_definition],
+        graph.query().type_symbols().iter().collect::<Vec<_>>()
+    );
+
+    let mut partials = PartialPaths::new();
+    let types = expression_types(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        expression,
+        StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .unwrap();
+    assert_eq!(vec![type_definition], types);
+}
@@ -69,8 +69,11 @@ fn serde_json_stack_graph() {
                 },
                 precedence: 0,
                 debug_info: Some(serde::DebugInfo { data: vec![] }),
+                root_key: None,
+                visibility: None,
             }],
         },
+        metadata: serde::Metadata::default(),
     };
 
     // formatted using: json_pp -json_opt utf8,canonical,pretty,indent_length=4
@@ -325,6 +328,213 @@ fn can_load_serialized_stack_graph() {
     assert!(sg.node_debug_info(handle).is_some());
 }
 
+#[test]
+fn can_round_trip_root_edge_keys() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let root = StackGraph::root_node();
+    let sym_a = graph.add_symbol("a");
+    let sym_b = graph.add_symbol("b");
+    let def_a_id = graph.new_node_id(file);
+    let def_a = graph.add_pop_symbol_node(def_a_id, sym_a, true).unwrap();
+    graph.add_edge(root, def_a, 0);
+    graph.set_root_edge_key(root, def_a, &[sym_a, sym_b]);
+
+    let serialized = graph.to_serializable();
+    assert_eq!(
+        Some(vec!["a".to_string(), "b".to_string()]),
+        serialized.edges.data[0].root_key
+    );
+
+    let mut loaded = StackGraph::new();
+    serialized.load_into(&mut loaded).unwrap();
+    let file = loaded.get_file("test.py").unwrap();
+    let def_a = loaded
+        .node_for_id(graph::NodeID::new_in_file(file, 0))
+        .unwrap();
+    let key = loaded
+        .root_edge_key(root, def_a)
+        .unwrap()
+        .iter()
+        .map(|symbol| loaded[*symbol].to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["a", "b"], key);
+}
+
+#[test]
+fn can_round_trip_excluded_symbols() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let scope_id = graph.new_node_id(file);
+    let scope = graph.add_scope_node(scope_id, true).unwrap();
+    let sym_a = graph.add_symbol("a");
+    let sym_b = graph.add_symbol("b");
+    graph.set_excluded_symbols(scope, &[sym_b, sym_a]);
+
+    let serialized = graph.to_serializable();
+    assert!(serialized.nodes.data.iter().any(|node| matches!(
+        node,
+        serde::Node::Scope { excluded_symbols: Some(symbols), .. } if symbols == &["a", "b"]
+    )));
+
+    let mut loaded = StackGraph::new();
+    serialized.load_into(&mut loaded).unwrap();
+    let file = loaded.get_file("test.py").unwrap();
+    let scope = loaded
+        .node_for_id(graph::NodeID::new_in_file(file, 0))
+        .unwrap();
+    let excluded = loaded
+        .excluded_symbols(scope)
+        .unwrap()
+        .iter()
+        .map(|symbol| loaded[*symbol].to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["a", "b"], excluded);
+}
+
+#[test]
+fn can_round_trip_edge_visibility() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let root = StackGraph::root_node();
+    let sym_a = graph.add_symbol("a");
+    let def_a_id = graph.new_node_id(file);
+    let def_a = graph.add_pop_symbol_node(def_a_id, sym_a, true).unwrap();
+    let ref_a_id = graph.new_node_id(file);
+    let ref_a = graph.add_push_symbol_node(ref_a_id, sym_a, true).unwrap();
+    graph.add_edge(root, def_a, 0);
+    graph.add_edge(ref_a, root, 0);
+    graph.set_edge_visibility(root, def_a, graph::Visibility::Protected);
+
+    let serialized = graph.to_serializable();
+    let visibilities = serialized
+        .edges
+        .data
+        .iter()
+        .map(|edge| edge.visibility.clone())
+        .collect::<Vec<_>>();
+    assert!(visibilities.contains(&Some("protected".to_string())));
+    assert!(visibilities.contains(&None));
+
+    let mut loaded = StackGraph::new();
+    serialized.load_into(&mut loaded).unwrap();
+    let file = loaded.get_file("test.py").unwrap();
+    let def_a = loaded
+        .node_for_id(graph::NodeID::new_in_file(file, 0))
+        .unwrap();
+    let ref_a = loaded
+        .node_for_id(graph::NodeID::new_in_file(file, 1))
+        .unwrap();
+    assert_eq!(
+        graph::Visibility::Protected,
+        loaded.edge_visibility(root, def_a)
+    );
+    assert_eq!(
+        graph::Visibility::Public,
+        loaded.edge_visibility(ref_a, root)
+    );
+}
+
+#[test]
+fn can_round_trip_graph_metadata() {
+    let mut graph = StackGraph::new();
+    graph.get_or_create_file("test.py");
+    graph.set_metadata("indexer_version", "1.2.3");
+
+    let json = serde_json::to_value(graph.to_serializable()).unwrap();
+    assert_eq!(json!({ "indexer_version": "1.2.3" }), json["metadata"]);
+
+    let serialized: serde::StackGraph = serde_json::from_value(json).unwrap();
+    let mut loaded = StackGraph::new();
+    serialized.load_into(&mut loaded).unwrap();
+    assert_eq!(Some("1.2.3"), loaded.metadata("indexer_version"));
+}
+
+#[test]
+fn non_ascii_symbols_round_trip_unchanged() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    for symbol in ["caf\u{e9}", "cafe\u{301}", "λx"] {
+        let symbol = graph.add_symbol(symbol);
+        let id = graph.new_node_id(file);
+        graph.add_pop_symbol_node(id, symbol, true).unwrap();
+    }
+
+    let json = serde_json::to_string(&graph.to_serializable()).unwrap();
+    let serialized: serde::StackGraph = serde_json::from_str(&json).unwrap();
+    let mut loaded = StackGraph::new();
+    serialized.load_into(&mut loaded).unwrap();
+    let file = loaded.get_file("test.py").unwrap();
+    let symbols = loaded
+        .nodes_for_file(file)
+        .map(|node| loaded[loaded[node].symbol().unwrap()].to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["caf\u{e9}", "cafe\u{301}", "λx"], symbols);
+
+    // Loading into a graph that normalizes symbols merges the equivalent spellings
+    let mut normalized = StackGraph::new();
+    normalized.set_symbol_interning(graph::SymbolNormalization::none().with_unicode_nfc(true));
+    serialized.load_into(&mut normalized).unwrap();
+    let file = normalized.get_file("test.py").unwrap();
+    let symbols = normalized
+        .nodes_for_file(file)
+        .map(|node| normalized[normalized[node].symbol().unwrap()].to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["caf\u{e9}", "caf\u{e9}", "λx"], symbols);
+}
+
+#[test]
+fn can_round_trip_symbol_namespaces() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let root = StackGraph::root_node();
+    let type_x = graph.add_namespaced_symbol("x", "type");
+    let def_id = graph.new_node_id(file);
+    let def = graph.add_pop_symbol_node(def_id, type_x, true).unwrap();
+    graph.add_edge(root, def, 0);
+
+    let serialized = graph.to_serializable();
+    let mut loaded = StackGraph::new();
+    serialized.load_into(&mut loaded).unwrap();
+    let file = loaded.get_file("test.py").unwrap();
+    let loaded_def = loaded
+        .node_for_id(graph::NodeID::new_in_file(file, 0))
+        .unwrap();
+    let symbol = loaded[loaded_def].symbol().unwrap();
+    assert_eq!("x", &loaded[symbol]);
+    assert_eq!(Some("type"), loaded.symbol_namespace(symbol));
+
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        &graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, p| paths.push(p.clone()),
+    )
+    .unwrap();
+    assert_eq!(1, paths.len());
+    let path = serde::PartialPath::from_partial_path(&graph, &mut partials, &paths[0]);
+    let json = serde_json::to_value(&path).unwrap();
+    assert_eq!(
+        json!("type"),
+        json["symbol_stack_precondition"]["symbols"][0]["namespace"]
+    );
+
+    let mut loaded_partials = PartialPaths::new();
+    let loaded_path = path
+        .to_partial_path(&mut loaded, &mut loaded_partials)
+        .unwrap();
+    assert_eq!(
+        paths[0].display(&graph, &mut partials).to_string(),
+        loaded_path
+            .display(&loaded, &mut loaded_partials)
+            .to_string()
+    );
+}
+
 #[test]
 fn can_serialize_graph() {
     let graph: StackGraph = test_graphs::simple::new();
@@ -1276,3 +1486,60 @@ fn can_serialize_partial_paths() {
     );
     assert_json_eq!(expected, actual);
 }
+
+#[cfg(feature = "schema")]
+#[test]
+fn schema_describes_serialized_graph() {
+    let graph: StackGraph = test_graphs::simple::new();
+    let actual = serde_json::to_value(graph.to_serializable()).expect("Cannot serialize graph");
+    let schema =
+        serde_json::to_value(serde::schema::stack_graph()).expect("Cannot serialize schema");
+
+    for property in schema["required"].as_array().unwrap() {
+        let property = property.as_str().unwrap();
+        assert!(actual.get(property).is_some(), "missing {}", property);
+    }
+
+    let variants = schema["definitions"]["Node"]["oneOf"].as_array().unwrap();
+    for node in actual["nodes"].as_array().unwrap() {
+        let variant = variants
+            .iter()
+            .find(|v| v["properties"]["type"]["enum"][0] == node["type"])
+            .unwrap_or_else(|| panic!("no schema for node type {}", node["type"]));
+        for property in variant["required"].as_array().unwrap() {
+            let property = property.as_str().unwrap();
+            assert!(node.get(property).is_some(), "missing {}", property);
+        }
+        for property in node.as_object().unwrap().keys() {
+            assert!(
+                variant["properties"].get(property).is_some(),
+                "unexpected {}",
+                property
+            );
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+#[test]
+fn schema_describes_serialized_partial_paths() {
+    let schema = serde_json::to_value(serde::schema::database()).expect("Cannot serialize schema");
+    assert_eq!("array", schema["type"]);
+    assert_eq!("#/definitions/PartialPath", schema["items"]["$ref"]);
+    let path = &schema["definitions"]["PartialPath"];
+    for property in [
+        "start_node",
+        "end_node",
+        "symbol_stack_precondition",
+        "symbol_stack_postcondition",
+        "scope_stack_precondition",
+        "scope_stack_postcondition",
+        "edges",
+    ] {
+        assert!(
+            path["properties"].get(property).is_some(),
+            "missing {}",
+            property
+        );
+    }
+}
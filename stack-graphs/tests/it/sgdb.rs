@@ -0,0 +1,236 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::File;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::NodeID;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPath;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::sgdb::SgdbReader;
+use stack_graphs::sgdb::SgdbWriter;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::storage::FileStatus;
+use stack_graphs::storage::SQLiteWriter;
+use stack_graphs::storage::StorageBackend;
+use stack_graphs::storage::StorageError;
+use stack_graphs::NoCancellation;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::util::create_partial_path_and_edges;
+use crate::util::create_pop_symbol_node;
+use crate::util::create_push_symbol_node;
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("stack-graphs-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+/// Stores the partial paths of a file graph in a database under test.
+type StoreFn<'a> = dyn FnMut(&StackGraph, Handle<File>, &mut PartialPaths, &[PartialPath]) + 'a;
+
+/// Stores a file with a single partial path through the given nodes, which are created by the
+/// callback. Returns the local ID of the first node.
+fn store_file_with_path<F>(store: &mut StoreFn, file: &str, nodes: F) -> u32
+where
+    F: FnOnce(&mut StackGraph, Handle<File>) -> Vec<Handle<Node>>,
+{
+    let mut graph = StackGraph::new();
+    let file = graph.add_file(file).unwrap();
+    let mut partials = PartialPaths::new();
+    let nodes = nodes(&mut graph, file);
+    let mut path = create_partial_path_and_edges(&mut graph, &mut partials, &nodes).unwrap();
+    if graph[nodes[0]].is_reference() {
+        path.eliminate_precondition_stack_variables(&mut partials);
+    }
+    store(&graph, file, &mut partials, &[path]);
+    graph[nodes[0]].id().local_id()
+}
+
+/// Stores a reference to mod.x in main, where mod.x is a re-export of lib.x. Returns the local ID
+/// of the reference.
+fn store_reexport_chain(store: &mut StoreFn) -> u32 {
+    store_file_with_path(store, "lib", |graph, file| {
+        vec![
+            StackGraph::root_node(),
+            create_pop_symbol_node(graph, file, "lib", false),
+            create_pop_symbol_node(graph, file, "x", true),
+        ]
+    });
+    store_file_with_path(store, "mod", |graph, file| {
+        vec![
+            StackGraph::root_node(),
+            create_pop_symbol_node(graph, file, "mod", false),
+            create_push_symbol_node(graph, file, "lib", false),
+            StackGraph::root_node(),
+        ]
+    });
+    store_file_with_path(store, "main", |graph, file| {
+        vec![
+            create_push_symbol_node(graph, file, "x", true),
+            create_push_symbol_node(graph, file, "mod", false),
+            StackGraph::root_node(),
+        ]
+    })
+}
+
+/// Returns the number of complete paths from the given node, using any storage backend.
+fn find_complete_paths<B: StorageBackend>(backend: &mut B, file: &str, local_id: u32) -> usize {
+    let file = backend.load_graph_for_file(file).unwrap();
+    let (graph, _, _) = backend.get();
+    let reference = graph
+        .node_for_id(NodeID::new_in_file(file, local_id))
+        .unwrap();
+    let mut count = 0;
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        backend,
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, _| count += 1,
+    )
+    .unwrap();
+    count
+}
+
+fn write_reexport_chain(path: &Path) -> u32 {
+    let mut writer = SgdbWriter::open(path).unwrap();
+    let reference = store_reexport_chain(&mut |graph, file, partials, paths| {
+        writer
+            .store_result_for_file(graph, file, "tag", partials, paths)
+            .unwrap()
+    });
+    writer.finish().unwrap();
+    reference
+}
+
+#[test]
+fn can_resolve_references_from_sgdb_file() {
+    let path = temp_path("resolve.sgdb");
+    let reference = write_reexport_chain(&path);
+
+    let mut reader = SgdbReader::open(&path).unwrap();
+    assert_eq!(
+        vec!["lib", "main", "mod"],
+        reader.files().collect::<Vec<_>>()
+    );
+    assert_eq!(FileStatus::Indexed, reader.status_for_file("main", None));
+    assert_eq!(
+        FileStatus::Missing,
+        reader.status_for_file("main", Some("other"))
+    );
+    assert_eq!(1, find_complete_paths(&mut reader, "main", reference));
+    assert!(matches!(
+        reader.load_graph_for_file("missing"),
+        Err(StorageError::MissingFile(_))
+    ));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn sgdb_and_sqlite_backends_give_same_results() {
+    let path = temp_path("backends.sgdb");
+    let sgdb_reference = write_reexport_chain(&path);
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let sqlite_reference = store_reexport_chain(&mut |graph, file, partials, paths| {
+        writer
+            .store_result_for_file(graph, file, "tag", partials, paths)
+            .unwrap()
+    });
+
+    let mut sgdb = SgdbReader::open(&path).unwrap();
+    let mut sqlite = writer.into_reader();
+    assert_eq!(
+        find_complete_paths(&mut sqlite, "main", sqlite_reference),
+        find_complete_paths(&mut sgdb, "main", sgdb_reference)
+    );
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn reopened_files_are_appended_to() {
+    let path = temp_path("append.sgdb");
+    let reference = write_reexport_chain(&path);
+    let original = std::fs::read(&path).unwrap();
+
+    let mut writer = SgdbWriter::open(&path).unwrap();
+    writer
+        .store_error_for_file(Path::new("lib"), "tag2", "parse error")
+        .unwrap();
+    assert!(writer.clean_file(Path::new("mod")));
+    assert!(!writer.clean_file(Path::new("other")));
+    writer.finish().unwrap();
+
+    let appended = std::fs::read(&path).unwrap();
+    assert!(appended.len() > original.len());
+    assert_eq!(original[..], appended[..original.len()]);
+
+    let mut reader = SgdbReader::open(&path).unwrap();
+    assert_eq!(vec!["lib", "main"], reader.files().collect::<Vec<_>>());
+    assert_eq!(
+        FileStatus::Error("parse error".to_string()),
+        reader.status_for_file("lib", Some("tag2"))
+    );
+    assert_eq!(FileStatus::Missing, reader.status_for_file("mod", None));
+    assert_eq!(0, find_complete_paths(&mut reader, "main", reference));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn invalid_files_are_rejected() {
+    let path = temp_path("invalid.sgdb");
+    assert!(matches!(
+        SgdbReader::open(&path),
+        Err(StorageError::MissingDatabase(_))
+    ));
+
+    std::fs::write(&path, b"not a stack graph database").unwrap();
+    assert!(matches!(
+        SgdbReader::open(&path),
+        Err(StorageError::InvalidDatabaseFile(_))
+    ));
+    assert!(matches!(
+        SgdbWriter::open(&path),
+        Err(StorageError::InvalidDatabaseFile(_))
+    ));
+
+    std::fs::remove_file(&path).unwrap();
+    write_reexport_chain(&path);
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes.extend_from_slice(b"unfinished record");
+    std::fs::write(&path, bytes).unwrap();
+    assert!(matches!(
+        SgdbReader::open(&path),
+        Err(StorageError::InvalidDatabaseFile(_))
+    ));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn records_beyond_the_end_of_the_file_are_rejected() {
+    let path = temp_path("out-of-bounds.sgdb");
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"SGDB");
+    bytes.extend_from_slice(&2u32.to_le_bytes());
+    // an index with a single file, whose graph record claims to be a terabyte long
+    bytes.extend_from_slice(&[1, 1, b'a', 0, 0, 8, 253]);
+    bytes.extend_from_slice(&(1u64 << 40).to_le_bytes());
+    bytes.extend_from_slice(&[8, 0, 0, 0]);
+    bytes.extend_from_slice(&8u64.to_le_bytes());
+    bytes.extend_from_slice(b"SGDB");
+    std::fs::write(&path, bytes).unwrap();
+    let mut reader = SgdbReader::open(&path).unwrap();
+    assert!(matches!(
+        reader.load_graph_for_file("a"),
+        Err(StorageError::InvalidDatabaseFile(_))
+    ));
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,96 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::serde::NoFilter;
+use stack_graphs::stitching::Database;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
+
+use crate::test_graphs::CreateStackGraph;
+
+fn snapshot_with_paths(graph: &StackGraph, starting_nodes: Vec<Handle<Node>>) -> String {
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(graph, &mut partials, None),
+        starting_nodes,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |g, ps, p| {
+            db.add_partial_path(g, ps, p.clone());
+        },
+    )
+    .expect("should not be cancelled");
+    graph.to_snapshot_string(&mut partials, &db, &NoFilter)
+}
+
+#[test]
+fn snapshot_lists_files_nodes_edges_and_paths() {
+    let mut graph = StackGraph::new();
+    let file = graph.file("test.py");
+    let sym_x = graph.symbol("x");
+    let ref_x = graph.reference(file, 1, sym_x);
+    let def_x = graph.definition(file, 2, sym_x);
+    graph.edge(ref_x, def_x);
+    let source_info = graph.source_info_mut(ref_x);
+    source_info.span.start.line = 3;
+    source_info.span.start.column.utf8_offset = 4;
+    source_info.span.end.line = 3;
+    source_info.span.end.column.utf8_offset = 5;
+
+    let snapshot = snapshot_with_paths(&graph, vec![ref_x]);
+    assert_eq!(
+        "\
+files:
+  test.py
+nodes:
+  [root]
+  [jump to scope]
+  [test.py(1) reference x] at 3:4-3:5
+  [test.py(2) definition x]
+edges:
+  test.py(1) -> test.py(2)
+paths:
+  <> () [test.py(1) reference x] -> [test.py(2) definition x] <> ()
+",
+        snapshot
+    );
+}
+
+#[test]
+fn snapshot_does_not_depend_on_insertion_order() {
+    let mut first = StackGraph::new();
+    let a = first.file("a.py");
+    let b = first.file("b.py");
+    let sym_x = first.symbol("x");
+    let root = first.root_node();
+    let ref_x = first.reference(a, 1, sym_x);
+    let def_x = first.definition(b, 1, sym_x);
+    first.edge(ref_x, root);
+    first.edge(root, def_x);
+
+    let mut second = StackGraph::new();
+    let b = second.file("b.py");
+    let a = second.file("a.py");
+    let sym_x = second.symbol("x");
+    let root = second.root_node();
+    let def_x = second.definition(b, 1, sym_x);
+    second.edge(root, def_x);
+    let ref_x_2 = second.reference(a, 1, sym_x);
+    second.edge(ref_x_2, root);
+
+    assert_eq!(
+        snapshot_with_paths(&first, vec![ref_x]),
+        snapshot_with_paths(&second, vec![ref_x_2]),
+    );
+}
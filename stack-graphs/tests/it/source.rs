@@ -0,0 +1,112 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use std::collections::HashMap;
+
+use lsp_positions::Span;
+use lsp_positions::SpanCalculator;
+use stack_graphs::assert::AssertionError;
+use stack_graphs::assert::AssertionSource;
+use stack_graphs::assert::IncorrectlyDefined;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPath;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::source::Excerpt;
+#[cfg(feature = "trace")]
+use stack_graphs::trace::TraceEvent;
+
+use crate::util::*;
+
+const SOURCE: &str = "def foo(): pass\nfoo()\n";
+
+fn sources() -> HashMap<String, String> {
+    HashMap::from([("a.py".to_string(), SOURCE.to_string())])
+}
+
+fn span(start: usize, end: usize) -> Span {
+    let mut calculator = SpanCalculator::new(SOURCE);
+    Span {
+        start: calculator.for_utf8_offset_in_string(start),
+        end: calculator.for_utf8_offset_in_string(end),
+    }
+}
+
+#[test]
+fn excerpt_marks_span() {
+    let excerpt = Excerpt::from_span(&sources(), "a.py", &span(16, 19)).unwrap();
+    assert_eq!(2, excerpt.line_number);
+    assert_eq!("2 | foo()\n  | ^^^", excerpt.to_string());
+
+    let excerpt = Excerpt::from_span(&sources(), "a.py", &span(4, 19)).unwrap();
+    assert_eq!(
+        "1 | def foo(): pass\n  |     ^^^^^^^^^^^",
+        excerpt.to_string()
+    );
+
+    let mut calculator = SpanCalculator::new("s = \"héllo\"\n");
+    let multibyte = Span {
+        start: calculator.for_utf8_offset_in_string(5),
+        end: calculator.for_utf8_offset_in_string(11),
+    };
+    let multibyte_sources = HashMap::from([("b.py".to_string(), "s = \"héllo\"\n".to_string())]);
+    let excerpt = Excerpt::from_span(&multibyte_sources, "b.py", &multibyte).unwrap();
+    assert_eq!(5..10, excerpt.columns);
+
+    assert_eq!(None, Excerpt::from_span(&sources(), "b.py", &span(16, 19)));
+    let provider = |path: &str| (path == "b.py").then(|| SOURCE.to_string());
+    assert!(Excerpt::from_span(&provider, "b.py", &span(16, 19)).is_some());
+}
+
+#[test]
+fn assertion_errors_show_source() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("a.py");
+    let reference = create_push_symbol_node(&mut graph, file, "foo", true);
+    let definition = create_pop_symbol_node(&mut graph, file, "foo", true);
+    graph.source_info_mut(definition).span = span(4, 7);
+    graph.add_edge(reference, definition, 0);
+
+    let mut partials = PartialPaths::new();
+    let mut path = PartialPath::from_node(&graph, &mut partials, reference);
+    path.append(
+        &graph,
+        &mut partials,
+        graph.outgoing_edges(reference).next().unwrap(),
+    )
+    .unwrap();
+    let error = AssertionError::IncorrectlyDefined(Box::new(IncorrectlyDefined {
+        source: AssertionSource {
+            file,
+            position: span(16, 19).start,
+        },
+        references: vec![reference],
+        missing_targets: vec![],
+        unexpected_paths: vec![path],
+    }));
+    let output = error.display_with_source(&graph, &sources()).to_string();
+    assert!(output.starts_with("a.py:2:1: references are not defined by the expected targets"));
+    assert!(output.contains("2 | foo()\n  | ^"));
+    assert!(output.contains("1 | def foo(): pass\n  |     ^^^"));
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn trace_events_show_source() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("a.py");
+    let reference = create_push_symbol_node(&mut graph, file, "foo", true);
+    graph.source_info_mut(reference).span = span(16, 19);
+
+    let mut partials = PartialPaths::new();
+    let path = PartialPath::from_node(&graph, &mut partials, reference);
+    let event = TraceEvent::Cyclic {
+        path: stack_graphs::serde::PartialPath::from_partial_path(&graph, &mut partials, &path),
+    };
+    let output = event.display_with_source(&graph, &sources()).to_string();
+    assert!(output.starts_with("cyclic "));
+    assert!(output.ends_with("2 | foo()\n  | ^^^"));
+}
@@ -5,14 +5,52 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
+
 use itertools::Itertools;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::Degree;
+use stack_graphs::graph::Edge;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::NodeID;
 use stack_graphs::graph::StackGraph;
+use stack_graphs::graph::Visibility;
+use stack_graphs::observer::QueryEvent;
+use stack_graphs::observer::QueryObserver;
+use stack_graphs::partial::PartialPath;
 use stack_graphs::partial::PartialPaths;
+use stack_graphs::paths::PathResolutionError;
+use stack_graphs::stitching::Appendable;
+use stack_graphs::stitching::AsyncForwardCandidates;
 use stack_graphs::stitching::Database;
+use stack_graphs::stitching::DatabaseCandidates;
+use stack_graphs::stitching::DefaultPartialPathPolicy;
+use stack_graphs::stitching::EmptyScopeStack;
+use stack_graphs::stitching::ForwardCandidates;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::GraphEdges;
+use stack_graphs::stitching::NodeInconsistency;
+use stack_graphs::stitching::PartialPathPolicy;
+use stack_graphs::stitching::PathFilters;
+use stack_graphs::stitching::SameFileVisibilityPolicy;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::stitching::ToAppendable;
+use stack_graphs::CancellationError;
+use stack_graphs::CancellationFlag;
+use stack_graphs::NoCancellation;
 
+use crate::test_graphs;
 use crate::util::create_partial_path_and_edges;
 use crate::util::create_pop_symbol_node;
 use crate::util::create_push_symbol_node;
+use crate::util::create_scope_node;
+use crate::util::edge;
 
 fn test_foo_bar_root_candidate_paths(symbols: &[&str], variable: bool) -> usize {
     let mut graph = StackGraph::new();
@@ -102,3 +140,888 @@ fn find_candidates_for_shorter_symbol_stack_without_variable() {
     let results = test_foo_bar_root_candidate_paths(&["foo"], false);
     assert_eq!(0, results);
 }
+
+#[test]
+fn can_compact_database() {
+    let graph: StackGraph = test_graphs::class_field_through_function_parameter::new();
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .expect("should never be cancelled");
+    }
+    let a = graph.get_file("a.py").unwrap();
+    db.remove_file(&graph, &mut partials, a);
+    let display = |partials: &mut PartialPaths, db: &Database| {
+        db.iter_partial_paths()
+            .map(|path| db[path].display(&graph, partials).to_string())
+            .collect_vec()
+    };
+    let expected = display(&mut partials, &db);
+
+    let compaction = db.compact(&mut partials);
+    assert!(compaction.freed_cells() > 0);
+    assert!(compaction.freed_bytes() > 0);
+    assert_eq!(compaction.after, partials.stats());
+    assert_eq!(expected, display(&mut partials, &db));
+
+    // Compacting again does not free anything else.
+    let compaction = db.compact(&mut partials);
+    assert_eq!(0, compaction.freed_cells());
+    assert_eq!(expected, display(&mut partials, &db));
+}
+
+#[test]
+fn database_finds_identical_paths() {
+    let mut graph = StackGraph::new();
+    let a = graph.add_file("a").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let s = create_scope_node(&mut graph, a, false);
+    let t = create_scope_node(&mut graph, a, false);
+    let foo_def = create_pop_symbol_node(&mut graph, a, "foo", false);
+    let foo_def_2 = create_pop_symbol_node(&mut graph, a, "foo", false);
+    let bar_def = create_pop_symbol_node(&mut graph, a, "bar", false);
+    let foo = create_partial_path_and_edges(&mut graph, &mut partials, &[s, foo_def, t]).unwrap();
+    let foo_again =
+        create_partial_path_and_edges(&mut graph, &mut partials, &[s, foo_def, t]).unwrap();
+    let foo_elsewhere =
+        create_partial_path_and_edges(&mut graph, &mut partials, &[s, foo_def_2, t]).unwrap();
+    let bar = create_partial_path_and_edges(&mut graph, &mut partials, &[s, bar_def, t]).unwrap();
+
+    let mut db = Database::new();
+    let handle = db.add_partial_path(&graph, &mut partials, foo);
+    assert_eq!(
+        Some(handle),
+        db.find_partial_path(&mut partials, &foo_again)
+    );
+    // Equal paths with different edges are not identical.
+    assert!(foo_elsewhere.equals(&mut partials, &foo_again));
+    assert_eq!(None, db.find_partial_path(&mut partials, &foo_elsewhere));
+    assert_eq!(None, db.find_partial_path(&mut partials, &bar));
+
+    db.remove_file(&graph, &mut partials, a);
+    assert_eq!(None, db.find_partial_path(&mut partials, &foo_again));
+}
+
+#[test]
+fn can_remove_file_from_database() {
+    let mut graph = StackGraph::new();
+    let a = graph.add_file("a").unwrap();
+    let b = graph.add_file("b").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let r = StackGraph::root_node();
+    let ref_a = create_push_symbol_node(&mut graph, a, "foo", true);
+    let def_a = create_pop_symbol_node(&mut graph, a, "foo", true);
+    let ref_b = create_push_symbol_node(&mut graph, b, "foo", true);
+    let def_b = create_pop_symbol_node(&mut graph, b, "bar", true);
+    let paths = [
+        create_partial_path_and_edges(&mut graph, &mut partials, &[ref_a, r]).unwrap(),
+        create_partial_path_and_edges(&mut graph, &mut partials, &[r, def_a]).unwrap(),
+        create_partial_path_and_edges(&mut graph, &mut partials, &[ref_b, r]).unwrap(),
+        create_partial_path_and_edges(&mut graph, &mut partials, &[r, def_b]).unwrap(),
+    ];
+
+    let mut db = Database::new();
+    let handles = paths
+        .iter()
+        .map(|path| db.add_partial_path(&graph, &mut partials, path.clone()))
+        .collect_vec();
+    db.mark_local_node(def_a);
+    db.mark_local_node(def_b);
+    assert_eq!(Degree::Multiple, db.get_incoming_path_degree(r));
+
+    let root_candidates = |graph: &StackGraph, partials: &mut PartialPaths, db: &mut Database| {
+        let mut results = Vec::new();
+        db.find_candidate_partial_paths_from_root(
+            graph,
+            partials,
+            Some(paths[0].symbol_stack_postcondition),
+            &mut results,
+        );
+        results
+    };
+    assert_eq!(
+        vec![handles[1]],
+        root_candidates(&graph, &mut partials, &mut db)
+    );
+
+    assert_eq!(2, db.remove_file(&graph, &mut partials, a));
+    assert_eq!(
+        vec![handles[2], handles[3]],
+        db.iter_partial_paths().collect_vec()
+    );
+    assert!(root_candidates(&graph, &mut partials, &mut db).is_empty());
+    let mut results = Vec::new();
+    db.find_candidate_partial_paths_from_node(&graph, &mut partials, ref_a, &mut results);
+    assert!(results.is_empty());
+    db.find_candidate_partial_paths_from_node(&graph, &mut partials, ref_b, &mut results);
+    assert_eq!(vec![handles[2]], results);
+    assert_eq!(Degree::One, db.get_incoming_path_degree(r));
+    assert_eq!(Degree::Zero, db.get_incoming_path_degree(def_a));
+    assert!(!db.node_is_local(def_a));
+    assert!(db.node_is_local(def_b));
+    assert_eq!(0, db.remove_file(&graph, &mut partials, a));
+
+    // re-adding the file's paths makes them available again
+    let handle = db.add_partial_path(&graph, &mut partials, paths[1].clone());
+    assert_eq!(
+        vec![handle],
+        root_candidates(&graph, &mut partials, &mut db)
+    );
+}
+
+#[test]
+fn local_nodes_are_recomputed_after_removing_file() {
+    let mut graph = StackGraph::new();
+    let a = graph.add_file("a").unwrap();
+    let b = graph.add_file("b").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let r = StackGraph::root_node();
+    let scope_a = create_scope_node(&mut graph, a, false);
+    let scope_b = create_scope_node(&mut graph, b, false);
+    let def_b = create_pop_symbol_node(&mut graph, b, "foo", false);
+    let paths = [
+        create_partial_path_and_edges(&mut graph, &mut partials, &[r, scope_a]).unwrap(),
+        create_partial_path_and_edges(&mut graph, &mut partials, &[scope_a, scope_b]).unwrap(),
+        create_partial_path_and_edges(&mut graph, &mut partials, &[scope_b, def_b]).unwrap(),
+    ];
+
+    let mut db = Database::new();
+    for path in paths {
+        db.add_partial_path(&graph, &mut partials, path);
+    }
+    db.find_local_nodes();
+    assert!(!db.node_is_local(scope_b));
+
+    db.remove_file(&graph, &mut partials, a);
+    assert!(!db.node_is_local(scope_a));
+    assert!(!db.node_is_local(scope_b));
+
+    db.find_local_nodes();
+    assert!(!db.node_is_local(scope_a));
+    assert!(db.node_is_local(scope_b));
+    assert!(db.node_is_local(def_b));
+}
+
+#[test]
+fn root_edge_keys_restrict_candidates_at_root() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let root = StackGraph::root_node();
+    let ref_a = create_push_symbol_node(&mut graph, file, "a", true);
+    let def_a = create_pop_symbol_node(&mut graph, file, "a", true);
+    let def_b = create_pop_symbol_node(&mut graph, file, "b", true);
+    let sym_a = graph.add_symbol("a");
+    let sym_b = graph.add_symbol("b");
+    graph.add_edge(ref_a, root, 0);
+    graph.add_edge(root, def_a, 0);
+    graph.add_edge(root, def_b, 0);
+    graph.set_root_edge_key(root, def_a, &[sym_a]);
+    graph.set_root_edge_key(root, def_b, &[sym_b]);
+
+    let mut path = PartialPath::from_node(&graph, &mut partials, ref_a);
+    path.append(&graph, &mut partials, edge(ref_a, root, 0))
+        .unwrap();
+
+    let mut candidates = Vec::new();
+    GraphEdgeCandidates::new(&graph, &mut partials, None)
+        .get_forward_candidates(&path, &mut candidates);
+    assert_eq!(vec![edge(root, def_a, 0)], candidates);
+
+    assert!(matches!(
+        path.clone()
+            .append(&graph, &mut partials, edge(root, def_b, 0)),
+        Err(PathResolutionError::RootEdgeKeyUnsatisfied)
+    ));
+}
+
+#[test]
+fn excluded_symbols_restrict_candidates_into_scope() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let ref_a = create_push_symbol_node(&mut graph, file, "a", true);
+    let ref_b = create_push_symbol_node(&mut graph, file, "b", true);
+    let scope = create_scope_node(&mut graph, file, true);
+    graph.add_edge(ref_a, scope, 0);
+    graph.add_edge(ref_b, scope, 0);
+    let sym_b = graph.add_symbol("b");
+    graph.set_excluded_symbols(scope, &[sym_b]);
+
+    let path_a = PartialPath::from_node(&graph, &mut partials, ref_a);
+    let path_b = PartialPath::from_node(&graph, &mut partials, ref_b);
+    let mut candidates = Vec::new();
+    GraphEdgeCandidates::new(&graph, &mut partials, None)
+        .get_forward_candidates(&path_a, &mut candidates);
+    GraphEdgeCandidates::new(&graph, &mut partials, None)
+        .get_forward_candidates(&path_b, &mut candidates);
+    assert_eq!(vec![edge(ref_a, scope, 0)], candidates);
+
+    assert!(path_a
+        .clone()
+        .append(&graph, &mut partials, edge(ref_a, scope, 0))
+        .is_ok());
+    assert!(matches!(
+        path_b
+            .clone()
+            .append(&graph, &mut partials, edge(ref_b, scope, 0)),
+        Err(PathResolutionError::ExcludedSymbol)
+    ));
+}
+
+#[test]
+fn parallel_stitching_finds_same_paths_as_sequential_stitching() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test");
+    let root = StackGraph::root_node();
+    let mut references = Vec::new();
+    for i in 0..50 {
+        let symbol = format!("x{}", i % 7);
+        let reference = create_push_symbol_node(&mut graph, file, &symbol, true);
+        graph.add_edge(reference, root, 0);
+        let definition = create_pop_symbol_node(&mut graph, file, &symbol, true);
+        graph.add_edge(root, definition, 0);
+        references.push(reference);
+    }
+
+    let mut partials = PartialPaths::new();
+    let mut expected = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        references.iter().copied(),
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| expected.push((path.start_node, path.end_node)),
+    )
+    .unwrap();
+    expected.sort();
+
+    let single_threaded = ForwardPartialPathStitcher::find_all_complete_partial_paths_in_parallel(
+        &graph,
+        &references,
+        StitcherConfig::default(),
+        1,
+        &NoCancellation,
+        |_, _, path| (path.start_node, path.end_node),
+    )
+    .unwrap();
+    let multi_threaded = ForwardPartialPathStitcher::find_all_complete_partial_paths_in_parallel(
+        &graph,
+        &references,
+        StitcherConfig::default().with_collect_stats(true),
+        4,
+        &NoCancellation,
+        |_, _, path| (path.start_node, path.end_node),
+    )
+    .unwrap();
+
+    assert_eq!(single_threaded.results, multi_threaded.results);
+    let mut actual = multi_threaded.results;
+    actual.sort();
+    assert_eq!(expected, actual);
+    assert_eq!(
+        expected.len(),
+        multi_threaded.stats.accepted_path_length.count()
+    );
+}
+
+#[test]
+fn parallel_stitching_can_be_cancelled() {
+    struct AlwaysCancelled;
+    impl CancellationFlag for AlwaysCancelled {
+        fn check(&self, at: &'static str) -> Result<(), CancellationError> {
+            Err(CancellationError(at))
+        }
+    }
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test");
+    let references = (0..40)
+        .map(|_| create_push_symbol_node(&mut graph, file, "x", true))
+        .collect::<Vec<_>>();
+
+    let result = ForwardPartialPathStitcher::find_all_complete_partial_paths_in_parallel(
+        &graph,
+        &references,
+        StitcherConfig::default(),
+        4,
+        &AlwaysCancelled,
+        |_, _, _| (),
+    );
+    assert!(result.is_err());
+}
+
+struct AsyncGraphEdgeCandidates<'a> {
+    inner: GraphEdgeCandidates<'a>,
+    loads: usize,
+}
+
+impl ForwardCandidates<Edge, Edge, GraphEdges, CancellationError> for AsyncGraphEdgeCandidates<'_> {
+    fn get_forward_candidates<R>(&mut self, path: &PartialPath, result: &mut R)
+    where
+        R: std::iter::Extend<Edge>,
+    {
+        self.inner.get_forward_candidates(path, result)
+    }
+
+    fn get_joining_candidate_degree(&self, path: &PartialPath) -> Degree {
+        self.inner.get_joining_candidate_degree(path)
+    }
+
+    fn get_graph_partials_and_db(&mut self) -> (&StackGraph, &mut PartialPaths, &GraphEdges) {
+        self.inner.get_graph_partials_and_db()
+    }
+}
+
+impl AsyncForwardCandidates<Edge, Edge, GraphEdges, CancellationError>
+    for AsyncGraphEdgeCandidates<'_>
+{
+    async fn load_forward_candidates_async(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &(dyn CancellationFlag + Sync),
+    ) -> Result<(), CancellationError> {
+        self.loads += 1;
+        self.inner.load_forward_candidates(path, cancellation_flag)
+    }
+}
+
+/// Polls a future that never waits to completion, which is enough for candidates that are
+/// immediately ready.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut context = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+#[test]
+fn async_stitching_finds_same_paths_as_sequential_stitching() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test");
+    let root = StackGraph::root_node();
+    let mut references = Vec::new();
+    for i in 0..10 {
+        let symbol = format!("x{}", i % 3);
+        let reference = create_push_symbol_node(&mut graph, file, &symbol, true);
+        graph.add_edge(reference, root, 0);
+        let definition = create_pop_symbol_node(&mut graph, file, &symbol, true);
+        graph.add_edge(root, definition, 0);
+        references.push(reference);
+    }
+
+    let mut partials = PartialPaths::new();
+    let mut expected = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        references.iter().copied(),
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| expected.push((path.start_node, path.end_node)),
+    )
+    .unwrap();
+
+    let mut partials = PartialPaths::new();
+    let mut candidates = AsyncGraphEdgeCandidates {
+        inner: GraphEdgeCandidates::new(&graph, &mut partials, None),
+        loads: 0,
+    };
+    let mut actual = Vec::new();
+    let stats = block_on(
+        ForwardPartialPathStitcher::find_all_complete_partial_paths_async(
+            &mut candidates,
+            references.iter().copied(),
+            StitcherConfig::default().with_collect_stats(true),
+            &NoCancellation,
+            |_, _, path| actual.push((path.start_node, path.end_node)),
+        ),
+    )
+    .unwrap();
+
+    assert_eq!(expected, actual);
+    assert_eq!(expected.len(), stats.accepted_path_length.count());
+    assert!(candidates.loads > 0);
+}
+
+fn scoped_reference_graph() -> (StackGraph, [Handle<Node>; 3]) {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test");
+    let root = StackGraph::root_node();
+    let reference = create_push_symbol_node(&mut graph, file, "x", true);
+    let scope = create_scope_node(&mut graph, file, false);
+    let definition = create_pop_symbol_node(&mut graph, file, "x", true);
+    graph.add_edge(reference, scope, 0);
+    graph.add_edge(scope, root, 0);
+    graph.add_edge(root, definition, 0);
+    (graph, [reference, scope, definition])
+}
+
+fn find_paths_with_policy(
+    graph: &StackGraph,
+    policy: &dyn PartialPathPolicy,
+) -> Vec<(Handle<Node>, Handle<Node>)> {
+    let file = graph.get_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file_with_policy(
+        graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        policy,
+        &NoCancellation,
+        |_, _, path| paths.push((path.start_node, path.end_node)),
+    )
+    .unwrap();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn default_policy_finds_minimal_partial_path_set() {
+    let (graph, _) = scoped_reference_graph();
+    let file = graph.get_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+    let mut expected = Vec::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        &graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| expected.push((path.start_node, path.end_node)),
+    )
+    .unwrap();
+    expected.sort();
+    assert_eq!(
+        expected,
+        find_paths_with_policy(&graph, &DefaultPartialPathPolicy)
+    );
+}
+
+#[test]
+fn policies_decide_which_partial_paths_are_kept() {
+    let (mut graph, [reference, scope, definition]) = scoped_reference_graph();
+    let root = StackGraph::root_node();
+
+    // Keeps only the paths that end at definitions
+    struct DefinitionsOnly;
+    impl PartialPathPolicy for DefinitionsOnly {
+        fn is_complete_as_possible(&self, graph: &StackGraph, path: &PartialPath) -> bool {
+            DefaultPartialPathPolicy.is_complete_as_possible(graph, path)
+        }
+        fn is_productive(
+            &self,
+            graph: &StackGraph,
+            _partials: &mut PartialPaths,
+            path: &PartialPath,
+        ) -> bool {
+            graph[path.end_node].is_definition()
+        }
+    }
+    assert_eq!(
+        vec![(root, definition)],
+        find_paths_with_policy(&graph, &DefinitionsOnly)
+    );
+
+    // Also stops at, and keeps paths ending at, the scope node
+    struct StopAtScopes;
+    impl PartialPathPolicy for StopAtScopes {
+        fn is_complete_as_possible(&self, graph: &StackGraph, path: &PartialPath) -> bool {
+            DefaultPartialPathPolicy.is_complete_as_possible(graph, path)
+                || (path.starts_at_endpoint(graph)
+                    && matches!(graph[path.end_node], Node::Scope(_)))
+        }
+    }
+    let paths = find_paths_with_policy(&graph, &StopAtScopes);
+    assert!(paths.contains(&(reference, scope)));
+    assert!(!paths.contains(&(reference, root)));
+
+    // Database inserts are filtered by the policy as well
+    let mut partials = PartialPaths::new();
+    let mut database = Database::new();
+    let path =
+        create_partial_path_and_edges(&mut graph, &mut partials, &[reference, scope]).unwrap();
+    assert!(database
+        .add_partial_path_with_policy(
+            &graph,
+            &mut partials,
+            path.clone(),
+            &DefaultPartialPathPolicy
+        )
+        .is_none());
+    assert!(database
+        .add_partial_path_with_policy(&graph, &mut partials, path, &StopAtScopes)
+        .is_some());
+}
+
+fn find_paths_with_filters(
+    graph: &StackGraph,
+    filters: &PathFilters,
+) -> Vec<(Handle<Node>, Handle<Node>)> {
+    let file = graph.get_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file_with_filters(
+        graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        &DefaultPartialPathPolicy,
+        filters,
+        &NoCancellation,
+        |_, _, path| paths.push((path.start_node, path.end_node)),
+    )
+    .unwrap();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn filters_prune_partial_path_search() {
+    let (graph, [reference, scope, definition]) = scoped_reference_graph();
+    let root = StackGraph::root_node();
+
+    let unfiltered = find_paths_with_filters(&graph, &PathFilters::default());
+    assert_eq!(
+        find_paths_with_policy(&graph, &DefaultPartialPathPolicy),
+        unfiltered
+    );
+    assert!(unfiltered.contains(&(reference, root)));
+    assert!(unfiltered.contains(&(root, definition)));
+
+    // Paths are not extended with rejected edges
+    let filters =
+        PathFilters::default().with_edge_filter(Arc::new(move |_: &StackGraph, edge: &Edge| {
+            edge.source != scope
+        }));
+    assert_eq!(
+        vec![(root, definition)],
+        find_paths_with_filters(&graph, &filters)
+    );
+
+    // Paths ending in rejected nodes are visited, but not extended
+    let filters = PathFilters::default().with_node_visitor(Arc::new(
+        move |_: &StackGraph, node: Handle<Node>| node != root,
+    ));
+    assert_eq!(
+        vec![(reference, root)],
+        find_paths_with_filters(&graph, &filters)
+    );
+}
+
+fn private_definition_graph() -> (StackGraph, [Handle<Node>; 2]) {
+    let mut graph = StackGraph::new();
+    let file_a = graph.get_or_create_file("a");
+    let file_b = graph.get_or_create_file("b");
+    let root = StackGraph::root_node();
+    let ref_a = create_push_symbol_node(&mut graph, file_a, "x", true);
+    let ref_b = create_push_symbol_node(&mut graph, file_b, "x", true);
+    let def_b = create_pop_symbol_node(&mut graph, file_b, "x", true);
+    graph.add_edge(ref_a, root, 0);
+    graph.add_edge(ref_b, root, 0);
+    graph.add_edge(root, def_b, 0);
+    graph.set_edge_visibility(root, def_b, Visibility::Private);
+    (graph, [ref_a, ref_b])
+}
+
+fn count_complete_paths<A, Db, C>(candidates: &mut C, reference: Handle<Node>) -> usize
+where
+    A: Appendable,
+    Db: ToAppendable<Handle<PartialPath>, A>,
+    C: ForwardCandidates<Handle<PartialPath>, A, Db, CancellationError>,
+{
+    let mut count = 0;
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        candidates,
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, _| count += 1,
+    )
+    .unwrap();
+    count
+}
+
+#[test]
+fn visibility_policy_restricts_edges_in_stitcher() {
+    let (graph, [ref_a, ref_b]) = private_definition_graph();
+    let policy = Arc::new(SameFileVisibilityPolicy);
+
+    let complete_paths = |filters: PathFilters, reference| {
+        let mut partials = PartialPaths::new();
+        let mut count = 0;
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut GraphEdgeCandidates::new(&graph, &mut partials, None).with_filters(filters),
+            vec![reference],
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, _| count += 1,
+        )
+        .unwrap();
+        count
+    };
+    assert_eq!(1, complete_paths(PathFilters::default(), ref_a));
+    assert_eq!(
+        0,
+        complete_paths(
+            PathFilters::default().with_visibility_policy(policy.clone()),
+            ref_a
+        )
+    );
+    assert_eq!(
+        1,
+        complete_paths(PathFilters::default().with_visibility_policy(policy), ref_b)
+    );
+}
+
+#[test]
+fn visibility_policy_restricts_partial_paths_in_stitcher() {
+    let (graph, [ref_a, ref_b]) = private_definition_graph();
+    let mut partials = PartialPaths::new();
+    let mut database = Database::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                database.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .unwrap();
+    }
+
+    assert_eq!(
+        1,
+        count_complete_paths(
+            &mut DatabaseCandidates::new(&graph, &mut partials, &mut database),
+            ref_a
+        )
+    );
+    let policy = Arc::new(SameFileVisibilityPolicy);
+    assert_eq!(
+        0,
+        count_complete_paths(
+            &mut DatabaseCandidates::new(&graph, &mut partials, &mut database)
+                .with_visibility_policy(policy.clone()),
+            ref_a
+        )
+    );
+    assert_eq!(
+        1,
+        count_complete_paths(
+            &mut DatabaseCandidates::new(&graph, &mut partials, &mut database)
+                .with_visibility_policy(policy),
+            ref_b
+        )
+    );
+}
+
+#[test]
+fn inconsistent_nodes_are_reported_and_skipped() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test");
+    let root = StackGraph::root_node();
+    let x = graph.add_symbol("x");
+
+    let id = graph.new_node_id(file);
+    let reference = graph.add_push_symbol_node(id, x, true).unwrap();
+    graph.add_edge(reference, root, 0);
+    let id = graph.new_node_id(file);
+    let definition = graph.add_pop_symbol_node(id, x, true).unwrap();
+    graph.add_edge(root, definition, 0);
+
+    let missing_scope = NodeID::new_in_file(file, 1000);
+    let id = graph.new_node_id(file);
+    let unknown = graph
+        .add_push_scoped_symbol_node(id, x, missing_scope, true)
+        .unwrap();
+    graph.add_edge(unknown, root, 0);
+    let id = graph.new_node_id(file);
+    let internal_scope = graph.add_scope_node(id, false).unwrap();
+    let id = graph.new_node_id(file);
+    let unexported = graph
+        .add_push_scoped_symbol_node(id, x, graph[internal_scope].id(), true)
+        .unwrap();
+    graph.add_edge(unexported, root, 0);
+
+    let mut partials = PartialPaths::new();
+    let mut diagnostics = Vec::new();
+    let mut starts = Vec::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file_with_diagnostics(
+        &graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, node, inconsistency| diagnostics.push((node, inconsistency)),
+        |_, _, path| starts.push(path.start_node),
+    )
+    .unwrap();
+
+    assert_eq!(
+        vec![
+            (unknown, NodeInconsistency::UnknownAttachedScope),
+            (
+                unexported,
+                NodeInconsistency::UnexportedAttachedScope(internal_scope)
+            ),
+        ],
+        diagnostics
+    );
+    assert!(starts.contains(&reference));
+    assert!(starts.contains(&root));
+    assert!(!starts.contains(&unknown));
+    assert!(!starts.contains(&unexported));
+}
+
+#[test]
+fn attached_scope_list_length_can_be_bounded() {
+    // A chain of scoped calls, where every call attaches the scopes of the previous call.
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test");
+    let f = graph.add_symbol("f");
+    let id = graph.new_node_id(file);
+    let scope = graph.add_scope_node(id, true).unwrap();
+    let scope_id = graph[scope].id();
+
+    let id = graph.new_node_id(file);
+    let reference = graph
+        .add_push_scoped_symbol_node(id, f, scope_id, true)
+        .unwrap();
+    let mut previous = reference;
+    for _ in 0..2 {
+        let id = graph.new_node_id(file);
+        let pop = graph.add_pop_scoped_symbol_node(id, f, false).unwrap();
+        let id = graph.new_node_id(file);
+        let push = graph
+            .add_push_scoped_symbol_node(id, f, scope_id, false)
+            .unwrap();
+        graph.add_edge(previous, pop, 0);
+        graph.add_edge(pop, push, 0);
+        previous = push;
+    }
+    let id = graph.new_node_id(file);
+    let definition = graph.add_pop_scoped_symbol_node(id, f, true).unwrap();
+    graph.add_edge(previous, definition, 0);
+
+    let complete_paths = |config: StitcherConfig| {
+        let mut partials = PartialPaths::new();
+        let mut count = 0;
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+            vec![reference],
+            config,
+            &NoCancellation,
+            |_, _, _| count += 1,
+        )
+        .unwrap();
+        count
+    };
+    assert_eq!(1, complete_paths(StitcherConfig::default()));
+    assert_eq!(
+        1,
+        complete_paths(StitcherConfig::default().with_max_attached_scope_list_length(Some(3)))
+    );
+    assert_eq!(
+        0,
+        complete_paths(StitcherConfig::default().with_max_attached_scope_list_length(Some(2)))
+    );
+}
+
+#[derive(Default)]
+struct EmptyScopeStackObserver {
+    paths: Mutex<Vec<EmptyScopeStack>>,
+}
+
+impl QueryObserver for EmptyScopeStackObserver {
+    fn observe(&self, event: &QueryEvent) {
+        if let QueryEvent::EmptyScopeStack { path } = event {
+            self.paths.lock().unwrap().push((*path).clone());
+        }
+    }
+}
+
+#[test]
+fn empty_scope_stacks_can_be_collected() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let reference = create_push_symbol_node(&mut graph, file, "x", true);
+    let definition = create_pop_symbol_node(&mut graph, file, "x", true);
+    let dynamic = create_pop_symbol_node(&mut graph, file, "x", false);
+    graph.add_edge(reference, definition, 0);
+    graph.add_edge(reference, dynamic, 0);
+    graph.add_edge(dynamic, StackGraph::jump_to_node(), 0);
+
+    let find_paths = |config: StitcherConfig| {
+        let mut partials = PartialPaths::new();
+        let mut count = 0;
+        let stats = ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+            vec![reference],
+            config,
+            &NoCancellation,
+            |_, _, _| count += 1,
+        )
+        .unwrap();
+        (count, stats)
+    };
+
+    let (count, stats) = find_paths(StitcherConfig::default());
+    assert_eq!(1, count);
+    assert!(stats.empty_scope_stacks.is_empty());
+
+    let (count, stats) =
+        find_paths(StitcherConfig::default().with_collect_empty_scope_stacks(true));
+    assert_eq!(1, count);
+    assert_eq!(1, stats.empty_scope_stacks.len());
+    let empty_scope_stack = &stats.empty_scope_stacks[0];
+    assert_eq!(
+        stack_graphs::serde::NodeID::from_node(&graph, dynamic),
+        empty_scope_stack.node
+    );
+
+    let mut partials = PartialPaths::new();
+    let mut path = PartialPath::from_node(&graph, &mut partials, reference);
+    path.eliminate_precondition_stack_variables(&mut partials);
+    let mut stitcher =
+        ForwardPartialPathStitcher::from_partial_paths(&graph, &mut partials, vec![path]);
+    let observer = Arc::new(EmptyScopeStackObserver::default());
+    stitcher.set_observer(observer.clone());
+    stitcher.set_collect_empty_scope_stacks(true);
+    while !stitcher.is_complete() {
+        stitcher.process_next_phase(
+            &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+            |_, _, _| true,
+        );
+    }
+    assert_eq!(
+        vec![empty_scope_stack.clone()],
+        *observer.paths.lock().unwrap()
+    );
+    assert_eq!(
+        Some(vec![empty_scope_stack.clone()]),
+        stitcher.take_empty_scope_stacks()
+    );
+}
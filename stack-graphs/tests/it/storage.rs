@@ -6,14 +6,55 @@
 // ------------------------------------------------------------------------------------------------
 
 use itertools::Itertools;
+#[cfg(feature = "analysis")]
+use stack_graphs::analysis::compare_databases;
+#[cfg(feature = "analysis")]
+use stack_graphs::analysis::ResolutionChange;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::File;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::NodeID;
 use stack_graphs::graph::StackGraph;
+use stack_graphs::observer::QueryEvent;
+use stack_graphs::observer::QueryObserver;
+use stack_graphs::partial::PartialPath;
 use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::Database;
+use stack_graphs::stitching::DatabaseCandidates;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::storage::database_library;
+use stack_graphs::storage::database_version;
+use stack_graphs::storage::migrate;
+use stack_graphs::storage::AsyncSQLiteReader;
+use stack_graphs::storage::FileStatus;
+use stack_graphs::storage::Library;
+use stack_graphs::storage::ManifestDependencySelector;
+use stack_graphs::storage::Revision;
+use stack_graphs::storage::SQLiteReader;
+use stack_graphs::storage::SQLiteReaderPool;
 use stack_graphs::storage::SQLiteWriter;
+use stack_graphs::storage::ShardedDatabase;
+use stack_graphs::storage::ShardingStrategy;
+use stack_graphs::storage::StorageError;
+use stack_graphs::storage::BINCODE_CONFIG;
+use stack_graphs::storage::FILE_GROUP_METADATA;
+use stack_graphs::storage::RULES_VERSION_METADATA;
 use stack_graphs::NoCancellation;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
 
 use crate::util::create_partial_path_and_edges;
 use crate::util::create_pop_symbol_node;
 use crate::util::create_push_symbol_node;
+use crate::util::create_scope_node;
 
 fn test_foo_bar_root_candidate_paths(symbols: &[&str], variable: bool) -> usize {
     let mut reader = {
@@ -125,3 +166,1940 @@ fn find_candidates_for_shorter_symbol_stack_without_variable() {
     let results = test_foo_bar_root_candidate_paths(&["foo"], false);
     assert_eq!(0, results);
 }
+
+#[test]
+fn failed_files_are_listed_with_error() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("ok").unwrap();
+    let mut partials = PartialPaths::new();
+    writer
+        .store_result_for_file(&graph, file, "tag", &mut partials, vec![])
+        .unwrap();
+    writer
+        .store_error_for_file(Path::new("failed"), "tag", "parse error")
+        .unwrap();
+
+    assert_eq!(
+        FileStatus::Error("parse error".into()),
+        writer.status_for_file("failed", None).unwrap()
+    );
+    assert_eq!(
+        FileStatus::Indexed,
+        writer.status_for_file("ok", None).unwrap()
+    );
+
+    let mut files = writer.failed_files().unwrap();
+    let failed = files
+        .try_iter()
+        .unwrap()
+        .map(|e| e.unwrap())
+        .map(|e| (e.path.to_string_lossy().to_string(), e.status))
+        .collect_vec();
+    assert_eq!(
+        vec![(
+            "failed".to_string(),
+            FileStatus::Error("parse error".into())
+        )],
+        failed
+    );
+}
+
+#[test]
+fn graph_metadata_is_listed_with_files() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test.py").unwrap();
+    graph.set_metadata("indexer_version", "1.2.3");
+    graph.set_metadata("commit", "abc123");
+    let mut partials = PartialPaths::new();
+    writer
+        .store_result_for_file(&graph, file, "tag", &mut partials, vec![])
+        .unwrap();
+    writer
+        .store_error_for_file(Path::new("failed.py"), "tag", "parse error")
+        .unwrap();
+
+    let mut reader = writer.into_reader();
+    let mut files = reader.list_all().unwrap();
+    let metadata = files
+        .try_iter()
+        .unwrap()
+        .map(|e| e.unwrap())
+        .map(|e| (e.path.to_string_lossy().to_string(), e.metadata))
+        .sorted()
+        .collect_vec();
+    assert_eq!(
+        vec![
+            ("failed.py".to_string(), BTreeMap::new()),
+            (
+                "test.py".to_string(),
+                BTreeMap::from([
+                    ("commit".to_string(), "abc123".to_string()),
+                    ("indexer_version".to_string(), "1.2.3".to_string()),
+                ])
+            ),
+        ],
+        metadata
+    );
+}
+
+#[test]
+fn files_in_group_are_loaded_together() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    for (name, group) in [
+        ("foo.h", Some("foo")),
+        ("foo.c", Some("foo")),
+        ("bar.c", None),
+    ] {
+        let mut graph = StackGraph::new();
+        let file = graph.add_file(name).unwrap();
+        if let Some(group) = group {
+            graph.set_metadata(FILE_GROUP_METADATA, group);
+        }
+        let mut partials = PartialPaths::new();
+        writer
+            .store_result_for_file(&graph, file, "tag", &mut partials, vec![])
+            .unwrap();
+    }
+
+    let mut reader = writer.into_reader();
+    reader.load_graph_for_file("foo.h").unwrap();
+    assert_eq!(2, reader.stats().file_loads);
+    let (graph, _, _) = reader.get();
+    assert!(graph.get_file("foo.c").is_some());
+    assert!(graph.get_file("bar.c").is_none());
+
+    reader.load_graph_for_file("foo.c").unwrap();
+    assert_eq!(2, reader.stats().file_loads);
+}
+
+#[test]
+fn readers_select_file_variants() {
+    fn store(writer: &mut SQLiteWriter, name: &str, config: &str) {
+        let mut graph = StackGraph::new();
+        let file = graph.add_file(name).unwrap();
+        graph.set_metadata("config", config);
+        let mut partials = PartialPaths::new();
+        writer
+            .store_result_for_file(&graph, file, "tag", &mut partials, vec![])
+            .unwrap();
+    }
+    fn list(reader: &mut SQLiteReader) -> Vec<(String, String)> {
+        let mut files = reader.list_all().unwrap();
+        let result = files
+            .try_iter()
+            .unwrap()
+            .map(|e| e.unwrap())
+            .map(|e| {
+                (
+                    e.path.to_string_lossy().to_string(),
+                    e.metadata["config"].clone(),
+                )
+            })
+            .sorted()
+            .collect_vec();
+        result
+    }
+
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    store(&mut writer, "foo.c", "default");
+    store(&mut writer, "bar.c", "default");
+    let mut writer = writer.with_variant("linux").unwrap();
+    store(&mut writer, "foo.c", "linux");
+    store(&mut writer, "baz.c", "linux");
+    assert_eq!(
+        FileStatus::Missing,
+        writer.status_for_file("bar.c", None).unwrap()
+    );
+    let mut writer = writer.with_variant("windows").unwrap();
+    store(&mut writer, "foo.c", "windows");
+
+    let mut reader = writer.into_reader();
+    assert_eq!(
+        vec![
+            ("bar.c".to_string(), "default".to_string()),
+            ("foo.c".to_string(), "default".to_string()),
+        ],
+        list(&mut reader)
+    );
+
+    let mut reader = reader.with_variants(["linux"]).unwrap();
+    assert_eq!(
+        vec![
+            ("bar.c".to_string(), "default".to_string()),
+            ("baz.c".to_string(), "linux".to_string()),
+            ("foo.c".to_string(), "linux".to_string()),
+        ],
+        list(&mut reader)
+    );
+
+    let mut reader = reader.with_variants(["windows", "linux"]).unwrap();
+    assert_eq!(
+        vec![
+            ("bar.c".to_string(), "default".to_string()),
+            ("baz.c".to_string(), "linux".to_string()),
+            ("foo.c".to_string(), "windows".to_string()),
+        ],
+        list(&mut reader)
+    );
+    reader.load_graph_for_file("foo.c").unwrap();
+    assert_eq!(Some("windows"), reader.get().0.metadata("config"));
+}
+
+#[test]
+fn failed_file_can_be_retried() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    writer
+        .store_error_for_file(Path::new("test"), "tag", "timed out")
+        .unwrap();
+    writer
+        .store_error_for_file(Path::new("test"), "tag", "failed")
+        .unwrap();
+    assert_eq!(
+        FileStatus::Error("failed".into()),
+        writer.status_for_file("test", Some("tag")).unwrap()
+    );
+
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+    writer
+        .store_result_for_file(&graph, file, "tag", &mut partials, vec![])
+        .unwrap();
+    assert_eq!(
+        FileStatus::Indexed,
+        writer.status_for_file("test", Some("tag")).unwrap()
+    );
+    assert_eq!(
+        0,
+        writer.failed_files().unwrap().try_iter().unwrap().count()
+    );
+}
+
+#[test]
+fn hash_sharding_assigns_files_to_fixed_number_of_shards() {
+    let db = ShardedDatabase::new("shards", ShardingStrategy::Hash(3));
+    let partitions = db.partition_files((0..100).map(|i| format!("src/file{}.py", i)));
+    assert!(partitions.len() <= 3);
+    assert_eq!(
+        100,
+        partitions.values().map(|files| files.len()).sum::<usize>()
+    );
+    assert_eq!(
+        db.shard_for_file(Path::new("src/file1.py")),
+        db.shard_for_file(Path::new("src/file1.py"))
+    );
+}
+
+#[test]
+fn directory_sharding_uses_leading_directories() {
+    let strategy = ShardingStrategy::Directory {
+        root: PathBuf::from("/src"),
+        depth: 1,
+    };
+    let db = ShardedDatabase::new("shards", strategy);
+    assert_eq!("dir-a", db.shard_for_file(Path::new("/src/a/b/foo.py")));
+    assert_eq!("dir-a", db.shard_for_file(Path::new("/src/a/bar.py")));
+    assert_eq!("dir", db.shard_for_file(Path::new("/src/quz.py")));
+    assert_eq!("other", db.shard_for_file(Path::new("/lib/quz.py")));
+}
+
+#[test]
+fn sharded_database_can_be_written_in_parallel_and_read_together() {
+    let dir = std::env::temp_dir().join(format!("stack-graphs-shards-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let strategy = ShardingStrategy::Directory {
+        root: PathBuf::from("/src"),
+        depth: 1,
+    };
+    let db = ShardedDatabase::new(&dir, strategy);
+    let partitions = db.partition_files(["/src/a/foo.py", "/src/b/bar.py"]);
+    assert_eq!(2, partitions.len());
+
+    std::thread::scope(|s| {
+        for (shard, files) in &partitions {
+            let db = &db;
+            s.spawn(move || {
+                let mut writer = db.open_writer(shard).unwrap();
+                for file in files {
+                    let mut graph = StackGraph::new();
+                    let file_handle = graph.add_file(&file.to_string_lossy()).unwrap();
+                    let mut partials = PartialPaths::new();
+                    let symbol = file.file_stem().unwrap().to_string_lossy();
+                    let r = StackGraph::root_node();
+                    let def = create_pop_symbol_node(&mut graph, file_handle, &symbol, true);
+                    let mut path =
+                        create_partial_path_and_edges(&mut graph, &mut partials, &[r, def])
+                            .unwrap();
+                    path.eliminate_precondition_stack_variables(&mut partials);
+                    writer
+                        .store_result_for_file(&graph, file_handle, "", &mut partials, vec![&path])
+                        .unwrap();
+                }
+            });
+        }
+    });
+
+    let mut reader = db.open_reader().unwrap();
+    assert_eq!(2, reader.list_all().unwrap().try_iter().unwrap().count());
+    assert_eq!(
+        FileStatus::Indexed,
+        reader
+            .status_for_file("/src/b/bar.py", None::<&str>)
+            .unwrap()
+    );
+
+    for symbol in ["foo", "bar"] {
+        let (graph, partials, _) = reader.get();
+        let file = graph.get_or_create_file(&format!("query-{}", symbol));
+        let r = StackGraph::root_node();
+        let reference = create_push_symbol_node(graph, file, symbol, true);
+        let mut path = create_partial_path_and_edges(graph, partials, &[reference, r]).unwrap();
+        path.eliminate_precondition_stack_variables(partials);
+
+        reader
+            .load_partial_path_extensions(&path, &NoCancellation)
+            .unwrap();
+
+        let (graph, partials, db) = reader.get();
+        let mut results = Vec::new();
+        db.find_candidate_partial_paths_from_root(
+            graph,
+            partials,
+            Some(path.symbol_stack_postcondition),
+            &mut results,
+        );
+        assert_eq!(1, results.len(), "candidates for {}", symbol);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn partial_files_are_listed_with_reason() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+    writer
+        .store_partial_result_for_file(&graph, file, "tag", &mut partials, vec![], "timed out")
+        .unwrap();
+    assert_eq!(
+        FileStatus::Partial("timed out".into()),
+        writer.status_for_file("test", Some("tag")).unwrap()
+    );
+    let partial_files = writer
+        .partial_files()
+        .unwrap()
+        .try_iter()
+        .unwrap()
+        .map(|e| e.unwrap().path)
+        .collect::<Vec<_>>();
+    assert_eq!(vec![PathBuf::from("test")], partial_files);
+    assert_eq!(
+        0,
+        writer.failed_files().unwrap().try_iter().unwrap().count()
+    );
+
+    writer
+        .store_result_for_file(&graph, file, "tag", &mut partials, vec![])
+        .unwrap();
+    assert_eq!(
+        FileStatus::Indexed,
+        writer.status_for_file("test", Some("tag")).unwrap()
+    );
+    assert_eq!(
+        0,
+        writer.partial_files().unwrap().try_iter().unwrap().count()
+    );
+}
+
+fn store_empty_file(writer: &mut SQLiteWriter, file: &str, tag: &str) {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file(file).unwrap();
+    let mut partials = PartialPaths::new();
+    writer
+        .store_result_for_file(&graph, file, tag, &mut partials, vec![])
+        .unwrap();
+}
+
+#[test]
+fn revisions_share_unchanged_files() {
+    let base = Revision::new("repo", "c1");
+    let next = Revision::new("repo", "c2");
+
+    let mut writer = SQLiteWriter::open_in_memory()
+        .unwrap()
+        .with_revision(base.clone());
+    store_empty_file(&mut writer, "a.py", "a1");
+    store_empty_file(&mut writer, "b.py", "b1");
+
+    let mut writer = writer.with_revision(next.clone());
+    assert_eq!(2, writer.copy_files_from_revision(&base).unwrap());
+    store_empty_file(&mut writer, "b.py", "b2");
+    assert_eq!(1, writer.clean_file(Path::new("a.py")).unwrap());
+    assert_eq!(
+        FileStatus::Missing,
+        writer.status_for_file("a.py", None).unwrap()
+    );
+    assert_eq!(
+        FileStatus::Indexed,
+        writer.status_for_file("b.py", Some("b2")).unwrap()
+    );
+
+    let mut writer = writer.with_revision(base.clone());
+    assert_eq!(
+        FileStatus::Indexed,
+        writer.status_for_file("a.py", Some("a1")).unwrap()
+    );
+    assert_eq!(
+        FileStatus::Indexed,
+        writer.status_for_file("b.py", Some("b1")).unwrap()
+    );
+
+    let reader = writer.into_reader();
+    assert_eq!(vec![base, next.clone()], reader.revisions().unwrap());
+    let mut reader = reader.with_revision(next);
+    assert_eq!(1, reader.list_all().unwrap().try_iter().unwrap().count());
+    assert!(reader.load_graph_for_file("b.py").is_ok());
+    assert!(reader.load_graph_for_file("a.py").is_err());
+}
+
+#[test]
+fn identical_files_are_stored_once() {
+    let mut writer = SQLiteWriter::open_in_memory()
+        .unwrap()
+        .with_revision(Revision::new("repo1", "c1"));
+    store_empty_file(&mut writer, "a.py", "a1");
+    store_empty_file(&mut writer, "b.py", "b1");
+
+    let mut writer = writer.with_revision(Revision::new("repo2", "c1"));
+    store_empty_file(&mut writer, "a.py", "a1");
+    assert!(writer.reuse_stored_file(Path::new("b.py"), "b1").unwrap());
+    assert!(writer.reuse_stored_file(Path::new("b.py"), "b1").unwrap());
+    assert!(!writer.reuse_stored_file(Path::new("c.py"), "c1").unwrap());
+    assert_eq!(
+        FileStatus::Indexed,
+        writer.status_for_file("b.py", Some("b1")).unwrap()
+    );
+
+    let stats = writer.deduplication_stats().unwrap();
+    assert_eq!(4, stats.file_count);
+    assert_eq!(2, stats.entry_count);
+    assert_eq!(2, stats.shared_file_count());
+
+    // cleaning a file in one revision keeps the shared entry for the other
+    writer.clean_file(Path::new("a.py")).unwrap();
+    let mut writer = writer.with_revision(Revision::new("repo1", "c1"));
+    assert_eq!(
+        FileStatus::Indexed,
+        writer.status_for_file("a.py", Some("a1")).unwrap()
+    );
+    assert_eq!(2, writer.deduplication_stats().unwrap().entry_count);
+}
+
+#[test]
+fn checkpoint_records_completed_files() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    store_empty_file(&mut writer, "a", "tag-a");
+    writer.checkpoint_file(Path::new("a"), "tag-a").unwrap();
+    writer
+        .store_error_for_file(Path::new("b"), "tag-b", "failed")
+        .unwrap();
+    writer.checkpoint_file(Path::new("b"), "tag-b").unwrap();
+    store_empty_file(&mut writer, "c", "tag-c");
+
+    assert!(writer
+        .is_file_checkpointed(Path::new("a"), "tag-a")
+        .unwrap());
+    assert!(writer
+        .is_file_checkpointed(Path::new("b"), "tag-b")
+        .unwrap());
+    // files that changed since they were completed are not checkpointed
+    assert!(!writer
+        .is_file_checkpointed(Path::new("a"), "tag-x")
+        .unwrap());
+    // files that were not completed by the current run are not checkpointed
+    assert!(!writer
+        .is_file_checkpointed(Path::new("c"), "tag-c")
+        .unwrap());
+
+    // files that were cleaned are not checkpointed
+    writer.clean_file(Path::new("b")).unwrap();
+    assert!(!writer
+        .is_file_checkpointed(Path::new("b"), "tag-b")
+        .unwrap());
+
+    assert_eq!(2, writer.clear_checkpoint().unwrap());
+    assert!(!writer
+        .is_file_checkpointed(Path::new("a"), "tag-a")
+        .unwrap());
+}
+
+#[test]
+fn garbage_collection_removes_entries_of_removed_revisions() {
+    let old = Revision::new("repo", "c1");
+    let new = Revision::new("repo", "c2");
+
+    let mut writer = SQLiteWriter::open_in_memory()
+        .unwrap()
+        .with_revision(old.clone());
+    store_empty_file(&mut writer, "a.py", "a1");
+    store_empty_file(&mut writer, "b.py", "b1");
+    let mut writer = writer.with_revision(new);
+    writer.copy_files_from_revision(&old).unwrap();
+    store_empty_file(&mut writer, "b.py", "b2");
+
+    assert_eq!(2, writer.remove_revision(&old).unwrap());
+    assert_eq!(3, writer.deduplication_stats().unwrap().entry_count);
+
+    let dry_run = writer.collect_garbage(true).unwrap();
+    assert_eq!(1, dry_run.entries);
+    assert_eq!(3, writer.deduplication_stats().unwrap().entry_count);
+
+    assert_eq!(dry_run, writer.collect_garbage(false).unwrap());
+    assert_eq!(2, writer.deduplication_stats().unwrap().entry_count);
+    assert_eq!(
+        FileStatus::Indexed,
+        writer.status_for_file("a.py", Some("a1")).unwrap()
+    );
+    assert_eq!(0, writer.collect_garbage(false).unwrap().entries);
+}
+
+#[test]
+fn strings_are_shared_between_stored_graphs() {
+    let path = temp_database_path("shared-strings");
+    let mut writer = SQLiteWriter::open(&path).unwrap();
+    let reference = store_reexport_chain(&mut writer);
+    let string_count = |value: &str| -> usize {
+        rusqlite::Connection::open(&path)
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM strings WHERE value = ?",
+                [value],
+                |r| r.get(0),
+            )
+            .unwrap()
+    };
+    assert_eq!(1, string_count("x"));
+    assert_eq!(1, string_count("lib"));
+
+    let mut reader = SQLiteReader::open(&path).unwrap();
+    assert_eq!(1, find_complete_paths(&mut reader, "main", reference));
+
+    writer.clean_file(Path::new("main")).unwrap();
+    let dry_run = writer.collect_garbage(true).unwrap();
+    assert_eq!(1, dry_run.strings);
+    assert_eq!(1, string_count("main"));
+    assert_eq!(dry_run, writer.collect_garbage(false).unwrap());
+    assert_eq!(0, string_count("main"));
+    assert_eq!(1, string_count("x"));
+
+    let mut reader = SQLiteReader::open(&path).unwrap();
+    reader.load_graph_for_file("lib").unwrap();
+    let (graph, _, _) = reader.get();
+    assert!(graph.iter_symbols().any(|s| &graph[s] == "x"));
+}
+
+#[test]
+fn snapshot_does_not_see_later_writes() {
+    let path = temp_database_path("snapshot");
+    let mut writer = SQLiteWriter::open(&path).unwrap();
+    store_empty_file(&mut writer, "a.py", "a1");
+
+    let mut reader = SQLiteReader::open(&path).unwrap();
+    let mut snapshot = reader.begin_read().unwrap();
+    assert_eq!(1, snapshot.list_all().unwrap().try_iter().unwrap().count());
+    store_empty_file(&mut writer, "b.py", "b1");
+    assert_eq!(
+        FileStatus::Missing,
+        snapshot.status_for_file("b.py", Some("b1")).unwrap()
+    );
+    assert_eq!(1, snapshot.list_all().unwrap().try_iter().unwrap().count());
+    assert!(snapshot.load_graph_for_file("a.py").is_ok());
+    snapshot.end().unwrap();
+
+    assert_eq!(
+        FileStatus::Indexed,
+        reader.status_for_file("b.py", Some("b1")).unwrap()
+    );
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn data_version_changes_when_database_is_written() {
+    let path = std::env::temp_dir().join(format!(
+        "stack-graphs-data-version-{}.sqlite",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let mut writer = SQLiteWriter::open(&path).unwrap();
+    store_empty_file(&mut writer, "a.py", "a1");
+
+    let reader = SQLiteReader::open(&path).unwrap();
+    let version = reader.data_version().unwrap();
+    assert_eq!(version, reader.data_version().unwrap());
+
+    store_empty_file(&mut writer, "b.py", "b1");
+    assert_ne!(version, reader.data_version().unwrap());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn database_stats_aggregate_stored_files() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    store_reexport_chain(&mut writer);
+    writer
+        .store_error_for_file(Path::new("broken"), "broken1", "parse error")
+        .unwrap();
+
+    let reader = writer.into_reader();
+    let stats = reader.database_stats().unwrap();
+    assert_eq!(
+        vec!["broken", "lib", "main", "mod"],
+        stats
+            .files
+            .iter()
+            .map(|f| f.path.to_str().unwrap())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        FileStatus::Error("parse error".to_string()),
+        stats.files[0].status
+    );
+    assert_eq!(0, stats.files[0].node_count);
+    assert_eq!(6, stats.node_count());
+    assert_eq!(3, stats.path_count());
+    assert_eq!(1, stats.files[1].root_path_count);
+    assert_eq!(1, stats.files[2].node_path_count);
+    assert!(stats.size_on_disk > 0);
+
+    let largest = stats.largest_files(2);
+    assert_eq!(2, largest.len());
+    assert!(largest[0].stored_size >= largest[1].stored_size);
+    assert_ne!("broken", largest[0].path.to_str().unwrap());
+
+    assert_eq!(
+        vec![("lib", 2), ("mod", 2), ("x", 2)],
+        stats.most_frequent_symbols(5)
+    );
+}
+
+/// Stores a file with a single partial path through the given nodes, which are created by the
+/// callback. Returns the local ID of the first node. Paths starting at a reference have their
+/// precondition variables eliminated.
+fn store_file_with_path<F>(writer: &mut SQLiteWriter, file: &str, tag: &str, nodes: F) -> u32
+where
+    F: FnOnce(&mut StackGraph, Handle<File>) -> Vec<Handle<Node>>,
+{
+    let mut graph = StackGraph::new();
+    let file = graph.add_file(file).unwrap();
+    let mut partials = PartialPaths::new();
+    let nodes = nodes(&mut graph, file);
+    let mut path = create_partial_path_and_edges(&mut graph, &mut partials, &nodes).unwrap();
+    if graph[nodes[0]].is_reference() {
+        path.eliminate_precondition_stack_variables(&mut partials);
+    }
+    writer
+        .store_result_for_file(&graph, file, tag, &mut partials, vec![&path])
+        .unwrap();
+    graph[nodes[0]].id().local_id()
+}
+
+fn find_complete_paths(reader: &mut SQLiteReader, file: &str, local_id: u32) -> usize {
+    let file = reader.load_graph_for_file(file).unwrap();
+    let (graph, _, _) = reader.get();
+    let reference = graph
+        .node_for_id(NodeID::new_in_file(file, local_id))
+        .unwrap();
+    let mut count = 0;
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        reader,
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, _| count += 1,
+    )
+    .unwrap();
+    count
+}
+
+/// Stores a reference to mod.x in main, where mod.x is a re-export of lib.x. Returns the local ID
+/// of the reference.
+fn store_reexport_chain(writer: &mut SQLiteWriter) -> u32 {
+    store_file_with_path(writer, "lib", "lib1", |graph, file| {
+        vec![
+            StackGraph::root_node(),
+            create_pop_symbol_node(graph, file, "lib", false),
+            create_pop_symbol_node(graph, file, "x", true),
+        ]
+    });
+    store_file_with_path(writer, "mod", "mod1", |graph, file| {
+        vec![
+            StackGraph::root_node(),
+            create_pop_symbol_node(graph, file, "mod", false),
+            create_push_symbol_node(graph, file, "lib", false),
+            StackGraph::root_node(),
+        ]
+    });
+    store_file_with_path(writer, "main", "main1", |graph, file| {
+        vec![
+            create_push_symbol_node(graph, file, "x", true),
+            create_push_symbol_node(graph, file, "mod", false),
+            StackGraph::root_node(),
+        ]
+    })
+}
+
+#[test]
+fn async_readers_stitch_paths_on_multi_threaded_runtime() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let local_id = store_reexport_chain(&mut writer);
+    let mut reader = writer.into_reader();
+    let file = reader.load_graph_for_file("main").unwrap();
+    let (graph, _, _) = reader.get();
+    let reference = graph
+        .node_for_id(NodeID::new_in_file(file, local_id))
+        .unwrap();
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .build()
+        .unwrap();
+    let mut candidates = AsyncSQLiteReader::new(reader).unwrap();
+    let task = runtime.spawn(async move {
+        let mut count = 0;
+        ForwardPartialPathStitcher::find_all_complete_partial_paths_async(
+            &mut candidates,
+            vec![reference],
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, _| count += 1,
+        )
+        .await
+        .unwrap();
+        (candidates, count)
+    });
+    let (candidates, count) = runtime.block_on(task).unwrap();
+
+    assert_eq!(1, count);
+    assert!(candidates.into_inner().stats().root_path_loads > 0);
+}
+
+#[test]
+fn prestitched_root_paths_are_preferred_by_readers() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let reference = store_reexport_chain(&mut writer);
+    assert_eq!(
+        1,
+        writer
+            .prestitch_root_paths(10, StitcherConfig::default(), &NoCancellation)
+            .unwrap()
+    );
+
+    let mut reader = writer.into_reader();
+    assert_eq!(1, find_complete_paths(&mut reader, "main", reference));
+    let stats = reader.stats();
+    assert_eq!(1, stats.prestitched_root_path_loads);
+    assert_eq!(0, stats.root_path_loads);
+}
+
+#[test]
+fn prestitched_root_paths_are_kept_when_unrelated_files_change() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let reference = store_reexport_chain(&mut writer);
+    writer
+        .prestitch_root_paths(10, StitcherConfig::default(), &NoCancellation)
+        .unwrap();
+    store_empty_file(&mut writer, "other", "other1");
+    writer.clean_file(Path::new("other")).unwrap();
+
+    let mut reader = writer.into_reader();
+    assert_eq!(1, find_complete_paths(&mut reader, "main", reference));
+    let stats = reader.stats();
+    assert_eq!(1, stats.prestitched_root_path_loads);
+    assert_eq!(0, stats.root_path_loads);
+}
+
+#[test]
+fn prestitched_root_paths_are_removed_when_dependencies_change() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let reference = store_reexport_chain(&mut writer);
+    writer
+        .prestitch_root_paths(10, StitcherConfig::default(), &NoCancellation)
+        .unwrap();
+    writer.clean_file(Path::new("lib")).unwrap();
+
+    let mut reader = writer.into_reader();
+    assert_eq!(0, find_complete_paths(&mut reader, "main", reference));
+    let stats = reader.stats();
+    assert_eq!(0, stats.prestitched_root_path_loads);
+    assert!(stats.root_path_loads > 0);
+}
+
+#[test]
+fn in_memory_writers_prestitch_from_their_own_database() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let reference = store_reexport_chain(&mut writer);
+    let mut other = SQLiteWriter::open_in_memory().unwrap();
+    store_alternative_definition(&mut other);
+    assert_eq!(
+        0,
+        other
+            .prestitch_root_paths(10, StitcherConfig::default(), &NoCancellation)
+            .unwrap()
+    );
+    assert_eq!(
+        1,
+        writer
+            .prestitch_root_paths(10, StitcherConfig::default(), &NoCancellation)
+            .unwrap()
+    );
+    store_empty_file(&mut writer, "other", "other1");
+
+    let mut reader = writer.into_reader();
+    assert_eq!(1, find_complete_paths(&mut reader, "main", reference));
+    assert_eq!(1, reader.stats().prestitched_root_path_loads);
+}
+
+#[test]
+fn shared_files_keep_their_paths_when_another_revision_changes_them() {
+    let mut writer = SQLiteWriter::open_in_memory()
+        .unwrap()
+        .with_revision(Revision::new("repo1", "c1"));
+    let reference = store_reexport_chain(&mut writer);
+    let mut writer = writer.with_revision(Revision::new("repo2", "c1"));
+    store_reexport_chain(&mut writer);
+    assert_eq!(3, writer.deduplication_stats().unwrap().entry_count);
+
+    // storing lib again with the same tag adds a reference to the shared entry, and does not
+    // replace the paths the other revision uses
+    store_file_with_path(&mut writer, "lib", "lib1", |graph, file| {
+        vec![
+            StackGraph::root_node(),
+            create_pop_symbol_node(graph, file, "lib", false),
+            create_pop_symbol_node(graph, file, "y", true),
+        ]
+    });
+    assert_eq!(3, writer.deduplication_stats().unwrap().entry_count);
+
+    // deleting lib from one revision keeps the entry for the other
+    writer.clean_file(Path::new("lib")).unwrap();
+    assert_eq!(3, writer.deduplication_stats().unwrap().entry_count);
+
+    let mut reader = writer
+        .into_reader()
+        .with_revision(Revision::new("repo1", "c1"));
+    assert_eq!(1, find_complete_paths(&mut reader, "main", reference));
+    let mut reader = reader.with_revision(Revision::new("repo2", "c1"));
+    reader.clear();
+    assert_eq!(0, find_complete_paths(&mut reader, "main", reference));
+}
+
+/// Stores a file that defines mod.x directly.
+fn store_alternative_definition(writer: &mut SQLiteWriter) {
+    store_file_with_path(writer, "alt", "alt1", |graph, file| {
+        vec![
+            StackGraph::root_node(),
+            create_pop_symbol_node(graph, file, "mod", false),
+            create_pop_symbol_node(graph, file, "x", true),
+        ]
+    });
+}
+
+#[test]
+fn prestitched_root_paths_are_removed_when_new_files_match() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let reference = store_reexport_chain(&mut writer);
+    writer
+        .prestitch_root_paths(10, StitcherConfig::default(), &NoCancellation)
+        .unwrap();
+    store_alternative_definition(&mut writer);
+
+    let mut reader = writer.into_reader();
+    assert_eq!(2, find_complete_paths(&mut reader, "main", reference));
+    let stats = reader.stats();
+    assert_eq!(0, stats.prestitched_root_path_loads);
+    assert!(stats.root_path_loads > 0);
+}
+
+/// Loads the resolved paths of the reference with the given local ID, and returns the names of
+/// the files their definitions are in.
+fn load_resolved_paths(
+    reader: &mut SQLiteReader,
+    file: &str,
+    local_id: u32,
+) -> Option<Vec<String>> {
+    let file = reader.load_graph_for_file(file).unwrap();
+    let (graph, _, _) = reader.get();
+    let reference = graph
+        .node_for_id(NodeID::new_in_file(file, local_id))
+        .unwrap();
+    let paths = reader
+        .load_resolved_paths(reference, &NoCancellation)
+        .unwrap()?;
+    let (graph, _, _) = reader.get();
+    Some(
+        paths
+            .iter()
+            .map(|path| {
+                graph[graph[path.end_node].file().unwrap()]
+                    .name()
+                    .to_string()
+            })
+            .collect(),
+    )
+}
+
+#[test]
+fn resolved_references_are_served_by_readers() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let reference = store_reexport_chain(&mut writer);
+    assert_eq!(
+        1,
+        writer
+            .resolve_references_in_files(["main"], StitcherConfig::default(), &NoCancellation)
+            .unwrap()
+    );
+
+    let mut reader = writer.into_reader();
+    assert_eq!(
+        Some(vec!["lib".to_string()]),
+        load_resolved_paths(&mut reader, "main", reference)
+    );
+    let stats = reader.stats();
+    assert_eq!(1, stats.resolved_reference_loads);
+    assert_eq!(0, stats.root_path_loads);
+}
+
+#[test]
+fn resolved_references_are_kept_when_unrelated_files_change() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let reference = store_reexport_chain(&mut writer);
+    writer
+        .resolve_references_in_files(["main"], StitcherConfig::default(), &NoCancellation)
+        .unwrap();
+    store_empty_file(&mut writer, "other", "other1");
+
+    let mut reader = writer.into_reader();
+    assert_eq!(
+        Some(vec!["lib".to_string()]),
+        load_resolved_paths(&mut reader, "main", reference)
+    );
+}
+
+#[test]
+fn resolved_references_are_removed_when_dependencies_change() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let reference = store_reexport_chain(&mut writer);
+    writer
+        .resolve_references_in_files(["main"], StitcherConfig::default(), &NoCancellation)
+        .unwrap();
+    store_file_with_path(&mut writer, "mod", "mod2", |graph, file| {
+        vec![
+            StackGraph::root_node(),
+            create_pop_symbol_node(graph, file, "mod", false),
+            create_push_symbol_node(graph, file, "lib", false),
+            StackGraph::root_node(),
+        ]
+    });
+
+    let mut reader = writer.into_reader();
+    assert_eq!(None, load_resolved_paths(&mut reader, "main", reference));
+    assert_eq!(1, find_complete_paths(&mut reader, "main", reference));
+}
+
+#[test]
+fn resolved_references_are_removed_when_new_files_match() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let reference = store_reexport_chain(&mut writer);
+    writer
+        .resolve_references_in_files(["main"], StitcherConfig::default(), &NoCancellation)
+        .unwrap();
+    store_alternative_definition(&mut writer);
+
+    let mut reader = writer.into_reader();
+    assert_eq!(None, load_resolved_paths(&mut reader, "main", reference));
+    assert_eq!(2, find_complete_paths(&mut reader, "main", reference));
+}
+
+/// Stores a library package, which defines lib.x through an internal scope, and re-exports
+/// other.w as lib.z. The definition of y is not reachable from the root.
+fn store_library(writer: &mut SQLiteWriter) {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("node_modules/lib/index").unwrap();
+    let mut partials = PartialPaths::new();
+    let lib = create_pop_symbol_node(&mut graph, file, "lib", false);
+    let scope = create_scope_node(&mut graph, file, false);
+    let x = create_pop_symbol_node(&mut graph, file, "x", true);
+    create_pop_symbol_node(&mut graph, file, "y", true);
+    let z = create_pop_symbol_node(&mut graph, file, "z", false);
+    let w = create_push_symbol_node(&mut graph, file, "w", false);
+    let other = create_push_symbol_node(&mut graph, file, "other", false);
+    let paths = vec![
+        vec![StackGraph::root_node(), lib, scope],
+        vec![scope, x],
+        vec![scope, z, w, other, StackGraph::root_node()],
+    ]
+    .into_iter()
+    .map(|nodes| create_partial_path_and_edges(&mut graph, &mut partials, &nodes).unwrap())
+    .collect::<Vec<_>>();
+    writer
+        .store_result_for_file(&graph, file, "lib1", &mut partials, &paths)
+        .unwrap();
+}
+
+/// Stores a reference to lib.<symbol> in main, and returns its local ID.
+fn store_library_reference(writer: &mut SQLiteWriter, symbol: &str) -> u32 {
+    store_file_with_path(writer, "main", symbol, |graph, file| {
+        vec![
+            create_push_symbol_node(graph, file, symbol, true),
+            create_push_symbol_node(graph, file, "lib", false),
+            StackGraph::root_node(),
+        ]
+    })
+}
+
+#[test]
+fn linked_libraries_resolve_references() {
+    let lib_path = temp_database_path("library-lib");
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    store_library(&mut writer);
+    assert_eq!(
+        2,
+        writer
+            .seal_library(
+                &Library::new("lib", "1.0.0"),
+                &lib_path,
+                StitcherConfig::default(),
+                &NoCancellation
+            )
+            .unwrap()
+    );
+    assert_eq!(
+        Some(Library::new("lib", "1.0.0")),
+        database_library(&lib_path).unwrap()
+    );
+
+    let other_path = temp_database_path("library-other");
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    store_file_with_path(
+        &mut writer,
+        "node_modules/other/index",
+        "other1",
+        |graph, file| {
+            vec![
+                StackGraph::root_node(),
+                create_pop_symbol_node(graph, file, "other", false),
+                create_pop_symbol_node(graph, file, "w", true),
+            ]
+        },
+    );
+    writer
+        .seal_library(
+            &Library::new("other", "1.0.0"),
+            &other_path,
+            StitcherConfig::default(),
+            &NoCancellation,
+        )
+        .unwrap();
+
+    let mut app = SQLiteWriter::open_in_memory().unwrap();
+    let x = store_library_reference(&mut app, "x");
+    let mut reader = app.into_reader();
+    assert_eq!(0, find_complete_paths(&mut reader, "main", x));
+
+    let mut reader = reader
+        .with_library(&lib_path)
+        .unwrap()
+        .with_library(&other_path)
+        .unwrap();
+    assert_eq!(
+        vec![Library::new("lib", "1.0.0"), Library::new("other", "1.0.0")],
+        reader.libraries()
+    );
+    assert_eq!(1, find_complete_paths(&mut reader, "main", x));
+    assert!(reader.stats().library_root_path_loads > 0);
+
+    let mut app = SQLiteWriter::open_in_memory().unwrap();
+    let z = store_library_reference(&mut app, "z");
+    let mut reader = app
+        .into_reader()
+        .with_library(&lib_path)
+        .unwrap()
+        .with_library(&other_path)
+        .unwrap();
+    assert_eq!(1, find_complete_paths(&mut reader, "main", z));
+
+    // The library graph only contains the nodes of the summary paths.
+    let mut library = SQLiteReader::open(&lib_path).unwrap();
+    library
+        .load_graph_for_file("node_modules/lib/index")
+        .unwrap();
+    let (graph, _, _) = library.get();
+    let symbols = graph
+        .iter_nodes()
+        .filter_map(|node| graph[node].symbol())
+        .map(|symbol| graph[symbol].to_string())
+        .sorted()
+        .collect_vec();
+    assert_eq!(vec!["lib", "other", "w", "x", "z"], symbols);
+
+    let _ = std::fs::remove_file(&lib_path);
+    let _ = std::fs::remove_file(&other_path);
+}
+
+#[test]
+fn sealed_libraries_are_read_only() {
+    let lib_path = temp_database_path("sealed-library");
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    store_library(&mut writer);
+    let library = Library::new("lib", "1.0.0");
+    writer
+        .seal_library(
+            &library,
+            &lib_path,
+            StitcherConfig::default(),
+            &NoCancellation,
+        )
+        .unwrap();
+    assert!(matches!(
+        SQLiteWriter::open(&lib_path),
+        Err(StorageError::SealedLibrary(_))
+    ));
+    assert!(matches!(
+        writer.seal_library(
+            &library,
+            &lib_path,
+            StitcherConfig::default(),
+            &NoCancellation
+        ),
+        Err(StorageError::Io(_))
+    ));
+
+    let app_path = temp_database_path("sealed-library-app");
+    SQLiteWriter::open(&app_path).unwrap();
+    assert_eq!(None, database_library(&app_path).unwrap());
+    assert!(matches!(
+        SQLiteReader::open(&app_path)
+            .unwrap()
+            .with_library(&app_path),
+        Err(StorageError::NotALibrary(_))
+    ));
+
+    let _ = std::fs::remove_file(&lib_path);
+    let _ = std::fs::remove_file(&app_path);
+}
+
+/// Seals a library with a single file in the given directory, which defines <name>.x. If a
+/// re-export is given, <name>.x is a re-export of <reexport>.x instead.
+fn seal_version(name: &str, version: &str, directory: &str, reexport: Option<&str>) -> PathBuf {
+    let path = temp_database_path(&format!("library-{}-{}", name, version));
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let file = format!("{}/index", directory);
+    store_file_with_path(&mut writer, &file, version, |graph, file| {
+        let mut nodes = vec![
+            StackGraph::root_node(),
+            create_pop_symbol_node(graph, file, name, false),
+        ];
+        match reexport {
+            Some(reexport) => nodes.extend([
+                create_pop_symbol_node(graph, file, "x", false),
+                create_push_symbol_node(graph, file, "x", false),
+                create_push_symbol_node(graph, file, reexport, false),
+                StackGraph::root_node(),
+            ]),
+            None => nodes.push(create_pop_symbol_node(graph, file, "x", true)),
+        }
+        nodes
+    });
+    writer
+        .seal_library(
+            &Library::new(name, version),
+            &path,
+            StitcherConfig::default(),
+            &NoCancellation,
+        )
+        .unwrap();
+    path
+}
+
+/// Returns the files of the definitions that the reference with the given local ID resolves to.
+fn find_definition_files(reader: &mut SQLiteReader, file: &str, local_id: u32) -> Vec<String> {
+    let file = reader.load_graph_for_file(file).unwrap();
+    let (graph, _, _) = reader.get();
+    let reference = graph
+        .node_for_id(NodeID::new_in_file(file, local_id))
+        .unwrap();
+    let mut files = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        reader,
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |graph, _, path| {
+            let file = graph[path.end_node].file().unwrap();
+            files.push(graph[file].name().to_string());
+        },
+    )
+    .unwrap();
+    files.sort();
+    files
+}
+
+#[test]
+fn dependency_selector_picks_declared_library_versions() {
+    let libraries = [
+        seal_version("lib", "1", "node_modules/lib-1", None),
+        seal_version("lib", "2", "node_modules/lib-2", Some("other")),
+        seal_version("other", "1", "node_modules/other-1", None),
+        seal_version("other", "2", "node_modules/other-2", None),
+    ];
+    let mut app = SQLiteWriter::open_in_memory().unwrap();
+    let reference = store_library_reference(&mut app, "x");
+    let mut reader = app.into_reader();
+    for library in &libraries {
+        reader = reader.with_library(library).unwrap();
+    }
+    assert_eq!(
+        vec![
+            "node_modules/lib-1/index",
+            "node_modules/other-1/index",
+            "node_modules/other-2/index",
+        ],
+        find_definition_files(&mut reader, "main", reference)
+    );
+
+    let mut selector = ManifestDependencySelector::new();
+    selector.add_package("", [("lib", "2")]);
+    selector.add_package("node_modules/lib-2", [("other", "1")]);
+    let mut reader = reader.with_dependency_selector(Arc::new(selector));
+    assert_eq!(
+        vec!["node_modules/other-1/index"],
+        find_definition_files(&mut reader, "main", reference)
+    );
+
+    for library in &libraries {
+        let _ = std::fs::remove_file(library);
+    }
+}
+
+#[derive(Default)]
+struct RecordingObserver {
+    files: Mutex<Vec<String>>,
+    loaded_root_paths: AtomicUsize,
+    extended_paths: AtomicUsize,
+    phases: AtomicUsize,
+}
+
+impl QueryObserver for RecordingObserver {
+    fn observe(&self, event: &QueryEvent) {
+        match event {
+            QueryEvent::FileLoaded { file } => self.files.lock().unwrap().push(file.to_string()),
+            QueryEvent::PathsLoaded {
+                from_root: true,
+                count,
+            } => {
+                self.loaded_root_paths.fetch_add(*count, Ordering::Relaxed);
+            }
+            QueryEvent::PathsLoaded { .. } => {}
+            QueryEvent::PathExtended { .. } => {
+                self.extended_paths.fetch_add(1, Ordering::Relaxed);
+            }
+            QueryEvent::PhaseCompleted { .. } => {
+                self.phases.fetch_add(1, Ordering::Relaxed);
+            }
+            QueryEvent::EmptyScopeStack { .. } => {}
+        }
+    }
+}
+
+#[test]
+fn readers_collect_provenance_of_loaded_paths() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    store_file_with_path(&mut writer, "lib", "lib1", |graph, file| {
+        graph.set_metadata(RULES_VERSION_METADATA, "rules1");
+        vec![
+            StackGraph::root_node(),
+            create_pop_symbol_node(graph, file, "x", true),
+        ]
+    });
+    let reference = store_file_with_path(&mut writer, "main", "main1", |graph, file| {
+        vec![
+            create_push_symbol_node(graph, file, "x", true),
+            StackGraph::root_node(),
+        ]
+    });
+
+    let mut reader = writer.into_reader().with_collect_provenance(true);
+    assert_eq!(1, find_complete_paths(&mut reader, "main", reference));
+    let (_, _, db) = reader.get();
+    let provenance = db
+        .iter_partial_paths()
+        .map(|p| db.provenance(p).unwrap())
+        .map(|p| {
+            assert!(p.indexed_at.is_some());
+            (
+                p.file.as_str(),
+                p.tag.as_deref(),
+                p.rules_version.as_deref(),
+            )
+        })
+        .sorted()
+        .collect_vec();
+    assert_eq!(
+        vec![
+            ("lib", Some("lib1"), Some("rules1")),
+            ("main", Some("main1"), None),
+        ],
+        provenance
+    );
+}
+
+#[test]
+fn observer_is_notified_of_query_events() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let reference = store_reexport_chain(&mut writer);
+
+    let observer = Arc::new(RecordingObserver::default());
+    let mut reader = writer.into_reader().with_observer(observer.clone());
+    assert_eq!(1, find_complete_paths(&mut reader, "main", reference));
+
+    let mut files = observer.files.lock().unwrap().clone();
+    files.sort();
+    assert_eq!(vec!["lib", "main", "mod"], files);
+    assert_eq!(2, observer.loaded_root_paths.load(Ordering::Relaxed));
+    assert!(observer.extended_paths.load(Ordering::Relaxed) > 0);
+    assert!(observer.phases.load(Ordering::Relaxed) > 0);
+}
+
+#[test]
+fn readers_can_be_shared_between_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SQLiteReader>();
+    assert_send_sync::<SQLiteReaderPool>();
+}
+
+#[test]
+fn pooled_readers_can_query_concurrently() {
+    let path = temp_database_path("pool-concurrent");
+    let reference = store_reexport_chain(&mut SQLiteWriter::open(&path).unwrap());
+    let pool = SQLiteReaderPool::open(&path).unwrap();
+
+    std::thread::scope(|s| {
+        let handles = (0..4)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut reader = pool.reader().unwrap();
+                    find_complete_paths(&mut reader, "main", reference)
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert_eq!(1, handle.join().unwrap());
+        }
+    });
+    assert!((1..=4).contains(&pool.idle_count()));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn pool_reuses_connections_of_dropped_readers() {
+    let path = temp_database_path("pool-reuse");
+    store_reexport_chain(&mut SQLiteWriter::open(&path).unwrap());
+    let pool = SQLiteReaderPool::open(&path).unwrap();
+    assert_eq!(1, pool.idle_count());
+
+    let first = pool.reader().unwrap();
+    assert_eq!(0, pool.idle_count());
+    let second = pool.clone().reader().unwrap();
+    assert_eq!(0, pool.idle_count());
+    drop(first);
+    drop(second);
+    assert_eq!(2, pool.idle_count());
+
+    drop(pool.reader().unwrap());
+    assert_eq!(2, pool.idle_count());
+    assert!(matches!(
+        SQLiteReaderPool::open("/nonexistent/stack-graphs.sqlite"),
+        Err(StorageError::MissingDatabase(_))
+    ));
+    let _ = std::fs::remove_file(&path);
+}
+
+/// A stack graph in the encoding of database versions before 10, whose edges have no root keys.
+#[derive(bincode::Encode)]
+struct StackGraphV6 {
+    files: stack_graphs::serde::Files,
+    nodes: NodesV6,
+    edges: EdgesV6,
+}
+
+#[derive(bincode::Encode)]
+struct NodesV6 {
+    data: Vec<NodeV6>,
+}
+
+/// Nodes before symbols had namespaces.  Symbol nodes in the test are unscoped, so scoped
+/// symbol variants do not carry data.
+#[derive(bincode::Encode)]
+#[allow(dead_code)]
+enum NodeV6 {
+    DropScopes {
+        id: stack_graphs::serde::NodeID,
+        source_info: Option<stack_graphs::serde::SourceInfo>,
+        debug_info: Option<stack_graphs::serde::DebugInfo>,
+    },
+    JumpToScope {
+        id: stack_graphs::serde::NodeID,
+        source_info: Option<stack_graphs::serde::SourceInfo>,
+        debug_info: Option<stack_graphs::serde::DebugInfo>,
+    },
+    PopScopedSymbol,
+    PopSymbol {
+        id: stack_graphs::serde::NodeID,
+        symbol: String,
+        is_definition: bool,
+        source_info: Option<stack_graphs::serde::SourceInfo>,
+        debug_info: Option<stack_graphs::serde::DebugInfo>,
+    },
+    PushScopedSymbol,
+    PushSymbol {
+        id: stack_graphs::serde::NodeID,
+        symbol: String,
+        is_reference: bool,
+        source_info: Option<stack_graphs::serde::SourceInfo>,
+        debug_info: Option<stack_graphs::serde::DebugInfo>,
+    },
+    Root {
+        id: stack_graphs::serde::NodeID,
+        source_info: Option<stack_graphs::serde::SourceInfo>,
+        debug_info: Option<stack_graphs::serde::DebugInfo>,
+    },
+    Scope {
+        id: stack_graphs::serde::NodeID,
+        is_exported: bool,
+        source_info: Option<stack_graphs::serde::SourceInfo>,
+        debug_info: Option<stack_graphs::serde::DebugInfo>,
+    },
+}
+
+impl From<stack_graphs::serde::Node> for NodeV6 {
+    fn from(node: stack_graphs::serde::Node) -> Self {
+        match node {
+            stack_graphs::serde::Node::DropScopes {
+                id,
+                source_info,
+                debug_info,
+            } => Self::DropScopes {
+                id,
+                source_info,
+                debug_info,
+            },
+            stack_graphs::serde::Node::JumpToScope {
+                id,
+                source_info,
+                debug_info,
+            } => Self::JumpToScope {
+                id,
+                source_info,
+                debug_info,
+            },
+            stack_graphs::serde::Node::PopSymbol {
+                id,
+                symbol,
+                is_definition,
+                source_info,
+                debug_info,
+                ..
+            } => Self::PopSymbol {
+                id,
+                symbol,
+                is_definition,
+                source_info,
+                debug_info,
+            },
+            stack_graphs::serde::Node::PushSymbol {
+                id,
+                symbol,
+                is_reference,
+                source_info,
+                debug_info,
+                ..
+            } => Self::PushSymbol {
+                id,
+                symbol,
+                is_reference,
+                source_info,
+                debug_info,
+            },
+            stack_graphs::serde::Node::Root {
+                id,
+                source_info,
+                debug_info,
+            } => Self::Root {
+                id,
+                source_info,
+                debug_info,
+            },
+            stack_graphs::serde::Node::Scope {
+                id,
+                is_exported,
+                source_info,
+                debug_info,
+                ..
+            } => Self::Scope {
+                id,
+                is_exported,
+                source_info,
+                debug_info,
+            },
+            _ => panic!("unexpected scoped symbol node in version 6 test database"),
+        }
+    }
+}
+
+#[derive(bincode::Encode)]
+struct EdgesV6 {
+    data: Vec<EdgeV6>,
+}
+
+#[derive(bincode::Encode)]
+struct EdgeV6 {
+    source: stack_graphs::serde::NodeID,
+    sink: stack_graphs::serde::NodeID,
+    precedence: i32,
+    debug_info: Option<stack_graphs::serde::DebugInfo>,
+}
+
+/// Creates a version 6 database, containing a file with a reference that resolves within the
+/// file, and a file that failed to index. Returns the local ID of the reference.
+fn create_v6_database(db_path: &Path) -> u32 {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test.py").unwrap();
+    let mut partials = PartialPaths::new();
+    let reference = create_push_symbol_node(&mut graph, file, "x", true);
+    let definition = create_pop_symbol_node(&mut graph, file, "x", true);
+    let mut path =
+        create_partial_path_and_edges(&mut graph, &mut partials, &[reference, definition]).unwrap();
+    path.eliminate_precondition_stack_variables(&mut partials);
+    // The path's symbol stacks are empty, so its encoding is the same as in version 6.
+    let path = stack_graphs::serde::PartialPath::from_partial_path(&graph, &mut partials, &path);
+
+    let serialized = graph.to_serializable();
+    let legacy = StackGraphV6 {
+        files: serialized.files,
+        nodes: NodesV6 {
+            data: serialized
+                .nodes
+                .data
+                .into_iter()
+                .map(NodeV6::from)
+                .collect(),
+        },
+        edges: EdgesV6 {
+            data: serialized
+                .edges
+                .data
+                .into_iter()
+                .map(|e| EdgeV6 {
+                    source: e.source,
+                    sink: e.sink,
+                    precedence: e.precedence,
+                    debug_info: e.debug_info,
+                })
+                .collect(),
+        },
+    };
+    let empty = StackGraphV6 {
+        files: stack_graphs::serde::Files { data: Vec::new() },
+        nodes: NodesV6 { data: Vec::new() },
+        edges: EdgesV6 { data: Vec::new() },
+    };
+
+    let conn = rusqlite::Connection::open(db_path).unwrap();
+    conn.execute_batch(
+        r#"
+        CREATE TABLE metadata (version INTEGER NOT NULL) STRICT;
+        CREATE TABLE graphs (
+            file   TEXT PRIMARY KEY,
+            tag    TEXT NOT NULL,
+            error  TEXT,
+            value  BLOB NOT NULL
+        ) STRICT;
+        CREATE TABLE file_paths (
+            file     TEXT NOT NULL,
+            local_id INTEGER NOT NULL,
+            value    BLOB NOT NULL,
+            FOREIGN KEY(file) REFERENCES graphs(file)
+        ) STRICT;
+        CREATE TABLE root_paths (
+            file         TEXT NOT NULL,
+            symbol_stack TEXT NOT NULL,
+            value        BLOB NOT NULL,
+            FOREIGN KEY(file) REFERENCES graphs(file)
+        ) STRICT;
+        CREATE INDEX idx_graphs_file ON graphs(file);
+        CREATE INDEX idx_file_paths_local_id ON file_paths(file, local_id);
+        CREATE INDEX idx_root_paths_symbol_stack ON root_paths(symbol_stack);
+        INSERT INTO metadata (version) VALUES (6);
+        "#,
+    )
+    .unwrap();
+    let local_id = graph[reference].id().local_id();
+    conn.execute(
+        "INSERT INTO graphs (file, tag, value) VALUES ('test.py', 't1', ?)",
+        [bincode::encode_to_vec(&legacy, BINCODE_CONFIG).unwrap()],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO graphs (file, tag, error, value) VALUES ('broken.py', 'b1', 'failed', ?)",
+        [bincode::encode_to_vec(&empty, BINCODE_CONFIG).unwrap()],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO file_paths (file, local_id, value) VALUES ('test.py', ?, ?)",
+        (
+            local_id,
+            bincode::encode_to_vec(&path, BINCODE_CONFIG).unwrap(),
+        ),
+    )
+    .unwrap();
+    local_id
+}
+
+fn temp_database_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "stack-graphs-{}-{}.sqlite",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn can_migrate_database_from_oldest_version() {
+    let path = temp_database_path("migrate-v6");
+    let reference = create_v6_database(&path);
+    assert!(matches!(
+        SQLiteReader::open(&path),
+        Err(StorageError::IncorrectVersion(6))
+    ));
+
+    assert_eq!(6, migrate(&path, stack_graphs::storage::VERSION).unwrap());
+    assert_eq!(
+        stack_graphs::storage::VERSION,
+        database_version(&path).unwrap()
+    );
+
+    let mut reader = SQLiteReader::open(&path).unwrap();
+    assert_eq!(
+        FileStatus::Indexed,
+        reader.status_for_file("test.py", Some("t1")).unwrap()
+    );
+    assert_eq!(
+        FileStatus::Error("failed".to_string()),
+        reader.status_for_file::<&str>("broken.py", None).unwrap()
+    );
+    assert_eq!(1, find_complete_paths(&mut reader, "test.py", reference));
+
+    let mut writer = SQLiteWriter::open(&path).unwrap();
+    store_empty_file(&mut writer, "new.py", "n1");
+    assert_eq!(
+        FileStatus::Indexed,
+        writer.status_for_file("new.py", Some("n1")).unwrap()
+    );
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn migrations_can_stop_at_intermediate_versions() {
+    let path = temp_database_path("migrate-v9");
+    create_v6_database(&path);
+
+    assert_eq!(6, migrate(&path, 9).unwrap());
+    assert_eq!(9, database_version(&path).unwrap());
+    assert_eq!(9, migrate(&path, 9).unwrap());
+    assert_eq!(9, migrate(&path, stack_graphs::storage::VERSION).unwrap());
+    assert!(SQLiteReader::open(&path).is_ok());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn unsupported_migrations_are_rejected() {
+    let path = temp_database_path("migrate-unsupported");
+    SQLiteWriter::open(&path).unwrap();
+    let version = stack_graphs::storage::VERSION;
+
+    assert!(matches!(
+        migrate(&path, version - 1),
+        Err(StorageError::UnsupportedMigration(from, to)) if from == version && to == version - 1
+    ));
+    assert!(matches!(
+        migrate(&path, version + 1),
+        Err(StorageError::UnsupportedMigration(_, _))
+    ));
+    assert_eq!(version, migrate(&path, version).unwrap());
+    assert!(matches!(
+        migrate("/nonexistent/stack-graphs.sqlite", version),
+        Err(StorageError::MissingDatabase(_))
+    ));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[cfg(feature = "analysis")]
+/// Stores a reference to x in main, and a definition of x in lib on the given line.
+fn store_definition_on_line(writer: &mut SQLiteWriter, line: usize) {
+    store_file_with_path(writer, "main", "main1", |graph, file| {
+        let reference = create_push_symbol_node(graph, file, "x", true);
+        graph.source_info_mut(reference).span.start.line = 0;
+        vec![reference, StackGraph::root_node()]
+    });
+    store_file_with_path(writer, "lib", "lib1", |graph, file| {
+        let definition = create_pop_symbol_node(graph, file, "x", true);
+        graph.source_info_mut(definition).span.start.line = line;
+        vec![StackGraph::root_node(), definition]
+    });
+}
+
+#[cfg(feature = "analysis")]
+#[test]
+fn resolutions_are_compared_between_databases() {
+    let old_path = temp_database_path("compare-old");
+    let new_path = temp_database_path("compare-new");
+    store_definition_on_line(&mut SQLiteWriter::open(&old_path).unwrap(), 1);
+    store_definition_on_line(&mut SQLiteWriter::open(&new_path).unwrap(), 2);
+    let comparison = compare_databases(
+        &mut SQLiteReader::open(&old_path).unwrap(),
+        &mut SQLiteReader::open(&new_path).unwrap(),
+        &[Path::new("main")],
+        StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .unwrap();
+    assert_eq!(1, comparison.references);
+    assert_eq!(1, comparison.count(ResolutionChange::Changed));
+    assert_eq!(2, comparison.differences[0].old[0].line);
+    assert_eq!(3, comparison.differences[0].new[0].line);
+    let _ = std::fs::remove_file(&old_path);
+    let _ = std::fs::remove_file(&new_path);
+}
+
+#[test]
+fn readers_only_load_paths_of_entered_root_domain() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    for (file, domain) in [("npm-lib", "npm"), ("pypi-lib", "pypi")] {
+        store_file_with_path(&mut writer, file, "v1", |graph, file| {
+            let domain = graph.add_root_domain(file, domain);
+            vec![
+                StackGraph::root_node(),
+                domain.exit,
+                create_pop_symbol_node(graph, file, "x", true),
+            ]
+        });
+    }
+    // A module named like the root domain, which is exported to the root node itself
+    store_file_with_path(&mut writer, "npm-module", "v1", |graph, file| {
+        vec![
+            StackGraph::root_node(),
+            create_pop_symbol_node(graph, file, "npm", false),
+            create_pop_symbol_node(graph, file, "x", true),
+        ]
+    });
+    let reference = store_file_with_path(&mut writer, "main", "v1", |graph, file| {
+        let npm = graph.add_root_domain(file, "npm");
+        vec![
+            create_push_symbol_node(graph, file, "x", true),
+            npm.entry,
+            StackGraph::root_node(),
+        ]
+    });
+
+    let observer = Arc::new(RecordingObserver::default());
+    let mut reader = writer.into_reader().with_observer(observer.clone());
+    assert_eq!(1, find_complete_paths(&mut reader, "main", reference));
+
+    let mut files = observer.files.lock().unwrap().clone();
+    files.sort();
+    assert_eq!(vec!["main", "npm-lib"], files);
+}
+
+/// Builds a graph where main looks up a.b and other looks up a.c through the root, and lib
+/// defines both, behind an edge out of the root whose key is a.b.
+fn root_edge_key_graph() -> StackGraph {
+    let mut graph = StackGraph::new();
+    let root = StackGraph::root_node();
+    for (name, symbol) in [("main", "b"), ("other", "c")] {
+        let file = graph.add_file(name).unwrap();
+        let reference = create_push_symbol_node(&mut graph, file, symbol, true);
+        let push_a = create_push_symbol_node(&mut graph, file, "a", false);
+        graph.add_edge(reference, push_a, 0);
+        graph.add_edge(push_a, root, 0);
+    }
+    let file = graph.add_file("lib").unwrap();
+    let pop_a = create_pop_symbol_node(&mut graph, file, "a", false);
+    let def_b = create_pop_symbol_node(&mut graph, file, "b", true);
+    let def_c = create_pop_symbol_node(&mut graph, file, "c", true);
+    graph.add_edge(root, pop_a, 0);
+    graph.add_edge(pop_a, def_b, 0);
+    graph.add_edge(pop_a, def_c, 0);
+    let key = [graph.add_symbol("a"), graph.add_symbol("b")];
+    graph.set_root_edge_key(root, pop_a, &key);
+    graph
+}
+
+/// Returns the references in the given files.
+fn references_in_files(graph: &StackGraph, files: &[&str]) -> Vec<Handle<Node>> {
+    graph
+        .iter_nodes()
+        .filter(|node| {
+            graph[*node].is_reference()
+                && files
+                    .iter()
+                    .any(|file| graph[*node].is_in_file(graph.get_file(file).unwrap()))
+        })
+        .collect()
+}
+
+/// Returns the start and end node of a path, as `file:local_id` strings.
+fn path_ends(graph: &StackGraph, path: &PartialPath) -> (String, String) {
+    let node_id = |node: Handle<Node>| {
+        let id = graph[node].id();
+        format!("{}:{}", graph[id.file().unwrap()].name(), id.local_id())
+    };
+    (node_id(path.start_node), node_id(path.end_node))
+}
+
+#[test]
+fn root_edge_keys_agree_between_graph_database_and_storage() {
+    let graph = root_edge_key_graph();
+    let files = ["main", "other"];
+
+    let mut partials = PartialPaths::new();
+    let mut in_graph = BTreeSet::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        references_in_files(&graph, &files),
+        StitcherConfig::default(),
+        &NoCancellation,
+        |graph, _, path| {
+            in_graph.insert(path_ends(graph, path));
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        BTreeSet::from([("main:0".to_string(), "lib:1".to_string())]),
+        in_graph,
+    );
+
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    for file in graph.iter_files() {
+        let mut paths = Vec::new();
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, path| paths.push(path.clone()),
+        )
+        .unwrap();
+        writer
+            .store_result_for_file(&graph, file, "tag", &mut partials, &paths)
+            .unwrap();
+        for path in paths {
+            db.add_partial_path(&graph, &mut partials, path);
+        }
+    }
+
+    let mut in_database = BTreeSet::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+        references_in_files(&graph, &files),
+        StitcherConfig::default(),
+        &NoCancellation,
+        |graph, _, path| {
+            in_database.insert(path_ends(graph, path));
+        },
+    )
+    .unwrap();
+    assert_eq!(in_graph, in_database);
+
+    let mut reader = writer.into_reader();
+    for file in files {
+        reader.load_graph_for_file(file).unwrap();
+    }
+    let (graph, _, _) = reader.get();
+    let references = references_in_files(graph, &files);
+    let mut in_storage = BTreeSet::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut reader,
+        references,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |graph, _, path| {
+            in_storage.insert(path_ends(graph, path));
+        },
+    )
+    .unwrap();
+    assert_eq!(in_graph, in_storage);
+}
+
+#[test]
+fn readers_load_candidates_for_spilled_paths() {
+    let graph: StackGraph = crate::test_graphs::class_field_through_function_parameter::new();
+    let mut partials = PartialPaths::new();
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    for file in graph.iter_files() {
+        let mut paths = Vec::new();
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, path| paths.push(path.clone()),
+        )
+        .unwrap();
+        writer
+            .store_result_for_file(&graph, file, "tag", &mut partials, &paths)
+            .unwrap();
+    }
+
+    let mut reader = writer.into_reader();
+    let mut find_paths = |config: StitcherConfig| {
+        reader.clear();
+        reader.load_graph_for_file("main.py").unwrap();
+        let (graph, _, _) = reader.get();
+        let references = references_in_files(graph, &["main.py"]);
+        let mut paths = BTreeSet::new();
+        let stats = ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut reader,
+            references,
+            config,
+            &NoCancellation,
+            |graph, _, path| {
+                paths.insert(path_ends(graph, path));
+            },
+        )
+        .unwrap();
+        (paths, stats)
+    };
+    let (expected, _) = find_paths(StitcherConfig::default());
+    assert!(!expected.is_empty());
+    let (paths, stats) = find_paths(
+        StitcherConfig::default()
+            .with_collect_stats(true)
+            .with_max_queued_paths(Some(1))
+            .with_spill_queue_to_disk(true),
+    );
+    assert_eq!(expected, paths);
+    assert!(stats.spilled_paths > 0);
+    assert_eq!(0, stats.dropped_paths);
+}
@@ -0,0 +1,140 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use itertools::Itertools;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::trace::StitchingTrace;
+use stack_graphs::trace::TraceCandidate;
+use stack_graphs::trace::TraceEvent;
+use stack_graphs::trace::TraceOutcome;
+use stack_graphs::trace::TraceRecording;
+use stack_graphs::trace::TraceReplay;
+use stack_graphs::NoCancellation;
+
+use crate::util::create_pop_symbol_node;
+use crate::util::create_push_symbol_node;
+
+fn record_x_y_trace() -> (StackGraph, StitchingTrace) {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let ref_x = create_push_symbol_node(&mut graph, file, "x", true);
+    let def_x = create_pop_symbol_node(&mut graph, file, "x", true);
+    let def_y = create_pop_symbol_node(&mut graph, file, "y", true);
+    graph.add_edge(ref_x, def_x, 0);
+    graph.add_edge(ref_x, def_y, 0);
+
+    let stats = ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![ref_x],
+        StitcherConfig::default().with_collect_trace(true),
+        &NoCancellation,
+        |_, _, _| {},
+    )
+    .unwrap();
+    let trace = stats.trace.expect("trace should be recorded");
+    (graph, trace)
+}
+
+#[test]
+fn trace_records_accepted_and_rejected_candidates() {
+    let (_, trace) = record_x_y_trace();
+
+    match &trace.events[0] {
+        TraceEvent::Phase { paths } => assert_eq!(1, paths.len()),
+        event => panic!("expected phase, got {:?}", event),
+    }
+    let outcomes = trace
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            TraceEvent::Candidate {
+                candidate, outcome, ..
+            } => match candidate.as_ref() {
+                TraceCandidate::Edge { sink, .. } => Some((sink.local_id, outcome.as_ref())),
+                _ => None,
+            },
+            _ => None,
+        })
+        .sorted_by_key(|(sink, _)| *sink)
+        .collect_vec();
+    assert_eq!(2, outcomes.len());
+    assert!(matches!(outcomes[0].1, TraceOutcome::Accepted { .. }));
+    assert_eq!(
+        &TraceOutcome::Invalid {
+            reason: "IncorrectPoppedSymbol".to_string()
+        },
+        outcomes[1].1
+    );
+}
+
+#[test]
+fn trace_is_not_recorded_by_default() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+    let ref_x = create_push_symbol_node(&mut graph, file, "x", true);
+
+    let stats = ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![ref_x],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, _| {},
+    )
+    .unwrap();
+    assert!(stats.trace.is_none());
+}
+
+#[test]
+fn replayed_trace_agrees_with_recording() {
+    let (graph, trace) = record_x_y_trace();
+    let event_count = trace.events.len();
+    let recording = TraceRecording::new(&graph, trace);
+    assert_eq!(1, recording.graph.files.data.len());
+
+    let mut replay = TraceReplay::new(recording).unwrap();
+    let mut steps = Vec::new();
+    while let Some(step) = replay.step() {
+        steps.push(step.unwrap());
+    }
+    assert_eq!(event_count, steps.len());
+    assert_eq!(2, steps.iter().filter(|s| s.replayed.is_some()).count());
+    assert!(steps.iter().all(|s| !s.diverges()));
+}
+
+#[test]
+fn replay_reports_diverging_outcomes() {
+    let (graph, mut trace) = record_x_y_trace();
+    let index = trace
+        .events
+        .iter()
+        .position(|e| {
+            matches!(e, TraceEvent::Candidate { outcome, .. }
+                if matches!(outcome.as_ref(), TraceOutcome::Accepted { .. }))
+        })
+        .unwrap();
+    if let TraceEvent::Candidate { outcome, .. } = &mut trace.events[index] {
+        **outcome = TraceOutcome::Invalid {
+            reason: "IncorrectPoppedSymbol".to_string(),
+        };
+    }
+
+    let mut replay = TraceReplay::new(TraceRecording::new(&graph, trace)).unwrap();
+    let divergences = replay.divergences().unwrap();
+    assert_eq!(1, divergences.len());
+    assert_eq!(index, divergences[0].index);
+    assert!(matches!(
+        divergences[0].replayed,
+        Some(TraceOutcome::Accepted { .. })
+    ));
+}
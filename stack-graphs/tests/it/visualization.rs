@@ -0,0 +1,133 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use std::collections::HashMap;
+
+use lsp_positions::Span;
+use lsp_positions::SpanCalculator;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::serde::NoFilter;
+use stack_graphs::stitching::Database;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
+
+use crate::test_graphs::CreateStackGraph;
+
+#[test]
+fn dot_output_contains_nodes_and_edges() {
+    let mut graph = StackGraph::new();
+    let file = graph.file("test.py");
+    let sym_x = graph.symbol("x");
+    let ref_x = graph.reference(file, 1, sym_x);
+    let def_x = graph.definition(file, 2, sym_x);
+    graph.edge(ref_x, def_x);
+
+    let mut partials = PartialPaths::new();
+    let dot = graph.to_dot_string("test", &mut partials, &Database::new(), &NoFilter);
+    assert!(dot.starts_with("digraph \"test\" {"));
+    assert!(dot.contains(&format!("n{} -> n{};", ref_x.as_u32(), def_x.as_u32())));
+}
+
+#[test]
+fn dot_output_contains_source_excerpts() {
+    let mut graph = StackGraph::new();
+    let file = graph.file("test.py");
+    let sym_x = graph.symbol("x");
+    let ref_x = graph.reference(file, 1, sym_x);
+    let def_x = graph.definition(file, 2, sym_x);
+    graph.edge(ref_x, def_x);
+    let source = "x = 1\nprint(x)\n";
+    let mut calculator = SpanCalculator::new(source);
+    graph.source_info_mut(ref_x).span = Span {
+        start: calculator.for_utf8_offset_in_string(12),
+        end: calculator.for_utf8_offset_in_string(13),
+    };
+
+    let sources = HashMap::from([("test.py".to_string(), source.to_string())]);
+    let mut partials = PartialPaths::new();
+    let dot = graph.to_dot_string_with_source(
+        "test",
+        &mut partials,
+        &Database::new(),
+        &NoFilter,
+        &sources,
+    );
+    assert!(dot.contains("print(x)"));
+    assert!(!dot.contains("x = 1"));
+}
+
+#[test]
+fn dot_output_highlights_path_edges() {
+    let mut graph = StackGraph::new();
+    let file = graph.file("test.py");
+    let sym_x = graph.symbol("x");
+    let ref_x = graph.reference(file, 1, sym_x);
+    let def_x = graph.definition(file, 2, sym_x);
+    let unused = graph.definition(file, 3, sym_x);
+    graph.edge(ref_x, def_x);
+    graph.edge(unused, def_x);
+
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![ref_x],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |g, ps, p| {
+            db.add_partial_path(g, ps, p.clone());
+        },
+    )
+    .expect("should not be cancelled");
+
+    let dot = graph.to_dot_string("test", &mut partials, &db, &NoFilter);
+    assert!(dot.contains(&format!(
+        "n{} -> n{} [color=red, penwidth=2];",
+        ref_x.as_u32(),
+        def_x.as_u32()
+    )));
+    assert!(dot.contains(&format!("n{} -> n{};", unused.as_u32(), def_x.as_u32())));
+}
+
+#[test]
+fn html_output_contains_trace() {
+    let mut graph = StackGraph::new();
+    let file = graph.file("test.py");
+    let sym_x = graph.symbol("x");
+    let ref_x = graph.reference(file, 1, sym_x);
+    let def_x = graph.definition(file, 2, sym_x);
+    graph.edge(ref_x, def_x);
+
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    let stats = ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![ref_x],
+        StitcherConfig::default().with_collect_trace(true),
+        &NoCancellation,
+        |g, ps, p| {
+            db.add_partial_path(g, ps, p.clone());
+        },
+    )
+    .expect("should not be cancelled");
+    let trace = stats.trace.expect("trace should be recorded");
+
+    let html = graph
+        .to_html_string_with_trace("test", &mut partials, &mut db, &NoFilter, &trace)
+        .unwrap();
+    assert!(html.contains(&format!(
+        "trace: {}",
+        serde_json::to_string(&trace).unwrap()
+    )));
+    let html = graph
+        .to_html_string("test", &mut partials, &mut db, &NoFilter)
+        .unwrap();
+    assert!(html.contains("trace: null"));
+}
@@ -57,18 +57,30 @@
 //! }
 //! ```
 
+pub mod build;
 pub mod clean;
+pub mod compare;
+pub mod config;
+pub mod coverage;
 pub mod database;
+pub mod edges;
+pub mod evaluate;
+pub mod export;
 pub mod index;
 pub mod init;
 pub mod load;
 #[cfg(feature = "lsp")]
 pub mod lsp;
 pub mod r#match;
+pub mod output;
 pub mod parse;
 pub mod query;
+pub mod replay;
+pub mod schema;
+pub mod stats;
 pub mod status;
 pub mod test;
+pub mod upgrade;
 pub mod util;
 pub mod visualize;
 
@@ -77,8 +89,15 @@ pub mod path_loading {
 
     use clap::Subcommand;
 
+    use crate::cli::build::BuildArgs;
     use crate::cli::clean::CleanArgs;
+    use crate::cli::compare::CompareArgs;
+    use crate::cli::config::ConfigArgs;
+    use crate::cli::coverage::CoverageArgs;
     use crate::cli::database::DatabaseArgs;
+    use crate::cli::edges::EdgesArgs;
+    use crate::cli::evaluate::EvaluateArgs;
+    use crate::cli::export::ExportArgs;
     use crate::cli::index::IndexArgs;
     use crate::cli::init::InitArgs;
     use crate::cli::load::PathLoaderArgs;
@@ -87,13 +106,25 @@ pub mod path_loading {
     use crate::cli::parse::ParseArgs;
     use crate::cli::query::QueryArgs;
     use crate::cli::r#match::MatchArgs;
+    use crate::cli::replay::ReplayArgs;
+    use crate::cli::schema::SchemaArgs;
+    use crate::cli::stats::StatsArgs;
     use crate::cli::status::StatusArgs;
+    use crate::cli::test::exit_on_test_failure;
     use crate::cli::test::TestArgs;
+    use crate::cli::upgrade::UpgradeArgs;
     use crate::cli::visualize::VisualizeArgs;
 
     #[derive(Subcommand)]
     pub enum Subcommands {
+        Build(Build),
         Clean(Clean),
+        Compare(Compare),
+        Coverage(Coverage),
+        Db(Db),
+        Edges(Edges),
+        Evaluate(Evaluate),
+        Export(Export),
         Index(Index),
         Init(Init),
         #[cfg(feature = "lsp")]
@@ -101,6 +132,9 @@ pub mod path_loading {
         Match(Match),
         Parse(Parse),
         Query(Query),
+        Replay(Replay),
+        Schema(Schema),
+        Stats(Stats),
         Status(Status),
         Test(Test),
         Visualize(Visualize),
@@ -109,7 +143,14 @@ pub mod path_loading {
     impl Subcommands {
         pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
             match self {
+                Self::Build(cmd) => cmd.run(),
                 Self::Clean(cmd) => cmd.run(default_db_path),
+                Self::Compare(cmd) => cmd.run(default_db_path),
+                Self::Coverage(cmd) => cmd.run(default_db_path),
+                Self::Db(cmd) => cmd.run(default_db_path),
+                Self::Edges(cmd) => cmd.run(default_db_path),
+                Self::Evaluate(cmd) => cmd.run(default_db_path),
+                Self::Export(cmd) => cmd.run(default_db_path),
                 Self::Index(cmd) => cmd.run(default_db_path),
                 Self::Init(cmd) => cmd.run(),
                 #[cfg(feature = "lsp")]
@@ -117,6 +158,9 @@ pub mod path_loading {
                 Self::Match(cmd) => cmd.run(),
                 Self::Parse(cmd) => cmd.run(),
                 Self::Query(cmd) => cmd.run(default_db_path),
+                Self::Replay(cmd) => cmd.run(),
+                Self::Schema(cmd) => cmd.run(),
+                Self::Stats(cmd) => cmd.run(default_db_path),
                 Self::Status(cmd) => cmd.run(default_db_path),
                 Self::Test(cmd) => cmd.run(),
                 Self::Visualize(cmd) => cmd.run(default_db_path),
@@ -124,6 +168,25 @@ pub mod path_loading {
         }
     }
 
+    /// Build the stack graph for a single file, and show its nodes, edges, and partial paths.
+    #[derive(clap::Parser)]
+    pub struct Build {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
+        #[clap(flatten)]
+        load_args: PathLoaderArgs,
+        #[clap(flatten)]
+        build_args: BuildArgs,
+    }
+
+    impl Build {
+        pub fn run(self) -> anyhow::Result<()> {
+            let config = self.config_args.get()?;
+            let loader = self.load_args.get_with_config(&config)?;
+            self.build_args.run(loader)
+        }
+    }
+
     /// Clean the indexing database.
     #[derive(clap::Parser)]
     pub struct Clean {
@@ -140,9 +203,91 @@ pub mod path_loading {
         }
     }
 
+    /// Compare the definitions found for indexed source files against a baseline database.
+    #[derive(clap::Parser)]
+    pub struct Compare {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        compare_args: CompareArgs,
+    }
+
+    impl Compare {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.compare_args.run(&db_path)
+        }
+    }
+
+    /// Report the share of resolved references in indexed source files.
+    #[derive(clap::Parser)]
+    pub struct Coverage {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        coverage_args: CoverageArgs,
+    }
+
+    impl Coverage {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.coverage_args.run(&db_path)
+        }
+    }
+
+    /// Export the definitions that all references in the database resolve to, as an edge list.
+    #[derive(clap::Parser)]
+    pub struct Edges {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        edges_args: EdgesArgs,
+    }
+
+    impl Edges {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.edges_args.run(&db_path)
+        }
+    }
+
+    /// Score definitions found for indexed source files against a ground truth file.
+    #[derive(clap::Parser)]
+    pub struct Evaluate {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        evaluate_args: EvaluateArgs,
+    }
+
+    impl Evaluate {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.evaluate_args.run(&db_path)
+        }
+    }
+
+    /// Export indexed source files to DOT, JSON, LSIF, or SCIP.
+    #[derive(clap::Parser)]
+    pub struct Export {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        export_args: ExportArgs,
+    }
+
+    impl Export {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.export_args.run(&db_path)
+        }
+    }
+
     /// Index source files into the database.
     #[derive(clap::Parser)]
     pub struct Index {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
         #[clap(flatten)]
         load_args: PathLoaderArgs,
         #[clap(flatten)]
@@ -153,9 +298,10 @@ pub mod path_loading {
 
     impl Index {
         pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
-            let loader = self.load_args.get()?;
+            let config = self.config_args.get()?;
+            let loader = self.load_args.get_with_config(&config)?;
             let db_path = self.db_args.get_or(default_db_path);
-            self.index_args.run(&db_path, loader)
+            self.index_args.with_config(&config).run(&db_path, loader)
         }
     }
 
@@ -176,6 +322,8 @@ pub mod path_loading {
     #[cfg(feature = "lsp")]
     #[derive(clap::Parser)]
     pub struct Lsp {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
         #[clap(flatten)]
         load_args: PathLoaderArgs,
         #[clap(flatten)]
@@ -187,15 +335,18 @@ pub mod path_loading {
     #[cfg(feature = "lsp")]
     impl Lsp {
         pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
-            let loader = self.load_args.get()?;
+            let config = self.config_args.get()?;
+            let loader = self.load_args.get_with_config(&config)?;
             let db_path = self.db_args.get_or(default_db_path);
-            self.lsp_args.run(db_path, loader)
+            self.lsp_args.with_config(&config).run(db_path, loader)
         }
     }
 
     /// Match stanza queries against a source file.
     #[derive(clap::Parser)]
     pub struct Match {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
         #[clap(flatten)]
         load_args: PathLoaderArgs,
         #[clap(flatten)]
@@ -204,7 +355,8 @@ pub mod path_loading {
 
     impl Match {
         pub fn run(self) -> anyhow::Result<()> {
-            let loader = self.load_args.get()?;
+            let config = self.config_args.get()?;
+            let loader = self.load_args.get_with_config(&config)?;
             self.match_args.run(loader)
         }
     }
@@ -212,6 +364,8 @@ pub mod path_loading {
     /// Parse a source file and show the parse tree.
     #[derive(clap::Parser)]
     pub struct Parse {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
         #[clap(flatten)]
         load_args: PathLoaderArgs,
         #[clap(flatten)]
@@ -220,7 +374,8 @@ pub mod path_loading {
 
     impl Parse {
         pub fn run(self) -> anyhow::Result<()> {
-            let loader = self.load_args.get()?;
+            let config = self.config_args.get()?;
+            let loader = self.load_args.get_with_config(&config)?;
             self.parse_args.run(loader)
         }
     }
@@ -228,6 +383,8 @@ pub mod path_loading {
     /// Query the database to resolve references.
     #[derive(clap::Parser)]
     pub struct Query {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
         #[clap(flatten)]
         db_args: DatabaseArgs,
         #[clap(flatten)]
@@ -235,9 +392,52 @@ pub mod path_loading {
     }
 
     impl Query {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let config = self.config_args.get()?;
+            let db_path = self.db_args.get_or(default_db_path);
+            self.query_args.with_config(&config).run(&db_path)
+        }
+    }
+
+    /// Replay a recorded path stitching trace.
+    #[derive(clap::Parser)]
+    pub struct Replay {
+        #[clap(flatten)]
+        replay_args: ReplayArgs,
+    }
+
+    impl Replay {
+        pub fn run(self) -> anyhow::Result<()> {
+            self.replay_args.run()
+        }
+    }
+
+    /// Print the JSON Schema of a serialization format.
+    #[derive(clap::Parser)]
+    pub struct Schema {
+        #[clap(flatten)]
+        schema_args: SchemaArgs,
+    }
+
+    impl Schema {
+        pub fn run(self) -> anyhow::Result<()> {
+            self.schema_args.run()
+        }
+    }
+
+    /// Show aggregate statistics for the indexing database.
+    #[derive(clap::Parser)]
+    pub struct Stats {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        stats_args: StatsArgs,
+    }
+
+    impl Stats {
         pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
             let db_path = self.db_args.get_or(default_db_path);
-            self.query_args.run(&db_path)
+            self.stats_args.run(&db_path)
         }
     }
 
@@ -260,6 +460,8 @@ pub mod path_loading {
     /// Run test files and show results.
     #[derive(clap::Parser)]
     pub struct Test {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
         #[clap(flatten)]
         load_args: PathLoaderArgs,
         #[clap(flatten)]
@@ -268,8 +470,45 @@ pub mod path_loading {
 
     impl Test {
         pub fn run(self) -> anyhow::Result<()> {
-            let loader = self.load_args.get()?;
-            self.test_args.run(loader)
+            let config = self.config_args.get()?;
+            let loader = self.load_args.get_with_config(&config)?;
+            exit_on_test_failure(self.test_args.with_config(&config).run(loader))
+        }
+    }
+
+    /// Manage the indexing database.
+    #[derive(clap::Parser)]
+    pub struct Db {
+        #[clap(subcommand)]
+        command: DbSubcommands,
+    }
+
+    #[derive(Subcommand)]
+    pub enum DbSubcommands {
+        Upgrade(Upgrade),
+    }
+
+    impl Db {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            match self.command {
+                DbSubcommands::Upgrade(cmd) => cmd.run(default_db_path),
+            }
+        }
+    }
+
+    /// Upgrade the indexing database to the version used by this program.
+    #[derive(clap::Parser)]
+    pub struct Upgrade {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        upgrade_args: UpgradeArgs,
+    }
+
+    impl Upgrade {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.upgrade_args.run(&db_path)
         }
     }
 
@@ -295,8 +534,15 @@ pub mod provided_languages {
 
     use clap::Subcommand;
 
+    use crate::cli::build::BuildArgs;
     use crate::cli::clean::CleanArgs;
+    use crate::cli::compare::CompareArgs;
+    use crate::cli::config::ConfigArgs;
+    use crate::cli::coverage::CoverageArgs;
     use crate::cli::database::DatabaseArgs;
+    use crate::cli::edges::EdgesArgs;
+    use crate::cli::evaluate::EvaluateArgs;
+    use crate::cli::export::ExportArgs;
     use crate::cli::index::IndexArgs;
     use crate::cli::init::InitArgs;
     use crate::cli::load::LanguageConfigurationsLoaderArgs;
@@ -305,14 +551,26 @@ pub mod provided_languages {
     use crate::cli::parse::ParseArgs;
     use crate::cli::query::QueryArgs;
     use crate::cli::r#match::MatchArgs;
+    use crate::cli::replay::ReplayArgs;
+    use crate::cli::schema::SchemaArgs;
+    use crate::cli::stats::StatsArgs;
     use crate::cli::status::StatusArgs;
+    use crate::cli::test::exit_on_test_failure;
     use crate::cli::test::TestArgs;
+    use crate::cli::upgrade::UpgradeArgs;
     use crate::cli::visualize::VisualizeArgs;
     use crate::loader::LanguageConfiguration;
 
     #[derive(Subcommand)]
     pub enum Subcommands {
+        Build(Build),
         Clean(Clean),
+        Compare(Compare),
+        Coverage(Coverage),
+        Db(Db),
+        Edges(Edges),
+        Evaluate(Evaluate),
+        Export(Export),
         Index(Index),
         Init(Init),
         #[cfg(feature = "lsp")]
@@ -320,6 +578,9 @@ pub mod provided_languages {
         Match(Match),
         Parse(Parse),
         Query(Query),
+        Replay(Replay),
+        Schema(Schema),
+        Stats(Stats),
         Status(Status),
         Test(Test),
         Visualize(Visualize),
@@ -332,7 +593,14 @@ pub mod provided_languages {
             configurations: Vec<LanguageConfiguration>,
         ) -> anyhow::Result<()> {
             match self {
+                Self::Build(cmd) => cmd.run(configurations),
                 Self::Clean(cmd) => cmd.run(default_db_path),
+                Self::Compare(cmd) => cmd.run(default_db_path),
+                Self::Coverage(cmd) => cmd.run(default_db_path),
+                Self::Db(cmd) => cmd.run(default_db_path),
+                Self::Edges(cmd) => cmd.run(default_db_path),
+                Self::Evaluate(cmd) => cmd.run(default_db_path),
+                Self::Export(cmd) => cmd.run(default_db_path),
                 Self::Index(cmd) => cmd.run(default_db_path, configurations),
                 Self::Init(cmd) => cmd.run(),
                 #[cfg(feature = "lsp")]
@@ -340,6 +608,9 @@ pub mod provided_languages {
                 Self::Match(cmd) => cmd.run(configurations),
                 Self::Parse(cmd) => cmd.run(configurations),
                 Self::Query(cmd) => cmd.run(default_db_path),
+                Self::Replay(cmd) => cmd.run(),
+                Self::Schema(cmd) => cmd.run(),
+                Self::Stats(cmd) => cmd.run(default_db_path),
                 Self::Status(cmd) => cmd.run(default_db_path),
                 Self::Test(cmd) => cmd.run(configurations),
                 Self::Visualize(cmd) => cmd.run(default_db_path),
@@ -347,6 +618,25 @@ pub mod provided_languages {
         }
     }
 
+    /// Build the stack graph for a single file, and show its nodes, edges, and partial paths.
+    #[derive(clap::Parser)]
+    pub struct Build {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
+        #[clap(flatten)]
+        load_args: LanguageConfigurationsLoaderArgs,
+        #[clap(flatten)]
+        build_args: BuildArgs,
+    }
+
+    impl Build {
+        pub fn run(self, configurations: Vec<LanguageConfiguration>) -> anyhow::Result<()> {
+            let config = self.config_args.get()?;
+            let loader = self.load_args.get_with_config(configurations, &config)?;
+            self.build_args.run(loader)
+        }
+    }
+
     /// Clean the indexing database.
     #[derive(clap::Parser)]
     pub struct Clean {
@@ -363,9 +653,91 @@ pub mod provided_languages {
         }
     }
 
+    /// Compare the definitions found for indexed source files against a baseline database.
+    #[derive(clap::Parser)]
+    pub struct Compare {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        compare_args: CompareArgs,
+    }
+
+    impl Compare {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.compare_args.run(&db_path)
+        }
+    }
+
+    /// Report the share of resolved references in indexed source files.
+    #[derive(clap::Parser)]
+    pub struct Coverage {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        coverage_args: CoverageArgs,
+    }
+
+    impl Coverage {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.coverage_args.run(&db_path)
+        }
+    }
+
+    /// Export the definitions that all references in the database resolve to, as an edge list.
+    #[derive(clap::Parser)]
+    pub struct Edges {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        edges_args: EdgesArgs,
+    }
+
+    impl Edges {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.edges_args.run(&db_path)
+        }
+    }
+
+    /// Score definitions found for indexed source files against a ground truth file.
+    #[derive(clap::Parser)]
+    pub struct Evaluate {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        evaluate_args: EvaluateArgs,
+    }
+
+    impl Evaluate {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.evaluate_args.run(&db_path)
+        }
+    }
+
+    /// Export indexed source files to DOT, JSON, LSIF, or SCIP.
+    #[derive(clap::Parser)]
+    pub struct Export {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        export_args: ExportArgs,
+    }
+
+    impl Export {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.export_args.run(&db_path)
+        }
+    }
+
     /// Index source files into the database.
     #[derive(clap::Parser)]
     pub struct Index {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
         #[clap(flatten)]
         load_args: LanguageConfigurationsLoaderArgs,
         #[clap(flatten)]
@@ -380,9 +752,10 @@ pub mod provided_languages {
             default_db_path: PathBuf,
             configurations: Vec<LanguageConfiguration>,
         ) -> anyhow::Result<()> {
-            let loader = self.load_args.get(configurations)?;
+            let config = self.config_args.get()?;
+            let loader = self.load_args.get_with_config(configurations, &config)?;
             let db_path = self.db_args.get_or(default_db_path);
-            self.index_args.run(&db_path, loader)
+            self.index_args.with_config(&config).run(&db_path, loader)
         }
     }
 
@@ -403,6 +776,8 @@ pub mod provided_languages {
     #[cfg(feature = "lsp")]
     #[derive(clap::Parser)]
     pub struct Lsp {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
         #[clap(flatten)]
         load_args: LanguageConfigurationsLoaderArgs,
         #[clap(flatten)]
@@ -418,15 +793,18 @@ pub mod provided_languages {
             default_db_path: PathBuf,
             configurations: Vec<LanguageConfiguration>,
         ) -> anyhow::Result<()> {
-            let loader = self.load_args.get(configurations)?;
+            let config = self.config_args.get()?;
+            let loader = self.load_args.get_with_config(configurations, &config)?;
             let db_path = self.db_args.get_or(default_db_path);
-            self.lsp_args.run(db_path, loader)
+            self.lsp_args.with_config(&config).run(db_path, loader)
         }
     }
 
     /// Match stanza queries against a source file.
     #[derive(clap::Parser)]
     pub struct Match {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
         #[clap(flatten)]
         load_args: LanguageConfigurationsLoaderArgs,
         #[clap(flatten)]
@@ -435,7 +813,8 @@ pub mod provided_languages {
 
     impl Match {
         pub fn run(self, configurations: Vec<LanguageConfiguration>) -> anyhow::Result<()> {
-            let loader = self.load_args.get(configurations)?;
+            let config = self.config_args.get()?;
+            let loader = self.load_args.get_with_config(configurations, &config)?;
             self.match_args.run(loader)
         }
     }
@@ -443,6 +822,8 @@ pub mod provided_languages {
     /// Parse a source file and show the parse tree.
     #[derive(clap::Parser)]
     pub struct Parse {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
         #[clap(flatten)]
         load_args: LanguageConfigurationsLoaderArgs,
         #[clap(flatten)]
@@ -451,7 +832,8 @@ pub mod provided_languages {
 
     impl Parse {
         pub fn run(self, configurations: Vec<LanguageConfiguration>) -> anyhow::Result<()> {
-            let loader = self.load_args.get(configurations)?;
+            let config = self.config_args.get()?;
+            let loader = self.load_args.get_with_config(configurations, &config)?;
             self.parse_args.run(loader)
         }
     }
@@ -459,6 +841,8 @@ pub mod provided_languages {
     /// Query the database to resolve references.
     #[derive(clap::Parser)]
     pub struct Query {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
         #[clap(flatten)]
         db_args: DatabaseArgs,
         #[clap(flatten)]
@@ -466,9 +850,52 @@ pub mod provided_languages {
     }
 
     impl Query {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let config = self.config_args.get()?;
+            let db_path = self.db_args.get_or(default_db_path);
+            self.query_args.with_config(&config).run(&db_path)
+        }
+    }
+
+    /// Replay a recorded path stitching trace.
+    #[derive(clap::Parser)]
+    pub struct Replay {
+        #[clap(flatten)]
+        replay_args: ReplayArgs,
+    }
+
+    impl Replay {
+        pub fn run(self) -> anyhow::Result<()> {
+            self.replay_args.run()
+        }
+    }
+
+    /// Print the JSON Schema of a serialization format.
+    #[derive(clap::Parser)]
+    pub struct Schema {
+        #[clap(flatten)]
+        schema_args: SchemaArgs,
+    }
+
+    impl Schema {
+        pub fn run(self) -> anyhow::Result<()> {
+            self.schema_args.run()
+        }
+    }
+
+    /// Show aggregate statistics for the indexing database.
+    #[derive(clap::Parser)]
+    pub struct Stats {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        stats_args: StatsArgs,
+    }
+
+    impl Stats {
         pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
             let db_path = self.db_args.get_or(default_db_path);
-            self.query_args.run(&db_path)
+            self.stats_args.run(&db_path)
         }
     }
 
@@ -491,6 +918,8 @@ pub mod provided_languages {
     /// Run test files and show results.
     #[derive(clap::Parser)]
     pub struct Test {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
         #[clap(flatten)]
         load_args: LanguageConfigurationsLoaderArgs,
         #[clap(flatten)]
@@ -499,8 +928,45 @@ pub mod provided_languages {
 
     impl Test {
         pub fn run(self, configurations: Vec<LanguageConfiguration>) -> anyhow::Result<()> {
-            let loader = self.load_args.get(configurations)?;
-            self.test_args.run(loader)
+            let config = self.config_args.get()?;
+            let loader = self.load_args.get_with_config(configurations, &config)?;
+            exit_on_test_failure(self.test_args.with_config(&config).run(loader))
+        }
+    }
+
+    /// Manage the indexing database.
+    #[derive(clap::Parser)]
+    pub struct Db {
+        #[clap(subcommand)]
+        command: DbSubcommands,
+    }
+
+    #[derive(Subcommand)]
+    pub enum DbSubcommands {
+        Upgrade(Upgrade),
+    }
+
+    impl Db {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            match self.command {
+                DbSubcommands::Upgrade(cmd) => cmd.run(default_db_path),
+            }
+        }
+    }
+
+    /// Upgrade the indexing database to the version used by this program.
+    #[derive(clap::Parser)]
+    pub struct Upgrade {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        upgrade_args: UpgradeArgs,
+    }
+
+    impl Upgrade {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.upgrade_args.run(&db_path)
         }
     }
 
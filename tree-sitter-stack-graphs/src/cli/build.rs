@@ -0,0 +1,232 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use anyhow::anyhow;
+use clap::Args;
+use clap::ValueEnum;
+use clap::ValueHint;
+use colored::Colorize;
+use stack_graphs::events::GraphEventConsumer;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::serde::NoFilter;
+use stack_graphs::stitching::Database;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::StitcherConfig;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::cli::index::Indexer;
+use crate::cli::util::ExistingPathBufValueParser;
+use crate::loader::FileReader;
+use crate::loader::Loader;
+use crate::NoCancellation;
+
+/// Build the stack graph for a single file
+#[derive(Args)]
+#[clap(after_help = r#"INPUT FORMATS:
+    The input is a source file by default, for which the stack graph is built using
+    the TSG rules of its language. Alternatively, the input can be a stack graph in
+    the JSON format written by `test --save-graph`, or a stream of graph events in the
+    binary format documented in the `stack_graphs::events` module, which is useful for
+    indexers that are not based on TSG rules.
+"#)]
+pub struct BuildArgs {
+    /// Input file path.
+    #[clap(
+        value_name = "INPUT_PATH",
+        required = true,
+        value_hint = ValueHint::AnyPath,
+        value_parser = ExistingPathBufValueParser,
+    )]
+    pub input_path: PathBuf,
+
+    /// Format of the input file.
+    #[clap(long, value_enum, default_value_t = InputFormat::Source)]
+    pub input_format: InputFormat,
+
+    /// Do not compute and show the partial paths of the graph.
+    #[clap(long)]
+    pub no_paths: bool,
+
+    /// Save an HTML visualization of the graph and its partial paths.
+    /// Takes an optional path argument for the output file.
+    /// [default: stack-graph.html]
+    #[clap(
+        long,
+        short = 'V',
+        value_name = "OUTPUT_PATH",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "stack-graph.html",
+        value_hint = ValueHint::AnyPath,
+    )]
+    pub visualize: Option<PathBuf>,
+
+    /// Open the HTML visualization in the default browser. Implies --visualize.
+    #[clap(long)]
+    pub open: bool,
+}
+
+/// Format of the input of the build command
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum InputFormat {
+    /// Source file, built using the TSG rules of its language
+    Source,
+    /// Stack graph in JSON format
+    Json,
+    /// Binary stream of graph events
+    Events,
+}
+
+impl BuildArgs {
+    pub fn run(self, mut loader: Loader) -> anyhow::Result<()> {
+        let mut graph = StackGraph::new();
+        // Always detect similar paths for graphs that are not built from source, because we do
+        // not know the language configurations for them.
+        let mut detect_similar_paths = true;
+        match self.input_format {
+            InputFormat::Source => {
+                let mut file_reader = FileReader::new();
+                let lcs =
+                    loader.load_for_file(&self.input_path, &mut file_reader, &NoCancellation)?;
+                if !lcs.has_some() {
+                    return Err(anyhow!("No stack graph language found"));
+                }
+                detect_similar_paths = !lcs.no_similar_paths_in_file();
+                let source = file_reader.get(&self.input_path)?;
+                let file = graph.get_or_create_file(&self.input_path.to_string_lossy());
+                let source_root = self.input_path.parent().unwrap_or(Path::new(""));
+                if let Err(err) = Indexer::build_stack_graph(
+                    &mut graph,
+                    file,
+                    source_root,
+                    &self.input_path,
+                    source,
+                    lcs,
+                    &NoCancellation,
+                ) {
+                    eprintln!("{}", err.display_pretty());
+                    return Err(anyhow!(
+                        "Failed to build graph for {}",
+                        self.input_path.display()
+                    ));
+                }
+            }
+            InputFormat::Json => {
+                let input = std::fs::read_to_string(&self.input_path)?;
+                let input = serde_json::from_str::<stack_graphs::serde::StackGraph>(&input)?;
+                input.load_into(&mut graph)?;
+            }
+            InputFormat::Events => {
+                let mut input = std::io::BufReader::new(std::fs::File::open(&self.input_path)?);
+                GraphEventConsumer::new(&mut graph).consume(&mut input)?;
+            }
+        }
+        print_graph(&graph);
+
+        let mut partials = PartialPaths::new();
+        let mut db = Database::new();
+        if !self.no_paths {
+            let stitcher_config =
+                StitcherConfig::default().with_detect_similar_paths(detect_similar_paths);
+            println!("{}", "partial paths:".bold());
+            for file in graph.iter_files() {
+                ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+                    &graph,
+                    &mut partials,
+                    file,
+                    stitcher_config,
+                    &stack_graphs::NoCancellation,
+                    |g, ps, p| {
+                        println!("  {}", p.display(g, ps));
+                        db.add_partial_path(g, ps, p.clone());
+                    },
+                )?;
+            }
+        }
+
+        let visualization_path = match self.visualize {
+            Some(path) => Some(path),
+            None if self.open => Some(PathBuf::from("stack-graph.html")),
+            None => None,
+        };
+        if let Some(visualization_path) = visualization_path {
+            let source = |path: &str| std::fs::read_to_string(path).ok();
+            let html = graph.to_html_string_with_source(
+                "stack-graph",
+                &mut partials,
+                &mut db,
+                &NoFilter,
+                None,
+                &source,
+            )?;
+            if let Some(dir) = visualization_path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(&visualization_path, html)?;
+            println!("Visualization at {}", visualization_path.display());
+            if self.open {
+                open_in_browser(&visualization_path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prints the nodes of the graph, with their spans, and their outgoing edges.
+fn print_graph(graph: &StackGraph) {
+    println!("{}", "nodes:".bold());
+    for node in graph.iter_nodes() {
+        print!("  {}", node.display(graph));
+        if let Some(source_info) = graph.source_info(node) {
+            let span = &source_info.span;
+            if span != &Default::default() {
+                print!(
+                    " at {}:{}-{}:{}",
+                    span.start.line + 1,
+                    span.start.column.grapheme_offset + 1,
+                    span.end.line + 1,
+                    span.end.column.grapheme_offset + 1,
+                );
+            }
+        }
+        println!();
+    }
+    println!("{}", "edges:".bold());
+    for node in graph.iter_nodes() {
+        for edge in graph.outgoing_edges(node) {
+            print!(
+                "  {} -> {}",
+                edge.source.display(graph),
+                edge.sink.display(graph)
+            );
+            if edge.precedence != 0 {
+                print!(" precedence {}", edge.precedence);
+            }
+            println!();
+        }
+    }
+}
+
+/// Opens the given file with the default application of the platform.
+fn open_in_browser(path: &Path) -> anyhow::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    let status = command.arg(path).status()?;
+    if !status.success() {
+        return Err(anyhow!("Failed to open {}", path.display()));
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use clap::Args;
+use clap::ValueEnum;
+use clap::ValueHint;
+use stack_graphs::analysis::compare_databases;
+use stack_graphs::analysis::ResolutionChange;
+use stack_graphs::analysis::ResolutionComparison;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::storage::SQLiteReader;
+use stack_graphs::NoCancellation;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Compare the resolution of indexed files against a baseline database
+#[derive(Args)]
+pub struct CompareArgs {
+    /// Database to compare against, for example one indexed with an older version of the rules.
+    #[clap(long, value_name = "DATABASE_PATH", value_hint = ValueHint::AnyPath)]
+    pub baseline: PathBuf,
+
+    /// Source file or directory paths to compare.
+    #[clap(
+        value_name = "SOURCE_PATH",
+        required = true,
+        value_hint = ValueHint::AnyPath,
+    )]
+    pub source_paths: Vec<PathBuf>,
+
+    /// Report format.
+    #[clap(long, value_enum, default_value_t = CompareFormat::Text)]
+    pub format: CompareFormat,
+}
+
+/// Comparison report format
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum CompareFormat {
+    /// Human-readable list of differing references
+    Text,
+    /// JSON object with the differing references and their definitions
+    Json,
+}
+
+impl CompareArgs {
+    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+        let source_paths = self
+            .source_paths
+            .iter()
+            .map(|p| p.canonicalize())
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let source_paths = source_paths
+            .iter()
+            .map(PathBuf::as_path)
+            .collect::<Vec<_>>();
+        let mut baseline = SQLiteReader::open(&self.baseline)?;
+        let mut db = SQLiteReader::open(db_path)?;
+        let stitcher_config = StitcherConfig::default()
+            // always detect similar paths, we don't know the language configurations for the data in the database
+            .with_detect_similar_paths(true);
+        let comparison = compare_databases(
+            &mut baseline,
+            &mut db,
+            &source_paths,
+            stitcher_config,
+            &NoCancellation,
+        )?;
+
+        match self.format {
+            CompareFormat::Text => Self::print(&comparison),
+            CompareFormat::Json => println!("{}", serde_json::to_string_pretty(&comparison)?),
+        }
+        Ok(())
+    }
+
+    fn print(comparison: &ResolutionComparison) {
+        for difference in &comparison.differences {
+            let change = match difference.change {
+                ResolutionChange::Gained => "gained",
+                ResolutionChange::Lost => "lost",
+                ResolutionChange::Changed => "changed",
+            };
+            println!("{:<8} {}", change, difference.reference);
+            for definition in &difference.old {
+                println!("  - {}", definition);
+            }
+            for definition in &difference.new {
+                println!("  + {}", definition);
+            }
+        }
+        println!(
+            "Compared {} references: {} gained, {} lost, {} changed",
+            comparison.references,
+            comparison.count(ResolutionChange::Gained),
+            comparison.count(ResolutionChange::Lost),
+            comparison.count(ResolutionChange::Changed),
+        );
+    }
+}
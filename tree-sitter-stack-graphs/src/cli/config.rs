@@ -0,0 +1,46 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use clap::Args;
+use clap::ValueHint;
+use std::path::PathBuf;
+
+use crate::cli::util::ExistingPathBufValueParser;
+use crate::config::Config;
+
+#[derive(Args)]
+pub struct ConfigArgs {
+    /// Project configuration file to use. If omitted, the stack-graphs.toml file in the current
+    /// directory or its closest ancestor is used, if any. Command line arguments take precedence
+    /// over the configuration.
+    #[clap(
+        long,
+        value_name = "CONFIG_PATH",
+        value_hint = ValueHint::FilePath,
+        value_parser = ExistingPathBufValueParser,
+        conflicts_with = "no_config",
+    )]
+    pub config: Option<PathBuf>,
+
+    /// Do not use a project configuration file.
+    #[clap(long)]
+    pub no_config: bool,
+}
+
+impl ConfigArgs {
+    /// Returns the selected configuration, or the default configuration if there is none.
+    pub fn get(&self) -> anyhow::Result<Config> {
+        if self.no_config {
+            return Ok(Config::default());
+        }
+        let config = match &self.config {
+            Some(path) => Some(Config::from_path(path)?),
+            None => Config::discover(&std::env::current_dir()?)?,
+        };
+        Ok(config.unwrap_or_default())
+    }
+}
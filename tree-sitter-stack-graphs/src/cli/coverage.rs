@@ -0,0 +1,123 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use anyhow::anyhow;
+use clap::Args;
+use clap::ValueEnum;
+use clap::ValueHint;
+use stack_graphs::analysis::coverage;
+use stack_graphs::analysis::unresolved_references;
+use stack_graphs::analysis::CoverageCounts;
+use stack_graphs::analysis::CoverageReport;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::storage::SQLiteReader;
+use stack_graphs::NoCancellation;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Report the share of resolved references in indexed files
+#[derive(Args)]
+pub struct CoverageArgs {
+    /// Source file or directory paths to report on.
+    #[clap(
+        value_name = "SOURCE_PATH",
+        required = true,
+        value_hint = ValueHint::AnyPath,
+    )]
+    pub source_paths: Vec<PathBuf>,
+
+    /// Report format.
+    #[clap(long, value_enum, default_value_t = CoverageFormat::Text)]
+    pub format: CoverageFormat,
+
+    /// Fail if the share of resolved references, between 0 and 1, is below this value.
+    #[clap(long, value_name = "RATIO")]
+    pub min_resolved: Option<f64>,
+}
+
+/// Coverage report format
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum CoverageFormat {
+    /// Human-readable summary
+    Text,
+    /// JSON object with the total, per file, and per syntax type counts
+    Json,
+    /// CSV with one line for the total, each file, and each syntax type
+    Csv,
+}
+
+impl CoverageArgs {
+    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+        let cancellation_flag = &NoCancellation;
+        let mut db = SQLiteReader::open(db_path)?;
+        for source_path in &self.source_paths {
+            let source_path = source_path.canonicalize()?;
+            db.load_graphs_for_file_or_directory(&source_path, cancellation_flag)?;
+        }
+        let (graph, _, _) = db.get();
+        let files = graph.iter_files().collect::<Vec<_>>();
+        if files.is_empty() {
+            return Err(anyhow!("No indexed files in the given source paths"));
+        }
+
+        let stitcher_config = StitcherConfig::default()
+            // always detect similar paths, we don't know the language configurations for the data in the database
+            .with_detect_similar_paths(true);
+        let unresolved = unresolved_references(&mut db, stitcher_config, cancellation_flag)?;
+        let (graph, _, _) = db.get();
+        let report = coverage(graph, files, &unresolved);
+
+        match self.format {
+            CoverageFormat::Text => Self::print(&report),
+            CoverageFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            CoverageFormat::Csv => print!("{}", report.to_csv()),
+        }
+
+        if let Some(min_resolved) = self.min_resolved {
+            let resolved = report.total.resolved_ratio();
+            if resolved < min_resolved {
+                return Err(anyhow!(
+                    "Resolved {:.2}% of references, below the minimum of {:.2}%",
+                    resolved * 100.0,
+                    min_resolved * 100.0
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn print(report: &CoverageReport) {
+        let line = |counts: &CoverageCounts, name: &str| {
+            println!(
+                "  {:>7.2}%  {:>6} / {:<6}  {}",
+                counts.resolved_ratio() * 100.0,
+                counts.resolved,
+                counts.references,
+                name
+            )
+        };
+        println!("Resolved references:");
+        line(&report.total, "total");
+        println!();
+        println!("By file:");
+        for (file, counts) in &report.files {
+            line(counts, file);
+        }
+        println!();
+        println!("By syntax type:");
+        for (syntax_type, counts) in &report.syntax_types {
+            line(
+                counts,
+                if syntax_type.is_empty() {
+                    "(none)"
+                } else {
+                    syntax_type
+                },
+            );
+        }
+    }
+}
@@ -0,0 +1,104 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use clap::Args;
+use clap::ValueEnum;
+use clap::ValueHint;
+use stack_graphs::export::edges::EdgeExporter;
+use stack_graphs::export::edges::EdgeFormat;
+use stack_graphs::storage::SQLiteReaderPool;
+use stack_graphs::NoCancellation;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Export the definitions that all references in the database resolve to
+#[derive(Args)]
+pub struct EdgesArgs {
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = EdgesFormat::Csv)]
+    pub format: EdgesFormat,
+
+    /// Number of threads that resolve references. Defaults to the number of available CPUs.
+    #[clap(long, short = 'j', value_name = "THREADS")]
+    pub jobs: Option<usize>,
+
+    /// Output path. Defaults to edges.csv, edges.jsonl, or edges.parquet, depending on the format.
+    /// If an earlier CSV or JSON Lines export to the same path was interrupted, the export is
+    /// resumed.
+    #[clap(
+        long,
+        short = 'o',
+        value_name = "OUTPUT_PATH",
+        value_hint = ValueHint::AnyPath,
+    )]
+    pub output: Option<PathBuf>,
+}
+
+/// Edge list format
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum EdgesFormat {
+    /// Comma-separated values, with a header row
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+    /// Parquet file, which is not resumed if the export is interrupted
+    #[cfg(feature = "arrow")]
+    Parquet,
+}
+
+impl EdgesFormat {
+    fn default_output(&self) -> PathBuf {
+        match self {
+            Self::Csv => PathBuf::from("edges.csv"),
+            Self::Jsonl => PathBuf::from("edges.jsonl"),
+            #[cfg(feature = "arrow")]
+            Self::Parquet => PathBuf::from("edges.parquet"),
+        }
+    }
+}
+
+impl From<EdgesFormat> for EdgeFormat {
+    fn from(value: EdgesFormat) -> Self {
+        match value {
+            EdgesFormat::Csv => Self::Csv,
+            EdgesFormat::Jsonl => Self::Jsonl,
+            #[cfg(feature = "arrow")]
+            EdgesFormat::Parquet => Self::Parquet,
+        }
+    }
+}
+
+impl EdgesArgs {
+    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+        let pool = SQLiteReaderPool::open(db_path)?;
+        let format = self.format;
+        let output = self.output.unwrap_or_else(|| format.default_output());
+        if let Some(dir) = output.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let thread_count = match self.jobs {
+            Some(jobs) => jobs,
+            None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        };
+        let stats = EdgeExporter::new(format.into())
+            .with_thread_count(thread_count)
+            .export(&pool, &output, &NoCancellation)?;
+        if stats.resumed_files > 0 {
+            println!(
+                "Resumed export after {} previously exported files",
+                stats.resumed_files
+            );
+        }
+        println!(
+            "Exported {} edges from {} files to {}",
+            stats.edges,
+            stats.files,
+            output.display()
+        );
+        Ok(())
+    }
+}
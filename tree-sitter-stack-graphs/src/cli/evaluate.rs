@@ -0,0 +1,223 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use anyhow::anyhow;
+use anyhow::Context as _;
+use clap::Args;
+use clap::ValueHint;
+use stack_graphs::storage::SQLiteReader;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::cli::query::Querier;
+use crate::cli::util::reporter::ConsoleReporter;
+use crate::cli::util::SourcePosition;
+use crate::cli::util::SourceSpan;
+use crate::NoCancellation;
+
+/// Score resolution of indexed files against expected definitions
+#[derive(Args)]
+pub struct EvaluateArgs {
+    /// Directory containing the indexed source files. Paths in the ground truth file are
+    /// relative to this directory.
+    #[clap(value_name = "SOURCE_DIR", value_hint = ValueHint::DirPath)]
+    pub source_dir: PathBuf,
+
+    /// Ground truth file. Each line contains a reference position, followed by the positions
+    /// of its expected definitions, all formatted as PATH:LINE:COLUMN and separated by
+    /// whitespace. A reference without definition positions is expected not to resolve. Empty
+    /// lines and lines starting with # are ignored.
+    #[clap(value_name = "GROUND_TRUTH", value_hint = ValueHint::FilePath)]
+    pub ground_truth: PathBuf,
+
+    /// Fail if the precision, between 0 and 1, is below this value.
+    #[clap(long, value_name = "RATIO")]
+    pub min_precision: Option<f64>,
+
+    /// Fail if the recall, between 0 and 1, is below this value.
+    #[clap(long, value_name = "RATIO")]
+    pub min_recall: Option<f64>,
+
+    /// Show the missing and unexpected definitions of each reference.
+    #[clap(long, short = 'v')]
+    pub verbose: bool,
+}
+
+impl EvaluateArgs {
+    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+        let source_dir = self.source_dir.canonicalize()?;
+        let ground_truth = std::fs::read_to_string(&self.ground_truth)
+            .with_context(|| format!("Cannot read {}", self.ground_truth.display()))?;
+        let expectations = parse_ground_truth(&ground_truth, &source_dir)
+            .with_context(|| format!("Invalid ground truth in {}", self.ground_truth.display()))?;
+
+        let mut db = SQLiteReader::open(db_path)?;
+        let reporter = ConsoleReporter::none();
+        let mut querier = Querier::new(&mut db, &reporter);
+        let mut evaluation = Evaluation::default();
+        for expectation in expectations {
+            let results = querier.definitions(expectation.reference.clone(), &NoCancellation)?;
+            let targets = results
+                .into_iter()
+                .flat_map(|r| r.targets)
+                .collect::<Vec<_>>();
+            evaluation.add(&expectation, &targets);
+        }
+
+        println!(
+            "Evaluated {} references against {}",
+            evaluation.references,
+            self.ground_truth.display()
+        );
+        println!(
+            "  precision: {:>7.2}%  ({} / {})",
+            evaluation.precision() * 100.0,
+            evaluation.true_positives,
+            evaluation.true_positives + evaluation.false_positives.len(),
+        );
+        println!(
+            "  recall:    {:>7.2}%  ({} / {})",
+            evaluation.recall() * 100.0,
+            evaluation.true_positives,
+            evaluation.true_positives + evaluation.false_negatives.len(),
+        );
+        if self.verbose {
+            for (reference, definition) in &evaluation.false_negatives {
+                println!("  missing    {} -> {}", reference, definition);
+            }
+            for (reference, definition) in &evaluation.false_positives {
+                println!(
+                    "  unexpected {} -> {}:{}:{}",
+                    reference,
+                    definition.path.display(),
+                    definition.span.start.line + 1,
+                    definition.span.start.column.grapheme_offset + 1
+                );
+            }
+        }
+
+        if let Some(min_precision) = self.min_precision {
+            if evaluation.precision() < min_precision {
+                return Err(anyhow!(
+                    "Precision {:.2}% is below the minimum of {:.2}%",
+                    evaluation.precision() * 100.0,
+                    min_precision * 100.0
+                ));
+            }
+        }
+        if let Some(min_recall) = self.min_recall {
+            if evaluation.recall() < min_recall {
+                return Err(anyhow!(
+                    "Recall {:.2}% is below the minimum of {:.2}%",
+                    evaluation.recall() * 100.0,
+                    min_recall * 100.0
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The expected definitions of a reference.
+pub struct Expectation {
+    pub reference: SourcePosition,
+    pub definitions: Vec<SourcePosition>,
+}
+
+/// Parses a ground truth file, resolving relative paths against the source directory. Lines
+/// for the same reference are merged into a single expectation.
+pub fn parse_ground_truth(input: &str, source_dir: &Path) -> anyhow::Result<Vec<Expectation>> {
+    let mut expectations: Vec<Expectation> = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut positions = line
+            .split_whitespace()
+            .map(|p| {
+                let mut position = SourcePosition::from_str(p)?;
+                position.path = source_dir.join(position.path);
+                Ok(position)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .with_context(|| format!("Line {}", index + 1))?;
+        let reference = positions.remove(0);
+        match expectations.iter_mut().find(|e| {
+            e.reference.path == reference.path
+                && e.reference.line == reference.line
+                && e.reference.column == reference.column
+        }) {
+            Some(expectation) => expectation.definitions.extend(positions),
+            None => expectations.push(Expectation {
+                reference,
+                definitions: positions,
+            }),
+        }
+    }
+    Ok(expectations)
+}
+
+/// Precision and recall of resolved definitions, compared to the expected definitions.
+#[derive(Default)]
+pub struct Evaluation {
+    /// Number of evaluated references.
+    pub references: usize,
+    /// Number of expected definitions that were found.
+    pub true_positives: usize,
+    /// Definitions that were found, but not expected, with their reference.
+    pub false_positives: Vec<(SourcePosition, SourceSpan)>,
+    /// Definitions that were expected, but not found, with their reference.
+    pub false_negatives: Vec<(SourcePosition, SourcePosition)>,
+}
+
+impl Evaluation {
+    /// Adds the definitions found for the reference of an expectation. A found definition
+    /// matches an expected definition if its span contains the expected position.
+    pub fn add(&mut self, expectation: &Expectation, found: &[SourceSpan]) {
+        self.references += 1;
+        for expected in &expectation.definitions {
+            if found.iter().any(|f| expected.is_within(f)) {
+                self.true_positives += 1;
+            } else {
+                self.false_negatives
+                    .push((expectation.reference.clone(), expected.clone()));
+            }
+        }
+        for found in found {
+            if !expectation.definitions.iter().any(|e| e.is_within(found)) {
+                self.false_positives
+                    .push((expectation.reference.clone(), found.clone()));
+            }
+        }
+    }
+
+    /// Returns the share of found definitions that were expected. Is 1 if nothing was found.
+    pub fn precision(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_positives.len(),
+        )
+    }
+
+    /// Returns the share of expected definitions that were found. Is 1 if nothing was expected.
+    pub fn recall(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_negatives.len(),
+        )
+    }
+}
+
+fn ratio(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        1.0
+    } else {
+        count as f64 / total as f64
+    }
+}
@@ -0,0 +1,170 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use anyhow::anyhow;
+use clap::Args;
+use clap::ValueEnum;
+use clap::ValueHint;
+use stack_graphs::arena::Handle;
+use stack_graphs::export::lsif::write_lsif;
+use stack_graphs::export::scip;
+use stack_graphs::export::scip::Message;
+use stack_graphs::export::Resolutions;
+use stack_graphs::graph::File;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::stitching::Database;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::storage::SQLiteReader;
+use stack_graphs::NoCancellation;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Export indexed files from the database
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Source file or directory paths to export.
+    #[clap(
+        value_name = "SOURCE_PATH",
+        required = true,
+        value_hint = ValueHint::AnyPath,
+    )]
+    pub source_paths: Vec<PathBuf>,
+
+    /// Export format.
+    #[clap(long, value_enum, default_value_t = ExportFormat::Json)]
+    pub format: ExportFormat,
+
+    /// Project root that LSIF and SCIP document paths are relative to. Defaults to the current
+    /// directory.
+    #[clap(
+        long,
+        value_name = "PROJECT_ROOT",
+        value_hint = ValueHint::DirPath,
+    )]
+    pub project_root: Option<PathBuf>,
+
+    /// Output path. Defaults to stack-graph.json, stack-graph.dot, dump.lsif, or index.scip,
+    /// depending on the format.
+    #[clap(
+        long,
+        short = 'o',
+        value_name = "OUTPUT_PATH",
+        value_hint = ValueHint::AnyPath,
+    )]
+    pub output: Option<PathBuf>,
+}
+
+/// Export format
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Stack graph of the exported files, in the JSON serialization format
+    Json,
+    /// Graphviz DOT file of the exported files, with the edges of resolved references highlighted
+    Dot,
+    /// LSIF dump of the definitions and resolved references in the exported files
+    Lsif,
+    /// SCIP index of the definitions and resolved references in the exported files
+    Scip,
+}
+
+impl ExportFormat {
+    fn default_output(&self) -> PathBuf {
+        match self {
+            Self::Json => PathBuf::from("stack-graph.json"),
+            Self::Dot => PathBuf::from("stack-graph.dot"),
+            Self::Lsif => PathBuf::from("dump.lsif"),
+            Self::Scip => PathBuf::from("index.scip"),
+        }
+    }
+}
+
+impl ExportArgs {
+    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+        let cancellation_flag = &NoCancellation;
+        let mut db = SQLiteReader::open(db_path)?;
+        for source_path in &self.source_paths {
+            let source_path = source_path.canonicalize()?;
+            db.load_graphs_for_file_or_directory(&source_path, cancellation_flag)?;
+        }
+        let (graph, _, _) = db.get();
+        let files = graph.iter_files().collect::<Vec<_>>();
+        if files.is_empty() {
+            return Err(anyhow!("No indexed files in the given source paths"));
+        }
+
+        // Files of definitions are loaded during path stitching, so references are resolved
+        // after the exported files are fixed.
+        let mut resolutions = Resolutions::new();
+        let mut complete_paths_db = Database::new();
+        if self.format != ExportFormat::Json {
+            let references = files
+                .iter()
+                .flat_map(|file| graph.nodes_for_file(*file))
+                .filter(|node| graph[*node].is_reference())
+                .collect::<Vec<_>>();
+            let stitcher_config = StitcherConfig::default()
+                // always detect similar paths, we don't know the language configurations for the data in the database
+                .with_detect_similar_paths(true);
+            ForwardPartialPathStitcher::find_all_complete_partial_paths(
+                &mut db,
+                references,
+                stitcher_config,
+                cancellation_flag,
+                |g, ps, p| {
+                    resolutions.add_path(p);
+                    complete_paths_db.add_partial_path(g, ps, p.clone());
+                },
+            )?;
+        }
+
+        let project_root = match self.project_root {
+            Some(project_root) => project_root.canonicalize()?,
+            None => std::env::current_dir()?,
+        };
+        let filter = |_: &StackGraph, file: &Handle<File>| files.contains(file);
+        let (graph, partials, _) = db.get();
+        let output = match self.format {
+            ExportFormat::Json => serde_json::to_vec(&graph.to_serializable_filter(&filter))?,
+            ExportFormat::Dot => graph
+                .to_dot_string("stack-graph", partials, &complete_paths_db, &filter)
+                .into_bytes(),
+            ExportFormat::Lsif => {
+                let mut output = Vec::new();
+                write_lsif(graph, &files, &resolutions, &project_root, &mut output)?;
+                output
+            }
+            ExportFormat::Scip => {
+                scip::Index::from_resolutions(graph, &files, &resolutions, &project_root)
+                    .encode_to_vec()
+            }
+        };
+
+        let format = self.format;
+        let output_path = self.output.unwrap_or_else(|| format.default_output());
+        if let Some(dir) = output_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&output_path, output)?;
+        match format {
+            ExportFormat::Json => {
+                println!(
+                    "Exported {} files to {}",
+                    files.len(),
+                    output_path.display()
+                )
+            }
+            _ => println!(
+                "Exported {} files with {} resolved references to {}",
+                files.len(),
+                resolutions.len(),
+                output_path.display()
+            ),
+        }
+        Ok(())
+    }
+}
@@ -7,6 +7,10 @@
 
 use clap::Args;
 use clap::ValueHint;
+use notify::event::ModifyKind;
+use notify::EventKind;
+use notify::RecursiveMode;
+use notify::Watcher;
 use stack_graphs::arena::Handle;
 use stack_graphs::graph::File;
 use stack_graphs::graph::StackGraph;
@@ -17,15 +21,20 @@ use stack_graphs::stitching::Stats as StitchingStats;
 use stack_graphs::stitching::StitcherConfig;
 use stack_graphs::storage::FileStatus;
 use stack_graphs::storage::SQLiteWriter;
+use stack_graphs::storage::FILE_GROUP_METADATA;
+use stack_graphs::storage::RULES_VERSION_METADATA;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::mpsc;
 use std::time::Duration;
+use std::time::Instant;
 use thiserror::Error;
 use tree_sitter_graph::Variables;
 
 use crate::cli::util::duration_from_seconds_str;
-use crate::cli::util::iter_files_and_directories;
+use crate::cli::util::iter_files_and_directories_with_rules;
 use crate::cli::util::print_indexing_stats;
 use crate::cli::util::reporter::ConsoleReporter;
 use crate::cli::util::reporter::Level;
@@ -35,6 +44,8 @@ use crate::cli::util::wait_for_input;
 use crate::cli::util::BuildErrorWithSource;
 use crate::cli::util::CLIFileReporter;
 use crate::cli::util::ExistingPathBufValueParser;
+use crate::cli::util::FileWalkRules;
+use crate::config::Config;
 use crate::loader::FileLanguageConfigurations;
 use crate::loader::FileReader;
 use crate::loader::Loader;
@@ -70,6 +81,31 @@ pub struct IndexArgs {
     #[clap(long, short = 'f')]
     pub force: bool,
 
+    /// Index files that failed to index before, or that were partially indexed,
+    /// even if they did not change. Files that are indexed successfully and did
+    /// not change are skipped.
+    #[clap(long)]
+    pub retry_failed: bool,
+
+    /// Resume an interrupted indexing run. Files that were completed during that run and
+    /// did not change are skipped, even if --force or --retry-failed is given.
+    #[clap(long)]
+    pub resume: bool,
+
+    /// Only index files in source directories that match the given glob.
+    /// Can be specified multiple times.
+    #[clap(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Do not index files in source directories that match the given glob.
+    /// Can be specified multiple times, and takes precedence over --include.
+    #[clap(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Also index files that are ignored by .gitignore or .ignore files.
+    #[clap(long)]
+    pub no_ignore: bool,
+
     /// Hide details of indexing errors on files.
     #[clap(long)]
     pub hide_error_details: bool,
@@ -88,6 +124,27 @@ pub struct IndexArgs {
     /// Wait for user input before starting analysis. Useful for profiling.
     #[clap(long)]
     pub wait_at_start: bool,
+
+    /// Keep watching the source paths after indexing, and re-index files when they change.
+    #[clap(long, short = 'w')]
+    pub watch: bool,
+
+    /// Store the files as the given variant, such as the name of a build configuration,
+    /// instead of the default variant. Queries select variants with --variant.
+    #[clap(long, value_name = "NAME")]
+    pub variant: Option<String>,
+
+    /// Resolve all references in the given file after indexing, and store the results in
+    /// the database, so that queries for these references do not have to stitch paths.
+    /// Stored results are discarded when files are indexed again. Can be specified
+    /// multiple times.
+    #[clap(
+        long,
+        value_name = "SOURCE_PATH",
+        value_hint = ValueHint::AnyPath,
+        value_parser = ExistingPathBufValueParser,
+    )]
+    pub resolve_references: Vec<PathBuf>,
 }
 
 impl IndexArgs {
@@ -95,13 +152,34 @@ impl IndexArgs {
         Self {
             source_paths,
             force: false,
+            retry_failed: false,
+            resume: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            no_ignore: false,
             continue_from: None,
             verbose: false,
             hide_error_details: false,
             max_file_time: None,
             wait_at_start: false,
             stats: false,
+            watch: false,
+            variant: None,
+            resolve_references: Vec::new(),
+        }
+    }
+
+    /// Uses the values of the project configuration for the arguments that are not given.
+    pub fn with_config(mut self, config: &Config) -> Self {
+        if self.include.is_empty() {
+            self.include = config.files.include.clone();
+        }
+        if self.exclude.is_empty() {
+            self.exclude = config.files.exclude.clone();
         }
+        self.no_ignore |= config.files.no_ignore;
+        self.max_file_time = self.max_file_time.or(config.limits.max_file_time);
+        self
     }
 
     pub fn run(self, db_path: &Path, mut loader: Loader) -> anyhow::Result<()> {
@@ -109,9 +187,15 @@ impl IndexArgs {
             wait_for_input()?;
         }
         let mut db = SQLiteWriter::open(&db_path)?;
+        if let Some(variant) = &self.variant {
+            db = db.with_variant(variant)?;
+        }
         let reporter = self.get_reporter();
         let mut indexer = Indexer::new(&mut db, &mut loader, &reporter);
         indexer.force = self.force;
+        indexer.retry_failed = self.retry_failed;
+        indexer.resume = self.resume;
+        indexer.walk_rules = FileWalkRules::new(!self.no_ignore, self.include, self.exclude)?;
         indexer.max_file_time = self.max_file_time;
         indexer.set_collect_stats(self.stats);
 
@@ -120,11 +204,33 @@ impl IndexArgs {
             .into_iter()
             .map(|p| p.canonicalize())
             .collect::<std::result::Result<Vec<_>, _>>()?;
-        indexer.index_all(source_paths, self.continue_from, &NoCancellation)?;
+        indexer.index_all(&source_paths, self.continue_from, &NoCancellation)?;
+
+        if !self.resolve_references.is_empty() {
+            let files = self
+                .resolve_references
+                .into_iter()
+                .map(|p| p.canonicalize())
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            let count = indexer.db.resolve_references_in_files(
+                files,
+                StitcherConfig::default(),
+                &stack_graphs::NoCancellation,
+            )?;
+            if self.verbose {
+                println!("Resolved {} references", count);
+            }
+        }
 
         if self.stats {
             println!();
-            print_indexing_stats(indexer.into_stats());
+            // Take the stats, so that they are not collected while watching.
+            print_indexing_stats(indexer.stats.take().unwrap_or_default());
+        }
+
+        if self.watch {
+            println!("Watching for changes...");
+            indexer.watch(&source_paths, &NoCancellation)?;
         }
         Ok(())
     }
@@ -162,8 +268,18 @@ pub struct Indexer<'a> {
     stats: Option<IndexingStats>,
     /// Index files, even if they already exist in the database.
     pub force: bool,
+    /// Index files that failed or were partially indexed before, even if they did not change.
+    pub retry_failed: bool,
+    /// Resume an interrupted run of [`index_all`][Self::index_all], skipping files that were
+    /// completed during that run and did not change.
+    pub resume: bool,
+    /// Whether completed files are recorded in the checkpoint, which is only done by
+    /// [`index_all`][Self::index_all].
+    checkpointing: bool,
     /// Maximum time per file.
     pub max_file_time: Option<Duration>,
+    /// Rules selecting the files to index in source directories.
+    pub walk_rules: FileWalkRules,
 }
 
 impl<'a> Indexer<'a> {
@@ -177,7 +293,11 @@ impl<'a> Indexer<'a> {
             loader,
             reporter,
             force: false,
+            retry_failed: false,
+            resume: false,
+            checkpointing: false,
             max_file_time: None,
+            walk_rules: FileWalkRules::default(),
             stats: None,
         }
     }
@@ -190,6 +310,9 @@ impl<'a> Indexer<'a> {
         }
     }
 
+    /// Index all files in the given source paths. Completed files are recorded in the checkpoint
+    /// of the database, which is cleared when all files are indexed, so that an interrupted run
+    /// can be resumed by setting [`resume`][Self::resume].
     pub fn index_all<P, IP, Q>(
         &mut self,
         source_paths: IP,
@@ -201,14 +324,38 @@ impl<'a> Indexer<'a> {
         IP: IntoIterator<Item = P>,
         Q: AsRef<Path>,
     {
-        for (source_root, source_path, strict) in iter_files_and_directories(source_paths) {
+        if !self.resume {
+            self.db.clear_checkpoint()?;
+        }
+        self.checkpointing = true;
+        let result = self.index_all_inner(source_paths, &mut continue_from, cancellation_flag);
+        self.checkpointing = false;
+        result?;
+        self.db.clear_checkpoint()?;
+        Ok(())
+    }
+
+    fn index_all_inner<P, IP, Q>(
+        &mut self,
+        source_paths: IP,
+        continue_from: &mut Option<Q>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+        IP: IntoIterator<Item = P>,
+        Q: AsRef<Path>,
+    {
+        for (source_root, source_path, strict) in
+            iter_files_and_directories_with_rules(source_paths, self.walk_rules.clone())
+        {
             let mut file_status = CLIFileReporter::new(self.reporter, &source_path);
             cancellation_flag.check("indexing all files")?;
             self.index_file(
                 &source_root,
                 &source_path,
                 strict,
-                &mut continue_from,
+                continue_from,
                 cancellation_flag,
                 &mut file_status,
             )?;
@@ -262,7 +409,7 @@ impl<'a> Indexer<'a> {
                 ok
             }
             err @ Err(_) => {
-                file_status.failure_if_processing("error", Some(&format!("Error analyzing file {}. To continue analysis from this file later, add: --continue-from {}", source_path.display(), source_path.display())));
+                file_status.failure_if_processing("error", Some(&format!("Error analyzing file {}. To continue analysis from this file later, add: --continue-from {} or --resume", source_path.display(), source_path.display())));
                 err
             }
         }
@@ -301,7 +448,7 @@ impl<'a> Indexer<'a> {
                 file_status.warning("language loading timed out", None);
                 return Ok(());
             }
-            Err(e) => return Err(IndexError::LoadError(e)),
+            Err(e) => return Err(IndexError::LoadError(Box::new(e))),
         };
         let stitcher_config = StitcherConfig::default()
             .with_detect_similar_paths(!lcs.no_similar_paths_in_file())
@@ -310,6 +457,11 @@ impl<'a> Indexer<'a> {
         let source = file_reader.get(source_path)?;
         let tag = sha1(source);
 
+        if self.resume && self.db.is_file_checkpointed(source_path, &tag)? {
+            file_status.skipped("completed before interruption", None);
+            return Ok(());
+        }
+
         let success_status = match self
             .db
             .status_for_file(&source_path.to_string_lossy(), Some(&tag))?
@@ -323,9 +475,21 @@ impl<'a> Indexer<'a> {
                     return Ok(());
                 }
             }
+            FileStatus::Partial(reason) => {
+                if self.force {
+                    "reindexed"
+                } else if self.retry_failed {
+                    "retried"
+                } else {
+                    file_status.skipped(&format!("cached partial index ({})", reason), None);
+                    return Ok(());
+                }
+            }
             FileStatus::Error(error) => {
                 if self.force {
                     "reindexed"
+                } else if self.retry_failed {
+                    "retried"
                 } else {
                     file_status.skipped(&format!("cached error ({})", error), None);
                     return Ok(());
@@ -342,6 +506,16 @@ impl<'a> Indexer<'a> {
         let file = graph
             .add_file(&source_path.to_string_lossy())
             .expect("file not present in empty graph");
+        graph.set_metadata(INDEXER_VERSION_METADATA, env!("CARGO_PKG_VERSION"));
+        if let Some(scope) = lcs.primary.and_then(|lc| lc.scope.as_ref()) {
+            graph.set_metadata(LANGUAGE_SCOPE_METADATA, scope.as_str());
+        }
+        if let Some(lc) = lcs.primary {
+            graph.set_metadata(RULES_VERSION_METADATA, sha1(lc.sgl.tsg_source()));
+        }
+        if let Some(file_group) = lcs.file_group(source_path) {
+            graph.set_metadata(FILE_GROUP_METADATA, file_group);
+        }
 
         let result = Self::build_stack_graph(
             &mut graph,
@@ -353,16 +527,18 @@ impl<'a> Indexer<'a> {
             &cancellation_flag,
         );
         if let Err(err) = result {
-            match err.inner {
+            match *err.inner {
                 BuildError::Cancelled(_) => {
                     file_status.warning("timed out", None);
                     self.db
                         .store_error_for_file(source_path, &tag, "timed out")?;
+                    self.checkpoint_file(source_path, &tag)?;
                     return Ok(());
                 }
                 _ => {
                     file_status.failure("failed", Some(&err.display_pretty()));
                     self.db.store_error_for_file(source_path, &tag, "failed")?;
+                    self.checkpoint_file(source_path, &tag)?;
                     return Ok(());
                 }
             }
@@ -400,25 +576,32 @@ impl<'a> Indexer<'a> {
                 }
             }
             Err(_) => {
-                file_status.warning("path computation timed out", None);
-                self.db.store_error_for_file(
-                    source_path,
+                // The paths that were found before the timeout are stored, so that the file is
+                // usable, but marked as partial, so that it can be identified later.
+                file_status.warning("path computation timed out, partially indexed", None);
+                self.db.store_partial_result_for_file(
+                    &graph,
+                    file,
                     &tag,
-                    &format!("path computation timed out"),
+                    &mut partials,
+                    &paths,
+                    "path computation timed out",
                 )?;
+                self.checkpoint_file(source_path, &tag)?;
                 return Ok(());
             }
         }
 
         self.db
             .store_result_for_file(&graph, file, &tag, &mut partials, &paths)?;
+        self.checkpoint_file(source_path, &tag)?;
 
         file_status.success(success_status, None);
 
         Ok(())
     }
 
-    fn build_stack_graph<'b>(
+    pub(crate) fn build_stack_graph<'b>(
         graph: &mut StackGraph,
         file: Handle<File>,
         source_root: &Path,
@@ -433,14 +616,19 @@ impl<'a> Indexer<'a> {
             lc.sgl
                 .build_stack_graph_into(graph, file, source, &globals, cancellation_flag)
                 .map_err(|inner| BuildErrorWithSource {
-                    inner,
+                    inner: Box::new(inner),
                     source_path: source_path.to_path_buf(),
                     source_str: source,
                     tsg_path: lc.sgl.tsg_path().to_path_buf(),
                     tsg_str: &lc.sgl.tsg_source(),
                 })?;
         }
-        for (_, fa) in lcs.secondary {
+        for fa in lcs
+            .secondary
+            .into_iter()
+            .map(|(_, fa)| fa)
+            .chain(lcs.fallback)
+        {
             fa.build_stack_graph_into(
                 graph,
                 file,
@@ -451,7 +639,7 @@ impl<'a> Indexer<'a> {
                 cancellation_flag,
             )
             .map_err(|inner| BuildErrorWithSource {
-                inner,
+                inner: Box::new(inner),
                 source_path: source_path.to_path_buf(),
                 source_str: &source,
                 tsg_path: PathBuf::new(),
@@ -461,6 +649,14 @@ impl<'a> Indexer<'a> {
         Ok(())
     }
 
+    /// Records the file as completed in the checkpoint, if this is a checkpointed run.
+    fn checkpoint_file(&mut self, source_path: &Path, tag: &str) -> Result<()> {
+        if self.checkpointing {
+            self.db.checkpoint_file(source_path, tag)?;
+        }
+        Ok(())
+    }
+
     /// Determines if a path should be skipped because we have not seen the
     /// continue_from mark yet. If the mark is seen, it is cleared, after which
     /// all paths are accepted.
@@ -477,6 +673,93 @@ impl<'a> Indexer<'a> {
         false
     }
 
+    /// Watches the given source paths, and re-indexes files when they change. Data for removed
+    /// files is cleaned from the database. The time it took to process each changed file is
+    /// printed. Returns only if an error occurs or the cancellation flag is raised.
+    pub fn watch<P>(
+        &mut self,
+        source_paths: &[P],
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        // Files are indexed relative to their source root, which is the source path itself for
+        // directories, and the directory containing it for files.
+        let mut source_roots = Vec::new();
+        for source_path in source_paths {
+            let source_path = source_path.as_ref();
+            let source_root = if source_path.is_dir() {
+                source_path
+            } else {
+                source_path
+                    .parent()
+                    .ok_or_else(|| IndexError::NoParentDirectory(source_path.to_path_buf()))?
+            };
+            watcher.watch(source_path, RecursiveMode::Recursive)?;
+            source_roots.push((source_path, source_root));
+        }
+
+        loop {
+            cancellation_flag.check("watching files")?;
+            // Wait for the first event, and collect events that follow in quick succession, so
+            // that editors writing a file in multiple steps result in a single re-index.
+            let event = match receiver.recv_timeout(WATCH_POLL_INTERVAL) {
+                Ok(event) => event?,
+                Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+            };
+            let mut changed_paths = BTreeSet::new();
+            if changes_content(&event) {
+                changed_paths.extend(event.paths);
+            }
+            while let Ok(event) = receiver.recv_timeout(WATCH_DEBOUNCE_INTERVAL) {
+                let event = event?;
+                if changes_content(&event) {
+                    changed_paths.extend(event.paths);
+                }
+            }
+
+            for changed_path in changed_paths {
+                let source_root = match source_roots
+                    .iter()
+                    .find(|(source_path, _)| changed_path.starts_with(source_path))
+                {
+                    Some((_, source_root)) => *source_root,
+                    None => continue,
+                };
+                let start = Instant::now();
+                if changed_path.is_file() {
+                    if !self.walk_rules.selects(source_root, &changed_path) {
+                        continue;
+                    }
+                    let mut file_status = CLIFileReporter::new(self.reporter, &changed_path);
+                    self.index_file(
+                        source_root,
+                        &changed_path,
+                        false,
+                        &mut None::<&Path>,
+                        cancellation_flag,
+                        &mut file_status,
+                    )?;
+                } else if !changed_path.exists() {
+                    if self.db.clean_file_or_directory(&changed_path)? == 0 {
+                        continue;
+                    }
+                } else {
+                    continue;
+                }
+                println!(
+                    "{}: {} ms",
+                    changed_path.display(),
+                    start.elapsed().as_millis()
+                );
+            }
+        }
+    }
+
     pub fn into_stats(self) -> IndexingStats {
         self.stats.unwrap_or_default()
     }
@@ -487,13 +770,17 @@ pub enum IndexError {
     #[error("cancelled at {0}")]
     Cancelled(&'static str),
     #[error("failed to load language")]
-    LoadError(#[source] crate::loader::LoadError<'static>),
+    LoadError(#[source] Box<crate::loader::LoadError<'static>>),
     #[error("failed to read file")]
     ReadError(#[from] std::io::Error),
     #[error("failed to build stack graph")]
     StackGraph,
     #[error(transparent)]
     StorageError(#[from] stack_graphs::storage::StorageError),
+    #[error("source path {0} has no parent directory")]
+    NoParentDirectory(PathBuf),
+    #[error("failed to watch files")]
+    WatchError(#[from] notify::Error),
 }
 
 impl From<crate::CancellationError> for IndexError {
@@ -504,6 +791,25 @@ impl From<crate::CancellationError> for IndexError {
 
 type Result<T> = std::result::Result<T, IndexError>;
 
+/// Returns whether a file system event creates, modifies, or removes files.  Other events, such
+/// as files being accessed or their metadata changing, do not require re-indexing.
+fn changes_content(event: &notify::Event) -> bool {
+    match event.kind {
+        EventKind::Create(_) | EventKind::Remove(_) => true,
+        EventKind::Modify(ModifyKind::Metadata(_)) => false,
+        EventKind::Modify(_) => true,
+        _ => false,
+    }
+}
+
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const WATCH_DEBOUNCE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Metadata key for the version of the indexer that created a file graph.
+pub const INDEXER_VERSION_METADATA: &str = "indexer_version";
+/// Metadata key for the scope of the language a file graph was created for.
+pub const LANGUAGE_SCOPE_METADATA: &str = "language_scope";
+
 #[derive(Clone, Debug, Default)]
 pub struct IndexingStats {
     // The distribution of the total number of nodes per file graph.
@@ -9,12 +9,15 @@ use clap::Args;
 use std::path::PathBuf;
 use tree_sitter_config::Config as TsConfig;
 
+use crate::config::Config;
+use crate::loader::ExtensionLanguageDetector;
 use crate::loader::LanguageConfiguration;
 use crate::loader::LoadError;
 use crate::loader::LoadPath;
 use crate::loader::Loader;
 use crate::loader::DEFAULT_BUILTINS_PATHS;
 use crate::loader::DEFAULT_TSG_PATHS;
+use crate::null_language::NullLanguage;
 
 #[derive(Args)]
 pub struct PathLoaderArgs {
@@ -37,6 +40,22 @@ pub struct PathLoaderArgs {
     /// See https://tree-sitter.github.io/tree-sitter/syntax-highlighting#basics for details.
     #[clap(long, value_name = "SCOPE")]
     pub scope: Option<String>,
+
+    /// INI file mapping file extensions to language scopes, which take precedence over the
+    /// file types of the languages. Mappings are `EXTENSION = SCOPE` entries in a `[languages]` section.
+    #[clap(long, value_name = "CONFIG_PATH")]
+    pub language_config: Option<PathBuf>,
+
+    /// Index files for which no language is found with the built-in null language, which needs
+    /// no grammar or TSG rules. It defines the names assigned on lines like `NAME = ...`, and
+    /// references all other identifiers.
+    #[clap(long)]
+    pub null_language: bool,
+
+    /// The maximum depth of syntax trees that stack graphs are built for. Files with deeper syntax
+    /// trees fail to index, instead of exhausting the stack. Defaults to 1000.
+    #[clap(long, value_name = "DEPTH")]
+    pub max_tree_depth: Option<usize>,
 }
 
 impl PathLoaderArgs {
@@ -46,10 +65,19 @@ impl PathLoaderArgs {
             builtins: None,
             grammar: Vec::new(),
             scope: None,
+            language_config: None,
+            null_language: false,
+            max_tree_depth: None,
         }
     }
 
     pub fn get(&self) -> Result<Loader, LoadError<'static>> {
+        self.get_with_config(&Config::default())
+    }
+
+    /// Returns a loader for the arguments, using the project configuration for the values
+    /// that are not given as arguments.
+    pub fn get_with_config(&self, config: &Config) -> Result<Loader, LoadError<'static>> {
         let tsg_paths = match &self.tsg {
             Some(tsg_path) => vec![LoadPath::Regular(tsg_path.clone())],
             None => DEFAULT_TSG_PATHS.clone(),
@@ -59,9 +87,15 @@ impl PathLoaderArgs {
             None => DEFAULT_BUILTINS_PATHS.clone(),
         };
 
-        let loader = if !self.grammar.is_empty() {
+        let grammar_paths = if !self.grammar.is_empty() {
+            &self.grammar
+        } else {
+            &config.grammars
+        };
+
+        let mut loader = if !grammar_paths.is_empty() {
             Loader::from_paths(
-                self.grammar.clone(),
+                grammar_paths.clone(),
                 self.scope.clone(),
                 tsg_paths,
                 builtins_paths,
@@ -77,6 +111,27 @@ impl PathLoaderArgs {
                 builtins_paths,
             )?
         };
+        for language in &config.languages {
+            if let (None, Some(tsg)) = (&self.tsg, &language.tsg) {
+                loader.set_tsg_paths_for_scope(
+                    language.scope.clone(),
+                    vec![LoadPath::Regular(tsg.clone())],
+                );
+            }
+            if let (None, Some(builtins)) = (&self.builtins, &language.builtins) {
+                loader.set_builtins_paths_for_scope(
+                    language.scope.clone(),
+                    vec![LoadPath::Regular(builtins.clone())],
+                );
+            }
+        }
+        set_language_detector(&mut loader, &self.language_config, config)?;
+        if self.null_language || config.null_language {
+            loader.set_fallback_analyzer(NullLanguage);
+        }
+        if let Some(max_tree_depth) = self.max_tree_depth {
+            loader.set_max_tree_depth(Some(max_tree_depth));
+        }
         Ok(loader)
     }
 }
@@ -88,18 +143,72 @@ pub struct LanguageConfigurationsLoaderArgs {
     /// See https://tree-sitter.github.io/tree-sitter/syntax-highlighting#basics for details.
     #[clap(long, value_name = "SCOPE")]
     scope: Option<String>,
+
+    /// INI file mapping file extensions to language scopes, which take precedence over the
+    /// file types of the languages. Mappings are `EXTENSION = SCOPE` entries in a `[languages]` section.
+    #[clap(long, value_name = "CONFIG_PATH")]
+    language_config: Option<PathBuf>,
+
+    /// Index files for which no language is found with the built-in null language, which needs
+    /// no grammar or TSG rules. It defines the names assigned on lines like `NAME = ...`, and
+    /// references all other identifiers.
+    #[clap(long)]
+    pub null_language: bool,
+
+    /// The maximum depth of syntax trees that stack graphs are built for. Files with deeper syntax
+    /// trees fail to index, instead of exhausting the stack. Defaults to 1000.
+    #[clap(long, value_name = "DEPTH")]
+    pub max_tree_depth: Option<usize>,
 }
 
 impl LanguageConfigurationsLoaderArgs {
     pub fn new() -> Self {
-        Self { scope: None }
+        Self {
+            scope: None,
+            language_config: None,
+            null_language: false,
+            max_tree_depth: None,
+        }
     }
 
     pub fn get(
         &self,
         configurations: Vec<LanguageConfiguration>,
     ) -> Result<Loader, LoadError<'static>> {
-        let loader = Loader::from_language_configurations(configurations, self.scope.clone())?;
+        self.get_with_config(configurations, &Config::default())
+    }
+
+    /// Returns a loader for the arguments, using the file extensions of the project configuration
+    /// if no language configuration file is given. The stack graphs definitions and builtins of the
+    /// project configuration are ignored, because the provided languages include them.
+    pub fn get_with_config(
+        &self,
+        configurations: Vec<LanguageConfiguration>,
+        config: &Config,
+    ) -> Result<Loader, LoadError<'static>> {
+        let mut loader = Loader::from_language_configurations(configurations, self.scope.clone())?;
+        set_language_detector(&mut loader, &self.language_config, config)?;
+        if self.null_language || config.null_language {
+            loader.set_fallback_analyzer(NullLanguage);
+        }
+        if let Some(max_tree_depth) = self.max_tree_depth {
+            loader.set_max_tree_depth(Some(max_tree_depth));
+        }
         Ok(loader)
     }
 }
+
+fn set_language_detector(
+    loader: &mut Loader,
+    language_config: &Option<PathBuf>,
+    config: &Config,
+) -> Result<(), LoadError<'static>> {
+    if let Some(language_config) = language_config {
+        loader.set_language_detector(ExtensionLanguageDetector::from_config_path(
+            language_config,
+        )?);
+    } else if config.has_extensions() {
+        loader.set_language_detector(config.language_detector());
+    }
+    Ok(())
+}
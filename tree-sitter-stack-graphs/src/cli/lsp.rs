@@ -31,15 +31,18 @@ use tower_lsp::Server;
 use crate::cli::index::Indexer;
 use crate::cli::query::Querier;
 use crate::cli::query::QueryError;
+use crate::cli::query::QueryLimits;
 use crate::cli::util::duration_from_milliseconds_str;
 use crate::cli::util::duration_from_seconds_str;
 use crate::cli::util::reporter::Reporter;
 use crate::cli::util::SourcePosition;
 use crate::cli::util::SourceSpan;
+use crate::config::Config;
 use crate::loader::Loader;
 use crate::AtomicCancellationFlag;
 use crate::CancelAfterDuration;
 use crate::CancellationFlag;
+use crate::NoCancellation;
 
 #[derive(Args, Clone)]
 pub struct LspArgs {
@@ -69,6 +72,13 @@ pub struct LspArgs {
 }
 
 impl LspArgs {
+    /// Uses the values of the project configuration for the arguments that are not given.
+    pub fn with_config(mut self, config: &Config) -> Self {
+        self.max_file_index_time = self.max_file_index_time.or(config.limits.max_file_time);
+        self.max_query_time = self.max_query_time.or(config.limits.max_query_time);
+        self
+    }
+
     pub fn run(self, db_path: PathBuf, loader: Loader) -> anyhow::Result<()> {
         let rt = tokio::runtime::Runtime::new()?;
         rt.block_on(async {
@@ -287,11 +297,21 @@ impl Backend {
         };
         let result = {
             let mut querier = Querier::new(&mut db, &reporter);
-            let cancellation_flag = CancelAfterDuration::from_option(self.args.max_query_time);
-            querier.definitions(reference, cancellation_flag.as_ref())
+            querier.set_limits(QueryLimits {
+                max_duration: self.args.max_query_time,
+                ..Default::default()
+            });
+            querier.definitions(reference, &NoCancellation)
         };
         match result {
-            Ok(result) => result.into_iter().flat_map(|r| r.targets).collect(),
+            Ok(result) => {
+                if let Some(limit) = result.iter().find_map(|r| r.limit_hit) {
+                    self.logger
+                        .info(format!("query returned partial results: {}", limit))
+                        .await;
+                }
+                result.into_iter().flat_map(|r| r.targets).collect()
+            }
             Err(QueryError::Cancelled(at)) => {
                 self.logger
                     .error(format!("query timed out at {}", at,))
@@ -637,15 +657,7 @@ impl Reporter for LspReporter {
 impl SourceSpan {
     fn try_into_location(self) -> std::result::Result<Location, ()> {
         let uri = Url::from_file_path(self.path)?;
-        let start = Position {
-            line: self.span.start.line as u32,
-            character: self.span.start.column.grapheme_offset as u32,
-        };
-        let end = Position {
-            line: self.span.end.line as u32,
-            character: self.span.end.column.grapheme_offset as u32,
-        };
-        let range = Range { start, end };
+        let range = (&self.span).into();
         Ok(Location { uri, range })
     }
 }
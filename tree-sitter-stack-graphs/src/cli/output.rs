@@ -0,0 +1,360 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Machine-readable output of CLI commands.
+//!
+//! Commands that support `--format json` print a single JSON document, which is one of the output
+//! types in this module. The JSON Schemas of these documents are printed by the `schema` command,
+//! so that scripts and editors can rely on their shape. All line and column numbers are 1-based,
+//! and columns count graphemes, like the positions in the text output of the commands.
+
+use clap::ValueEnum;
+use lsp_positions::Position;
+use lsp_positions::Span;
+use schemars::JsonSchema;
+use serde::Serialize;
+use stack_graphs::storage::DatabaseStats;
+use stack_graphs::storage::FileStatus;
+use std::path::Path;
+
+use crate::cli::query::QueryResult;
+use crate::cli::test::TestFailureKind;
+use crate::cli::util::SourcePosition;
+use crate::cli::util::SourceSpan;
+use crate::test::TestResult;
+
+/// Output format of commands that support machine-readable output
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    Text,
+    /// JSON document, described by the schema printed by the `schema` command
+    Json,
+}
+
+/// A location in a source file.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct Location {
+    /// The path of the file.
+    pub path: String,
+    /// The line of the start of the location.
+    pub line: usize,
+    /// The column of the start of the location.
+    pub column: usize,
+    /// The line of the end of the location, if the location is a range.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+    /// The column of the end of the location, if the location is a range.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_column: Option<usize>,
+}
+
+impl Location {
+    pub fn from_position(path: &Path, position: &Position) -> Self {
+        Self {
+            path: path.to_string_lossy().into_owned(),
+            line: position.line + 1,
+            column: position.column.grapheme_offset + 1,
+            end_line: None,
+            end_column: None,
+        }
+    }
+
+    pub fn from_span(path: &Path, span: &Span) -> Self {
+        Self {
+            path: path.to_string_lossy().into_owned(),
+            line: span.start.line + 1,
+            column: span.start.column.grapheme_offset + 1,
+            end_line: Some(span.end.line + 1),
+            end_column: Some(span.end.column.grapheme_offset + 1),
+        }
+    }
+}
+
+impl From<&SourceSpan> for Location {
+    fn from(value: &SourceSpan) -> Self {
+        Self::from_span(&value.path, &value.span)
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// query
+
+/// Output of the `query definition` command.
+#[derive(Clone, Debug, Default, Serialize, JsonSchema)]
+pub struct QueryOutput {
+    /// The queried positions, in the order in which they were given.
+    pub queries: Vec<PositionQueryOutput>,
+}
+
+/// The references at a queried position, and their definitions.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct PositionQueryOutput {
+    /// The queried position.
+    pub position: Location,
+    /// The references at the position. The list is empty if the file is not indexed, or has no
+    /// references at the position.
+    pub references: Vec<ReferenceOutput>,
+}
+
+/// A reference and its definitions.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct ReferenceOutput {
+    /// The location of the reference.
+    pub location: Location,
+    /// The limit that stopped the search for definitions, if any, in which case the definitions
+    /// may be incomplete.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_hit: Option<String>,
+    /// The definitions of the reference, most likely definition first.
+    pub definitions: Vec<DefinitionOutput>,
+}
+
+/// A definition of a reference.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct DefinitionOutput {
+    /// The location of the definition.
+    pub location: Location,
+    /// The confidence that this is the intended definition, between 0 and 1.
+    pub confidence: f64,
+    /// The steps where the path to the definition enters a file, which are usually the imports
+    /// or exports that lead to the definition. Only included with `--show-paths`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub via: Option<Vec<PathStepOutput>>,
+}
+
+/// A step of the path from a reference to a definition.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct PathStepOutput {
+    /// The symbol of the step, if it has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    /// The location of the step, if it has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+}
+
+impl PositionQueryOutput {
+    pub fn new(position: &SourcePosition, results: &[QueryResult]) -> Self {
+        let references = results
+            .iter()
+            .map(|result| ReferenceOutput {
+                location: (&result.source).into(),
+                limit_hit: result.limit_hit.map(|limit| limit.to_string()),
+                definitions: result
+                    .ranked_targets()
+                    .into_iter()
+                    .map(|(definition, confidence)| DefinitionOutput {
+                        location: definition.into(),
+                        confidence,
+                        via: result.path_to(definition).map(|path| {
+                            path.file_crossings()
+                                .map(|step| PathStepOutput {
+                                    symbol: step.symbol.clone(),
+                                    location: step.source.as_ref().map(Location::from),
+                                })
+                                .collect()
+                        }),
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self {
+            position: Location {
+                path: position.path.to_string_lossy().into_owned(),
+                line: position.line + 1,
+                column: position.column + 1,
+                end_line: None,
+                end_column: None,
+            },
+            references,
+        }
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// stats
+
+/// Output of the `stats` command.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct StatsOutput {
+    /// The number of files in the database.
+    pub files: usize,
+    /// The number of files that were indexed completely.
+    pub indexed_files: usize,
+    /// The number of files that were indexed partially.
+    pub partial_files: usize,
+    /// The number of files that failed to index.
+    pub failed_files: usize,
+    /// The total number of nodes.
+    pub nodes: usize,
+    /// The total number of edges.
+    pub edges: usize,
+    /// The total number of partial paths.
+    pub paths: usize,
+    /// The size of the database on disk, in bytes.
+    pub size_on_disk: u64,
+    /// The largest files, largest first.
+    pub largest_files: Vec<FileStatsOutput>,
+    /// The most frequent symbols, most frequent first.
+    pub most_frequent_symbols: Vec<SymbolOutput>,
+}
+
+/// Statistics about a single file.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct FileStatsOutput {
+    /// The path of the file.
+    pub path: String,
+    /// The size of the stored graph and paths of the file, in bytes.
+    pub stored_size: u64,
+    /// The number of nodes of the file.
+    pub nodes: usize,
+    /// The number of edges of the file.
+    pub edges: usize,
+    /// The number of partial paths of the file.
+    pub paths: usize,
+}
+
+/// The number of occurrences of a symbol.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct SymbolOutput {
+    /// The symbol.
+    pub symbol: String,
+    /// The number of nodes with the symbol.
+    pub count: usize,
+}
+
+impl StatsOutput {
+    pub fn new(stats: &DatabaseStats, top: usize) -> Self {
+        let count =
+            |f: fn(&FileStatus) -> bool| stats.files.iter().filter(|s| f(&s.status)).count();
+        Self {
+            files: stats.files.len(),
+            indexed_files: count(|s| matches!(s, FileStatus::Indexed)),
+            partial_files: count(|s| matches!(s, FileStatus::Partial(_))),
+            failed_files: count(|s| matches!(s, FileStatus::Error(_))),
+            nodes: stats.node_count(),
+            edges: stats.edge_count(),
+            paths: stats.path_count(),
+            size_on_disk: stats.size_on_disk,
+            largest_files: stats
+                .largest_files(top)
+                .into_iter()
+                .map(|file| FileStatsOutput {
+                    path: file.path.to_string_lossy().into_owned(),
+                    stored_size: file.stored_size,
+                    nodes: file.node_count,
+                    edges: file.edge_count,
+                    paths: file.path_count(),
+                })
+                .collect(),
+            most_frequent_symbols: stats
+                .most_frequent_symbols(top)
+                .into_iter()
+                .map(|(symbol, count)| SymbolOutput {
+                    symbol: symbol.to_string(),
+                    count,
+                })
+                .collect(),
+        }
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// test
+
+/// Output of the `test` command.
+#[derive(Clone, Debug, Default, Serialize, JsonSchema)]
+pub struct TestOutput {
+    /// The exit code of the command, which reflects the most severe failure of the tests that
+    /// are not allowed to fail: 0 if all assertions passed, 1 for assertion failures, 2 for
+    /// indexing errors, and 3 for infrastructure errors.
+    pub exit_code: i32,
+    /// The total number of assertions.
+    pub assertions: usize,
+    /// The total number of passed assertions.
+    pub passed: usize,
+    /// The total number of failed assertions.
+    pub failed: usize,
+    /// The test files that contain assertions, or that failed with an error.
+    pub files: Vec<TestFileOutput>,
+}
+
+/// The results of a single test file.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct TestFileOutput {
+    /// The path of the test file.
+    pub path: String,
+    /// The number of assertions in the file.
+    pub assertions: usize,
+    /// The number of passed assertions.
+    pub passed: usize,
+    /// The number of failed assertions.
+    pub failed: usize,
+    /// The failed assertions.
+    pub failures: Vec<TestFailureOutput>,
+    /// The error that prevented the assertions from running, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<TestErrorOutput>,
+    /// Whether the test is allowed to fail, in which case it does not affect the exit code.
+    pub allowed_failure: bool,
+}
+
+/// An error that prevented the assertions of a test file from running.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct TestErrorOutput {
+    /// The kind of error, which is either `indexing` or `infrastructure`.
+    pub kind: TestFailureKind,
+    /// A description of the error.
+    pub message: String,
+}
+
+/// A failed assertion.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct TestFailureOutput {
+    /// The location of the assertion, if the failure belongs to an assertion.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+    /// A description of the failure.
+    pub message: String,
+}
+
+impl TestOutput {
+    /// Adds the result of the given test file. Files without assertions or errors are not
+    /// included.
+    pub fn add(
+        &mut self,
+        path: &Path,
+        result: &TestResult,
+        error: Option<(TestFailureKind, String)>,
+        allowed_failure: bool,
+    ) {
+        self.assertions += result.count();
+        self.passed += result.success_count();
+        self.failed += result.failure_count();
+        if result.count() == 0 && error.is_none() {
+            return;
+        }
+        self.files.push(TestFileOutput {
+            path: path.to_string_lossy().into_owned(),
+            assertions: result.count(),
+            passed: result.success_count(),
+            failed: result.failure_count(),
+            failures: result
+                .failures_iter()
+                .map(|failure| TestFailureOutput {
+                    location: failure
+                        .location()
+                        .map(|(path, position)| Location::from_position(path, position)),
+                    message: failure.to_string(),
+                })
+                .collect(),
+            error: error.map(|(kind, message)| TestErrorOutput { kind, message }),
+            allowed_failure,
+        });
+    }
+}
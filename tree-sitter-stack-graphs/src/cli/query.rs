@@ -5,21 +5,43 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::cell::Cell;
+use std::collections::HashMap;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
 use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
 use clap::ValueHint;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::Degree;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::observer::QueryObserver;
+use stack_graphs::partial::rank_by_confidence;
+use stack_graphs::partial::PartialPath;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::partial::PathQuality;
+use stack_graphs::stitching::Database;
+use stack_graphs::stitching::ForwardCandidates;
 use stack_graphs::stitching::ForwardPartialPathStitcher;
 use stack_graphs::stitching::Stats as StitchingStats;
 use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::stitching::VisibilityPolicy;
 use stack_graphs::storage::FileStatus;
 use stack_graphs::storage::SQLiteReader;
+use stack_graphs::storage::StorageError;
 use thiserror::Error;
 use tree_sitter_graph::parse_error::Excerpt;
 
+use crate::cli::output::OutputFormat;
+use crate::cli::output::PositionQueryOutput;
+use crate::cli::output::QueryOutput;
+use crate::cli::util::duration_from_seconds_str;
 use crate::cli::util::print_database_stats;
 use crate::cli::util::print_stitching_stats;
 use crate::cli::util::reporter::ConsoleReporter;
@@ -28,7 +50,9 @@ use crate::cli::util::sha1;
 use crate::cli::util::wait_for_input;
 use crate::cli::util::SourcePosition;
 use crate::cli::util::SourceSpan;
+use crate::config::Config;
 use crate::loader::FileReader;
+use crate::loader::BUILTINS_FILENAME;
 use crate::CancellationFlag;
 use crate::NoCancellation;
 
@@ -41,17 +65,71 @@ pub struct QueryArgs {
     #[clap(long)]
     pub stats: bool,
 
+    /// Maximum number of files loaded from the database per query.
+    #[clap(long, value_name = "COUNT")]
+    pub max_files_loaded: Option<usize>,
+
+    /// Maximum number of paths found per query.
+    #[clap(long, value_name = "COUNT")]
+    pub max_paths: Option<usize>,
+
+    /// Maximum runtime per query in seconds.
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        value_parser = duration_from_seconds_str,
+    )]
+    pub max_query_time: Option<Duration>,
+
+    /// Reuse the definitions found for a reference for other references to the same symbol
+    /// in the same scope.
+    #[clap(long)]
+    pub cache_results: bool,
+
+    /// Show the files and nodes crossed by the path to each definition.
+    #[clap(long)]
+    pub show_paths: bool,
+
+    /// Use the given variant of files, if it exists, instead of the default variant.
+    /// Can be specified multiple times, in order of preference.
+    #[clap(long, value_name = "NAME")]
+    pub variant: Vec<String>,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text, conflicts_with = "stats")]
+    pub format: OutputFormat,
+
     #[clap(subcommand)]
     target: Target,
 }
 
 impl QueryArgs {
+    /// Uses the values of the project configuration for the arguments that are not given.
+    pub fn with_config(mut self, config: &Config) -> Self {
+        self.max_files_loaded = self.max_files_loaded.or(config.limits.max_files_loaded);
+        self.max_paths = self.max_paths.or(config.limits.max_paths);
+        self.max_query_time = self.max_query_time.or(config.limits.max_query_time);
+        self
+    }
+
     pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
         if self.wait_at_start {
             wait_for_input()?;
         }
-        let mut db = SQLiteReader::open(&db_path)?;
-        let stitching_stats = self.target.run(&mut db, self.stats)?;
+        let mut db = SQLiteReader::open(db_path)?.with_variants(self.variant)?;
+        let limits = QueryLimits {
+            max_files_loaded: self.max_files_loaded,
+            max_paths: self.max_paths,
+            max_duration: self.max_query_time,
+        };
+        let stitching_stats = self.target.run(
+            &mut db,
+            limits,
+            self.cache_results,
+            self.show_paths,
+            self.stats,
+            self.format,
+        )?;
         if self.stats {
             println!();
             print_stitching_stats(stitching_stats);
@@ -68,12 +146,26 @@ pub enum Target {
 }
 
 impl Target {
-    fn run(self, db: &mut SQLiteReader, collect_stats: bool) -> anyhow::Result<StitchingStats> {
-        let reporter = ConsoleReporter::details();
+    fn run(
+        self,
+        db: &mut SQLiteReader,
+        limits: QueryLimits,
+        cache_results: bool,
+        show_paths: bool,
+        collect_stats: bool,
+        format: OutputFormat,
+    ) -> anyhow::Result<StitchingStats> {
+        let reporter = match format {
+            OutputFormat::Text => ConsoleReporter::details(),
+            OutputFormat::Json => ConsoleReporter::none(),
+        };
         let mut querier = Querier::new(db, &reporter);
+        querier.set_limits(limits);
+        querier.set_cache_results(cache_results);
+        querier.set_collect_paths(show_paths);
         querier.set_collect_stats(collect_stats);
         match self {
-            Self::Definition(cmd) => cmd.run(&mut querier)?,
+            Self::Definition(cmd) => cmd.run(&mut querier, format)?,
         }
         Ok(querier.into_stats())
     }
@@ -92,26 +184,28 @@ pub struct Definition {
 }
 
 impl Definition {
-    pub fn run(self, querier: &mut Querier) -> anyhow::Result<()> {
+    pub fn run(self, querier: &mut Querier, format: OutputFormat) -> anyhow::Result<()> {
         let cancellation_flag = NoCancellation;
         let mut file_reader = FileReader::new();
+        let mut output = QueryOutput::default();
         for mut reference in self.references {
             reference.canonicalize()?;
 
             let results = querier.definitions(reference.clone(), &cancellation_flag)?;
+            if format == OutputFormat::Json {
+                output
+                    .queries
+                    .push(PositionQueryOutput::new(&reference, &results));
+                continue;
+            }
             let numbered = results.len() > 1;
             let indent = if numbered { 6 } else { 0 };
             if numbered {
                 println!("found {} references at position", results.len());
             }
-            for (
-                idx,
-                QueryResult {
-                    source: reference,
-                    targets: definitions,
-                },
-            ) in results.into_iter().enumerate()
-            {
+            for (idx, result) in results.iter().enumerate() {
+                let reference = &result.source;
+                let definitions = result.ranked_targets();
                 if numbered {
                     println!("{:4}: queried reference", idx);
                 } else {
@@ -127,12 +221,42 @@ impl Definition {
                         indent
                     )
                 );
+                if let Some(limit) = result.limit_hit {
+                    println!("{}search stopped early: {}", " ".repeat(indent), limit);
+                }
                 match definitions.len() {
                     0 => println!("{}has no definitions", " ".repeat(indent)),
                     1 => println!("{}has definition", " ".repeat(indent)),
                     n => println!("{}has {} definitions", " ".repeat(indent), n),
                 }
-                for definition in definitions.into_iter() {
+                let ambiguous = definitions.len() > 1;
+                for (definition, confidence) in definitions.into_iter() {
+                    if let Some(path) = result.path_to(definition) {
+                        for step in path.file_crossings() {
+                            let position = match &step.source {
+                                Some(source) => format!(
+                                    "{}:{}:{}",
+                                    source.path.display(),
+                                    source.first_line() + 1,
+                                    source.span.start.column.grapheme_offset + 1
+                                ),
+                                None => "<unknown position>".to_string(),
+                            };
+                            match &step.symbol {
+                                Some(symbol) => {
+                                    println!("{}via {} at {}", " ".repeat(indent), symbol, position)
+                                }
+                                None => println!("{}via {}", " ".repeat(indent), position),
+                            }
+                        }
+                    }
+                    if ambiguous {
+                        println!(
+                            "{}candidate with confidence {:.2}",
+                            " ".repeat(indent),
+                            confidence
+                        );
+                    }
                     print!(
                         "{}",
                         Excerpt::from_source(
@@ -146,6 +270,9 @@ impl Definition {
                 }
             }
         }
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
         Ok(())
     }
 }
@@ -153,6 +280,9 @@ impl Definition {
 pub struct Querier<'a> {
     db: &'a mut SQLiteReader,
     reporter: &'a dyn Reporter,
+    limits: QueryLimits,
+    cache: Option<ResultCache>,
+    collect_paths: bool,
     stats: Option<StitchingStats>,
 }
 
@@ -161,10 +291,46 @@ impl<'a> Querier<'a> {
         Self {
             db,
             reporter,
+            limits: QueryLimits::default(),
+            cache: None,
+            collect_paths: false,
             stats: None,
         }
     }
 
+    /// Reuse the results of earlier queries of this querier for references that must resolve
+    /// identically: references to the same symbol, in the same file, with the same outgoing
+    /// edges. Cached results are discarded when the database changes.
+    pub fn set_cache_results(&mut self, cache_results: bool) {
+        if !cache_results {
+            self.cache = None;
+        } else if self.cache.is_none() {
+            self.cache = Some(ResultCache::default());
+        }
+    }
+
+    /// Returns the number of references that were resolved from the result cache.
+    pub fn cache_hits(&self) -> usize {
+        self.cache.as_ref().map_or(0, |c| c.hits)
+    }
+
+    /// Set the resource limits for each query. A query that hits a limit returns the
+    /// definitions found so far, and reports the limit in [`QueryResult::limit_hit`][].
+    pub fn set_limits(&mut self, limits: QueryLimits) {
+        self.limits = limits;
+    }
+
+    /// Return the full path to each definition in [`QueryResult::paths`][], in addition to the
+    /// definitions themselves. Results cached before paths were collected are discarded.
+    pub fn set_collect_paths(&mut self, collect_paths: bool) {
+        if collect_paths && !self.collect_paths {
+            if let Some(cache) = &mut self.cache {
+                cache.entries.clear();
+            }
+        }
+        self.collect_paths = collect_paths;
+    }
+
     pub fn set_collect_stats(&mut self, collect_stats: bool) {
         if !collect_stats {
             self.stats = None;
@@ -186,7 +352,7 @@ impl<'a> Querier<'a> {
             .db
             .status_for_file(&reference.path.to_string_lossy(), tag.as_ref())?
         {
-            FileStatus::Indexed => {}
+            FileStatus::Indexed | FileStatus::Partial(_) => {}
             _ => {
                 self.reporter.started(&log_path);
                 self.reporter.failed(&log_path, "file not indexed", None);
@@ -196,9 +362,14 @@ impl<'a> Querier<'a> {
 
         self.reporter.started(&log_path);
 
+        if let Some(cache) = &mut self.cache {
+            cache.check_data_version(self.db.data_version()?);
+        }
         self.db
             .load_graph_for_file(&reference.path.to_string_lossy())?;
-        let (graph, _, _) = self.db.get();
+        let path_count = Cell::new(0);
+        let mut candidates = LimitedCandidates::new(self.db, &self.limits, &path_count);
+        let (graph, _, _) = candidates.db.get();
 
         let starting_nodes = reference.iter_references(graph).collect::<Vec<_>>();
         if starting_nodes.is_empty() {
@@ -214,69 +385,147 @@ impl<'a> Querier<'a> {
                 span,
             };
 
-            let mut reference_paths = Vec::new();
-            let stitcher_config = StitcherConfig::default()
-                // always detect similar paths, we don't know the language configurations for the data in the database
-                .with_detect_similar_paths(true)
-                .with_collect_stats(self.stats.is_some());
-            let ref_result = ForwardPartialPathStitcher::find_all_complete_partial_paths(
-                self.db,
-                std::iter::once(node),
-                stitcher_config,
-                &cancellation_flag,
-                |_g, _ps, p| {
-                    reference_paths.push(p.clone());
-                },
-            );
-            match ref_result {
-                Ok(ref_stats) => {
-                    if let Some(stats) = &mut self.stats {
-                        *stats += ref_stats
-                    }
-                }
-                Err(err) => {
-                    self.reporter.failed(&log_path, "query timed out", None);
-                    return Err(err.into());
+            let (graph, _, _) = candidates.db.get();
+            let cache_key = self
+                .cache
+                .as_ref()
+                .and_then(|_| ReferenceKey::new(graph, node));
+            if let (Some(cache), Some(key)) = (&mut self.cache, &cache_key) {
+                if let Some(entry) = cache.get(key) {
+                    result.push(QueryResult {
+                        source: reference_span,
+                        targets: entry.targets,
+                        qualities: entry.qualities,
+                        paths: entry.paths,
+                        limit_hit: None,
+                    });
+                    continue;
                 }
             }
 
-            let (graph, partials, _) = self.db.get();
-            let mut actual_paths = Vec::new();
-            for reference_path in &reference_paths {
-                if let Err(err) = cancellation_flag.check("shadowing") {
-                    self.reporter.failed(&log_path, "query timed out", None);
-                    return Err(err.into());
+            // references in hot files may have been resolved in advance, in which case their
+            // paths are not stitched again
+            let actual_paths = match candidates
+                .db
+                .load_resolved_paths(node, &cancellation_flag)?
+            {
+                Some(mut resolved_paths) => {
+                    if let Some(max_paths) = self.limits.max_paths {
+                        resolved_paths.truncate(max_paths);
+                    }
+                    resolved_paths
                 }
-                if reference_paths
-                    .iter()
-                    .all(|other| !other.shadows(partials, reference_path))
-                {
-                    actual_paths.push(reference_path.clone());
+                None => {
+                    let mut reference_paths = Vec::new();
+                    let stitcher_config = StitcherConfig::default()
+                        // always detect similar paths, we don't know the language configurations for the data in the database
+                        .with_detect_similar_paths(true)
+                        .with_collect_stats(self.stats.is_some());
+                    // the path limit applies to each reference, the other limits to the whole query
+                    path_count.set(0);
+                    if candidates.limit_hit == Some(QueryLimit::Paths) {
+                        candidates.limit_hit = None;
+                    }
+                    let ref_result = ForwardPartialPathStitcher::find_all_complete_partial_paths(
+                        &mut candidates,
+                        std::iter::once(node),
+                        stitcher_config,
+                        &cancellation_flag,
+                        |_g, _ps, p| {
+                            reference_paths.push(p.clone());
+                            path_count.set(path_count.get() + 1);
+                        },
+                    );
+                    match ref_result {
+                        Ok(ref_stats) => {
+                            if let Some(stats) = &mut self.stats {
+                                *stats += ref_stats
+                            }
+                        }
+                        Err(_) if candidates.limit_hit.is_some() => {}
+                        Err(err) => {
+                            self.reporter.failed(&log_path, "query timed out", None);
+                            return Err(err.into());
+                        }
+                    }
+                    if let Some(max_paths) = self.limits.max_paths {
+                        reference_paths.truncate(max_paths);
+                    }
+
+                    let (_, partials, _) = candidates.db.get();
+                    let mut actual_paths = Vec::new();
+                    for reference_path in &reference_paths {
+                        if let Err(err) = cancellation_flag.check("shadowing") {
+                            self.reporter.failed(&log_path, "query timed out", None);
+                            return Err(err.into());
+                        }
+                        if reference_paths
+                            .iter()
+                            .all(|other| !other.shadows(partials, reference_path))
+                        {
+                            actual_paths.push(reference_path.clone());
+                        }
+                    }
+                    actual_paths
                 }
-            }
+            };
 
-            let definitions = actual_paths
-                .into_iter()
-                .filter_map(|path| {
-                    let span = match graph.source_info(path.end_node) {
-                        Some(p) => p.span.clone(),
-                        None => return None,
-                    };
-                    let path = match graph[path.end_node].id().file() {
-                        Some(f) => PathBuf::from(graph[f].name()),
-                        None => return None,
-                    };
-                    Some(SourceSpan { path, span })
-                })
-                .collect::<Vec<_>>();
+            let (graph, partials, _) = candidates.db.get();
+            let mut definitions = Vec::new();
+            let mut qualities = Vec::new();
+            let mut definition_paths = Vec::new();
+            for path in actual_paths {
+                let span = match graph.source_info(path.end_node) {
+                    Some(p) => p.span.clone(),
+                    None => continue,
+                };
+                let file = match graph[path.end_node].id().file() {
+                    Some(f) => PathBuf::from(graph[f].name()),
+                    None => continue,
+                };
+                definitions.push(SourceSpan { path: file, span });
+                qualities
+                    .push(path.quality(graph, partials, |f| graph[f].name() == BUILTINS_FILENAME));
+                if self.collect_paths {
+                    definition_paths
+                        .push(DefinitionPath::from_partial_path(graph, partials, &path));
+                }
+            }
 
+            if let (Some(cache), Some(key), None) =
+                (&mut self.cache, cache_key, candidates.limit_hit)
+            {
+                cache.insert(
+                    key,
+                    CachedResult {
+                        targets: definitions.clone(),
+                        qualities: qualities.clone(),
+                        paths: definition_paths.clone(),
+                    },
+                );
+            }
             result.push(QueryResult {
                 source: reference_span,
                 targets: definitions,
+                qualities,
+                paths: definition_paths,
+                limit_hit: candidates.limit_hit,
             });
         }
 
         let count: usize = result.iter().map(|r| r.targets.len()).sum();
+        if let Some(limit) = result.iter().find_map(|r| r.limit_hit) {
+            self.reporter.cancelled(
+                &log_path,
+                &format!(
+                    "found {} definitions for {} references before reaching a limit",
+                    count,
+                    result.len()
+                ),
+                Some(&limit.to_string()),
+            );
+            return Ok(result);
+        }
         self.reporter.succeeded(
             &log_path,
             &format!(
@@ -320,6 +569,311 @@ impl From<crate::CancellationError> for QueryError {
 pub struct QueryResult {
     pub source: SourceSpan,
     pub targets: Vec<SourceSpan>,
+    /// The quality of the path to each target, in the same order as the targets.
+    pub qualities: Vec<PathQuality>,
+    /// The path to each target, in the same order as the targets. Only collected if enabled
+    /// with [`Querier::set_collect_paths`][], and empty otherwise.
+    pub paths: Vec<DefinitionPath>,
+    /// The limit that stopped the search for definitions, if any. If set, the targets may be
+    /// incomplete, and an empty list of targets does not mean that the reference has no
+    /// definition.
+    pub limit_hit: Option<QueryLimit>,
+}
+
+impl QueryResult {
+    /// Returns the targets together with the confidence that they are the intended resolution,
+    /// most likely target first.
+    pub fn ranked_targets(&self) -> Vec<(&SourceSpan, f64)> {
+        rank_by_confidence(&self.qualities)
+            .into_iter()
+            .map(|r| (&self.targets[r.index], r.confidence))
+            .collect()
+    }
+
+    /// Returns the path to the given target, if paths were collected. The target must be a
+    /// reference into this result's targets, such as those returned by [`Self::ranked_targets`][].
+    pub fn path_to(&self, target: &SourceSpan) -> Option<&DefinitionPath> {
+        let index = self.targets.iter().position(|t| std::ptr::eq(t, target))?;
+        self.paths.get(index)
+    }
+}
+
+/// The stitched path from a reference to one of its definitions.
+#[derive(Clone, Debug)]
+pub struct DefinitionPath {
+    /// The nodes along the path, starting with the reference and ending with the definition.
+    pub steps: Vec<PathStep>,
+    /// The path in a serializable form, suitable for transport to other processes.
+    pub serialized: stack_graphs::serde::PartialPath,
+}
+
+/// A node along a [`DefinitionPath`][].
+#[derive(Clone, Debug)]
+pub struct PathStep {
+    pub node: stack_graphs::serde::NodeID,
+    /// The source location of the node, if it has one.
+    pub source: Option<SourceSpan>,
+    /// The symbol of the node, if it pushes or pops one.
+    pub symbol: Option<String>,
+}
+
+impl DefinitionPath {
+    fn from_partial_path(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        path: &PartialPath,
+    ) -> Self {
+        let mut steps = Vec::new();
+        let mut edges = path.edges;
+        while let Some(edge) = edges.pop_front(partials) {
+            if let Some(node) = graph.node_for_id(edge.source_node_id) {
+                steps.push(PathStep::from_node(graph, node));
+            }
+        }
+        steps.push(PathStep::from_node(graph, path.end_node));
+        Self {
+            steps,
+            serialized: stack_graphs::serde::PartialPath::from_partial_path(graph, partials, path),
+        }
+    }
+
+    /// Returns the files crossed by this path, in the order in which they are visited.
+    pub fn files(&self) -> Vec<&str> {
+        let mut files = Vec::<&str>::new();
+        for file in self.steps.iter().filter_map(|s| s.node.file.as_deref()) {
+            if files.last() != Some(&file) {
+                files.push(file);
+            }
+        }
+        files
+    }
+
+    /// Returns the steps where this path enters a file, excluding the file of the reference.
+    /// These are usually the imports or exports that lead to the definition.
+    pub fn file_crossings(&self) -> impl Iterator<Item = &PathStep> + '_ {
+        let mut current = self.steps.first().and_then(|s| s.node.file.as_deref());
+        self.steps
+            .iter()
+            .filter(move |step| match step.node.file.as_deref() {
+                Some(file) if Some(file) != current => {
+                    current = Some(file);
+                    true
+                }
+                _ => false,
+            })
+    }
+
+    /// Returns a compact JSON serialization of the path.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.serialized).expect("path serializes")
+    }
+}
+
+impl PathStep {
+    fn from_node(graph: &StackGraph, node: Handle<Node>) -> Self {
+        let id = graph[node].id();
+        let source = id
+            .file()
+            .zip(graph.source_info(node))
+            .map(|(file, info)| SourceSpan {
+                path: PathBuf::from(graph[file].name()),
+                span: info.span.clone(),
+            });
+        Self {
+            node: stack_graphs::serde::NodeID::from_node_id(graph, id),
+            source,
+            symbol: graph[node].symbol().map(|s| graph[s].to_string()),
+        }
+    }
+}
+
+/// Resource limits for a single query. Limits that are `None` are not enforced.
+#[derive(Clone, Debug, Default)]
+pub struct QueryLimits {
+    /// Maximum number of files loaded from the database.
+    pub max_files_loaded: Option<usize>,
+    /// Maximum number of complete paths found for each reference.
+    pub max_paths: Option<usize>,
+    /// Maximum time spent on the query.
+    pub max_duration: Option<Duration>,
+}
+
+/// A query limit that was reached.
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+pub enum QueryLimit {
+    #[error("maximum number of files loaded reached")]
+    FilesLoaded,
+    #[error("maximum number of paths reached")]
+    Paths,
+    #[error("maximum query time reached")]
+    Duration,
+}
+
+/// Identifies references that resolve identically. Complete paths from a reference only depend on
+/// its symbol and the edges leaving it, so references to the same symbol with the same outgoing
+/// edges, usually into the same enclosing scope, resolve to the same definitions.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct ReferenceKey {
+    file: String,
+    symbol: String,
+    /// The (file, local ID, precedence) of the sink of every outgoing edge, in sorted order.
+    sinks: Vec<(Option<String>, u32, i32)>,
+}
+
+impl ReferenceKey {
+    /// Returns the key for a reference, or `None` if its results cannot be shared. Only plain
+    /// push symbol references are keyed, because scoped symbols carry additional context.
+    fn new(graph: &StackGraph, node: Handle<Node>) -> Option<Self> {
+        if !matches!(graph[node], Node::PushSymbol(_)) {
+            return None;
+        }
+        let file = graph[node].file()?;
+        let symbol = graph[node].symbol()?;
+        let mut sinks = Vec::new();
+        for edge in graph.outgoing_edges(node) {
+            if edge.sink == node {
+                return None;
+            }
+            let id = graph[edge.sink].id();
+            let sink_file = id.file().map(|f| graph[f].name().to_string());
+            sinks.push((sink_file, id.local_id(), edge.precedence));
+        }
+        sinks.sort();
+        Some(Self {
+            file: graph[file].name().to_string(),
+            symbol: graph[symbol].to_string(),
+            sinks,
+        })
+    }
+}
+
+/// Definitions found for references, which are valid for a single version of the database.
+#[derive(Default)]
+struct ResultCache {
+    data_version: Vec<i64>,
+    entries: HashMap<ReferenceKey, CachedResult>,
+    hits: usize,
+}
+
+impl ResultCache {
+    /// Discards all cached results if the database changed since they were computed.
+    fn check_data_version(&mut self, data_version: Vec<i64>) {
+        if self.data_version != data_version {
+            self.entries.clear();
+            self.data_version = data_version;
+        }
+    }
+
+    fn get(&mut self, key: &ReferenceKey) -> Option<CachedResult> {
+        let entry = self.entries.get(key)?;
+        self.hits += 1;
+        Some(entry.clone())
+    }
+
+    fn insert(&mut self, key: ReferenceKey, entry: CachedResult) {
+        self.entries.insert(key, entry);
+    }
+}
+
+#[derive(Clone)]
+struct CachedResult {
+    targets: Vec<SourceSpan>,
+    qualities: Vec<PathQuality>,
+    paths: Vec<DefinitionPath>,
+}
+
+/// Candidates from the database that stop path stitching when a query limit is reached. The
+/// limit that was reached is recorded, so that it can be distinguished from other errors.
+struct LimitedCandidates<'a, 'b> {
+    db: &'a mut SQLiteReader,
+    limits: &'a QueryLimits,
+    start: Instant,
+    initial_file_loads: usize,
+    /// The number of complete paths found for the current reference.
+    path_count: &'b Cell<usize>,
+    limit_hit: Option<QueryLimit>,
+}
+
+impl<'a, 'b> LimitedCandidates<'a, 'b> {
+    fn new(db: &'a mut SQLiteReader, limits: &'a QueryLimits, path_count: &'b Cell<usize>) -> Self {
+        let initial_file_loads = db.stats().file_loads;
+        Self {
+            db,
+            limits,
+            start: Instant::now(),
+            initial_file_loads,
+            path_count,
+            limit_hit: None,
+        }
+    }
+
+    /// Checks the limits that must hold before more candidates are loaded.
+    fn check_limits(&mut self) -> Option<QueryLimit> {
+        if self.limit_hit.is_some() {
+            return self.limit_hit;
+        }
+        let path_count = self.path_count.get();
+        if self.limits.max_paths.is_some_and(|max| path_count >= max) {
+            self.limit_hit = Some(QueryLimit::Paths);
+        } else if self
+            .limits
+            .max_duration
+            .is_some_and(|max| self.start.elapsed() >= max)
+        {
+            self.limit_hit = Some(QueryLimit::Duration);
+        }
+        self.limit_hit
+    }
+}
+
+impl ForwardCandidates<Handle<PartialPath>, PartialPath, Database, StorageError>
+    for LimitedCandidates<'_, '_>
+{
+    fn load_forward_candidates(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &dyn stack_graphs::CancellationFlag,
+    ) -> std::result::Result<(), StorageError> {
+        if self.check_limits().is_some() {
+            return Err(StorageError::Cancelled("query limit"));
+        }
+        self.db.load_forward_candidates(path, cancellation_flag)?;
+        // The file of the reference itself is not counted, and the limit is only reached if
+        // more files are needed than allowed.
+        let file_loads = self.db.stats().file_loads - self.initial_file_loads;
+        if self
+            .limits
+            .max_files_loaded
+            .is_some_and(|max| file_loads > max)
+        {
+            self.limit_hit = Some(QueryLimit::FilesLoaded);
+            return Err(StorageError::Cancelled("query limit"));
+        }
+        Ok(())
+    }
+
+    fn get_forward_candidates<R>(&mut self, path: &PartialPath, result: &mut R)
+    where
+        R: std::iter::Extend<Handle<PartialPath>>,
+    {
+        self.db.get_forward_candidates(path, result)
+    }
+
+    fn get_joining_candidate_degree(&self, path: &PartialPath) -> Degree {
+        self.db.get_joining_candidate_degree(path)
+    }
+
+    fn get_graph_partials_and_db(&mut self) -> (&StackGraph, &mut PartialPaths, &Database) {
+        self.db.get_graph_partials_and_db()
+    }
+
+    fn get_observer(&self) -> Option<Arc<dyn QueryObserver>> {
+        self.db.get_observer()
+    }
+
+    fn get_visibility_policy(&self) -> Option<Arc<dyn VisibilityPolicy>> {
+        self.db.get_visibility_policy()
+    }
 }
 
 type Result<T> = std::result::Result<T, QueryError>;
@@ -0,0 +1,60 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use anyhow::anyhow;
+use clap::Args;
+use clap::ValueHint;
+use stack_graphs::trace::TraceRecording;
+use stack_graphs::trace::TraceReplay;
+use std::path::PathBuf;
+
+/// Replay a recorded path stitching trace
+#[derive(Args)]
+pub struct ReplayArgs {
+    /// Trace recording, as written by `visualize --trace-output`.
+    #[clap(
+        value_name = "TRACE_PATH",
+        value_hint = ValueHint::AnyPath,
+    )]
+    pub trace_path: PathBuf,
+
+    /// Print all replayed events, instead of only the ones that diverge from the recording.
+    #[clap(long, short = 'v')]
+    pub verbose: bool,
+}
+
+impl ReplayArgs {
+    pub fn run(self) -> anyhow::Result<()> {
+        let recording: TraceRecording =
+            serde_json::from_str(&std::fs::read_to_string(&self.trace_path)?)?;
+        let mut replay = TraceReplay::new(recording)?;
+        let mut event_count = 0usize;
+        let mut divergence_count = 0usize;
+        while let Some(step) = replay.step() {
+            let step = step?;
+            event_count += 1;
+            if step.diverges() {
+                divergence_count += 1;
+                println!("#{} {}", step.index, step.event);
+                if let Some(replayed) = &step.replayed {
+                    println!("  replayed: {}", replayed);
+                }
+            } else if self.verbose {
+                println!("#{} {}", step.index, step.event);
+            }
+        }
+        if divergence_count > 0 {
+            return Err(anyhow!(
+                "{} of {} events diverged from the recording",
+                divergence_count,
+                event_count
+            ));
+        }
+        println!("Replayed {} events", event_count);
+        Ok(())
+    }
+}
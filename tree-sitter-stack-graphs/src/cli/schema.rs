@@ -0,0 +1,70 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use clap::Args;
+use clap::ValueEnum;
+use clap::ValueHint;
+use schemars::schema_for;
+use stack_graphs::serde::schema;
+use std::path::PathBuf;
+
+use crate::cli::output::QueryOutput;
+use crate::cli::output::StatsOutput;
+use crate::cli::output::TestOutput;
+
+/// Print the JSON Schema of a serialization format
+#[derive(Args)]
+pub struct SchemaArgs {
+    /// Serialization format to describe.
+    #[clap(value_enum, default_value_t = SchemaFormat::Graph)]
+    pub format: SchemaFormat,
+
+    /// Write the schema to the given file, instead of printing it.
+    #[clap(
+        long,
+        short = 'o',
+        value_name = "OUTPUT_PATH",
+        value_hint = ValueHint::AnyPath,
+    )]
+    pub output: Option<PathBuf>,
+}
+
+/// Serialization format described by a schema
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum SchemaFormat {
+    /// Stack graph
+    Graph,
+    /// Single partial path
+    PartialPath,
+    /// List of partial paths
+    Database,
+    /// JSON output of the `query definition` command
+    QueryOutput,
+    /// JSON output of the `stats` command
+    StatsOutput,
+    /// JSON output of the `test` command
+    TestOutput,
+}
+
+impl SchemaArgs {
+    pub fn run(self) -> anyhow::Result<()> {
+        let schema = match self.format {
+            SchemaFormat::Graph => schema::stack_graph(),
+            SchemaFormat::PartialPath => schema::partial_path(),
+            SchemaFormat::Database => schema::database(),
+            SchemaFormat::QueryOutput => schema_for!(QueryOutput),
+            SchemaFormat::StatsOutput => schema_for!(StatsOutput),
+            SchemaFormat::TestOutput => schema_for!(TestOutput),
+        };
+        let json = serde_json::to_string_pretty(&schema)?;
+        match &self.output {
+            Some(output) => std::fs::write(output, json + "\n")?,
+            None => println!("{}", json),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use clap::Args;
+#[cfg(feature = "arrow")]
+use clap::ValueHint;
+#[cfg(feature = "arrow")]
+use stack_graphs::export::arrow;
+use stack_graphs::storage::DatabaseStats;
+use stack_graphs::storage::FileStatus;
+use stack_graphs::storage::SQLiteReader;
+use std::path::Path;
+#[cfg(feature = "arrow")]
+use std::path::PathBuf;
+
+use crate::cli::output::OutputFormat;
+use crate::cli::output::StatsOutput;
+
+#[derive(Args)]
+pub struct StatsArgs {
+    /// Number of largest files and most frequent symbols to show.
+    #[clap(long, value_name = "N", default_value = "10")]
+    pub top: usize,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Also write the statistics of every file to a Parquet file.
+    #[cfg(feature = "arrow")]
+    #[clap(
+        long,
+        value_name = "PARQUET_PATH",
+        value_hint = ValueHint::AnyPath,
+    )]
+    pub parquet: Option<PathBuf>,
+}
+
+impl StatsArgs {
+    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+        let db = SQLiteReader::open(db_path)?;
+        let stats = db.database_stats()?;
+        #[cfg(feature = "arrow")]
+        if let Some(path) = &self.parquet {
+            arrow::write_parquet(
+                std::fs::File::create(path)?,
+                arrow::file_stats_schema(),
+                [arrow::file_stats_to_record_batch(&stats.files)?],
+            )?;
+        }
+        match self.format {
+            OutputFormat::Text => self.print(&stats),
+            OutputFormat::Json => println!(
+                "{}",
+                serde_json::to_string_pretty(&StatsOutput::new(&stats, self.top))?
+            ),
+        }
+        Ok(())
+    }
+
+    fn print(&self, stats: &DatabaseStats) {
+        let count =
+            |f: fn(&FileStatus) -> bool| stats.files.iter().filter(|s| f(&s.status)).count();
+        println!("Files:        {}", stats.files.len());
+        println!(
+            "  indexed:    {}",
+            count(|s| matches!(s, FileStatus::Indexed))
+        );
+        println!(
+            "  partial:    {}",
+            count(|s| matches!(s, FileStatus::Partial(_)))
+        );
+        println!(
+            "  failed:     {}",
+            count(|s| matches!(s, FileStatus::Error(_)))
+        );
+        println!("Nodes:        {}", stats.node_count());
+        println!("Edges:        {}", stats.edge_count());
+        println!("Paths:        {}", stats.path_count());
+        println!("Size on disk: {} bytes", stats.size_on_disk);
+
+        let largest = stats.largest_files(self.top);
+        if !largest.is_empty() {
+            println!();
+            println!("Largest files:");
+            for file in largest {
+                println!(
+                    "  {:>10} bytes  {:>6} nodes  {:>6} edges  {:>6} paths  {}",
+                    file.stored_size,
+                    file.node_count,
+                    file.edge_count,
+                    file.path_count(),
+                    file.path.display(),
+                );
+            }
+        }
+
+        let symbols = stats.most_frequent_symbols(self.top);
+        if !symbols.is_empty() {
+            println!();
+            println!("Most frequent symbols:");
+            for (symbol, count) in symbols {
+                println!("  {:>10}  {}", count, symbol);
+            }
+        }
+    }
+}
@@ -21,7 +21,8 @@ use crate::cli::util::reporter::Reporter;
 #[clap(group(
     ArgGroup::new("paths")
         .required(true)
-        .args(&["source_paths", "all"]),
+        .multiple(true)
+        .args(&["source_paths", "all", "failed", "partial"]),
 ))]
 pub struct StatusArgs {
     /// Source file or directory paths.
@@ -32,9 +33,20 @@ pub struct StatusArgs {
     pub source_paths: Vec<PathBuf>,
 
     /// Show status of all indexed source paths.
-    #[clap(long, short = 'a')]
+    #[clap(long, short = 'a', conflicts_with_all = &["source_paths", "failed", "partial"])]
     pub all: bool,
 
+    /// Only show source paths that failed to index. Shows all failed source paths,
+    /// if no source paths are given.
+    #[clap(long)]
+    pub failed: bool,
+
+    /// Only show source paths that were partially indexed, for example because path
+    /// computation timed out. Shows all partially indexed source paths, if no source
+    /// paths are given.
+    #[clap(long, conflicts_with = "failed")]
+    pub partial: bool,
+
     #[clap(long, short = 'v')]
     pub verbose: bool,
 }
@@ -43,7 +55,15 @@ impl StatusArgs {
     pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
         let reporter = self.get_reporter();
         let mut db = SQLiteReader::open(&db_path)?;
-        if self.all {
+        if self.source_paths.is_empty() && self.failed {
+            let mut files = db.failed_files()?;
+            let mut entries = files.try_iter()?;
+            self.status(&mut entries, &reporter)?;
+        } else if self.source_paths.is_empty() && self.partial {
+            let mut files = db.partial_files()?;
+            let mut entries = files.try_iter()?;
+            self.status(&mut entries, &reporter)?;
+        } else if self.all {
             let mut files = db.list_all()?;
             let mut entries = files.try_iter()?;
             self.status(&mut entries, &reporter)?;
@@ -73,14 +93,32 @@ impl StatusArgs {
     ) -> anyhow::Result<()> {
         for entry in entries {
             let entry = entry?;
+            if self.failed && !matches!(entry.status, FileStatus::Error(_)) {
+                continue;
+            }
+            if self.partial && !matches!(entry.status, FileStatus::Partial(_)) {
+                continue;
+            }
             reporter.started(&entry.path);
             match &entry.status {
                 FileStatus::Missing => {
                     reporter.cancelled(&entry.path, "missing", None);
                 }
-                FileStatus::Indexed => {
+                FileStatus::Indexed if entry.metadata.is_empty() => {
                     reporter.succeeded(&entry.path, "indexed", None);
                 }
+                FileStatus::Indexed => {
+                    let metadata = entry
+                        .metadata
+                        .iter()
+                        .map(|(key, value)| format!("{}: {}", key, value))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    reporter.succeeded(&entry.path, "indexed", Some(&metadata));
+                }
+                FileStatus::Partial(reason) => {
+                    reporter.cancelled(&entry.path, "partially indexed", Some(reason));
+                }
                 FileStatus::Error(error) => {
                     reporter.failed(&entry.path, "failed", Some(error));
                 }
@@ -9,6 +9,7 @@ use anyhow::anyhow;
 use clap::Args;
 use clap::ValueEnum;
 use clap::ValueHint;
+use ignore::overrides::OverrideBuilder;
 use itertools::Itertools;
 use stack_graphs::arena::Handle;
 use stack_graphs::graph::File;
@@ -20,14 +21,18 @@ use stack_graphs::stitching::DatabaseCandidates;
 use stack_graphs::stitching::ForwardPartialPathStitcher;
 use stack_graphs::stitching::StitcherConfig;
 use std::collections::hash_map::Entry;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::path::Path;
 use std::path::PathBuf;
 use std::time::Duration;
+use thiserror::Error;
 use tree_sitter::Language;
 use tree_sitter_graph::Variables;
 
+use crate::cli::output::OutputFormat;
+use crate::cli::output::TestOutput;
 use crate::cli::util::duration_from_seconds_str;
 use crate::cli::util::iter_files_and_directories;
 use crate::cli::util::reporter::ConsoleReporter;
@@ -35,6 +40,7 @@ use crate::cli::util::reporter::Level;
 use crate::cli::util::CLIFileReporter;
 use crate::cli::util::ExistingPathBufValueParser;
 use crate::cli::util::PathSpec;
+use crate::config::Config;
 use crate::loader::ContentProvider;
 use crate::loader::FileReader;
 use crate::loader::LanguageConfiguration;
@@ -62,6 +68,15 @@ use crate::CancellationFlag;
     Note that on Windows the path specification must be valid Unicode, but all valid
     paths (including ones that are not valid Unicode) are accepted as arguments, and
     placeholders are correctly subtituted for all paths.
+
+EXIT CODES:
+    The exit code reflects the most severe failure among the tests that are not
+    allowed to fail with --allow-failure:
+         0   all assertions passed
+         1   assertions failed
+         2   indexing errors, such as parse errors or failing TSG rules
+         3   infrastructure errors, such as unreadable files, invalid test files,
+             or languages that failed to load
 "#)]
 pub struct TestArgs {
     /// Test file or directory paths. Files or files inside directories ending in .skip are excluded.
@@ -81,6 +96,10 @@ pub struct TestArgs {
     #[clap(long)]
     pub hide_error_details: bool,
 
+    /// Show failure error details as a diff of expected and actual results.
+    #[clap(long, conflicts_with = "hide_error_details")]
+    pub diff: bool,
+
     /// Show skipped files in output.
     #[clap(long)]
     pub show_skipped: bool,
@@ -143,6 +162,23 @@ pub struct TestArgs {
         value_parser = duration_from_seconds_str,
     )]
     pub max_test_time: Option<Duration>,
+
+    /// Output format. JSON output replaces the progress and summary output, and does not
+    /// include the paths of saved graphs, paths, or visualizations.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Allow tests matching the given glob to fail, without affecting the exit code. Failures
+    /// are still reported. Globs use gitignore syntax and are relative to the test path argument
+    /// that contains the test. Can be specified multiple times.
+    #[clap(long, value_name = "GLOB")]
+    pub allow_failure: Vec<String>,
+
+    /// Fail assertions if paths of the asserted references jump to scope while the scope stack
+    /// is empty, even if the references resolve as expected. Such paths are otherwise dropped
+    /// silently, and usually point at mistakes in the stack graph construction rules.
+    #[clap(long)]
+    pub deny_empty_scope_stacks: bool,
 }
 
 /// Flag to control output
@@ -161,12 +197,151 @@ impl OutputMode {
     }
 }
 
+/// Kind of failure of a test file, in increasing order of severity
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TestFailureKind {
+    /// Assertions in the test file failed.
+    Assertion,
+    /// The stack graph or the partial paths of the test file could not be computed.
+    Indexing,
+    /// The test could not be run, for example because the file could not be read, is not a
+    /// valid test file, or its language failed to load.
+    Infrastructure,
+}
+
+impl TestFailureKind {
+    /// Returns the exit code of the test command, if this is the most severe failure.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Assertion => 1,
+            Self::Indexing => 2,
+            Self::Infrastructure => 3,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Assertion => "assertion failures",
+            Self::Indexing => "indexing errors",
+            Self::Infrastructure => "infrastructure errors",
+        }
+    }
+}
+
+/// Error returned by the test command if tests failed that were not allowed to fail
+#[derive(Debug, Error)]
+#[error("{summary}")]
+pub struct TestRunError {
+    pub kind: TestFailureKind,
+    summary: String,
+}
+
+impl TestRunError {
+    pub fn exit_code(&self) -> i32 {
+        self.kind.exit_code()
+    }
+}
+
+/// Exits the process with the exit code of a failed test run, so that CI can distinguish the
+/// kinds of failures. Other results are returned unchanged.
+pub(crate) fn exit_on_test_failure(result: anyhow::Result<()>) -> anyhow::Result<()> {
+    if let Err(err) = &result {
+        if let Some(err) = err.downcast_ref::<TestRunError>() {
+            eprintln!("Error: {}", err);
+            std::process::exit(err.exit_code());
+        }
+    }
+    result
+}
+
+/// Error that marks a test file whose stack graph or paths could not be computed
+#[derive(Debug, Error)]
+#[error("{0}")]
+struct IndexingError(String);
+
+/// Number of test files per outcome
+#[derive(Default)]
+struct TestSummary {
+    passed: usize,
+    /// Failed test files per kind of failure, and how many of them were allowed to fail
+    failed: BTreeMap<TestFailureKind, (usize, usize)>,
+}
+
+impl TestSummary {
+    fn add(&mut self, failure: Option<TestFailureKind>, has_assertions: bool, allowed: bool) {
+        match failure {
+            Some(kind) => {
+                let (count, allowed_count) = self.failed.entry(kind).or_default();
+                *count += 1;
+                if allowed {
+                    *allowed_count += 1;
+                }
+            }
+            None if has_assertions => self.passed += 1,
+            None => {}
+        }
+    }
+
+    /// Returns the most severe kind of failure of the tests that were not allowed to fail.
+    fn most_severe_failure(&self) -> Option<TestFailureKind> {
+        self.failed
+            .iter()
+            .rev()
+            .find(|(_, (count, allowed_count))| count > allowed_count)
+            .map(|(kind, _)| *kind)
+    }
+
+    fn print(&self) {
+        println!();
+        println!("| {:<21} | {:>9} | {:>9} |", "result", "files", "allowed");
+        println!("|-----------------------|-----------|-----------|");
+        println!("| {:<21} | {:>9} | {:>9} |", "passed", self.passed, "");
+        for kind in [
+            TestFailureKind::Assertion,
+            TestFailureKind::Indexing,
+            TestFailureKind::Infrastructure,
+        ] {
+            let (count, allowed_count) = self.failed.get(&kind).copied().unwrap_or_default();
+            println!(
+                "| {:<21} | {:>9} | {:>9} |",
+                kind.label(),
+                count,
+                allowed_count
+            );
+        }
+    }
+}
+
+impl std::fmt::Display for TestSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "test files: {} passed, {} failed",
+            self.passed,
+            self.failed.values().map(|(count, _)| count).sum::<usize>()
+        )?;
+        let allowed_count = self
+            .failed
+            .values()
+            .map(|(_, allowed_count)| allowed_count)
+            .sum::<usize>();
+        if allowed_count > 0 {
+            write!(f, " ({} allowed)", allowed_count)?;
+        }
+        Ok(())
+    }
+}
+
 impl TestArgs {
     pub fn new(test_paths: Vec<PathBuf>) -> Self {
         Self {
             test_paths,
             quiet: false,
             hide_error_details: false,
+            diff: false,
             show_skipped: false,
             save_graph: None,
             save_paths: None,
@@ -174,32 +349,88 @@ impl TestArgs {
             output_mode: OutputMode::OnFailure,
             no_builtins: false,
             max_test_time: None,
+            format: OutputFormat::Text,
+            allow_failure: Vec::new(),
+            deny_empty_scope_stacks: false,
         }
     }
 
+    /// Uses the values of the project configuration for the arguments that are not given.
+    pub fn with_config(mut self, config: &Config) -> Self {
+        self.max_test_time = self.max_test_time.or(config.limits.max_test_time);
+        self
+    }
+
     pub fn run(self, mut loader: Loader) -> anyhow::Result<()> {
         let reporter = self.get_reporter();
         let mut total_result = TestResult::new();
+        let mut summary = TestSummary::default();
+        let mut output = TestOutput::default();
         let mut cache = HashMap::new();
         for (test_root, test_path, _) in iter_files_and_directories(self.test_paths.clone()) {
+            let allowed = self.allows_failure(&test_root, &test_path)?;
             let mut file_status = CLIFileReporter::new(&reporter, &test_path);
-            let test_result = self.run_test(
+            let (test_result, error) = match self.run_test(
                 &test_root,
                 &test_path,
                 &mut loader,
                 &mut file_status,
                 &mut cache,
-            )?;
+            ) {
+                Ok(test_result) => (test_result, None),
+                Err(err) => {
+                    let kind = if err.is::<IndexingError>() {
+                        TestFailureKind::Indexing
+                    } else {
+                        TestFailureKind::Infrastructure
+                    };
+                    (TestResult::new(), Some((kind, format!("{:#}", err))))
+                }
+            };
             file_status.assert_reported();
+            let kind = match &error {
+                Some((kind, _)) => Some(*kind),
+                None if test_result.failure_count() > 0 => Some(TestFailureKind::Assertion),
+                None => None,
+            };
+            summary.add(kind, test_result.count() > 0, allowed);
+            output.add(&test_path, &test_result, error, allowed);
             total_result.absorb(test_result);
         }
-        if total_result.failure_count() > 0 {
-            return Err(anyhow!(total_result.to_string()));
+        let failure = summary.most_severe_failure();
+        match self.format {
+            OutputFormat::Text => summary.print(),
+            OutputFormat::Json => {
+                output.exit_code = failure.map_or(0, |kind| kind.exit_code());
+                println!("{}", serde_json::to_string_pretty(&output)?);
+            }
+        }
+        if let Some(kind) = failure {
+            return Err(TestRunError {
+                kind,
+                summary: format!("{}; {}", total_result, summary),
+            }
+            .into());
         }
         Ok(())
     }
 
+    /// Returns whether the given test is allowed to fail.
+    fn allows_failure(&self, test_root: &Path, test_path: &Path) -> anyhow::Result<bool> {
+        if self.allow_failure.is_empty() {
+            return Ok(false);
+        }
+        let mut builder = OverrideBuilder::new(test_root);
+        for glob in &self.allow_failure {
+            builder.add(glob)?;
+        }
+        Ok(builder.build()?.matched(test_path, false).is_whitelist())
+    }
+
     fn get_reporter(&self) -> ConsoleReporter {
+        if self.format == OutputFormat::Json {
+            return ConsoleReporter::none();
+        }
         return ConsoleReporter {
             skipped_level: if self.show_skipped {
                 Level::Summary
@@ -231,9 +462,9 @@ impl TestArgs {
     ) -> anyhow::Result<TestResult> {
         match self.run_test_inner(test_root, test_path, loader, file_status, cache) {
             ok @ Ok(_) => ok,
-            err @ Err(_) => {
-                file_status.failure_if_processing("error", None);
-                err
+            Err(err) => {
+                file_status.failure_if_not_reported("error", Some(&format!("{:#}", err)));
+                Err(err)
             }
         }
     }
@@ -275,8 +506,9 @@ impl TestArgs {
 
         file_status.processing();
 
-        let stitcher_config =
-            StitcherConfig::default().with_detect_similar_paths(!lc.no_similar_paths_in_file);
+        let stitcher_config = StitcherConfig::default()
+            .with_detect_similar_paths(!lc.no_similar_paths_in_file)
+            .with_deny_empty_scope_stacks(self.deny_empty_scope_stacks);
         let mut partials = PartialPaths::new();
         let mut db = Database::new();
 
@@ -345,7 +577,11 @@ impl TestArgs {
                             )
                         )),
                     );
-                    return Err(anyhow!("Failed to build graph for {}", test_path.display()));
+                    return Err(IndexingError(format!(
+                        "Failed to build graph for {}",
+                        test_path.display()
+                    ))
+                    .into());
                 }
                 Ok(_) => {}
             }
@@ -360,12 +596,20 @@ impl TestArgs {
                 |g, ps, p| {
                     db.add_partial_path(g, ps, p.clone());
                 },
-            )?;
+            )
+            .map_err(|err| {
+                IndexingError(format!(
+                    "Failed to compute paths for {}: {}",
+                    test_path.display(),
+                    err
+                ))
+            })?;
         }
-        let result = test.run(
+        let result = test.run_with_tie_breaker(
             &mut partials,
             &mut db,
             stitcher_config,
+            lc.tie_breaker.as_deref(),
             cancellation_flag.as_ref(),
         )?;
         let success = result.failure_count() == 0;
@@ -392,7 +636,13 @@ impl TestArgs {
         } else {
             let details = result
                 .failures_iter()
-                .map(|f| f.to_string())
+                .map(|f| {
+                    if self.diff {
+                        f.display_diff().to_string()
+                    } else {
+                        f.to_string()
+                    }
+                })
                 .chain(outputs)
                 .join("\n");
             file_status.failure(
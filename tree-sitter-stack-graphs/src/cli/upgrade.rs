@@ -0,0 +1,51 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use clap::Args;
+use stack_graphs::storage::database_version;
+use stack_graphs::storage::migrate;
+use stack_graphs::storage::VERSION;
+use std::path::Path;
+
+#[derive(Args)]
+pub struct UpgradeArgs {
+    /// Database version to upgrade to. Defaults to the version used by this program.
+    #[clap(long, value_name = "VERSION")]
+    pub to: Option<usize>,
+
+    /// Only print the database version, without upgrading it.
+    #[clap(long)]
+    pub check: bool,
+}
+
+impl UpgradeArgs {
+    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+        let to_version = self.to.unwrap_or(VERSION);
+        if self.check {
+            let version = database_version(db_path)?;
+            if version == to_version {
+                println!("database is at version {}", version);
+            } else {
+                println!(
+                    "database is at version {}, upgrade to version {} required",
+                    version, to_version
+                );
+            }
+            return Ok(());
+        }
+        let from_version = migrate(db_path, to_version)?;
+        if from_version == to_version {
+            println!("database is already at version {}", to_version);
+        } else {
+            println!(
+                "upgraded database from version {} to version {}",
+                from_version, to_version
+            );
+        }
+        Ok(())
+    }
+}
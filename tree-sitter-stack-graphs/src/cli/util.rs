@@ -12,11 +12,17 @@ use clap::builder::TypedValueParser;
 use clap::error::ContextKind;
 use clap::error::ContextValue;
 use clap::error::ErrorKind;
+use ignore::gitignore::Gitignore;
+use ignore::overrides::Override;
+use ignore::overrides::OverrideBuilder;
+use ignore::Match;
+use ignore::WalkBuilder;
 use lsp_positions::Span;
 use sha1::Digest;
 use sha1::Sha1;
 use stack_graphs::arena::Handle;
 use stack_graphs::graph::Node;
+use stack_graphs::graph::NodeID;
 use stack_graphs::graph::StackGraph;
 use stack_graphs::stats::FrequencyDistribution;
 use stack_graphs::stitching::Stats as StitchingStats;
@@ -30,7 +36,6 @@ use std::ops::Range;
 use std::path::Path;
 use std::path::PathBuf;
 use std::time::Duration;
-use walkdir::WalkDir;
 
 use crate::cli::index::IndexingStats;
 use crate::cli::util::reporter::Reporter;
@@ -220,6 +225,11 @@ impl SourcePosition {
                 || (span.end.line > self.line))
     }
 
+    /// Returns whether this position is in the given span of the same file.
+    pub fn is_within(&self, span: &SourceSpan) -> bool {
+        self.path == span.path && self.within_span(&span.span)
+    }
+
     pub fn canonicalize(&mut self) -> std::io::Result<()> {
         self.path = self.path.canonicalize()?;
         Ok(())
@@ -294,6 +304,54 @@ impl std::str::FromStr for SourcePosition {
     }
 }
 
+#[derive(Clone, Debug)]
+/// A node in a source file, identified by its local ID.
+pub struct SourceNode {
+    /// File path
+    pub path: PathBuf,
+    /// Local node ID within the file
+    pub local_id: u32,
+}
+
+impl SourceNode {
+    pub fn to_node(&self, graph: &StackGraph) -> Option<Handle<Node>> {
+        let file = graph.get_file(&self.path.to_string_lossy())?;
+        graph.node_for_id(NodeID::new_in_file(file, self.local_id))
+    }
+
+    pub fn canonicalize(&mut self) -> std::io::Result<()> {
+        self.path = self.path.canonicalize()?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for SourceNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.path.display(), self.local_id)
+    }
+}
+
+impl std::str::FromStr for SourceNode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, local_id) = match s.rsplit_once(':') {
+            Some(parts) => parts,
+            None => return Err(anyhow!("Missing local ID in expected format PATH:LOCAL_ID")),
+        };
+        let local_id = u32::from_str(local_id).map_err(|_| {
+            anyhow!(
+                "Expected local ID to be a number, got {} in expected format PATH:LOCAL_ID",
+                local_id
+            )
+        })?;
+        Ok(Self {
+            path: PathBuf::from(path),
+            local_id,
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 /// A source span.
 pub struct SourceSpan {
@@ -337,6 +395,19 @@ pub(crate) fn duration_from_milliseconds_str(s: &str) -> Result<Duration, anyhow
 pub fn iter_files_and_directories<'a, P, IP>(
     paths: IP,
 ) -> impl Iterator<Item = (PathBuf, PathBuf, bool)> + 'a
+where
+    P: AsRef<Path> + 'a,
+    IP: IntoIterator<Item = P> + 'a,
+{
+    iter_files_and_directories_with_rules(paths, FileWalkRules::default())
+}
+
+/// Iterates over the given files, and the files in the given directories that are selected by
+/// the rules. Files that are given explicitly are always included.
+pub fn iter_files_and_directories_with_rules<'a, P, IP>(
+    paths: IP,
+    rules: FileWalkRules,
+) -> impl Iterator<Item = (PathBuf, PathBuf, bool)> + 'a
 where
     P: AsRef<Path> + 'a,
     IP: IntoIterator<Item = P> + 'a,
@@ -344,15 +415,14 @@ where
     paths
         .into_iter()
         .filter_map(
-            |source_path| -> Option<Box<dyn Iterator<Item = (PathBuf, PathBuf, bool)>>> {
+            move |source_path| -> Option<Box<dyn Iterator<Item = (PathBuf, PathBuf, bool)>>> {
                 if source_path.as_ref().is_dir() {
                     let source_root = source_path;
-                    let paths = WalkDir::new(&source_root)
-                        .follow_links(true)
-                        .sort_by_file_name()
-                        .into_iter()
+                    let paths = rules
+                        .walk_builder(source_root.as_ref())
+                        .build()
                         .filter_map(|e| e.ok())
-                        .filter(|e| e.file_type().is_file())
+                        .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
                         .map(move |e| (source_root.as_ref().to_path_buf(), e.into_path(), false));
                     Some(Box::new(paths))
                 } else {
@@ -371,6 +441,93 @@ where
         .flatten()
 }
 
+/// Rules that select which files are visited when walking directories. By default, all files
+/// are visited.
+#[derive(Clone, Debug, Default)]
+pub struct FileWalkRules {
+    respect_ignore_files: bool,
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl FileWalkRules {
+    /// Creates new rules. If `respect_ignore_files` is true, files ignored by `.gitignore` or
+    /// `.ignore` files are skipped. If `include` globs are given, only files matching one of
+    /// them are visited. Files matching any of the `exclude` globs are never visited. Globs
+    /// use gitignore syntax and are relative to the directory that is walked.
+    pub fn new(
+        respect_ignore_files: bool,
+        include: Vec<String>,
+        exclude: Vec<String>,
+    ) -> Result<Self, ignore::Error> {
+        let rules = Self {
+            respect_ignore_files,
+            include,
+            exclude,
+        };
+        // validate the globs, so that building overrides for a root cannot fail later
+        rules.overrides(Path::new("."))?;
+        Ok(rules)
+    }
+
+    /// Returns whether the rules select the given file in the given root directory. This mirrors
+    /// the selection made when walking the root directory, without having to walk it.
+    pub fn selects(&self, root: &Path, path: &Path) -> bool {
+        let overrides = self.overrides(root).expect("globs are validated");
+        match overrides.matched(path, false) {
+            Match::Ignore(_) => return false,
+            Match::Whitelist(_) => return true,
+            Match::None => {}
+        }
+        if !self.respect_ignore_files {
+            return true;
+        }
+        // ignore files in deeper directories take precedence
+        for dir in path.ancestors().skip(1) {
+            for name in [".ignore", ".gitignore"] {
+                let (gitignore, _) = Gitignore::new(dir.join(name));
+                match gitignore.matched_path_or_any_parents(path, false) {
+                    Match::Ignore(_) => return false,
+                    Match::Whitelist(_) => return true,
+                    Match::None => {}
+                }
+            }
+        }
+        true
+    }
+
+    fn overrides(&self, root: &Path) -> Result<Override, ignore::Error> {
+        let mut builder = OverrideBuilder::new(root);
+        for glob in &self.include {
+            builder.add(glob)?;
+        }
+        // later globs take precedence, so excludes win over includes
+        for glob in &self.exclude {
+            builder.add(&format!("!{}", glob))?;
+        }
+        builder.build()
+    }
+
+    fn walk_builder(&self, root: &Path) -> WalkBuilder {
+        let mut builder = WalkBuilder::new(root);
+        builder
+            .standard_filters(false)
+            .follow_links(true)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .overrides(self.overrides(root).expect("globs are validated"));
+        if self.respect_ignore_files {
+            builder
+                .git_ignore(true)
+                .git_global(true)
+                .git_exclude(true)
+                .ignore(true)
+                .parents(true)
+                .require_git(false);
+        }
+        builder
+    }
+}
+
 /// Wraps a reporter and ensures that reporter is called properly without requiring
 /// the caller of the wrapper to be overly careful about which methods must be called
 /// in which order
@@ -449,6 +606,17 @@ impl<'a> CLIFileReporter<'a> {
         self.failure(status, details);
     }
 
+    pub(super) fn failure_if_not_reported(
+        &mut self,
+        status: &str,
+        details: Option<&dyn std::fmt::Display>,
+    ) {
+        if self.status_logged {
+            return;
+        }
+        self.failure(status, details);
+    }
+
     pub(super) fn assert_reported(&mut self) {
         if self.path_logged && !self.status_logged {
             panic!("status not reported");
@@ -472,7 +640,7 @@ pub(crate) fn wait_for_input() -> anyhow::Result<()> {
 
 /// Wraps a build error with the relevant sources
 pub(crate) struct BuildErrorWithSource<'a> {
-    pub inner: crate::BuildError,
+    pub inner: Box<crate::BuildError>,
     pub source_path: PathBuf,
     pub source_str: &'a str,
     pub tsg_path: PathBuf,
@@ -5,17 +5,23 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use anyhow::anyhow;
 use clap::Args;
+use clap::ValueEnum;
 use clap::ValueHint;
 use stack_graphs::serde::NoFilter;
 use stack_graphs::stitching::Database;
 use stack_graphs::stitching::ForwardPartialPathStitcher;
 use stack_graphs::stitching::StitcherConfig;
 use stack_graphs::storage::SQLiteReader;
+use stack_graphs::trace::TraceRecording;
 use stack_graphs::NoCancellation;
 use std::path::Path;
 use std::path::PathBuf;
 
+use crate::cli::util::SourceNode;
+use crate::cli::util::SourcePosition;
+
 /// Visualize database
 #[derive(Args)]
 #[clap(after_help = r#"LIMITATIONS:
@@ -31,50 +37,167 @@ pub struct VisualizeArgs {
     )]
     pub source_paths: Vec<PathBuf>,
 
+    /// Only include paths starting at references at the given source positions, formatted as
+    /// PATH:LINE:COLUMN. The files of the references are loaded automatically.
+    #[clap(
+        long = "reference",
+        value_name = "SOURCE_POSITION",
+        value_hint = ValueHint::AnyPath,
+        value_parser,
+    )]
+    pub references: Vec<SourcePosition>,
+
+    /// Only include paths starting at the given nodes, formatted as PATH:LOCAL_ID. The files of
+    /// the nodes are loaded automatically.
+    #[clap(
+        long = "node",
+        value_name = "NODE_ID",
+        value_hint = ValueHint::AnyPath,
+        value_parser,
+    )]
+    pub nodes: Vec<SourceNode>,
+
+    /// Output format of the visualization.
+    #[clap(long, value_enum, default_value_t = VisualizationFormat::Html)]
+    pub format: VisualizationFormat,
+
+    /// Record a trace of all path stitching decisions. The trace can be stepped through in the
+    /// HTML visualization.
+    #[clap(long)]
+    pub trace: bool,
+
+    /// Write the recorded path stitching trace, together with the files it refers to, as JSON to
+    /// the given path. The recording can be replayed with the `replay` command. Implies --trace.
+    #[clap(
+        long,
+        value_name = "TRACE_PATH",
+        value_hint = ValueHint::AnyPath,
+    )]
+    pub trace_output: Option<PathBuf>,
+
+    /// Output path. Defaults to stack-graph.html or stack-graph.dot, depending on the format.
     #[clap(
         long,
         short = 'o',
         value_name = "OUTPUT_PATH",
         value_hint = ValueHint::AnyPath,
-        default_value = "stack-graph.html",
     )]
-    pub output: PathBuf,
+    pub output: Option<PathBuf>,
+}
+
+/// Output format of a visualization
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum VisualizationFormat {
+    /// Interactive HTML page
+    Html,
+    /// Graphviz DOT file, with the edges of the selected paths highlighted
+    Dot,
+}
+
+impl VisualizationFormat {
+    fn default_output(&self) -> PathBuf {
+        match self {
+            Self::Html => PathBuf::from("stack-graph.html"),
+            Self::Dot => PathBuf::from("stack-graph.dot"),
+        }
+    }
 }
 
 impl VisualizeArgs {
-    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+    pub fn run(mut self, db_path: &Path) -> anyhow::Result<()> {
         let cancellation_flag = &NoCancellation;
-        let mut db = SQLiteReader::open(&db_path)?;
+        let collect_trace = self.trace || self.trace_output.is_some();
+        let mut db = SQLiteReader::open(db_path)?.with_collect_provenance(collect_trace);
         for source_path in &self.source_paths {
             let source_path = source_path.canonicalize()?;
             db.load_graphs_for_file_or_directory(&source_path, cancellation_flag)?;
         }
+        for reference in &mut self.references {
+            reference.canonicalize()?;
+            db.load_graph_for_file(&reference.path.to_string_lossy())?;
+        }
+        for node in &mut self.nodes {
+            node.canonicalize()?;
+            db.load_graph_for_file(&node.path.to_string_lossy())?;
+        }
+
         let (graph, _, _) = db.get();
-        let starting_nodes = graph
-            .iter_nodes()
-            .filter(|n| graph[*n].is_reference())
-            .collect::<Vec<_>>();
+        let has_query = !self.references.is_empty() || !self.nodes.is_empty();
+        let mut starting_nodes = Vec::new();
+        for reference in &self.references {
+            let len = starting_nodes.len();
+            starting_nodes.extend(reference.iter_references(graph).map(|(n, _)| n));
+            if starting_nodes.len() == len {
+                return Err(anyhow!("No references at {}", reference));
+            }
+        }
+        for node in &self.nodes {
+            match node.to_node(graph) {
+                Some(node) => starting_nodes.push(node),
+                None => return Err(anyhow!("No node {}", node)),
+            }
+        }
+        if !has_query {
+            starting_nodes.extend(graph.iter_nodes().filter(|n| graph[*n].is_reference()));
+        }
+
+        // Without a query, DOT output has nothing to highlight, and we can skip path finding.
         let mut complete_paths_db = Database::new();
-        let stitcher_config = StitcherConfig::default()
-            // always detect similar paths, we don't know the language configurations for the data in the database
-            .with_detect_similar_paths(true);
-        ForwardPartialPathStitcher::find_all_complete_partial_paths(
-            &mut db,
-            starting_nodes,
-            stitcher_config,
-            cancellation_flag,
-            |g, ps, p| {
-                complete_paths_db.add_partial_path(g, ps, p.clone());
-            },
-        )?;
+        let mut trace = None;
+        if has_query || self.format == VisualizationFormat::Html || collect_trace {
+            let stitcher_config = StitcherConfig::default()
+                // always detect similar paths, we don't know the language configurations for the data in the database
+                .with_detect_similar_paths(true)
+                .with_collect_trace(collect_trace);
+            let stats = ForwardPartialPathStitcher::find_all_complete_partial_paths(
+                &mut db,
+                starting_nodes,
+                stitcher_config,
+                cancellation_flag,
+                |g, ps, p| {
+                    complete_paths_db.add_partial_path(g, ps, p.clone());
+                },
+            )?;
+            trace = stats.trace;
+        }
+        if let (Some(trace_output), Some(trace)) = (&self.trace_output, &trace) {
+            if let Some(dir) = trace_output.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let (graph, _, _) = db.get();
+            let recording = TraceRecording::new(graph, trace.clone());
+            std::fs::write(trace_output, serde_json::to_string(&recording)?)?;
+            println!("Trace at {}", trace_output.display());
+        }
+
+        // The files in the database are stored with their canonical paths, so their source text
+        // can be read from the file system, if it still exists.
+        let source = |path: &str| std::fs::read_to_string(path).ok();
         let (graph, partials, _) = db.get();
-        let html =
-            graph.to_html_string("stack-graph", partials, &mut complete_paths_db, &NoFilter)?;
-        if let Some(dir) = self.output.parent() {
+        let output = match self.format {
+            VisualizationFormat::Html => graph.to_html_string_with_source(
+                "stack-graph",
+                partials,
+                &mut complete_paths_db,
+                &NoFilter,
+                trace.as_ref(),
+                &source,
+            )?,
+            VisualizationFormat::Dot => graph.to_dot_string_with_source(
+                "stack-graph",
+                partials,
+                &complete_paths_db,
+                &NoFilter,
+                &source,
+            ),
+        };
+        let format = self.format;
+        let output_path = self.output.unwrap_or_else(|| format.default_output());
+        if let Some(dir) = output_path.parent() {
             std::fs::create_dir_all(dir)?;
         }
-        std::fs::write(&self.output, html)?;
-        println!("Visualization at {}", self.output.display());
+        std::fs::write(&output_path, output)?;
+        println!("Visualization at {}", output_path.display());
         Ok(())
     }
 }
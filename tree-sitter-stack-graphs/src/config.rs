@@ -0,0 +1,240 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Project configuration, read from `stack-graphs.toml` files.
+//!
+//! A project configuration declares the languages of a project, the files to index, and limits
+//! for indexing and querying. The CLI uses the configuration file in the current directory or
+//! its closest ancestor that has one, and explicit command line arguments take precedence over
+//! the configuration. Library users can load the same file to get the same defaults.
+//!
+//! A configuration file looks as follows. All sections and fields are optional, and relative
+//! paths are resolved against the directory of the configuration file.
+//!
+//! ``` toml
+//! # Directories of tree-sitter grammars, which are used instead of the directories from the
+//! # tree-sitter configuration.
+//! grammars = ["vendor/tree-sitter-python"]
+//! # Index files for which no language is found with the null language.
+//! null-language = false
+//!
+//! [[language]]
+//! scope = "source.python"
+//! # File extensions of the language, which take precedence over the file types of the grammars.
+//! extensions = ["py", "pyi"]
+//! # Stack graphs definitions and builtins, which are used instead of the ones of the grammar.
+//! tsg = "stack-graphs/python.tsg"
+//! builtins = "stack-graphs/builtins.py"
+//!
+//! [files]
+//! # Globs of files to index, relative to the indexed directories.
+//! include = ["src/**"]
+//! exclude = ["**/generated/**"]
+//! # Also index files that are ignored by .gitignore or .ignore files.
+//! no-ignore = false
+//!
+//! [limits]
+//! # Times are in seconds.
+//! max-file-time = 10
+//! max-test-time = 60
+//! max-query-time = 5
+//! max-paths = 100000
+//! max-files-loaded = 1000
+//! ```
+
+use serde::Deserialize;
+use serde::Deserializer;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::loader::ExtensionLanguageDetector;
+use crate::loader::LoadPath;
+use crate::loader::Loader;
+use crate::null_language::NullLanguage;
+
+/// The name of project configuration files.
+pub const CONFIG_FILE_NAME: &str = "stack-graphs.toml";
+
+/// A project configuration.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// The directory against which relative paths in the configuration are resolved, which is
+    /// the directory of the configuration file.
+    #[serde(skip)]
+    pub root: PathBuf,
+    /// Directories of tree-sitter grammars.
+    pub grammars: Vec<PathBuf>,
+    /// Whether to index files for which no language is found with the
+    /// [`NullLanguage`][crate::null_language::NullLanguage].
+    pub null_language: bool,
+    /// The languages of the project.
+    #[serde(rename = "language")]
+    pub languages: Vec<LanguageConfig>,
+    /// The files to index.
+    pub files: FilesConfig,
+    /// Limits for indexing and querying.
+    pub limits: LimitsConfig,
+}
+
+/// The configuration of a single language.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LanguageConfig {
+    /// The tree-sitter scope of the language.
+    pub scope: String,
+    /// File extensions, without leading dot, of files in this language.
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// The stack graphs definitions of the language.
+    #[serde(default)]
+    pub tsg: Option<PathBuf>,
+    /// The builtins of the language.
+    #[serde(default)]
+    pub builtins: Option<PathBuf>,
+}
+
+/// The configuration of the files to index.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FilesConfig {
+    /// Only index files that match one of these globs.
+    pub include: Vec<String>,
+    /// Do not index files that match one of these globs, which takes precedence over `include`.
+    pub exclude: Vec<String>,
+    /// Also index files that are ignored by `.gitignore` or `.ignore` files.
+    pub no_ignore: bool,
+}
+
+/// Limits for indexing and querying.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LimitsConfig {
+    /// Maximum time to index a single file.
+    #[serde(deserialize_with = "duration_from_seconds")]
+    pub max_file_time: Option<Duration>,
+    /// Maximum time to run a single test file.
+    #[serde(deserialize_with = "duration_from_seconds")]
+    pub max_test_time: Option<Duration>,
+    /// Maximum time to answer a query.
+    #[serde(deserialize_with = "duration_from_seconds")]
+    pub max_query_time: Option<Duration>,
+    /// Maximum number of paths that are considered for a query.
+    pub max_paths: Option<usize>,
+    /// Maximum number of files that are loaded for a query.
+    pub max_files_loaded: Option<usize>,
+}
+
+fn duration_from_seconds<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let seconds = f64::deserialize(deserializer)?;
+    Duration::try_from_secs_f64(seconds)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+impl Config {
+    /// Reads the configuration from the given file.
+    pub fn from_path(path: &Path) -> Result<Self, ConfigError> {
+        let config = std::fs::read_to_string(path)
+            .map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+        Self::parse(&config, path.parent().unwrap_or(Path::new("")), path)
+    }
+
+    /// Parses the configuration from the given string. Relative paths are resolved against the
+    /// given root directory.
+    pub fn from_config_str(config: &str, root: &Path) -> Result<Self, ConfigError> {
+        Self::parse(config, root, &root.join(CONFIG_FILE_NAME))
+    }
+
+    fn parse(config: &str, root: &Path, path: &Path) -> Result<Self, ConfigError> {
+        let mut config: Self =
+            toml::from_str(config).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))?;
+        config.root = root.to_path_buf();
+        for grammar in &mut config.grammars {
+            *grammar = root.join(&grammar);
+        }
+        for language in &mut config.languages {
+            if let Some(tsg) = &mut language.tsg {
+                *tsg = root.join(&tsg);
+            }
+            if let Some(builtins) = &mut language.builtins {
+                *builtins = root.join(&builtins);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Returns the path of the configuration file in the given directory, or in its closest
+    /// ancestor that has one.
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    /// Reads the configuration file in the given directory, or in its closest ancestor that has
+    /// one. Returns `None` if there is no configuration file.
+    pub fn discover(dir: &Path) -> Result<Option<Self>, ConfigError> {
+        Self::find(dir)
+            .map(|path| Self::from_path(&path))
+            .transpose()
+    }
+
+    /// Returns a language detector for the file extensions of the configured languages.
+    pub fn language_detector(&self) -> ExtensionLanguageDetector {
+        let mut detector = ExtensionLanguageDetector::new();
+        for language in &self.languages {
+            for extension in &language.extensions {
+                detector.add(extension.clone(), language.scope.clone());
+            }
+        }
+        detector
+    }
+
+    /// Returns whether any of the configured languages declares file extensions.
+    pub fn has_extensions(&self) -> bool {
+        self.languages.iter().any(|l| !l.extensions.is_empty())
+    }
+
+    /// Configures the given loader to use the stack graphs definitions, builtins, and file
+    /// extensions of the configured languages, and the null language if it is enabled.
+    pub fn configure_loader(&self, loader: &mut Loader) {
+        for language in &self.languages {
+            if let Some(tsg) = &language.tsg {
+                loader.set_tsg_paths_for_scope(
+                    language.scope.clone(),
+                    vec![LoadPath::Regular(tsg.clone())],
+                );
+            }
+            if let Some(builtins) = &language.builtins {
+                loader.set_builtins_paths_for_scope(
+                    language.scope.clone(),
+                    vec![LoadPath::Regular(builtins.clone())],
+                );
+            }
+        }
+        if self.has_extensions() {
+            loader.set_language_detector(self.language_detector());
+        }
+        if self.null_language {
+            loader.set_fallback_analyzer(NullLanguage);
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Cannot read configuration file {0}")]
+    Io(PathBuf, #[source] std::io::Error),
+    #[error("Invalid configuration file {0}")]
+    Parse(PathBuf, #[source] toml::de::Error),
+}
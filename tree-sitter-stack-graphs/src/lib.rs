@@ -94,6 +94,33 @@
 //! }
 //! ```
 //!
+//! Symbol nodes allow an optional `symbol_namespace` attribute, which puts the symbol in a namespace.
+//! Use namespaces for languages that keep separate kinds of names, such as values and types, apart.
+//! A symbol only matches symbols with the same name in the same namespace, and symbols without a
+//! `symbol_namespace` attribute only match other symbols without a namespace.
+//!
+//! ``` skip
+//! (type_identifier) @id {
+//!   node new_node
+//!   attr (new_node) type = "push_symbol", symbol = (source-text @id), symbol_namespace = "type"
+//! }
+//! ```
+//!
+//! Symbols in the `root_domain` namespace name [root domains][stack_graphs::graph::StackGraph::add_root_domain],
+//! which isolate the parts of a graph that meet at the root node. Push the root domain symbol
+//! before edges to the root node, and pop it after edges from the root node:
+//!
+//! ``` skip
+//! (program) @prog {
+//!   node @prog.domain_entry
+//!   attr (@prog.domain_entry) type = "push_symbol", symbol = "npm", symbol_namespace = "root_domain"
+//!   edge @prog.domain_entry -> ROOT_NODE
+//!   node @prog.domain_exit
+//!   attr (@prog.domain_exit) type = "pop_symbol", symbol = "npm", symbol_namespace = "root_domain"
+//!   edge ROOT_NODE -> @prog.domain_exit
+//! }
+//! ```
+//!
 //! Node types `pop_symbol` and `pop_scoped_symbol` allow an optional `is_definition` attribute, which
 //! marks that node as a proper definition.  Node types `push_symbol` and `push_scoped_symbol` allow
 //! an optional `is_reference` attribute, which marks the node as a proper reference.  When `is_definition`
@@ -125,6 +152,22 @@
 //! Nodes of type `scope` allow an optional `is_exported` attribute, that is required to use the scope
 //! in a `push_scoped_symbol` node.
 //!
+//! Exported scopes also allow an `excluded_symbols` attribute, whose value is a symbol, or a list of
+//! symbols.  Paths can only pass through the scope if the symbol on top of their symbol stack is not
+//! one of them.  Use this for wildcard imports and re-exports, with a single edge to the exported
+//! scope of the other module, instead of a pop and push node for every symbol that is imported.  For
+//! instance, for Python's `from m import *`, which does not import names that start with an
+//! underscore, when the module defines `_helper`:
+//!
+//! ``` skip
+//! (import_from_statement (wildcard_import)) @import {
+//!   node @import.wildcard
+//!   attr (@import.wildcard) is_exported, excluded_symbols = ["_helper"]
+//!   edge @import.defs -> @import.wildcard
+//!   edge @import.wildcard -> @import.module_exports
+//! }
+//! ```
+//!
 //!
 //! ### Annotating nodes with location information
 //!
@@ -217,6 +260,34 @@
 //!
 //! (If you don't specify a `precedence`, the default is 0.)
 //!
+//! In large graphs, the root node can have a very large number of edges, and trying all of them
+//! when a path reaches the root node is expensive.  You can add a `root_key` attribute to an edge
+//! into or out of the root node, to indicate that only paths whose symbol stack starts with the
+//! given symbols can traverse the edge.  The value is a symbol, or a list of symbols:
+//!
+//! ``` skip
+//! (module name: (identifier) @id) @mod {
+//!   node def
+//!   attr (def) type = "pop_symbol", symbol = (source-text @id), source_node = @mod, is_definition
+//!   edge ROOT_NODE -> def
+//!   attr (ROOT_NODE -> def) root_key = (source-text @id)
+//! }
+//! ```
+//!
+//! For languages that restrict which symbols are visible across module boundaries, you can add a
+//! `visibility` attribute to an edge, with one of the values `"public"`, `"internal"`,
+//! `"protected"`, or `"private"`.  Edges without the attribute are public.  Whether paths can
+//! traverse non-public edges is decided at query time by a visibility policy:
+//!
+//! ``` skip
+//! (class_body (method_definition "private" name: (identifier) @id) @method) @body {
+//!   node def
+//!   attr (def) type = "pop_symbol", symbol = (source-text @id), source_node = @method, is_definition
+//!   edge @body.members -> def
+//!   attr (@body.members -> def) visibility = "private"
+//! }
+//! ```
+//!
 //! ### Referring to the singleton nodes
 //!
 //! The _root node_ and _jump to scope node_ are singleton nodes that always exist for all stack
@@ -293,6 +364,34 @@
 //! }
 //! ```
 //!
+//! ## Crate features
+//!
+//! The stack graph data structures and algorithms live in the [`stack_graphs`][] crate, which
+//! this crate re-exports, so that embedders only need to depend on this crate.  Without any
+//! features enabled, neither crate depends on storage, visualization, or command-line libraries.
+//! Those subsystems are opt-in.  The following features enable the subsystems of the
+//! [`stack_graphs`][] crate, which are available through the re-exported crate:
+//!
+//! - `storage`: storing graphs and paths in SQLite databases, in `stack_graphs::storage`.
+//! - `serde`: serialization of graphs and paths, in `stack_graphs::serde`.
+//! - `export`: exporting graphs and resolutions in other formats, in `stack_graphs::export`.
+//! - `arrow`: exporting resolutions as Arrow record batches and Parquet files, on top of the
+//!   `export` and `storage` features.
+//! - `visualization`: rendering graphs and paths as HTML, in `stack_graphs::visualization`.
+//! - `analysis`: linting graphs and measuring resolution coverage, in `stack_graphs::analysis`.
+//! - `events`: building graphs from a stream of graph events, in `stack_graphs::events`.
+//! - `query`: structural queries and hover information, in `stack_graphs::query`.
+//! - `snapshot`: normalized text dumps of graphs and paths, in `stack_graphs::snapshot`.
+//! - `source`: source code excerpts in diagnostics, in `stack_graphs::source`.
+//! - `trace`: recording and replaying stitching traces, in `stack_graphs::trace`.
+//!
+//! The command-line programs are enabled with the following features:
+//!
+//! - `cli`: the building blocks of the `tree-sitter-stack-graphs` command-line programs in the
+//!   `cli` module, on top of the `analysis`, `events`, `export`, `serde`, `storage`, `trace`, and
+//!   `visualization` features, which also pull in file system watching and terminal libraries.
+//! - `lsp`: a language server, on top of the `cli` feature.
+//!
 //! ## Using this crate from Rust
 //!
 //! If you need very fine-grained control over how to use the resulting stack graphs, you can
@@ -373,11 +472,14 @@ use util::TreeSitterCancellationFlag;
 pub mod ci;
 #[cfg(feature = "cli")]
 pub mod cli;
+pub mod config;
 pub mod functions;
 pub mod loader;
+pub mod null_language;
 pub mod test;
 mod util;
 
+pub use stack_graphs;
 pub use tree_sitter_graph::VariableError;
 pub use tree_sitter_graph::Variables;
 
@@ -394,6 +496,7 @@ static SCOPE_TYPE: &'static str = "scope";
 // Node attribute names
 static DEBUG_ATTR_PREFIX: &'static str = "debug_";
 static DEFINIENS_NODE_ATTR: &'static str = "definiens_node";
+static EXCLUDED_SYMBOLS_ATTR: &str = "excluded_symbols";
 static EMPTY_SOURCE_SPAN_ATTR: &'static str = "empty_source_span";
 static IS_DEFINITION_ATTR: &'static str = "is_definition";
 static IS_ENDPOINT_ATTR: &'static str = "is_endpoint";
@@ -402,6 +505,7 @@ static IS_REFERENCE_ATTR: &'static str = "is_reference";
 static SCOPE_ATTR: &'static str = "scope";
 static SOURCE_NODE_ATTR: &'static str = "source_node";
 static SYMBOL_ATTR: &'static str = "symbol";
+static SYMBOL_NAMESPACE_ATTR: &str = "symbol_namespace";
 static SYNTAX_TYPE_ATTR: &'static str = "syntax_type";
 static TYPE_ATTR: &'static str = "type";
 
@@ -410,6 +514,7 @@ static POP_SCOPED_SYMBOL_ATTRS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
     HashSet::from([
         TYPE_ATTR,
         SYMBOL_ATTR,
+        SYMBOL_NAMESPACE_ATTR,
         IS_DEFINITION_ATTR,
         DEFINIENS_NODE_ATTR,
         SYNTAX_TYPE_ATTR,
@@ -419,20 +524,47 @@ static POP_SYMBOL_ATTRS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
     HashSet::from([
         TYPE_ATTR,
         SYMBOL_ATTR,
+        SYMBOL_NAMESPACE_ATTR,
         IS_DEFINITION_ATTR,
         DEFINIENS_NODE_ATTR,
         SYNTAX_TYPE_ATTR,
     ])
 });
-static PUSH_SCOPED_SYMBOL_ATTRS: Lazy<HashSet<&'static str>> =
-    Lazy::new(|| HashSet::from([TYPE_ATTR, SYMBOL_ATTR, SCOPE_ATTR, IS_REFERENCE_ATTR]));
-static PUSH_SYMBOL_ATTRS: Lazy<HashSet<&'static str>> =
-    Lazy::new(|| HashSet::from([TYPE_ATTR, SYMBOL_ATTR, IS_REFERENCE_ATTR]));
-static SCOPE_ATTRS: Lazy<HashSet<&'static str>> =
-    Lazy::new(|| HashSet::from([TYPE_ATTR, IS_EXPORTED_ATTR, IS_ENDPOINT_ATTR]));
+static PUSH_SCOPED_SYMBOL_ATTRS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+    HashSet::from([
+        TYPE_ATTR,
+        SYMBOL_ATTR,
+        SYMBOL_NAMESPACE_ATTR,
+        SCOPE_ATTR,
+        IS_REFERENCE_ATTR,
+    ])
+});
+static PUSH_SYMBOL_ATTRS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+    HashSet::from([
+        TYPE_ATTR,
+        SYMBOL_ATTR,
+        SYMBOL_NAMESPACE_ATTR,
+        IS_REFERENCE_ATTR,
+    ])
+});
+static SCOPE_ATTRS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+    HashSet::from([
+        TYPE_ATTR,
+        IS_EXPORTED_ATTR,
+        IS_ENDPOINT_ATTR,
+        EXCLUDED_SYMBOLS_ATTR,
+    ])
+});
 
 // Edge attribute names
 static PRECEDENCE_ATTR: &'static str = "precedence";
+static ROOT_KEY_ATTR: &str = "root_key";
+static VISIBILITY_ATTR: &str = "visibility";
+
+/// The default maximum depth of syntax trees that stack graphs are built for. Executing the TSG
+/// rules nests calls for every level of the syntax tree, so that deeper trees can exhaust the stack
+/// of the thread that builds the stack graph. See [`StackGraphLanguage::set_max_tree_depth`][].
+pub const DEFAULT_MAX_TREE_DEPTH: usize = 1000;
 
 // Global variables
 static ROOT_NODE_VAR: &'static str = "ROOT_NODE";
@@ -446,6 +578,7 @@ pub struct StackGraphLanguage {
     tsg_path: PathBuf,
     tsg_source: std::borrow::Cow<'static, str>,
     functions: Functions,
+    max_tree_depth: Option<usize>,
 }
 
 impl StackGraphLanguage {
@@ -462,6 +595,7 @@ impl StackGraphLanguage {
             tsg_path: PathBuf::from("<tsg>"),
             tsg_source: Cow::from(String::new()),
             functions: Self::default_functions(),
+            max_tree_depth: Some(DEFAULT_MAX_TREE_DEPTH),
         }
     }
 
@@ -479,6 +613,7 @@ impl StackGraphLanguage {
             tsg_path: PathBuf::from("<missing tsg path>"),
             tsg_source: Cow::from(tsg_source.to_string()),
             functions: Self::default_functions(),
+            max_tree_depth: Some(DEFAULT_MAX_TREE_DEPTH),
         })
     }
 
@@ -515,6 +650,21 @@ impl StackGraphLanguage {
         self.language
     }
 
+    /// Returns the maximum depth of syntax trees that stack graphs are built for, if any.
+    pub fn max_tree_depth(&self) -> Option<usize> {
+        self.max_tree_depth
+    }
+
+    /// Sets the maximum depth of syntax trees that stack graphs are built for. Executing the TSG
+    /// rules on very deeply nested source code can exhaust the stack, which aborts the process.
+    /// Building a stack graph for a file whose syntax tree is deeper than this limit fails with
+    /// [`BuildError::TreeTooDeep`][] instead. By default, the limit is [`DEFAULT_MAX_TREE_DEPTH`][].
+    /// Raising the limit, or removing it with `None`, may require running the build on a thread
+    /// with a larger stack.
+    pub fn set_max_tree_depth(&mut self, max_tree_depth: Option<usize>) {
+        self.max_tree_depth = max_tree_depth;
+    }
+
     /// Returns the original TSG path, if it was provided at construction or set with
     /// [`set_tsg_info`][]. Can be used as input for [`BuildError::display_pretty`][].
     pub fn tsg_path(&self) -> &Path {
@@ -633,6 +783,9 @@ impl<'a> Builder<'a> {
             return Err(BuildError::ParseErrors(parse_errors));
         }
         let tree = parse_errors.into_tree();
+        if let Some(max_tree_depth) = self.sgl.max_tree_depth {
+            check_tree_depth(&tree, max_tree_depth)?;
+        }
 
         let mut globals = Variables::nested(globals);
         if globals.get(&ROOT_NODE_VAR.into()).is_none() {
@@ -814,6 +967,8 @@ pub enum BuildError {
     UnknownNodeType(String),
     #[error("Unknown symbol type {0}")]
     UnknownSymbolType(String),
+    #[error("Unknown edge visibility {0}")]
+    UnknownVisibility(String),
     #[error(transparent)]
     ExecutionError(tree_sitter_graph::ExecutionError),
     #[error("Error parsing source")]
@@ -826,6 +981,8 @@ pub enum BuildError {
     LanguageError(#[from] tree_sitter::LanguageError),
     #[error("Expected exported symbol scope in {0}, got {1}")]
     SymbolScopeError(String, String),
+    #[error("Syntax tree is deeper than the maximum depth of {0}")]
+    TreeTooDeep(usize),
 }
 
 impl From<stack_graphs::CancellationError> for BuildError {
@@ -892,6 +1049,28 @@ impl std::fmt::Display for DisplayBuildErrorPretty<'_> {
     }
 }
 
+/// Checks that the tree is not deeper than the given maximum depth. The tree is walked with a
+/// cursor, so that this check does not itself use stack space proportional to the tree depth.
+fn check_tree_depth(tree: &tree_sitter::Tree, max_tree_depth: usize) -> Result<(), BuildError> {
+    let mut cursor = tree.walk();
+    let mut depth = 0usize;
+    loop {
+        if depth > max_tree_depth {
+            return Err(BuildError::TreeTooDeep(max_tree_depth));
+        }
+        if cursor.goto_first_child() {
+            depth += 1;
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return Ok(());
+            }
+            depth -= 1;
+        }
+    }
+}
+
 impl<'a> Builder<'a> {
     fn load(mut self, cancellation_flag: &dyn CancellationFlag) -> Result<(), BuildError> {
         let cancellation_flag: &dyn stack_graphs::CancellationFlag = &cancellation_flag;
@@ -956,6 +1135,22 @@ impl<'a> Builder<'a> {
                 let sink_handle = self.stack_graph.node_for_id(sink_node_id).unwrap();
                 self.stack_graph
                     .add_edge(source_handle, sink_handle, precedence);
+                if let Some(root_key) = edge.attributes.get(ROOT_KEY_ATTR) {
+                    Self::load_root_edge_key(
+                        self.stack_graph,
+                        source_handle,
+                        sink_handle,
+                        root_key,
+                    )?;
+                }
+                if let Some(visibility) = edge.attributes.get(VISIBILITY_ATTR) {
+                    let visibility = visibility
+                        .as_str()?
+                        .parse()
+                        .map_err(BuildError::UnknownVisibility)?;
+                    self.stack_graph
+                        .set_edge_visibility(source_handle, sink_handle, visibility);
+                }
                 Self::load_edge_debug_info(
                     &mut self.stack_graph,
                     source_handle,
@@ -1045,7 +1240,10 @@ impl<'a> Builder<'a> {
             Some(symbol) => self.load_symbol(symbol)?,
             None => return Err(BuildError::MissingSymbol(node_ref)),
         };
-        let symbol = self.stack_graph.add_symbol(&symbol);
+        let namespace = self.load_symbol_namespace(node)?;
+        let symbol = self
+            .stack_graph
+            .add_symbol_in_namespace(&symbol, namespace.as_deref());
         let id = self.node_id_for_graph_node(node_ref);
         let is_definition = self.load_flag(node, IS_DEFINITION_ATTR)?;
         self.verify_attributes(node, POP_SCOPED_SYMBOL_TYPE, &POP_SCOPED_SYMBOL_ATTRS);
@@ -1065,7 +1263,10 @@ impl<'a> Builder<'a> {
             Some(symbol) => self.load_symbol(symbol)?,
             None => return Err(BuildError::MissingSymbol(node_ref)),
         };
-        let symbol = self.stack_graph.add_symbol(&symbol);
+        let namespace = self.load_symbol_namespace(node)?;
+        let symbol = self
+            .stack_graph
+            .add_symbol_in_namespace(&symbol, namespace.as_deref());
         let id = self.node_id_for_graph_node(node_ref);
         let is_definition = self.load_flag(node, IS_DEFINITION_ATTR)?;
         self.verify_attributes(node, POP_SYMBOL_TYPE, &POP_SYMBOL_ATTRS);
@@ -1088,7 +1289,10 @@ impl<'a> Builder<'a> {
             Some(symbol) => self.load_symbol(symbol)?,
             None => return Err(BuildError::MissingSymbol(node_ref)),
         };
-        let symbol = self.stack_graph.add_symbol(&symbol);
+        let namespace = self.load_symbol_namespace(node)?;
+        let symbol = self
+            .stack_graph
+            .add_symbol_in_namespace(&symbol, namespace.as_deref());
         let id = self.node_id_for_graph_node(node_ref);
         let scope = match node.attributes.get(SCOPE_ATTR) {
             Some(scope) => self.node_id_for_graph_node(scope.as_graph_node_ref()?),
@@ -1108,7 +1312,10 @@ impl<'a> Builder<'a> {
             Some(symbol) => self.load_symbol(symbol)?,
             None => return Err(BuildError::MissingSymbol(node_ref)),
         };
-        let symbol = self.stack_graph.add_symbol(&symbol);
+        let namespace = self.load_symbol_namespace(node)?;
+        let symbol = self
+            .stack_graph
+            .add_symbol_in_namespace(&symbol, namespace.as_deref());
         let id = self.node_id_for_graph_node(node_ref);
         let is_reference = self.load_flag(node, IS_REFERENCE_ATTR)?;
         self.verify_attributes(node, PUSH_SYMBOL_TYPE, &PUSH_SYMBOL_ATTRS);
@@ -1124,7 +1331,20 @@ impl<'a> Builder<'a> {
         let is_exported =
             self.load_flag(node, IS_EXPORTED_ATTR)? || self.load_flag(node, IS_ENDPOINT_ATTR)?;
         self.verify_attributes(node, SCOPE_TYPE, &SCOPE_ATTRS);
-        Ok(self.stack_graph.add_scope_node(id, is_exported).unwrap())
+        let handle = self.stack_graph.add_scope_node(id, is_exported).unwrap();
+        if let Some(excluded_symbols) = node.attributes.get(EXCLUDED_SYMBOLS_ATTR) {
+            let symbols = match excluded_symbols {
+                Value::List(symbols) => symbols.iter().collect::<Vec<_>>(),
+                symbol => vec![symbol],
+            };
+            let mut excluded = Vec::with_capacity(symbols.len());
+            for symbol in symbols {
+                let symbol = self.load_symbol(symbol)?;
+                excluded.push(self.stack_graph.add_symbol(&symbol));
+            }
+            self.stack_graph.set_excluded_symbols(handle, &excluded);
+        }
+        Ok(handle)
     }
 
     fn load_symbol(&self, value: &Value) -> Result<String, BuildError> {
@@ -1135,6 +1355,13 @@ impl<'a> Builder<'a> {
         }
     }
 
+    fn load_symbol_namespace(&self, node: &GraphNode) -> Result<Option<String>, BuildError> {
+        match node.attributes.get(SYMBOL_NAMESPACE_ATTR) {
+            Some(namespace) => self.load_symbol(namespace).map(Some),
+            None => Ok(None),
+        }
+    }
+
     fn load_flag(&self, node: &GraphNode, attribute: &str) -> Result<bool, BuildError> {
         match node.attributes.get(attribute) {
             Some(value) => value.as_boolean().map_err(|_| {
@@ -1219,6 +1446,24 @@ impl<'a> Builder<'a> {
         Ok(())
     }
 
+    fn load_root_edge_key(
+        stack_graph: &mut StackGraph,
+        source_handle: Handle<Node>,
+        sink_handle: Handle<Node>,
+        root_key: &Value,
+    ) -> Result<(), BuildError> {
+        let symbols = match root_key {
+            Value::List(symbols) => symbols.iter().collect::<Vec<_>>(),
+            symbol => vec![symbol],
+        };
+        let mut key = Vec::with_capacity(symbols.len());
+        for symbol in symbols {
+            key.push(stack_graph.add_symbol(symbol.as_str()?));
+        }
+        stack_graph.set_root_edge_key(source_handle, sink_handle, &key);
+        Ok(())
+    }
+
     fn load_edge_debug_info(
         stack_graph: &mut StackGraph,
         source_handle: Handle<Node>,
@@ -12,6 +12,7 @@ use itertools::Itertools;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::TieBreaker;
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::ffi::OsStr;
@@ -29,12 +30,16 @@ use tree_sitter_loader::Loader as TsLoader;
 use crate::CancellationFlag;
 use crate::FileAnalyzer;
 use crate::StackGraphLanguage;
+use crate::DEFAULT_MAX_TREE_DEPTH;
 
 pub static DEFAULT_TSG_PATHS: Lazy<Vec<LoadPath>> =
     Lazy::new(|| vec![LoadPath::Grammar("queries/stack-graphs".into())]);
 pub static DEFAULT_BUILTINS_PATHS: Lazy<Vec<LoadPath>> =
     Lazy::new(|| vec![LoadPath::Grammar("queries/builtins".into())]);
 
+/// The name of the file that contains the builtins of a language.
+pub const BUILTINS_FILENAME: &str = "<builtins>";
+
 /// Data type that holds all information to recognize and analyze files for a language
 pub struct LanguageConfiguration {
     pub language: Language,
@@ -49,6 +54,12 @@ pub struct LanguageConfiguration {
     /// incorrectly set to true, performance of path finding suffers from exponential
     /// blow up.
     pub no_similar_paths_in_file: bool,
+    /// Groups files of this language that are treated as a single unit when loaded from a
+    /// database, such as C header and source files.
+    pub file_grouping: FileGrouping,
+    /// Decides between definitions of a reference that shadowing cannot decide between, using
+    /// language-specific rules. If not set, all such definitions are kept.
+    pub tie_breaker: Option<Arc<dyn TieBreaker>>,
 }
 
 impl LanguageConfiguration {
@@ -78,7 +89,7 @@ impl LanguageConfiguration {
             if let Some(builtins_config) = builtins_config {
                 Loader::load_globals_from_config_str(builtins_config, &mut builtins_globals)?;
             }
-            let file = builtins.add_file("<builtins>").unwrap();
+            let file = builtins.add_file(BUILTINS_FILENAME).unwrap();
             sgl.build_stack_graph_into(
                 &mut builtins,
                 file,
@@ -87,7 +98,7 @@ impl LanguageConfiguration {
                 cancellation_flag,
             )
             .map_err(|err| LoadError::Builtins {
-                inner: err,
+                inner: Box::new(err),
                 source_path: builtins_path,
                 source: Cow::from(builtins_source),
                 tsg_path: sgl.tsg_path.clone(),
@@ -103,6 +114,8 @@ impl LanguageConfiguration {
             builtins,
             special_files: FileAnalyzers::new(),
             no_similar_paths_in_file: false,
+            file_grouping: FileGrouping::None,
+            tie_breaker: None,
         })
     }
 
@@ -136,6 +149,27 @@ impl LanguageConfiguration {
     }
 }
 
+/// Determines which files form a group. The files in a group are always loaded together from a
+/// database, see `stack_graphs::storage::FILE_GROUP_METADATA`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum FileGrouping {
+    /// Files do not belong to a group.
+    #[default]
+    None,
+    /// Files with the same path up to their extension form a group, such as `foo.h` and `foo.c`.
+    ByStem,
+}
+
+impl FileGrouping {
+    /// Returns the group of the given file, or `None` if it does not belong to one.
+    pub fn group_for_file(&self, path: &Path) -> Option<String> {
+        match self {
+            Self::None => None,
+            Self::ByStem => Some(path.with_extension("").to_string_lossy().into_owned()),
+        }
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct FileAnalyzers {
     file_analyzers: HashMap<String, Arc<dyn FileAnalyzer + Send + Sync>>,
@@ -224,7 +258,12 @@ impl Loader {
             scope,
             tsg_paths,
             builtins_paths,
+            scope_tsg_paths: HashMap::new(),
+            scope_builtins_paths: HashMap::new(),
             cache: Vec::new(),
+            detector: None,
+            fallback: None,
+            max_tree_depth: Some(DEFAULT_MAX_TREE_DEPTH),
         })))
     }
 
@@ -240,7 +279,12 @@ impl Loader {
             scope,
             tsg_paths,
             builtins_paths,
+            scope_tsg_paths: HashMap::new(),
+            scope_builtins_paths: HashMap::new(),
             cache: Vec::new(),
+            detector: None,
+            fallback: None,
+            max_tree_depth: Some(DEFAULT_MAX_TREE_DEPTH),
         })))
     }
 
@@ -254,9 +298,72 @@ impl Loader {
             .collect();
         Ok(Self(LoaderImpl::Provided(LanguageConfigurationsLoader {
             configurations,
+            detector: None,
+            fallback: None,
         })))
     }
 
+    /// Sets a language detector, which is consulted before the file types and content regexes
+    /// of the available languages are used to select the language for a file.
+    pub fn set_language_detector(
+        &mut self,
+        detector: impl LanguageDetector + Send + Sync + 'static,
+    ) {
+        let detector: Arc<dyn LanguageDetector + Send + Sync> = Arc::new(detector);
+        match &mut self.0 {
+            LoaderImpl::Paths(loader) => loader.detector = Some(detector),
+            LoaderImpl::Provided(loader) => loader.detector = Some(detector),
+        }
+    }
+
+    /// Sets a file analyzer for files for which no language is found, such as the
+    /// [`NullLanguage`][crate::null_language::NullLanguage]. If a fallback analyzer is set, it is
+    /// not an error if the loader does not find any languages at all.
+    pub fn set_fallback_analyzer(&mut self, analyzer: impl FileAnalyzer + Send + Sync + 'static) {
+        let analyzer: Arc<dyn FileAnalyzer + Send + Sync> = Arc::new(analyzer);
+        match &mut self.0 {
+            LoaderImpl::Paths(loader) => loader.fallback = Some(analyzer),
+            LoaderImpl::Provided(loader) => loader.fallback = Some(analyzer),
+        }
+    }
+
+    /// Sets the maximum depth of syntax trees that stack graphs are built for, for all languages of
+    /// this loader, including the languages that were already loaded. See
+    /// [`StackGraphLanguage::set_max_tree_depth`][].
+    pub fn set_max_tree_depth(&mut self, max_tree_depth: Option<usize>) {
+        match &mut self.0 {
+            LoaderImpl::Paths(loader) => {
+                loader.max_tree_depth = max_tree_depth;
+                for (_, lc) in &mut loader.cache {
+                    lc.sgl.set_max_tree_depth(max_tree_depth);
+                }
+            }
+            LoaderImpl::Provided(loader) => {
+                for lc in &mut loader.configurations {
+                    lc.sgl.set_max_tree_depth(max_tree_depth);
+                }
+            }
+        }
+    }
+
+    /// Sets the paths for stack graphs definitions of the language with the given scope, which are
+    /// used instead of the general paths the loader was created with. Has no effect on loaders created
+    /// from language configurations, whose stack graphs definitions are already loaded.
+    pub fn set_tsg_paths_for_scope(&mut self, scope: String, tsg_paths: Vec<LoadPath>) {
+        if let LoaderImpl::Paths(loader) = &mut self.0 {
+            loader.scope_tsg_paths.insert(scope, tsg_paths);
+        }
+    }
+
+    /// Sets the paths for builtins of the language with the given scope, which are used instead of
+    /// the general paths the loader was created with. Has no effect on loaders created from language
+    /// configurations, whose builtins are already loaded.
+    pub fn set_builtins_paths_for_scope(&mut self, scope: String, builtins_paths: Vec<LoadPath>) {
+        if let LoaderImpl::Paths(loader) = &mut self.0 {
+            loader.scope_builtins_paths.insert(scope, builtins_paths);
+        }
+    }
+
     /// Load a Tree-sitter language for the given file. Loading is based on the loader configuration and the given file path.
     /// Most users should use [`Self::load_for_file`], but this method can be useful if only the underlying Tree-sitter language
     /// is necessary, as it will not attempt to load the TSG file.
@@ -280,9 +387,37 @@ impl Loader {
         content: &mut dyn ContentProvider,
         cancellation_flag: &dyn CancellationFlag,
     ) -> Result<FileLanguageConfigurations<'a>, LoadError<'static>> {
-        match &mut self.0 {
-            LoaderImpl::Paths(loader) => loader.load_for_file(path, content, cancellation_flag),
-            LoaderImpl::Provided(loader) => loader.load_for_file(path, content),
+        let (result, fallback) = match &mut self.0 {
+            LoaderImpl::Paths(loader) => {
+                let fallback = loader.fallback.clone();
+                (
+                    loader.load_for_file(path, content, cancellation_flag),
+                    fallback,
+                )
+            }
+            LoaderImpl::Provided(loader) => {
+                let fallback = loader.fallback.clone();
+                (loader.load_for_file(path, content), fallback)
+            }
+        };
+        let fallback = match fallback {
+            Some(fallback) => fallback,
+            None => return result,
+        };
+        let lcs = match result {
+            Ok(lcs) if lcs.has_some() => return Ok(lcs),
+            Ok(lcs) => lcs,
+            Err(LoadError::NoLanguagesFound(_)) => FileLanguageConfigurations::default(),
+            Err(err) => return Err(err),
+        };
+        // The fallback analyzer is only used for text files.
+        match content.get(path) {
+            Ok(_) => Ok(FileLanguageConfigurations {
+                fallback: Some(fallback),
+                ..lcs
+            }),
+            Err(err) if err.kind() == std::io::ErrorKind::InvalidData => Ok(lcs),
+            Err(err) => Err(err.into()),
         }
     }
 
@@ -330,7 +465,7 @@ impl Loader {
         Self::load_globals_from_config_str(&config, &mut globals)?;
         sgl.build_stack_graph_into(graph, file, &source, &globals, cancellation_flag)
             .map_err(|err| LoadError::Builtins {
-                inner: err,
+                inner: Box::new(err),
                 source_path: path.to_path_buf(),
                 source,
                 tsg_path: sgl.tsg_path.to_path_buf(),
@@ -356,6 +491,86 @@ impl Loader {
     }
 }
 
+/// A language detector selects the language for a file by its scope. Detectors allow
+/// overriding the default selection, which is based on the file types and content regexes
+/// of the available languages.
+pub trait LanguageDetector {
+    /// Returns the scope of the language for the given file, or `None` if the default
+    /// selection should be used.
+    fn detect_scope(
+        &self,
+        path: &Path,
+        content: &mut dyn ContentProvider,
+    ) -> std::io::Result<Option<String>>;
+}
+
+/// A language detector that maps file extensions to language scopes.
+///
+/// The mapping can be read from an INI configuration file with a `languages` section, where
+/// every key is a file extension and every value a scope:
+///
+/// ``` ini
+/// [languages]
+/// pyw = source.python
+/// mjs = source.js
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ExtensionLanguageDetector {
+    scopes: HashMap<String, String>,
+}
+
+impl ExtensionLanguageDetector {
+    pub fn new() -> Self {
+        Self {
+            scopes: HashMap::new(),
+        }
+    }
+
+    pub fn with(mut self, extension: String, scope: String) -> Self {
+        self.scopes.insert(extension, scope);
+        self
+    }
+
+    pub fn add(&mut self, extension: String, scope: String) -> &mut Self {
+        self.scopes.insert(extension, scope);
+        self
+    }
+
+    pub fn from_config_path(path: &Path) -> Result<Self, LoadError<'static>> {
+        let conf = Ini::load_from_file(path)?;
+        Ok(Self::from_config(&conf))
+    }
+
+    pub fn from_config_str(config: &str) -> Result<Self, LoadError<'static>> {
+        let conf = Ini::load_from_str(config).map_err(ini::Error::Parse)?;
+        Ok(Self::from_config(&conf))
+    }
+
+    fn from_config(conf: &Ini) -> Self {
+        let mut detector = Self::new();
+        if let Some(languages_section) = conf.section(Some("languages")) {
+            for (extension, scope) in languages_section.iter() {
+                detector.add(extension.into(), scope.into());
+            }
+        }
+        detector
+    }
+}
+
+impl LanguageDetector for ExtensionLanguageDetector {
+    fn detect_scope(
+        &self,
+        path: &Path,
+        _content: &mut dyn ContentProvider,
+    ) -> std::io::Result<Option<String>> {
+        Ok(path
+            .extension()
+            .and_then(OsStr::to_str)
+            .and_then(|extension| self.scopes.get(extension))
+            .cloned())
+    }
+}
+
 /// Struct holding the language configurations for a file.
 #[derive(Default)]
 pub struct FileLanguageConfigurations<'a> {
@@ -366,11 +581,13 @@ pub struct FileLanguageConfigurations<'a> {
         &'a LanguageConfiguration,
         Arc<dyn FileAnalyzer + Send + Sync>,
     )>,
+    /// The fallback analyzer of the loader, if the file has no primary or secondary languages.
+    pub fallback: Option<Arc<dyn FileAnalyzer + Send + Sync>>,
 }
 
 impl FileLanguageConfigurations<'_> {
     pub fn has_some(&self) -> bool {
-        self.primary.is_some() || !self.secondary.is_empty()
+        self.primary.is_some() || !self.secondary.is_empty() || self.fallback.is_some()
     }
 
     pub fn no_similar_paths_in_file(&self) -> bool {
@@ -383,6 +600,13 @@ impl FileLanguageConfigurations<'_> {
         }
         return no_similar_paths_in_file;
     }
+
+    /// Returns the group of the given file according to the primary language, or `None` if it
+    /// does not belong to one.
+    pub fn file_group(&self, path: &Path) -> Option<String> {
+        self.primary
+            .and_then(|lc| lc.file_grouping.group_for_file(path))
+    }
 }
 
 #[derive(Debug, Error)]
@@ -409,7 +633,7 @@ pub enum LoadError<'a> {
     #[error("{inner}")]
     Builtins {
         #[source]
-        inner: crate::BuildError,
+        inner: Box<crate::BuildError>,
         source_path: PathBuf,
         source: Cow<'a, str>,
         tsg_path: PathBuf,
@@ -470,6 +694,8 @@ impl std::fmt::Display for DisplayLoadErrorPretty<'_> {
 
 struct LanguageConfigurationsLoader {
     configurations: Vec<LanguageConfiguration>,
+    detector: Option<Arc<dyn LanguageDetector + Send + Sync>>,
+    fallback: Option<Arc<dyn FileAnalyzer + Send + Sync>>,
 }
 
 impl LanguageConfigurationsLoader {
@@ -481,6 +707,9 @@ impl LanguageConfigurationsLoader {
         path: &Path,
         content: &mut dyn ContentProvider,
     ) -> Result<Option<tree_sitter::Language>, LoadError<'static>> {
+        if let Some(scope) = self.detect_scope(path, content)? {
+            return Ok(self.configuration_for_scope(&scope).map(|lc| lc.language));
+        }
         for configuration in self.configurations.iter() {
             if configuration.matches_file(path, content)? {
                 return Ok(Some(configuration.language));
@@ -495,7 +724,10 @@ impl LanguageConfigurationsLoader {
         path: &Path,
         content: &mut dyn ContentProvider,
     ) -> Result<FileLanguageConfigurations<'a>, LoadError<'static>> {
-        let primary = LanguageConfiguration::best_for_file(&self.configurations, path, content)?;
+        let primary = match self.detect_scope(path, content)? {
+            Some(scope) => self.configuration_for_scope(&scope),
+            None => LanguageConfiguration::best_for_file(&self.configurations, path, content)?,
+        };
         let mut secondary = Vec::new();
         for language in self.configurations.iter() {
             if let Some(fa) = path
@@ -505,7 +737,28 @@ impl LanguageConfigurationsLoader {
                 secondary.push((language, fa));
             }
         }
-        Ok(FileLanguageConfigurations { primary, secondary })
+        Ok(FileLanguageConfigurations {
+            primary,
+            secondary,
+            fallback: None,
+        })
+    }
+
+    fn detect_scope(
+        &self,
+        path: &Path,
+        content: &mut dyn ContentProvider,
+    ) -> std::io::Result<Option<String>> {
+        match &self.detector {
+            Some(detector) => detector.detect_scope(path, content),
+            None => Ok(None),
+        }
+    }
+
+    fn configuration_for_scope(&self, scope: &str) -> Option<&LanguageConfiguration> {
+        self.configurations
+            .iter()
+            .find(|lc| lc.scope.as_deref() == Some(scope))
     }
 }
 
@@ -518,7 +771,12 @@ struct PathLoader {
     scope: Option<String>,
     tsg_paths: Vec<LoadPath>,
     builtins_paths: Vec<LoadPath>,
+    scope_tsg_paths: HashMap<String, Vec<LoadPath>>,
+    scope_builtins_paths: HashMap<String, Vec<LoadPath>>,
     cache: Vec<(Language, LanguageConfiguration)>,
+    detector: Option<Arc<dyn LanguageDetector + Send + Sync>>,
+    fallback: Option<Arc<dyn FileAnalyzer + Send + Sync>>,
+    max_tree_depth: Option<usize>,
 }
 
 impl PathLoader {
@@ -575,7 +833,8 @@ impl PathLoader {
             Some(index) => index,
             None => {
                 let tsg = self.load_tsg_from_paths(&language)?;
-                let sgl = StackGraphLanguage::new(language.language, tsg);
+                let mut sgl = StackGraphLanguage::new(language.language, tsg);
+                sgl.set_max_tree_depth(self.max_tree_depth);
 
                 let mut builtins = StackGraph::new();
                 self.load_builtins_from_paths_into(
@@ -595,6 +854,8 @@ impl PathLoader {
                     special_files: FileAnalyzers::new(),
                     // always detect similar paths, we don't know the language configuration when loading from the file system
                     no_similar_paths_in_file: false,
+                    file_grouping: FileGrouping::None,
+                    tie_breaker: None,
                 };
                 self.cache.push((language.language, lc));
 
@@ -605,6 +866,7 @@ impl PathLoader {
         Ok(FileLanguageConfigurations {
             primary: Some(lc),
             secondary: Vec::default(),
+            fallback: None,
         })
     }
 
@@ -614,6 +876,13 @@ impl PathLoader {
         file_path: &Path,
         file_content: &mut dyn ContentProvider,
     ) -> Result<Option<&SupplementedLanguage>, LoadError<'static>> {
+        let detected_scope = match &self.detector {
+            Some(detector) => detector.detect_scope(file_path, file_content)?,
+            None => None,
+        };
+        if let Some(detected_scope) = detected_scope {
+            return self.select_language_for_scope(&detected_scope);
+        }
         // The borrow checker is not smart enough to realize that the early returns
         // ensure any references from the self.select_* call (which require a mutable
         // borrow) do not outlive the match. Therefore, we use a raw self_ptr and unsafe
@@ -644,6 +913,28 @@ impl PathLoader {
         Ok(None)
     }
 
+    // Select language with the given scope, considering paths and scope fields
+    fn select_language_for_scope(
+        &mut self,
+        scope: &str,
+    ) -> Result<Option<&SupplementedLanguage>, LoadError<'static>> {
+        if self.scope.as_deref().is_some_and(|s| s != scope) {
+            return Ok(None);
+        }
+        // See select_language_for_file for why we need the raw pointer here. Languages must
+        // be requested using the loader scope, because the result is cached per path.
+        let self_ptr = self as *mut Self;
+        for path in &self.paths {
+            let languages = unsafe { &mut *self_ptr }
+                .loader
+                .languages_at_path(path, self.scope.as_deref())?;
+            if let Some(language) = languages.into_iter().find(|l| l.matches_scope(scope)) {
+                return Ok(Some(language));
+            }
+        }
+        Ok(None)
+    }
+
     // Select language from the given path for the given file, considering scope field
     fn select_language_for_file_from_path(
         &mut self,
@@ -673,7 +964,12 @@ impl PathLoader {
         &self,
         language: &SupplementedLanguage,
     ) -> Result<TsgFile, LoadError<'static>> {
-        for tsg_path in &self.tsg_paths {
+        let tsg_paths = language
+            .scope
+            .as_ref()
+            .and_then(|scope| self.scope_tsg_paths.get(scope))
+            .unwrap_or(&self.tsg_paths);
+        for tsg_path in tsg_paths {
             let mut tsg_path = tsg_path.get_for_grammar(&language.root_path);
             if tsg_path.extension().is_none() {
                 tsg_path.set_extension("tsg");
@@ -696,7 +992,12 @@ impl PathLoader {
         graph: &mut StackGraph,
         cancellation_flag: &dyn CancellationFlag,
     ) -> Result<(), LoadError<'static>> {
-        for builtins_path in &self.builtins_paths {
+        let builtins_paths = language
+            .scope
+            .as_ref()
+            .and_then(|scope| self.scope_builtins_paths.get(scope))
+            .unwrap_or(&self.builtins_paths);
+        for builtins_path in builtins_paths {
             let mut builtins_path = builtins_path.get_for_grammar(&language.root_path);
             if builtins_path.exists() && !builtins_path.is_dir() {
                 return Self::load_builtins_from_path_into(
@@ -0,0 +1,120 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! A built-in language that indexes plain identifiers, without a grammar or TSG rules.
+//!
+//! The null language makes it possible to try indexing and querying on arbitrary text files.
+//! It works line by line:
+//!
+//!   - A line of the form `NAME = ...` or `NAME := ...`, optionally preceded by `const`,
+//!     `export`, `let`, or `var`, defines `NAME`.
+//!   - Every other identifier is a reference.
+//!
+//! Definitions are visible in all files, and references resolve to every definition with the
+//! same name. The language knows nothing about scoping, strings, or comments, so its results are
+//! only an approximation of the real definitions of a language.
+//!
+//! The null language is a [`FileAnalyzer`][crate::FileAnalyzer]. Use it for files without a
+//! language by setting it as the fallback analyzer of a loader:
+//!
+//! ```
+//! # use tree_sitter_stack_graphs::loader::Loader;
+//! # use tree_sitter_stack_graphs::null_language::NullLanguage;
+//! let mut loader = Loader::from_language_configurations(vec![], None).unwrap();
+//! loader.set_fallback_analyzer(NullLanguage);
+//! ```
+
+use lsp_positions::Span;
+use lsp_positions::SpanCalculator;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::File;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::StackGraph;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::BuildError;
+use crate::CancellationFlag;
+use crate::FileAnalyzer;
+
+static DEFINITION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:(?:const|export|let|var)\s+)*([\p{L}_][\p{L}\p{N}_]*)\s*:?=(?:[^=]|$)")
+        .unwrap()
+});
+static IDENTIFIER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b[\p{L}_][\p{L}\p{N}_]*").unwrap());
+
+/// A file analyzer that defines assigned names and references all other identifiers.
+/// See the [module documentation][self] for details.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NullLanguage;
+
+impl FileAnalyzer for NullLanguage {
+    fn build_stack_graph_into(
+        &self,
+        graph: &mut StackGraph,
+        file: Handle<File>,
+        _path: &Path,
+        source: &str,
+        _all_paths: &mut dyn Iterator<Item = &Path>,
+        _globals: &HashMap<String, String>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), BuildError> {
+        let root = StackGraph::root_node();
+        let mut span_calculator = SpanCalculator::new(source);
+        let mut line_start = 0;
+        for line in source.split_inclusive('\n') {
+            cancellation_flag
+                .check("building null language graph")
+                .map_err(|err| BuildError::Cancelled(err.0))?;
+            let mut references_from = 0;
+            if let Some(name) = DEFINITION.captures(line).and_then(|c| c.get(1)) {
+                let definition = add_symbol_node(graph, file, name.as_str(), false);
+                graph.source_info_mut(definition).span = span(
+                    &mut span_calculator,
+                    line_start + name.start(),
+                    line_start + name.end(),
+                );
+                graph.add_edge(root, definition, 0);
+                references_from = name.end();
+            }
+            for name in IDENTIFIER.find_iter(&line[references_from..]) {
+                let start = line_start + references_from + name.start();
+                let end = line_start + references_from + name.end();
+                let reference = add_symbol_node(graph, file, name.as_str(), true);
+                graph.source_info_mut(reference).span = span(&mut span_calculator, start, end);
+                graph.add_edge(reference, root, 0);
+            }
+            line_start += line.len();
+        }
+        Ok(())
+    }
+}
+
+fn add_symbol_node(
+    graph: &mut StackGraph,
+    file: Handle<File>,
+    name: &str,
+    is_reference: bool,
+) -> Handle<Node> {
+    let id = graph.new_node_id(file);
+    let symbol = graph.add_symbol(name);
+    if is_reference {
+        graph.add_push_symbol_node(id, symbol, true)
+    } else {
+        graph.add_pop_symbol_node(id, symbol, true)
+    }
+    .expect("new node id is unique")
+}
+
+fn span(span_calculator: &mut SpanCalculator, start: usize, end: usize) -> Span {
+    Span {
+        start: span_calculator.for_utf8_offset_in_string(start),
+        end: span_calculator.for_utf8_offset_in_string(end),
+    }
+}
@@ -68,11 +68,13 @@ use stack_graphs::assert::Assertion;
 use stack_graphs::assert::AssertionError;
 use stack_graphs::assert::AssertionSource;
 use stack_graphs::assert::AssertionTarget;
+use stack_graphs::assert::IncorrectlyDefined;
 use stack_graphs::graph::File;
 use stack_graphs::graph::Node;
 use stack_graphs::graph::SourceInfo;
 use stack_graphs::graph::StackGraph;
 use stack_graphs::partial::PartialPaths;
+use stack_graphs::partial::TieBreaker;
 use stack_graphs::stitching::Database;
 use stack_graphs::stitching::StitcherConfig;
 use std::collections::HashMap;
@@ -491,9 +493,30 @@ pub enum TestFailure {
         missing_symbols: Vec<String>,
         unexpected_symbols: Vec<String>,
     },
+    EmptyScopeStack {
+        path: PathBuf,
+        position: Position,
+        references: Vec<String>,
+        lines: Vec<Option<usize>>,
+    },
     Cancelled(stack_graphs::CancellationError),
 }
 
+impl TestFailure {
+    /// Returns the test file and position of the failed assertion, if the failure belongs to
+    /// an assertion.
+    pub fn location(&self) -> Option<(&Path, &Position)> {
+        match self {
+            Self::NoReferences { path, position }
+            | Self::IncorrectResolutions { path, position, .. }
+            | Self::IncorrectDefinitions { path, position, .. }
+            | Self::IncorrectReferences { path, position, .. }
+            | Self::EmptyScopeStack { path, position, .. } => Some((path, position)),
+            Self::Cancelled(_) => None,
+        }
+    }
+}
+
 impl std::fmt::Display for TestFailure {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -611,11 +634,131 @@ impl std::fmt::Display for TestFailure {
                 }
                 Ok(())
             }
+            Self::EmptyScopeStack {
+                path,
+                position,
+                references,
+                lines,
+            } => {
+                write!(
+                    f,
+                    "{}:{}:{}: reference(s)",
+                    path.display(),
+                    position.line + 1,
+                    position.column.grapheme_offset + 1
+                )?;
+                for reference in references {
+                    write!(f, " ‘{}’", reference)?;
+                }
+                write!(
+                    f,
+                    " jump to scope with an empty scope stack on line(s) {}",
+                    lines
+                        .iter()
+                        .map(|l| l.map(|l| format!("{}", l + 1)).unwrap_or("?".into()))
+                        .format(", ")
+                )
+            }
             Self::Cancelled(err) => write!(f, "{}", err),
         }
     }
 }
 
+impl TestFailure {
+    /// Returns a displayable diff of the expected and the actual results of the failed
+    /// assertion. Missing expectations are marked with `-`, unexpected results with `+`.
+    /// Failures that have no expected and actual results are displayed as usual.
+    pub fn display_diff(&self) -> impl std::fmt::Display + '_ {
+        DisplayTestFailureDiff(self)
+    }
+}
+
+struct DisplayTestFailureDiff<'a>(&'a TestFailure);
+
+impl std::fmt::Display for DisplayTestFailureDiff<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            TestFailure::IncorrectResolutions {
+                path,
+                position,
+                references,
+                missing_lines,
+                unexpected_lines,
+            } => {
+                write!(
+                    f,
+                    "{}:{}:{}: definition(s) for reference(s)",
+                    path.display(),
+                    position.line + 1,
+                    position.column.grapheme_offset + 1
+                )?;
+                for reference in references {
+                    write!(f, " ‘{}’", reference)?;
+                }
+                for line in missing_lines {
+                    write!(f, "\n  - definition on line {}", line + 1)?;
+                }
+                for (definition, lines) in unexpected_lines.iter().sorted_by_key(|(d, _)| *d) {
+                    for line in lines {
+                        match line {
+                            Some(line) => write!(f, "\n  + ‘{}’ on line {}", definition, line + 1)?,
+                            None => write!(f, "\n  + ‘{}’ on unknown line", definition)?,
+                        }
+                    }
+                }
+                Ok(())
+            }
+            TestFailure::IncorrectDefinitions {
+                path,
+                position,
+                missing_symbols,
+                unexpected_symbols,
+            } => {
+                write!(
+                    f,
+                    "{}:{}:{}: definitions",
+                    path.display(),
+                    position.line + 1,
+                    position.column.grapheme_offset + 1
+                )?;
+                Self::fmt_symbols(f, missing_symbols, unexpected_symbols)
+            }
+            TestFailure::IncorrectReferences {
+                path,
+                position,
+                missing_symbols,
+                unexpected_symbols,
+            } => {
+                write!(
+                    f,
+                    "{}:{}:{}: references",
+                    path.display(),
+                    position.line + 1,
+                    position.column.grapheme_offset + 1
+                )?;
+                Self::fmt_symbols(f, missing_symbols, unexpected_symbols)
+            }
+            failure => write!(f, "{}", failure),
+        }
+    }
+}
+
+impl DisplayTestFailureDiff<'_> {
+    fn fmt_symbols(
+        f: &mut std::fmt::Formatter<'_>,
+        missing_symbols: &[String],
+        unexpected_symbols: &[String],
+    ) -> std::fmt::Result {
+        for symbol in missing_symbols {
+            write!(f, "\n  - {}", symbol)?;
+        }
+        for symbol in unexpected_symbols {
+            write!(f, "\n  + {}", symbol)?;
+        }
+        Ok(())
+    }
+}
+
 impl Test {
     /// Run the test. It is the responsibility of the caller to ensure that
     /// the stack graph for the test fragments has been constructed, and the
@@ -626,16 +769,30 @@ impl Test {
         db: &mut Database,
         stitcher_config: StitcherConfig,
         cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<TestResult, stack_graphs::CancellationError> {
+        self.run_with_tie_breaker(partials, db, stitcher_config, None, cancellation_flag)
+    }
+
+    /// Run the test like [`Self::run`][], using the given tie breaker to decide between
+    /// definitions that shadowing cannot decide between.
+    pub fn run_with_tie_breaker(
+        &mut self,
+        partials: &mut PartialPaths,
+        db: &mut Database,
+        stitcher_config: StitcherConfig,
+        tie_breaker: Option<&dyn TieBreaker>,
+        cancellation_flag: &dyn CancellationFlag,
     ) -> Result<TestResult, stack_graphs::CancellationError> {
         let mut result = TestResult::new();
         for fragment in &self.fragments {
             for assertion in &fragment.assertions {
                 match assertion
-                    .run(
+                    .run_with_tie_breaker(
                         &self.graph,
                         partials,
                         db,
                         stitcher_config,
+                        tie_breaker,
                         &cancellation_flag,
                     )
                     .map_or_else(|e| self.from_error(e), |v| Ok(v))
@@ -655,12 +812,13 @@ impl Test {
                 path: self.path.clone(),
                 position: source.position,
             }),
-            AssertionError::IncorrectlyDefined {
-                source,
-                references,
-                missing_targets,
-                unexpected_paths,
-            } => {
+            AssertionError::IncorrectlyDefined(error) => {
+                let IncorrectlyDefined {
+                    source,
+                    references,
+                    missing_targets,
+                    unexpected_paths,
+                } = *error;
                 let references = references
                     .into_iter()
                     .map(|r| self.graph[self.graph[r].symbol().unwrap()].to_string())
@@ -743,6 +901,43 @@ impl Test {
                     unexpected_symbols,
                 })
             }
+            AssertionError::EmptyScopeStack {
+                source,
+                references,
+                paths,
+            } => {
+                let references = references
+                    .into_iter()
+                    .map(|r| self.graph[self.graph[r].symbol().unwrap()].to_string())
+                    .unique()
+                    .sorted()
+                    .collect();
+                let lines = paths
+                    .into_iter()
+                    .map(|p| {
+                        p.node
+                            .to_node_id(&self.graph)
+                            .ok()
+                            .and_then(|id| self.graph.node_for_id(id))
+                            // ignore lines outside of this test, which may be include files or builtins
+                            .filter(|n| {
+                                self.fragments
+                                    .iter()
+                                    .any(|f| Some(f.file) == self.graph[*n].id().file())
+                            })
+                            .and_then(|n| self.get_source_info(n))
+                            .map(|si| si.span.start.line)
+                    })
+                    .unique()
+                    .sorted()
+                    .collect();
+                Err(TestFailure::EmptyScopeStack {
+                    path: self.path.clone(),
+                    position: source.position,
+                    references,
+                    lines,
+                })
+            }
             AssertionError::Cancelled(err) => Err(TestFailure::Cancelled(err)),
         }
     }
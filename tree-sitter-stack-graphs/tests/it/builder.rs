@@ -7,8 +7,10 @@
 
 use stack_graphs::graph::StackGraph;
 use tree_sitter_graph::Variables;
+use tree_sitter_stack_graphs::BuildError;
 use tree_sitter_stack_graphs::NoCancellation;
 use tree_sitter_stack_graphs::StackGraphLanguage;
+use tree_sitter_stack_graphs::DEFAULT_MAX_TREE_DEPTH;
 
 use crate::edges::check_stack_graph_edges;
 use crate::nodes::check_stack_graph_nodes;
@@ -72,3 +74,56 @@ fn can_support_injected_nodes() {
         &["[test.py(1) scope] -0-> [test.py(0) exported scope]"],
     );
 }
+
+#[test]
+fn can_limit_tree_depth() {
+    let tsg = r#"
+    (module)@mod {
+      node @mod.lexical_scope
+    }
+    "#;
+    let python = "x = [[[[1]]]]";
+
+    let mut language = StackGraphLanguage::from_str(tree_sitter_python::language(), tsg).unwrap();
+    let globals = Variables::new();
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    language.set_max_tree_depth(Some(3));
+    let result =
+        language.build_stack_graph_into(&mut graph, file, python, &globals, &NoCancellation);
+    assert!(matches!(result, Err(BuildError::TreeTooDeep(3))));
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    language.set_max_tree_depth(Some(20));
+    language
+        .build_stack_graph_into(&mut graph, file, python, &globals, &NoCancellation)
+        .expect("Failed to build graph");
+}
+
+#[test]
+fn deep_trees_are_rejected_by_default() {
+    let tsg = r#"
+    (module)@mod {
+      node @mod.lexical_scope
+    }
+    "#;
+    let depth = DEFAULT_MAX_TREE_DEPTH + 1;
+    let python = format!("x = {}1{}", "[".repeat(depth), "]".repeat(depth));
+
+    let language = StackGraphLanguage::from_str(tree_sitter_python::language(), tsg).unwrap();
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let result = language.build_stack_graph_into(
+        &mut graph,
+        file,
+        &python,
+        &Variables::new(),
+        &NoCancellation,
+    );
+    assert!(matches!(
+        result,
+        Err(BuildError::TreeTooDeep(DEFAULT_MAX_TREE_DEPTH))
+    ));
+}
@@ -0,0 +1,192 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use clap::Parser;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::storage::SQLiteWriter;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+use tree_sitter_stack_graphs::cli::index::IndexError;
+use tree_sitter_stack_graphs::cli::index::Indexer;
+use tree_sitter_stack_graphs::cli::load::LanguageConfigurationsLoaderArgs;
+use tree_sitter_stack_graphs::cli::path_loading::Subcommands;
+use tree_sitter_stack_graphs::cli::status::StatusArgs;
+use tree_sitter_stack_graphs::cli::util::reporter::Reporter;
+use tree_sitter_stack_graphs::loader::FileAnalyzers;
+use tree_sitter_stack_graphs::loader::FileGrouping;
+use tree_sitter_stack_graphs::loader::LanguageConfiguration;
+use tree_sitter_stack_graphs::loader::Loader;
+use tree_sitter_stack_graphs::CancellationError;
+use tree_sitter_stack_graphs::CancellationFlag;
+use tree_sitter_stack_graphs::NoCancellation;
+use tree_sitter_stack_graphs::StackGraphLanguage;
+use tree_sitter_stack_graphs::DEFAULT_MAX_TREE_DEPTH;
+
+#[derive(Parser)]
+struct Cli {
+    #[clap(subcommand)]
+    subcommand: Subcommands,
+}
+
+#[derive(Parser)]
+struct Status {
+    #[clap(flatten)]
+    args: StatusArgs,
+}
+
+#[test]
+fn status_accepts_failed_or_partial_with_source_paths() {
+    let status = Status::try_parse_from(["status", "--failed", "src/main.py"]).unwrap();
+    assert!(status.args.failed);
+    assert_eq!(vec![PathBuf::from("src/main.py")], status.args.source_paths);
+
+    let status = Status::try_parse_from(["status", "--partial", "src", "lib"]).unwrap();
+    assert!(status.args.partial);
+    assert_eq!(2, status.args.source_paths.len());
+}
+
+#[test]
+fn status_rejects_conflicting_arguments() {
+    assert!(Status::try_parse_from(["status"]).is_err());
+    assert!(Status::try_parse_from(["status", "--failed", "--partial"]).is_err());
+    assert!(Status::try_parse_from(["status", "--all", "src/main.py"]).is_err());
+    assert!(Status::try_parse_from(["status", "--all", "--failed"]).is_err());
+}
+
+#[derive(Parser)]
+struct Load {
+    #[clap(flatten)]
+    args: LanguageConfigurationsLoaderArgs,
+}
+
+fn python_language_configuration() -> LanguageConfiguration {
+    let language = tree_sitter_python::language();
+    let sgl = StackGraphLanguage::from_str(language, "(module) {}").unwrap();
+    LanguageConfiguration {
+        language,
+        scope: Some("source.py".into()),
+        content_regex: None,
+        file_types: vec!["py".into()],
+        sgl,
+        builtins: StackGraph::new(),
+        special_files: FileAnalyzers::new(),
+        no_similar_paths_in_file: false,
+        file_grouping: FileGrouping::None,
+        tie_breaker: None,
+    }
+}
+
+#[test]
+fn loader_args_set_max_tree_depth() {
+    let max_tree_depth = |args: &[&str]| {
+        let load = Load::try_parse_from(args).unwrap();
+        let mut loader = load
+            .args
+            .get(vec![python_language_configuration()])
+            .unwrap();
+        let lcs = loader
+            .load_for_file(Path::new("test.py"), &mut None, &NoCancellation)
+            .unwrap();
+        lcs.primary.unwrap().sgl.max_tree_depth()
+    };
+    assert_eq!(Some(DEFAULT_MAX_TREE_DEPTH), max_tree_depth(&["load"]));
+    assert_eq!(Some(3), max_tree_depth(&["load", "--max-tree-depth", "3"]));
+}
+
+#[test]
+fn upgrade_is_a_database_subcommand() {
+    let cli = Cli::try_parse_from(["stack-graphs", "db", "upgrade", "--check"]).unwrap();
+    assert!(matches!(cli.subcommand, Subcommands::Db(_)));
+    assert!(Cli::try_parse_from(["stack-graphs", "upgrade"]).is_err());
+}
+
+/// Records whether a given file was indexed successfully.
+struct IndexedReporter {
+    path: PathBuf,
+    indexed: Arc<AtomicBool>,
+}
+
+impl Reporter for IndexedReporter {
+    fn skipped(&self, _path: &Path, _summary: &str, _details: Option<&dyn std::fmt::Display>) {}
+
+    fn started(&self, _path: &Path) {}
+
+    fn succeeded(&self, path: &Path, _summary: &str, _details: Option<&dyn std::fmt::Display>) {
+        if path == self.path {
+            self.indexed.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn failed(&self, _path: &Path, _summary: &str, _details: Option<&dyn std::fmt::Display>) {}
+
+    fn cancelled(&self, _path: &Path, _summary: &str, _details: Option<&dyn std::fmt::Display>) {}
+}
+
+/// Cancels once the file is indexed, or when the deadline has passed.
+struct UntilIndexed {
+    indexed: Arc<AtomicBool>,
+    deadline: Instant,
+}
+
+impl CancellationFlag for UntilIndexed {
+    fn check(&self, at: &'static str) -> Result<(), CancellationError> {
+        if self.indexed.load(Ordering::SeqCst) || Instant::now() > self.deadline {
+            return Err(CancellationError(at));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn watch_indexes_written_files() {
+    let dir = std::env::temp_dir().join(format!("stack-graphs-watch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dir = dir.canonicalize().unwrap();
+    let path = dir.join("test.py");
+
+    let mut loader =
+        Loader::from_language_configurations(vec![python_language_configuration()], None).unwrap();
+    let mut db = SQLiteWriter::open_in_memory().unwrap();
+    let indexed = Arc::new(AtomicBool::new(false));
+    let reporter = IndexedReporter {
+        path: path.clone(),
+        indexed: indexed.clone(),
+    };
+    let cancellation_flag = UntilIndexed {
+        indexed: indexed.clone(),
+        deadline: Instant::now() + Duration::from_secs(30),
+    };
+
+    // The file is written repeatedly, because the watcher may not be set up yet when the
+    // first write happens.
+    let writer = {
+        let path = path.clone();
+        let indexed = indexed.clone();
+        std::thread::spawn(move || {
+            for _ in 0..100 {
+                if indexed.load(Ordering::SeqCst) {
+                    break;
+                }
+                std::fs::write(&path, "pass\n").unwrap();
+                std::thread::sleep(Duration::from_millis(300));
+            }
+        })
+    };
+
+    let mut indexer = Indexer::new(&mut db, &mut loader, &reporter);
+    let result = indexer.watch(&[&dir], &cancellation_flag);
+    writer.join().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(matches!(result, Err(IndexError::Cancelled(_))));
+    assert!(indexed.load(Ordering::SeqCst));
+}
@@ -0,0 +1,141 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use pretty_assertions::assert_eq;
+use stack_graphs::graph::StackGraph;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use tree_sitter_stack_graphs::config::Config;
+use tree_sitter_stack_graphs::config::ConfigError;
+use tree_sitter_stack_graphs::config::CONFIG_FILE_NAME;
+use tree_sitter_stack_graphs::loader::FileAnalyzers;
+use tree_sitter_stack_graphs::loader::FileGrouping;
+use tree_sitter_stack_graphs::loader::LanguageConfiguration;
+use tree_sitter_stack_graphs::loader::Loader;
+use tree_sitter_stack_graphs::NoCancellation;
+use tree_sitter_stack_graphs::StackGraphLanguage;
+
+const CONFIG: &str = r#"
+grammars = ["vendor/tree-sitter-python", "/opt/tree-sitter-java"]
+
+[[language]]
+scope = "source.py"
+extensions = ["py", "pyw"]
+tsg = "rules/python.tsg"
+
+[files]
+include = ["src/**"]
+exclude = ["**/generated/**"]
+
+[limits]
+max-file-time = 10
+max-query-time = 0.5
+max-paths = 1000
+"#;
+
+#[test]
+fn can_parse_config() {
+    let root = Path::new("/project");
+    let config = Config::from_config_str(CONFIG, root).expect("Expected config to parse");
+    assert_eq!(root, config.root);
+    assert_eq!(
+        vec![
+            PathBuf::from("/project/vendor/tree-sitter-python"),
+            PathBuf::from("/opt/tree-sitter-java"),
+        ],
+        config.grammars
+    );
+    assert_eq!(1, config.languages.len());
+    assert_eq!("source.py", config.languages[0].scope);
+    assert_eq!(vec!["py", "pyw"], config.languages[0].extensions);
+    assert_eq!(
+        Some(PathBuf::from("/project/rules/python.tsg")),
+        config.languages[0].tsg
+    );
+    assert_eq!(None, config.languages[0].builtins);
+    assert_eq!(vec!["src/**"], config.files.include);
+    assert_eq!(vec!["**/generated/**"], config.files.exclude);
+    assert!(!config.files.no_ignore);
+    assert_eq!(Some(Duration::from_secs(10)), config.limits.max_file_time);
+    assert_eq!(
+        Some(Duration::from_millis(500)),
+        config.limits.max_query_time
+    );
+    assert_eq!(None, config.limits.max_test_time);
+    assert_eq!(Some(1000), config.limits.max_paths);
+    assert_eq!(None, config.limits.max_files_loaded);
+}
+
+#[test]
+fn empty_config_is_default() {
+    let config = Config::from_config_str("", Path::new("")).expect("Expected config to parse");
+    assert_eq!(Config::default(), config);
+}
+
+#[test]
+fn cannot_parse_config_with_unknown_fields() {
+    let result = Config::from_config_str("[limits]\nmax-time = 10\n", Path::new("/project"));
+    match result {
+        Err(ConfigError::Parse(path, _)) => {
+            assert_eq!(Path::new("/project").join(CONFIG_FILE_NAME), path)
+        }
+        _ => panic!("Expected parse error"),
+    }
+    assert!(
+        Config::from_config_str("[[language]]\nextensions = [\"py\"]\n", Path::new("")).is_err()
+    );
+    assert!(Config::from_config_str("[limits]\nmax-file-time = -1\n", Path::new("")).is_err());
+}
+
+#[test]
+fn can_discover_config_in_ancestor() {
+    let root = std::env::temp_dir().join(format!("stack-graphs-config-{}", std::process::id()));
+    let nested = root.join("src").join("nested");
+    std::fs::create_dir_all(&nested).unwrap();
+    assert_eq!(
+        None,
+        Config::find(&nested).filter(|path| path.starts_with(&root))
+    );
+
+    std::fs::write(root.join(CONFIG_FILE_NAME), CONFIG).unwrap();
+    assert_eq!(Some(root.join(CONFIG_FILE_NAME)), Config::find(&nested));
+    let config = Config::discover(&nested)
+        .expect("Expected config to load")
+        .expect("Expected config to be found");
+    assert_eq!(root, config.root);
+    assert_eq!(Some(root.join("rules/python.tsg")), config.languages[0].tsg);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn configured_extensions_select_language() {
+    let language = tree_sitter_python::language();
+    let sgl = StackGraphLanguage::from_str(language, "(module) {}").unwrap();
+    let lc = LanguageConfiguration {
+        language,
+        scope: Some("source.py".into()),
+        content_regex: None,
+        file_types: vec!["py".into()],
+        sgl,
+        builtins: StackGraph::new(),
+        special_files: FileAnalyzers::new(),
+        no_similar_paths_in_file: false,
+        file_grouping: FileGrouping::None,
+        tie_breaker: None,
+    };
+    let mut loader =
+        Loader::from_language_configurations(vec![lc], None).expect("Expected loader to succeed");
+    let config = Config::from_config_str(CONFIG, Path::new("")).unwrap();
+    config.configure_loader(&mut loader);
+
+    let lc = loader
+        .load_for_file(&PathBuf::from("test.pyw"), &mut None, &NoCancellation)
+        .expect("Expected loading stack graph language to succeed");
+    assert_eq!(lc.primary.map(|lc| lc.language), Some(language));
+}
@@ -119,3 +119,92 @@ fn can_create_edges_to_singleton_nodes() {
         ],
     );
 }
+
+#[test]
+fn can_create_edges_with_root_keys() {
+    let tsg = r#"
+      global ROOT_NODE
+
+      (identifier) @id {
+         node source
+         attr (source) type = "push_symbol", symbol = (source-text @id), is_reference
+         edge source -> ROOT_NODE
+         attr (source -> ROOT_NODE) root_key = (source-text @id)
+         node sink
+         attr (sink) type = "pop_symbol", symbol = (source-text @id), is_definition
+         edge ROOT_NODE -> sink
+         attr (ROOT_NODE -> sink) root_key = [(source-text @id), "b"]
+      }
+    "#;
+    let python = "a";
+    let (graph, _) = build_stack_graph(python, tsg).expect("Could not load stack graph");
+    let mut keys = BTreeSet::new();
+    for edge in graph.iter_edges() {
+        if let Some(key) = graph.root_edge_key(edge.source, edge.sink) {
+            keys.insert(format!(
+                "{} -> {}: {}",
+                graph[edge.source].display(&graph),
+                graph[edge.sink].display(&graph),
+                key.iter().map(|s| &graph[*s]).collect::<Vec<_>>().join(".")
+            ));
+        }
+    }
+    assert_eq!(
+        BTreeSet::from([
+            "[test.py(0) reference a] -> [root]: a".to_string(),
+            "[root] -> [test.py(1) definition a]: a.b".to_string(),
+        ]),
+        keys
+    );
+}
+
+#[test]
+fn can_create_edges_with_visibility() {
+    let tsg = r#"
+      global ROOT_NODE
+
+      (identifier) @id {
+         node sink
+         attr (sink) type = "pop_symbol", symbol = (source-text @id), is_definition
+         edge ROOT_NODE -> sink
+         attr (ROOT_NODE -> sink) visibility = "private"
+         node source
+         attr (source) type = "push_symbol", symbol = (source-text @id), is_reference
+         edge source -> ROOT_NODE
+      }
+    "#;
+    let python = "a";
+    let (graph, _) = build_stack_graph(python, tsg).expect("Could not load stack graph");
+    let mut visibilities = BTreeSet::new();
+    for edge in graph.iter_edges() {
+        visibilities.insert(format!(
+            "{} -> {}: {}",
+            graph[edge.source].display(&graph),
+            graph[edge.sink].display(&graph),
+            graph.edge_visibility(edge.source, edge.sink),
+        ));
+    }
+    assert_eq!(
+        BTreeSet::from([
+            "[root] -> [test.py(0) definition a]: private".to_string(),
+            "[test.py(1) reference a] -> [root]: public".to_string(),
+        ]),
+        visibilities
+    );
+}
+
+#[test]
+fn cannot_create_edges_with_unknown_visibility() {
+    let tsg = r#"
+      global ROOT_NODE
+
+      (identifier) @id {
+         node sink
+         attr (sink) type = "pop_symbol", symbol = (source-text @id), is_definition
+         edge ROOT_NODE -> sink
+         attr (ROOT_NODE -> sink) visibility = "friend"
+      }
+    "#;
+    let python = "a";
+    assert!(build_stack_graph(python, tsg).is_err());
+}
@@ -9,7 +9,9 @@ use once_cell::sync::Lazy;
 use pretty_assertions::assert_eq;
 use stack_graphs::graph::StackGraph;
 use std::path::PathBuf;
+use tree_sitter_stack_graphs::loader::ExtensionLanguageDetector;
 use tree_sitter_stack_graphs::loader::FileAnalyzers;
+use tree_sitter_stack_graphs::loader::FileGrouping;
 use tree_sitter_stack_graphs::loader::LanguageConfiguration;
 use tree_sitter_stack_graphs::loader::Loader;
 use tree_sitter_stack_graphs::NoCancellation;
@@ -23,11 +25,10 @@ static TSG: Lazy<String> = Lazy::new(|| {
     .to_string()
 });
 
-#[test]
-fn can_load_from_provided_language_configuration() {
+fn python_language_configuration() -> LanguageConfiguration {
     let language = tree_sitter_python::language();
     let sgl = StackGraphLanguage::from_str(language, &TSG).unwrap();
-    let lc = LanguageConfiguration {
+    LanguageConfiguration {
         language: language,
         scope: Some("source.py".into()),
         content_regex: None,
@@ -36,7 +37,15 @@ fn can_load_from_provided_language_configuration() {
         builtins: StackGraph::new(),
         special_files: FileAnalyzers::new(),
         no_similar_paths_in_file: false,
-    };
+        file_grouping: FileGrouping::None,
+        tie_breaker: None,
+    }
+}
+
+#[test]
+fn can_load_from_provided_language_configuration() {
+    let language = tree_sitter_python::language();
+    let lc = python_language_configuration();
     let mut loader =
         Loader::from_language_configurations(vec![lc], None).expect("Expected loader to succeed");
 
@@ -50,3 +59,76 @@ fn can_load_from_provided_language_configuration() {
         .expect("Expected loading stack graph language to succeed");
     assert_eq!(lc.primary.map(|lc| lc.language), Some(language));
 }
+
+#[test]
+fn max_tree_depth_applies_to_loaded_languages() {
+    let lc = python_language_configuration();
+    let mut loader =
+        Loader::from_language_configurations(vec![lc], None).expect("Expected loader to succeed");
+    loader.set_max_tree_depth(Some(3));
+
+    let lc = loader
+        .load_for_file(&PATH, &mut None, &NoCancellation)
+        .expect("Expected loading stack graph language to succeed");
+    assert_eq!(lc.primary.map(|lc| lc.sgl.max_tree_depth()), Some(Some(3)));
+}
+
+#[test]
+fn can_load_for_extension_mapped_by_detector() {
+    let language = tree_sitter_python::language();
+    let lc = python_language_configuration();
+    let mut loader =
+        Loader::from_language_configurations(vec![lc], None).expect("Expected loader to succeed");
+    let detector = ExtensionLanguageDetector::from_config_str("[languages]\npyw = source.py\n")
+        .expect("Expected config to parse");
+    loader.set_language_detector(detector);
+
+    let lc = loader
+        .load_for_file(&PathBuf::from("test.pyw"), &mut None, &NoCancellation)
+        .expect("Expected loading stack graph language to succeed");
+    assert_eq!(lc.primary.map(|lc| lc.language), Some(language));
+
+    let lc = loader
+        .load_for_file(&PATH, &mut None, &NoCancellation)
+        .expect("Expected loading stack graph language to succeed");
+    assert_eq!(lc.primary.map(|lc| lc.language), Some(language));
+}
+
+#[test]
+fn cannot_load_for_extension_mapped_to_unknown_scope() {
+    let lc = python_language_configuration();
+    let mut loader =
+        Loader::from_language_configurations(vec![lc], None).expect("Expected loader to succeed");
+    loader.set_language_detector(
+        ExtensionLanguageDetector::new().with("py".into(), "source.unknown".into()),
+    );
+
+    let lc = loader
+        .load_for_file(&PATH, &mut None, &NoCancellation)
+        .expect("Expected loading stack graph language to succeed");
+    assert!(lc.primary.is_none());
+}
+
+#[test]
+fn files_with_same_stem_are_grouped() {
+    let mut lc = python_language_configuration();
+    lc.file_grouping = FileGrouping::ByStem;
+    let mut loader =
+        Loader::from_language_configurations(vec![lc], None).expect("Expected loader to succeed");
+
+    let lc = loader
+        .load_for_file(&PathBuf::from("src/foo.py"), &mut None, &NoCancellation)
+        .expect("Expected loading stack graph language to succeed");
+    assert_eq!(
+        lc.file_group(&PathBuf::from("src/foo.py")),
+        Some("src/foo".to_string())
+    );
+    assert_eq!(
+        FileGrouping::ByStem.group_for_file(&PathBuf::from("src/foo.pyi")),
+        Some("src/foo".to_string())
+    );
+    assert_eq!(
+        FileGrouping::None.group_for_file(&PathBuf::from("src/foo.py")),
+        None
+    );
+}
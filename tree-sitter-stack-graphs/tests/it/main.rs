@@ -14,9 +14,13 @@ use tree_sitter_stack_graphs::NoCancellation;
 use tree_sitter_stack_graphs::StackGraphLanguage;
 
 mod builder;
+#[cfg(feature = "cli")]
+mod cli;
+mod config;
 mod edges;
 mod loader;
 mod nodes;
+mod null_language;
 mod test;
 
 pub(self) fn build_stack_graph(
@@ -96,6 +96,26 @@ fn can_create_endpoint_node() {
     build_and_check_stack_graph_nodes(python, tsg, &["[test.py(0) exported scope]"]);
 }
 
+#[test]
+fn can_create_exported_node_with_excluded_symbols() {
+    let tsg = r#"
+      (identifier) @id {
+         node result
+         attr (result) is_exported, excluded_symbols = [(source-text @id), "_b"]
+      }
+    "#;
+    let python = "a";
+    let (graph, file) = build_stack_graph(python, tsg).expect("Could not load stack graph");
+    let node = graph.nodes_for_file(file).next().unwrap();
+    let excluded = graph
+        .excluded_symbols(node)
+        .unwrap()
+        .iter()
+        .map(|symbol| graph[*symbol].to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["a", "_b"], excluded);
+}
+
 #[test]
 fn can_create_implicit_internal_node() {
     let tsg = r#"
@@ -181,6 +201,18 @@ fn can_create_push_node() {
     build_and_check_stack_graph_nodes(python, tsg, &["[test.py(0) push a]"]);
 }
 
+#[test]
+fn can_create_namespaced_push_node() {
+    let tsg = r#"
+      (identifier) @id {
+         node result
+         attr (result) type = "push_symbol", symbol = (source-text @id), symbol_namespace = "type"
+      }
+    "#;
+    let python = "a";
+    build_and_check_stack_graph_nodes(python, tsg, &["[test.py(0) push type:a]"]);
+}
+
 #[test]
 fn cannot_create_push_symbol_node_without_symbol() {
     let tsg = r#"
@@ -0,0 +1,130 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use pretty_assertions::assert_eq;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::StitcherConfig;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use tree_sitter_stack_graphs::loader::Loader;
+use tree_sitter_stack_graphs::null_language::NullLanguage;
+use tree_sitter_stack_graphs::FileAnalyzer;
+use tree_sitter_stack_graphs::NoCancellation;
+
+fn build(graph: &mut StackGraph, path: &str, source: &str) {
+    let file = graph.get_or_create_file(path);
+    NullLanguage
+        .build_stack_graph_into(
+            graph,
+            file,
+            Path::new(path),
+            source,
+            &mut std::iter::empty(),
+            &HashMap::new(),
+            &NoCancellation,
+        )
+        .expect("Expected graph to build");
+}
+
+/// Returns the definitions and references in the graph, as `NAME@LINE:COLUMN` strings.
+fn symbols(graph: &StackGraph) -> (Vec<String>, Vec<String>) {
+    let mut definitions = Vec::new();
+    let mut references = Vec::new();
+    for node in graph.iter_nodes() {
+        let symbol = match graph[node].symbol() {
+            Some(symbol) => symbol,
+            None => continue,
+        };
+        let span = &graph.source_info(node).unwrap().span;
+        let symbol = format!(
+            "{}@{}:{}",
+            &graph[symbol], span.start.line, span.start.column.utf8_offset
+        );
+        if graph[node].is_definition() {
+            definitions.push(symbol);
+        } else if graph[node].is_reference() {
+            references.push(symbol);
+        }
+    }
+    (definitions, references)
+}
+
+#[test]
+fn assignments_are_definitions() {
+    let mut graph = StackGraph::new();
+    build(
+        &mut graph,
+        "a.txt",
+        "x = 1\n  let y := x + 1\nif x == y then\nconst é = 2a\n",
+    );
+    let (definitions, references) = symbols(&graph);
+    assert_eq!(vec!["x@0:0", "y@1:6", "é@3:6"], definitions);
+    assert_eq!(
+        vec!["x@1:11", "if@2:0", "x@2:3", "y@2:8", "then@2:10"],
+        references
+    );
+}
+
+#[test]
+fn references_resolve_across_files() {
+    let mut graph = StackGraph::new();
+    build(&mut graph, "a.txt", "answer = 42\n");
+    build(&mut graph, "b.txt", "print answer\n");
+    let references = graph
+        .iter_nodes()
+        .filter(|n| graph[*n].is_reference())
+        .collect::<Vec<_>>();
+
+    let mut partials = PartialPaths::new();
+    let mut results = BTreeSet::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        references,
+        StitcherConfig::default(),
+        &stack_graphs::NoCancellation,
+        |graph, _, path| {
+            results.insert(format!(
+                "{} -> {}",
+                graph[path.start_node]
+                    .id()
+                    .file()
+                    .map(|f| graph[f].name())
+                    .unwrap(),
+                graph[path.end_node]
+                    .id()
+                    .file()
+                    .map(|f| graph[f].name())
+                    .unwrap(),
+            ));
+        },
+    )
+    .unwrap();
+    assert_eq!(BTreeSet::from(["b.txt -> a.txt".to_string()]), results);
+}
+
+#[test]
+fn loader_uses_fallback_for_files_without_language() {
+    let mut loader =
+        Loader::from_language_configurations(vec![], None).expect("Expected loader to succeed");
+    let lcs = loader
+        .load_for_file(&PathBuf::from("test.txt"), &mut None, &NoCancellation)
+        .expect("Expected loading to succeed");
+    assert!(!lcs.has_some());
+
+    loader.set_fallback_analyzer(NullLanguage);
+    let lcs = loader
+        .load_for_file(&PathBuf::from("test.txt"), &mut None, &NoCancellation)
+        .expect("Expected loading to succeed");
+    assert!(lcs.has_some());
+    assert!(lcs.primary.is_none());
+    assert!(lcs.fallback.is_some());
+}
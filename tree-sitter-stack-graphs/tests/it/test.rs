@@ -5,6 +5,7 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use lsp_positions::Position;
 use once_cell::sync::Lazy;
 use pretty_assertions::assert_eq;
 use stack_graphs::arena::Handle;
@@ -18,6 +19,7 @@ use std::path::Path;
 use std::path::PathBuf;
 use tree_sitter_graph::Variables;
 use tree_sitter_stack_graphs::test::Test;
+use tree_sitter_stack_graphs::test::TestFailure;
 use tree_sitter_stack_graphs::BuildError;
 use tree_sitter_stack_graphs::NoCancellation;
 use tree_sitter_stack_graphs::StackGraphLanguage;
@@ -83,6 +85,24 @@ fn check_test(
     tsg_source: &str,
     expected_successes: usize,
     expected_failures: usize,
+) {
+    check_test_with_config(
+        python_path,
+        python_source,
+        tsg_source,
+        StitcherConfig::default(),
+        expected_successes,
+        expected_failures,
+    )
+}
+
+fn check_test_with_config(
+    python_path: &Path,
+    python_source: &str,
+    tsg_source: &str,
+    stitcher_config: StitcherConfig,
+    expected_successes: usize,
+    expected_failures: usize,
 ) {
     let mut test =
         Test::from_source(python_path, python_source, python_path).expect("Could not parse test");
@@ -124,12 +144,7 @@ fn check_test(
     }
 
     let results = test
-        .run(
-            &mut partials,
-            &mut db,
-            StitcherConfig::default(),
-            &NoCancellation,
-        )
+        .run(&mut partials, &mut db, stitcher_config, &NoCancellation)
         .expect("should never be cancelled");
     assert_eq!(
         expected_successes,
@@ -334,3 +349,48 @@ fn test_cannot_set_global_before_first_fragment() {
         panic!("Parsing test unexpectedly succeeded.");
     }
 }
+
+#[test]
+fn failure_can_be_displayed_as_diff() {
+    let failure = TestFailure::IncorrectDefinitions {
+        path: PATH.clone(),
+        position: Position::default(),
+        missing_symbols: vec!["x".to_string()],
+        unexpected_symbols: vec!["y".to_string(), "z".to_string()],
+    };
+    assert_eq!(
+        "test.py:1:1: definitions\n  - x\n  + y\n  + z",
+        failure.display_diff().to_string()
+    );
+}
+
+#[test]
+fn empty_scope_stacks_can_be_denied() {
+    let tsg = r#"
+      global JUMP_TO_SCOPE_NODE
+    "#
+    .to_string()
+        + &TSG
+        + r#"
+      (module) @mod {
+          node @mod.dynamic
+          attr (@mod.dynamic) type = "pop_symbol", symbol = "x"
+          edge ROOT_NODE -> @mod.dynamic
+          edge @mod.dynamic -> JUMP_TO_SCOPE_NODE
+      }
+    "#;
+    let python = r#"
+      x = 1
+      x
+    # ^ defined: 2
+    "#;
+    check_test(&PATH, python, &tsg, 1, 0);
+    check_test_with_config(
+        &PATH,
+        python,
+        &tsg,
+        StitcherConfig::default().with_deny_empty_scope_stacks(true),
+        0,
+        1,
+    );
+}